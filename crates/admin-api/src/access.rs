@@ -0,0 +1,61 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use karateway_config::{
+    client_ip::{self, ClientIpSource},
+    ip_allowlist::IpAllowlist,
+};
+use karateway_core::KaratewayError;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::error::ApiError;
+
+/// Network-level access control for the admin API, independent of authentication
+#[derive(Clone)]
+pub struct AdminAccess {
+    allowlist: Arc<IpAllowlist>,
+    /// Ordered sources the client IP is resolved from
+    client_ip_sources: Arc<Vec<ClientIpSource>>,
+}
+
+impl AdminAccess {
+    pub fn new(allowlist: IpAllowlist, client_ip_sources: Vec<ClientIpSource>) -> Self {
+        Self {
+            allowlist: Arc::new(allowlist),
+            client_ip_sources: Arc::new(client_ip_sources),
+        }
+    }
+}
+
+/// Middleware answering 403 to clients outside the admin IP allowlist
+pub async fn require_allowed_ip(
+    State(access): State<AdminAccess>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client_ip = client_ip::resolve(
+        request.headers(),
+        Some(peer.ip().to_string()),
+        &access.client_ip_sources,
+    );
+
+    if !access.allowlist.allows(client_ip.as_deref()) {
+        warn!(
+            "Admin API request from {:?} denied by IP allowlist: {} {}",
+            client_ip,
+            request.method(),
+            request.uri().path()
+        );
+        return ApiError(KaratewayError::Forbidden(
+            "Client IP not allowed".to_string(),
+        ))
+        .into_response();
+    }
+
+    next.run(request).await
+}
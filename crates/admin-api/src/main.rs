@@ -1,13 +1,21 @@
+mod access;
 mod error;
 mod openapi;
+mod pool_guard;
 mod routes;
 mod state;
 
+use access::AdminAccess;
 use anyhow::Context;
-use axum::Router;
-use deadpool_redis::{Config as RedisConfig, Runtime};
-use karateway_config::{init_env, AppConfig, DatabaseConfig};
-use state::AppState;
+use axum::{middleware, Router};
+use karateway_config::{
+    client_ip, config_limits::ConfigLimits, database::PoolExhaustedMode, init_env,
+    ip_allowlist::IpAllowlist, pagination::PageLimits, readiness, AppConfig, DatabaseConfig,
+    RedisConfig,
+};
+use state::{AdminSettings, AppState};
+use std::net::SocketAddr;
+use std::time::Duration;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 use tracing_subscriber::EnvFilter;
@@ -19,6 +27,9 @@ async fn main() -> anyhow::Result<()> {
     // Load environment variables
     init_env();
 
+    // Initialize rustls crypto provider (used for rediss:// connections)
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
     // Load configuration
     let config = AppConfig::from_env().context("Failed to load configuration")?;
 
@@ -40,18 +51,43 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Failed to create database pool")?;
 
-    info!("Database connection pool created");
+    info!(
+        "Database connection pool created (acquire timeout {:?})",
+        db_config.acquire_timeout()
+    );
+    let pool_exhausted_mode = PoolExhaustedMode::parse_or_default(&config.db_pool_exhausted_mode);
 
     // Create Redis connection pool
-    let redis_cfg = RedisConfig::from_url(&config.redis_url());
-    let redis_pool = redis_cfg
-        .create_pool(Some(Runtime::Tokio1))
+    let redis_pool = RedisConfig::new(config.clone())
+        .create_pool()
         .context("Failed to create Redis pool")?;
 
     info!("Redis connection pool created");
 
     // Create application state
-    let state = AppState::new(pool, redis_pool);
+    let state = AppState::new(
+        pool.clone(),
+        redis_pool,
+        AdminSettings {
+            health_cache_ttl_seconds: config.health_cache_ttl_seconds,
+            health_probe_coalesce_ttl: Duration::from_secs(
+                config.health_probe_coalesce_ttl_seconds,
+            ),
+            default_rate_limit: config.default_rate_limit(),
+            health_dependencies: readiness::parse_dependencies(
+                &config.health_required_dependencies,
+            ),
+            page_limits: PageLimits::parse_or_default(
+                config.admin_max_page_size,
+                &config.admin_page_size_over_limit,
+            ),
+            audit_webhook: config.audit_webhook(),
+            snapshot_before_delete: config.admin_snapshot_before_delete,
+            route_defaults: config.route_defaults(),
+            config_limits: ConfigLimits::from_config(&config),
+            case_insensitive_paths: config.gateway_case_insensitive_paths,
+        },
+    );
 
     // Create router with CORS
     let cors = CorsLayer::new()
@@ -59,12 +95,41 @@ async fn main() -> anyhow::Result<()> {
         .allow_methods(Any)
         .allow_headers(Any);
 
-    let app = Router::new()
-        .merge(
-            SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", openapi::ApiDoc::openapi()),
-        )
-        .merge(routes::create_router(state))
-        .layer(cors);
+    let mut app = Router::new();
+
+    // Without the Swagger UI neither it nor the OpenAPI JSON is routed, so both 404
+    if config.admin_swagger_enabled {
+        let swagger_path = config.swagger_path();
+        routes::check_swagger_path(&swagger_path)?;
+        info!("Swagger UI available at {}", swagger_path);
+        app = app.merge(
+            SwaggerUi::new(swagger_path).url("/api-docs/openapi.json", openapi::ApiDoc::openapi()),
+        );
+    } else {
+        info!("Swagger UI disabled");
+    }
+
+    let ip_allowlist = IpAllowlist::parse(&config.admin_ip_allowlist);
+    if let IpAllowlist::Networks(networks) = &ip_allowlist {
+        info!("Admin API restricted to {} IP networks", networks.len());
+    }
+    let access = AdminAccess::new(
+        ip_allowlist,
+        client_ip::parse_sources(&config.admin_client_ip_sources),
+    );
+
+    let mut app = app.merge(routes::create_router(state));
+    if pool_exhausted_mode == PoolExhaustedMode::FailFast {
+        info!("Rejecting requests with 503 while the database pool is exhausted");
+        app = app.layer(middleware::from_fn_with_state(
+            pool,
+            pool_guard::reject_when_exhausted,
+        ));
+    }
+    let app = app.layer(cors).layer(middleware::from_fn_with_state(
+        access,
+        access::require_allowed_ip,
+    ));
 
     // Get bind address from config
     let addr = format!("{}:{}", config.admin_api_host, config.admin_api_port);
@@ -76,7 +141,13 @@ async fn main() -> anyhow::Result<()> {
         .await
         .context("Failed to bind to address")?;
 
-    axum::serve(listener, app).await.context("Server error")?;
+    // The peer address feeds the IP allowlist
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("Server error")?;
 
     Ok(())
 }
@@ -2,59 +2,105 @@ use utoipa::OpenApi;
 
 use karateway_core::{
     models::{
-        ApiRoute, AuditLog, BackendService, CreateApiRouteRequest, CreateBackendServiceRequest,
-        CreateRateLimitRequest, CreateWhitelistRuleRequest, HttpMethod, IdentifierType, RateLimit,
-        RuleType, UpdateApiRouteRequest, UpdateBackendServiceRequest, UpdateRateLimitRequest,
-        UpdateWhitelistRuleRequest, WhitelistRule,
+        ApiRoute, AuditLog, AuditLogCount, AuditLogStats, BackendService, CloneApiRouteRequest,
+        CloneBackendServiceRequest, CreateApiRouteRequest, CreateBackendServiceRequest,
+        CreateMetricTagRuleRequest, CreateRateLimitRequest, CreateWhitelistRuleRequest,
+        DeploymentColor, DiscoveryType, HttpMethod, IdentifierType, MetricTagRule, RateLimit,
+        RouteFieldChange, RoutePreview, RoutePreviewIssue, RoutePreviewIssueKind, RuleType,
+        TagMatchType, TokenValidationResult, UpdateApiRouteRequest, UpdateBackendServiceRequest,
+        UpdateMetricTagRuleRequest, UpdateRateLimitRequest, UpdateWhitelistRuleRequest,
+        ValidateTokenRequest, WhitelistRule,
     },
     JsonResponse, MetaResponse,
 };
 
 use crate::routes::{
-    audit_log::{AuditLogQuery, AuditLogResponse},
-    backend_service::BackendServiceWithRoutes,
-    health::{DatabaseStatus, HealthResponse},
+    audit_log::{AuditLogQuery, AuditLogResponse, AuditLogStatsQuery},
+    backend_service::{BackendServiceWithRoutes, EffectivePolicies},
+    health::{
+        BackendsStatus, ConfigLimitStatus, DatabasePoolStatus, DatabaseStatus, HealthResponse,
+    },
+    rate_limit::RateLimitWithStatus,
+    service_health::{
+        CircuitBreaker, CircuitBreakerState, CircuitBreakersResponse, HealthHistoryEntry,
+        ServiceHealthHistory,
+    },
+    stats::{StateCounts, StatsResponse},
+    BulkDeleteResponse, DeleteResponse,
 };
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         crate::routes::health::health_check,
+        crate::routes::stats::get_stats,
         crate::routes::backend_service::create_service,
         crate::routes::backend_service::list_services,
         crate::routes::backend_service::get_service,
         crate::routes::backend_service::update_service,
         crate::routes::backend_service::delete_service,
         crate::routes::backend_service::get_service_with_routes,
+        crate::routes::backend_service::clone_service,
+        crate::routes::service_health::get_service_health_history,
+        crate::routes::service_health::list_circuit_breakers,
         crate::routes::api_route::create_route,
         crate::routes::api_route::list_routes,
+        crate::routes::api_route::get_routes_openapi,
         crate::routes::api_route::get_route,
         crate::routes::api_route::update_route,
+        crate::routes::api_route::preview_route_update,
         crate::routes::api_route::delete_route,
+        crate::routes::api_route::bulk_delete_routes,
+        crate::routes::api_route::switch_route,
+        crate::routes::api_route::clone_route,
         crate::routes::rate_limit::create_limit,
         crate::routes::rate_limit::list_limits,
         crate::routes::rate_limit::get_limit,
         crate::routes::rate_limit::update_limit,
         crate::routes::rate_limit::delete_limit,
+        crate::routes::rate_limit::bulk_delete_limits,
         crate::routes::whitelist_rule::create_rule,
         crate::routes::whitelist_rule::list_rules,
         crate::routes::whitelist_rule::get_rule,
         crate::routes::whitelist_rule::update_rule,
         crate::routes::whitelist_rule::delete_rule,
+        crate::routes::whitelist_rule::bulk_delete_rules,
+        crate::routes::whitelist_rule::validate_token,
+        crate::routes::metric_tag_rule::create_tag_rule,
+        crate::routes::metric_tag_rule::list_tag_rules,
+        crate::routes::metric_tag_rule::get_tag_rule,
+        crate::routes::metric_tag_rule::update_tag_rule,
+        crate::routes::metric_tag_rule::delete_tag_rule,
         crate::routes::audit_log::list_audit_logs,
+        crate::routes::audit_log::audit_log_stats,
     ),
     components(
         schemas(
             // Core models
             BackendService,
             BackendServiceWithRoutes,
+            EffectivePolicies,
+            ServiceHealthHistory,
+            HealthHistoryEntry,
+            CircuitBreaker,
+            CircuitBreakerState,
+            CircuitBreakersResponse,
             CreateBackendServiceRequest,
             UpdateBackendServiceRequest,
+            CloneBackendServiceRequest,
+            DiscoveryType,
             ApiRoute,
             CreateApiRouteRequest,
             UpdateApiRouteRequest,
+            CloneApiRouteRequest,
+            RoutePreview,
+            RouteFieldChange,
+            RoutePreviewIssue,
+            RoutePreviewIssueKind,
             HttpMethod,
+            DeploymentColor,
             RateLimit,
+            RateLimitWithStatus,
             CreateRateLimitRequest,
             UpdateRateLimitRequest,
             IdentifierType,
@@ -62,31 +108,60 @@ use crate::routes::{
             CreateWhitelistRuleRequest,
             UpdateWhitelistRuleRequest,
             RuleType,
+            ValidateTokenRequest,
+            TokenValidationResult,
+            MetricTagRule,
+            CreateMetricTagRuleRequest,
+            UpdateMetricTagRuleRequest,
+            TagMatchType,
             AuditLog,
             AuditLogQuery,
             AuditLogResponse,
+            AuditLogStatsQuery,
+            AuditLogStats,
+            AuditLogCount,
+            BulkDeleteResponse,
+            DeleteResponse,
             // Response wrappers
             JsonResponse<BackendService>,
             JsonResponse<BackendServiceWithRoutes>,
+            JsonResponse<ServiceHealthHistory>,
+            JsonResponse<CircuitBreakersResponse>,
             JsonResponse<Vec<BackendService>>,
             JsonResponse<ApiRoute>,
             JsonResponse<Vec<ApiRoute>>,
+            JsonResponse<RoutePreview>,
             JsonResponse<RateLimit>,
             JsonResponse<Vec<RateLimit>>,
+            JsonResponse<RateLimitWithStatus>,
+            JsonResponse<Vec<RateLimitWithStatus>>,
             JsonResponse<WhitelistRule>,
             JsonResponse<Vec<WhitelistRule>>,
+            JsonResponse<TokenValidationResult>,
+            JsonResponse<MetricTagRule>,
+            JsonResponse<Vec<MetricTagRule>>,
             JsonResponse<HealthResponse>,
+            JsonResponse<StatsResponse>,
+            JsonResponse<BulkDeleteResponse>,
+            JsonResponse<DeleteResponse>,
             MetaResponse,
             HealthResponse,
             DatabaseStatus,
+            DatabasePoolStatus,
+            BackendsStatus,
+            ConfigLimitStatus,
+            StatsResponse,
+            StateCounts,
         )
     ),
     tags(
         (name = "health", description = "Health check endpoints"),
+        (name = "stats", description = "Counts of the configured objects"),
         (name = "backend-services", description = "Backend service management"),
         (name = "api-routes", description = "API route management"),
         (name = "rate-limits", description = "Rate limiting configuration"),
         (name = "whitelist-rules", description = "Whitelist and access control rules"),
+        (name = "metric-tags", description = "Rules tagging requests for metrics breakdowns"),
         (name = "audit-logs", description = "Security audit logs"),
     ),
     info(
@@ -0,0 +1,39 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use karateway_config::database::PoolUsage;
+use karateway_core::KaratewayError;
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::error::ApiError;
+
+/// Middleware answering 503 right away while every pooled connection is in use
+///
+/// Only layered in `fail_fast` mode; in `queue` mode requests wait for a
+/// connection up to the acquire timeout instead.
+pub async fn reject_when_exhausted(
+    State(pool): State<PgPool>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let usage = PoolUsage::of(&pool);
+
+    if usage.is_exhausted() {
+        warn!(
+            "Database pool exhausted ({}/{} in use), rejecting {} {}",
+            usage.in_use(),
+            usage.max,
+            request.method(),
+            request.uri().path()
+        );
+        return ApiError(KaratewayError::ServiceUnavailable(
+            "Database connection pool exhausted, retry shortly".to_string(),
+        ))
+        .into_response();
+    }
+
+    next.run(request).await
+}
@@ -4,15 +4,23 @@ use axum::{
     routing::{delete, get, post, put},
     Json, Router,
 };
+use karateway_config::config_limits::ConfigKind;
 use karateway_core::{
-    models::{ApiRoute, CreateApiRouteRequest, UpdateApiRouteRequest},
-    JsonResponse, MetaResponse,
+    models::{
+        routes_openapi, ApiRoute, AuditEventCategory, AuditEventType, AuditLogBuilder,
+        AuditSeverity, CloneApiRouteRequest, CreateApiRouteRequest, RoutePreview,
+        RoutePreviewIssue, RoutePreviewIssueKind, UpdateApiRouteRequest,
+    },
+    JsonResponse, KaratewayError, MetaResponse,
 };
 use serde::Deserialize;
+use std::collections::HashMap;
 use utoipa::IntoParams;
 use uuid::Uuid;
 use validator::Validate;
 
+use super::rate_limit::check_request_cost;
+use super::{require_bulk_delete_filter, BulkDeleteResponse, DeleteResponse, PendingDelete};
 use crate::{error::ApiResult, state::AppState};
 
 #[derive(Debug, Deserialize, IntoParams)]
@@ -31,13 +39,27 @@ fn default_limit() -> u32 {
     10
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BulkDeleteQuery {
+    /// Delete every route pointing at this backend service
+    pub backend_service_id: Option<Uuid>,
+    /// Must be `true` for the delete to run
+    #[serde(default)]
+    pub confirm: bool,
+}
+
 pub fn routes(_state: AppState) -> Router<AppState> {
     Router::new()
         .route("/", post(create_route))
         .route("/", get(list_routes))
+        .route("/", delete(bulk_delete_routes))
+        .route("/openapi", get(get_routes_openapi))
         .route("/{id}", get(get_route))
         .route("/{id}", put(update_route))
         .route("/{id}", delete(delete_route))
+        .route("/{id}/preview", post(preview_route_update))
+        .route("/{id}/switch", post(switch_route))
+        .route("/{id}/clone", post(clone_route))
 }
 
 #[utoipa::path(
@@ -47,7 +69,8 @@ pub fn routes(_state: AppState) -> Router<AppState> {
     responses(
         (status = 201, description = "API route created successfully", body = JsonResponse<ApiRoute>),
         (status = 400, description = "Invalid request"),
-        (status = 404, description = "Backend service not found")
+        (status = 404, description = "Backend service not found"),
+        (status = 409, description = "A route with the same path, method and query match already exists, or a hard limit was reached")
     ),
     tag = "api-routes"
 )]
@@ -55,18 +78,15 @@ async fn create_route(
     State(state): State<AppState>,
     Json(req): Json<CreateApiRouteRequest>,
 ) -> ApiResult<(StatusCode, Json<JsonResponse<ApiRoute>>)> {
-    // Validate request
-    req.validate()?;
+    validate_create(&state, &req).await?;
+    state.check_config_limit(ConfigKind::Routes).await?;
 
-    // Verify backend service exists
-    state
-        .backend_service_repo
-        .find_by_id(req.backend_service_id)
+    // Create route, with the operator's defaults for omitted flags
+    let route = state
+        .api_route_repo
+        .create(req.with_defaults(state.route_defaults))
         .await?;
 
-    // Create route
-    let route = state.api_route_repo.create(req).await?;
-
     Ok((
         StatusCode::CREATED,
         Json(JsonResponse::created(
@@ -76,12 +96,71 @@ async fn create_route(
     ))
 }
 
+/// Checks a new route has to pass, whether it was posted or cloned
+async fn validate_create(state: &AppState, req: &CreateApiRouteRequest) -> ApiResult<()> {
+    req.validate()?;
+    req.validate_active_color()?;
+    if let Some(query_match) = &req.query_match {
+        ApiRoute::validate_query_match(query_match)?;
+    }
+    if let Some(header) = &req.request_cost_header {
+        ApiRoute::validate_request_cost_header(header)?;
+    }
+    if let Some(prefix) = &req.upstream_path_prefix {
+        ApiRoute::validate_upstream_path_prefix(prefix)?;
+    }
+
+    // Verify backend service exists
+    state
+        .backend_service_repo
+        .find_by_id(req.backend_service_id)
+        .await?;
+    if let Some(green_backend_service_id) = req.green_backend_service_id {
+        state
+            .backend_service_repo
+            .find_by_id(green_backend_service_id)
+            .await?;
+    }
+    for fallback_id in req.fallback_backend_service_ids.iter().flatten() {
+        state.backend_service_repo.find_by_id(*fallback_id).await?;
+    }
+    if let Some(request_cost) = req.request_cost {
+        check_request_cost(state, None, request_cost).await?;
+    }
+
+    // The unique index only catches exact duplicates, not paths the gateway folds together
+    if state.case_insensitive_paths {
+        let query_match = req
+            .query_match
+            .clone()
+            .unwrap_or_else(|| serde_json::json!({}));
+        for other in state
+            .api_route_repo
+            .list_by_path_pattern(&req.path_pattern, true)
+            .await?
+        {
+            if other.method == req.method
+                && other.path_pattern.eq_ignore_ascii_case(&req.path_pattern)
+                && other.query_match == query_match
+            {
+                return Err(KaratewayError::Conflict(format!(
+                    "Route {} already serves {} {} with the same query match",
+                    other.id, other.method, other.path_pattern
+                ))
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
 #[utoipa::path(
     get,
     path = "/api/routes",
     params(ListQuery),
     responses(
-        (status = 200, description = "List of API routes", body = JsonResponse<Vec<ApiRoute>>)
+        (status = 200, description = "List of API routes", body = JsonResponse<Vec<ApiRoute>>),
+        (status = 400, description = "Invalid page size")
     ),
     tag = "api-routes"
 )]
@@ -89,15 +168,37 @@ async fn list_routes(
     State(state): State<AppState>,
     Query(query): Query<ListQuery>,
 ) -> ApiResult<Json<JsonResponse<Vec<ApiRoute>>>> {
-    let routes = state.api_route_repo.list(query.page, query.limit).await?;
+    let limit = state.page_limits.apply(query.limit)?;
+    let routes = state.api_route_repo.list(query.page, limit).await?;
 
     let total = state.api_route_repo.count().await?;
 
-    let meta = MetaResponse::new(query.page, query.limit, total);
+    let meta = MetaResponse::new(query.page, limit, total);
 
     Ok(Json(JsonResponse::success_paginated(routes, meta)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/routes/openapi",
+    responses(
+        (status = 200, description = "OpenAPI document of the active routes the gateway proxies", body = serde_json::Value)
+    ),
+    tag = "api-routes"
+)]
+async fn get_routes_openapi(State(state): State<AppState>) -> ApiResult<Json<serde_json::Value>> {
+    let routes = state.api_route_repo.list_active().await?;
+    let service_names: HashMap<Uuid, String> = state
+        .backend_service_repo
+        .list_active()
+        .await?
+        .into_iter()
+        .map(|service| (service.id, service.name))
+        .collect();
+
+    Ok(Json(routes_openapi(&routes, &service_names)))
+}
+
 #[utoipa::path(
     get,
     path = "/api/routes/{id}",
@@ -128,7 +229,9 @@ async fn get_route(
     request_body = UpdateApiRouteRequest,
     responses(
         (status = 200, description = "API route updated", body = JsonResponse<ApiRoute>),
-        (status = 404, description = "API route not found")
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "API route or a backend service it would point at not found"),
+        (status = 409, description = "Another route already serves the same path, method and query match")
     ),
     tag = "api-routes"
 )]
@@ -137,15 +240,10 @@ async fn update_route(
     Path(id): Path<Uuid>,
     Json(req): Json<UpdateApiRouteRequest>,
 ) -> ApiResult<Json<JsonResponse<ApiRoute>>> {
-    // Validate request
-    req.validate()?;
-
-    // If backend_service_id is being updated, verify it exists
-    if let Some(backend_service_id) = req.backend_service_id {
-        state
-            .backend_service_repo
-            .find_by_id(backend_service_id)
-            .await?;
+    let current = state.api_route_repo.find_by_id(id).await?;
+    let (_, problems) = check_update(&state, &current, &req).await?;
+    if let Some(problem) = problems.into_iter().next() {
+        return Err(problem.into());
     }
 
     // Update route
@@ -157,6 +255,131 @@ async fn update_route(
     )))
 }
 
+/// The route updating `current` with `req` gives, and everything wrong with it
+///
+/// `update_route` rejects the update with the first problem and the preview
+/// lists them all, so a preview without problems is an update that goes
+/// through. Problems are `Validation` errors, `NotFound` for a backend that
+/// doesn't exist and `Conflict` for a route already serving the same match.
+async fn check_update(
+    state: &AppState,
+    current: &ApiRoute,
+    req: &UpdateApiRouteRequest,
+) -> ApiResult<(ApiRoute, Vec<KaratewayError>)> {
+    let mut problems = Vec::new();
+
+    if let Err(e) = req.validate() {
+        problems.push(KaratewayError::from(e));
+    }
+    if let Some(query_match) = &req.query_match {
+        if let Err(e) = ApiRoute::validate_query_match(query_match) {
+            problems.push(e);
+        }
+    }
+    if let Some(header) = &req.request_cost_header {
+        if let Err(e) = ApiRoute::validate_request_cost_header(header) {
+            problems.push(e);
+        }
+    }
+    if let Some(prefix) = &req.upstream_path_prefix {
+        if let Err(e) = ApiRoute::validate_upstream_path_prefix(prefix) {
+            problems.push(e);
+        }
+    }
+    if let Some(request_cost) = req.request_cost {
+        match check_request_cost(state, Some(current.id), request_cost).await {
+            Ok(()) => {}
+            Err(e @ KaratewayError::Validation(_)) => problems.push(e),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let mut route = current.clone();
+    route.apply_update(req.clone());
+
+    let backends = std::iter::once(("backend_service_id", route.backend_service_id))
+        .chain(
+            route
+                .green_backend_service_id
+                .map(|id| ("green_backend_service_id", id)),
+        )
+        .chain(
+            route
+                .fallback_service_ids()
+                .into_iter()
+                .map(|id| ("fallback_backend_service_ids", id)),
+        );
+    for (field, backend_id) in backends {
+        match state.backend_service_repo.find_by_id(backend_id).await {
+            Ok(_) => {}
+            Err(KaratewayError::NotFound(_)) => problems.push(KaratewayError::NotFound(format!(
+                "{} {} does not exist",
+                field, backend_id
+            ))),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    for other in state
+        .api_route_repo
+        .list_by_path_pattern(&route.path_pattern, state.case_insensitive_paths)
+        .await?
+    {
+        if route.conflicts_with(&other, state.case_insensitive_paths) {
+            problems.push(KaratewayError::Conflict(format!(
+                "Route {} already serves {} {} with the same query match",
+                other.id, other.method, other.path_pattern
+            )));
+        }
+    }
+
+    Ok((route, problems))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/routes/{id}/preview",
+    params(
+        ("id" = Uuid, Path, description = "API route ID")
+    ),
+    request_body = UpdateApiRouteRequest,
+    responses(
+        (status = 200, description = "What the update would change; nothing is saved", body = JsonResponse<RoutePreview>),
+        (status = 404, description = "API route not found")
+    ),
+    tag = "api-routes"
+)]
+async fn preview_route_update(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateApiRouteRequest>,
+) -> ApiResult<Json<JsonResponse<RoutePreview>>> {
+    let current = state.api_route_repo.find_by_id(id).await?;
+    let (route, problems) = check_update(&state, &current, &req).await?;
+    let issues: Vec<RoutePreviewIssue> = problems
+        .into_iter()
+        .map(|problem| {
+            let (kind, message) = match problem {
+                KaratewayError::NotFound(message) => {
+                    (RoutePreviewIssueKind::DanglingReference, message)
+                }
+                KaratewayError::Conflict(message) => (RoutePreviewIssueKind::Conflict, message),
+                KaratewayError::Validation(message) => (RoutePreviewIssueKind::Invalid, message),
+                other => (RoutePreviewIssueKind::Invalid, other.to_string()),
+            };
+            RoutePreviewIssue { kind, message }
+        })
+        .collect();
+
+    let changes = current.diff(&route);
+    Ok(Json(JsonResponse::success(RoutePreview {
+        route,
+        changes,
+        valid: issues.is_empty(),
+        issues,
+    })))
+}
+
 #[utoipa::path(
     delete,
     path = "/api/routes/{id}",
@@ -164,7 +387,7 @@ async fn update_route(
         ("id" = Uuid, Path, description = "API route ID")
     ),
     responses(
-        (status = 200, description = "API route deleted"),
+        (status = 200, description = "API route deleted", body = JsonResponse<DeleteResponse>),
         (status = 404, description = "API route not found")
     ),
     tag = "api-routes"
@@ -172,8 +395,184 @@ async fn update_route(
 async fn delete_route(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
-) -> ApiResult<(StatusCode, Json<JsonResponse<()>>)> {
-    state.api_route_repo.delete(id).await?;
+) -> ApiResult<Json<JsonResponse<DeleteResponse>>> {
+    // Look the route up first so a missing one is a 404 without a snapshot
+    let route = state.api_route_repo.find_by_id(id).await?;
+
+    let mut pending = PendingDelete::begin(
+        &state,
+        format!(
+            "Delete of API route {} {}",
+            route.method, route.path_pattern
+        ),
+    )
+    .await?;
+    state.api_route_repo.delete(pending.conn(), id).await?;
+    let snapshot_id = pending.commit().await?;
+
+    Ok(Json(JsonResponse::success_with_message(
+        DeleteResponse { snapshot_id },
+        "API route deleted successfully",
+    )))
+}
 
-    Ok((StatusCode::OK, Json(JsonResponse::no_content())))
+#[utoipa::path(
+    post,
+    path = "/api/routes/{id}/switch",
+    params(
+        ("id" = Uuid, Path, description = "API route ID")
+    ),
+    responses(
+        (status = 200, description = "Active color flipped", body = JsonResponse<ApiRoute>),
+        (status = 400, description = "Route has no green backend service"),
+        (status = 404, description = "API route not found")
+    ),
+    tag = "api-routes"
+)]
+async fn switch_route(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<JsonResponse<ApiRoute>>> {
+    let route = state.api_route_repo.switch_active_color(id).await?;
+
+    let from = route.active_color.flipped();
+    let audit_log = AuditLogBuilder::new(
+        AuditEventType::ConfigurationChanged,
+        AuditEventCategory::Admin,
+        AuditSeverity::Info,
+        format!(
+            "Switched route {} {} from {} to {}",
+            route.method, route.path_pattern, from, route.active_color
+        ),
+    )
+    .request_method("POST")
+    .request_path(format!("/api/routes/{}/switch", id))
+    .api_route_id(route.id)
+    .backend_service_id(route.active_backend_service_id())
+    .metadata(serde_json::json!({
+        "from": from.to_string(),
+        "to": route.active_color.to_string(),
+        "blue_backend_service_id": route.backend_service_id,
+        "green_backend_service_id": route.green_backend_service_id,
+    }))
+    .status_code(200)
+    .build();
+    state.audit_logger.log(audit_log);
+
+    let message = format!("API route switched to {}", route.active_color);
+    Ok(Json(JsonResponse::success_with_message(route, message)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/routes/{id}/clone",
+    params(
+        ("id" = Uuid, Path, description = "API route ID to copy")
+    ),
+    request_body = CloneApiRouteRequest,
+    responses(
+        (status = 201, description = "API route cloned", body = JsonResponse<ApiRoute>),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "API route not found"),
+        (status = 409, description = "A route with the same path, method and query match already exists, or a hard limit was reached")
+    ),
+    tag = "api-routes"
+)]
+async fn clone_route(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CloneApiRouteRequest>,
+) -> ApiResult<(StatusCode, Json<JsonResponse<ApiRoute>>)> {
+    req.validate()?;
+    let original = state.api_route_repo.find_by_id(id).await?;
+
+    // The copy is a new route and gets the same checks as one posted directly
+    let create = original.clone_request(&req);
+    validate_create(&state, &create).await?;
+    state.check_config_limit(ConfigKind::Routes).await?;
+    let route = state.api_route_repo.create(create).await?;
+
+    if req.include_policies() {
+        // Don't leave a half-copied route behind; its policies go with it
+        if let Err(e) = clone_policies(&state, original.id, route.id).await {
+            if let Err(cleanup) = state.api_route_repo.delete(&state.db_pool, route.id).await {
+                tracing::error!(
+                    "Failed to remove partially cloned route {}: {}",
+                    route.id,
+                    cleanup
+                );
+            }
+            return Err(e.into());
+        }
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(JsonResponse::created(
+            route,
+            format!("API route cloned from {}", original.path_pattern),
+        )),
+    ))
+}
+
+/// Copy the active rate limits and whitelist rules of route `from` onto route `to`
+async fn clone_policies(state: &AppState, from: Uuid, to: Uuid) -> karateway_core::Result<()> {
+    for limit in state.rate_limit_repo.list_by_route(from).await? {
+        state.check_config_limit(ConfigKind::Rules).await?;
+        state
+            .rate_limit_repo
+            .create(limit.clone_request(to))
+            .await?;
+    }
+    for rule in state.whitelist_rule_repo.list_by_route(from).await? {
+        state.check_config_limit(ConfigKind::Rules).await?;
+        state
+            .whitelist_rule_repo
+            .create(rule.clone_request(to))
+            .await?;
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/routes",
+    params(BulkDeleteQuery),
+    responses(
+        (status = 200, description = "API routes deleted", body = JsonResponse<BulkDeleteResponse>),
+        (status = 400, description = "Missing filter or confirmation")
+    ),
+    tag = "api-routes"
+)]
+async fn bulk_delete_routes(
+    State(state): State<AppState>,
+    Query(query): Query<BulkDeleteQuery>,
+) -> ApiResult<Json<JsonResponse<BulkDeleteResponse>>> {
+    let backend_service_id = require_bulk_delete_filter(
+        query.backend_service_id,
+        "backend_service_id",
+        query.confirm,
+    )?;
+
+    let mut pending = PendingDelete::begin(
+        &state,
+        format!(
+            "Bulk delete of API routes for backend service {}",
+            backend_service_id
+        ),
+    )
+    .await?;
+    let deleted = state
+        .api_route_repo
+        .delete_by_backend_service(pending.conn(), backend_service_id)
+        .await?;
+    let snapshot_id = pending.commit().await?;
+
+    Ok(Json(JsonResponse::success_with_message(
+        BulkDeleteResponse {
+            deleted,
+            snapshot_id,
+        },
+        format!("Deleted {} API routes", deleted),
+    )))
 }
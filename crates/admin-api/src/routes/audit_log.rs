@@ -4,7 +4,12 @@ use axum::{
     routing::get,
     Json, Router,
 };
-use karateway_core::{models::AuditLog, JsonResponse};
+use chrono::{DateTime, Utc};
+use karateway_config::repository::audit_log::stats_window;
+use karateway_core::{
+    models::{AuditLog, AuditLogStats},
+    JsonResponse, KaratewayError,
+};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
@@ -24,6 +29,24 @@ fn default_limit() -> i64 {
     50
 }
 
+/// Most client IPs `top_ips` can ask for
+const MAX_TOP_IPS: u64 = 100;
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+pub struct AuditLogStatsQuery {
+    /// Start of the window, inclusive (RFC 3339); defaults to 24 hours before `to`
+    pub from: Option<DateTime<Utc>>,
+    /// End of the window, exclusive (RFC 3339); defaults to now
+    pub to: Option<DateTime<Utc>>,
+    /// How many of the noisiest client IPs to return (1-100)
+    #[serde(default = "default_top_ips")]
+    pub top_ips: u64,
+}
+
+fn default_top_ips() -> u64 {
+    10
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct AuditLogResponse {
     pub logs: Vec<AuditLog>,
@@ -33,7 +56,9 @@ pub struct AuditLogResponse {
 }
 
 pub fn routes(_state: AppState) -> Router<AppState> {
-    Router::new().route("/", get(list_audit_logs))
+    Router::new()
+        .route("/", get(list_audit_logs))
+        .route("/stats", get(audit_log_stats))
 }
 
 /// List audit logs with optional filtering
@@ -44,6 +69,7 @@ pub fn routes(_state: AppState) -> Router<AppState> {
     params(AuditLogQuery),
     responses(
         (status = 200, description = "Successfully retrieved audit logs", body = JsonResponse<AuditLogResponse>),
+        (status = 400, description = "Invalid page size"),
         (status = 500, description = "Internal server error")
     )
 )]
@@ -51,7 +77,9 @@ async fn list_audit_logs(
     State(state): State<AppState>,
     Query(query): Query<AuditLogQuery>,
 ) -> Result<Json<JsonResponse<AuditLogResponse>>, ApiError> {
-    let limit = query.limit.min(1000); // Max 1000 records at once
+    // Negative limits are rejected like 0, huge ones go through the page size cap
+    let requested = u32::try_from(query.limit.max(0)).unwrap_or(u32::MAX);
+    let limit = i64::from(state.page_limits.apply(requested)?);
     let offset = query.offset;
 
     // Use the repository to fetch audit logs
@@ -70,3 +98,36 @@ async fn list_audit_logs(
         offset,
     })))
 }
+
+/// Audit event counts by type, severity and top client IPs over a time window
+#[utoipa::path(
+    get,
+    path = "/api/audit-logs/stats",
+    tag = "audit-logs",
+    params(AuditLogStatsQuery),
+    responses(
+        (status = 200, description = "Successfully computed audit log statistics", body = JsonResponse<AuditLogStats>),
+        (status = 400, description = "Empty time window or invalid top_ips"),
+        (status = 500, description = "Internal server error")
+    )
+)]
+async fn audit_log_stats(
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogStatsQuery>,
+) -> Result<Json<JsonResponse<AuditLogStats>>, ApiError> {
+    let (from, to) = stats_window(query.from, query.to, Utc::now()).map_err(ApiError)?;
+    if query.top_ips == 0 || query.top_ips > MAX_TOP_IPS {
+        return Err(ApiError(KaratewayError::Validation(format!(
+            "top_ips must be between 1 and {}",
+            MAX_TOP_IPS
+        ))));
+    }
+
+    let stats = state
+        .audit_log_repo
+        .stats(from, to, query.top_ips)
+        .await
+        .map_err(ApiError)?;
+
+    Ok(Json(JsonResponse::success(stats)))
+}
@@ -4,23 +4,58 @@ use axum::{
     routing::{delete, get, post, put},
     Json, Router,
 };
+use karateway_config::config_limits::ConfigKind;
 use karateway_core::{
-    models::{ApiRoute, BackendService, CreateBackendServiceRequest, UpdateBackendServiceRequest},
+    models::{
+        effective_rate_limits, effective_whitelist_rules, ApiRoute, BackendService,
+        CloneBackendServiceRequest, CreateBackendServiceRequest, RateLimit,
+        UpdateBackendServiceRequest, WhitelistRule,
+    },
     JsonResponse, MetaResponse,
 };
-use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::{error::ApiResult, routes::service_health, state::AppState};
+use crate::{
+    error::ApiResult,
+    routes::{service_health, DeleteResponse, PendingDelete},
+    state::AppState,
+};
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct BackendServiceWithRoutes {
     #[serde(flatten)]
     pub service: BackendService,
     pub routes: Vec<ApiRoute>,
+    /// Per route, the policies the gateway applies (with `?include=effective_policies`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_policies: Option<Vec<EffectivePolicies>>,
+}
+
+/// Rate limits and whitelist rules in force for one route, globals and the default limit included
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EffectivePolicies {
+    pub api_route_id: Uuid,
+    pub rate_limits: Vec<RateLimit>,
+    /// Highest priority first, the order the gateway evaluates them in
+    pub whitelist_rules: Vec<WhitelistRule>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ServiceRoutesQuery {
+    /// Comma-separated extras; `effective_policies` adds the policies in force per route
+    pub include: Option<String>,
+}
+
+impl ServiceRoutesQuery {
+    fn includes(&self, name: &str) -> bool {
+        self.include
+            .as_deref()
+            .is_some_and(|include| include.split(',').any(|item| item.trim() == name))
+    }
 }
 
 #[derive(Debug, Deserialize, IntoParams)]
@@ -47,6 +82,11 @@ pub fn routes(_state: AppState) -> Router<AppState> {
         .route("/{id}", put(update_service))
         .route("/{id}", delete(delete_service))
         .route("/{id}/routes", get(get_service_with_routes))
+        .route("/{id}/clone", post(clone_service))
+        .route(
+            "/{id}/health/history",
+            get(service_health::get_service_health_history),
+        )
 }
 
 #[utoipa::path(
@@ -56,7 +96,7 @@ pub fn routes(_state: AppState) -> Router<AppState> {
     responses(
         (status = 201, description = "Backend service created successfully", body = JsonResponse<BackendService>),
         (status = 400, description = "Invalid request"),
-        (status = 409, description = "Service with same name already exists")
+        (status = 409, description = "Service with same name already exists, or hard limit of active services reached")
     ),
     tag = "backend-services"
 )]
@@ -66,6 +106,15 @@ async fn create_service(
 ) -> ApiResult<(StatusCode, Json<JsonResponse<BackendService>>)> {
     // Validate request
     req.validate()?;
+    req.discovery_type
+        .clone()
+        .unwrap_or_default()
+        .validate_srv_name(req.srv_name.as_deref())?;
+    BackendService::validate_client_cert(
+        req.tls_client_cert_path.as_deref(),
+        req.tls_client_key_path.as_deref(),
+    )?;
+    BackendService::validate_health_check_headers(req.health_check_headers.as_ref())?;
 
     // Check if service with same name exists
     if let Some(_existing) = state.backend_service_repo.find_by_name(&req.name).await? {
@@ -76,6 +125,8 @@ async fn create_service(
         .into());
     }
 
+    state.check_config_limit(ConfigKind::Services).await?;
+
     // Create service
     let service = state.backend_service_repo.create(req).await?;
 
@@ -93,7 +144,7 @@ async fn create_service(
 
             // Invalidate cache to force refresh on next request
             if let Ok(mut redis_conn) = state_clone.redis_pool.get().await {
-                let _: Result<(), _> = redis_conn.del("services:health:data").await;
+                let _ = karateway_config::health_cache::invalidate(&mut redis_conn).await;
                 tracing::debug!("Invalidated health cache after creating new service");
             }
         }
@@ -108,12 +159,40 @@ async fn create_service(
     ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/services/{id}/clone",
+    params(
+        ("id" = Uuid, Path, description = "Backend service ID to copy")
+    ),
+    request_body = CloneBackendServiceRequest,
+    responses(
+        (status = 201, description = "Backend service cloned", body = JsonResponse<BackendService>),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Backend service not found"),
+        (status = 409, description = "Service with same name already exists, or hard limit of active services reached")
+    ),
+    tag = "backend-services"
+)]
+async fn clone_service(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<CloneBackendServiceRequest>,
+) -> ApiResult<(StatusCode, Json<JsonResponse<BackendService>>)> {
+    req.validate()?;
+    let original = state.backend_service_repo.find_by_id(id).await?;
+
+    // The copy goes through the same checks as a new service
+    create_service(State(state), Json(original.clone_request(&req))).await
+}
+
 #[utoipa::path(
     get,
     path = "/api/services",
     params(ListQuery),
     responses(
-        (status = 200, description = "List of backend services", body = JsonResponse<Vec<BackendService>>)
+        (status = 200, description = "List of backend services", body = JsonResponse<Vec<BackendService>>),
+        (status = 400, description = "Invalid page size")
     ),
     tag = "backend-services"
 )]
@@ -121,14 +200,12 @@ async fn list_services(
     State(state): State<AppState>,
     Query(query): Query<ListQuery>,
 ) -> ApiResult<Json<JsonResponse<Vec<BackendService>>>> {
-    let services = state
-        .backend_service_repo
-        .list(query.page, query.limit)
-        .await?;
+    let limit = state.page_limits.apply(query.limit)?;
+    let services = state.backend_service_repo.list(query.page, limit).await?;
 
     let total = state.backend_service_repo.count().await?;
 
-    let meta = MetaResponse::new(query.page, query.limit, total);
+    let meta = MetaResponse::new(query.page, limit, total);
 
     Ok(Json(JsonResponse::success_paginated(services, meta)))
 }
@@ -174,6 +251,29 @@ async fn update_service(
 ) -> ApiResult<Json<JsonResponse<BackendService>>> {
     // Validate request
     req.validate()?;
+    BackendService::validate_health_check_headers(req.health_check_headers.as_ref())?;
+
+    // Check discovery settings against what the service will end up with
+    if req.discovery_type.is_some() || req.srv_name.is_some() {
+        let existing = state.backend_service_repo.find_by_id(id).await?;
+        let discovery_type = req
+            .discovery_type
+            .as_ref()
+            .unwrap_or(&existing.discovery_type);
+        let srv_name = req.srv_name.as_deref().or(existing.srv_name.as_deref());
+        discovery_type.validate_srv_name(srv_name)?;
+    }
+    if req.tls_client_cert_path.is_some() || req.tls_client_key_path.is_some() {
+        let existing = state.backend_service_repo.find_by_id(id).await?;
+        BackendService::validate_client_cert(
+            req.tls_client_cert_path
+                .as_deref()
+                .or(existing.tls_client_cert_path.as_deref()),
+            req.tls_client_key_path
+                .as_deref()
+                .or(existing.tls_client_key_path.as_deref()),
+        )?;
+    }
 
     // Update service
     let service = state.backend_service_repo.update(id, req).await?;
@@ -191,7 +291,7 @@ async fn update_service(
         ("id" = Uuid, Path, description = "Backend service ID")
     ),
     responses(
-        (status = 200, description = "Backend service deleted"),
+        (status = 200, description = "Backend service deleted", body = JsonResponse<DeleteResponse>),
         (status = 404, description = "Backend service not found")
     ),
     tag = "backend-services"
@@ -199,17 +299,33 @@ async fn update_service(
 async fn delete_service(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
-) -> ApiResult<(StatusCode, Json<JsonResponse<()>>)> {
-    state.backend_service_repo.delete(id).await?;
+) -> ApiResult<Json<JsonResponse<DeleteResponse>>> {
+    // Look the service up first so a missing one is a 404 without a snapshot
+    let service = state.backend_service_repo.find_by_id(id).await?;
+
+    let mut pending = PendingDelete::begin(
+        &state,
+        format!("Delete of backend service {}", service.name),
+    )
+    .await?;
+    state
+        .backend_service_repo
+        .delete(pending.conn(), id)
+        .await?;
+    let snapshot_id = pending.commit().await?;
 
-    Ok((StatusCode::OK, Json(JsonResponse::no_content())))
+    Ok(Json(JsonResponse::success_with_message(
+        DeleteResponse { snapshot_id },
+        "Backend service deleted successfully",
+    )))
 }
 
 #[utoipa::path(
     get,
     path = "/api/services/{id}/routes",
     params(
-        ("id" = Uuid, Path, description = "Backend service ID")
+        ("id" = Uuid, Path, description = "Backend service ID"),
+        ServiceRoutesQuery
     ),
     responses(
         (status = 200, description = "Backend service with routes", body = JsonResponse<BackendServiceWithRoutes>),
@@ -220,6 +336,7 @@ async fn delete_service(
 async fn get_service_with_routes(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
+    Query(query): Query<ServiceRoutesQuery>,
 ) -> ApiResult<Json<JsonResponse<BackendServiceWithRoutes>>> {
     // Get the service
     let service = state.backend_service_repo.find_by_id(id).await?;
@@ -227,7 +344,64 @@ async fn get_service_with_routes(
     // Get all routes for this service using the repository
     let routes = state.api_route_repo.list_by_backend_service(id).await?;
 
-    let response = BackendServiceWithRoutes { service, routes };
+    let effective_policies = if query.includes("effective_policies") {
+        Some(effective_policies(&state, &service, &routes).await?)
+    } else {
+        None
+    };
+
+    let response = BackendServiceWithRoutes {
+        service,
+        routes,
+        effective_policies,
+    };
 
     Ok(Json(JsonResponse::success(response)))
 }
+
+/// Merge active policies per route the way the gateway does
+async fn effective_policies(
+    state: &AppState,
+    service: &BackendService,
+    routes: &[ApiRoute],
+) -> ApiResult<Vec<EffectivePolicies>> {
+    let mut rate_limits: HashMap<Option<Uuid>, Vec<RateLimit>> = HashMap::new();
+    for mut limit in state.rate_limit_repo.list_active().await? {
+        limit.max_requests = limit.effective_max_requests(service.capacity);
+        rate_limits
+            .entry(limit.api_route_id)
+            .or_default()
+            .push(limit);
+    }
+
+    let mut whitelist_rules: HashMap<Option<Uuid>, Vec<WhitelistRule>> = HashMap::new();
+    for rule in state.whitelist_rule_repo.list_active().await? {
+        whitelist_rules
+            .entry(rule.api_route_id)
+            .or_default()
+            .push(rule);
+    }
+
+    fn scoped<T>(by_route: &HashMap<Option<Uuid>, Vec<T>>, route_id: Option<Uuid>) -> &[T] {
+        by_route
+            .get(&route_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    Ok(routes
+        .iter()
+        .map(|route| EffectivePolicies {
+            api_route_id: route.id,
+            rate_limits: effective_rate_limits(
+                scoped(&rate_limits, Some(route.id)),
+                scoped(&rate_limits, None),
+                state.default_rate_limit.as_ref(),
+            ),
+            whitelist_rules: effective_whitelist_rules(
+                scoped(&whitelist_rules, Some(route.id)),
+                scoped(&whitelist_rules, None),
+            ),
+        })
+        .collect())
+}
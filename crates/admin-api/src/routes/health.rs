@@ -1,17 +1,42 @@
 use axum::{extract::State, Json};
+use karateway_config::{
+    config_limits::LimitExceeded,
+    database::PoolUsage,
+    readiness::{self, HealthDependency},
+};
 use karateway_core::JsonResponse;
 use redis::AsyncCommands;
 use sea_query::{Expr, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 use utoipa::ToSchema;
 
-use crate::state::AppState;
+use crate::{routes::service_health, state::AppState};
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct DatabaseStatus {
     pub connected: bool,
     pub message: String,
+    pub pool: DatabasePoolStatus,
+}
+
+/// Connection pool utilization, sampled before the health query runs
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DatabasePoolStatus {
+    pub in_use: u32,
+    pub idle: u32,
+    pub max_connections: u32,
+}
+
+impl From<PoolUsage> for DatabasePoolStatus {
+    fn from(usage: PoolUsage) -> Self {
+        Self {
+            in_use: usage.in_use(),
+            idle: usage.idle,
+            max_connections: usage.max,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -20,12 +45,46 @@ pub struct RedisStatus {
     pub message: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BackendsStatus {
+    pub healthy: bool,
+    pub message: String,
+}
+
+/// A config soft limit the active objects are over
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConfigLimitStatus {
+    /// `routes`, `services` or `rules`
+    pub kind: String,
+    pub count: u64,
+    pub soft_limit: u64,
+}
+
+impl From<LimitExceeded> for ConfigLimitStatus {
+    fn from(exceeded: LimitExceeded) -> Self {
+        Self {
+            kind: exceeded.kind.to_string(),
+            count: exceeded.count,
+            soft_limit: exceeded.soft_limit,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub version: String,
     pub database: DatabaseStatus,
     pub redis: RedisStatus,
+    /// Only checked when `at_least_one_backend_healthy` is required
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backends: Option<BackendsStatus>,
+    /// Exceeded soft limits, only listed with `HEALTH_REPORT_CONFIG_LIMITS`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config_limits: Option<Vec<ConfigLimitStatus>>,
+    /// Dependencies that decide `status` (`HEALTH_REQUIRED_DEPENDENCIES`)
+    #[schema(value_type = Vec<String>)]
+    pub required_dependencies: Vec<HealthDependency>,
 }
 
 #[utoipa::path(
@@ -38,6 +97,7 @@ pub struct HealthResponse {
 )]
 pub async fn health_check(State(state): State<AppState>) -> Json<JsonResponse<HealthResponse>> {
     // Check database connection
+    let pool = DatabasePoolStatus::from(PoolUsage::of(&state.db_pool));
     let (sql, values) = Query::select()
         .expr(Expr::value(1))
         .build_sqlx(PostgresQueryBuilder);
@@ -49,10 +109,12 @@ pub async fn health_check(State(state): State<AppState>) -> Json<JsonResponse<He
         Ok(_) => DatabaseStatus {
             connected: true,
             message: "Database connection healthy".to_string(),
+            pool,
         },
         Err(e) => DatabaseStatus {
             connected: false,
             message: format!("Database connection failed: {}", e),
+            pool,
         },
     };
 
@@ -77,19 +139,63 @@ pub async fn health_check(State(state): State<AppState>) -> Json<JsonResponse<He
         },
     };
 
-    let overall_status = if database.connected && redis.connected {
-        "healthy"
-    } else if database.connected || redis.connected {
-        "degraded"
+    // Probing backends is only worth it when readiness depends on them
+    let required = &state.health_dependencies;
+    let backends = if required.contains(&HealthDependency::AtLeastOneBackendHealthy) {
+        Some(match service_health::any_backend_healthy(&state).await {
+            Ok(true) => BackendsStatus {
+                healthy: true,
+                message: "At least one backend service is healthy".to_string(),
+            },
+            Ok(false) => BackendsStatus {
+                healthy: false,
+                message: "No backend service is healthy".to_string(),
+            },
+            Err(e) => BackendsStatus {
+                healthy: false,
+                message: format!("Backend health check failed: {}", e),
+            },
+        })
     } else {
-        "unhealthy"
+        None
     };
 
+    // Informational only: soft limits never lower the status
+    let config_limits = if state.config_limits.report_in_health {
+        match state.config_counts().await {
+            Ok(counts) => Some(
+                state
+                    .config_limits
+                    .exceeded(counts)
+                    .into_iter()
+                    .map(ConfigLimitStatus::from)
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Failed to count active config for the soft limits: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let overall_status = readiness::overall_status(required, |dependency| match dependency {
+        HealthDependency::Database => database.connected,
+        HealthDependency::Redis => redis.connected,
+        HealthDependency::AtLeastOneBackendHealthy => {
+            backends.as_ref().is_some_and(|backends| backends.healthy)
+        }
+    });
+
     let health = HealthResponse {
         status: overall_status.to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
         database,
         redis,
+        backends,
+        config_limits,
+        required_dependencies: required.clone(),
     };
 
     Json(JsonResponse::success(health))
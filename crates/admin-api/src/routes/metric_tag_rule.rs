@@ -0,0 +1,181 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use karateway_core::{
+    models::{CreateMetricTagRuleRequest, MetricTagRule, UpdateMetricTagRuleRequest},
+    JsonResponse, MetaResponse,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{error::ApiResult, state::AppState};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ListQuery {
+    #[serde(default = "default_page")]
+    pub page: u32,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+}
+
+fn default_page() -> u32 {
+    1
+}
+
+fn default_limit() -> u32 {
+    10
+}
+
+pub fn routes(_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/", post(create_tag_rule))
+        .route("/", get(list_tag_rules))
+        .route("/{id}", get(get_tag_rule))
+        .route("/{id}", put(update_tag_rule))
+        .route("/{id}", delete(delete_tag_rule))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/metric-tags",
+    request_body = CreateMetricTagRuleRequest,
+    responses(
+        (status = 201, description = "Metric tag rule created successfully", body = JsonResponse<MetricTagRule>),
+        (status = 400, description = "Invalid request")
+    ),
+    tag = "metric-tags"
+)]
+async fn create_tag_rule(
+    State(state): State<AppState>,
+    Json(req): Json<CreateMetricTagRuleRequest>,
+) -> ApiResult<(StatusCode, Json<JsonResponse<MetricTagRule>>)> {
+    // Validate request
+    req.validate()?;
+    req.match_type
+        .validate_rule(req.header_name.as_deref(), &req.pattern)?;
+
+    // Create rule
+    let rule = state.metric_tag_rule_repo.create(req).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(JsonResponse::created(
+            rule,
+            "Metric tag rule created successfully",
+        )),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/metric-tags",
+    params(ListQuery),
+    responses(
+        (status = 200, description = "List of metric tag rules", body = JsonResponse<Vec<MetricTagRule>>),
+        (status = 400, description = "Invalid page size")
+    ),
+    tag = "metric-tags"
+)]
+async fn list_tag_rules(
+    State(state): State<AppState>,
+    Query(query): Query<ListQuery>,
+) -> ApiResult<Json<JsonResponse<Vec<MetricTagRule>>>> {
+    let limit = state.page_limits.apply(query.limit)?;
+    let rules = state.metric_tag_rule_repo.list(query.page, limit).await?;
+
+    let total = state.metric_tag_rule_repo.count().await?;
+
+    let meta = MetaResponse::new(query.page, limit, total);
+
+    Ok(Json(JsonResponse::success_paginated(rules, meta)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/metric-tags/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Metric tag rule ID")
+    ),
+    responses(
+        (status = 200, description = "Metric tag rule found", body = JsonResponse<MetricTagRule>),
+        (status = 404, description = "Metric tag rule not found")
+    ),
+    tag = "metric-tags"
+)]
+async fn get_tag_rule(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<Json<JsonResponse<MetricTagRule>>> {
+    let rule = state.metric_tag_rule_repo.find_by_id(id).await?;
+
+    Ok(Json(JsonResponse::success(rule)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/metric-tags/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Metric tag rule ID")
+    ),
+    request_body = UpdateMetricTagRuleRequest,
+    responses(
+        (status = 200, description = "Metric tag rule updated", body = JsonResponse<MetricTagRule>),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Metric tag rule not found")
+    ),
+    tag = "metric-tags"
+)]
+async fn update_tag_rule(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<UpdateMetricTagRuleRequest>,
+) -> ApiResult<Json<JsonResponse<MetricTagRule>>> {
+    // Validate request
+    req.validate()?;
+
+    // Check the rule as it will look after the update
+    if req.match_type.is_some() || req.header_name.is_some() || req.pattern.is_some() {
+        let existing = state.metric_tag_rule_repo.find_by_id(id).await?;
+        let match_type = req.match_type.unwrap_or(existing.match_type);
+        let header_name = req
+            .header_name
+            .as_deref()
+            .or(existing.header_name.as_deref());
+        let pattern = req.pattern.as_deref().unwrap_or(&existing.pattern);
+        match_type.validate_rule(header_name, pattern)?;
+    }
+
+    // Update rule
+    let rule = state.metric_tag_rule_repo.update(id, req).await?;
+
+    Ok(Json(JsonResponse::success_with_message(
+        rule,
+        "Metric tag rule updated successfully",
+    )))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/metric-tags/{id}",
+    params(
+        ("id" = Uuid, Path, description = "Metric tag rule ID")
+    ),
+    responses(
+        (status = 200, description = "Metric tag rule deleted"),
+        (status = 404, description = "Metric tag rule not found")
+    ),
+    tag = "metric-tags"
+)]
+async fn delete_tag_rule(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> ApiResult<(StatusCode, Json<JsonResponse<()>>)> {
+    state.metric_tag_rule_repo.delete(id).await?;
+
+    Ok((StatusCode::OK, Json(JsonResponse::no_content())))
+}
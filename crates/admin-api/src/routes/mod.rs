@@ -2,24 +2,176 @@ pub mod api_route;
 pub mod audit_log;
 pub mod backend_service;
 pub mod health;
+pub mod metric_tag_rule;
 pub mod rate_limit;
 pub mod service_health;
+pub mod stats;
 pub mod whitelist_rule;
 
 use crate::state::AppState;
 use axum::{routing::get, Router};
+use chrono::Utc;
+use karateway_config::repository::config_version::{snapshot_name, SNAPSHOT_CREATED_BY};
+use karateway_core::KaratewayError;
+use serde::Serialize;
+use sqlx::{PgConnection, Postgres, Transaction};
+use tracing::info;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+/// Result of a single delete
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeleteResponse {
+    /// Config snapshot taken just before the delete, `null` when disabled
+    pub snapshot_id: Option<Uuid>,
+}
+
+/// Result of a filtered bulk delete
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkDeleteResponse {
+    /// Number of rows removed
+    pub deleted: u64,
+    /// Config snapshot taken just before the delete, `null` when disabled
+    pub snapshot_id: Option<Uuid>,
+}
+
+/// A delete in progress, in one transaction with its `pre-delete-<timestamp>` config snapshot
+///
+/// Run the delete on [`PendingDelete::conn`] and then [`PendingDelete::commit`].
+/// Dropping it instead rolls back the snapshot too, so a failed delete leaves
+/// no restore point behind and a failed snapshot aborts the delete.
+struct PendingDelete {
+    tx: Transaction<'static, Postgres>,
+    version_name: String,
+    description: String,
+    snapshot_id: Option<Uuid>,
+}
+
+impl PendingDelete {
+    /// Open the transaction and take the snapshot in it, when enabled
+    async fn begin(state: &AppState, description: String) -> Result<Self, KaratewayError> {
+        let mut tx = state.db_pool.begin().await?;
+        let version_name = snapshot_name("pre-delete", Utc::now());
+        let snapshot_id = if state.snapshot_before_delete {
+            Some(
+                state
+                    .config_version_repo
+                    .create_snapshot(&mut *tx, &version_name, &description, SNAPSHOT_CREATED_BY)
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        Ok(Self {
+            tx,
+            version_name,
+            description,
+            snapshot_id,
+        })
+    }
+
+    /// Connection to run the delete on
+    fn conn(&mut self) -> &mut PgConnection {
+        &mut self.tx
+    }
+
+    /// Commit the delete and its snapshot, returning the snapshot id
+    async fn commit(self) -> Result<Option<Uuid>, KaratewayError> {
+        self.tx.commit().await?;
+        if let Some(snapshot_id) = self.snapshot_id {
+            info!(
+                "Took config snapshot {} ({}) before: {}",
+                self.version_name, snapshot_id, self.description
+            );
+        }
+
+        Ok(self.snapshot_id)
+    }
+}
+
+/// Check the parameters shared by the bulk delete endpoints
+///
+/// A filter is mandatory so a bare `DELETE` can never wipe a whole table, and
+/// `confirm=true` must be passed explicitly.
+fn require_bulk_delete_filter<T>(
+    filter: Option<T>,
+    filter_name: &str,
+    confirm: bool,
+) -> Result<T, KaratewayError> {
+    let filter = filter.ok_or_else(|| {
+        KaratewayError::Validation(format!("Bulk delete requires the {} filter", filter_name))
+    })?;
+
+    if !confirm {
+        return Err(KaratewayError::Validation(
+            "Bulk delete requires confirm=true".to_string(),
+        ));
+    }
+
+    Ok(filter)
+}
+
+/// Top-level paths of [`create_router`] and the OpenAPI JSON
+const RESERVED_PATHS: &[&str] = &["/api", "/api-docs", "/health"];
+
+/// Refuse a Swagger UI path that would collide with an admin route
+///
+/// The Swagger UI answers its path and everything below it, so it can't
+/// sit at or above a real route (`/`, `/api`), nor inside one.
+pub fn check_swagger_path(path: &str) -> anyhow::Result<()> {
+    let within = |path: &str, parent: &str| {
+        path == parent || parent == "/" || path.starts_with(&format!("{}/", parent))
+    };
+
+    match RESERVED_PATHS
+        .iter()
+        .find(|reserved| within(path, reserved) || within(reserved, path))
+    {
+        Some(reserved) => anyhow::bail!(
+            "ADMIN_SWAGGER_PATH {} collides with the admin API's {} routes",
+            path,
+            reserved
+        ),
+        None => Ok(()),
+    }
+}
 
 pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/health", get(health::health_check))
+        .route("/api/stats", get(stats::get_stats))
         .route(
             "/api/services/health",
             get(service_health::get_services_health),
         )
+        .route(
+            "/api/services/circuit-breakers",
+            get(service_health::list_circuit_breakers),
+        )
         .nest("/api/services", backend_service::routes(state.clone()))
         .nest("/api/routes", api_route::routes(state.clone()))
         .nest("/api/whitelist", whitelist_rule::routes(state.clone()))
         .nest("/api/rate-limits", rate_limit::routes(state.clone()))
+        .nest("/api/metric-tags", metric_tag_rule::routes(state.clone()))
         .nest("/api/audit-logs", audit_log::routes(state.clone()))
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swagger_path_must_not_collide_with_admin_routes() {
+        assert!(check_swagger_path("/swagger-ui").is_ok());
+        assert!(check_swagger_path("/docs/swagger").is_ok());
+        // A shared prefix alone isn't a collision
+        assert!(check_swagger_path("/apis").is_ok());
+
+        for path in ["/", "/api", "/api/swagger", "/api-docs", "/health/ui"] {
+            let error = check_swagger_path(path).unwrap_err().to_string();
+            assert!(error.contains("collides"), "{}: {}", path, error);
+        }
+    }
+}
@@ -4,17 +4,34 @@ use axum::{
     routing::{delete, get, post, put},
     Json, Router,
 };
+use karateway_config::config_limits::ConfigKind;
 use karateway_core::{
-    models::{CreateRateLimitRequest, RateLimit, UpdateRateLimitRequest},
+    models::{effective_rate_limits, CreateRateLimitRequest, RateLimit, UpdateRateLimitRequest},
     JsonResponse, MetaResponse,
 };
-use serde::Deserialize;
-use utoipa::IntoParams;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
 use validator::Validate;
 
+use super::{require_bulk_delete_filter, BulkDeleteResponse, PendingDelete};
 use crate::{error::ApiResult, state::AppState};
 
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RateLimitWithStatus {
+    #[serde(flatten)]
+    pub limit: RateLimit,
+    /// Whether the gateway can currently apply this limit (active and Redis reachable)
+    pub enforced: bool,
+}
+
+impl RateLimitWithStatus {
+    fn new(limit: RateLimit, redis_available: bool) -> Self {
+        let enforced = limit.is_active && redis_available;
+        Self { limit, enforced }
+    }
+}
+
 #[derive(Debug, Deserialize, IntoParams)]
 pub struct ListQuery {
     #[serde(default = "default_page")]
@@ -31,10 +48,20 @@ fn default_limit() -> u32 {
     10
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BulkDeleteQuery {
+    /// Delete every rate limit attached to this route
+    pub api_route_id: Option<Uuid>,
+    /// Must be `true` for the delete to run
+    #[serde(default)]
+    pub confirm: bool,
+}
+
 pub fn routes(_state: AppState) -> Router<AppState> {
     Router::new()
         .route("/", post(create_limit))
         .route("/", get(list_limits))
+        .route("/", delete(bulk_delete_limits))
         .route("/{id}", get(get_limit))
         .route("/{id}", put(update_limit))
         .route("/{id}", delete(delete_limit))
@@ -45,27 +72,38 @@ pub fn routes(_state: AppState) -> Router<AppState> {
     path = "/api/rate-limits",
     request_body = CreateRateLimitRequest,
     responses(
-        (status = 201, description = "Rate limit created successfully", body = JsonResponse<RateLimit>),
-        (status = 400, description = "Invalid request")
+        (status = 201, description = "Rate limit created successfully", body = JsonResponse<RateLimitWithStatus>),
+        (status = 400, description = "Invalid request"),
+        (status = 409, description = "Rate limit with same name already exists, or hard limit of active rules reached")
     ),
     tag = "rate-limits"
 )]
 async fn create_limit(
     State(state): State<AppState>,
     Json(req): Json<CreateRateLimitRequest>,
-) -> ApiResult<(StatusCode, Json<JsonResponse<RateLimit>>)> {
+) -> ApiResult<(StatusCode, Json<JsonResponse<RateLimitWithStatus>>)> {
     // Validate request
     req.validate()?;
+    req.identifier_type
+        .validate_header(req.identifier_header.as_deref())?;
+    RateLimit::validate_override(req.api_route_id, req.override_global)?;
+    check_route_costs(&state, req.api_route_id, req.max_requests, req.burst_size).await?;
+    state.check_config_limit(ConfigKind::Rules).await?;
 
     // Create limit
     let limit = state.rate_limit_repo.create(req).await?;
+    let limit = RateLimitWithStatus::new(limit, state.redis_available().await);
+
+    // The gateway skips rate limiting entirely without Redis, so say so up front
+    let message = if limit.enforced {
+        "Rate limit created successfully"
+    } else {
+        "Rate limit created, but it is not enforced: Redis is unreachable"
+    };
 
     Ok((
         StatusCode::CREATED,
-        Json(JsonResponse::created(
-            limit,
-            "Rate limit created successfully",
-        )),
+        Json(JsonResponse::created(limit, message)),
     ))
 }
 
@@ -74,19 +112,27 @@ async fn create_limit(
     path = "/api/rate-limits",
     params(ListQuery),
     responses(
-        (status = 200, description = "List of rate limits", body = JsonResponse<Vec<RateLimit>>)
+        (status = 200, description = "List of rate limits", body = JsonResponse<Vec<RateLimitWithStatus>>),
+        (status = 400, description = "Invalid page size")
     ),
     tag = "rate-limits"
 )]
 async fn list_limits(
     State(state): State<AppState>,
     Query(query): Query<ListQuery>,
-) -> ApiResult<Json<JsonResponse<Vec<RateLimit>>>> {
-    let limits = state.rate_limit_repo.list(query.page, query.limit).await?;
+) -> ApiResult<Json<JsonResponse<Vec<RateLimitWithStatus>>>> {
+    let limit = state.page_limits.apply(query.limit)?;
+    let limits = state.rate_limit_repo.list(query.page, limit).await?;
+
+    let redis_available = state.redis_available().await;
+    let limits = limits
+        .into_iter()
+        .map(|limit| RateLimitWithStatus::new(limit, redis_available))
+        .collect();
 
     let total = state.rate_limit_repo.count().await?;
 
-    let meta = MetaResponse::new(query.page, query.limit, total);
+    let meta = MetaResponse::new(query.page, limit, total);
 
     Ok(Json(JsonResponse::success_paginated(limits, meta)))
 }
@@ -98,7 +144,7 @@ async fn list_limits(
         ("id" = Uuid, Path, description = "Rate limit ID")
     ),
     responses(
-        (status = 200, description = "Rate limit found", body = JsonResponse<RateLimit>),
+        (status = 200, description = "Rate limit found", body = JsonResponse<RateLimitWithStatus>),
         (status = 404, description = "Rate limit not found")
     ),
     tag = "rate-limits"
@@ -106,8 +152,9 @@ async fn list_limits(
 async fn get_limit(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
-) -> ApiResult<Json<JsonResponse<RateLimit>>> {
+) -> ApiResult<Json<JsonResponse<RateLimitWithStatus>>> {
     let limit = state.rate_limit_repo.find_by_id(id).await?;
+    let limit = RateLimitWithStatus::new(limit, state.redis_available().await);
 
     Ok(Json(JsonResponse::success(limit)))
 }
@@ -120,8 +167,9 @@ async fn get_limit(
     ),
     request_body = UpdateRateLimitRequest,
     responses(
-        (status = 200, description = "Rate limit updated", body = JsonResponse<RateLimit>),
-        (status = 404, description = "Rate limit not found")
+        (status = 200, description = "Rate limit updated", body = JsonResponse<RateLimitWithStatus>),
+        (status = 404, description = "Rate limit not found"),
+        (status = 409, description = "Rate limit with same name already exists")
     ),
     tag = "rate-limits"
 )]
@@ -129,12 +177,43 @@ async fn update_limit(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
     Json(req): Json<UpdateRateLimitRequest>,
-) -> ApiResult<Json<JsonResponse<RateLimit>>> {
+) -> ApiResult<Json<JsonResponse<RateLimitWithStatus>>> {
     // Validate request
     req.validate()?;
 
+    // Check the identifier against what the limit will end up with
+    if req.identifier_type.is_some() || req.identifier_header.is_some() {
+        let existing = state.rate_limit_repo.find_by_id(id).await?;
+        let identifier_type = req
+            .identifier_type
+            .as_ref()
+            .unwrap_or(&existing.identifier_type);
+        let identifier_header = req
+            .identifier_header
+            .as_deref()
+            .or(existing.identifier_header.as_deref());
+        identifier_type.validate_header(identifier_header)?;
+    }
+    if req.override_global == Some(true) && req.api_route_id.is_none() {
+        let existing = state.rate_limit_repo.find_by_id(id).await?;
+        RateLimit::validate_override(existing.api_route_id, true)?;
+    }
+    if req.max_requests.is_some() || req.burst_size.is_some() || req.api_route_id.is_some() {
+        let existing = state.rate_limit_repo.find_by_id(id).await?;
+        if req.is_active.unwrap_or(existing.is_active) {
+            check_route_costs(
+                &state,
+                req.api_route_id.or(existing.api_route_id),
+                req.max_requests.unwrap_or(existing.max_requests),
+                req.burst_size.or(existing.burst_size),
+            )
+            .await?;
+        }
+    }
+
     // Update limit
     let limit = state.rate_limit_repo.update(id, req).await?;
+    let limit = RateLimitWithStatus::new(limit, state.redis_available().await);
 
     Ok(Json(JsonResponse::success_with_message(
         limit,
@@ -162,3 +241,102 @@ async fn delete_limit(
 
     Ok((StatusCode::OK, Json(JsonResponse::no_content())))
 }
+
+#[utoipa::path(
+    delete,
+    path = "/api/rate-limits",
+    params(BulkDeleteQuery),
+    responses(
+        (status = 200, description = "Rate limits deleted", body = JsonResponse<BulkDeleteResponse>),
+        (status = 400, description = "Missing filter or confirmation")
+    ),
+    tag = "rate-limits"
+)]
+async fn bulk_delete_limits(
+    State(state): State<AppState>,
+    Query(query): Query<BulkDeleteQuery>,
+) -> ApiResult<Json<JsonResponse<BulkDeleteResponse>>> {
+    let api_route_id =
+        require_bulk_delete_filter(query.api_route_id, "api_route_id", query.confirm)?;
+
+    let mut pending = PendingDelete::begin(
+        &state,
+        format!("Bulk delete of rate limits for API route {}", api_route_id),
+    )
+    .await?;
+    let deleted = state
+        .rate_limit_repo
+        .delete_by_api_route(pending.conn(), api_route_id)
+        .await?;
+    let snapshot_id = pending.commit().await?;
+
+    Ok(Json(JsonResponse::success_with_message(
+        BulkDeleteResponse {
+            deleted,
+            snapshot_id,
+        },
+        format!("Deleted {} rate limits", deleted),
+    )))
+}
+
+/// Reject a limit too small for the `request_cost` of a route it applies to
+///
+/// A route-specific limit is checked against its route; a global one against
+/// every active route, except those whose own limits override the globals.
+async fn check_route_costs(
+    state: &AppState,
+    api_route_id: Option<Uuid>,
+    max_requests: i32,
+    burst_size: Option<i32>,
+) -> karateway_core::Result<()> {
+    let routes = match api_route_id {
+        Some(route_id) => vec![state.api_route_repo.find_by_id(route_id).await?],
+        None => {
+            let overriding: Vec<Uuid> = state
+                .rate_limit_repo
+                .list_active()
+                .await?
+                .into_iter()
+                .filter(|limit| limit.override_global)
+                .filter_map(|limit| limit.api_route_id)
+                .collect();
+            state
+                .api_route_repo
+                .list_active()
+                .await?
+                .into_iter()
+                .filter(|route| !overriding.contains(&route.id))
+                .collect()
+        }
+    };
+
+    for route in routes {
+        if let Some(request_cost) = route.request_cost {
+            RateLimit::validate_request_cost(max_requests, burst_size, request_cost)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reject a `request_cost` that a rate limit applying to the route could never admit
+///
+/// `route_id` is `None` for a route that doesn't exist yet, which only gets
+/// the global limits.
+pub(crate) async fn check_request_cost(
+    state: &AppState,
+    route_id: Option<Uuid>,
+    request_cost: i32,
+) -> karateway_core::Result<()> {
+    let (route_limits, global_limits): (Vec<RateLimit>, Vec<RateLimit>) = state
+        .rate_limit_repo
+        .list_active()
+        .await?
+        .into_iter()
+        .filter(|limit| limit.api_route_id.is_none() || limit.api_route_id == route_id)
+        .partition(|limit| limit.api_route_id.is_some());
+
+    for limit in effective_rate_limits(&route_limits, &global_limits, None) {
+        RateLimit::validate_request_cost(limit.max_requests, limit.burst_size, request_cost)?;
+    }
+    Ok(())
+}
@@ -1,19 +1,22 @@
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
     Json,
 };
 use chrono::{DateTime, Utc};
-use karateway_core::JsonResponse;
+use karateway_config::circuit_breaker::{self, BreakerState};
+use karateway_config::health_cache::{self, HealthCheckRecord, HealthVerdict, HEALTH_CACHE_KEY};
+use karateway_config::health_probe::{self, ProbeResult};
+use karateway_core::{models::BackendService, JsonResponse, KaratewayError};
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use utoipa::ToSchema;
 use uuid;
 
-use crate::state::AppState;
+use crate::{error::ApiResult, state::AppState};
 
-const HEALTH_CACHE_KEY: &str = "services:health:data";
-const HEALTH_CACHE_TTL: i64 = 12 * 60 * 60; // 12 hours in seconds
+/// Timeout for the admin API's health probes; shorter than the gateway's since a request waits on them
+const ADMIN_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ServiceHealth {
@@ -25,12 +28,99 @@ pub struct ServiceHealth {
     pub status_message: String,
 }
 
+impl ServiceHealth {
+    fn new(service: BackendService, result: ProbeResult) -> Self {
+        Self {
+            id: service.id.to_string(),
+            name: service.name,
+            base_url: service.base_url,
+            health_check_url: service.health_check_url,
+            is_healthy: result.is_healthy,
+            status_message: result.status_message,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ServicesHealthResponse {
     pub services: Vec<ServiceHealth>,
     pub last_checked: DateTime<Utc>,
 }
 
+/// One health check the gateway ran
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthHistoryEntry {
+    pub checked_at: DateTime<Utc>,
+    pub is_healthy: bool,
+    pub latency_ms: u64,
+    /// Why the check failed, absent when it passed
+    pub error: Option<String>,
+}
+
+impl From<HealthCheckRecord> for HealthHistoryEntry {
+    fn from(record: HealthCheckRecord) -> Self {
+        Self {
+            checked_at: record.checked_at,
+            is_healthy: record.is_healthy,
+            latency_ms: record.latency_ms,
+            error: record.error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ServiceHealthHistory {
+    pub service_id: uuid::Uuid,
+    /// Newest first
+    pub history: Vec<HealthHistoryEntry>,
+}
+
+/// State of a circuit breaker, see [`BreakerState`]
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitBreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl From<BreakerState> for CircuitBreakerState {
+    fn from(state: BreakerState) -> Self {
+        match state {
+            BreakerState::Closed => CircuitBreakerState::Closed,
+            BreakerState::Open => CircuitBreakerState::Open,
+            BreakerState::HalfOpen => CircuitBreakerState::HalfOpen,
+        }
+    }
+}
+
+/// The breaker of one backend instance, as last reported by one gateway
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CircuitBreaker {
+    /// Gateway process that reported it; every gateway keeps its own breakers
+    pub gateway_id: String,
+    pub service_id: uuid::Uuid,
+    /// Absent when the service was deleted or deactivated since the report
+    pub service_name: Option<String>,
+    pub host: String,
+    pub port: u16,
+    pub state: CircuitBreakerState,
+    pub consecutive_failures: u32,
+    /// Requests and failures in the current error rate window
+    pub window_requests: u32,
+    pub window_failures: u32,
+    /// Seconds until an open breaker lets traffic through again
+    pub next_probe_in_seconds: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CircuitBreakersResponse {
+    /// Open breakers first
+    pub breakers: Vec<CircuitBreaker>,
+    /// When the latest gateway report was sent, absent when none arrived recently
+    pub reported_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct HealthQueryParams {
     #[serde(default)]
@@ -85,47 +175,34 @@ pub async fn get_services_health(
     };
 
     // Create HTTP client for health checks
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(3))
-        .build()
-        .expect("Failed to create HTTP client");
+    let client = health_probe::client(ADMIN_PROBE_TIMEOUT).expect("Failed to create HTTP client");
 
     let mut health_statuses = Vec::new();
+    let mut verdicts = Vec::new();
 
+    // Probes the gateways ran moments ago are reused rather than repeated
+    let mut probe_conn = state.redis_pool.get().await.ok();
     for service in services {
-        let (is_healthy, status_message) = if let Some(ref health_url) = service.health_check_url {
-            // Build full health check URL
-            let full_url =
-                if health_url.starts_with("http://") || health_url.starts_with("https://") {
-                    health_url.clone()
-                } else {
-                    format!("{}{}", service.base_url, health_url)
-                };
-
-            // Perform health check
-            match client.get(&full_url).send().await {
-                Ok(response) => {
-                    if response.status().is_success() {
-                        (true, format!("Healthy ({})", response.status()))
-                    } else {
-                        (false, format!("Unhealthy - returned {}", response.status()))
-                    }
-                }
-                Err(e) => (false, format!("Unhealthy - {}", e)),
+        let result = match probe_conn.as_mut() {
+            Some(conn) => {
+                health_probe::probe_coalesced(
+                    conn,
+                    &client,
+                    &service,
+                    state.health_probe_coalesce_ttl,
+                )
+                .await
             }
-        } else {
-            // No health check configured
-            (true, "No health check configured".to_string())
+            None => health_probe::probe(&client, &service).await,
         };
-
-        health_statuses.push(ServiceHealth {
-            id: service.id.to_string(),
-            name: service.name,
-            base_url: service.base_url,
-            health_check_url: service.health_check_url,
-            is_healthy,
-            status_message,
-        });
+        // Only what the gateway probes itself: active services with a health check
+        if service.is_active && service.health_check_url.is_some() {
+            verdicts.push(HealthVerdict {
+                service_id: service.id,
+                is_healthy: result.is_healthy,
+            });
+        }
+        health_statuses.push(ServiceHealth::new(service, result));
     }
 
     let response = ServicesHealthResponse {
@@ -133,15 +210,20 @@ pub async fn get_services_health(
         last_checked: Utc::now(),
     };
 
-    // Cache the result in Redis with 12-hour TTL
+    // Cache the result in Redis; the gateway drops it early on any status change
     if let Ok(mut redis_conn) = state.redis_pool.get().await {
+        // Hand the fresh results to the gateways so routing agrees with what is shown here
+        if let Err(e) = health_cache::publish_verdicts(&mut redis_conn, &verdicts).await {
+            tracing::warn!("Failed to publish health verdicts to the gateway: {}", e);
+        }
+
         if let Ok(json) = serde_json::to_string(&response) {
             let _: Result<(), _> = redis_conn
-                .set_ex(HEALTH_CACHE_KEY, json, HEALTH_CACHE_TTL as u64)
+                .set_ex(HEALTH_CACHE_KEY, json, state.health_cache_ttl_seconds)
                 .await;
             tracing::debug!(
                 "Cached health check data in Redis for {} seconds",
-                HEALTH_CACHE_TTL
+                state.health_cache_ttl_seconds
             );
         }
     }
@@ -149,6 +231,113 @@ pub async fn get_services_health(
     Json(JsonResponse::success(response))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/services/{id}/health/history",
+    params(
+        ("id" = uuid::Uuid, Path, description = "Backend service ID")
+    ),
+    responses(
+        (status = 200, description = "Recent health check results of the service, newest first", body = JsonResponse<ServiceHealthHistory>),
+        (status = 404, description = "Backend service not found"),
+        (status = 503, description = "Redis unavailable")
+    ),
+    tag = "backend-services"
+)]
+pub async fn get_service_health_history(
+    State(state): State<AppState>,
+    Path(id): Path<uuid::Uuid>,
+) -> ApiResult<Json<JsonResponse<ServiceHealthHistory>>> {
+    // Unknown services are a 404 rather than an empty history
+    state.backend_service_repo.find_by_id(id).await?;
+
+    // The gateway keeps the history and stores it in Redis after every check
+    let mut redis_conn = state
+        .redis_pool
+        .get()
+        .await
+        .map_err(|e| KaratewayError::ServiceUnavailable(format!("Redis unavailable: {}", e)))?;
+    let history = health_cache::load_history(&mut redis_conn, id)
+        .await
+        .map_err(KaratewayError::from)?;
+
+    Ok(Json(JsonResponse::success(ServiceHealthHistory {
+        service_id: id,
+        history: history.into_iter().map(HealthHistoryEntry::from).collect(),
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/services/circuit-breakers",
+    responses(
+        (status = 200, description = "Circuit breaker of every backend instance each gateway has sent traffic to", body = JsonResponse<CircuitBreakersResponse>),
+        (status = 503, description = "Redis unavailable")
+    ),
+    tag = "services"
+)]
+pub async fn list_circuit_breakers(
+    State(state): State<AppState>,
+) -> ApiResult<Json<JsonResponse<CircuitBreakersResponse>>> {
+    // Each gateway keeps its own breakers and reports them to Redis every few seconds
+    let mut redis_conn = state
+        .redis_pool
+        .get()
+        .await
+        .map_err(|e| KaratewayError::ServiceUnavailable(format!("Redis unavailable: {}", e)))?;
+    let reports = circuit_breaker::load_all(&mut redis_conn)
+        .await
+        .map_err(KaratewayError::from)?;
+    if reports.is_empty() {
+        return Ok(Json(JsonResponse::success(CircuitBreakersResponse {
+            breakers: Vec::new(),
+            reported_at: None,
+        })));
+    }
+
+    // Gateways only send traffic to active services
+    let names: std::collections::HashMap<uuid::Uuid, String> = state
+        .backend_service_repo
+        .list_active()
+        .await?
+        .into_iter()
+        .map(|service| (service.id, service.name))
+        .collect();
+
+    let now = Utc::now();
+    let reported_at = reports.iter().map(|report| report.reported_at).max();
+    let mut breakers: Vec<CircuitBreaker> = reports
+        .into_iter()
+        .flat_map(|report| {
+            let gateway_id = report.gateway_id;
+            report
+                .breakers
+                .into_iter()
+                .map(move |breaker| (gateway_id.clone(), breaker))
+        })
+        .map(|(gateway_id, breaker)| CircuitBreaker {
+            gateway_id,
+            service_name: names.get(&breaker.service_id).cloned(),
+            service_id: breaker.service_id,
+            host: breaker.host,
+            port: breaker.port,
+            state: breaker.state.into(),
+            consecutive_failures: breaker.consecutive_failures,
+            window_requests: breaker.window_requests,
+            window_failures: breaker.window_failures,
+            next_probe_in_seconds: breaker
+                .retry_at
+                .map(|retry_at| (retry_at - now).num_seconds().max(0)),
+        })
+        .collect();
+    breakers.sort_by_key(|breaker| !matches!(breaker.state, CircuitBreakerState::Open));
+
+    Ok(Json(JsonResponse::success(CircuitBreakersResponse {
+        breakers,
+        reported_at,
+    })))
+}
+
 /// Force health check for a specific service (used after creating new service)
 pub async fn check_service_health(state: &AppState, service_id: &str) -> Option<ServiceHealth> {
     // Parse service_id to Uuid
@@ -157,38 +346,40 @@ pub async fn check_service_health(state: &AppState, service_id: &str) -> Option<
     // Get the specific service
     let service = state.backend_service_repo.find_by_id(id).await.ok()?;
 
-    let (is_healthy, status_message) = if let Some(health_url) = &service.health_check_url {
-        let full_url = if health_url.starts_with("http://") || health_url.starts_with("https://") {
-            health_url.clone()
-        } else {
-            format!("{}{}", service.base_url, health_url)
-        };
+    let client = health_probe::client(ADMIN_PROBE_TIMEOUT).ok()?;
+    let result = health_probe::probe(&client, &service).await;
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(3))
-            .build()
-            .ok()?;
-
-        match client.get(&full_url).send().await {
-            Ok(response) => {
-                if response.status().is_success() {
-                    (true, format!("Healthy ({})", response.status()))
-                } else {
-                    (false, format!("Unhealthy - returned {}", response.status()))
-                }
+    Some(ServiceHealth::new(service, result))
+}
+
+/// Whether any backend is healthy, for the `at_least_one_backend_healthy` readiness check
+///
+/// Uses the cached snapshot when there is one, otherwise probes active
+/// services until one passes.
+pub async fn any_backend_healthy(state: &AppState) -> Result<bool, String> {
+    if let Ok(mut redis_conn) = state.redis_pool.get().await {
+        if let Ok(Some(cached_json)) = redis_conn
+            .get::<&str, Option<String>>(HEALTH_CACHE_KEY)
+            .await
+        {
+            if let Ok(cached) = serde_json::from_str::<ServicesHealthResponse>(&cached_json) {
+                return Ok(cached.services.iter().any(|service| service.is_healthy));
             }
-            Err(e) => (false, format!("Unhealthy - {}", e)),
         }
-    } else {
-        (true, "No health check configured".to_string())
-    };
+    }
+
+    let services = state
+        .backend_service_repo
+        .list_active()
+        .await
+        .map_err(|e| format!("Failed to fetch services: {}", e))?;
+    let client = health_probe::client(ADMIN_PROBE_TIMEOUT).map_err(|e| e.to_string())?;
+
+    for service in services {
+        if health_probe::probe(&client, &service).await.is_healthy {
+            return Ok(true);
+        }
+    }
 
-    Some(ServiceHealth {
-        id: service.id.to_string(),
-        name: service.name,
-        base_url: service.base_url,
-        health_check_url: service.health_check_url,
-        is_healthy,
-        status_message,
-    })
+    Ok(false)
 }
@@ -0,0 +1,60 @@
+use axum::{extract::State, Json};
+use karateway_config::repository::EntityCounts;
+use karateway_core::JsonResponse;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::{error::ApiResult, state::AppState};
+
+/// Rows of one kind, split by `is_active`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StateCounts {
+    pub total: u64,
+    pub active: u64,
+    pub inactive: u64,
+}
+
+impl From<EntityCounts> for StateCounts {
+    fn from(counts: EntityCounts) -> Self {
+        Self {
+            total: counts.total,
+            active: counts.active,
+            inactive: counts.inactive(),
+        }
+    }
+}
+
+/// Configured objects at a glance, for the dashboard's landing page
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StatsResponse {
+    pub services: StateCounts,
+    pub routes: StateCounts,
+    pub rate_limits: StateCounts,
+    pub whitelist_rules: StateCounts,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    responses(
+        (status = 200, description = "Total, active and inactive counts of each kind of object", body = JsonResponse<StatsResponse>)
+    ),
+    tag = "stats"
+)]
+pub async fn get_stats(
+    State(state): State<AppState>,
+) -> ApiResult<Json<JsonResponse<StatsResponse>>> {
+    let (services, routes, rate_limits, whitelist_rules) = futures::try_join!(
+        state.backend_service_repo.count_by_state(),
+        state.api_route_repo.count_by_state(),
+        state.rate_limit_repo.count_by_state(),
+        state.whitelist_rule_repo.count_by_state(),
+    )?;
+
+    Ok(Json(JsonResponse::success(StatsResponse {
+        services: services.into(),
+        routes: routes.into(),
+        rate_limits: rate_limits.into(),
+        whitelist_rules: whitelist_rules.into(),
+    })))
+}
@@ -4,15 +4,20 @@ use axum::{
     routing::{delete, get, post, put},
     Json, Router,
 };
+use karateway_config::{config_limits::ConfigKind, jwt_rule};
 use karateway_core::{
-    models::{CreateWhitelistRuleRequest, UpdateWhitelistRuleRequest, WhitelistRule},
-    JsonResponse, MetaResponse,
+    models::{
+        CreateWhitelistRuleRequest, RuleType, TokenValidationResult, UpdateWhitelistRuleRequest,
+        ValidateTokenRequest, WhitelistRule,
+    },
+    JsonResponse, KaratewayError, MetaResponse,
 };
 use serde::Deserialize;
 use utoipa::IntoParams;
 use uuid::Uuid;
 use validator::Validate;
 
+use super::{require_bulk_delete_filter, BulkDeleteResponse, PendingDelete};
 use crate::{error::ApiResult, state::AppState};
 
 #[derive(Debug, Deserialize, IntoParams)]
@@ -31,13 +36,24 @@ fn default_limit() -> u32 {
     10
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BulkDeleteQuery {
+    /// Delete every whitelist rule attached to this route
+    pub api_route_id: Option<Uuid>,
+    /// Must be `true` for the delete to run
+    #[serde(default)]
+    pub confirm: bool,
+}
+
 pub fn routes(_state: AppState) -> Router<AppState> {
     Router::new()
         .route("/", post(create_rule))
         .route("/", get(list_rules))
+        .route("/", delete(bulk_delete_rules))
         .route("/{id}", get(get_rule))
         .route("/{id}", put(update_rule))
         .route("/{id}", delete(delete_rule))
+        .route("/{id}/validate-token", post(validate_token))
 }
 
 #[utoipa::path(
@@ -46,7 +62,8 @@ pub fn routes(_state: AppState) -> Router<AppState> {
     request_body = CreateWhitelistRuleRequest,
     responses(
         (status = 201, description = "Whitelist rule created successfully", body = JsonResponse<WhitelistRule>),
-        (status = 400, description = "Invalid request")
+        (status = 400, description = "Invalid request"),
+        (status = 409, description = "Whitelist rule with same name already exists, or hard limit of active rules reached")
     ),
     tag = "whitelist-rules"
 )]
@@ -56,6 +73,8 @@ async fn create_rule(
 ) -> ApiResult<(StatusCode, Json<JsonResponse<WhitelistRule>>)> {
     // Validate request
     req.validate()?;
+    req.rule_type.validate_config(&req.config)?;
+    state.check_config_limit(ConfigKind::Rules).await?;
 
     // Create rule
     let rule = state.whitelist_rule_repo.create(req).await?;
@@ -74,7 +93,8 @@ async fn create_rule(
     path = "/api/whitelist",
     params(ListQuery),
     responses(
-        (status = 200, description = "List of whitelist rules", body = JsonResponse<Vec<WhitelistRule>>)
+        (status = 200, description = "List of whitelist rules", body = JsonResponse<Vec<WhitelistRule>>),
+        (status = 400, description = "Invalid page size")
     ),
     tag = "whitelist-rules"
 )]
@@ -82,14 +102,12 @@ async fn list_rules(
     State(state): State<AppState>,
     Query(query): Query<ListQuery>,
 ) -> ApiResult<Json<JsonResponse<Vec<WhitelistRule>>>> {
-    let rules = state
-        .whitelist_rule_repo
-        .list(query.page, query.limit)
-        .await?;
+    let limit = state.page_limits.apply(query.limit)?;
+    let rules = state.whitelist_rule_repo.list(query.page, limit).await?;
 
     let total = state.whitelist_rule_repo.count().await?;
 
-    let meta = MetaResponse::new(query.page, query.limit, total);
+    let meta = MetaResponse::new(query.page, limit, total);
 
     Ok(Json(JsonResponse::success_paginated(rules, meta)))
 }
@@ -124,7 +142,9 @@ async fn get_rule(
     request_body = UpdateWhitelistRuleRequest,
     responses(
         (status = 200, description = "Whitelist rule updated", body = JsonResponse<WhitelistRule>),
-        (status = 404, description = "Whitelist rule not found")
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Whitelist rule not found"),
+        (status = 409, description = "Whitelist rule with same name already exists")
     ),
     tag = "whitelist-rules"
 )]
@@ -136,6 +156,14 @@ async fn update_rule(
     // Validate request
     req.validate()?;
 
+    // Check the config against the rule type it will end up with
+    if req.rule_type.is_some() || req.config.is_some() {
+        let existing = state.whitelist_rule_repo.find_by_id(id).await?;
+        let rule_type = req.rule_type.as_ref().unwrap_or(&existing.rule_type);
+        let config = req.config.as_ref().unwrap_or(&existing.config);
+        rule_type.validate_config(config)?;
+    }
+
     // Update rule
     let rule = state.whitelist_rule_repo.update(id, req).await?;
 
@@ -165,3 +193,94 @@ async fn delete_rule(
 
     Ok((StatusCode::OK, Json(JsonResponse::no_content())))
 }
+
+#[utoipa::path(
+    delete,
+    path = "/api/whitelist",
+    params(BulkDeleteQuery),
+    responses(
+        (status = 200, description = "Whitelist rules deleted", body = JsonResponse<BulkDeleteResponse>),
+        (status = 400, description = "Missing filter or confirmation")
+    ),
+    tag = "whitelist-rules"
+)]
+async fn bulk_delete_rules(
+    State(state): State<AppState>,
+    Query(query): Query<BulkDeleteQuery>,
+) -> ApiResult<Json<JsonResponse<BulkDeleteResponse>>> {
+    let api_route_id =
+        require_bulk_delete_filter(query.api_route_id, "api_route_id", query.confirm)?;
+
+    let mut pending = PendingDelete::begin(
+        &state,
+        format!(
+            "Bulk delete of whitelist rules for API route {}",
+            api_route_id
+        ),
+    )
+    .await?;
+    let deleted = state
+        .whitelist_rule_repo
+        .delete_by_api_route(pending.conn(), api_route_id)
+        .await?;
+    let snapshot_id = pending.commit().await?;
+
+    Ok(Json(JsonResponse::success_with_message(
+        BulkDeleteResponse {
+            deleted,
+            snapshot_id,
+        },
+        format!("Deleted {} whitelist rules", deleted),
+    )))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/whitelist/{id}/validate-token",
+    params(
+        ("id" = Uuid, Path, description = "Whitelist rule ID")
+    ),
+    request_body = ValidateTokenRequest,
+    responses(
+        (status = 200, description = "Whether the token passes the rule, with its decoded claims", body = JsonResponse<TokenValidationResult>),
+        (status = 400, description = "Invalid request, or the rule is not a jwt rule"),
+        (status = 404, description = "Whitelist rule not found")
+    ),
+    tag = "whitelist-rules"
+)]
+async fn validate_token(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<ValidateTokenRequest>,
+) -> ApiResult<Json<JsonResponse<TokenValidationResult>>> {
+    req.validate()?;
+
+    let rule = state.whitelist_rule_repo.find_by_id(id).await?;
+    if rule.rule_type != RuleType::Jwt {
+        return Err(KaratewayError::Validation(format!(
+            "whitelist rule {} is a {} rule, not a jwt rule",
+            rule.rule_name, rule.rule_type
+        ))
+        .into());
+    }
+    let jwt_rule = jwt_rule::JwtRule::from_config(&rule.config).ok_or_else(|| {
+        KaratewayError::Validation(format!(
+            "whitelist rule {} has no jwt_secret",
+            rule.rule_name
+        ))
+    })?;
+
+    // Checked the same way the gateway checks a bearer token
+    let token = req.token.trim();
+    let token = token.strip_prefix("Bearer ").unwrap_or(token);
+    let rejection = jwt_rule.verify(token).err();
+    let decoded = jwt_rule::decode_unverified(token);
+
+    Ok(Json(JsonResponse::success(TokenValidationResult {
+        valid: rejection.is_none(),
+        reason: rejection.map(|rejection| rejection.to_string()),
+        token: jwt_rule::redact_signature(token),
+        header: decoded.as_ref().map(|(header, _)| header.clone()),
+        claims: decoded.map(|(_, claims)| claims.into()),
+    })))
+}
@@ -1,9 +1,22 @@
 use deadpool_redis::Pool as RedisPool;
-use karateway_config::repository::{
-    ApiRouteRepository, AuditLogRepository, BackendServiceRepository, RateLimitRepository,
-    WhitelistRuleRepository,
+use karateway_config::{
+    audit_webhook::WebhookConfig,
+    config_limits::{ConfigCounts, ConfigKind, ConfigLimits},
+    pagination::PageLimits,
+    readiness::HealthDependency,
+    repository::{
+        ApiRouteRepository, AuditLogRepository, BackendServiceRepository, ConfigVersionRepository,
+        MetricTagRuleRepository, RateLimitRepository, WhitelistRuleRepository,
+    },
+    AuditLogger,
+};
+use karateway_core::{
+    models::{RateLimit, RouteDefaults},
+    Result,
 };
 use sqlx::PgPool;
+use std::time::Duration;
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -14,10 +27,57 @@ pub struct AppState {
     pub whitelist_rule_repo: WhitelistRuleRepository,
     pub rate_limit_repo: RateLimitRepository,
     pub audit_log_repo: AuditLogRepository,
+    pub metric_tag_rule_repo: MetricTagRuleRepository,
+    pub config_version_repo: ConfigVersionRepository,
+    pub audit_logger: AuditLogger,
+    pub health_cache_ttl_seconds: u64,
+    /// How long a probe result is shared with the gateways, zero to always probe
+    pub health_probe_coalesce_ttl: Duration,
+    /// The gateway's catch-all rate limit, for the effective policy view
+    pub default_rate_limit: Option<RateLimit>,
+    /// What `/health` requires to report healthy
+    pub health_dependencies: Vec<HealthDependency>,
+    /// Page size bounds for the list endpoints
+    pub page_limits: PageLimits,
+    /// Whether deletes are preceded by an automatic config snapshot
+    pub snapshot_before_delete: bool,
+    /// Flags for route create requests that leave them out
+    pub route_defaults: RouteDefaults,
+    /// Caps on active routes, services and rules
+    pub config_limits: ConfigLimits,
+    /// Whether the gateway matches route paths ignoring ASCII case
+    pub case_insensitive_paths: bool,
+}
+
+/// Settings of the admin API, parsed from its config at startup
+pub struct AdminSettings {
+    pub health_cache_ttl_seconds: u64,
+    pub health_probe_coalesce_ttl: Duration,
+    pub default_rate_limit: Option<RateLimit>,
+    pub health_dependencies: Vec<HealthDependency>,
+    pub page_limits: PageLimits,
+    pub audit_webhook: Option<WebhookConfig>,
+    pub snapshot_before_delete: bool,
+    pub route_defaults: RouteDefaults,
+    pub config_limits: ConfigLimits,
+    pub case_insensitive_paths: bool,
 }
 
 impl AppState {
-    pub fn new(pool: PgPool, redis_pool: RedisPool) -> Self {
+    pub fn new(pool: PgPool, redis_pool: RedisPool, settings: AdminSettings) -> Self {
+        let AdminSettings {
+            health_cache_ttl_seconds,
+            health_probe_coalesce_ttl,
+            default_rate_limit,
+            health_dependencies,
+            page_limits,
+            audit_webhook,
+            snapshot_before_delete,
+            route_defaults,
+            config_limits,
+            case_insensitive_paths,
+        } = settings;
+
         Self {
             db_pool: pool.clone(),
             redis_pool,
@@ -25,7 +85,66 @@ impl AppState {
             api_route_repo: ApiRouteRepository::new(pool.clone()),
             whitelist_rule_repo: WhitelistRuleRepository::new(pool.clone()),
             rate_limit_repo: RateLimitRepository::new(pool.clone()),
-            audit_log_repo: AuditLogRepository::new(pool),
+            audit_log_repo: AuditLogRepository::new(pool.clone()),
+            metric_tag_rule_repo: MetricTagRuleRepository::new(pool.clone()),
+            config_version_repo: ConfigVersionRepository::new(pool.clone()),
+            audit_logger: AuditLogger::with_webhook(pool, Vec::new(), audit_webhook),
+            health_cache_ttl_seconds,
+            health_probe_coalesce_ttl,
+            default_rate_limit,
+            health_dependencies,
+            page_limits,
+            snapshot_before_delete,
+            route_defaults,
+            config_limits,
+            case_insensitive_paths,
+        }
+    }
+
+    /// Number of active objects of `kind`
+    async fn count_active(&self, kind: ConfigKind) -> Result<u64> {
+        match kind {
+            ConfigKind::Routes => self.api_route_repo.count_active().await,
+            ConfigKind::Services => self.backend_service_repo.count_active().await,
+            ConfigKind::Rules => Ok(self.whitelist_rule_repo.count_active().await?
+                + self.rate_limit_repo.count_active().await?),
+        }
+    }
+
+    /// Number of active objects of every kind
+    pub async fn config_counts(&self) -> Result<ConfigCounts> {
+        Ok(ConfigCounts {
+            routes: self.count_active(ConfigKind::Routes).await?,
+            services: self.count_active(ConfigKind::Services).await?,
+            rules: self.count_active(ConfigKind::Rules).await?,
+        })
+    }
+
+    /// Reject creating another object of `kind` once its hard limit is reached
+    pub async fn check_config_limit(&self, kind: ConfigKind) -> Result<()> {
+        if self.config_limits.limit(kind).hard.is_none() {
+            return Ok(());
+        }
+        let active = self.count_active(kind).await?;
+        self.config_limits.check_create(kind, active)
+    }
+
+    /// Whether Redis answers a PING, i.e. whether the gateway can enforce rate limits
+    pub async fn redis_available(&self) -> bool {
+        let mut conn = match self.redis_pool.get().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Redis connection failed: {}", e);
+                return false;
+            }
+        };
+
+        match redis::cmd("PING").query_async::<String>(&mut conn).await {
+            Ok(_) => true,
+            Err(e) => {
+                warn!("Redis ping failed: {}", e);
+                false
+            }
         }
     }
 }
@@ -1,4 +1,9 @@
+use crate::audit_webhook::WebhookConfig;
 use envconfig::Envconfig;
+use karateway_core::models::{IdentifierType, RateLimit, RouteDefaults};
+use std::time::Duration;
+use tracing::warn;
+use uuid::Uuid;
 
 #[derive(Envconfig, Clone, Debug)]
 pub struct AppConfig {
@@ -30,6 +35,14 @@ pub struct AppConfig {
     #[envconfig(from = "DB_IDLE_TIMEOUT_SECONDS", default = "600")]
     pub db_idle_timeout_seconds: u64,
 
+    // How long a query waits for a pooled connection; defaults to DB_CONNECT_TIMEOUT_SECONDS
+    #[envconfig(from = "DB_ACQUIRE_TIMEOUT_MS")]
+    pub db_acquire_timeout_ms: Option<u64>,
+
+    // When every pooled connection is busy: queue (wait up to the acquire timeout) or fail_fast (503)
+    #[envconfig(from = "DB_POOL_EXHAUSTED_MODE", default = "queue")]
+    pub db_pool_exhausted_mode: String,
+
     // Redis Configuration
     #[envconfig(from = "REDIS_HOST", default = "localhost")]
     pub redis_host: String,
@@ -43,6 +56,18 @@ pub struct AppConfig {
     #[envconfig(from = "REDIS_POOL_SIZE", default = "10")]
     pub redis_pool_size: usize,
 
+    // Connect over TLS (rediss://), as required by most managed Redis offerings
+    #[envconfig(from = "REDIS_TLS", default = "false")]
+    pub redis_tls: bool,
+
+    // Skip certificate verification (development only)
+    #[envconfig(from = "REDIS_TLS_INSECURE", default = "false")]
+    pub redis_tls_insecure: bool,
+
+    // PEM file with the CA that signed the Redis server certificate
+    #[envconfig(from = "REDIS_TLS_CA_CERT")]
+    pub redis_tls_ca_cert: Option<String>,
+
     // Gateway Configuration
     #[envconfig(from = "GATEWAY_HOST", default = "0.0.0.0")]
     pub gateway_host: String,
@@ -50,6 +75,320 @@ pub struct AppConfig {
     #[envconfig(from = "GATEWAY_PORT", default = "8080")]
     pub gateway_port: u16,
 
+    // Port of the metrics endpoint (GET /metrics, ?format=json for JSON)
+    #[envconfig(from = "GATEWAY_METRICS_PORT", default = "9091")]
+    pub gateway_metrics_port: u16,
+
+    // Lowest TLS version the HTTPS listener accepts: 1.2 or 1.3
+    #[envconfig(from = "GATEWAY_TLS_MIN_VERSION", default = "1.2")]
+    pub gateway_tls_min_version: String,
+
+    // Cipher suite profile: intermediate (TLS 1.2+) or modern (TLS 1.3 only)
+    #[envconfig(from = "GATEWAY_TLS_PROFILE", default = "intermediate")]
+    pub gateway_tls_profile: String,
+
+    // ALPN protocols the HTTPS listener negotiates: h2, http/1.1, or both comma-separated
+    #[envconfig(from = "GATEWAY_TLS_ALPN", default = "h2,http/1.1")]
+    pub gateway_tls_alpn: String,
+
+    // Ordered client IP sources: forwarded, x-forwarded-for, peer
+    #[envconfig(
+        from = "GATEWAY_CLIENT_IP_SOURCES",
+        default = "x-forwarded-for,forwarded,peer"
+    )]
+    pub gateway_client_ip_sources: String,
+
+    // Headers a client's request id is read from, in order; the first names generated ids
+    #[envconfig(from = "GATEWAY_REQUEST_ID_HEADERS", default = "X-Request-ID")]
+    pub gateway_request_id_headers: String,
+
+    // Ordered API version sources: path, accept, header:<name> (empty disables)
+    #[envconfig(from = "GATEWAY_API_VERSION_SOURCES", default = "path,accept")]
+    pub gateway_api_version_sources: String,
+
+    // How long a service removed from the config keeps its health status
+    #[envconfig(from = "GATEWAY_HEALTH_REMOVAL_GRACE_SECONDS", default = "300")]
+    pub health_removal_grace_seconds: u64,
+
+    // Health check results kept per service for its history (0 disables, at most 1000)
+    #[envconfig(from = "GATEWAY_HEALTH_HISTORY_SIZE", default = "50")]
+    pub gateway_health_history_size: usize,
+
+    // Retry-After on 503s for unhealthy services that have no health_check_interval_seconds
+    #[envconfig(from = "GATEWAY_UNHEALTHY_RETRY_AFTER_SECONDS", default = "10")]
+    pub gateway_unhealthy_retry_after_seconds: u64,
+
+    // Retries of a failed config reload before waiting for the next poll (0 disables)
+    #[envconfig(from = "CONFIG_RELOAD_MAX_RETRIES", default = "5")]
+    pub config_reload_max_retries: u32,
+
+    // Delay before the first reload retry, doubled for each further retry
+    #[envconfig(from = "CONFIG_RELOAD_RETRY_BASE_MS", default = "250")]
+    pub config_reload_retry_base_ms: u64,
+
+    // Longest delay between reload retries
+    #[envconfig(from = "CONFIG_RELOAD_RETRY_MAX_MS", default = "5000")]
+    pub config_reload_retry_max_ms: u64,
+
+    // Warn at config load above this many active routes (0 disables)
+    #[envconfig(from = "CONFIG_MAX_ROUTES_SOFT", default = "0")]
+    pub config_max_routes_soft: u64,
+
+    // Reject creating routes beyond this many active ones (0 disables)
+    #[envconfig(from = "CONFIG_MAX_ROUTES_HARD", default = "0")]
+    pub config_max_routes_hard: u64,
+
+    // Warn at config load above this many active backend services (0 disables)
+    #[envconfig(from = "CONFIG_MAX_SERVICES_SOFT", default = "0")]
+    pub config_max_services_soft: u64,
+
+    // Reject creating backend services beyond this many active ones (0 disables)
+    #[envconfig(from = "CONFIG_MAX_SERVICES_HARD", default = "0")]
+    pub config_max_services_hard: u64,
+
+    // Warn at config load above this many active whitelist rules and rate limits (0 disables)
+    #[envconfig(from = "CONFIG_MAX_RULES_SOFT", default = "0")]
+    pub config_max_rules_soft: u64,
+
+    // Reject creating whitelist rules and rate limits beyond this many active ones (0 disables)
+    #[envconfig(from = "CONFIG_MAX_RULES_HARD", default = "0")]
+    pub config_max_rules_hard: u64,
+
+    // Honour X-HTTP-Method-Override on all routes (otherwise only routes with allow_method_override)
+    #[envconfig(from = "GATEWAY_METHOD_OVERRIDE", default = "false")]
+    pub gateway_method_override: bool,
+
+    // Emit an invalid_request audit event for requests that match no route (possible probing)
+    #[envconfig(from = "GATEWAY_AUDIT_UNMATCHED_ROUTES", default = "false")]
+    pub gateway_audit_unmatched_routes: bool,
+
+    // Most unmatched-route audit events per client IP per minute
+    #[envconfig(from = "GATEWAY_AUDIT_UNMATCHED_MAX_PER_MINUTE", default = "10")]
+    pub gateway_audit_unmatched_max_per_minute: u32,
+
+    // Response to requests that match no route (the body defaults to a JSON error)
+    #[envconfig(from = "GATEWAY_NOT_FOUND_STATUS", default = "404")]
+    pub gateway_not_found_status: u16,
+
+    #[envconfig(from = "GATEWAY_NOT_FOUND_CONTENT_TYPE", default = "application/json")]
+    pub gateway_not_found_content_type: String,
+
+    #[envconfig(from = "GATEWAY_NOT_FOUND_BODY")]
+    pub gateway_not_found_body: Option<String>,
+
+    // Answer every request with a 503 maintenance page, skipping routing entirely
+    #[envconfig(from = "GATEWAY_MAINTENANCE_MODE", default = "false")]
+    pub gateway_maintenance_mode: bool,
+
+    // File the maintenance page is read from (.html, .json or text), re-read when it changes
+    #[envconfig(from = "GATEWAY_MAINTENANCE_PAGE")]
+    pub gateway_maintenance_page: Option<String>,
+
+    // Comma-separated path prefixes still proxied during maintenance, e.g. health checks
+    #[envconfig(from = "GATEWAY_MAINTENANCE_BYPASS_PATHS", default = "")]
+    pub gateway_maintenance_bypass_paths: String,
+
+    // Largest upstream response head (all header lines, in bytes) before answering 502
+    #[envconfig(from = "GATEWAY_MAX_RESPONSE_HEADER_BYTES", default = "65536")]
+    pub gateway_max_response_header_bytes: usize,
+
+    // Most upstream response headers before answering 502
+    #[envconfig(from = "GATEWAY_MAX_RESPONSE_HEADER_COUNT", default = "100")]
+    pub gateway_max_response_header_count: usize,
+
+    // Add X-Upstream-Time-Ms and X-Gateway-Time-Ms to all responses (routes can opt in on their own)
+    #[envconfig(from = "GATEWAY_TIMING_HEADERS", default = "false")]
+    pub gateway_timing_headers: bool,
+
+    // Name the backend service that served each request in X-Backend-Service (exposes the topology)
+    #[envconfig(from = "GATEWAY_BACKEND_SERVICE_HEADER", default = "false")]
+    pub gateway_backend_service_header: bool,
+
+    // Send the matched route's id to the backend in a request header (exposes internal ids)
+    #[envconfig(from = "GATEWAY_ROUTE_ID_HEADER_ENABLED", default = "false")]
+    pub gateway_route_id_header_enabled: bool,
+
+    #[envconfig(from = "GATEWAY_ROUTE_ID_HEADER", default = "X-Route-ID")]
+    pub gateway_route_id_header: String,
+
+    // Let trusted clients override a route's total timeout with X-Gateway-Timeout-Ms (debugging only)
+    #[envconfig(from = "GATEWAY_TIMEOUT_OVERRIDE_ENABLED", default = "false")]
+    pub gateway_timeout_override_enabled: bool,
+
+    // IPs/CIDRs trusted to send X-Gateway-Timeout-Ms, comma-separated (empty trusts no IP)
+    #[envconfig(from = "GATEWAY_TIMEOUT_OVERRIDE_TRUSTED_IPS", default = "")]
+    pub gateway_timeout_override_trusted_ips: String,
+
+    // Token that makes a client trusted when sent in X-Gateway-Debug-Token
+    #[envconfig(from = "GATEWAY_TIMEOUT_OVERRIDE_TOKEN")]
+    pub gateway_timeout_override_token: Option<String>,
+
+    // Largest timeout a client can ask for; bigger values are clamped
+    #[envconfig(from = "GATEWAY_TIMEOUT_OVERRIDE_MAX_MS", default = "60000")]
+    pub gateway_timeout_override_max_ms: u64,
+
+    // Let trusted clients reach a blue/green route's inactive backend by sending the canary header
+    #[envconfig(from = "GATEWAY_CANARY_HEADER_ENABLED", default = "false")]
+    pub gateway_canary_header_enabled: bool,
+
+    // Header that asks for the canary, and the value it must have
+    #[envconfig(from = "GATEWAY_CANARY_HEADER", default = "X-Canary")]
+    pub gateway_canary_header: String,
+
+    #[envconfig(from = "GATEWAY_CANARY_HEADER_VALUE", default = "true")]
+    pub gateway_canary_header_value: String,
+
+    // IPs/CIDRs allowed to force the canary, comma-separated (empty allows no one; 0.0.0.0/0,::/0 anyone)
+    #[envconfig(from = "GATEWAY_CANARY_TRUSTED_IPS", default = "")]
+    pub gateway_canary_trusted_ips: String,
+
+    // Most concurrent client connections across the proxy listeners (0: unlimited)
+    #[envconfig(from = "GATEWAY_MAX_CONNECTIONS", default = "10000")]
+    pub gateway_max_connections: usize,
+
+    // Time a client has to send a complete request head, incl. keep-alive idle time (0: no limit)
+    #[envconfig(from = "GATEWAY_HEADER_READ_TIMEOUT_MS", default = "10000")]
+    pub gateway_header_read_timeout_ms: u64,
+
+    // Most concurrent connections and in-flight requests from a single client IP (0: unlimited)
+    #[envconfig(from = "GATEWAY_MAX_CONNECTIONS_PER_IP", default = "0")]
+    pub gateway_max_connections_per_ip: usize,
+
+    #[envconfig(from = "GATEWAY_MAX_REQUESTS_PER_IP", default = "0")]
+    pub gateway_max_requests_per_ip: usize,
+
+    // IPs/CIDRs the per-IP caps don't apply to, comma-separated (empty exempts no IP)
+    #[envconfig(from = "GATEWAY_CLIENT_LIMIT_EXEMPT_IPS", default = "")]
+    pub gateway_client_limit_exempt_ips: String,
+
+    // Hard cap on a request's whole time in the gateway, before and with the upstream (0: no limit)
+    #[envconfig(from = "GATEWAY_MAX_REQUEST_DURATION_MS", default = "0")]
+    pub gateway_max_request_duration_ms: u64,
+
+    // Lookups retried when a backend hostname fails to resolve transiently, at most 5 (0: fail at once)
+    #[envconfig(from = "GATEWAY_DNS_RETRIES", default = "2")]
+    pub gateway_dns_retries: u32,
+
+    // Wait before the first DNS retry, doubled for each one after it
+    #[envconfig(from = "GATEWAY_DNS_RETRY_BACKOFF_MS", default = "25")]
+    pub gateway_dns_retry_backoff_ms: u64,
+
+    // Require a PROXY protocol v1/v2 header on the HTTP listener, for L4 balancers like AWS NLB
+    #[envconfig(from = "GATEWAY_PROXY_PROTOCOL", default = "false")]
+    pub gateway_proxy_protocol: bool,
+
+    // TCP keepalive on accepted proxy connections: idle seconds before probing (0 leaves it off),
+    // seconds between probes and unanswered probes before the connection is dropped
+    #[envconfig(from = "GATEWAY_TCP_KEEPALIVE_SECONDS", default = "0")]
+    pub gateway_tcp_keepalive_seconds: u64,
+
+    #[envconfig(from = "GATEWAY_TCP_KEEPALIVE_INTERVAL_SECONDS", default = "10")]
+    pub gateway_tcp_keepalive_interval_seconds: u64,
+
+    #[envconfig(from = "GATEWAY_TCP_KEEPALIVE_PROBES", default = "6")]
+    pub gateway_tcp_keepalive_probes: usize,
+
+    // Bind the proxy listeners with SO_REUSEPORT
+    #[envconfig(from = "GATEWAY_SO_REUSEPORT", default = "false")]
+    pub gateway_so_reuseport: bool,
+
+    // TCP Fast Open queue length of the proxy listeners (0 leaves it off)
+    #[envconfig(from = "GATEWAY_TCP_FASTOPEN_QUEUE", default = "0")]
+    pub gateway_tcp_fastopen_queue: usize,
+
+    // Allow routes with debug_log_body to log request/response bodies; off so it must be opted into
+    #[envconfig(from = "GATEWAY_DEBUG_BODY_LOGGING", default = "false")]
+    pub gateway_debug_body_logging: bool,
+
+    // Largest body logged by debug_log_body; bigger bodies are logged by size only
+    #[envconfig(from = "GATEWAY_DEBUG_BODY_MAX_BYTES", default = "4096")]
+    pub gateway_debug_body_max_bytes: usize,
+
+    // Fields masked in logged JSON and text bodies, comma-separated and case-insensitive
+    #[envconfig(
+        from = "GATEWAY_DEBUG_BODY_REDACT_FIELDS",
+        default = "password,token,access_token,refresh_token,secret,authorization,api_key"
+    )]
+    pub gateway_debug_body_redact_fields: String,
+
+    // Largest decompressed body of routes with decompress_response; bigger responses are cut off
+    #[envconfig(from = "GATEWAY_DECOMPRESS_MAX_BYTES", default = "10485760")]
+    pub gateway_decompress_max_bytes: usize,
+
+    // Recent route matches remembered per config snapshot, one per route pattern and query/content type (0: off)
+    #[envconfig(from = "GATEWAY_ROUTE_CACHE_SIZE", default = "0")]
+    pub gateway_route_cache_size: usize,
+
+    // Match route paths ignoring ASCII case, so /API/Users hits a /api/users route (query strings stay exact)
+    #[envconfig(from = "GATEWAY_CASE_INSENSITIVE_PATHS", default = "false")]
+    pub gateway_case_insensitive_paths: bool,
+
+    // Threads for Pingora's request handling and the background runtime (unset: library defaults)
+    #[envconfig(from = "GATEWAY_WORKER_THREADS")]
+    pub gateway_worker_threads: Option<usize>,
+
+    // Write one gateway_metrics row per request (latency, size, error message)
+    #[envconfig(from = "GATEWAY_REQUEST_LOG", default = "false")]
+    pub gateway_request_log: bool,
+
+    // Days gateway_metrics rows are kept before an hourly cleanup deletes them (0 keeps them forever)
+    #[envconfig(from = "GATEWAY_METRICS_RETENTION_DAYS", default = "30")]
+    pub gateway_metrics_retention_days: u32,
+
+    // Roll deleted gateway_metrics rows up into gateway_metrics_hourly first
+    #[envconfig(from = "GATEWAY_METRICS_HOURLY_ROLLUP", default = "true")]
+    pub gateway_metrics_hourly_rollup: bool,
+
+    // Default Rate Limit (applied to routes without a route-specific limit)
+    #[envconfig(from = "DEFAULT_RATE_LIMIT_MAX_REQUESTS")]
+    pub default_rate_limit_max_requests: Option<i32>,
+
+    #[envconfig(from = "DEFAULT_RATE_LIMIT_WINDOW_SECONDS", default = "60")]
+    pub default_rate_limit_window_seconds: i32,
+
+    #[envconfig(from = "DEFAULT_RATE_LIMIT_IDENTIFIER", default = "ip")]
+    pub default_rate_limit_identifier: String,
+
+    #[envconfig(from = "DEFAULT_RATE_LIMIT_BURST_SIZE")]
+    pub default_rate_limit_burst_size: Option<i32>,
+
+    // What to do when the rate limiter errors (e.g. Redis down): open (allow) or closed (503)
+    #[envconfig(from = "RATE_LIMIT_FAILURE_MODE", default = "closed")]
+    pub rate_limit_failure_mode: String,
+
+    // Longest a single rate limiter Redis command may take before it counts as a failure, 0 to wait indefinitely
+    #[envconfig(from = "RATE_LIMIT_REDIS_TIMEOUT_MS", default = "100")]
+    pub rate_limit_redis_timeout_ms: u64,
+
+    // Audit escalation rules: event_type:threshold:window_seconds, comma-separated
+    #[envconfig(from = "AUDIT_ESCALATION_RULES", default = "whitelist_denied:10:60")]
+    pub audit_escalation_rules: String,
+
+    // Audit sampling rules: event_type:max_events:window_seconds per client IP, comma-separated
+    #[envconfig(from = "AUDIT_SAMPLING_RULES", default = "")]
+    pub audit_sampling_rules: String,
+
+    // Audit categories never recorded, comma-separated: authentication, rate_limit, whitelist, admin
+    #[envconfig(from = "AUDIT_DISABLED_CATEGORIES", default = "")]
+    pub audit_disabled_categories: String,
+
+    // Forward audit events as JSON batches to this webhook (e.g. a SIEM), in addition to the database
+    #[envconfig(from = "AUDIT_WEBHOOK_URL")]
+    pub audit_webhook_url: Option<String>,
+
+    #[envconfig(from = "AUDIT_WEBHOOK_BATCH_SIZE", default = "100")]
+    pub audit_webhook_batch_size: usize,
+
+    #[envconfig(from = "AUDIT_WEBHOOK_FLUSH_INTERVAL_MS", default = "1000")]
+    pub audit_webhook_flush_interval_ms: u64,
+
+    // Retries per batch, with exponential backoff starting at AUDIT_WEBHOOK_RETRY_BACKOFF_MS
+    #[envconfig(from = "AUDIT_WEBHOOK_MAX_RETRIES", default = "5")]
+    pub audit_webhook_max_retries: u32,
+
+    #[envconfig(from = "AUDIT_WEBHOOK_RETRY_BACKOFF_MS", default = "500")]
+    pub audit_webhook_retry_backoff_ms: u64,
+
     // Admin API Configuration
     #[envconfig(from = "ADMIN_API_HOST", default = "0.0.0.0")]
     pub admin_api_host: String,
@@ -57,6 +396,58 @@ pub struct AppConfig {
     #[envconfig(from = "ADMIN_API_PORT", default = "8081")]
     pub admin_api_port: u16,
 
+    // IPs and CIDR ranges allowed to reach the admin API, comma-separated (empty allows all)
+    #[envconfig(from = "ADMIN_IP_ALLOWLIST", default = "")]
+    pub admin_ip_allowlist: String,
+
+    // Ordered client IP sources for the allowlist: forwarded, x-forwarded-for, peer
+    #[envconfig(from = "ADMIN_CLIENT_IP_SOURCES", default = "peer")]
+    pub admin_client_ip_sources: String,
+
+    // Serve the Swagger UI and /api-docs/openapi.json (disable in production)
+    #[envconfig(from = "ADMIN_SWAGGER_ENABLED", default = "true")]
+    pub admin_swagger_enabled: bool,
+
+    // Where the Swagger UI is mounted; must not be /, or at or under /api, /api-docs or /health
+    #[envconfig(from = "ADMIN_SWAGGER_PATH", default = "/swagger-ui")]
+    pub admin_swagger_path: String,
+
+    // Largest page size the admin list endpoints serve
+    #[envconfig(from = "ADMIN_MAX_PAGE_SIZE", default = "200")]
+    pub admin_max_page_size: u32,
+
+    // What to do with a larger `limit`: clamp (serve the maximum) or reject (400)
+    #[envconfig(from = "ADMIN_PAGE_SIZE_OVER_LIMIT", default = "clamp")]
+    pub admin_page_size_over_limit: String,
+
+    // Take a config snapshot (create_config_snapshot) before deletes, as a restore point
+    #[envconfig(from = "ADMIN_SNAPSHOT_BEFORE_DELETE", default = "true")]
+    pub admin_snapshot_before_delete: bool,
+
+    // strip_path_prefix for routes created without one
+    #[envconfig(from = "ROUTE_DEFAULT_STRIP_PATH_PREFIX", default = "false")]
+    pub route_default_strip_path_prefix: bool,
+
+    // preserve_host_header for routes created without one
+    #[envconfig(from = "ROUTE_DEFAULT_PRESERVE_HOST_HEADER", default = "false")]
+    pub route_default_preserve_host_header: bool,
+
+    // Dependencies /health needs for "healthy": database, redis, at_least_one_backend_healthy
+    #[envconfig(from = "HEALTH_REQUIRED_DEPENDENCIES", default = "database,redis")]
+    pub health_required_dependencies: String,
+
+    // List config soft limits that are exceeded in /health
+    #[envconfig(from = "HEALTH_REPORT_CONFIG_LIMITS", default = "false")]
+    pub health_report_config_limits: bool,
+
+    // How long the service health snapshot is cached in Redis
+    #[envconfig(from = "HEALTH_CACHE_TTL_SECONDS", default = "30")]
+    pub health_cache_ttl_seconds: u64,
+
+    // How long a backend's health probe result is shared by every prober (0 disables)
+    #[envconfig(from = "HEALTH_PROBE_COALESCE_TTL_SECONDS", default = "2")]
+    pub health_probe_coalesce_ttl_seconds: u64,
+
     // JWT Secret
     #[envconfig(from = "JWT_SECRET")]
     pub jwt_secret: String,
@@ -80,12 +471,116 @@ impl AppConfig {
         )
     }
 
+    /// Build the catch-all default rate limit, if one is configured
+    ///
+    /// Returns `None` when `DEFAULT_RATE_LIMIT_MAX_REQUESTS` is unset or any of
+    /// the default rate limit settings are invalid.
+    pub fn default_rate_limit(&self) -> Option<RateLimit> {
+        let max_requests = self.default_rate_limit_max_requests?;
+
+        if max_requests <= 0 || self.default_rate_limit_window_seconds <= 0 {
+            warn!(
+                "Ignoring default rate limit: max_requests ({}) and window_seconds ({}) must be positive",
+                max_requests, self.default_rate_limit_window_seconds
+            );
+            return None;
+        }
+
+        let identifier_type = match self.default_rate_limit_identifier.parse::<IdentifierType>() {
+            Ok(identifier_type) => identifier_type,
+            Err(e) => {
+                warn!("Ignoring default rate limit: {}", e);
+                return None;
+            }
+        };
+        // There is no setting naming the header to count by
+        if let Err(e) = identifier_type.validate_header(None) {
+            warn!("Ignoring default rate limit: {}", e);
+            return None;
+        }
+
+        let now = chrono::Utc::now();
+        Some(RateLimit {
+            id: Uuid::nil(),
+            name: "default".to_string(),
+            api_route_id: None,
+            max_requests,
+            window_seconds: self.default_rate_limit_window_seconds,
+            identifier_type,
+            identifier_header: None,
+            is_active: true,
+            burst_size: self.default_rate_limit_burst_size,
+            capacity_factor: None,
+            override_global: false,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Flags applied to route create requests that leave them out
+    pub fn route_defaults(&self) -> RouteDefaults {
+        RouteDefaults {
+            strip_path_prefix: self.route_default_strip_path_prefix,
+            preserve_host_header: self.route_default_preserve_host_header,
+        }
+    }
+
+    /// Build the audit webhook settings, if a webhook is configured
+    ///
+    /// Returns `None` when `AUDIT_WEBHOOK_URL` is unset, empty or not an
+    /// http(s) URL.
+    pub fn audit_webhook(&self) -> Option<WebhookConfig> {
+        let url = self.audit_webhook_url.as_deref().map(str::trim)?;
+        if url.is_empty() {
+            return None;
+        }
+
+        match reqwest::Url::parse(url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            _ => {
+                warn!("Ignoring audit webhook: invalid URL {}", url);
+                return None;
+            }
+        }
+
+        Some(WebhookConfig {
+            url: url.to_string(),
+            batch_size: self.audit_webhook_batch_size.max(1),
+            flush_interval: Duration::from_millis(self.audit_webhook_flush_interval_ms),
+            max_retries: self.audit_webhook_max_retries,
+            retry_backoff: Duration::from_millis(self.audit_webhook_retry_backoff_ms),
+        })
+    }
+
+    /// Configured gateway worker thread count, `None` for the defaults
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.gateway_worker_threads.filter(|threads| *threads > 0)
+    }
+
+    /// Swagger UI mount path with a single leading and no trailing slash
+    pub fn swagger_path(&self) -> String {
+        format!("/{}", self.admin_swagger_path.trim().trim_matches('/'))
+    }
+
     /// Build Redis connection URL
+    ///
+    /// Uses `rediss://` when `REDIS_TLS` is set; `REDIS_TLS_INSECURE` adds the
+    /// `#insecure` fragment that turns off certificate verification.
     pub fn redis_url(&self) -> String {
-        if self.redis_password.is_empty() {
-            format!("redis://{}:{}", self.redis_host, self.redis_port)
+        let scheme = if self.redis_tls { "rediss" } else { "redis" };
+        let mut url = if self.redis_password.is_empty() {
+            format!("{}://{}:{}", scheme, self.redis_host, self.redis_port)
         } else {
-            format!("redis://:{}@{}:{}", self.redis_password, self.redis_host, self.redis_port)
+            format!(
+                "{}://:{}@{}:{}",
+                scheme, self.redis_password, self.redis_host, self.redis_port
+            )
+        };
+
+        if self.redis_tls && self.redis_tls_insecure {
+            url.push_str("#insecure");
         }
+
+        url
     }
 }
@@ -0,0 +1,69 @@
+use karateway_core::models::AuditEventCategory;
+use std::collections::HashSet;
+use tracing::warn;
+
+/// Every category an audit event can have
+const CATEGORIES: [AuditEventCategory; 4] = [
+    AuditEventCategory::Authentication,
+    AuditEventCategory::RateLimit,
+    AuditEventCategory::Whitelist,
+    AuditEventCategory::Admin,
+];
+
+/// Audit event categories a deployment doesn't record
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DisabledCategories(HashSet<String>);
+
+impl DisabledCategories {
+    /// Parse a comma-separated list of category names, e.g. `rate_limit,admin`
+    ///
+    /// Unknown names are logged and skipped.
+    pub fn parse(value: &str) -> Self {
+        let known: Vec<String> = CATEGORIES.iter().map(ToString::to_string).collect();
+
+        Self(
+            value
+                .split(',')
+                .map(|name| name.trim().to_lowercase())
+                .filter(|name| !name.is_empty())
+                .filter(|name| {
+                    let valid = known.contains(name);
+                    if !valid {
+                        warn!(
+                            "Ignoring unknown audit category {}, expected one of {}",
+                            name,
+                            known.join(", ")
+                        );
+                    }
+                    valid
+                })
+                .collect(),
+        )
+    }
+
+    /// Whether events of `category` (as stored, e.g. `rate_limit`) are recorded
+    pub fn is_enabled(&self, category: &str) -> bool {
+        !self.0.contains(category)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_disabled_categories() {
+        let disabled = DisabledCategories::parse(" rate_limit, ADMIN ,,bogus");
+        assert!(!disabled.is_enabled("rate_limit"));
+        assert!(!disabled.is_enabled("admin"));
+        assert!(disabled.is_enabled("whitelist"));
+        assert!(disabled.is_enabled("authentication"));
+
+        assert!(DisabledCategories::parse("").is_empty());
+        assert!(DisabledCategories::parse("bogus").is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+use karateway_core::models::{
+    AuditEventCategory, AuditEventType, AuditLog, AuditLogBuilder, AuditSeverity,
+};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Upper bound on the number of (rule, client IP) pairs tracked at once
+///
+/// Each pair keeps at most `threshold + 1` timestamps, so memory stays around
+/// `MAX_TRACKED_KEYS * (threshold + 1) * 16` bytes per rule. Longer windows
+/// keep pairs alive longer and fill the table faster; once it is full, pairs
+/// without events inside their window are dropped first, and if that frees
+/// nothing new client IPs go untracked until older ones expire.
+pub const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Escalate when one client IP produces more than `threshold` events of `event_type` within `window`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EscalationRule {
+    pub event_type: String,
+    pub threshold: usize,
+    pub window: Duration,
+}
+
+impl std::str::FromStr for EscalationRule {
+    type Err = String;
+
+    /// Parse `event_type:threshold:window_seconds`, e.g. `whitelist_denied:10:60`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid audit escalation rule: {}", s);
+
+        let mut parts = s.trim().splitn(3, ':');
+        let event_type = parts.next().filter(|p| !p.is_empty()).ok_or_else(invalid)?;
+        let threshold = parts
+            .next()
+            .and_then(|p| p.parse::<usize>().ok())
+            .filter(|t| *t > 0)
+            .ok_or_else(invalid)?;
+        let window_seconds = parts
+            .next()
+            .and_then(|p| p.parse::<u64>().ok())
+            .filter(|w| *w > 0)
+            .ok_or_else(invalid)?;
+
+        Ok(Self {
+            event_type: event_type.to_string(),
+            threshold,
+            window: Duration::from_secs(window_seconds),
+        })
+    }
+}
+
+/// Parse a comma-separated list of escalation rules
+///
+/// Invalid entries are logged and skipped.
+pub fn parse_rules(value: &str) -> Vec<EscalationRule> {
+    value
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .filter_map(|s| match s.parse() {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Sliding-window counter turning bursts of audit events into Critical alerts
+pub struct EscalationTracker {
+    rules: Vec<EscalationRule>,
+    /// Recent event times per (rule index, client IP)
+    events: HashMap<(usize, String), VecDeque<Instant>>,
+}
+
+impl EscalationTracker {
+    pub fn new(rules: Vec<EscalationRule>) -> Self {
+        Self {
+            rules,
+            events: HashMap::new(),
+        }
+    }
+
+    /// Record an audit event, returning a synthetic Critical event for each rule it tripped
+    ///
+    /// A rule fires once per burst: its counter is reset after escalating, so a
+    /// client that keeps going triggers again only after another `threshold`
+    /// events.
+    pub fn record(&mut self, log: &AuditLog, now: Instant) -> Vec<AuditLog> {
+        // Events without a real client IP can't be attributed to one source
+        let Some(client_ip) = log.client_ip.as_deref().filter(|ip| *ip != "unknown") else {
+            return Vec::new();
+        };
+
+        let mut escalations = Vec::new();
+
+        for index in 0..self.rules.len() {
+            let rule = &self.rules[index];
+            if rule.event_type != log.event_type {
+                continue;
+            }
+
+            let key = (index, client_ip.to_string());
+            if !self.events.contains_key(&key) && self.events.len() >= MAX_TRACKED_KEYS {
+                self.prune(now);
+                if self.events.len() >= MAX_TRACKED_KEYS {
+                    continue;
+                }
+            }
+
+            let rule = &self.rules[index];
+            let times = self.events.entry(key).or_default();
+            while times
+                .front()
+                .is_some_and(|t| now.saturating_duration_since(*t) > rule.window)
+            {
+                times.pop_front();
+            }
+            times.push_back(now);
+
+            if times.len() > rule.threshold {
+                let count = times.len();
+                self.events.remove(&(index, client_ip.to_string()));
+                escalations.push(escalation_event(rule, log, client_ip, count));
+            }
+        }
+
+        escalations
+    }
+
+    /// Drop pairs whose most recent event has left its rule's window
+    fn prune(&mut self, now: Instant) {
+        let rules = &self.rules;
+        self.events.retain(|(index, _), times| {
+            times
+                .back()
+                .is_some_and(|t| now.saturating_duration_since(*t) <= rules[*index].window)
+        });
+    }
+}
+
+fn escalation_event(
+    rule: &EscalationRule,
+    trigger: &AuditLog,
+    client_ip: &str,
+    count: usize,
+) -> AuditLog {
+    let mut builder = AuditLogBuilder::new(
+        AuditEventType::SecurityAlert,
+        AuditEventCategory::Admin,
+        AuditSeverity::Critical,
+        format!(
+            "{} {} events from {} within {}s",
+            count,
+            rule.event_type,
+            client_ip,
+            rule.window.as_secs()
+        ),
+    )
+    .client_ip(client_ip)
+    .metadata(serde_json::json!({
+        "rule_event_type": rule.event_type,
+        "threshold": rule.threshold,
+        "window_seconds": rule.window.as_secs(),
+        "count": count,
+        "trigger_category": trigger.event_category,
+    }));
+
+    if let Some(method) = &trigger.request_method {
+        builder = builder.request_method(method.clone());
+    }
+    if let Some(path) = &trigger.request_path {
+        builder = builder.request_path(path.clone());
+    }
+    if let Some(route_id) = trigger.api_route_id {
+        builder = builder.api_route_id(route_id);
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn denial(client_ip: &str) -> AuditLog {
+        AuditLogBuilder::new(
+            AuditEventType::WhitelistDenied,
+            AuditEventCategory::Whitelist,
+            AuditSeverity::Warning,
+            "denied",
+        )
+        .client_ip(client_ip)
+        .build()
+    }
+
+    fn tracker() -> EscalationTracker {
+        EscalationTracker::new(parse_rules("whitelist_denied:3:60"))
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(
+            parse_rules("whitelist_denied:10:60, rate_limit_exceeded:100:30"),
+            vec![
+                EscalationRule {
+                    event_type: "whitelist_denied".to_string(),
+                    threshold: 10,
+                    window: Duration::from_secs(60),
+                },
+                EscalationRule {
+                    event_type: "rate_limit_exceeded".to_string(),
+                    threshold: 100,
+                    window: Duration::from_secs(30),
+                },
+            ]
+        );
+        assert!(parse_rules("").is_empty());
+        assert!(parse_rules("whitelist_denied:0:60,bogus,x:1").is_empty());
+    }
+
+    #[test]
+    fn test_escalates_once_threshold_is_crossed() {
+        let mut tracker = tracker();
+        let now = Instant::now();
+
+        for i in 0..3 {
+            let at = now + Duration::from_secs(i);
+            assert!(tracker.record(&denial("192.0.2.1"), at).is_empty());
+        }
+
+        let escalations = tracker.record(&denial("192.0.2.1"), now + Duration::from_secs(3));
+        assert_eq!(escalations.len(), 1);
+        assert_eq!(escalations[0].severity, "critical");
+        assert_eq!(escalations[0].event_type, "security_alert");
+        assert_eq!(escalations[0].client_ip.as_deref(), Some("192.0.2.1"));
+
+        // The counter starts over after escalating
+        assert!(tracker
+            .record(&denial("192.0.2.1"), now + Duration::from_secs(4))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_events_outside_window_and_other_ips_do_not_count() {
+        let mut tracker = tracker();
+        let now = Instant::now();
+
+        for i in 0..3 {
+            tracker.record(&denial("192.0.2.1"), now + Duration::from_secs(i * 30));
+        }
+        // Only the last two denials are still inside the 60s window
+        assert!(tracker
+            .record(&denial("192.0.2.1"), now + Duration::from_secs(91))
+            .is_empty());
+
+        for _ in 0..3 {
+            tracker.record(&denial("198.51.100.7"), now);
+        }
+        assert!(tracker.record(&denial("203.0.113.9"), now).is_empty());
+    }
+}
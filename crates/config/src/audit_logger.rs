@@ -1,45 +1,165 @@
-use karateway_core::models::{AuditLog, AuditLogs};
+use crate::audit_categories::DisabledCategories;
+use crate::audit_escalation::{EscalationRule, EscalationTracker};
+use crate::audit_sampling::{AuditSampler, SamplingRule};
+use crate::audit_webhook::{WebhookConfig, WebhookSink};
+use karateway_core::models::{AuditEventCategory, AuditLog, AuditLogs};
 use sea_query::{PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
 use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tracing::{error, info};
 
+/// How often windows closed by sampling are checked for a pending summary
+const SAMPLING_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Audit logger service that handles async logging to database
 #[derive(Clone)]
 pub struct AuditLogger {
     tx: mpsc::UnboundedSender<AuditLog>,
+    sampler: Option<Arc<AuditSampler>>,
+    disabled: Arc<DisabledCategories>,
 }
 
 impl AuditLogger {
     /// Create a new audit logger with a background worker
     pub fn new(pool: PgPool) -> Self {
+        Self::with_escalation_rules(pool, Vec::new())
+    }
+
+    /// Create an audit logger that also raises Critical events for bursts matching `rules`
+    pub fn with_escalation_rules(pool: PgPool, rules: Vec<EscalationRule>) -> Self {
+        Self::with_webhook(pool, rules, None)
+    }
+
+    /// Create an audit logger that also forwards every event to `webhook`, when set
+    pub fn with_webhook(
+        pool: PgPool,
+        rules: Vec<EscalationRule>,
+        webhook: Option<WebhookConfig>,
+    ) -> Self {
         let (tx, rx) = mpsc::unbounded_channel();
+        let sink = webhook.map(|config| {
+            info!("Forwarding audit events to {}", config.url);
+            WebhookSink::spawn(config)
+        });
 
         // Spawn background worker to process audit logs
-        tokio::spawn(audit_log_worker(pool, rx));
+        tokio::spawn(audit_log_worker(
+            pool,
+            rx,
+            EscalationTracker::new(rules),
+            sink,
+        ));
+
+        Self {
+            tx,
+            sampler: None,
+            disabled: Arc::default(),
+        }
+    }
+
+    /// Cap noisy event types per client IP according to `rules`
+    ///
+    /// Suppressed events are never queued, so they don't count towards
+    /// escalation either; keep each sampling budget above the matching
+    /// escalation threshold. No-op when `rules` is empty.
+    pub fn with_sampling(mut self, rules: Vec<SamplingRule>) -> Self {
+        if rules.is_empty() {
+            return self;
+        }
 
-        Self { tx }
+        let sampler = Arc::new(AuditSampler::new(rules));
+        tokio::spawn(sampling_flush_task(sampler.clone(), self.tx.downgrade()));
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Drop events of the `disabled` categories instead of recording them
+    ///
+    /// Dropped events are never queued, so they don't reach sampling,
+    /// escalation or the webhook either.
+    pub fn with_disabled_categories(mut self, disabled: DisabledCategories) -> Self {
+        self.disabled = Arc::new(disabled);
+        self
+    }
+
+    /// Whether events of `category` are recorded, so callers can skip building them
+    pub fn is_enabled(&self, category: &AuditEventCategory) -> bool {
+        self.disabled.is_enabled(&category.to_string())
     }
 
     /// Log an audit event (non-blocking)
     pub fn log(&self, audit_log: AuditLog) {
+        if !self.disabled.is_enabled(&audit_log.event_category) {
+            return;
+        }
+
+        if let Some(sampler) = &self.sampler {
+            let sampled = sampler.sample(&audit_log, Instant::now());
+            if let Some(summary) = sampled.summary {
+                self.send(summary);
+            }
+            if !sampled.keep {
+                return;
+            }
+        }
+
+        self.send(audit_log);
+    }
+
+    fn send(&self, audit_log: AuditLog) {
         if let Err(e) = self.tx.send(audit_log) {
             error!("Failed to send audit log to worker: {}", e);
         }
     }
 }
 
-/// Background worker that processes audit logs and writes to database
-async fn audit_log_worker(pool: PgPool, mut rx: mpsc::UnboundedReceiver<AuditLog>) {
+/// Periodically queue summaries for sampling windows that ended without a new event
+///
+/// Holds only a weak sender, so it stops once every logger is gone.
+async fn sampling_flush_task(sampler: Arc<AuditSampler>, tx: mpsc::WeakUnboundedSender<AuditLog>) {
+    let mut tick = tokio::time::interval(SAMPLING_FLUSH_INTERVAL);
+
+    loop {
+        tick.tick().await;
+        let Some(tx) = tx.upgrade() else {
+            break;
+        };
+
+        for summary in sampler.flush(Instant::now()) {
+            if let Err(e) = tx.send(summary) {
+                error!("Failed to send audit log to worker: {}", e);
+            }
+        }
+    }
+}
+
+/// Background worker that processes audit logs and writes to database (and the webhook, if any)
+async fn audit_log_worker(
+    pool: PgPool,
+    mut rx: mpsc::UnboundedReceiver<AuditLog>,
+    mut tracker: EscalationTracker,
+    sink: Option<WebhookSink>,
+) {
     info!("Audit log worker started");
 
     while let Some(log) = rx.recv().await {
-        if let Err(e) = save_audit_log(&pool, &log).await {
-            error!(
-                "Failed to save audit log to database: {} - Event: {:?}",
-                e, log
-            );
+        let escalations = tracker.record(&log, Instant::now());
+
+        for log in std::iter::once(log).chain(escalations) {
+            // Queued without waiting, so the webhook never delays the database write
+            if let Some(sink) = &sink {
+                sink.forward(&log);
+            }
+
+            if let Err(e) = save_audit_log(&pool, &log).await {
+                error!(
+                    "Failed to save audit log to database: {} - Event: {:?}",
+                    e, log
+                );
+            }
         }
     }
 
@@ -88,3 +208,42 @@ async fn save_audit_log(pool: &PgPool, log: &AuditLog) -> Result<(), sqlx::Error
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use karateway_core::models::{AuditEventType, AuditLogBuilder, AuditSeverity};
+
+    fn event(event_type: AuditEventType, category: AuditEventCategory) -> AuditLog {
+        AuditLogBuilder::new(event_type, category, AuditSeverity::Warning, "test")
+            .client_ip("203.0.113.9")
+            .build()
+    }
+
+    #[test]
+    fn test_disabled_category_is_never_queued() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let logger = AuditLogger {
+            tx,
+            sampler: None,
+            disabled: Arc::default(),
+        }
+        .with_disabled_categories(DisabledCategories::parse("rate_limit"));
+
+        assert!(!logger.is_enabled(&AuditEventCategory::RateLimit));
+        assert!(logger.is_enabled(&AuditEventCategory::Whitelist));
+
+        logger.log(event(
+            AuditEventType::RateLimitExceeded,
+            AuditEventCategory::RateLimit,
+        ));
+        logger.log(event(
+            AuditEventType::WhitelistDenied,
+            AuditEventCategory::Whitelist,
+        ));
+
+        // Only the whitelist denial reaches the worker that writes rows
+        assert_eq!(rx.try_recv().unwrap().event_category, "whitelist");
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use chrono::Utc;
+use karateway_core::models::AuditLog;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Upper bound on the number of (rule, client IP) pairs tracked at once
+///
+/// Once full, pairs whose window has passed are dropped first; if that frees
+/// nothing, events from new client IPs share a single overflow budget per
+/// rule, so a flood spread over many addresses is still capped.
+pub const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Stand-in client IP for events counted against the overflow budget
+const OVERFLOW_KEY: &str = "*";
+
+/// Keep at most `max_events` events of `event_type` per client IP within `window`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SamplingRule {
+    pub event_type: String,
+    pub max_events: u32,
+    pub window: Duration,
+}
+
+impl std::str::FromStr for SamplingRule {
+    type Err = String;
+
+    /// Parse `event_type:max_events:window_seconds`, e.g. `whitelist_denied:20:60`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid audit sampling rule: {}", s);
+
+        let mut parts = s.trim().splitn(3, ':');
+        let event_type = parts.next().filter(|p| !p.is_empty()).ok_or_else(invalid)?;
+        let max_events = parts
+            .next()
+            .and_then(|p| p.parse::<u32>().ok())
+            .filter(|m| *m > 0)
+            .ok_or_else(invalid)?;
+        let window_seconds = parts
+            .next()
+            .and_then(|p| p.parse::<u64>().ok())
+            .filter(|w| *w > 0)
+            .ok_or_else(invalid)?;
+
+        Ok(Self {
+            event_type: event_type.to_string(),
+            max_events,
+            window: Duration::from_secs(window_seconds),
+        })
+    }
+}
+
+/// Parse a comma-separated list of sampling rules
+///
+/// Invalid entries are logged and skipped.
+pub fn parse_rules(value: &str) -> Vec<SamplingRule> {
+    value
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .filter_map(|s| match s.parse() {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// What to do with one audit event
+#[derive(Debug)]
+pub struct Sampled {
+    /// Whether the event itself should be logged
+    pub keep: bool,
+    /// Summary of a finished window that had suppressed events
+    pub summary: Option<AuditLog>,
+}
+
+struct Window {
+    started: Instant,
+    logged: u32,
+    suppressed: u64,
+    /// The most recent suppressed event, the template for the summary
+    last_suppressed: Option<AuditLog>,
+}
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            logged: 0,
+            suppressed: 0,
+            last_suppressed: None,
+        }
+    }
+}
+
+/// Fixed-window sampler capping noisy audit events per client IP
+///
+/// Events over a rule's budget are counted instead of logged. When the window
+/// ends, a single summary event carrying the suppressed count is logged in
+/// their place, so a sustained attack stays visible without flooding the log.
+pub struct AuditSampler {
+    rules: Vec<SamplingRule>,
+    /// Window state per (rule index, client IP)
+    windows: Mutex<HashMap<(usize, String), Window>>,
+}
+
+impl AuditSampler {
+    pub fn new(rules: Vec<SamplingRule>) -> Self {
+        Self {
+            rules,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Decide whether `log` is kept, closing its window first if it has ended
+    pub fn sample(&self, log: &AuditLog, now: Instant) -> Sampled {
+        let Some(index) = self
+            .rules
+            .iter()
+            .position(|rule| rule.event_type == log.event_type)
+        else {
+            return Sampled {
+                keep: true,
+                summary: None,
+            };
+        };
+        let rule = &self.rules[index];
+        let client_ip = log.client_ip.as_deref().unwrap_or("unknown");
+
+        let mut windows = self.windows.lock().unwrap();
+        let mut key = (index, client_ip.to_string());
+        if !windows.contains_key(&key) && windows.len() >= MAX_TRACKED_KEYS {
+            self.prune(&mut windows, now);
+            if windows.len() >= MAX_TRACKED_KEYS {
+                key.1 = OVERFLOW_KEY.to_string();
+            }
+        }
+
+        let overflow = key.1 == OVERFLOW_KEY;
+        let window = windows.entry(key).or_insert_with(|| Window::new(now));
+        let mut summary = None;
+        if now.saturating_duration_since(window.started) >= rule.window {
+            summary = summary_event(rule, window, overflow);
+            *window = Window::new(now);
+        }
+
+        let keep = window.logged < rule.max_events;
+        if keep {
+            window.logged += 1;
+        } else {
+            window.suppressed += 1;
+            window.last_suppressed = Some(log.clone());
+        }
+
+        Sampled { keep, summary }
+    }
+
+    /// Close every window that has ended, returning summaries for those with suppressed events
+    ///
+    /// Called periodically so the summary is logged even when the client has
+    /// gone quiet and no further event would close its window.
+    pub fn flush(&self, now: Instant) -> Vec<AuditLog> {
+        let mut windows = self.windows.lock().unwrap();
+        let mut summaries = Vec::new();
+
+        windows.retain(|(index, client_ip), window| {
+            let rule = &self.rules[*index];
+            if now.saturating_duration_since(window.started) < rule.window {
+                return true;
+            }
+            summaries.extend(summary_event(rule, window, client_ip == OVERFLOW_KEY));
+            false
+        });
+
+        summaries
+    }
+
+    /// Drop windows that have ended without suppressing anything
+    fn prune(&self, windows: &mut HashMap<(usize, String), Window>, now: Instant) {
+        windows.retain(|(index, _), window| {
+            window.suppressed > 0
+                || now.saturating_duration_since(window.started) < self.rules[*index].window
+        });
+    }
+}
+
+/// Summary event for a window, `None` when nothing was suppressed in it
+///
+/// An `overflow` window mixes many client IPs, so the summary names none of them.
+fn summary_event(rule: &SamplingRule, window: &Window, overflow: bool) -> Option<AuditLog> {
+    let mut summary = window.last_suppressed.clone()?;
+    if overflow {
+        summary.client_ip = None;
+    }
+    let source = match &summary.client_ip {
+        Some(client_ip) => client_ip.clone(),
+        None if overflow => "untracked client IPs".to_string(),
+        None => "unknown".to_string(),
+    };
+
+    summary.id = Uuid::new_v4();
+    summary.created_at = Utc::now();
+    summary.message = format!(
+        "{} {} events from {} suppressed within {}s",
+        window.suppressed,
+        rule.event_type,
+        source,
+        rule.window.as_secs()
+    );
+    summary.metadata = serde_json::json!({
+        "sampled": true,
+        "suppressed": window.suppressed,
+        "logged": window.logged,
+        "max_events": rule.max_events,
+        "window_seconds": rule.window.as_secs(),
+        "last_message": window.last_suppressed.as_ref().map(|log| log.message.clone()),
+    });
+
+    Some(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use karateway_core::models::{
+        AuditEventCategory, AuditEventType, AuditLogBuilder, AuditSeverity,
+    };
+
+    fn denial(client_ip: &str) -> AuditLog {
+        AuditLogBuilder::new(
+            AuditEventType::WhitelistDenied,
+            AuditEventCategory::Whitelist,
+            AuditSeverity::Warning,
+            "denied",
+        )
+        .client_ip(client_ip)
+        .build()
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        assert_eq!(
+            parse_rules("whitelist_denied:20:60"),
+            vec![SamplingRule {
+                event_type: "whitelist_denied".to_string(),
+                max_events: 20,
+                window: Duration::from_secs(60),
+            }]
+        );
+        assert!(parse_rules("").is_empty());
+        assert!(parse_rules("whitelist_denied:0:60,bogus,x:1:0").is_empty());
+    }
+
+    #[test]
+    fn test_events_over_budget_are_suppressed_and_summarised() {
+        let sampler = AuditSampler::new(parse_rules("whitelist_denied:2:60"));
+        let now = Instant::now();
+
+        let kept: Vec<bool> = (0..5)
+            .map(|_| sampler.sample(&denial("192.0.2.1"), now).keep)
+            .collect();
+        assert_eq!(kept, vec![true, true, false, false, false]);
+
+        // Other IPs and other event types are unaffected
+        assert!(sampler.sample(&denial("198.51.100.7"), now).keep);
+        let mut other = denial("192.0.2.1");
+        other.event_type = "rate_limit_exceeded".to_string();
+        assert!(sampler.sample(&other, now).keep);
+
+        // The next event after the window closes it with a summary
+        let sampled = sampler.sample(&denial("192.0.2.1"), now + Duration::from_secs(60));
+        assert!(sampled.keep);
+        let summary = sampled.summary.unwrap();
+        assert_eq!(summary.event_type, "whitelist_denied");
+        assert_eq!(summary.client_ip.as_deref(), Some("192.0.2.1"));
+        assert_eq!(summary.metadata["suppressed"], 3);
+    }
+
+    #[test]
+    fn test_flush_summarises_quiet_clients() {
+        let sampler = AuditSampler::new(parse_rules("whitelist_denied:1:60"));
+        let now = Instant::now();
+
+        for _ in 0..4 {
+            sampler.sample(&denial("192.0.2.1"), now);
+        }
+        sampler.sample(&denial("198.51.100.7"), now);
+
+        assert!(sampler.flush(now + Duration::from_secs(30)).is_empty());
+
+        // Only the client with suppressed events gets a summary
+        let summaries = sampler.flush(now + Duration::from_secs(60));
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].client_ip.as_deref(), Some("192.0.2.1"));
+        assert_eq!(summaries[0].metadata["suppressed"], 3);
+        assert!(sampler.flush(now + Duration::from_secs(120)).is_empty());
+    }
+}
@@ -0,0 +1,253 @@
+use karateway_core::models::AuditLog;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{error, info, warn};
+
+/// Audit events waiting for delivery before new ones are dropped
+pub const QUEUE_CAPACITY: usize = 10_000;
+
+/// Timeout for a single webhook delivery attempt
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest wait between two delivery attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Where and how audit events are forwarded, e.g. to a SIEM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Most events sent in one POST
+    pub batch_size: usize,
+    /// How long a partial batch waits for more events before it's sent
+    pub flush_interval: Duration,
+    /// Attempts after the first before a batch is dropped
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled on each following one
+    pub retry_backoff: Duration,
+}
+
+/// Wait before retry number `attempt` (starting at 0)
+pub fn backoff(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF)
+}
+
+/// Forwards audit events to a webhook as JSON arrays, off the database write path
+///
+/// Events are queued in a bounded channel and delivered by a background
+/// worker, so a slow or unreachable webhook never holds up persisting them;
+/// when the queue is full new events are dropped for the webhook only.
+pub struct WebhookSink {
+    tx: mpsc::Sender<AuditLog>,
+}
+
+impl WebhookSink {
+    /// Start the delivery worker
+    pub fn spawn(config: WebhookConfig) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+
+        tokio::spawn(webhook_worker(config, rx));
+
+        Self { tx }
+    }
+
+    /// Queue an event for delivery (non-blocking)
+    pub fn forward(&self, log: &AuditLog) {
+        match self.tx.try_send(log.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(log)) => {
+                warn!("Audit webhook queue full, dropping event {}", log.id)
+            }
+            Err(TrySendError::Closed(_)) => error!("Audit webhook worker is not running"),
+        }
+    }
+}
+
+/// Background worker that batches queued events and POSTs them
+async fn webhook_worker(config: WebhookConfig, mut rx: mpsc::Receiver<AuditLog>) {
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to create audit webhook client: {}", e);
+            return;
+        }
+    };
+
+    info!("Audit webhook worker started");
+
+    let batch_size = config.batch_size.max(1);
+    let mut batch = Vec::with_capacity(batch_size);
+
+    while let Some(log) = rx.recv().await {
+        batch.push(log);
+
+        // Fill the batch until it's full or the flush interval is up
+        let deadline = tokio::time::Instant::now() + config.flush_interval;
+        while batch.len() < batch_size {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(log)) => batch.push(log),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        deliver(&client, &config, &batch).await;
+        batch.clear();
+    }
+
+    info!("Audit webhook worker stopped");
+}
+
+/// POST one batch, retrying with exponential backoff; gives up after `max_retries`
+async fn deliver(client: &reqwest::Client, config: &WebhookConfig, batch: &[AuditLog]) {
+    for attempt in 0..=config.max_retries {
+        if attempt > 0 {
+            tokio::time::sleep(backoff(config.retry_backoff, attempt - 1)).await;
+        }
+
+        match client.post(&config.url).json(batch).send().await {
+            Ok(response) if response.status().is_success() => return,
+            Ok(response) => warn!(
+                "Audit webhook answered {} (attempt {}/{})",
+                response.status(),
+                attempt + 1,
+                config.max_retries + 1
+            ),
+            Err(e) => warn!(
+                "Audit webhook request failed: {} (attempt {}/{})",
+                e,
+                attempt + 1,
+                config.max_retries + 1
+            ),
+        }
+    }
+
+    error!(
+        "Dropping {} audit events after {} failed webhook attempts",
+        batch.len(),
+        config.max_retries + 1
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use karateway_core::models::{
+        AuditEventCategory, AuditEventType, AuditLogBuilder, AuditSeverity,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    fn event() -> AuditLog {
+        AuditLogBuilder::new(
+            AuditEventType::WhitelistDenied,
+            AuditEventCategory::Whitelist,
+            AuditSeverity::Warning,
+            "denied",
+        )
+        .client_ip("192.0.2.1")
+        .build()
+    }
+
+    fn config(url: String) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            batch_size: 2,
+            flush_interval: Duration::from_millis(50),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(10),
+        }
+    }
+
+    /// Minimal webhook answering with `statuses` in turn (200 once they run out)
+    ///
+    /// Every delivery it receives is reported as (status answered, event ids).
+    async fn webhook(statuses: Vec<u16>) -> (String, mpsc::UnboundedReceiver<(u16, Vec<Uuid>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/audit", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+
+                // Read the head, then as much body as Content-Length says
+                let body_start = loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break pos + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&request[..body_start]).to_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map(|value| value.trim().parse().unwrap())
+                    .unwrap_or(0);
+                while request.len() < body_start + length {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+
+                let logs: Vec<AuditLog> = serde_json::from_slice(&request[body_start..]).unwrap();
+                let status = statuses.next().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {} Webhook\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+
+                tx.send((status, logs.into_iter().map(|log| log.id).collect()))
+                    .unwrap();
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[tokio::test]
+    async fn test_events_reach_webhook_in_batches() {
+        let (url, mut deliveries) = webhook(Vec::new()).await;
+        let sink = WebhookSink::spawn(config(url));
+
+        let events: Vec<AuditLog> = (0..3).map(|_| event()).collect();
+        for event in &events {
+            sink.forward(event);
+        }
+
+        // A full batch, then the remainder once the flush interval is up
+        let (status, first) = deliveries.recv().await.unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(first, vec![events[0].id, events[1].id]);
+        let (_, second) = deliveries.recv().await.unwrap();
+        assert_eq!(second, vec![events[2].id]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried() {
+        let (url, mut deliveries) = webhook(vec![500, 503]).await;
+        let sink = WebhookSink::spawn(config(url));
+
+        let event = event();
+        sink.forward(&event);
+
+        for expected in [500, 503, 200] {
+            let (status, ids) = deliveries.recv().await.unwrap();
+            assert_eq!(status, expected);
+            assert_eq!(ids, vec![event.id]);
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_cap() {
+        let base = Duration::from_millis(500);
+        assert_eq!(backoff(base, 0), Duration::from_millis(500));
+        assert_eq!(backoff(base, 1), Duration::from_secs(1));
+        assert_eq!(backoff(base, 3), Duration::from_secs(4));
+        assert_eq!(backoff(base, 40), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionLike;
+use redis::RedisResult;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Prefix of the Redis keys holding each gateway's latest circuit breaker report
+pub const CIRCUIT_BREAKERS_KEY_PREFIX: &str = "services:circuit-breakers:";
+
+/// How many keys one `SCAN` step looks at while listing the reports
+const SCAN_COUNT: usize = 100;
+
+fn report_key(gateway_id: &str) -> String {
+    format!("{}{}", CIRCUIT_BREAKERS_KEY_PREFIX, gateway_id)
+}
+
+/// State of the breaker guarding one backend instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// Receiving traffic
+    Closed,
+    /// Ejected for failing requests, skipped until `retry_at`
+    Open,
+    /// The ejection has passed; the next request lets the instance back in
+    HalfOpen,
+}
+
+/// One instance's breaker as the gateway saw it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreakerSnapshot {
+    pub service_id: Uuid,
+    pub host: String,
+    pub port: u16,
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    /// Requests and failures in the current error rate window
+    pub window_requests: u32,
+    pub window_failures: u32,
+    /// When an open breaker lets traffic through again
+    pub retry_at: Option<DateTime<Utc>>,
+}
+
+/// Every breaker of a gateway at `reported_at`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreakerReport {
+    /// The reporting gateway process, so several gateways don't overwrite each other
+    pub gateway_id: String,
+    pub reported_at: DateTime<Utc>,
+    pub breakers: Vec<BreakerSnapshot>,
+}
+
+/// Replace the gateway's stored report with its current one
+///
+/// Each gateway has its own key, which expires after `ttl_seconds` so a
+/// gateway that stops reporting doesn't leave its breakers behind.
+pub async fn store<C: ConnectionLike + Send>(
+    conn: &mut C,
+    report: &BreakerReport,
+    ttl_seconds: u64,
+) -> RedisResult<()> {
+    let payload = serde_json::to_string(report).expect("breaker report serializes to JSON");
+    redis::cmd("SET")
+        .arg(report_key(&report.gateway_id))
+        .arg(payload)
+        .arg("EX")
+        .arg(ttl_seconds.max(1))
+        .query_async::<()>(conn)
+        .await
+}
+
+/// The stored report of every gateway that reported recently
+///
+/// Unreadable reports, and ones expiring between the scan and the read, are
+/// left out.
+pub async fn load_all<C: ConnectionLike + Send>(conn: &mut C) -> RedisResult<Vec<BreakerReport>> {
+    let mut keys = Vec::new();
+    let mut cursor = 0u64;
+    loop {
+        let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(format!("{}*", CIRCUIT_BREAKERS_KEY_PREFIX))
+            .arg("COUNT")
+            .arg(SCAN_COUNT)
+            .query_async(conn)
+            .await?;
+        keys.extend(batch);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    // SCAN may return a key more than once
+    keys.sort();
+    keys.dedup();
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let payloads: Vec<Option<String>> = redis::cmd("MGET").arg(&keys).query_async(conn).await?;
+    Ok(payloads
+        .into_iter()
+        .flatten()
+        .filter_map(|payload| serde_json::from_str(&payload).ok())
+        .collect())
+}
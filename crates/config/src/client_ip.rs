@@ -0,0 +1,315 @@
+use http::HeaderMap;
+use std::net::IpAddr;
+use tracing::warn;
+
+/// A place the client IP may be read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientIpSource {
+    /// RFC 7239 `Forwarded` header (`for=` parameter)
+    Forwarded,
+    /// De-facto `X-Forwarded-For` header
+    XForwardedFor,
+    /// Socket peer address of the downstream connection
+    Peer,
+}
+
+impl std::str::FromStr for ClientIpSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "forwarded" => Ok(ClientIpSource::Forwarded),
+            "x-forwarded-for" | "xff" => Ok(ClientIpSource::XForwardedFor),
+            "peer" => Ok(ClientIpSource::Peer),
+            other => Err(format!("Invalid client IP source: {}", other)),
+        }
+    }
+}
+
+/// Precedence used when no sources are configured
+pub const DEFAULT_SOURCES: [ClientIpSource; 3] = [
+    ClientIpSource::XForwardedFor,
+    ClientIpSource::Forwarded,
+    ClientIpSource::Peer,
+];
+
+/// Parse a comma-separated precedence list such as `forwarded,x-forwarded-for,peer`
+///
+/// Unknown entries are logged and skipped; an empty result falls back to [`DEFAULT_SOURCES`].
+pub fn parse_sources(value: &str) -> Vec<ClientIpSource> {
+    let sources: Vec<ClientIpSource> = value
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .filter_map(|s| match s.parse() {
+            Ok(source) => Some(source),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        })
+        .collect();
+
+    if sources.is_empty() {
+        DEFAULT_SOURCES.to_vec()
+    } else {
+        sources
+    }
+}
+
+/// Resolve the client IP by walking the configured sources in order
+pub fn resolve(
+    headers: &HeaderMap,
+    peer_ip: Option<String>,
+    sources: &[ClientIpSource],
+) -> Option<String> {
+    for source in sources {
+        let ip = match source {
+            ClientIpSource::Forwarded => headers
+                .get_all("Forwarded")
+                .iter()
+                .filter_map(|h| h.to_str().ok())
+                .find_map(parse_forwarded_for),
+            ClientIpSource::XForwardedFor => headers
+                .get("X-Forwarded-For")
+                .and_then(|h| h.to_str().ok())
+                .and_then(parse_x_forwarded_for),
+            ClientIpSource::Peer => peer_ip.clone(),
+        };
+
+        if ip.is_some() {
+            return ip;
+        }
+    }
+
+    None
+}
+
+/// Extract the client address from the first usable `for=` of a `Forwarded` header
+///
+/// Handles quoted values, bracketed IPv6 (`for="[2001:db8::1]:4711"`) and
+/// IPv4 with a port. Obfuscated identifiers (`_hidden`) and `unknown` are
+/// skipped in favour of the next element.
+pub fn parse_forwarded_for(value: &str) -> Option<String> {
+    split_unquoted(value, ',').into_iter().find_map(|element| {
+        split_unquoted(element, ';').into_iter().find_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            if !key.trim().eq_ignore_ascii_case("for") {
+                return None;
+            }
+            parse_node(value.trim())
+        })
+    })
+}
+
+/// Extract the client address from the first valid entry of an `X-Forwarded-For` header
+///
+/// Entries that aren't an IP address, e.g. `unknown` or a value injected by
+/// the client, are skipped in favour of the next one, so they never end up
+/// as a rate limit key or whitelist subject. Ports and bracketed IPv6 are
+/// accepted as some proxies append them.
+pub fn parse_x_forwarded_for(value: &str) -> Option<String> {
+    value.split(',').find_map(|entry| parse_node(entry.trim()))
+}
+
+/// Parse a `Forwarded` node value into a bare IP address
+fn parse_node(value: &str) -> Option<String> {
+    let value = value.trim_matches('"');
+
+    let host = if let Some(rest) = value.strip_prefix('[') {
+        // "[v6]" or "[v6]:port"
+        rest.split(']').next()?
+    } else if value.matches(':').count() == 1 {
+        // "v4:port"
+        value.split(':').next()?
+    } else {
+        value
+    };
+
+    host.parse::<IpAddr>().ok().map(|ip| ip.to_string())
+}
+
+/// Split on `delimiter`, ignoring delimiters inside quoted strings
+fn split_unquoted(value: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == delimiter && !in_quotes {
+            parts.push(value[start..i].trim());
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(value[start..].trim());
+
+    parts
+}
+
+/// Build a `Forwarded` element describing this hop
+///
+/// `client_ip` is the node this hop received the request from, i.e. the
+/// connection's peer, not an address a client claimed in a header.
+pub fn forwarded_element(client_ip: Option<&str>, proto: &str, host: Option<&str>) -> String {
+    let node = match client_ip.map(|ip| ip.parse::<IpAddr>()) {
+        Some(Ok(IpAddr::V6(ip))) => format!("\"[{}]\"", ip),
+        Some(Ok(IpAddr::V4(ip))) => ip.to_string(),
+        _ => "unknown".to_string(),
+    };
+
+    let mut element = format!("for={};proto={}", node, proto);
+    if let Some(host) = host {
+        element.push_str(&format!(";host=\"{}\"", host.replace('"', "")));
+    }
+    element
+}
+
+/// The `Forwarded` value to send on: every element received, in order, then `element`
+///
+/// A request may carry the header on several lines; all of them are kept so
+/// no earlier hop is lost.
+pub fn append_forwarded(headers: &HeaderMap, element: &str) -> String {
+    let mut elements: Vec<&str> = headers
+        .get_all("Forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .collect();
+    elements.push(element);
+    elements.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_forwarded_for() {
+        assert_eq!(
+            parse_forwarded_for("for=192.0.2.60;proto=http;by=203.0.113.43"),
+            Some("192.0.2.60".to_string())
+        );
+        assert_eq!(
+            parse_forwarded_for("For=\"192.0.2.60:8080\""),
+            Some("192.0.2.60".to_string())
+        );
+        assert_eq!(
+            parse_forwarded_for("for=\"[2001:db8:cafe::17]:4711\""),
+            Some("2001:db8:cafe::17".to_string())
+        );
+        assert_eq!(
+            parse_forwarded_for("for=\"[2001:db8:cafe::17]\""),
+            Some("2001:db8:cafe::17".to_string())
+        );
+        assert_eq!(
+            parse_forwarded_for("for=unknown, for=198.51.100.17"),
+            Some("198.51.100.17".to_string())
+        );
+        assert_eq!(
+            parse_forwarded_for("proto=https;host=\"a,b\";for=_hidden"),
+            None
+        );
+        assert_eq!(parse_forwarded_for("by=203.0.113.43"), None);
+    }
+
+    #[test]
+    fn test_resolve_respects_precedence() {
+        let mut headers = HeaderMap::new();
+        headers.insert("Forwarded", "for=192.0.2.60".parse().unwrap());
+        headers.insert("X-Forwarded-For", "198.51.100.1, 10.0.0.1".parse().unwrap());
+        let peer = Some("10.0.0.2".to_string());
+
+        let forwarded_first = parse_sources("forwarded,x-forwarded-for,peer");
+        assert_eq!(
+            resolve(&headers, peer.clone(), &forwarded_first),
+            Some("192.0.2.60".to_string())
+        );
+
+        assert_eq!(
+            resolve(&headers, peer.clone(), &DEFAULT_SOURCES),
+            Some("198.51.100.1".to_string())
+        );
+
+        assert_eq!(
+            resolve(&HeaderMap::new(), peer.clone(), &forwarded_first),
+            peer
+        );
+    }
+
+    #[test]
+    fn test_garbage_x_forwarded_for_entries_are_skipped() {
+        assert_eq!(
+            parse_x_forwarded_for("not-an-ip, 198.51.100.1, 10.0.0.1"),
+            Some("198.51.100.1".to_string())
+        );
+        assert_eq!(
+            parse_x_forwarded_for("unknown,, [2001:db8::1]:4711"),
+            Some("2001:db8::1".to_string())
+        );
+        assert_eq!(
+            parse_x_forwarded_for("198.51.100.1:8080"),
+            Some("198.51.100.1".to_string())
+        );
+
+        // An all-garbage chain falls through to the next source
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Forwarded-For",
+            "evil' OR 1=1, unknown, 999.1.1.1".parse().unwrap(),
+        );
+        assert_eq!(
+            parse_x_forwarded_for("evil' OR 1=1, unknown, 999.1.1.1"),
+            None
+        );
+        assert_eq!(
+            resolve(&headers, Some("10.0.0.2".to_string()), &DEFAULT_SOURCES),
+            Some("10.0.0.2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_sources_falls_back_to_default() {
+        assert_eq!(parse_sources(""), DEFAULT_SOURCES.to_vec());
+        assert_eq!(parse_sources("bogus"), DEFAULT_SOURCES.to_vec());
+        assert_eq!(parse_sources("peer"), vec![ClientIpSource::Peer]);
+    }
+
+    #[test]
+    fn test_forwarded_element() {
+        assert_eq!(
+            forwarded_element(Some("192.0.2.60"), "http", Some("example.com")),
+            "for=192.0.2.60;proto=http;host=\"example.com\""
+        );
+        assert_eq!(
+            forwarded_element(Some("2001:db8::1"), "https", None),
+            "for=\"[2001:db8::1]\";proto=https"
+        );
+        assert_eq!(
+            forwarded_element(None, "http", None),
+            "for=unknown;proto=http"
+        );
+    }
+
+    #[test]
+    fn test_every_forwarded_line_is_kept() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            "Forwarded",
+            "for=192.0.2.60;proto=https, for=198.51.100.17"
+                .parse()
+                .unwrap(),
+        );
+        headers.append("Forwarded", "for=203.0.113.43".parse().unwrap());
+
+        assert_eq!(
+            append_forwarded(&headers, "for=10.0.0.2;proto=http"),
+            "for=192.0.2.60;proto=https, for=198.51.100.17, for=203.0.113.43, for=10.0.0.2;proto=http"
+        );
+        assert_eq!(
+            append_forwarded(&HeaderMap::new(), "for=10.0.0.2;proto=http"),
+            "for=10.0.0.2;proto=http"
+        );
+    }
+}
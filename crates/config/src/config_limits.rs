@@ -0,0 +1,188 @@
+use crate::AppConfig;
+use karateway_core::{KaratewayError, Result};
+use serde::Serialize;
+use tracing::warn;
+
+/// A kind of config object the limits apply to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigKind {
+    Routes,
+    Services,
+    /// Whitelist rules and rate limits together
+    Rules,
+}
+
+impl std::fmt::Display for ConfigKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConfigKind::Routes => "routes",
+            ConfigKind::Services => "services",
+            ConfigKind::Rules => "rules",
+        })
+    }
+}
+
+/// Soft and hard caps on the number of active objects of one kind, `None` for no cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limit {
+    /// Exceeding it is only logged, and shown in `/health` when enabled
+    pub soft: Option<u64>,
+    /// Creating objects beyond it is rejected
+    pub hard: Option<u64>,
+}
+
+impl Limit {
+    fn new(soft: u64, hard: u64) -> Self {
+        Self {
+            soft: Some(soft).filter(|soft| *soft > 0),
+            hard: Some(hard).filter(|hard| *hard > 0),
+        }
+    }
+}
+
+/// Number of active objects of each kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfigCounts {
+    pub routes: u64,
+    pub services: u64,
+    pub rules: u64,
+}
+
+/// A soft limit the active config is over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LimitExceeded {
+    pub kind: ConfigKind,
+    pub count: u64,
+    pub soft_limit: u64,
+}
+
+/// Guardrails against config sprawl
+///
+/// Every route is checked against each request in turn, so soft limits let
+/// operators notice a config growing past what they sized matching for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfigLimits {
+    pub routes: Limit,
+    pub services: Limit,
+    pub rules: Limit,
+    /// Whether the admin API's `/health` lists exceeded soft limits
+    pub report_in_health: bool,
+}
+
+impl ConfigLimits {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            routes: Limit::new(config.config_max_routes_soft, config.config_max_routes_hard),
+            services: Limit::new(
+                config.config_max_services_soft,
+                config.config_max_services_hard,
+            ),
+            rules: Limit::new(config.config_max_rules_soft, config.config_max_rules_hard),
+            report_in_health: config.health_report_config_limits,
+        }
+    }
+
+    pub fn limit(&self, kind: ConfigKind) -> Limit {
+        match kind {
+            ConfigKind::Routes => self.routes,
+            ConfigKind::Services => self.services,
+            ConfigKind::Rules => self.rules,
+        }
+    }
+
+    /// The soft limits `counts` is over
+    pub fn exceeded(&self, counts: ConfigCounts) -> Vec<LimitExceeded> {
+        [
+            (ConfigKind::Routes, counts.routes),
+            (ConfigKind::Services, counts.services),
+            (ConfigKind::Rules, counts.rules),
+        ]
+        .into_iter()
+        .filter_map(|(kind, count)| {
+            let soft_limit = self.limit(kind).soft?;
+            (count > soft_limit).then_some(LimitExceeded {
+                kind,
+                count,
+                soft_limit,
+            })
+        })
+        .collect()
+    }
+
+    /// Log a warning for each soft limit `counts` is over, returning them
+    pub fn warn_exceeded(&self, counts: ConfigCounts) -> Vec<LimitExceeded> {
+        let exceeded = self.exceeded(counts);
+        for limit in &exceeded {
+            warn!(
+                "{} active {} exceed the soft limit of {}",
+                limit.count, limit.kind, limit.soft_limit
+            );
+        }
+        exceeded
+    }
+
+    /// Reject creating another object of `kind` when `active` already reaches its hard limit
+    pub fn check_create(&self, kind: ConfigKind, active: u64) -> Result<()> {
+        match self.limit(kind).hard {
+            Some(hard) if active >= hard => Err(KaratewayError::Conflict(format!(
+                "Hard limit of {} active {} reached",
+                hard, kind
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> ConfigLimits {
+        ConfigLimits {
+            routes: Limit::new(100, 0),
+            services: Limit::new(0, 20),
+            rules: Limit::new(50, 80),
+            report_in_health: false,
+        }
+    }
+
+    #[test]
+    fn test_warning_fires_past_the_soft_limit() {
+        let limits = limits();
+
+        let at_limit = ConfigCounts {
+            routes: 100,
+            services: 500,
+            rules: 50,
+        };
+        assert!(limits.warn_exceeded(at_limit).is_empty());
+
+        let over = ConfigCounts {
+            routes: 101,
+            ..at_limit
+        };
+        assert_eq!(
+            limits.warn_exceeded(over),
+            vec![LimitExceeded {
+                kind: ConfigKind::Routes,
+                count: 101,
+                soft_limit: 100,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_only_hard_limits_block_creates() {
+        let limits = limits();
+
+        // Soft limits never block
+        assert!(limits.check_create(ConfigKind::Routes, 5000).is_ok());
+        assert!(limits.check_create(ConfigKind::Services, 19).is_ok());
+        assert!(matches!(
+            limits.check_create(ConfigKind::Services, 20),
+            Err(KaratewayError::Conflict(_))
+        ));
+        assert!(limits.check_create(ConfigKind::Rules, 80).is_err());
+    }
+}
@@ -1,9 +1,74 @@
 use sqlx::postgres::{PgPool, PgPoolOptions};
 use sqlx::Error as SqlxError;
 use std::time::Duration;
+use tracing::warn;
 
 use crate::app_config::AppConfig;
 
+/// What a request does when every pooled connection is in use
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PoolExhaustedMode {
+    /// Wait for a connection, up to the acquire timeout
+    #[default]
+    Queue,
+    /// Reject with 503 straight away
+    FailFast,
+}
+
+impl std::str::FromStr for PoolExhaustedMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "queue" => Ok(PoolExhaustedMode::Queue),
+            "fail_fast" | "fail-fast" => Ok(PoolExhaustedMode::FailFast),
+            other => Err(format!(
+                "Invalid DB pool exhausted mode: {} (expected queue or fail_fast)",
+                other
+            )),
+        }
+    }
+}
+
+impl PoolExhaustedMode {
+    /// Parse the configured mode, falling back to `queue` on invalid values
+    pub fn parse_or_default(value: &str) -> Self {
+        value.parse().unwrap_or_else(|e| {
+            warn!("{}, using queue", e);
+            PoolExhaustedMode::default()
+        })
+    }
+}
+
+/// Point-in-time connection counts of a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolUsage {
+    /// Open connections, idle or not
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+impl PoolUsage {
+    pub fn of(pool: &PgPool) -> Self {
+        Self {
+            size: pool.size(),
+            idle: pool.num_idle() as u32,
+            max: pool.options().get_max_connections(),
+        }
+    }
+
+    /// Connections currently checked out
+    pub fn in_use(&self) -> u32 {
+        self.size.saturating_sub(self.idle)
+    }
+
+    /// Whether a new request would have to wait for a connection
+    pub fn is_exhausted(&self) -> bool {
+        self.size >= self.max && self.idle == 0
+    }
+}
+
 pub struct DatabaseConfig {
     config: AppConfig,
 }
@@ -13,13 +78,107 @@ impl DatabaseConfig {
         Self { config }
     }
 
+    /// How long a query waits for a free connection before failing with `PoolTimedOut`
+    ///
+    /// `DB_ACQUIRE_TIMEOUT_MS` when set, otherwise the connect timeout.
+    pub fn acquire_timeout(&self) -> Duration {
+        self.config
+            .db_acquire_timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(Duration::from_secs(self.config.db_connect_timeout_seconds))
+    }
+
     pub async fn create_pool(&self) -> Result<PgPool, SqlxError> {
         PgPoolOptions::new()
             .max_connections(self.config.db_max_connections)
             .min_connections(self.config.db_min_connections)
-            .acquire_timeout(Duration::from_secs(self.config.db_connect_timeout_seconds))
+            .acquire_timeout(self.acquire_timeout())
             .idle_timeout(Duration::from_secs(self.config.db_idle_timeout_seconds))
             .connect(&self.config.database_url())
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use envconfig::Envconfig;
+    use karateway_core::KaratewayError;
+    use std::collections::HashMap;
+    use std::time::Instant;
+    use tokio::net::TcpListener;
+
+    fn config(vars: &[(&str, &str)]) -> AppConfig {
+        let mut env: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        env.insert("DB_PASSWORD".to_string(), "secret".to_string());
+        env.insert("JWT_SECRET".to_string(), "secret".to_string());
+        AppConfig::init_from_hashmap(&env).unwrap()
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!("queue".parse(), Ok(PoolExhaustedMode::Queue));
+        assert_eq!("Fail_Fast".parse(), Ok(PoolExhaustedMode::FailFast));
+        assert_eq!(
+            PoolExhaustedMode::parse_or_default("drop"),
+            PoolExhaustedMode::Queue
+        );
+    }
+
+    #[test]
+    fn test_pool_usage() {
+        let busy = PoolUsage {
+            size: 10,
+            idle: 0,
+            max: 10,
+        };
+        assert!(busy.is_exhausted());
+        assert_eq!(busy.in_use(), 10);
+
+        // An idle connection or room to open one means no wait
+        assert!(!PoolUsage { idle: 1, ..busy }.is_exhausted());
+        assert!(!PoolUsage { size: 9, ..busy }.is_exhausted());
+    }
+
+    #[test]
+    fn test_acquire_timeout_falls_back_to_connect_timeout() {
+        let database = DatabaseConfig::new(config(&[("DB_CONNECT_TIMEOUT_SECONDS", "7")]));
+        assert_eq!(database.acquire_timeout(), Duration::from_secs(7));
+
+        let database = DatabaseConfig::new(config(&[("DB_ACQUIRE_TIMEOUT_MS", "250")]));
+        assert_eq!(database.acquire_timeout(), Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_gives_up_after_timeout() {
+        // Accepts connections but never answers, like a database that's stuck
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port().to_string();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                open.push(socket);
+            }
+        });
+
+        let database = DatabaseConfig::new(config(&[
+            ("DB_HOST", "127.0.0.1"),
+            ("DB_PORT", &port),
+            ("DB_MIN_CONNECTIONS", "0"),
+            ("DB_ACQUIRE_TIMEOUT_MS", "100"),
+        ]));
+
+        let started = Instant::now();
+        let err = database.create_pool().await.unwrap_err();
+        assert!(matches!(err, SqlxError::PoolTimedOut));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Surfaced as a 503 rather than a generic database error
+        let err = KaratewayError::from(err);
+        assert_eq!(err.status_code(), 503);
+        assert_eq!(err.error_code(), "DATABASE_POOL_EXHAUSTED");
+    }
+}
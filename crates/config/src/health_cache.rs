@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use redis::aio::ConnectionLike;
+use redis::RedisResult;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Redis key holding the admin API's cached service health snapshot
+pub const HEALTH_CACHE_KEY: &str = "services:health:data";
+
+/// Redis channel the admin API publishes fresh probe results on, for the gateway's health checker
+pub const HEALTH_VERDICTS_CHANNEL: &str = "services:health:verdicts";
+
+/// Drop the cached health snapshot so the next request re-checks all services
+pub async fn invalidate<C: ConnectionLike + Send>(conn: &mut C) -> RedisResult<()> {
+    redis::cmd("DEL")
+        .arg(HEALTH_CACHE_KEY)
+        .query_async::<()>(conn)
+        .await
+}
+
+/// Result of probing one backend service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthVerdict {
+    pub service_id: Uuid,
+    pub is_healthy: bool,
+}
+
+/// Publish probe results so every gateway subscribed to [`HEALTH_VERDICTS_CHANNEL`] applies them
+pub async fn publish_verdicts<C: ConnectionLike + Send>(
+    conn: &mut C,
+    verdicts: &[HealthVerdict],
+) -> RedisResult<()> {
+    let payload = serde_json::to_string(verdicts).expect("health verdicts serialize to JSON");
+    redis::cmd("PUBLISH")
+        .arg(HEALTH_VERDICTS_CHANNEL)
+        .arg(payload)
+        .query_async::<()>(conn)
+        .await
+}
+
+/// Parse a message published on [`HEALTH_VERDICTS_CHANNEL`]
+pub fn parse_verdicts(payload: &str) -> serde_json::Result<Vec<HealthVerdict>> {
+    serde_json::from_str(payload)
+}
+
+/// Redis key holding a service's recent health check results, newest first
+pub fn history_key(service_id: Uuid) -> String {
+    format!("services:health:history:{}", service_id)
+}
+
+/// One health check the gateway ran against a backend service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheckRecord {
+    pub checked_at: DateTime<Utc>,
+    pub is_healthy: bool,
+    /// How long the probe took, including a timeout
+    pub latency_ms: u64,
+    /// Why the check failed, `None` when it passed
+    pub error: Option<String>,
+}
+
+/// Replace a service's stored history with the gateway's current one
+///
+/// The key expires after `ttl_seconds`, so services that stopped being
+/// checked don't leave their history behind.
+pub async fn store_history<C: ConnectionLike + Send>(
+    conn: &mut C,
+    service_id: Uuid,
+    history: &[HealthCheckRecord],
+    ttl_seconds: u64,
+) -> RedisResult<()> {
+    let payload = serde_json::to_string(history).expect("health history serializes to JSON");
+    redis::cmd("SET")
+        .arg(history_key(service_id))
+        .arg(payload)
+        .arg("EX")
+        .arg(ttl_seconds.max(1))
+        .query_async::<()>(conn)
+        .await
+}
+
+/// A service's stored history, newest first; empty when none was stored or it is unreadable
+pub async fn load_history<C: ConnectionLike + Send>(
+    conn: &mut C,
+    service_id: Uuid,
+) -> RedisResult<Vec<HealthCheckRecord>> {
+    let payload: Option<String> = redis::cmd("GET")
+        .arg(history_key(service_id))
+        .query_async(conn)
+        .await?;
+
+    Ok(payload
+        .and_then(|payload| serde_json::from_str(&payload).ok())
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verdicts_round_trip() {
+        let verdicts = vec![
+            HealthVerdict {
+                service_id: Uuid::new_v4(),
+                is_healthy: true,
+            },
+            HealthVerdict {
+                service_id: Uuid::new_v4(),
+                is_healthy: false,
+            },
+        ];
+
+        let payload = serde_json::to_string(&verdicts).unwrap();
+        assert_eq!(parse_verdicts(&payload).unwrap(), verdicts);
+        assert!(parse_verdicts("not json").is_err());
+    }
+}
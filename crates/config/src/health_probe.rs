@@ -0,0 +1,536 @@
+use karateway_core::models::BackendService;
+use redis::aio::ConnectionLike;
+use redis::RedisResult;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Timeout for a gateway's health probe request, and the longest any prober waits on a shared one
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a prober waiting on another one's probe checks for its result
+const SHARED_RESULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Outcome of probing a backend service's health endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeResult {
+    pub is_healthy: bool,
+    pub status_message: String,
+    /// How long the probe took; a shared result keeps the latency its prober measured
+    #[serde(default)]
+    pub latency_ms: u64,
+}
+
+/// Build an HTTP client for health probes that gives up after `timeout`
+pub fn client(timeout: Duration) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder().timeout(timeout).build()
+}
+
+/// Resolve the full health check URL for a service
+///
+/// Absolute URLs are used as is; anything else is treated as a path on `base_url`.
+pub fn health_check_url(service: &BackendService) -> Option<String> {
+    let health_url = service.health_check_url.as_ref()?;
+
+    if health_url.starts_with("http://") || health_url.starts_with("https://") {
+        Some(health_url.clone())
+    } else {
+        Some(format!("{}{}", service.base_url, health_url))
+    }
+}
+
+/// The probe request for `url`, with the service's health check method, headers and body
+///
+/// Without a configured method probes are `GET`s. Headers that aren't valid
+/// HTTP are logged and left out rather than failing every probe.
+pub fn request(
+    client: &reqwest::Client,
+    service: &BackendService,
+    url: &str,
+) -> reqwest::RequestBuilder {
+    let method = service
+        .health_check_method
+        .as_ref()
+        .and_then(|method| reqwest::Method::from_bytes(method.to_string().as_bytes()).ok())
+        .unwrap_or(reqwest::Method::GET);
+    let mut request = client.request(method, url);
+
+    let headers = service
+        .health_check_headers
+        .as_ref()
+        .and_then(|headers| headers.as_object());
+    for (name, value) in headers.into_iter().flatten() {
+        let Some(value) = value.as_str() else {
+            continue;
+        };
+        match (
+            reqwest::header::HeaderName::from_bytes(name.as_bytes()),
+            reqwest::header::HeaderValue::from_str(value),
+        ) {
+            (Ok(name), Ok(value)) => request = request.header(name, value),
+            _ => warn!(
+                "Skipping invalid health check header {} of service {}",
+                name, service.name
+            ),
+        }
+    }
+
+    if let Some(body) = &service.health_check_body {
+        request = request.body(body.clone());
+    }
+    request
+}
+
+/// Probe a service's health endpoint; any 2xx response counts as healthy
+///
+/// Services without a `health_check_url` are reported healthy.
+pub async fn probe(client: &reqwest::Client, service: &BackendService) -> ProbeResult {
+    let Some(full_url) = health_check_url(service) else {
+        return ProbeResult {
+            is_healthy: true,
+            status_message: "No health check configured".to_string(),
+            latency_ms: 0,
+        };
+    };
+
+    let started = Instant::now();
+    let (is_healthy, status_message) = match request(client, service, &full_url).send().await {
+        Ok(response) if response.status().is_success() => {
+            (true, format!("Healthy ({})", response.status()))
+        }
+        Ok(response) => (false, format!("Unhealthy - returned {}", response.status())),
+        Err(e) => (false, format!("Unhealthy - {}", e)),
+    };
+    ProbeResult {
+        is_healthy,
+        status_message,
+        latency_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Redis key holding the latest probe result of a service, shared by everyone probing it
+///
+/// The key covers the probe request as well as the service, so after the
+/// health check is changed nobody picks up a result of the old one, even
+/// while some processes haven't reloaded yet.
+pub fn shared_result_key(service: &BackendService) -> String {
+    format!(
+        "services:health:probe:{}:{:016x}",
+        service.id,
+        probe_config_hash(service)
+    )
+}
+
+/// Redis key held by whoever is probing a service for the others
+fn probe_lock_key(service: &BackendService) -> String {
+    format!("{}:lock", shared_result_key(service))
+}
+
+/// Hash of the probe's URL, method, headers and body
+///
+/// FNV-1a rather than std's hasher, whose output may change between Rust
+/// releases: gateways and the admin API must agree on it.
+fn probe_config_hash(service: &BackendService) -> u64 {
+    let method = service
+        .health_check_method
+        .as_ref()
+        .map(|method| method.to_string());
+    let headers = service
+        .health_check_headers
+        .as_ref()
+        .map(|headers| headers.to_string());
+    let parts = [
+        health_check_url(service),
+        method,
+        headers,
+        service.health_check_body.clone(),
+    ];
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in &parts {
+        // Tell an absent part from an empty one, and parts from each other
+        let bytes = part.as_deref().map(str::as_bytes);
+        for byte in bytes
+            .into_iter()
+            .flatten()
+            .chain(&[part.is_some() as u8, 0])
+        {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Probe a service, sharing the result with every gateway and the admin API for `ttl`
+///
+/// A result stored less than `ttl` ago is used as is. Otherwise the first
+/// prober to take the service's lock runs the probe and stores its result,
+/// and the others wait for it, probing themselves only if it doesn't arrive
+/// within [`PROBE_TIMEOUT`]. A zero `ttl` or a Redis error means a plain
+/// [`probe`].
+pub async fn probe_coalesced<C: ConnectionLike + Send>(
+    conn: &mut C,
+    client: &reqwest::Client,
+    service: &BackendService,
+    ttl: Duration,
+) -> ProbeResult {
+    if ttl.is_zero() || service.health_check_url.is_none() {
+        return probe(client, service).await;
+    }
+
+    match shared_probe(conn, client, service, ttl).await {
+        Ok(result) => result,
+        Err(e) => {
+            warn!(
+                "Can't share the health probe of {} through Redis, probing directly: {}",
+                service.name, e
+            );
+            probe(client, service).await
+        }
+    }
+}
+
+async fn shared_probe<C: ConnectionLike + Send>(
+    conn: &mut C,
+    client: &reqwest::Client,
+    service: &BackendService,
+    ttl: Duration,
+) -> RedisResult<ProbeResult> {
+    let key = shared_result_key(service);
+    if let Some(result) = load_shared_result(conn, &key).await? {
+        debug!("Reusing a recent health probe of {}", service.name);
+        return Ok(result);
+    }
+
+    let lock = probe_lock_key(service);
+    let claimed: Option<String> = redis::cmd("SET")
+        .arg(&lock)
+        .arg(1)
+        .arg("NX")
+        .arg("PX")
+        .arg(PROBE_TIMEOUT.as_millis() as u64)
+        .query_async(conn)
+        .await?;
+
+    if claimed.is_some() {
+        let result = probe(client, service).await;
+        let payload = serde_json::to_string(&result).expect("probe result serializes to JSON");
+        // The result is already in hand, so a failed store only costs the others a probe
+        let stored = async {
+            redis::cmd("SET")
+                .arg(&key)
+                .arg(payload)
+                .arg("PX")
+                .arg(ttl.as_millis().max(1) as u64)
+                .query_async::<()>(conn)
+                .await?;
+            redis::cmd("DEL").arg(&lock).query_async::<()>(conn).await
+        };
+        if let Err(e) = stored.await {
+            warn!(
+                "Failed to share the health probe of {}: {}",
+                service.name, e
+            );
+        }
+        return Ok(result);
+    }
+
+    // Someone else is probing it; wait for their result
+    let deadline = Instant::now() + PROBE_TIMEOUT;
+    while Instant::now() < deadline {
+        tokio::time::sleep(SHARED_RESULT_POLL_INTERVAL).await;
+        if let Some(result) = load_shared_result(conn, &key).await? {
+            debug!("Shared a concurrent health probe of {}", service.name);
+            return Ok(result);
+        }
+    }
+    Ok(probe(client, service).await)
+}
+
+async fn load_shared_result<C: ConnectionLike + Send>(
+    conn: &mut C,
+    key: &str,
+) -> RedisResult<Option<ProbeResult>> {
+    let payload: Option<String> = redis::cmd("GET").arg(key).query_async(conn).await?;
+    Ok(payload.and_then(|payload| serde_json::from_str(&payload).ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use karateway_core::models::{DiscoveryType, HttpMethod};
+    use uuid::Uuid;
+
+    fn service(health_check_url: Option<&str>) -> BackendService {
+        BackendService {
+            id: Uuid::new_v4(),
+            name: "orders".to_string(),
+            description: None,
+            base_url: "http://orders:9000".to_string(),
+            health_check_url: health_check_url.map(|u| u.to_string()),
+            health_check_interval_seconds: None,
+            timeout_ms: None,
+            discovery_type: DiscoveryType::Static,
+            srv_name: None,
+            max_connections: None,
+            health_check_method: None,
+            health_check_headers: None,
+            health_check_body: None,
+            tls_sni: None,
+            capacity: None,
+            tls_client_cert_path: None,
+            tls_client_key_path: None,
+            liveness_ping_interval_seconds: None,
+            max_response_header_bytes: None,
+            max_response_header_count: None,
+            is_active: true,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_health_check_url() {
+        assert_eq!(health_check_url(&service(None)), None);
+        assert_eq!(
+            health_check_url(&service(Some("/health"))),
+            Some("http://orders:9000/health".to_string())
+        );
+        assert_eq!(
+            health_check_url(&service(Some("https://status.internal/orders"))),
+            Some("https://status.internal/orders".to_string())
+        );
+    }
+
+    /// A health endpoint that only answers `POST`s, sending back each raw request it got
+    async fn post_only_backend() -> (String, tokio::sync::mpsc::UnboundedReceiver<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (requests, received) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                // Read the head, then as much body as it announces
+                let mut raw = Vec::new();
+                let mut buf = [0; 1024];
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    raw.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&raw).to_string();
+                    let complete = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                        let length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().to_string())
+                            })
+                            .and_then(|v| v.parse::<usize>().ok())
+                            .unwrap_or(0);
+                        body.len() >= length
+                    });
+                    if n == 0 || complete {
+                        break;
+                    }
+                }
+                let request = String::from_utf8_lossy(&raw).to_string();
+                let status = if request.starts_with("POST ") {
+                    "200 OK"
+                } else {
+                    "405 Method Not Allowed"
+                };
+                let _ = requests.send(request);
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, received)
+    }
+
+    #[tokio::test]
+    async fn test_probe_sends_the_configured_method_headers_and_body() {
+        let (url, mut received) = post_only_backend().await;
+        let mut service = service(Some("/health"));
+        service.base_url = url;
+        service.health_check_method = Some(HttpMethod::POST);
+        service.health_check_headers = Some(serde_json::json!({"X-Health-Token": "s3cret"}));
+        service.health_check_body = Some(r#"{"check": "db"}"#.to_string());
+
+        let result = probe(&client(PROBE_TIMEOUT).unwrap(), &service).await;
+        assert!(result.is_healthy, "{}", result.status_message);
+
+        let request = received.recv().await.unwrap();
+        assert!(request.starts_with("POST /health "));
+        assert!(request.to_lowercase().contains("x-health-token: s3cret"));
+        assert!(request.ends_with(r#"{"check": "db"}"#));
+    }
+
+    #[tokio::test]
+    async fn test_probe_defaults_to_get() {
+        let (url, mut received) = post_only_backend().await;
+        let mut service = service(Some("/health"));
+        service.base_url = url;
+
+        let result = probe(&client(PROBE_TIMEOUT).unwrap(), &service).await;
+        assert!(!result.is_healthy);
+        assert!(result.status_message.contains("405"));
+        assert!(received.recv().await.unwrap().starts_with("GET /health "));
+    }
+
+    /// An in-memory stand-in for Redis, enough for GET, SET (with NX) and DEL
+    #[derive(Clone, Default)]
+    struct FakeRedis(std::sync::Arc<std::sync::Mutex<std::collections::HashMap<Vec<u8>, Vec<u8>>>>);
+
+    impl ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(
+            &'a mut self,
+            cmd: &'a redis::Cmd,
+        ) -> redis::RedisFuture<'a, redis::Value> {
+            let args: Vec<Vec<u8>> = cmd
+                .args_iter()
+                .filter_map(|arg| match arg {
+                    redis::Arg::Simple(arg) => Some(arg.to_vec()),
+                    redis::Arg::Cursor => None,
+                })
+                .collect();
+
+            let mut data = self.0.lock().unwrap();
+            let value = match args[0].as_slice() {
+                b"GET" => data
+                    .get(&args[1])
+                    .cloned()
+                    .map_or(redis::Value::Nil, redis::Value::BulkString),
+                b"SET"
+                    if args[3..].iter().any(|arg| arg == b"NX") && data.contains_key(&args[1]) =>
+                {
+                    redis::Value::Nil
+                }
+                b"SET" => {
+                    data.insert(args[1].clone(), args[2].clone());
+                    redis::Value::Okay
+                }
+                b"DEL" => redis::Value::Int(data.remove(&args[1]).is_some() as i64),
+                other => panic!("unexpected command {}", String::from_utf8_lossy(other)),
+            };
+            Box::pin(async move { Ok(value) })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _cmd: &'a redis::Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+            panic!("unexpected pipeline")
+        }
+
+        fn get_db(&self) -> i64 {
+            0
+        }
+    }
+
+    /// A slow, healthy endpoint counting the probes it gets
+    async fn counting_backend() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let hits = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut request = [0; 1024];
+                    let _ = socket.read(&mut request).await;
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                    let _ = socket
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        )
+                        .await;
+                });
+            }
+        });
+        (url, hits)
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_probes_within_the_ttl_hit_the_backend_once() {
+        use std::sync::atomic::Ordering;
+
+        let (url, hits) = counting_backend().await;
+        let mut service = service(Some("/health"));
+        service.base_url = url;
+        let client = client(PROBE_TIMEOUT).unwrap();
+        let redis = FakeRedis::default();
+        let ttl = Duration::from_secs(2);
+
+        // Say two gateways and the admin API
+        let (mut a, mut b, mut c) = (redis.clone(), redis.clone(), redis.clone());
+        let results = tokio::join!(
+            probe_coalesced(&mut a, &client, &service, ttl),
+            probe_coalesced(&mut b, &client, &service, ttl),
+            probe_coalesced(&mut c, &client, &service, ttl),
+        );
+        assert!(results.0.is_healthy, "{}", results.0.status_message);
+        assert_eq!(results.0, results.1);
+        assert_eq!(results.0, results.2);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // A later probe inside the TTL still reuses the stored result
+        let again = probe_coalesced(&mut redis.clone(), &client, &service, ttl).await;
+        assert_eq!(again, results.0);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // A changed health check doesn't reuse the old one's result
+        service.health_check_body = Some("deep".to_string());
+        probe_coalesced(&mut redis.clone(), &client, &service, ttl).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // Without a TTL every probe goes to the backend
+        probe_coalesced(&mut redis.clone(), &client, &service, Duration::ZERO).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_shared_result_key_follows_the_probe_config() {
+        let service = service(Some("/health"));
+        let key = shared_result_key(&service);
+        assert_eq!(key, shared_result_key(&service.clone()));
+        assert!(key.starts_with(&format!("services:health:probe:{}:", service.id)));
+
+        let mut changed = service.clone();
+        changed.health_check_url = Some("/ready".to_string());
+        assert_ne!(shared_result_key(&changed), key);
+
+        let mut changed = service.clone();
+        changed.health_check_method = Some(HttpMethod::HEAD);
+        assert_ne!(shared_result_key(&changed), key);
+
+        let mut changed = service.clone();
+        changed.health_check_headers = Some(serde_json::json!({"X-Health-Token": "a"}));
+        let with_header = shared_result_key(&changed);
+        assert_ne!(with_header, key);
+        changed.health_check_headers = Some(serde_json::json!({"X-Health-Token": "b"}));
+        assert_ne!(shared_result_key(&changed), with_header);
+
+        // No body and an empty one are different probes
+        let mut changed = service.clone();
+        changed.health_check_body = Some(String::new());
+        assert_ne!(shared_result_key(&changed), key);
+    }
+
+    #[tokio::test]
+    async fn test_probe_without_health_check_is_healthy() {
+        let result = probe(&client(PROBE_TIMEOUT).unwrap(), &service(None)).await;
+        assert!(result.is_healthy);
+        assert_eq!(result.status_message, "No health check configured");
+    }
+}
@@ -0,0 +1,172 @@
+use std::net::IpAddr;
+use tracing::{error, warn};
+
+/// An IP network such as `10.0.0.0/8`; a bare address is a single-host network
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl std::str::FromStr for IpNetwork {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid IP network: {}", s.trim());
+
+        let (address, prefix_len) = match s.trim().split_once('/') {
+            Some((address, prefix_len)) => (address, Some(prefix_len)),
+            None => (s.trim(), None),
+        };
+        let address: IpAddr = address.parse().map_err(|_| invalid())?;
+        let max_len = if address.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(invalid)?,
+            None => max_len,
+        };
+
+        Ok(Self {
+            address,
+            prefix_len,
+        })
+    }
+}
+
+impl IpNetwork {
+    /// Whether `ip` is inside this network; IPv4 and IPv6 never match each other
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(network).into(),
+                u32::from(ip).into(),
+                32,
+                self.prefix_len,
+            ),
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                prefix_matches(network.into(), ip.into(), 128, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compare the top `prefix_len` bits of two `bits`-wide addresses
+fn prefix_matches(network: u128, ip: u128, bits: u8, prefix_len: u8) -> bool {
+    if prefix_len == 0 {
+        return true;
+    }
+    let shift = u32::from(bits - prefix_len);
+    (network >> shift) == (ip >> shift)
+}
+
+/// Client networks allowed to reach a service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IpAllowlist {
+    /// Nothing configured: every client is allowed
+    AllowAll,
+    /// Only clients inside one of these networks are allowed
+    Networks(Vec<IpNetwork>),
+}
+
+impl IpAllowlist {
+    /// Parse a comma-separated list of IPs and CIDR ranges
+    ///
+    /// An empty value allows everyone. Invalid entries are logged and skipped;
+    /// if none are valid everyone is denied, so a typo never opens access up.
+    pub fn parse(value: &str) -> Self {
+        let entries: Vec<&str> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        if entries.is_empty() {
+            return IpAllowlist::AllowAll;
+        }
+
+        let networks: Vec<IpNetwork> = entries
+            .iter()
+            .filter_map(|entry| match entry.parse() {
+                Ok(network) => Some(network),
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            })
+            .collect();
+        if networks.is_empty() {
+            error!(
+                "No valid entries in IP allowlist {:?}, denying all clients",
+                value
+            );
+        }
+
+        IpAllowlist::Networks(networks)
+    }
+
+    /// Whether a client is allowed; an unknown or unparseable IP is only allowed under `AllowAll`
+    pub fn allows(&self, client_ip: Option<&str>) -> bool {
+        match self {
+            IpAllowlist::AllowAll => true,
+            IpAllowlist::Networks(networks) => client_ip
+                .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+                .is_some_and(|ip| networks.iter().any(|network| network.contains(ip))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_contains() {
+        let network: IpNetwork = "10.0.0.0/8".parse().unwrap();
+        assert!(network.contains("10.1.2.3".parse().unwrap()));
+        assert!(!network.contains("11.0.0.1".parse().unwrap()));
+        assert!(!network.contains("::ffff:10.1.2.3".parse().unwrap()));
+
+        let host: IpNetwork = "192.0.2.1".parse().unwrap();
+        assert!(host.contains("192.0.2.1".parse().unwrap()));
+        assert!(!host.contains("192.0.2.2".parse().unwrap()));
+
+        let v6: IpNetwork = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12:3456::1".parse().unwrap()));
+        assert!(!v6.contains("2001:db8::1".parse().unwrap()));
+
+        let any: IpNetwork = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("203.0.113.9".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("internal".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn test_allowlist_in_and_out_of_range() {
+        let allowlist = IpAllowlist::parse("10.0.0.0/8, 192.168.0.0/16, ::1");
+        assert!(allowlist.allows(Some("10.20.30.40")));
+        assert!(allowlist.allows(Some("192.168.1.10")));
+        assert!(allowlist.allows(Some("::1")));
+        assert!(!allowlist.allows(Some("203.0.113.9")));
+        assert!(!allowlist.allows(Some("unknown")));
+        assert!(!allowlist.allows(None));
+    }
+
+    #[test]
+    fn test_empty_allowlist_allows_everyone() {
+        let allowlist = IpAllowlist::parse("");
+        assert_eq!(allowlist, IpAllowlist::AllowAll);
+        assert!(allowlist.allows(Some("203.0.113.9")));
+        assert!(allowlist.allows(None));
+    }
+
+    #[test]
+    fn test_invalid_allowlist_denies_everyone() {
+        let allowlist = IpAllowlist::parse("10.0.0.0/99,internal");
+        assert!(!allowlist.allows(Some("10.0.0.1")));
+        assert!(!allowlist.allows(Some("203.0.113.9")));
+    }
+}
@@ -0,0 +1,259 @@
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+
+/// Why a token doesn't pass a JWT whitelist rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwtRejection {
+    /// Not three base64url JSON segments
+    Malformed(String),
+    /// Signed with something other than the HMAC algorithms a shared secret can check
+    UnsupportedAlgorithm(String),
+    InvalidSignature,
+    Expired,
+    /// `nbf` is still in the future
+    NotYetValid,
+    InvalidIssuer,
+    InvalidAudience,
+    MissingClaim(String),
+}
+
+impl std::fmt::Display for JwtRejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JwtRejection::Malformed(reason) => write!(f, "malformed token: {}", reason),
+            JwtRejection::UnsupportedAlgorithm(alg) => {
+                write!(
+                    f,
+                    "unsupported algorithm {}, expected HS256, HS384 or HS512",
+                    alg
+                )
+            }
+            JwtRejection::InvalidSignature => {
+                write!(f, "signature does not match the rule's secret")
+            }
+            JwtRejection::Expired => write!(f, "token has expired"),
+            JwtRejection::NotYetValid => write!(f, "token is not valid yet"),
+            JwtRejection::InvalidIssuer => write!(f, "issuer is not allowed"),
+            JwtRejection::InvalidAudience => write!(f, "audience is not allowed"),
+            JwtRejection::MissingClaim(claim) => write!(f, "missing required claim: {}", claim),
+        }
+    }
+}
+
+/// The settings of a `jwt` whitelist rule
+///
+/// Tokens must be signed with `jwt_secret` using HS256, HS384 or HS512 and
+/// carry an unexpired `exp`. `allowed_issuers` and `allowed_audiences` are
+/// only checked when non-empty.
+#[derive(Debug, Clone)]
+pub struct JwtRule {
+    pub secret: String,
+    pub allowed_issuers: Vec<String>,
+    pub allowed_audiences: Vec<String>,
+}
+
+impl JwtRule {
+    /// Read a rule's config, `None` when it has no secret to check against
+    pub fn from_config(config: &Value) -> Option<Self> {
+        let strings = |field: &str| -> Vec<String> {
+            config
+                .get(field)
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        };
+
+        let secret = config.get("jwt_secret")?.as_str()?;
+        (!secret.is_empty()).then(|| Self {
+            secret: secret.to_string(),
+            allowed_issuers: strings("allowed_issuers"),
+            allowed_audiences: strings("allowed_audiences"),
+        })
+    }
+
+    /// Check a token's signature and claims, returning the claims when it passes
+    pub fn verify(&self, token: &str) -> Result<Map<String, Value>, JwtRejection> {
+        let header = jsonwebtoken::decode_header(token)
+            .map_err(|e| JwtRejection::Malformed(e.to_string()))?;
+        if !matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(JwtRejection::UnsupportedAlgorithm(format!(
+                "{:?}",
+                header.alg
+            )));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.validate_nbf = true;
+        if !self.allowed_issuers.is_empty() {
+            validation.set_issuer(&self.allowed_issuers);
+        }
+        if self.allowed_audiences.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&self.allowed_audiences);
+        }
+
+        jsonwebtoken::decode::<Map<String, Value>>(
+            token,
+            &DecodingKey::from_secret(self.secret.as_bytes()),
+            &validation,
+        )
+        .map(|data| data.claims)
+        .map_err(|e| match e.into_kind() {
+            ErrorKind::InvalidSignature => JwtRejection::InvalidSignature,
+            ErrorKind::ExpiredSignature => JwtRejection::Expired,
+            ErrorKind::ImmatureSignature => JwtRejection::NotYetValid,
+            ErrorKind::InvalidIssuer => JwtRejection::InvalidIssuer,
+            ErrorKind::InvalidAudience => JwtRejection::InvalidAudience,
+            ErrorKind::MissingRequiredClaim(claim) => JwtRejection::MissingClaim(claim),
+            kind => JwtRejection::Malformed(format!("{:?}", kind)),
+        })
+    }
+}
+
+/// A token's header and claims without checking anything, for showing why it was rejected
+pub fn decode_unverified(token: &str) -> Option<(Value, Map<String, Value>)> {
+    let header = jsonwebtoken::decode_header(token).ok()?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.insecure_disable_signature_validation();
+    validation.validate_exp = false;
+    validation.validate_aud = false;
+    validation.required_spec_claims.clear();
+
+    let claims = jsonwebtoken::decode::<Map<String, Value>>(
+        token,
+        &DecodingKey::from_secret(&[]),
+        &validation,
+    )
+    .ok()?
+    .claims;
+    Some((serde_json::to_value(header).ok()?, claims))
+}
+
+/// `token` with its signature segment replaced, so it can be echoed back safely
+pub fn redact_signature(token: &str) -> String {
+    match token.rsplit_once('.') {
+        Some((signed, _)) => format!("{}.[redacted]", signed),
+        None => "[redacted]".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &str = "s3cret";
+
+    fn rule() -> JwtRule {
+        JwtRule::from_config(&json!({
+            "jwt_secret": SECRET,
+            "allowed_issuers": ["auth.example.com"],
+            "allowed_audiences": ["orders-api"]
+        }))
+        .unwrap()
+    }
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+
+    fn token(claims: Value, secret: &str) -> String {
+        jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn valid_claims() -> Value {
+        json!({
+            "sub": "user-1",
+            "iss": "auth.example.com",
+            "aud": "orders-api",
+            "exp": now() + 3600
+        })
+    }
+
+    #[test]
+    fn test_valid_token_passes() {
+        let claims = rule().verify(&token(valid_claims(), SECRET)).unwrap();
+        assert_eq!(claims["sub"], "user-1");
+
+        // Without allowed issuers or audiences, any are accepted
+        let open = JwtRule::from_config(&json!({"jwt_secret": SECRET})).unwrap();
+        let mut claims = valid_claims();
+        claims["iss"] = json!("someone-else");
+        claims["aud"] = json!("other-api");
+        assert!(open.verify(&token(claims, SECRET)).is_ok());
+    }
+
+    #[test]
+    fn test_each_failure_has_its_reason() {
+        let rule = rule();
+        let with = |field: &str, value: Value| {
+            let mut claims = valid_claims();
+            claims[field] = value;
+            rule.verify(&token(claims, SECRET)).unwrap_err()
+        };
+
+        assert_eq!(
+            rule.verify(&token(valid_claims(), "wrong")).unwrap_err(),
+            JwtRejection::InvalidSignature
+        );
+        assert_eq!(with("exp", json!(now() - 3600)), JwtRejection::Expired);
+        assert_eq!(with("nbf", json!(now() + 3600)), JwtRejection::NotYetValid);
+        assert_eq!(
+            with("iss", json!("evil.example.com")),
+            JwtRejection::InvalidIssuer
+        );
+        assert_eq!(
+            with("aud", json!("billing-api")),
+            JwtRejection::InvalidAudience
+        );
+
+        let mut claims = valid_claims();
+        claims.as_object_mut().unwrap().remove("exp");
+        assert_eq!(
+            rule.verify(&token(claims, SECRET)).unwrap_err(),
+            JwtRejection::MissingClaim("exp".to_string())
+        );
+
+        assert!(matches!(
+            rule.verify("not-a-jwt").unwrap_err(),
+            JwtRejection::Malformed(_)
+        ));
+    }
+
+    #[test]
+    fn test_rejected_tokens_can_still_be_inspected() {
+        let mut claims = valid_claims();
+        claims["exp"] = json!(now() - 3600);
+        let expired = token(claims, "wrong");
+
+        let (header, claims) = decode_unverified(&expired).unwrap();
+        assert_eq!(header["alg"], "HS256");
+        assert_eq!(claims["sub"], "user-1");
+        assert!(decode_unverified("not-a-jwt").is_none());
+
+        let redacted = redact_signature(&expired);
+        assert!(redacted.ends_with(".[redacted]"));
+        assert_eq!(redacted.matches('.').count(), 2);
+        assert!(!redacted.contains(expired.rsplit('.').next().unwrap()));
+    }
+
+    #[test]
+    fn test_config_without_secret_has_no_rule() {
+        assert!(JwtRule::from_config(&json!({})).is_none());
+        assert!(JwtRule::from_config(&json!({"jwt_secret": ""})).is_none());
+    }
+}
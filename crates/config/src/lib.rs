@@ -1,13 +1,29 @@
 pub mod app_config;
+pub mod audit_categories;
+pub mod audit_escalation;
 pub mod audit_logger;
+pub mod audit_sampling;
+pub mod audit_webhook;
+pub mod circuit_breaker;
+pub mod client_ip;
+pub mod config_limits;
 pub mod database;
+pub mod health_cache;
+pub mod health_probe;
+pub mod ip_allowlist;
+pub mod jwt_rule;
+pub mod metrics_retention;
+pub mod pagination;
+pub mod readiness;
 pub mod redis;
 pub mod repository;
+pub mod request_logger;
 
 pub use app_config::AppConfig;
 pub use audit_logger::AuditLogger;
 pub use database::DatabaseConfig;
 pub use redis::RedisConfig;
+pub use request_logger::RequestLogger;
 
 use dotenvy::dotenv;
 
@@ -0,0 +1,155 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::time::interval;
+use tracing::{error, info};
+
+use crate::AppConfig;
+
+/// How often old metrics are cleaned up
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How long `gateway_metrics` rows are kept before the cleanup deletes them
+///
+/// The deletion runs in the database's `cleanup_old_gateway_metrics`
+/// function, which rolls the rows up into `gateway_metrics_hourly` first
+/// unless `keep_hourly` is off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsRetention {
+    pub retention: ChronoDuration,
+    pub keep_hourly: bool,
+}
+
+impl MetricsRetention {
+    /// `None` when metrics are kept forever
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        (config.gateway_metrics_retention_days > 0).then(|| Self {
+            retention: ChronoDuration::days(config.gateway_metrics_retention_days as i64),
+            keep_hourly: config.gateway_metrics_hourly_rollup,
+        })
+    }
+
+    /// Rows recorded before this are purged
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now - self.retention
+    }
+
+    /// Delete the rows older than the retention period, returning how many were deleted
+    pub async fn cleanup(&self, pool: &PgPool, now: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT cleanup_old_gateway_metrics($1, $2)")
+            .bind(self.cutoff(now))
+            .bind(self.keep_hourly)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Run the cleanup now and then every hour
+    pub fn start_background_cleanup(self, pool: PgPool) {
+        tokio::spawn(async move {
+            info!(
+                "Starting gateway_metrics cleanup, keeping {} days",
+                self.retention.num_days()
+            );
+            let mut cleanup_interval = interval(CLEANUP_INTERVAL);
+
+            loop {
+                cleanup_interval.tick().await;
+                match self.cleanup(&pool, Utc::now()).await {
+                    Ok(0) => {}
+                    Ok(deleted) => info!("Deleted {} old gateway_metrics rows", deleted),
+                    Err(e) => error!("Failed to clean up gateway_metrics: {}", e),
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::test_db;
+    use chrono::TimeZone;
+
+    /// Record a request to `/orders` at `timestamp`
+    async fn insert_metric(
+        pool: &PgPool,
+        timestamp: DateTime<Utc>,
+        response_time_ms: f64,
+        error_message: Option<&str>,
+    ) {
+        sqlx::query(
+            "INSERT INTO gateway_metrics (timestamp, method, path, status_code, response_time_ms, error_message) \
+             VALUES ($1, 'GET', '/orders', 200, $2, $3)",
+        )
+        .bind(timestamp)
+        .bind(response_time_ms)
+        .bind(error_message)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn count(pool: &PgPool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT count(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored"]
+    async fn test_old_metrics_are_rolled_up_and_recent_ones_kept() {
+        let db = test_db().await;
+        let pool = db.pool.clone();
+        let mut retention = MetricsRetention {
+            retention: ChronoDuration::days(30),
+            keep_hourly: true,
+        };
+        let now = Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        let hour = Utc.with_ymd_and_hms(2026, 9, 1, 10, 0, 0).unwrap();
+        assert_eq!(
+            retention.cutoff(now),
+            Utc.with_ymd_and_hms(2026, 9, 14, 12, 0, 0).unwrap()
+        );
+
+        insert_metric(&pool, hour + ChronoDuration::minutes(5), 10.0, None).await;
+        insert_metric(
+            &pool,
+            hour + ChronoDuration::minutes(45),
+            30.0,
+            Some("timeout"),
+        )
+        .await;
+        // Exactly at the cutoff is still kept
+        insert_metric(&pool, retention.cutoff(now), 5.0, None).await;
+        insert_metric(&pool, now - ChronoDuration::hours(1), 5.0, None).await;
+
+        assert_eq!(retention.cleanup(&pool, now).await.unwrap(), 2);
+        assert_eq!(count(&pool, "gateway_metrics").await, 2);
+        let (request_count, error_count, total, max): (i64, i64, f64, Option<f64>) =
+            sqlx::query_as(
+                "SELECT request_count, error_count, total_response_time_ms, max_response_time_ms \
+                 FROM gateway_metrics_hourly WHERE hour = $1 AND status_code = 200",
+            )
+            .bind(hour)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!((request_count, error_count), (2, 1));
+        assert_eq!((total, max), (40.0, Some(30.0)));
+
+        // Nothing is left to purge, and a purge without rollup skips the hourly table
+        assert_eq!(retention.cleanup(&pool, now).await.unwrap(), 0);
+        retention.keep_hourly = false;
+        insert_metric(&pool, hour, 20.0, None).await;
+        assert_eq!(retention.cleanup(&pool, now).await.unwrap(), 1);
+        assert_eq!(count(&pool, "gateway_metrics").await, 2);
+        assert_eq!(count(&pool, "gateway_metrics_hourly").await, 1);
+        let request_count: i64 =
+            sqlx::query_scalar("SELECT request_count FROM gateway_metrics_hourly")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(request_count, 2);
+    }
+}
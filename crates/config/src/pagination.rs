@@ -0,0 +1,140 @@
+use karateway_core::{KaratewayError, Result};
+use tracing::warn;
+
+/// What a list endpoint does with a `limit` above the maximum page size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverLimit {
+    /// Serve the maximum page size instead
+    #[default]
+    Clamp,
+    /// Answer 400
+    Reject,
+}
+
+impl std::str::FromStr for OverLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "clamp" => Ok(OverLimit::Clamp),
+            "reject" => Ok(OverLimit::Reject),
+            other => Err(format!("Invalid page size over-limit mode: {}", other)),
+        }
+    }
+}
+
+/// Page size bounds shared by every admin list endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageLimits {
+    pub max_page_size: u32,
+    pub over_limit: OverLimit,
+}
+
+impl Default for PageLimits {
+    fn default() -> Self {
+        Self {
+            max_page_size: 200,
+            over_limit: OverLimit::Clamp,
+        }
+    }
+}
+
+impl PageLimits {
+    /// Parse the configured values, falling back to the defaults on invalid ones
+    pub fn parse_or_default(max_page_size: u32, over_limit: &str) -> Self {
+        let defaults = Self::default();
+
+        Self {
+            max_page_size: if max_page_size == 0 {
+                warn!("Invalid max page size 0, using {}", defaults.max_page_size);
+                defaults.max_page_size
+            } else {
+                max_page_size
+            },
+            over_limit: over_limit.parse().unwrap_or_else(|e| {
+                warn!("{}, using clamp", e);
+                defaults.over_limit
+            }),
+        }
+    }
+
+    /// The page size to serve for a requested `limit`
+    ///
+    /// A limit of 0 is always rejected; one above the maximum is clamped or
+    /// rejected depending on `over_limit`.
+    pub fn apply(&self, limit: u32) -> Result<u32> {
+        if limit == 0 {
+            return Err(KaratewayError::Validation(
+                "limit must be at least 1".to_string(),
+            ));
+        }
+
+        if limit <= self.max_page_size {
+            return Ok(limit);
+        }
+
+        match self.over_limit {
+            OverLimit::Clamp => Ok(self.max_page_size),
+            OverLimit::Reject => Err(KaratewayError::Validation(format!(
+                "limit must not exceed {}",
+                self.max_page_size
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(over_limit: OverLimit) -> PageLimits {
+        PageLimits {
+            max_page_size: 200,
+            over_limit,
+        }
+    }
+
+    #[test]
+    fn test_clamps_over_limit() {
+        let limits = limits(OverLimit::Clamp);
+        assert_eq!(limits.apply(10).unwrap(), 10);
+        assert_eq!(limits.apply(200).unwrap(), 200);
+        assert_eq!(limits.apply(201).unwrap(), 200);
+        assert_eq!(limits.apply(u32::MAX).unwrap(), 200);
+    }
+
+    #[test]
+    fn test_rejects_over_limit() {
+        let limits = limits(OverLimit::Reject);
+        assert_eq!(limits.apply(200).unwrap(), 200);
+        assert!(matches!(
+            limits.apply(201),
+            Err(KaratewayError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_zero_limit() {
+        for over_limit in [OverLimit::Clamp, OverLimit::Reject] {
+            assert!(matches!(
+                limits(over_limit).apply(0),
+                Err(KaratewayError::Validation(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_or_default() {
+        assert_eq!(
+            PageLimits::parse_or_default(50, "Reject"),
+            PageLimits {
+                max_page_size: 50,
+                over_limit: OverLimit::Reject,
+            }
+        );
+        assert_eq!(
+            PageLimits::parse_or_default(0, "bogus"),
+            PageLimits::default()
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Something the admin API needs to be fully ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthDependency {
+    Database,
+    Redis,
+    /// Any active backend service that passes its health check
+    AtLeastOneBackendHealthy,
+}
+
+impl std::str::FromStr for HealthDependency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "database" => Ok(HealthDependency::Database),
+            "redis" => Ok(HealthDependency::Redis),
+            "at_least_one_backend_healthy" => Ok(HealthDependency::AtLeastOneBackendHealthy),
+            other => Err(format!("Invalid health dependency: {}", other)),
+        }
+    }
+}
+
+/// Parse a comma-separated list of required dependencies
+///
+/// Invalid entries are logged and skipped, duplicates are ignored.
+pub fn parse_dependencies(value: &str) -> Vec<HealthDependency> {
+    let mut dependencies = Vec::new();
+
+    for item in value.split(',').filter(|s| !s.trim().is_empty()) {
+        match item.parse() {
+            Ok(dependency) if !dependencies.contains(&dependency) => dependencies.push(dependency),
+            Ok(_) => {}
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    dependencies
+}
+
+/// Overall status from the required dependencies only
+///
+/// `healthy` when all of them are satisfied, `unhealthy` when none are and
+/// `degraded` in between. Dependencies that aren't required never lower the
+/// status; with nothing required the service is always `healthy`.
+pub fn overall_status(
+    required: &[HealthDependency],
+    satisfied: impl Fn(HealthDependency) -> bool,
+) -> &'static str {
+    let up = required.iter().filter(|d| satisfied(**d)).count();
+
+    if up == required.len() {
+        "healthy"
+    } else if up > 0 {
+        "degraded"
+    } else {
+        "unhealthy"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use HealthDependency::*;
+
+    fn status(required: &str, up: &[HealthDependency]) -> &'static str {
+        overall_status(&parse_dependencies(required), |d| up.contains(&d))
+    }
+
+    #[test]
+    fn test_parse_dependencies() {
+        assert_eq!(parse_dependencies("database,redis"), vec![Database, Redis]);
+        assert_eq!(
+            parse_dependencies(" database , at_least_one_backend_healthy,database, bogus"),
+            vec![Database, AtLeastOneBackendHealthy]
+        );
+        assert!(parse_dependencies("").is_empty());
+    }
+
+    #[test]
+    fn test_default_database_and_redis() {
+        let required = "database,redis";
+        assert_eq!(status(required, &[Database, Redis]), "healthy");
+        assert_eq!(status(required, &[Database]), "degraded");
+        assert_eq!(status(required, &[Redis]), "degraded");
+        assert_eq!(status(required, &[]), "unhealthy");
+    }
+
+    #[test]
+    fn test_unrequired_dependencies_are_ignored() {
+        // No rate limiting, so Redis being down doesn't matter
+        assert_eq!(status("database", &[Database]), "healthy");
+        assert_eq!(status("database", &[Redis]), "unhealthy");
+        assert_eq!(status("", &[]), "healthy");
+    }
+
+    #[test]
+    fn test_backend_dependency() {
+        let required = "database,redis,at_least_one_backend_healthy";
+        assert_eq!(
+            status(required, &[Database, Redis, AtLeastOneBackendHealthy]),
+            "healthy"
+        );
+        assert_eq!(status(required, &[Database, Redis]), "degraded");
+        assert_eq!(status(required, &[AtLeastOneBackendHealthy]), "degraded");
+        assert_eq!(
+            status("at_least_one_backend_healthy", &[Database, Redis]),
+            "unhealthy"
+        );
+    }
+}
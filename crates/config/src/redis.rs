@@ -1,5 +1,5 @@
-use deadpool_redis::{Config, Pool, Runtime};
-use redis::RedisError;
+use deadpool_redis::{Manager, Pool, Runtime};
+use redis::{Client, RedisError, TlsCertificates};
 
 use crate::app_config::AppConfig;
 
@@ -12,14 +12,124 @@ impl RedisConfig {
         Self { config }
     }
 
+    /// Build a Redis client, trusting `REDIS_TLS_CA_CERT` on top of the system roots when set
+    pub fn client(&self) -> Result<Client, RedisError> {
+        let url = self.config.redis_url();
+
+        let ca_cert = self
+            .config
+            .redis_tls_ca_cert
+            .as_deref()
+            .filter(|path| self.config.redis_tls && !path.is_empty());
+
+        match ca_cert {
+            Some(path) => {
+                let root_cert = std::fs::read(path).map_err(|e| {
+                    RedisError::from((
+                        redis::ErrorKind::IoError,
+                        "Failed to read Redis CA certificate",
+                        format!("{}: {}", path, e),
+                    ))
+                })?;
+
+                Client::build_with_tls(
+                    url,
+                    TlsCertificates {
+                        client_tls: None,
+                        root_cert: Some(root_cert),
+                    },
+                )
+            }
+            None => Client::open(url),
+        }
+    }
+
     pub fn create_pool(&self) -> Result<Pool, RedisError> {
-        let cfg = Config::from_url(&self.config.redis_url());
-        cfg.create_pool(Some(Runtime::Tokio1)).map_err(|e| {
-            RedisError::from((
-                redis::ErrorKind::IoError,
-                "Pool creation failed",
-                e.to_string(),
-            ))
-        })
+        // Going through the client keeps the TLS parameters (custom CA) in the connection info
+        let client = self.client()?;
+        let manager = Manager::new(client.get_connection_info().clone())?;
+
+        Pool::builder(manager)
+            .runtime(Runtime::Tokio1)
+            .build()
+            .map_err(|e| {
+                RedisError::from((
+                    redis::ErrorKind::IoError,
+                    "Pool creation failed",
+                    e.to_string(),
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use envconfig::Envconfig;
+    use redis::ConnectionAddr;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> AppConfig {
+        let mut env: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        env.insert("DB_PASSWORD".to_string(), "secret".to_string());
+        env.insert("JWT_SECRET".to_string(), "secret".to_string());
+        AppConfig::init_from_hashmap(&env).unwrap()
+    }
+
+    #[test]
+    fn test_redis_url_scheme() {
+        assert_eq!(config(&[]).redis_url(), "redis://localhost:6379");
+        assert_eq!(
+            config(&[("REDIS_TLS", "true"), ("REDIS_PASSWORD", "pw")]).redis_url(),
+            "rediss://:pw@localhost:6379"
+        );
+        assert_eq!(
+            config(&[("REDIS_TLS", "true"), ("REDIS_TLS_INSECURE", "true")]).redis_url(),
+            "rediss://localhost:6379#insecure"
+        );
+        // Insecure only makes sense together with TLS
+        assert_eq!(
+            config(&[("REDIS_TLS_INSECURE", "true")]).redis_url(),
+            "redis://localhost:6379"
+        );
+    }
+
+    #[test]
+    fn test_tls_client_connection_info() {
+        let client = RedisConfig::new(config(&[
+            ("REDIS_HOST", "cache.example.com"),
+            ("REDIS_TLS", "true"),
+            ("REDIS_TLS_INSECURE", "true"),
+        ]))
+        .client()
+        .unwrap();
+
+        match &client.get_connection_info().addr {
+            ConnectionAddr::TcpTls {
+                host,
+                port,
+                insecure,
+                ..
+            } => {
+                assert_eq!(host, "cache.example.com");
+                assert_eq!(*port, 6379);
+                assert!(*insecure);
+            }
+            addr => panic!("expected a TLS address, got {:?}", addr),
+        }
+    }
+
+    #[test]
+    fn test_missing_ca_cert_is_an_error() {
+        let result = RedisConfig::new(config(&[
+            ("REDIS_TLS", "true"),
+            ("REDIS_TLS_CA_CERT", "/nonexistent/ca.pem"),
+        ]))
+        .client();
+
+        assert!(result.is_err());
     }
 }
@@ -1,12 +1,14 @@
 use karateway_core::{
-    models::{ApiRoute, ApiRoutes, CreateApiRouteRequest, UpdateApiRouteRequest},
+    models::{ApiRoute, ApiRoutes, CreateApiRouteRequest, DeploymentColor, UpdateApiRouteRequest},
     KaratewayError, Result,
 };
 use sea_query::{Expr, Func, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
+use super::EntityCounts;
+
 #[derive(Clone)]
 pub struct ApiRouteRepository {
     pool: PgPool,
@@ -24,9 +26,29 @@ impl ApiRouteRepository {
                 ApiRoutes::PathPattern,
                 ApiRoutes::Method,
                 ApiRoutes::BackendServiceId,
+                ApiRoutes::GreenBackendServiceId,
+                ApiRoutes::ActiveColor,
+                ApiRoutes::QueryMatch,
                 ApiRoutes::StripPathPrefix,
                 ApiRoutes::PreserveHostHeader,
+                ApiRoutes::AllowMethodOverride,
                 ApiRoutes::TimeoutMs,
+                ApiRoutes::IdleTimeoutMs,
+                ApiRoutes::FallbackBackendServiceIds,
+                ApiRoutes::AccessLogEnabled,
+                ApiRoutes::CookieDomain,
+                ApiRoutes::CookiePath,
+                ApiRoutes::DecompressResponse,
+                ApiRoutes::ApiVersion,
+                ApiRoutes::TimingHeaders,
+                ApiRoutes::DebugLogBody,
+                ApiRoutes::ContentTypeMatch,
+                ApiRoutes::UpstreamPathPrefix,
+                ApiRoutes::CoalesceRequests,
+                ApiRoutes::QueueDepth,
+                ApiRoutes::QueueTimeoutMs,
+                ApiRoutes::RequestCost,
+                ApiRoutes::RequestCostHeader,
                 ApiRoutes::Priority,
                 ApiRoutes::Metadata,
             ])
@@ -34,9 +56,29 @@ impl ApiRouteRepository {
                 req.path_pattern.into(),
                 req.method.to_string().into(),
                 req.backend_service_id.into(),
+                req.green_backend_service_id.into(),
+                req.active_color.unwrap_or_default().to_string().into(),
+                req.query_match.unwrap_or(serde_json::json!({})).into(),
                 req.strip_path_prefix.unwrap_or(false).into(),
                 req.preserve_host_header.unwrap_or(false).into(),
+                req.allow_method_override.unwrap_or(false).into(),
                 req.timeout_ms.into(),
+                req.idle_timeout_ms.into(),
+                serde_json::json!(req.fallback_backend_service_ids.unwrap_or_default()).into(),
+                req.access_log_enabled.unwrap_or(true).into(),
+                req.cookie_domain.clone().into(),
+                req.cookie_path.clone().into(),
+                req.decompress_response.unwrap_or(false).into(),
+                req.api_version.into(),
+                req.timing_headers.unwrap_or(false).into(),
+                req.debug_log_body.unwrap_or(false).into(),
+                req.content_type_match.into(),
+                req.upstream_path_prefix.clone().into(),
+                req.coalesce_requests.unwrap_or(false).into(),
+                req.queue_depth.into(),
+                req.queue_timeout_ms.into(),
+                req.request_cost.into(),
+                req.request_cost_header.into(),
                 req.priority.unwrap_or(0).into(),
                 req.metadata.unwrap_or(serde_json::json!({})).into(),
             ])
@@ -57,9 +99,29 @@ impl ApiRouteRepository {
                 ApiRoutes::PathPattern,
                 ApiRoutes::Method,
                 ApiRoutes::BackendServiceId,
+                ApiRoutes::GreenBackendServiceId,
+                ApiRoutes::ActiveColor,
+                ApiRoutes::QueryMatch,
                 ApiRoutes::StripPathPrefix,
                 ApiRoutes::PreserveHostHeader,
+                ApiRoutes::AllowMethodOverride,
                 ApiRoutes::TimeoutMs,
+                ApiRoutes::IdleTimeoutMs,
+                ApiRoutes::FallbackBackendServiceIds,
+                ApiRoutes::AccessLogEnabled,
+                ApiRoutes::CookieDomain,
+                ApiRoutes::CookiePath,
+                ApiRoutes::DecompressResponse,
+                ApiRoutes::ApiVersion,
+                ApiRoutes::TimingHeaders,
+                ApiRoutes::DebugLogBody,
+                ApiRoutes::ContentTypeMatch,
+                ApiRoutes::UpstreamPathPrefix,
+                ApiRoutes::CoalesceRequests,
+                ApiRoutes::QueueDepth,
+                ApiRoutes::QueueTimeoutMs,
+                ApiRoutes::RequestCost,
+                ApiRoutes::RequestCostHeader,
                 ApiRoutes::IsActive,
                 ApiRoutes::Priority,
                 ApiRoutes::Metadata,
@@ -89,9 +151,29 @@ impl ApiRouteRepository {
                 ApiRoutes::PathPattern,
                 ApiRoutes::Method,
                 ApiRoutes::BackendServiceId,
+                ApiRoutes::GreenBackendServiceId,
+                ApiRoutes::ActiveColor,
+                ApiRoutes::QueryMatch,
                 ApiRoutes::StripPathPrefix,
                 ApiRoutes::PreserveHostHeader,
+                ApiRoutes::AllowMethodOverride,
                 ApiRoutes::TimeoutMs,
+                ApiRoutes::IdleTimeoutMs,
+                ApiRoutes::FallbackBackendServiceIds,
+                ApiRoutes::AccessLogEnabled,
+                ApiRoutes::CookieDomain,
+                ApiRoutes::CookiePath,
+                ApiRoutes::DecompressResponse,
+                ApiRoutes::ApiVersion,
+                ApiRoutes::TimingHeaders,
+                ApiRoutes::DebugLogBody,
+                ApiRoutes::ContentTypeMatch,
+                ApiRoutes::UpstreamPathPrefix,
+                ApiRoutes::CoalesceRequests,
+                ApiRoutes::QueueDepth,
+                ApiRoutes::QueueTimeoutMs,
+                ApiRoutes::RequestCost,
+                ApiRoutes::RequestCostHeader,
                 ApiRoutes::IsActive,
                 ApiRoutes::Priority,
                 ApiRoutes::Metadata,
@@ -125,6 +207,25 @@ impl ApiRouteRepository {
         Ok(count.0 as u64)
     }
 
+    pub async fn count_active(&self) -> Result<u64> {
+        let (sql, values) = Query::select()
+            .expr(Func::count(Expr::col(ApiRoutes::Id)))
+            .from(ApiRoutes::Table)
+            .and_where(Expr::col(ApiRoutes::IsActive).eq(true))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: (i64,) = sqlx::query_as_with(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0 as u64)
+    }
+
+    /// Total and active routes in one query
+    pub async fn count_by_state(&self) -> Result<EntityCounts> {
+        super::count_by_state(&self.pool, ApiRoutes::Table, ApiRoutes::IsActive).await
+    }
+
     pub async fn list_by_backend_service(&self, backend_service_id: Uuid) -> Result<Vec<ApiRoute>> {
         let (sql, values) = Query::select()
             .columns([
@@ -132,9 +233,29 @@ impl ApiRouteRepository {
                 ApiRoutes::PathPattern,
                 ApiRoutes::Method,
                 ApiRoutes::BackendServiceId,
+                ApiRoutes::GreenBackendServiceId,
+                ApiRoutes::ActiveColor,
+                ApiRoutes::QueryMatch,
                 ApiRoutes::StripPathPrefix,
                 ApiRoutes::PreserveHostHeader,
+                ApiRoutes::AllowMethodOverride,
                 ApiRoutes::TimeoutMs,
+                ApiRoutes::IdleTimeoutMs,
+                ApiRoutes::FallbackBackendServiceIds,
+                ApiRoutes::AccessLogEnabled,
+                ApiRoutes::CookieDomain,
+                ApiRoutes::CookiePath,
+                ApiRoutes::DecompressResponse,
+                ApiRoutes::ApiVersion,
+                ApiRoutes::TimingHeaders,
+                ApiRoutes::DebugLogBody,
+                ApiRoutes::ContentTypeMatch,
+                ApiRoutes::UpstreamPathPrefix,
+                ApiRoutes::CoalesceRequests,
+                ApiRoutes::QueueDepth,
+                ApiRoutes::QueueTimeoutMs,
+                ApiRoutes::RequestCost,
+                ApiRoutes::RequestCostHeader,
                 ApiRoutes::IsActive,
                 ApiRoutes::Priority,
                 ApiRoutes::Metadata,
@@ -154,58 +275,142 @@ impl ApiRouteRepository {
         Ok(routes)
     }
 
+    /// Routes on `path_pattern`, or on any case of it with `ignore_case`
+    ///
+    /// Case is folded by Postgres, which may return more routes than ASCII
+    /// folding would; callers compare the paths themselves.
+    pub async fn list_by_path_pattern(
+        &self,
+        path_pattern: &str,
+        ignore_case: bool,
+    ) -> Result<Vec<ApiRoute>> {
+        let same_path = if ignore_case {
+            Expr::expr(Func::lower(Expr::col(ApiRoutes::PathPattern)))
+                .eq(Func::lower(Expr::val(path_pattern)))
+        } else {
+            Expr::col(ApiRoutes::PathPattern).eq(path_pattern)
+        };
+        let (sql, values) = Query::select()
+            .columns([
+                ApiRoutes::Id,
+                ApiRoutes::PathPattern,
+                ApiRoutes::Method,
+                ApiRoutes::BackendServiceId,
+                ApiRoutes::GreenBackendServiceId,
+                ApiRoutes::ActiveColor,
+                ApiRoutes::QueryMatch,
+                ApiRoutes::StripPathPrefix,
+                ApiRoutes::PreserveHostHeader,
+                ApiRoutes::AllowMethodOverride,
+                ApiRoutes::TimeoutMs,
+                ApiRoutes::IdleTimeoutMs,
+                ApiRoutes::FallbackBackendServiceIds,
+                ApiRoutes::AccessLogEnabled,
+                ApiRoutes::CookieDomain,
+                ApiRoutes::CookiePath,
+                ApiRoutes::DecompressResponse,
+                ApiRoutes::ApiVersion,
+                ApiRoutes::TimingHeaders,
+                ApiRoutes::DebugLogBody,
+                ApiRoutes::ContentTypeMatch,
+                ApiRoutes::UpstreamPathPrefix,
+                ApiRoutes::CoalesceRequests,
+                ApiRoutes::QueueDepth,
+                ApiRoutes::QueueTimeoutMs,
+                ApiRoutes::RequestCost,
+                ApiRoutes::RequestCostHeader,
+                ApiRoutes::IsActive,
+                ApiRoutes::Priority,
+                ApiRoutes::Metadata,
+                ApiRoutes::CreatedAt,
+                ApiRoutes::UpdatedAt,
+            ])
+            .from(ApiRoutes::Table)
+            .and_where(same_path)
+            .order_by(ApiRoutes::Priority, sea_query::Order::Desc)
+            .order_by(ApiRoutes::CreatedAt, sea_query::Order::Desc)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let routes = sqlx::query_as_with::<_, ApiRoute, _>(&sql, values)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(routes)
+    }
+
     pub async fn update(&self, id: Uuid, req: UpdateApiRouteRequest) -> Result<ApiRoute> {
         let mut route = self.find_by_id(id).await?;
 
-        // Apply updates
-        if let Some(path_pattern) = req.path_pattern {
-            route.path_pattern = path_pattern;
-        }
-        if let Some(method) = req.method {
-            route.method = method;
-        }
-        if let Some(backend_service_id) = req.backend_service_id {
-            route.backend_service_id = backend_service_id;
-        }
-        if let Some(strip_path_prefix) = req.strip_path_prefix {
-            route.strip_path_prefix = strip_path_prefix;
-        }
-        if let Some(preserve_host_header) = req.preserve_host_header {
-            route.preserve_host_header = preserve_host_header;
-        }
-        if let Some(timeout_ms) = req.timeout_ms {
-            route.timeout_ms = Some(timeout_ms);
-        }
-        if let Some(is_active) = req.is_active {
-            route.is_active = is_active;
-        }
-        if let Some(priority) = req.priority {
-            route.priority = priority;
-        }
-        if let Some(metadata) = req.metadata {
-            route.metadata = metadata;
-        }
+        route.apply_update(req);
 
         // Save to database
-        let (sql, values) = Query::update()
+        let mut query = Query::update();
+        query
             .table(ApiRoutes::Table)
             .values([
                 (ApiRoutes::PathPattern, route.path_pattern.clone().into()),
                 (ApiRoutes::Method, route.method.to_string().into()),
                 (ApiRoutes::BackendServiceId, route.backend_service_id.into()),
+                (
+                    ApiRoutes::GreenBackendServiceId,
+                    route.green_backend_service_id.into(),
+                ),
+                (ApiRoutes::QueryMatch, route.query_match.clone().into()),
                 (ApiRoutes::StripPathPrefix, route.strip_path_prefix.into()),
                 (
                     ApiRoutes::PreserveHostHeader,
                     route.preserve_host_header.into(),
                 ),
+                (
+                    ApiRoutes::AllowMethodOverride,
+                    route.allow_method_override.into(),
+                ),
                 (ApiRoutes::TimeoutMs, route.timeout_ms.into()),
+                (ApiRoutes::IdleTimeoutMs, route.idle_timeout_ms.into()),
+                (
+                    ApiRoutes::FallbackBackendServiceIds,
+                    route.fallback_backend_service_ids.clone().into(),
+                ),
+                (ApiRoutes::AccessLogEnabled, route.access_log_enabled.into()),
+                (ApiRoutes::CookieDomain, route.cookie_domain.clone().into()),
+                (ApiRoutes::CookiePath, route.cookie_path.clone().into()),
+                (
+                    ApiRoutes::DecompressResponse,
+                    route.decompress_response.into(),
+                ),
+                (ApiRoutes::ApiVersion, route.api_version.clone().into()),
+                (ApiRoutes::TimingHeaders, route.timing_headers.into()),
+                (ApiRoutes::DebugLogBody, route.debug_log_body.into()),
+                (
+                    ApiRoutes::ContentTypeMatch,
+                    route.content_type_match.clone().into(),
+                ),
+                (
+                    ApiRoutes::UpstreamPathPrefix,
+                    route.upstream_path_prefix.clone().into(),
+                ),
+                (ApiRoutes::CoalesceRequests, route.coalesce_requests.into()),
+                (ApiRoutes::QueueDepth, route.queue_depth.into()),
+                (ApiRoutes::QueueTimeoutMs, route.queue_timeout_ms.into()),
+                (ApiRoutes::RequestCost, route.request_cost.into()),
+                (
+                    ApiRoutes::RequestCostHeader,
+                    route.request_cost_header.clone().into(),
+                ),
                 (ApiRoutes::IsActive, route.is_active.into()),
                 (ApiRoutes::Priority, route.priority.into()),
                 (ApiRoutes::Metadata, route.metadata.clone().into()),
             ])
             .and_where(Expr::col(ApiRoutes::Id).eq(id))
-            .returning_all()
-            .build_sqlx(PostgresQueryBuilder);
+            .returning_all();
+
+        // The color belongs to `/switch`, so writing back the one read above
+        // could undo a concurrent switch. It is only set when the green backend
+        // is gone, since blue is then the only color left, whatever a switch did
+        if route.green_backend_service_id.is_none() {
+            query.value(ApiRoutes::ActiveColor, DeploymentColor::Blue.to_string());
+        }
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 
         let updated = sqlx::query_as_with::<_, ApiRoute, _>(&sql, values)
             .fetch_one(&self.pool)
@@ -214,13 +419,51 @@ impl ApiRouteRepository {
         Ok(updated)
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<()> {
+    /// Flip a blue/green route to its other color
+    ///
+    /// The flip happens in a single `UPDATE`, so concurrent switches can never
+    /// leave the route half-way. Routes without a green backend are rejected.
+    pub async fn switch_active_color(&self, id: Uuid) -> Result<ApiRoute> {
+        let (sql, values) = Query::update()
+            .table(ApiRoutes::Table)
+            .value(
+                ApiRoutes::ActiveColor,
+                Expr::case(
+                    Expr::col(ApiRoutes::ActiveColor).eq(DeploymentColor::Blue.to_string()),
+                    DeploymentColor::Green.to_string(),
+                )
+                .finally(DeploymentColor::Blue.to_string()),
+            )
+            .and_where(Expr::col(ApiRoutes::Id).eq(id))
+            .and_where(Expr::col(ApiRoutes::GreenBackendServiceId).is_not_null())
+            .returning_all()
+            .build_sqlx(PostgresQueryBuilder);
+
+        let switched = sqlx::query_as_with::<_, ApiRoute, _>(&sql, values)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match switched {
+            Some(route) => Ok(route),
+            None => {
+                // Distinguish a missing route from one that can't be switched
+                self.find_by_id(id).await?;
+                Err(KaratewayError::Validation(format!(
+                    "API route {} has no green backend service to switch to",
+                    id
+                )))
+            }
+        }
+    }
+
+    /// Delete route `id` on `executor`, the pool or a transaction
+    pub async fn delete(&self, executor: impl PgExecutor<'_>, id: Uuid) -> Result<()> {
         let (sql, values) = Query::delete()
             .from_table(ApiRoutes::Table)
             .and_where(Expr::col(ApiRoutes::Id).eq(id))
             .build_sqlx(PostgresQueryBuilder);
 
-        let result = sqlx::query_with(&sql, values).execute(&self.pool).await?;
+        let result = sqlx::query_with(&sql, values).execute(executor).await?;
 
         if result.rows_affected() == 0 {
             return Err(KaratewayError::NotFound(format!(
@@ -232,6 +475,22 @@ impl ApiRouteRepository {
         Ok(())
     }
 
+    /// Delete all routes belonging to `backend_service_id`, returning how many were removed
+    pub async fn delete_by_backend_service(
+        &self,
+        executor: impl PgExecutor<'_>,
+        backend_service_id: Uuid,
+    ) -> Result<u64> {
+        let (sql, values) = Query::delete()
+            .from_table(ApiRoutes::Table)
+            .and_where(Expr::col(ApiRoutes::BackendServiceId).eq(backend_service_id))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let result = sqlx::query_with(&sql, values).execute(executor).await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn list_active(&self) -> Result<Vec<ApiRoute>> {
         let (sql, values) = Query::select()
             .columns([
@@ -239,9 +498,29 @@ impl ApiRouteRepository {
                 ApiRoutes::PathPattern,
                 ApiRoutes::Method,
                 ApiRoutes::BackendServiceId,
+                ApiRoutes::GreenBackendServiceId,
+                ApiRoutes::ActiveColor,
+                ApiRoutes::QueryMatch,
                 ApiRoutes::StripPathPrefix,
                 ApiRoutes::PreserveHostHeader,
+                ApiRoutes::AllowMethodOverride,
                 ApiRoutes::TimeoutMs,
+                ApiRoutes::IdleTimeoutMs,
+                ApiRoutes::FallbackBackendServiceIds,
+                ApiRoutes::AccessLogEnabled,
+                ApiRoutes::CookieDomain,
+                ApiRoutes::CookiePath,
+                ApiRoutes::DecompressResponse,
+                ApiRoutes::ApiVersion,
+                ApiRoutes::TimingHeaders,
+                ApiRoutes::DebugLogBody,
+                ApiRoutes::ContentTypeMatch,
+                ApiRoutes::UpstreamPathPrefix,
+                ApiRoutes::CoalesceRequests,
+                ApiRoutes::QueueDepth,
+                ApiRoutes::QueueTimeoutMs,
+                ApiRoutes::RequestCost,
+                ApiRoutes::RequestCostHeader,
                 ApiRoutes::IsActive,
                 ApiRoutes::Priority,
                 ApiRoutes::Metadata,
@@ -261,3 +540,192 @@ impl ApiRouteRepository {
         Ok(routes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::test_db;
+
+    #[tokio::test]
+    #[ignore = "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored"]
+    async fn test_preview_shows_what_the_update_saves() {
+        let db = test_db().await;
+        let pool = db.pool.clone();
+        let mut backend_ids = Vec::new();
+        for name in ["blue", "green", "fallback"] {
+            let id = Uuid::new_v4();
+            sqlx::query("INSERT INTO backend_services (id, name, base_url) VALUES ($1, $2, $3)")
+                .bind(id)
+                .bind(name)
+                .bind(format!("http://{}:8080", name))
+                .execute(&pool)
+                .await
+                .unwrap();
+            backend_ids.push(id);
+        }
+        let repo = ApiRouteRepository::new(pool.clone());
+        let route = repo
+            .create(
+                serde_json::from_value(serde_json::json!({
+                    "path_pattern": "/orders",
+                    "method": "GET",
+                    "backend_service_id": backend_ids[0],
+                    "green_backend_service_id": backend_ids[1],
+                    "active_color": "Green",
+                }))
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let updates = [
+            serde_json::json!({
+                "path_pattern": "/orders/v2",
+                "query_match": {"version": "beta"},
+                "timeout_ms": 5000,
+                "fallback_backend_service_ids": [backend_ids[2]],
+                "cookie_path": "/orders",
+                "request_cost": 2,
+                "priority": 10,
+                "metadata": {"team": "checkout"},
+            }),
+            // Dropping the green backend also moves the route back to blue
+            serde_json::json!({ "green_backend_service_id": null, "is_active": false }),
+        ];
+        let mut current = route;
+        for update in updates {
+            let req: UpdateApiRouteRequest = serde_json::from_value(update).unwrap();
+            let mut preview = current.clone();
+            preview.apply_update(req.clone());
+
+            let saved = repo.update(current.id, req).await.unwrap();
+            assert_eq!(preview.diff(&saved), Vec::new());
+            assert!(!current.diff(&saved).is_empty());
+            current = saved;
+        }
+        assert_eq!(current.active_color, DeploymentColor::Blue);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored"]
+    async fn test_bulk_deletes_only_remove_matching_rows() {
+        use crate::repository::{RateLimitRepository, WhitelistRuleRepository};
+
+        let db = test_db().await;
+        let pool = db.pool.clone();
+        let mut backend_ids = Vec::new();
+        for name in ["orders", "search"] {
+            let id = Uuid::new_v4();
+            sqlx::query("INSERT INTO backend_services (id, name, base_url) VALUES ($1, $2, $3)")
+                .bind(id)
+                .bind(name)
+                .bind(format!("http://{}:8080", name))
+                .execute(&pool)
+                .await
+                .unwrap();
+            backend_ids.push(id);
+        }
+        let repo = ApiRouteRepository::new(pool.clone());
+        let mut route_ids = Vec::new();
+        for (path, backend_id) in [
+            ("/orders", backend_ids[0]),
+            ("/orders/archive", backend_ids[0]),
+            ("/search", backend_ids[1]),
+        ] {
+            let route = repo
+                .create(
+                    serde_json::from_value(serde_json::json!({
+                        "path_pattern": path,
+                        "method": "GET",
+                        "backend_service_id": backend_id,
+                    }))
+                    .unwrap(),
+                )
+                .await
+                .unwrap();
+            route_ids.push(route.id);
+        }
+        for (name, route_id) in [
+            ("orders-minute", route_ids[0]),
+            ("orders-hour", route_ids[0]),
+            ("search-minute", route_ids[2]),
+        ] {
+            sqlx::query(
+                "INSERT INTO rate_limits (id, name, api_route_id, max_requests, window_seconds, identifier_type) \
+                 VALUES ($1, $2, $3, 10, 60, 'ip')",
+            )
+            .bind(Uuid::new_v4())
+            .bind(name)
+            .bind(route_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        for (name, route_id) in [
+            ("orders-office", route_ids[0]),
+            ("search-office", route_ids[2]),
+        ] {
+            sqlx::query(
+                "INSERT INTO whitelist_rules (id, rule_name, rule_type, api_route_id, config) \
+                 VALUES ($1, $2, 'ip', $3, '{}')",
+            )
+            .bind(Uuid::new_v4())
+            .bind(name)
+            .bind(route_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let count = |table: &'static str| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM {}", table))
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let rate_limits = RateLimitRepository::new(pool.clone());
+        assert_eq!(
+            rate_limits
+                .delete_by_api_route(&pool, route_ids[0])
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(count("rate_limits").await, 1);
+        assert_eq!(
+            rate_limits
+                .delete_by_api_route(&pool, route_ids[1])
+                .await
+                .unwrap(),
+            0
+        );
+
+        let whitelist_rules = WhitelistRuleRepository::new(pool.clone());
+        assert_eq!(
+            whitelist_rules
+                .delete_by_api_route(&pool, route_ids[0])
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(count("whitelist_rules").await, 1);
+
+        assert_eq!(
+            repo.delete_by_backend_service(&pool, backend_ids[0])
+                .await
+                .unwrap(),
+            2
+        );
+        let left: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM api_routes")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, [route_ids[2]]);
+        // The other route keeps its rules
+        assert_eq!(count("rate_limits").await, 1);
+        assert_eq!(count("whitelist_rules").await, 1);
+    }
+}
@@ -1,8 +1,9 @@
+use chrono::{DateTime, Duration, Utc};
 use karateway_core::{
-    models::{AuditLog, AuditLogs},
-    Result,
+    models::{AuditLog, AuditLogCount, AuditLogStats, AuditLogs},
+    KaratewayError, Result,
 };
-use sea_query::{Expr, Func, PostgresQueryBuilder, Query};
+use sea_query::{Alias, Expr, Func, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
 use sqlx::PgPool;
 
@@ -59,4 +60,213 @@ impl AuditLogRepository {
 
         Ok(count.0)
     }
+
+    /// Event counts by type, severity and client IP for events created in `[from, to)`
+    ///
+    /// Each rollup is one grouped query over the `created_at` range, so the
+    /// rows themselves never leave the database. At most `top_ips` client IPs
+    /// are returned.
+    pub async fn stats(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        top_ips: u64,
+    ) -> Result<AuditLogStats> {
+        let (sql, values) = Query::select()
+            .expr(Func::count(Expr::col(AuditLogs::Id)))
+            .from(AuditLogs::Table)
+            .and_where(Expr::col(AuditLogs::CreatedAt).gte(from))
+            .and_where(Expr::col(AuditLogs::CreatedAt).lt(to))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let total: (i64,) = sqlx::query_as_with(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(AuditLogStats {
+            from,
+            to,
+            total: total.0,
+            by_event_type: self.count_by(AuditLogs::EventType, from, to, None).await?,
+            by_severity: self.count_by(AuditLogs::Severity, from, to, None).await?,
+            top_client_ips: self
+                .count_by(AuditLogs::ClientIp, from, to, Some(top_ips))
+                .await?,
+        })
+    }
+
+    /// Events per distinct `column` value in `[from, to)`, most frequent first
+    async fn count_by(
+        &self,
+        column: AuditLogs,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        limit: Option<u64>,
+    ) -> Result<Vec<AuditLogCount>> {
+        let mut query = Query::select();
+        query
+            .expr_as(Expr::col(column), Alias::new("value"))
+            .expr_as(Func::count(Expr::col(AuditLogs::Id)), Alias::new("count"))
+            .from(AuditLogs::Table)
+            .and_where(Expr::col(AuditLogs::CreatedAt).gte(from))
+            .and_where(Expr::col(AuditLogs::CreatedAt).lt(to))
+            .and_where(Expr::col(column).is_not_null())
+            .group_by_col(column)
+            .order_by(Alias::new("count"), sea_query::Order::Desc)
+            .order_by(Alias::new("value"), sea_query::Order::Asc);
+        if let Some(limit) = limit {
+            query.limit(limit);
+        }
+        let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+
+        let counts = sqlx::query_as_with::<_, AuditLogCount, _>(&sql, values)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(counts)
+    }
+}
+
+/// Resolve the stats time window, defaulting to the 24 hours before `now`
+///
+/// A missing `to` means `now` and a missing `from` means 24 hours before `to`.
+pub fn stats_window(
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let to = to.unwrap_or(now);
+    let from = from.unwrap_or(to - Duration::hours(24));
+
+    if from >= to {
+        return Err(KaratewayError::Validation(format!(
+            "from ({}) must be before to ({})",
+            from.to_rfc3339(),
+            to.to_rfc3339()
+        )));
+    }
+
+    Ok((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::test_db;
+    use uuid::Uuid;
+
+    async fn seed(
+        pool: &PgPool,
+        event_type: &str,
+        severity: &str,
+        client_ip: Option<&str>,
+        created_at: DateTime<Utc>,
+    ) {
+        sqlx::query(
+            "INSERT INTO audit_logs (id, event_type, event_category, severity, client_ip, message, created_at) \
+             VALUES ($1, $2, 'security', $3, $4, 'seeded', $5)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(event_type)
+        .bind(severity)
+        .bind(client_ip)
+        .bind(created_at)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn counts(counts: &[AuditLogCount]) -> Vec<(&str, i64)> {
+        counts
+            .iter()
+            .map(|count| (count.value.as_str(), count.count))
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored"]
+    async fn test_stats_aggregate_seeded_events() {
+        let db = test_db().await;
+        let pool = db.pool.clone();
+        let to = Utc::now();
+        let from = to - Duration::hours(1);
+
+        for ip in ["10.0.0.1", "10.0.0.1", "10.0.0.2"] {
+            seed(&pool, "rate_limit_exceeded", "warning", Some(ip), from).await;
+        }
+        seed(
+            &pool,
+            "whitelist_denied",
+            "critical",
+            Some("10.0.0.3"),
+            to - Duration::minutes(1),
+        )
+        .await;
+        seed(
+            &pool,
+            "whitelist_denied",
+            "warning",
+            None,
+            to - Duration::minutes(1),
+        )
+        .await;
+        // Outside the window on both ends
+        seed(
+            &pool,
+            "whitelist_denied",
+            "info",
+            Some("10.0.0.1"),
+            from - Duration::seconds(1),
+        )
+        .await;
+        seed(&pool, "whitelist_denied", "info", Some("10.0.0.1"), to).await;
+
+        let stats = AuditLogRepository::new(pool)
+            .stats(from, to, 2)
+            .await
+            .unwrap();
+
+        assert_eq!(stats.total, 5);
+        assert_eq!(
+            counts(&stats.by_event_type),
+            [("rate_limit_exceeded", 3), ("whitelist_denied", 2)]
+        );
+        assert_eq!(
+            counts(&stats.by_severity),
+            [("warning", 4), ("critical", 1)]
+        );
+        // Capped at the two noisiest IPs, ties broken by value; events without an IP are left out
+        assert_eq!(
+            counts(&stats.top_client_ips),
+            [("10.0.0.1", 2), ("10.0.0.2", 1)]
+        );
+    }
+
+    #[test]
+    fn test_stats_window_defaults_to_last_day() {
+        let now = Utc::now();
+        assert_eq!(
+            stats_window(None, None, now).unwrap(),
+            (now - Duration::hours(24), now)
+        );
+
+        let to = now - Duration::hours(2);
+        assert_eq!(
+            stats_window(None, Some(to), now).unwrap(),
+            (to - Duration::hours(24), to)
+        );
+
+        let from = now - Duration::minutes(5);
+        assert_eq!(stats_window(Some(from), None, now).unwrap(), (from, now));
+    }
+
+    #[test]
+    fn test_stats_window_must_not_be_empty() {
+        let now = Utc::now();
+        assert!(matches!(
+            stats_window(Some(now), Some(now), now),
+            Err(KaratewayError::Validation(_))
+        ));
+        assert!(stats_window(Some(now + Duration::hours(1)), None, now).is_err());
+    }
 }
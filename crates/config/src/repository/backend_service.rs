@@ -1,12 +1,16 @@
 use karateway_core::{
-    models::{BackendService, BackendServices, CreateBackendServiceRequest, UpdateBackendServiceRequest},
+    models::{
+        BackendService, BackendServices, CreateBackendServiceRequest, UpdateBackendServiceRequest,
+    },
     KaratewayError, Result,
 };
 use sea_query::{Expr, Func, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
+use super::EntityCounts;
+
 #[derive(Clone)]
 pub struct BackendServiceRepository {
     pool: PgPool,
@@ -27,6 +31,19 @@ impl BackendServiceRepository {
                 BackendServices::HealthCheckUrl,
                 BackendServices::HealthCheckIntervalSeconds,
                 BackendServices::TimeoutMs,
+                BackendServices::DiscoveryType,
+                BackendServices::SrvName,
+                BackendServices::MaxConnections,
+                BackendServices::HealthCheckMethod,
+                BackendServices::HealthCheckHeaders,
+                BackendServices::HealthCheckBody,
+                BackendServices::TlsSni,
+                BackendServices::Capacity,
+                BackendServices::TlsClientCertPath,
+                BackendServices::TlsClientKeyPath,
+                BackendServices::LivenessPingIntervalSeconds,
+                BackendServices::MaxResponseHeaderBytes,
+                BackendServices::MaxResponseHeaderCount,
             ])
             .values_panic([
                 req.name.into(),
@@ -35,6 +52,21 @@ impl BackendServiceRepository {
                 req.health_check_url.into(),
                 req.health_check_interval_seconds.into(),
                 req.timeout_ms.into(),
+                req.discovery_type.unwrap_or_default().to_string().into(),
+                req.srv_name.into(),
+                req.max_connections.into(),
+                req.health_check_method
+                    .map(|method| method.to_string())
+                    .into(),
+                req.health_check_headers.into(),
+                req.health_check_body.into(),
+                req.tls_sni.into(),
+                req.capacity.into(),
+                req.tls_client_cert_path.into(),
+                req.tls_client_key_path.into(),
+                req.liveness_ping_interval_seconds.into(),
+                req.max_response_header_bytes.into(),
+                req.max_response_header_count.into(),
             ])
             .returning_all()
             .build_sqlx(PostgresQueryBuilder);
@@ -56,6 +88,19 @@ impl BackendServiceRepository {
                 BackendServices::HealthCheckUrl,
                 BackendServices::HealthCheckIntervalSeconds,
                 BackendServices::TimeoutMs,
+                BackendServices::DiscoveryType,
+                BackendServices::SrvName,
+                BackendServices::MaxConnections,
+                BackendServices::HealthCheckMethod,
+                BackendServices::HealthCheckHeaders,
+                BackendServices::HealthCheckBody,
+                BackendServices::TlsSni,
+                BackendServices::Capacity,
+                BackendServices::TlsClientCertPath,
+                BackendServices::TlsClientKeyPath,
+                BackendServices::LivenessPingIntervalSeconds,
+                BackendServices::MaxResponseHeaderBytes,
+                BackendServices::MaxResponseHeaderCount,
                 BackendServices::IsActive,
                 BackendServices::CreatedAt,
                 BackendServices::UpdatedAt,
@@ -84,6 +129,19 @@ impl BackendServiceRepository {
                 BackendServices::HealthCheckUrl,
                 BackendServices::HealthCheckIntervalSeconds,
                 BackendServices::TimeoutMs,
+                BackendServices::DiscoveryType,
+                BackendServices::SrvName,
+                BackendServices::MaxConnections,
+                BackendServices::HealthCheckMethod,
+                BackendServices::HealthCheckHeaders,
+                BackendServices::HealthCheckBody,
+                BackendServices::TlsSni,
+                BackendServices::Capacity,
+                BackendServices::TlsClientCertPath,
+                BackendServices::TlsClientKeyPath,
+                BackendServices::LivenessPingIntervalSeconds,
+                BackendServices::MaxResponseHeaderBytes,
+                BackendServices::MaxResponseHeaderCount,
                 BackendServices::IsActive,
                 BackendServices::CreatedAt,
                 BackendServices::UpdatedAt,
@@ -111,6 +169,19 @@ impl BackendServiceRepository {
                 BackendServices::HealthCheckUrl,
                 BackendServices::HealthCheckIntervalSeconds,
                 BackendServices::TimeoutMs,
+                BackendServices::DiscoveryType,
+                BackendServices::SrvName,
+                BackendServices::MaxConnections,
+                BackendServices::HealthCheckMethod,
+                BackendServices::HealthCheckHeaders,
+                BackendServices::HealthCheckBody,
+                BackendServices::TlsSni,
+                BackendServices::Capacity,
+                BackendServices::TlsClientCertPath,
+                BackendServices::TlsClientKeyPath,
+                BackendServices::LivenessPingIntervalSeconds,
+                BackendServices::MaxResponseHeaderBytes,
+                BackendServices::MaxResponseHeaderCount,
                 BackendServices::IsActive,
                 BackendServices::CreatedAt,
                 BackendServices::UpdatedAt,
@@ -141,6 +212,30 @@ impl BackendServiceRepository {
         Ok(count.0 as u64)
     }
 
+    pub async fn count_active(&self) -> Result<u64> {
+        let (sql, values) = Query::select()
+            .expr(Func::count(Expr::col(BackendServices::Id)))
+            .from(BackendServices::Table)
+            .and_where(Expr::col(BackendServices::IsActive).eq(true))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: (i64,) = sqlx::query_as_with(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0 as u64)
+    }
+
+    /// Total and active services in one query
+    pub async fn count_by_state(&self) -> Result<EntityCounts> {
+        super::count_by_state(
+            &self.pool,
+            BackendServices::Table,
+            BackendServices::IsActive,
+        )
+        .await
+    }
+
     pub async fn update(
         &self,
         id: Uuid,
@@ -168,6 +263,45 @@ impl BackendServiceRepository {
         if let Some(timeout) = req.timeout_ms {
             service.timeout_ms = Some(timeout);
         }
+        if let Some(discovery_type) = req.discovery_type {
+            service.discovery_type = discovery_type;
+        }
+        if let Some(srv_name) = req.srv_name {
+            service.srv_name = Some(srv_name);
+        }
+        if let Some(max_connections) = req.max_connections {
+            service.max_connections = Some(max_connections);
+        }
+        if let Some(health_check_method) = req.health_check_method {
+            service.health_check_method = Some(health_check_method);
+        }
+        if let Some(health_check_headers) = req.health_check_headers {
+            service.health_check_headers = Some(health_check_headers);
+        }
+        if let Some(health_check_body) = req.health_check_body {
+            service.health_check_body = Some(health_check_body);
+        }
+        if let Some(tls_sni) = req.tls_sni {
+            service.tls_sni = Some(tls_sni);
+        }
+        if let Some(capacity) = req.capacity {
+            service.capacity = Some(capacity);
+        }
+        if let Some(tls_client_cert_path) = req.tls_client_cert_path {
+            service.tls_client_cert_path = Some(tls_client_cert_path);
+        }
+        if let Some(tls_client_key_path) = req.tls_client_key_path {
+            service.tls_client_key_path = Some(tls_client_key_path);
+        }
+        if let Some(liveness_ping_interval_seconds) = req.liveness_ping_interval_seconds {
+            service.liveness_ping_interval_seconds = Some(liveness_ping_interval_seconds);
+        }
+        if let Some(max_response_header_bytes) = req.max_response_header_bytes {
+            service.max_response_header_bytes = Some(max_response_header_bytes);
+        }
+        if let Some(max_response_header_count) = req.max_response_header_count {
+            service.max_response_header_count = Some(max_response_header_count);
+        }
         if let Some(is_active) = req.is_active {
             service.is_active = is_active;
         }
@@ -177,11 +311,67 @@ impl BackendServiceRepository {
             .table(BackendServices::Table)
             .values([
                 (BackendServices::Name, service.name.clone().into()),
-                (BackendServices::Description, service.description.clone().into()),
+                (
+                    BackendServices::Description,
+                    service.description.clone().into(),
+                ),
                 (BackendServices::BaseUrl, service.base_url.clone().into()),
-                (BackendServices::HealthCheckUrl, service.health_check_url.clone().into()),
-                (BackendServices::HealthCheckIntervalSeconds, service.health_check_interval_seconds.into()),
+                (
+                    BackendServices::HealthCheckUrl,
+                    service.health_check_url.clone().into(),
+                ),
+                (
+                    BackendServices::HealthCheckIntervalSeconds,
+                    service.health_check_interval_seconds.into(),
+                ),
                 (BackendServices::TimeoutMs, service.timeout_ms.into()),
+                (
+                    BackendServices::DiscoveryType,
+                    service.discovery_type.to_string().into(),
+                ),
+                (BackendServices::SrvName, service.srv_name.clone().into()),
+                (
+                    BackendServices::MaxConnections,
+                    service.max_connections.into(),
+                ),
+                (
+                    BackendServices::HealthCheckMethod,
+                    service
+                        .health_check_method
+                        .as_ref()
+                        .map(|method| method.to_string())
+                        .into(),
+                ),
+                (
+                    BackendServices::HealthCheckHeaders,
+                    service.health_check_headers.clone().into(),
+                ),
+                (
+                    BackendServices::HealthCheckBody,
+                    service.health_check_body.clone().into(),
+                ),
+                (BackendServices::TlsSni, service.tls_sni.clone().into()),
+                (BackendServices::Capacity, service.capacity.into()),
+                (
+                    BackendServices::TlsClientCertPath,
+                    service.tls_client_cert_path.clone().into(),
+                ),
+                (
+                    BackendServices::TlsClientKeyPath,
+                    service.tls_client_key_path.clone().into(),
+                ),
+                (
+                    BackendServices::LivenessPingIntervalSeconds,
+                    service.liveness_ping_interval_seconds.into(),
+                ),
+                (
+                    BackendServices::MaxResponseHeaderBytes,
+                    service.max_response_header_bytes.into(),
+                ),
+                (
+                    BackendServices::MaxResponseHeaderCount,
+                    service.max_response_header_count.into(),
+                ),
                 (BackendServices::IsActive, service.is_active.into()),
             ])
             .and_where(Expr::col(BackendServices::Id).eq(id))
@@ -195,15 +385,14 @@ impl BackendServiceRepository {
         Ok(updated)
     }
 
-    pub async fn delete(&self, id: Uuid) -> Result<()> {
+    /// Delete service `id` on `executor`, the pool or a transaction
+    pub async fn delete(&self, executor: impl PgExecutor<'_>, id: Uuid) -> Result<()> {
         let (sql, values) = Query::delete()
             .from_table(BackendServices::Table)
             .and_where(Expr::col(BackendServices::Id).eq(id))
             .build_sqlx(PostgresQueryBuilder);
 
-        let result = sqlx::query_with(&sql, values)
-            .execute(&self.pool)
-            .await?;
+        let result = sqlx::query_with(&sql, values).execute(executor).await?;
 
         if result.rows_affected() == 0 {
             return Err(KaratewayError::NotFound(format!(
@@ -225,6 +414,19 @@ impl BackendServiceRepository {
                 BackendServices::HealthCheckUrl,
                 BackendServices::HealthCheckIntervalSeconds,
                 BackendServices::TimeoutMs,
+                BackendServices::DiscoveryType,
+                BackendServices::SrvName,
+                BackendServices::MaxConnections,
+                BackendServices::HealthCheckMethod,
+                BackendServices::HealthCheckHeaders,
+                BackendServices::HealthCheckBody,
+                BackendServices::TlsSni,
+                BackendServices::Capacity,
+                BackendServices::TlsClientCertPath,
+                BackendServices::TlsClientKeyPath,
+                BackendServices::LivenessPingIntervalSeconds,
+                BackendServices::MaxResponseHeaderBytes,
+                BackendServices::MaxResponseHeaderCount,
                 BackendServices::IsActive,
                 BackendServices::CreatedAt,
                 BackendServices::UpdatedAt,
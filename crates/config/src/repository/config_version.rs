@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use karateway_core::Result;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+/// Who automatic snapshots are recorded as created by
+pub const SNAPSHOT_CREATED_BY: &str = "admin-api";
+
+#[derive(Clone)]
+pub struct ConfigVersionRepository {
+    pool: PgPool,
+}
+
+impl ConfigVersionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store the config as a new version with `create_config_snapshot`, returning its id
+    ///
+    /// Run it on the transaction of the change it guards, so the snapshot and
+    /// the change are committed or rolled back together.
+    pub async fn create_snapshot(
+        &self,
+        executor: impl PgExecutor<'_>,
+        version_name: &str,
+        description: &str,
+        created_by: &str,
+    ) -> Result<Uuid> {
+        let id = sqlx::query_scalar::<_, Uuid>("SELECT create_config_snapshot($1, $2, $3)")
+            .bind(version_name)
+            .bind(description)
+            .bind(created_by)
+            .fetch_one(executor)
+            .await?;
+
+        Ok(id)
+    }
+}
+
+/// Version name of an automatic snapshot, e.g. `pre-delete-20261014T093000.123456Z`
+///
+/// Down to the microsecond, since version names are unique.
+pub fn snapshot_name(operation: &str, now: DateTime<Utc>) -> String {
+    format!("{}-{}", operation, now.format("%Y%m%dT%H%M%S%.6fZ"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{tests::test_db, BackendServiceRepository};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_snapshot_name() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 14, 9, 30, 0).unwrap();
+        assert_eq!(
+            snapshot_name("pre-delete", now),
+            "pre-delete-20261014T093000.000000Z"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored"]
+    async fn test_snapshot_is_rolled_back_with_its_delete() {
+        let db = test_db().await;
+        let pool = db.pool.clone();
+        let repo = ConfigVersionRepository::new(pool.clone());
+        let services = BackendServiceRepository::new(pool.clone());
+        let disabled = Uuid::new_v4();
+        sqlx::query(
+            "INSERT INTO backend_services (id, name, base_url, is_active) \
+             VALUES ($1, 'disabled', 'http://disabled:8080', false)",
+        )
+        .bind(disabled)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // A failed delete takes its snapshot with it
+        let mut tx = pool.begin().await.unwrap();
+        repo.create_snapshot(&mut *tx, "pre-delete-1", "missing", SNAPSHOT_CREATED_BY)
+            .await
+            .unwrap();
+        assert!(services.delete(&mut *tx, Uuid::new_v4()).await.is_err());
+        drop(tx);
+        let versions: i64 = sqlx::query_scalar("SELECT count(*) FROM config_versions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(versions, 0);
+
+        let mut tx = pool.begin().await.unwrap();
+        let id = repo
+            .create_snapshot(&mut *tx, "pre-delete-2", "disabled", SNAPSHOT_CREATED_BY)
+            .await
+            .unwrap();
+        services.delete(&mut *tx, disabled).await.unwrap();
+        tx.commit().await.unwrap();
+
+        // Disabled rows are part of the restore point
+        let snapshot: serde_json::Value =
+            sqlx::query_scalar("SELECT config_snapshot FROM config_versions WHERE id = $1")
+                .bind(id)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(snapshot["backend_services"][0]["name"], "disabled");
+        assert_eq!(snapshot["backend_services"][0]["is_active"], false);
+    }
+}
@@ -0,0 +1,207 @@
+use karateway_core::{
+    models::{
+        CreateMetricTagRuleRequest, MetricTagRule, MetricTagRules, UpdateMetricTagRuleRequest,
+    },
+    KaratewayError, Result,
+};
+use sea_query::{Expr, Func, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct MetricTagRuleRepository {
+    pool: PgPool,
+}
+
+impl MetricTagRuleRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, req: CreateMetricTagRuleRequest) -> Result<MetricTagRule> {
+        let (sql, values) = Query::insert()
+            .into_table(MetricTagRules::Table)
+            .columns([
+                MetricTagRules::Tag,
+                MetricTagRules::MatchType,
+                MetricTagRules::HeaderName,
+                MetricTagRules::Pattern,
+                MetricTagRules::Priority,
+            ])
+            .values_panic([
+                req.tag.into(),
+                req.match_type.to_string().into(),
+                req.header_name.into(),
+                req.pattern.into(),
+                req.priority.unwrap_or(0).into(),
+            ])
+            .returning_all()
+            .build_sqlx(PostgresQueryBuilder);
+
+        let rule = sqlx::query_as_with::<_, MetricTagRule, _>(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(rule)
+    }
+
+    pub async fn find_by_id(&self, id: Uuid) -> Result<MetricTagRule> {
+        let (sql, values) = Query::select()
+            .columns([
+                MetricTagRules::Id,
+                MetricTagRules::Tag,
+                MetricTagRules::MatchType,
+                MetricTagRules::HeaderName,
+                MetricTagRules::Pattern,
+                MetricTagRules::IsActive,
+                MetricTagRules::Priority,
+                MetricTagRules::CreatedAt,
+                MetricTagRules::UpdatedAt,
+            ])
+            .from(MetricTagRules::Table)
+            .and_where(Expr::col(MetricTagRules::Id).eq(id))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let rule = sqlx::query_as_with::<_, MetricTagRule, _>(&sql, values)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| {
+                KaratewayError::NotFound(format!("Metric tag rule with id {} not found", id))
+            })?;
+
+        Ok(rule)
+    }
+
+    pub async fn list(&self, page: u32, limit: u32) -> Result<Vec<MetricTagRule>> {
+        let offset = (page.saturating_sub(1)) * limit;
+
+        let (sql, values) = Query::select()
+            .columns([
+                MetricTagRules::Id,
+                MetricTagRules::Tag,
+                MetricTagRules::MatchType,
+                MetricTagRules::HeaderName,
+                MetricTagRules::Pattern,
+                MetricTagRules::IsActive,
+                MetricTagRules::Priority,
+                MetricTagRules::CreatedAt,
+                MetricTagRules::UpdatedAt,
+            ])
+            .from(MetricTagRules::Table)
+            .order_by(MetricTagRules::Priority, sea_query::Order::Desc)
+            .order_by(MetricTagRules::CreatedAt, sea_query::Order::Desc)
+            .limit(limit as u64)
+            .offset(offset as u64)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let rules = sqlx::query_as_with::<_, MetricTagRule, _>(&sql, values)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rules)
+    }
+
+    pub async fn count(&self) -> Result<u64> {
+        let (sql, values) = Query::select()
+            .expr(Func::count(Expr::col(MetricTagRules::Id)))
+            .from(MetricTagRules::Table)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: (i64,) = sqlx::query_as_with(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0 as u64)
+    }
+
+    pub async fn update(&self, id: Uuid, req: UpdateMetricTagRuleRequest) -> Result<MetricTagRule> {
+        let mut rule = self.find_by_id(id).await?;
+
+        if let Some(tag) = req.tag {
+            rule.tag = tag;
+        }
+        if let Some(match_type) = req.match_type {
+            rule.match_type = match_type;
+        }
+        if let Some(header_name) = req.header_name {
+            rule.header_name = Some(header_name);
+        }
+        if let Some(pattern) = req.pattern {
+            rule.pattern = pattern;
+        }
+        if let Some(is_active) = req.is_active {
+            rule.is_active = is_active;
+        }
+        if let Some(priority) = req.priority {
+            rule.priority = priority;
+        }
+
+        let (sql, values) = Query::update()
+            .table(MetricTagRules::Table)
+            .values([
+                (MetricTagRules::Tag, rule.tag.clone().into()),
+                (
+                    MetricTagRules::MatchType,
+                    rule.match_type.to_string().into(),
+                ),
+                (MetricTagRules::HeaderName, rule.header_name.clone().into()),
+                (MetricTagRules::Pattern, rule.pattern.clone().into()),
+                (MetricTagRules::IsActive, rule.is_active.into()),
+                (MetricTagRules::Priority, rule.priority.into()),
+            ])
+            .and_where(Expr::col(MetricTagRules::Id).eq(id))
+            .returning_all()
+            .build_sqlx(PostgresQueryBuilder);
+
+        let updated = sqlx::query_as_with::<_, MetricTagRule, _>(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(updated)
+    }
+
+    pub async fn delete(&self, id: Uuid) -> Result<()> {
+        let (sql, values) = Query::delete()
+            .from_table(MetricTagRules::Table)
+            .and_where(Expr::col(MetricTagRules::Id).eq(id))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let result = sqlx::query_with(&sql, values).execute(&self.pool).await?;
+
+        if result.rows_affected() == 0 {
+            return Err(KaratewayError::NotFound(format!(
+                "Metric tag rule with id {} not found",
+                id
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_active(&self) -> Result<Vec<MetricTagRule>> {
+        let (sql, values) = Query::select()
+            .columns([
+                MetricTagRules::Id,
+                MetricTagRules::Tag,
+                MetricTagRules::MatchType,
+                MetricTagRules::HeaderName,
+                MetricTagRules::Pattern,
+                MetricTagRules::IsActive,
+                MetricTagRules::Priority,
+                MetricTagRules::CreatedAt,
+                MetricTagRules::UpdatedAt,
+            ])
+            .from(MetricTagRules::Table)
+            .and_where(Expr::col(MetricTagRules::IsActive).eq(true))
+            .order_by(MetricTagRules::Priority, sea_query::Order::Desc)
+            .order_by(MetricTagRules::CreatedAt, sea_query::Order::Asc)
+            .build_sqlx(PostgresQueryBuilder);
+
+        let rules = sqlx::query_as_with::<_, MetricTagRule, _>(&sql, values)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rules)
+    }
+}
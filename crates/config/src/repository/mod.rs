@@ -1,11 +1,311 @@
 pub mod api_route;
 pub mod backend_service;
+pub mod config_version;
+pub mod metric_tag_rule;
 pub mod rate_limit;
 pub mod whitelist_rule;
 pub mod audit_log;
 
 pub use api_route::ApiRouteRepository;
 pub use backend_service::BackendServiceRepository;
+pub use config_version::ConfigVersionRepository;
+pub use metric_tag_rule::MetricTagRuleRepository;
 pub use rate_limit::RateLimitRepository;
 pub use whitelist_rule::WhitelistRuleRepository;
 pub use audit_log::AuditLogRepository;
+
+use karateway_core::{KaratewayError, Result};
+use sea_query::{Expr, Iden, PostgresQueryBuilder, Query};
+use sea_query_binder::{SqlxBinder, SqlxValues};
+use sqlx::PgPool;
+
+/// How many rows of a table there are, and how many of them are active
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntityCounts {
+    pub total: u64,
+    pub active: u64,
+}
+
+impl EntityCounts {
+    pub fn inactive(&self) -> u64 {
+        self.total.saturating_sub(self.active)
+    }
+}
+
+/// Both counts of a table, active ones by its `is_active` column, in one scan
+fn count_by_state_query<T: Iden + 'static>(table: T, is_active: T) -> (String, SqlxValues) {
+    Query::select()
+        .expr(Expr::cust("COUNT(*)"))
+        .expr(Expr::cust_with_expr(
+            "COUNT(*) FILTER (WHERE $1)",
+            Expr::col(is_active),
+        ))
+        .from(table)
+        .build_sqlx(PostgresQueryBuilder)
+}
+
+pub(crate) async fn count_by_state<T: Iden + 'static>(
+    pool: &PgPool,
+    table: T,
+    is_active: T,
+) -> Result<EntityCounts> {
+    let (sql, values) = count_by_state_query(table, is_active);
+    let (total, active): (i64, i64) = sqlx::query_as_with(&sql, values).fetch_one(pool).await?;
+
+    Ok(EntityCounts {
+        total: total as u64,
+        active: active as u64,
+    })
+}
+
+/// Map a unique-constraint violation on a name to a 409 naming the duplicate
+///
+/// Any other error is passed through unchanged.
+pub(crate) fn conflict_on_duplicate(err: sqlx::Error, entity: &str, name: &str) -> KaratewayError {
+    match &err {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            KaratewayError::Conflict(format!("{} with name '{}' already exists", entity, name))
+        }
+        _ => err.into(),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use karateway_core::models::ApiRoutes;
+    use migration::sea_orm::SqlxPostgresConnector;
+    use migration::{Migrator, MigratorTrait};
+    use sqlx::error::{DatabaseError, ErrorKind};
+    use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+    use std::borrow::Cow;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    /// Why the tests of real queries only run on request
+    pub(crate) const NEEDS_DATABASE: &str =
+        "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored";
+
+    /// A fresh schema with every migration applied, for tests of real queries
+    ///
+    /// Each test gets a schema of its own, so seeded rows never meet, and the
+    /// schema is dropped again when the guard goes out of scope.
+    pub(crate) struct TestDb {
+        pub pool: PgPool,
+        url: String,
+        schema: String,
+    }
+
+    impl Drop for TestDb {
+        fn drop(&mut self) {
+            let url = self.url.clone();
+            let drop_schema = format!("DROP SCHEMA {} CASCADE", self.schema);
+            // Drop can't await, and the test's runtime may already be winding down
+            let cleanup = std::thread::spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(async {
+                        let admin = PgPool::connect(&url).await?;
+                        sqlx::query(&drop_schema).execute(&admin).await?;
+                        Ok::<_, sqlx::Error>(())
+                    })
+            });
+            if let Ok(Err(e)) = cleanup.join() {
+                eprintln!("Failed to drop test schema {}: {}", self.schema, e);
+            }
+        }
+    }
+
+    /// Connect to `TEST_DATABASE_URL`, failing the test loudly when it isn't set
+    ///
+    /// Tests using it are `#[ignore]`d with [`NEEDS_DATABASE`], so a plain
+    /// `cargo test` doesn't report them as passed.
+    pub(crate) async fn test_db() -> TestDb {
+        let url = std::env::var("TEST_DATABASE_URL").expect(NEEDS_DATABASE);
+        let schema = format!("test_{}", Uuid::new_v4().simple());
+
+        let admin = PgPool::connect(&url)
+            .await
+            .expect("TEST_DATABASE_URL is unreachable");
+        sqlx::query(&format!("CREATE SCHEMA {}", schema))
+            .execute(&admin)
+            .await
+            .unwrap();
+
+        let options = PgConnectOptions::from_str(&url)
+            .unwrap()
+            .options([("search_path", format!("{},public", schema))]);
+        let pool = PgPoolOptions::new().connect_with(options).await.unwrap();
+        Migrator::up(
+            &SqlxPostgresConnector::from_sqlx_postgres_pool(pool.clone()),
+            None,
+        )
+        .await
+        .unwrap();
+
+        TestDb { pool, url, schema }
+    }
+
+    /// Stand-in for a Postgres error, a unique violation or some other one
+    #[derive(Debug)]
+    struct FakeDbError {
+        unique: bool,
+    }
+
+    impl std::fmt::Display for FakeDbError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "fake database error")
+        }
+    }
+
+    impl std::error::Error for FakeDbError {}
+
+    impl DatabaseError for FakeDbError {
+        fn message(&self) -> &str {
+            "duplicate key value violates unique constraint"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            None
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            if self.unique {
+                ErrorKind::UniqueViolation
+            } else {
+                ErrorKind::ForeignKeyViolation
+            }
+        }
+    }
+
+    fn db_error(unique: bool) -> sqlx::Error {
+        sqlx::Error::Database(Box::new(FakeDbError { unique }))
+    }
+
+    #[test]
+    fn test_duplicate_names_are_conflicts() {
+        let err = conflict_on_duplicate(db_error(true), "Rate limit", "per-ip");
+        assert_eq!(err.status_code(), 409);
+        assert_eq!(
+            err.to_string(),
+            "Conflict: Rate limit with name 'per-ip' already exists"
+        );
+
+        let err = conflict_on_duplicate(db_error(true), "Whitelist rule", "office");
+        assert_eq!(err.status_code(), 409);
+        assert_eq!(
+            err.to_string(),
+            "Conflict: Whitelist rule with name 'office' already exists"
+        );
+    }
+
+    #[test]
+    fn test_counts_split_active_and_inactive_rows_in_one_query() {
+        let (sql, _) = count_by_state_query(ApiRoutes::Table, ApiRoutes::IsActive);
+        assert_eq!(
+            sql,
+            r#"SELECT COUNT(*), COUNT(*) FILTER (WHERE "is_active") FROM "api_routes""#
+        );
+
+        let counts = EntityCounts {
+            total: 5,
+            active: 3,
+        };
+        assert_eq!(counts.inactive(), 2);
+        assert_eq!(EntityCounts::default().inactive(), 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored"]
+    async fn test_counts_of_seeded_rows_with_mixed_states() {
+        let db = test_db().await;
+        let pool = db.pool.clone();
+
+        let mut backend_ids = Vec::new();
+        for (name, is_active) in [("orders", true), ("users", true), ("legacy", false)] {
+            let id = Uuid::new_v4();
+            sqlx::query(
+                "INSERT INTO backend_services (id, name, base_url, is_active) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(id)
+            .bind(name)
+            .bind(format!("http://{}:8080", name))
+            .bind(is_active)
+            .execute(&pool)
+            .await
+            .unwrap();
+            backend_ids.push(id);
+        }
+        for (path, is_active) in [("/orders", true), ("/users", false), ("/legacy", false)] {
+            sqlx::query(
+                "INSERT INTO api_routes (id, path_pattern, method, backend_service_id, is_active) \
+                 VALUES ($1, $2, 'GET', $3, $4)",
+            )
+            .bind(Uuid::new_v4())
+            .bind(path)
+            .bind(backend_ids[0])
+            .bind(is_active)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let backends = BackendServiceRepository::new(pool.clone())
+            .count_by_state()
+            .await
+            .unwrap();
+        assert_eq!(
+            backends,
+            EntityCounts {
+                total: 3,
+                active: 2
+            }
+        );
+
+        let routes = ApiRouteRepository::new(pool.clone())
+            .count_by_state()
+            .await
+            .unwrap();
+        assert_eq!(
+            routes,
+            EntityCounts {
+                total: 3,
+                active: 1
+            }
+        );
+        assert_eq!(routes.inactive(), 2);
+
+        // No rows at all
+        let rate_limits = RateLimitRepository::new(pool)
+            .count_by_state()
+            .await
+            .unwrap();
+        assert_eq!(rate_limits, EntityCounts::default());
+    }
+
+    #[test]
+    fn test_other_errors_pass_through() {
+        let err = conflict_on_duplicate(db_error(false), "Rate limit", "a");
+        assert_eq!(err.status_code(), 500);
+
+        let err = conflict_on_duplicate(sqlx::Error::RowNotFound, "Rate limit", "a");
+        assert!(matches!(
+            err,
+            KaratewayError::Database(sqlx::Error::RowNotFound)
+        ));
+    }
+}
@@ -4,9 +4,11 @@ use karateway_core::{
 };
 use sea_query::{Expr, Func, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
+use super::{conflict_on_duplicate, EntityCounts};
+
 #[derive(Clone)]
 pub struct RateLimitRepository {
     pool: PgPool,
@@ -18,6 +20,7 @@ impl RateLimitRepository {
     }
 
     pub async fn create(&self, req: CreateRateLimitRequest) -> Result<RateLimit> {
+        let name = req.name.clone();
         let (sql, values) = Query::insert()
             .into_table(RateLimits::Table)
             .columns([
@@ -26,7 +29,10 @@ impl RateLimitRepository {
                 RateLimits::MaxRequests,
                 RateLimits::WindowSeconds,
                 RateLimits::IdentifierType,
+                RateLimits::IdentifierHeader,
                 RateLimits::BurstSize,
+                RateLimits::CapacityFactor,
+                RateLimits::OverrideGlobal,
             ])
             .values_panic([
                 req.name.into(),
@@ -34,14 +40,18 @@ impl RateLimitRepository {
                 req.max_requests.into(),
                 req.window_seconds.into(),
                 req.identifier_type.to_string().into(),
+                req.identifier_header.into(),
                 req.burst_size.into(),
+                req.capacity_factor.into(),
+                req.override_global.into(),
             ])
             .returning_all()
             .build_sqlx(PostgresQueryBuilder);
 
         let limit = sqlx::query_as_with::<_, RateLimit, _>(&sql, values)
             .fetch_one(&self.pool)
-            .await?;
+            .await
+            .map_err(|e| conflict_on_duplicate(e, "Rate limit", &name))?;
 
         Ok(limit)
     }
@@ -55,8 +65,11 @@ impl RateLimitRepository {
                 RateLimits::MaxRequests,
                 RateLimits::WindowSeconds,
                 RateLimits::IdentifierType,
+                RateLimits::IdentifierHeader,
                 RateLimits::IsActive,
                 RateLimits::BurstSize,
+                RateLimits::CapacityFactor,
+                RateLimits::OverrideGlobal,
                 RateLimits::CreatedAt,
                 RateLimits::UpdatedAt,
             ])
@@ -83,8 +96,11 @@ impl RateLimitRepository {
                 RateLimits::MaxRequests,
                 RateLimits::WindowSeconds,
                 RateLimits::IdentifierType,
+                RateLimits::IdentifierHeader,
                 RateLimits::IsActive,
                 RateLimits::BurstSize,
+                RateLimits::CapacityFactor,
+                RateLimits::OverrideGlobal,
                 RateLimits::CreatedAt,
                 RateLimits::UpdatedAt,
             ])
@@ -114,6 +130,25 @@ impl RateLimitRepository {
         Ok(count.0 as u64)
     }
 
+    pub async fn count_active(&self) -> Result<u64> {
+        let (sql, values) = Query::select()
+            .expr(Func::count(Expr::col(RateLimits::Id)))
+            .from(RateLimits::Table)
+            .and_where(Expr::col(RateLimits::IsActive).eq(true))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: (i64,) = sqlx::query_as_with(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0 as u64)
+    }
+
+    /// Total and active rate limits in one query
+    pub async fn count_by_state(&self) -> Result<EntityCounts> {
+        super::count_by_state(&self.pool, RateLimits::Table, RateLimits::IsActive).await
+    }
+
     pub async fn list_by_route(&self, api_route_id: Uuid) -> Result<Vec<RateLimit>> {
         let (sql, values) = Query::select()
             .columns([
@@ -123,8 +158,11 @@ impl RateLimitRepository {
                 RateLimits::MaxRequests,
                 RateLimits::WindowSeconds,
                 RateLimits::IdentifierType,
+                RateLimits::IdentifierHeader,
                 RateLimits::IsActive,
                 RateLimits::BurstSize,
+                RateLimits::CapacityFactor,
+                RateLimits::OverrideGlobal,
                 RateLimits::CreatedAt,
                 RateLimits::UpdatedAt,
             ])
@@ -159,12 +197,21 @@ impl RateLimitRepository {
         if let Some(identifier_type) = req.identifier_type {
             limit.identifier_type = identifier_type;
         }
+        if let Some(identifier_header) = req.identifier_header {
+            limit.identifier_header = Some(identifier_header);
+        }
         if let Some(is_active) = req.is_active {
             limit.is_active = is_active;
         }
         if let Some(burst_size) = req.burst_size {
             limit.burst_size = Some(burst_size);
         }
+        if let Some(capacity_factor) = req.capacity_factor {
+            limit.capacity_factor = Some(capacity_factor);
+        }
+        if let Some(override_global) = req.override_global {
+            limit.override_global = override_global;
+        }
 
         let (sql, values) = Query::update()
             .table(RateLimits::Table)
@@ -174,8 +221,14 @@ impl RateLimitRepository {
                 (RateLimits::MaxRequests, limit.max_requests.into()),
                 (RateLimits::WindowSeconds, limit.window_seconds.into()),
                 (RateLimits::IdentifierType, limit.identifier_type.to_string().into()),
+                (
+                    RateLimits::IdentifierHeader,
+                    limit.identifier_header.clone().into(),
+                ),
                 (RateLimits::IsActive, limit.is_active.into()),
                 (RateLimits::BurstSize, limit.burst_size.into()),
+                (RateLimits::CapacityFactor, limit.capacity_factor.into()),
+                (RateLimits::OverrideGlobal, limit.override_global.into()),
             ])
             .and_where(Expr::col(RateLimits::Id).eq(id))
             .returning_all()
@@ -183,7 +236,8 @@ impl RateLimitRepository {
 
         let updated = sqlx::query_as_with::<_, RateLimit, _>(&sql, values)
             .fetch_one(&self.pool)
-            .await?;
+            .await
+            .map_err(|e| conflict_on_duplicate(e, "Rate limit", &limit.name))?;
 
         Ok(updated)
     }
@@ -208,6 +262,22 @@ impl RateLimitRepository {
         Ok(())
     }
 
+    /// Delete all rate limits belonging to `api_route_id`, returning how many were removed
+    pub async fn delete_by_api_route(
+        &self,
+        executor: impl PgExecutor<'_>,
+        api_route_id: Uuid,
+    ) -> Result<u64> {
+        let (sql, values) = Query::delete()
+            .from_table(RateLimits::Table)
+            .and_where(Expr::col(RateLimits::ApiRouteId).eq(api_route_id))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let result = sqlx::query_with(&sql, values).execute(executor).await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn list_active(&self) -> Result<Vec<RateLimit>> {
         let (sql, values) = Query::select()
             .columns([
@@ -217,8 +287,11 @@ impl RateLimitRepository {
                 RateLimits::MaxRequests,
                 RateLimits::WindowSeconds,
                 RateLimits::IdentifierType,
+                RateLimits::IdentifierHeader,
                 RateLimits::IsActive,
                 RateLimits::BurstSize,
+                RateLimits::CapacityFactor,
+                RateLimits::OverrideGlobal,
                 RateLimits::CreatedAt,
                 RateLimits::UpdatedAt,
             ])
@@ -4,9 +4,11 @@ use karateway_core::{
 };
 use sea_query::{Expr, Func, PostgresQueryBuilder, Query};
 use sea_query_binder::SqlxBinder;
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 use uuid::Uuid;
 
+use super::{conflict_on_duplicate, EntityCounts};
+
 #[derive(Clone)]
 pub struct WhitelistRuleRepository {
     pool: PgPool,
@@ -18,6 +20,7 @@ impl WhitelistRuleRepository {
     }
 
     pub async fn create(&self, req: CreateWhitelistRuleRequest) -> Result<WhitelistRule> {
+        let name = req.rule_name.clone();
         let (sql, values) = Query::insert()
             .into_table(WhitelistRules::Table)
             .columns([
@@ -39,7 +42,8 @@ impl WhitelistRuleRepository {
 
         let rule = sqlx::query_as_with::<_, WhitelistRule, _>(&sql, values)
             .fetch_one(&self.pool)
-            .await?;
+            .await
+            .map_err(|e| conflict_on_duplicate(e, "Whitelist rule", &name))?;
 
         Ok(rule)
     }
@@ -113,6 +117,25 @@ impl WhitelistRuleRepository {
         Ok(count.0 as u64)
     }
 
+    pub async fn count_active(&self) -> Result<u64> {
+        let (sql, values) = Query::select()
+            .expr(Func::count(Expr::col(WhitelistRules::Id)))
+            .from(WhitelistRules::Table)
+            .and_where(Expr::col(WhitelistRules::IsActive).eq(true))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let count: (i64,) = sqlx::query_as_with(&sql, values)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0 as u64)
+    }
+
+    /// Total and active whitelist rules in one query
+    pub async fn count_by_state(&self) -> Result<EntityCounts> {
+        super::count_by_state(&self.pool, WhitelistRules::Table, WhitelistRules::IsActive).await
+    }
+
     pub async fn list_by_route(&self, api_route_id: Uuid) -> Result<Vec<WhitelistRule>> {
         let (sql, values) = Query::select()
             .columns([
@@ -177,7 +200,8 @@ impl WhitelistRuleRepository {
 
         let updated = sqlx::query_as_with::<_, WhitelistRule, _>(&sql, values)
             .fetch_one(&self.pool)
-            .await?;
+            .await
+            .map_err(|e| conflict_on_duplicate(e, "Whitelist rule", &rule.rule_name))?;
 
         Ok(updated)
     }
@@ -202,6 +226,22 @@ impl WhitelistRuleRepository {
         Ok(())
     }
 
+    /// Delete all whitelist rules belonging to `api_route_id`, returning how many were removed
+    pub async fn delete_by_api_route(
+        &self,
+        executor: impl PgExecutor<'_>,
+        api_route_id: Uuid,
+    ) -> Result<u64> {
+        let (sql, values) = Query::delete()
+            .from_table(WhitelistRules::Table)
+            .and_where(Expr::col(WhitelistRules::ApiRouteId).eq(api_route_id))
+            .build_sqlx(PostgresQueryBuilder);
+
+        let result = sqlx::query_with(&sql, values).execute(executor).await?;
+
+        Ok(result.rows_affected())
+    }
+
     pub async fn list_active(&self) -> Result<Vec<WhitelistRule>> {
         let (sql, values) = Query::select()
             .columns([
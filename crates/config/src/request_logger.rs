@@ -0,0 +1,200 @@
+use karateway_core::models::{GatewayMetric, GatewayMetrics};
+use sea_query::{PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use sqlx::PgPool;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+/// Requests buffered for the worker before new ones are dropped
+///
+/// Unlike audit events these are written for every request, so the queue is
+/// bounded to keep a slow database from growing the gateway's memory.
+pub const QUEUE_CAPACITY: usize = 10_000;
+
+/// Writes one `gateway_metrics` row per proxied request from a background worker
+#[derive(Clone)]
+pub struct RequestLogger {
+    tx: mpsc::Sender<GatewayMetric>,
+}
+
+impl RequestLogger {
+    /// Create a new request logger with a background worker
+    pub fn new(pool: PgPool) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+
+        tokio::spawn(request_log_worker(pool, rx));
+
+        Self { tx }
+    }
+
+    /// Log a finished request (non-blocking, dropped when the queue is full)
+    pub fn log(&self, metric: GatewayMetric) {
+        if let Err(e) = self.tx.try_send(metric) {
+            warn!("Dropping request log entry: {}", e);
+        }
+    }
+}
+
+/// Background worker that writes request logs to the database
+async fn request_log_worker(pool: PgPool, mut rx: mpsc::Receiver<GatewayMetric>) {
+    info!("Request log worker started");
+
+    while let Some(mut metric) = rx.recv().await {
+        let mut saved = save_request_log(&pool, &metric).await;
+        // The matched route or backend may have been deleted since the request
+        if saved.as_ref().is_err_and(is_foreign_key_violation) {
+            detach_deleted_config(&mut metric);
+            saved = save_request_log(&pool, &metric).await;
+        }
+        if let Err(e) = saved {
+            error!("Failed to save request log to database: {}", e);
+        }
+    }
+
+    info!("Request log worker stopped");
+}
+
+/// Save a request, with its latency, response size and error, as a single row
+async fn save_request_log(pool: &PgPool, metric: &GatewayMetric) -> Result<(), sqlx::Error> {
+    let (sql, values) = Query::insert()
+        .into_table(GatewayMetrics::Table)
+        .columns([
+            GatewayMetrics::Id,
+            GatewayMetrics::Timestamp,
+            GatewayMetrics::RouteId,
+            GatewayMetrics::Method,
+            GatewayMetrics::Path,
+            GatewayMetrics::StatusCode,
+            GatewayMetrics::ResponseTimeMs,
+            GatewayMetrics::BackendServiceId,
+            GatewayMetrics::ErrorMessage,
+            GatewayMetrics::Metadata,
+        ])
+        .values_panic([
+            metric.id.into(),
+            metric.timestamp.into(),
+            metric.route_id.into(),
+            metric.method.clone().into(),
+            metric.path.clone().into(),
+            metric.status_code.into(),
+            metric.response_time_ms.into(),
+            metric.backend_service_id.into(),
+            metric.error_message.clone().into(),
+            metric.metadata.clone().into(),
+        ])
+        .build_sqlx(PostgresQueryBuilder);
+
+    sqlx::query_with(&sql, values).execute(pool).await?;
+
+    Ok(())
+}
+
+fn is_foreign_key_violation(error: &sqlx::Error) -> bool {
+    matches!(error, sqlx::Error::Database(e) if e.is_foreign_key_violation())
+}
+
+/// Drop a row's references to the route and backend, keeping their ids in `metadata`
+///
+/// Deleting a route or backend sets the references of its existing rows to
+/// NULL; this does the same for a row that was still queued at the time.
+fn detach_deleted_config(metric: &mut GatewayMetric) {
+    if let Some(route_id) = metric.route_id.take() {
+        metric.metadata["deleted_route_id"] = route_id.to_string().into();
+    }
+    if let Some(backend_service_id) = metric.backend_service_id.take() {
+        metric.metadata["deleted_backend_service_id"] = backend_service_id.to_string().into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::tests::test_db;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_deleted_config_is_detached_but_kept_in_metadata() {
+        let route_id = Uuid::new_v4();
+        let backend_service_id = Uuid::new_v4();
+        let mut metric = GatewayMetric {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            route_id: Some(route_id),
+            method: Some("GET".to_string()),
+            path: Some("/orders".to_string()),
+            status_code: Some(200),
+            response_time_ms: Some(12.0),
+            backend_service_id: Some(backend_service_id),
+            error_message: None,
+            metadata: serde_json::json!({ "response_bytes": 64 }),
+        };
+
+        detach_deleted_config(&mut metric);
+
+        assert_eq!(metric.route_id, None);
+        assert_eq!(metric.backend_service_id, None);
+        assert_eq!(metric.metadata["deleted_route_id"], route_id.to_string());
+        assert_eq!(
+            metric.metadata["deleted_backend_service_id"],
+            backend_service_id.to_string()
+        );
+        assert_eq!(metric.metadata["response_bytes"], 64);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres: set TEST_DATABASE_URL and run cargo test -- --ignored"]
+    async fn test_tag_round_trips_through_metadata() {
+        let db = test_db().await;
+        let pool = db.pool.clone();
+        // Logged like the gateway logs a tagged request, for a route deleted meanwhile
+        let deleted_route_id = Uuid::new_v4();
+        let metric = GatewayMetric {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            route_id: Some(deleted_route_id),
+            method: Some("GET".to_string()),
+            path: Some("/admin/users".to_string()),
+            status_code: Some(200),
+            response_time_ms: Some(3.5),
+            backend_service_id: None,
+            error_message: None,
+            metadata: serde_json::json!({ "response_bytes": 64, "tag": "admin" }),
+        };
+
+        RequestLogger::new(pool.clone()).log(metric.clone());
+
+        let mut stored = None;
+        for _ in 0..50 {
+            stored =
+                sqlx::query_as::<_, GatewayMetric>("SELECT * FROM gateway_metrics WHERE id = $1")
+                    .bind(metric.id)
+                    .fetch_optional(&pool)
+                    .await
+                    .unwrap();
+            if stored.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        let stored = stored.expect("the worker never wrote the row");
+
+        // The tag survives the retry that detaches the deleted route
+        assert_eq!(stored.route_id, None);
+        assert_eq!(
+            stored.metadata,
+            serde_json::json!({
+                "response_bytes": 64,
+                "tag": "admin",
+                "deleted_route_id": deleted_route_id.to_string(),
+            })
+        );
+        let tagged: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM gateway_metrics WHERE metadata->>'tag' = 'admin'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(tagged, 1);
+    }
+}
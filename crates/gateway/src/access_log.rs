@@ -0,0 +1,84 @@
+use tracing::info;
+
+/// What the access log line says about a finished request
+#[derive(Debug, Clone, Copy)]
+pub struct AccessLogEntry<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    /// 0 when no response was written
+    pub status: u16,
+    /// `host:port/path` the request was sent to
+    pub upstream: &'a str,
+}
+
+/// Write the `Request completed` line, unless the matched route turned it off
+///
+/// The fields are structured, so JSON logging gets them as their own keys.
+/// Metrics and the request log are recorded either way.
+pub fn record(enabled: bool, entry: AccessLogEntry<'_>) {
+    if !enabled {
+        return;
+    }
+
+    info!(
+        method = %entry.method,
+        path = %entry.path,
+        status = entry.status,
+        upstream = %entry.upstream,
+        "Request completed"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn logged(enabled: bool) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            record(
+                enabled,
+                AccessLogEntry {
+                    method: "GET",
+                    path: "/health",
+                    status: 200,
+                    upstream: "orders:9000/health",
+                },
+            )
+        });
+        let output = captured.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_routes_with_access_log_disabled_log_nothing() {
+        let line = logged(true);
+        assert!(line.contains("Request completed"));
+        assert!(line.contains("path=/health"));
+        assert!(line.contains("status=200"));
+
+        assert_eq!(logged(false), "");
+    }
+}
@@ -0,0 +1,199 @@
+use http::HeaderMap;
+use tracing::warn;
+
+/// Longest version accepted, so a client can't mint arbitrary metric labels
+const MAX_VERSION_LENGTH: usize = 16;
+
+/// A place the API version of a request may be read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSource {
+    /// A `/v2/` segment of the request path
+    Path,
+    /// A `version=2` parameter of the `Accept` header
+    Accept,
+    /// The value of the named request header, e.g. `X-API-Version: 2`
+    Header(String),
+}
+
+impl std::str::FromStr for VersionSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.to_lowercase().as_str() {
+            "path" => Ok(VersionSource::Path),
+            "accept" => Ok(VersionSource::Accept),
+            _ => match s.split_once(':') {
+                Some((kind, name))
+                    if kind.trim().eq_ignore_ascii_case("header") && !name.trim().is_empty() =>
+                {
+                    Ok(VersionSource::Header(name.trim().to_string()))
+                }
+                _ => Err(format!("Invalid API version source: {}", s)),
+            },
+        }
+    }
+}
+
+/// Parse a comma-separated precedence list such as `path,accept,header:X-API-Version`
+///
+/// Unknown entries are logged and skipped; an empty list turns version
+/// extraction off.
+pub fn parse_sources(value: &str) -> Vec<VersionSource> {
+    value
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .filter_map(|s| match s.parse() {
+            Ok(source) => Some(source),
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Canonical form of a version, without a leading `v`: `v2`, `V2` and `2` are all `2`
+///
+/// `None` for values that aren't a plausible version, so they are treated as
+/// if no version had been sent.
+pub fn normalize(raw: &str) -> Option<String> {
+    let raw = raw.trim().trim_matches('"');
+    let version = raw
+        .strip_prefix('v')
+        .or_else(|| raw.strip_prefix('V'))
+        .unwrap_or(raw);
+
+    let valid = !version.is_empty()
+        && version.len() <= MAX_VERSION_LENGTH
+        && version
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    valid.then(|| version.to_ascii_lowercase())
+}
+
+/// A path segment like `v2` or `v2.1`; segments such as `videos` are not versions
+fn from_path(path: &str) -> Option<String> {
+    path.split('/').find_map(|segment| {
+        let rest = segment
+            .strip_prefix('v')
+            .or_else(|| segment.strip_prefix('V'))?;
+        if !rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        normalize(rest)
+    })
+}
+
+/// The `version` parameter of the first media range in `Accept` that has one
+fn from_accept(accept: &str) -> Option<String> {
+    accept
+        .split(',')
+        .flat_map(|range| range.split(';').skip(1))
+        .find_map(|param| {
+            let (name, value) = param.split_once('=')?;
+            name.trim()
+                .eq_ignore_ascii_case("version")
+                .then(|| normalize(value))
+                .flatten()
+        })
+}
+
+/// Extract the API version of a request, trying `sources` in order
+pub fn extract(sources: &[VersionSource], path: &str, headers: &HeaderMap) -> Option<String> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    sources.iter().find_map(|source| match source {
+        VersionSource::Path => from_path(path),
+        VersionSource::Accept => header("Accept").and_then(from_accept),
+        VersionSource::Header(name) => header(name).and_then(normalize),
+    })
+}
+
+/// Whether a request's version satisfies a route's `api_version`
+///
+/// A route without one matches any request, with or without a version, while
+/// a route with one only matches requests carrying that version.
+pub fn matches(condition: Option<&str>, version: Option<&str>) -> bool {
+    match condition.and_then(normalize) {
+        Some(expected) => version == Some(expected.as_str()),
+        None => true,
+    }
+}
+
+/// Whether a route has a version condition, used to prefer the more specific match
+pub fn has_condition(condition: Option<&str>) -> bool {
+    condition.and_then(normalize).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_version_from_path() {
+        let sources = parse_sources("path");
+
+        assert_eq!(
+            extract(&sources, "/v2/users", &HeaderMap::new()).as_deref(),
+            Some("2")
+        );
+        assert_eq!(
+            extract(&sources, "/api/V1.1/orders/7", &HeaderMap::new()).as_deref(),
+            Some("1.1")
+        );
+        assert_eq!(extract(&sources, "/videos/42", &HeaderMap::new()), None);
+        assert_eq!(extract(&sources, "/users", &HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_version_from_headers() {
+        let sources = parse_sources("accept, header:X-API-Version");
+
+        let accept = headers(&[("Accept", "application/json; version=2")]);
+        assert_eq!(extract(&sources, "/users", &accept).as_deref(), Some("2"));
+
+        let header = headers(&[("X-API-Version", "v3")]);
+        assert_eq!(extract(&sources, "/users", &header).as_deref(), Some("3"));
+
+        // Sources are tried in order
+        let both = headers(&[
+            ("Accept", "application/json;version=2"),
+            ("X-API-Version", "3"),
+        ]);
+        assert_eq!(extract(&sources, "/users", &both).as_deref(), Some("2"));
+
+        // Nonsense values count as no version
+        let junk = headers(&[("X-API-Version", "<script>")]);
+        assert_eq!(extract(&sources, "/users", &junk), None);
+    }
+
+    #[test]
+    fn test_parse_sources() {
+        assert_eq!(
+            parse_sources("path,bogus,header:X-Version"),
+            vec![
+                VersionSource::Path,
+                VersionSource::Header("X-Version".to_string())
+            ]
+        );
+        assert!(parse_sources("").is_empty());
+    }
+
+    #[test]
+    fn test_route_version_conditions() {
+        assert!(matches(None, None));
+        assert!(matches(None, Some("2")));
+        assert!(matches(Some("v2"), Some("2")));
+        assert!(!matches(Some("2"), Some("1")));
+        assert!(!matches(Some("2"), None));
+    }
+}
@@ -0,0 +1,317 @@
+use karateway_config::AppConfig;
+use serde_json::Value;
+
+use crate::content_type::base_type;
+
+/// Replacement for the value of a redacted JSON field
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Global settings for routes with `debug_log_body`
+///
+/// Bodies are only logged when both this and the route flag are on, so a
+/// route can't start writing payloads to the logs without an operator
+/// enabling it for the whole gateway first.
+#[derive(Debug, Clone, Default)]
+pub struct BodyLogging {
+    pub enabled: bool,
+    /// Bodies larger than this are logged by size only
+    pub max_bytes: usize,
+    /// Lowercased field names whose values are masked, in JSON and in text
+    pub redact_fields: Vec<String>,
+}
+
+impl BodyLogging {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            enabled: config.gateway_debug_body_logging,
+            max_bytes: config.gateway_debug_body_max_bytes,
+            redact_fields: parse_fields(&config.gateway_debug_body_redact_fields),
+        }
+    }
+
+    /// Start capturing a body of the given content type, `None` when it shouldn't be logged
+    pub fn capture(&self, route_enabled: bool, content_type: Option<&str>) -> Option<BodyCapture> {
+        if !self.enabled || !route_enabled || !is_loggable_content_type(content_type) {
+            return None;
+        }
+
+        Some(BodyCapture {
+            json: content_type.is_some_and(is_json),
+            max_bytes: self.max_bytes,
+            buffer: Vec::new(),
+            total_bytes: 0,
+        })
+    }
+}
+
+/// Parse a comma-separated list of field names
+fn parse_fields(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|field| field.trim().to_lowercase())
+        .filter(|field| !field.is_empty())
+        .collect()
+}
+
+fn is_json(content_type: &str) -> bool {
+    let base = base_type(content_type);
+    base == "application/json" || base.ends_with("+json")
+}
+
+/// Whether a body of this content type is text that can be logged
+///
+/// Only `text/*` and JSON; anything else, or a body without a content type,
+/// may be binary and is never logged.
+pub fn is_loggable_content_type(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|ct| is_json(ct) || base_type(ct).starts_with("text/"))
+}
+
+/// Body chunks collected for logging, up to the size cap
+#[derive(Debug)]
+pub struct BodyCapture {
+    json: bool,
+    max_bytes: usize,
+    buffer: Vec<u8>,
+    total_bytes: usize,
+}
+
+impl BodyCapture {
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.total_bytes += chunk.len();
+        // Past the cap the body is only counted, the buffer is never used
+        if self.total_bytes <= self.max_bytes {
+            self.buffer.extend_from_slice(chunk);
+        } else {
+            self.buffer = Vec::new();
+        }
+    }
+
+    /// The body as it should appear in the log, with the fields redacted
+    ///
+    /// Bodies over the cap, invalid JSON and non-UTF-8 text are described
+    /// rather than shown: a partial or unparseable JSON body can't be
+    /// redacted reliably. Text bodies have `field=value` and `field: value`
+    /// pairs masked with [`redact_text`].
+    pub fn render(&self, redact_fields: &[String]) -> String {
+        if self.total_bytes > self.max_bytes {
+            return format!(
+                "<{} bytes, over the {} byte logging limit>",
+                self.total_bytes, self.max_bytes
+            );
+        }
+
+        if self.json {
+            return match serde_json::from_slice::<Value>(&self.buffer) {
+                Ok(mut value) => {
+                    redact(&mut value, redact_fields);
+                    value.to_string()
+                }
+                Err(_) => format!("<{} bytes of invalid JSON>", self.total_bytes),
+            };
+        }
+
+        match std::str::from_utf8(&self.buffer) {
+            Ok(text) => redact_text(text, redact_fields),
+            Err(_) => format!("<{} bytes of non-UTF-8 text>", self.total_bytes),
+        }
+    }
+}
+
+/// Mask the values of `fields` anywhere in a JSON document, matching names case-insensitively
+pub fn redact(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if fields.contains(&key.to_lowercase()) {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact(field, fields);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                redact(item, fields);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Mask the values of `fields` in `field=value` or `field: value` pairs of a text body
+///
+/// Covers form-style (`a=1&password=x`), header-style and quoted
+/// (`"token": "abc"`) pairs. A name only matches as a whole word, so
+/// `password` doesn't catch `old_password_hint`.
+pub fn redact_text(text: &str, fields: &[String]) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut logged = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut at = 0;
+    while at < text.len() {
+        match masked_value(lower.as_bytes(), at, fields) {
+            Some((start, end)) => {
+                logged.push_str(&text[copied..start]);
+                logged.push_str(REDACTED);
+                copied = end;
+                at = end;
+            }
+            None => at += 1,
+        }
+    }
+    logged.push_str(&text[copied..]);
+    logged
+}
+
+/// Byte range of the value following a field name starting at `at`
+///
+/// The range always starts and ends next to ASCII delimiters, so it falls
+/// on character boundaries of the original text.
+fn masked_value(text: &[u8], at: usize, fields: &[String]) -> Option<(usize, usize)> {
+    if at > 0 && is_name_byte(text[at - 1]) {
+        return None;
+    }
+    let field = fields.iter().find(|field| {
+        text[at..].starts_with(field.as_bytes())
+            && !text
+                .get(at + field.len())
+                .copied()
+                .is_some_and(is_name_byte)
+    })?;
+
+    let mut pos = at + field.len();
+    if matches!(text.get(pos), Some(b'"' | b'\'')) {
+        pos += 1;
+    }
+    pos += count_blanks(&text[pos..]);
+    if !matches!(text.get(pos), Some(b'=' | b':')) {
+        return None;
+    }
+    pos += 1;
+    pos += count_blanks(&text[pos..]);
+
+    let (start, end) = match text.get(pos) {
+        Some(&quote @ (b'"' | b'\'')) => {
+            let start = pos + 1;
+            let len = text[start..].iter().position(|b| *b == quote);
+            (start, len.map_or(text.len(), |len| start + len))
+        }
+        _ => {
+            let len = text[pos..]
+                .iter()
+                .position(|b| matches!(b, b'&' | b',' | b';' | b' ' | b'\t' | b'\r' | b'\n'));
+            (pos, len.map_or(text.len(), |len| pos + len))
+        }
+    };
+    (end > start).then_some((start, end))
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-'
+}
+
+fn count_blanks(text: &[u8]) -> usize {
+    text.iter()
+        .take_while(|b| matches!(b, b' ' | b'\t'))
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn logging(max_bytes: usize) -> BodyLogging {
+        BodyLogging {
+            enabled: true,
+            max_bytes,
+            redact_fields: parse_fields("password, Token,api_key"),
+        }
+    }
+
+    #[test]
+    fn test_redaction_removes_configured_fields() {
+        let logging = logging(4096);
+        let mut capture = logging
+            .capture(true, Some("application/json; charset=utf-8"))
+            .unwrap();
+        let body = json!({
+            "user": "alice",
+            "Password": "hunter2",
+            "sessions": [{"token": "abc", "device": "phone"}],
+            "nested": {"API_KEY": {"id": 1}},
+        });
+        capture.push(body.to_string().as_bytes());
+
+        let logged: Value = serde_json::from_str(&capture.render(&logging.redact_fields)).unwrap();
+        assert_eq!(
+            logged,
+            json!({
+                "user": "alice",
+                "Password": REDACTED,
+                "sessions": [{"token": REDACTED, "device": "phone"}],
+                "nested": {"API_KEY": REDACTED},
+            })
+        );
+        assert!(!logged.to_string().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_text_bodies_are_redacted() {
+        let logging = logging(4096);
+        let mut capture = logging.capture(true, Some("text/plain")).unwrap();
+        capture.push(
+            "user=alice&PASSWORD=hunter2&old_password_hint=pet\n\
+             Token: abc123; api_key = \"k-é 1\""
+                .as_bytes(),
+        );
+
+        assert_eq!(
+            capture.render(&logging.redact_fields),
+            "user=alice&PASSWORD=[REDACTED]&old_password_hint=pet\n\
+             Token: [REDACTED]; api_key = \"[REDACTED]\""
+        );
+    }
+
+    #[test]
+    fn test_only_text_and_json_are_captured() {
+        let logging = logging(4096);
+        assert!(logging.capture(true, Some("text/plain")).is_some());
+        assert!(logging
+            .capture(true, Some("application/problem+json"))
+            .is_some());
+        assert!(logging
+            .capture(true, Some("application/octet-stream"))
+            .is_none());
+        assert!(logging.capture(true, Some("image/png")).is_none());
+        assert!(logging.capture(true, None).is_none());
+
+        // Needs both the route flag and the global switch
+        assert!(logging.capture(false, Some("text/plain")).is_none());
+        let disabled = BodyLogging {
+            enabled: false,
+            ..logging
+        };
+        assert!(disabled.capture(true, Some("text/plain")).is_none());
+    }
+
+    #[test]
+    fn test_oversized_and_unparseable_bodies_are_not_shown() {
+        let logging = logging(8);
+        let mut capture = logging.capture(true, Some("text/plain")).unwrap();
+        capture.push(b"hello");
+        assert_eq!(capture.render(&logging.redact_fields), "hello");
+        capture.push(b" world");
+        assert_eq!(
+            capture.render(&logging.redact_fields),
+            "<11 bytes, over the 8 byte logging limit>"
+        );
+
+        let mut capture = logging.capture(true, Some("application/json")).unwrap();
+        capture.push(br#"{"pa"#);
+        assert_eq!(
+            capture.render(&logging.redact_fields),
+            "<4 bytes of invalid JSON>"
+        );
+    }
+}
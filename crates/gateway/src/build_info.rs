@@ -0,0 +1,55 @@
+use chrono::DateTime;
+
+/// Which gateway build is running, captured by `build.rs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Short commit hash, `unknown` when built outside a git checkout
+    pub git_commit: &'static str,
+    /// Seconds since the epoch
+    pub build_timestamp: i64,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("KARATEWAY_GIT_COMMIT"),
+            build_timestamp: env!("KARATEWAY_BUILD_TIMESTAMP").parse().unwrap_or(0),
+        }
+    }
+
+    /// The `/version` body
+    pub fn to_json(&self) -> serde_json::Value {
+        let built_at =
+            DateTime::from_timestamp(self.build_timestamp, 0).map(|built_at| built_at.to_rfc3339());
+        serde_json::json!({
+            "service": "karateway-gateway",
+            "version": self.version,
+            "git_commit": self.git_commit,
+            "build_timestamp": built_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info_reports_the_crate_version() {
+        let info = BuildInfo::current();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_commit.is_empty());
+
+        let body = BuildInfo {
+            version: "1.2.3",
+            git_commit: "0123456789ab",
+            build_timestamp: 1_760_400_000,
+        }
+        .to_json();
+        assert_eq!(body["version"], "1.2.3");
+        assert_eq!(body["git_commit"], "0123456789ab");
+        assert_eq!(body["build_timestamp"], "2025-10-14T00:00:00+00:00");
+    }
+}
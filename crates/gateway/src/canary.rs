@@ -0,0 +1,124 @@
+use http::HeaderMap;
+use karateway_config::ip_allowlist::IpAllowlist;
+use karateway_config::AppConfig;
+use tracing::warn;
+
+/// Who may send the canary header to reach a blue/green route's inactive backend
+///
+/// Lets QA hit the backend a route would switch to while everyone else stays
+/// on the active one. Only clients whose peer IP (the connection's, or the
+/// PROXY protocol client) is in `trusted_networks` are honoured, whatever
+/// `X-Forwarded-For` claims; with none configured nobody is, even when enabled.
+#[derive(Debug, Clone)]
+pub struct CanaryHeader {
+    pub enabled: bool,
+    pub header: String,
+    /// Compared case-insensitively
+    pub value: String,
+    /// `None` when no trusted IPs are configured
+    pub trusted_networks: Option<IpAllowlist>,
+}
+
+impl CanaryHeader {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let trusted_ips = config.gateway_canary_trusted_ips.trim();
+        Self {
+            enabled: config.gateway_canary_header_enabled,
+            header: config.gateway_canary_header.trim().to_string(),
+            value: config.gateway_canary_header_value.trim().to_string(),
+            trusted_networks: (!trusted_ips.is_empty()).then(|| IpAllowlist::parse(trusted_ips)),
+        }
+    }
+
+    /// Whether a request asks for the canary and may have it
+    pub fn forces_canary(&self, headers: &HeaderMap, peer_ip: Option<&str>) -> bool {
+        if !self.enabled || self.header.is_empty() {
+            return false;
+        }
+        let asked = headers
+            .get(self.header.as_str())
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case(&self.value));
+        if !asked {
+            return false;
+        }
+
+        let trusted = self
+            .trusted_networks
+            .as_ref()
+            .is_some_and(|networks| networks.allows(peer_ip));
+        if !trusted {
+            warn!(
+                "Ignoring {} from untrusted client {:?}",
+                self.header, peer_ip
+            );
+        }
+        trusted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_info::ClientInfo;
+    use karateway_config::client_ip;
+
+    fn canary_header() -> CanaryHeader {
+        CanaryHeader {
+            enabled: true,
+            header: "X-Canary".to_string(),
+            value: "true".to_string(),
+            trusted_networks: Some(IpAllowlist::parse("10.0.0.0/8")),
+        }
+    }
+
+    fn headers(value: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = value {
+            headers.insert("X-Canary", value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_trusted_clients_can_force_the_canary() {
+        let canary = canary_header();
+
+        assert!(canary.forces_canary(&headers(Some("true")), Some("10.1.2.3")));
+        assert!(canary.forces_canary(&headers(Some(" TRUE ")), Some("10.1.2.3")));
+        // Without the header, or with another value, requests follow the active color
+        assert!(!canary.forces_canary(&headers(None), Some("10.1.2.3")));
+        assert!(!canary.forces_canary(&headers(Some("false")), Some("10.1.2.3")));
+    }
+
+    #[test]
+    fn test_untrusted_clients_cannot_force_the_canary() {
+        let canary = canary_header();
+        assert!(!canary.forces_canary(&headers(Some("true")), Some("203.0.113.9")));
+        assert!(!canary.forces_canary(&headers(Some("true")), None));
+
+        // Claiming a trusted IP in X-Forwarded-For doesn't make a client trusted
+        let mut spoofed = headers(Some("true"));
+        spoofed.insert("X-Forwarded-For", "10.1.2.3".parse().unwrap());
+        let client = ClientInfo::from_request(
+            &spoofed,
+            Some("203.0.113.9".to_string()),
+            &client_ip::DEFAULT_SOURCES,
+            &[],
+        );
+        assert!(!canary.forces_canary(&spoofed, client.peer_ip.as_deref()));
+
+        // Nothing configured to trust means nobody is trusted
+        let nobody = CanaryHeader {
+            trusted_networks: None,
+            ..canary.clone()
+        };
+        assert!(!nobody.forces_canary(&headers(Some("true")), Some("10.1.2.3")));
+
+        let disabled = CanaryHeader {
+            enabled: false,
+            ..canary
+        };
+        assert!(!disabled.forces_canary(&headers(Some("true")), Some("10.1.2.3")));
+    }
+}
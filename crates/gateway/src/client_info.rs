@@ -0,0 +1,222 @@
+use http::{HeaderMap, HeaderName};
+use karateway_config::client_ip::{self, ClientIpSource};
+use tracing::warn;
+use uuid::Uuid;
+
+/// Header a client may send to identify its request
+pub const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
+/// Parse the comma-separated request id header names, in the order they are checked
+///
+/// Invalid names are logged and skipped; an empty result falls back to [`REQUEST_ID_HEADER`].
+pub fn parse_request_id_headers(value: &str) -> Vec<String> {
+    let headers: Vec<String> = value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .filter(|name| match HeaderName::from_bytes(name.as_bytes()) {
+            Ok(_) => true,
+            Err(_) => {
+                warn!("Invalid request id header name: {}", name);
+                false
+            }
+        })
+        .map(str::to_string)
+        .collect();
+
+    if headers.is_empty() {
+        vec![REQUEST_ID_HEADER.to_string()]
+    } else {
+        headers
+    }
+}
+
+/// A request's correlation id and the header it travels under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId {
+    pub header: String,
+    pub value: String,
+}
+
+impl RequestId {
+    /// The id from the first of `headers` the request carries
+    fn from_headers(headers: &HeaderMap, names: &[String]) -> Option<Self> {
+        names.iter().find_map(|name| {
+            let value = headers.get(name.as_str())?.to_str().ok()?.trim();
+            (!value.is_empty()).then(|| Self {
+                header: name.clone(),
+                value: value.to_string(),
+            })
+        })
+    }
+
+    /// A fresh id for a request that came without one
+    pub fn generate(header: &str) -> Self {
+        Self {
+            header: header.to_string(),
+            value: Uuid::new_v4().to_string(),
+        }
+    }
+}
+
+/// Who sent a request, resolved once when it arrives
+///
+/// Whitelist checks, rate limiting, instance selection and audit logging all
+/// read the client from here, so they agree on its IP.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// Client IP resolved from the configured sources
+    pub ip: Option<String>,
+    /// IP of the connection's peer, or of the client a PROXY protocol balancer reported
+    ///
+    /// Unlike `ip` it never comes from a request header, so it is what
+    /// trust and per-client caps are decided on.
+    pub peer_ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// The id from the first configured request id header the client sent
+    pub request_id: Option<RequestId>,
+    /// Addresses listed in `X-Forwarded-For`, client first
+    pub forwarded_for: Vec<String>,
+}
+
+impl ClientInfo {
+    pub fn from_request(
+        headers: &HeaderMap,
+        peer_ip: Option<String>,
+        sources: &[ClientIpSource],
+        request_id_headers: &[String],
+    ) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+
+        Self {
+            ip: client_ip::resolve(headers, peer_ip.clone(), sources),
+            peer_ip,
+            user_agent: header("User-Agent"),
+            request_id: RequestId::from_headers(headers, request_id_headers),
+            forwarded_for: headers
+                .get_all("X-Forwarded-For")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .filter(|hop| !hop.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// The client IP, or `unknown` when none could be resolved
+    pub fn ip_or_unknown(&self) -> String {
+        self.ip.clone().unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_request_id_headers() -> Vec<String> {
+        vec![REQUEST_ID_HEADER.to_string()]
+    }
+
+    #[test]
+    fn test_client_info_from_request() {
+        let mut headers = HeaderMap::new();
+        headers.insert("User-Agent", "curl/8.5.0".parse().unwrap());
+        headers.insert(REQUEST_ID_HEADER, "req-42".parse().unwrap());
+        headers.append("X-Forwarded-For", "203.0.113.9, 10.0.0.2".parse().unwrap());
+        headers.append("X-Forwarded-For", "10.0.0.3".parse().unwrap());
+
+        let client = ClientInfo::from_request(
+            &headers,
+            Some("10.0.0.4".to_string()),
+            &client_ip::DEFAULT_SOURCES,
+            &default_request_id_headers(),
+        );
+
+        assert_eq!(client.ip.as_deref(), Some("203.0.113.9"));
+        assert_eq!(client.peer_ip.as_deref(), Some("10.0.0.4"));
+        assert_eq!(client.user_agent.as_deref(), Some("curl/8.5.0"));
+        assert_eq!(
+            client.request_id,
+            Some(RequestId {
+                header: REQUEST_ID_HEADER.to_string(),
+                value: "req-42".to_string(),
+            })
+        );
+        assert_eq!(
+            client.forwarded_for,
+            vec!["203.0.113.9", "10.0.0.2", "10.0.0.3"]
+        );
+
+        // Only trusting the peer ignores what the client claims
+        let client = ClientInfo::from_request(
+            &headers,
+            Some("10.0.0.4".to_string()),
+            &[ClientIpSource::Peer],
+            &default_request_id_headers(),
+        );
+        assert_eq!(client.ip.as_deref(), Some("10.0.0.4"));
+    }
+
+    #[test]
+    fn test_bare_request_has_no_client_details() {
+        let client = ClientInfo::from_request(
+            &HeaderMap::new(),
+            None,
+            &client_ip::DEFAULT_SOURCES,
+            &default_request_id_headers(),
+        );
+        assert_eq!(client, ClientInfo::default());
+        assert_eq!(client.ip_or_unknown(), "unknown");
+    }
+
+    #[test]
+    fn test_each_configured_request_id_header_is_honored() {
+        let names = parse_request_id_headers("X-Correlation-ID, X-Trace-Id, X-Request-ID");
+        assert_eq!(
+            names,
+            vec!["X-Correlation-ID", "X-Trace-Id", "X-Request-ID"]
+        );
+
+        for name in ["X-Correlation-ID", "X-Trace-Id", "X-Request-ID"] {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, "abc-123".parse().unwrap());
+
+            let request_id = ClientInfo::from_request(&headers, None, &[], &names).request_id;
+            assert_eq!(
+                request_id,
+                Some(RequestId {
+                    header: name.to_string(),
+                    value: "abc-123".to_string(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_request_id_headers_are_checked_in_order() {
+        let names = parse_request_id_headers("X-Trace-Id,X-Request-ID");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-ID", "from-request-id".parse().unwrap());
+        headers.insert("X-Trace-Id", "from-trace-id".parse().unwrap());
+        let request_id = ClientInfo::from_request(&headers, None, &[], &names).request_id;
+        assert_eq!(request_id.unwrap().header, "X-Trace-Id");
+
+        // Unconfigured headers are ignored, and nothing configured means the default
+        let names = parse_request_id_headers(" , bad header");
+        assert_eq!(names, vec![REQUEST_ID_HEADER]);
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Trace-Id", "from-trace-id".parse().unwrap());
+        assert_eq!(
+            ClientInfo::from_request(&headers, None, &[], &names).request_id,
+            None
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use dashmap::DashMap;
+use karateway_config::ip_allowlist::IpAllowlist;
+use karateway_config::AppConfig;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Most client IPs counted at once; a client arriving when the table is full is turned away
+///
+/// Entries only live while a client has connections or requests open, so
+/// this is only reached when that many different IPs are connected at once.
+const MAX_TRACKED_CLIENTS: usize = 100_000;
+
+/// Caps on what a single client IP may hold open at the gateway
+#[derive(Debug, Clone, Default)]
+pub struct ClientLimits {
+    /// Most concurrent connections per IP, `None` for no cap
+    pub max_connections: Option<usize>,
+    /// Most requests in flight per IP, `None` for no cap
+    pub max_requests: Option<usize>,
+    /// Clients the caps don't apply to, `None` when no IPs are exempt
+    pub exempt: Option<IpAllowlist>,
+}
+
+impl ClientLimits {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let exempt_ips = config.gateway_client_limit_exempt_ips.trim();
+        Self {
+            max_connections: Some(config.gateway_max_connections_per_ip).filter(|max| *max > 0),
+            max_requests: Some(config.gateway_max_requests_per_ip).filter(|max| *max > 0),
+            exempt: (!exempt_ips.is_empty()).then(|| IpAllowlist::parse(exempt_ips)),
+        }
+    }
+}
+
+/// Counts what each client IP holds open against one cap
+#[derive(Debug)]
+pub struct ClientCounter {
+    max: Option<usize>,
+    exempt: Option<IpAllowlist>,
+    counts: DashMap<IpAddr, usize>,
+    rejected: AtomicU64,
+}
+
+impl ClientCounter {
+    pub fn new(max: Option<usize>, exempt: Option<IpAllowlist>) -> Self {
+        Self {
+            max,
+            exempt,
+            counts: DashMap::new(),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Count one more connection or request of `ip`, `None` when it is over the cap
+    ///
+    /// Exempt clients, and ones whose IP is unknown, are let through uncounted.
+    pub fn admit(self: &Arc<Self>, ip: Option<IpAddr>) -> Option<ClientSlot> {
+        let (Some(max), Some(ip)) = (self.max, ip) else {
+            return Some(ClientSlot { counted: None });
+        };
+        if self
+            .exempt
+            .as_ref()
+            .is_some_and(|exempt| exempt.allows(Some(&ip.to_string())))
+        {
+            return Some(ClientSlot { counted: None });
+        }
+
+        let admitted = if !self.counts.contains_key(&ip) && self.counts.len() >= MAX_TRACKED_CLIENTS
+        {
+            false
+        } else {
+            let mut count = self.counts.entry(ip).or_insert(0);
+            if *count < max {
+                *count += 1;
+                true
+            } else {
+                false
+            }
+        };
+        if !admitted {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        Some(ClientSlot {
+            counted: Some((self.clone(), ip)),
+        })
+    }
+
+    /// The cap, `None` when there is none
+    pub fn max(&self) -> Option<usize> {
+        self.max
+    }
+
+    /// Connections or requests turned away so far
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// How many connections or requests `ip` holds open
+    #[cfg(test)]
+    fn count(&self, ip: IpAddr) -> usize {
+        self.counts.get(&ip).map_or(0, |count| *count)
+    }
+
+    fn release(&self, ip: IpAddr) {
+        if let Some(mut count) = self.counts.get_mut(&ip) {
+            *count = count.saturating_sub(1);
+        }
+        // Forget clients with nothing open, so the table only holds active ones
+        self.counts.remove_if(&ip, |_, count| *count == 0);
+    }
+}
+
+/// A connection or request let through by a [`ClientCounter`], counted until dropped
+#[derive(Debug)]
+pub struct ClientSlot {
+    counted: Option<(Arc<ClientCounter>, IpAddr)>,
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        if let Some((counter, ip)) = &self.counted {
+            counter.release(*ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_over_the_cap_is_throttled_while_others_are_not() {
+        let counter = Arc::new(ClientCounter::new(
+            Some(2),
+            Some(IpAllowlist::parse("10.0.0.0/8")),
+        ));
+        let abusive: IpAddr = "203.0.113.9".parse().unwrap();
+        let other: IpAddr = "198.51.100.7".parse().unwrap();
+
+        let first = counter.admit(Some(abusive)).unwrap();
+        let _second = counter.admit(Some(abusive)).unwrap();
+        assert!(counter.admit(Some(abusive)).is_none());
+        assert_eq!(counter.rejected(), 1);
+
+        // Other clients keep their own budget
+        let others: Vec<_> = (0..2).map(|_| counter.admit(Some(other))).collect();
+        assert!(others.iter().all(Option::is_some));
+
+        // Exempt and unknown clients are never counted
+        let trusted: IpAddr = "10.1.2.3".parse().unwrap();
+        let trusted_slots: Vec<_> = (0..5).map(|_| counter.admit(Some(trusted))).collect();
+        assert!(trusted_slots.iter().all(Option::is_some));
+        assert_eq!(counter.count(trusted), 0);
+        assert!(counter.admit(None).is_some());
+
+        // A closed connection frees its slot, and a client with nothing open is forgotten
+        drop(first);
+        assert!(counter.admit(Some(abusive)).is_some());
+        drop(others);
+        assert_eq!(counter.count(other), 0);
+        assert!(!counter.counts.contains_key(&other));
+    }
+}
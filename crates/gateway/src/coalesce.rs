@@ -0,0 +1,397 @@
+use bytes::{Bytes, BytesMut};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// Upper bound on distinct requests being coalesced at once
+///
+/// Past it new requests simply go upstream on their own.
+pub const MAX_IN_FLIGHT: usize = 10_000;
+
+/// Largest response body buffered for sharing; bigger responses aren't shared
+pub const MAX_SHARED_BODY_BYTES: usize = 1024 * 1024;
+
+/// How long a follower waits for the leader when the route has no timeout
+pub const DEFAULT_WAIT: Duration = Duration::from_secs(30);
+
+/// Headers a response may depend on, folded into the key
+const KEY_HEADERS: [HeaderName; 5] = [
+    header::AUTHORIZATION,
+    header::COOKIE,
+    header::ACCEPT,
+    header::ACCEPT_ENCODING,
+    HeaderName::from_static("x-api-key"),
+];
+
+/// A finished upstream response, replayed to every follower
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedResponse {
+    pub status: u16,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub body: Bytes,
+}
+
+/// One flight: its id tells a finished leader's flight apart from a newer one under the same key
+struct Slot {
+    flight: Uuid,
+    tx: watch::Sender<Option<Arc<SharedResponse>>>,
+}
+
+/// Signature of a request that may be coalesced, `None` when it can't be
+///
+/// Only `GET` and `HEAD` requests without a body qualify: coalescing
+/// anything else could drop side effects, and a body is streamed to the
+/// upstream as it arrives, so it can't be hashed up front. Credentials and
+/// content negotiation headers are hashed into the key so two users never
+/// share a response, and so are `vary_headers`, the headers the route's
+/// whitelist rules read (see [`WhitelistValidator::header_names`]). The hash
+/// is SHA-256, so no two callers' headers end up under the same key.
+///
+/// [`WhitelistValidator::header_names`]: crate::whitelist_validator::WhitelistValidator::header_names
+pub fn request_key(
+    route_id: Uuid,
+    method: &str,
+    path_and_query: &str,
+    headers: &HeaderMap,
+    vary_headers: &[String],
+) -> Option<String> {
+    if !method.eq_ignore_ascii_case("GET") && !method.eq_ignore_ascii_case("HEAD") {
+        return None;
+    }
+
+    let has_body = headers.contains_key(header::TRANSFER_ENCODING)
+        || headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|h| h.to_str().ok())
+            .is_some_and(|len| len.trim() != "0");
+    if has_body {
+        return None;
+    }
+
+    // Each value goes in with its header's name, so moving a value to another header changes the key
+    let varying = KEY_HEADERS
+        .iter()
+        .map(HeaderName::as_str)
+        .chain(vary_headers.iter().map(String::as_str))
+        .flat_map(|name| {
+            headers
+                .get_all(name)
+                .iter()
+                .map(move |value| format!("{}={}", name, String::from_utf8_lossy(value.as_bytes())))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(format!(
+        "{}:{}:{}:{:x}",
+        route_id,
+        method.to_uppercase(),
+        path_and_query,
+        Sha256::digest(varying.as_bytes())
+    ))
+}
+
+/// Whether an upstream response may be handed to other clients
+///
+/// Responses setting a cookie or marked `Cache-Control: private` or
+/// `no-store` belong to the one client that asked; sharing them could hand
+/// one anonymous user's fresh session to another.
+pub fn shareable(headers: &HeaderMap) -> bool {
+    if headers.contains_key(header::SET_COOKIE) {
+        return false;
+    }
+    !headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|directive| directive.split('=').next().unwrap_or_default().trim())
+        .any(|name| name.eq_ignore_ascii_case("private") || name.eq_ignore_ascii_case("no-store"))
+}
+
+/// Single-flight table: the first request per key goes upstream, identical ones wait for it
+#[derive(Default)]
+pub struct Coalescer {
+    in_flight: DashMap<String, Slot>,
+}
+
+/// A request's part in coalescing
+pub enum Role {
+    /// Goes upstream and shares what it gets back
+    Leader(Leader),
+    /// Waits for the leader's response
+    Follower(watch::Receiver<Option<Arc<SharedResponse>>>),
+}
+
+impl Coalescer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lead `key` or follow the request already leading it; `None` when the table is full
+    pub fn join(self: &Arc<Self>, key: String) -> Option<Role> {
+        let full = self.in_flight.len() >= MAX_IN_FLIGHT;
+
+        match self.in_flight.entry(key) {
+            Entry::Occupied(entry) => Some(Role::Follower(entry.get().tx.subscribe())),
+            Entry::Vacant(_) if full => None,
+            Entry::Vacant(entry) => {
+                let (tx, _) = watch::channel(None);
+                let flight = Uuid::new_v4();
+                let key = entry.key().clone();
+                entry.insert(Slot { flight, tx });
+                Some(Role::Leader(Leader {
+                    coalescer: self.clone(),
+                    key,
+                    flight,
+                    status: 0,
+                    headers: Vec::new(),
+                    body: Some(BytesMut::new()),
+                }))
+            }
+        }
+    }
+
+    /// Requests currently being coalesced
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+/// Wait for the leader's response
+///
+/// Returns `None` when the leader gave up (error, streaming or oversized
+/// response) or didn't finish within `wait`; the follower should then go
+/// upstream itself.
+pub async fn wait_for(
+    mut rx: watch::Receiver<Option<Arc<SharedResponse>>>,
+    wait: Duration,
+) -> Option<Arc<SharedResponse>> {
+    let result = tokio::time::timeout(wait, rx.wait_for(|response| response.is_some())).await;
+    match result {
+        Ok(Ok(response)) => response.clone(),
+        _ => None,
+    }
+}
+
+/// Buffers the leader's response; dropping it without `finish` releases followers empty-handed
+pub struct Leader {
+    coalescer: Arc<Coalescer>,
+    key: String,
+    flight: Uuid,
+    status: u16,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    /// `None` once the body outgrew `MAX_SHARED_BODY_BYTES`
+    body: Option<BytesMut>,
+}
+
+impl Leader {
+    /// Record the response head as it will be sent to the client
+    pub fn set_head(&mut self, status: u16, headers: &HeaderMap) {
+        self.status = status;
+        self.headers = headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+    }
+
+    /// Append a body chunk, giving up on sharing past the size limit
+    pub fn push_body(&mut self, chunk: &[u8]) {
+        if let Some(body) = &mut self.body {
+            if body.len() + chunk.len() > MAX_SHARED_BODY_BYTES {
+                self.body = None;
+            } else {
+                body.extend_from_slice(chunk);
+            }
+        }
+    }
+
+    /// Hand the complete response to the followers
+    pub fn finish(mut self) {
+        let Some(body) = self.body.take() else {
+            return;
+        };
+
+        if let Some((_, slot)) = self.release() {
+            slot.tx.send_replace(Some(Arc::new(SharedResponse {
+                status: self.status,
+                headers: std::mem::take(&mut self.headers),
+                body: body.freeze(),
+            })));
+        }
+    }
+
+    /// Remove this leader's flight, leaving a newer one under the same key alone
+    fn release(&self) -> Option<(String, Slot)> {
+        self.coalescer
+            .in_flight
+            .remove_if(&self.key, |_, slot| slot.flight == self.flight)
+    }
+}
+
+impl Drop for Leader {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the followers, who then go upstream themselves
+        self.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn key(headers: &HeaderMap) -> Option<String> {
+        request_key(Uuid::nil(), "GET", "/products?page=1", headers, &[])
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_identical_requests_make_one_upstream_call() {
+        let coalescer = Arc::new(Coalescer::new());
+        let upstream_calls = Arc::new(AtomicUsize::new(0));
+        let key = key(&HeaderMap::new()).unwrap();
+
+        // Everyone joins before the leader's response comes back
+        let roles: Vec<Role> = (0..10)
+            .map(|_| coalescer.join(key.clone()).unwrap())
+            .collect();
+
+        let mut tasks = Vec::new();
+        for role in roles {
+            let upstream_calls = upstream_calls.clone();
+            tasks.push(tokio::spawn(async move {
+                match role {
+                    Role::Leader(mut leader) => {
+                        upstream_calls.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        leader.set_head(200, &HeaderMap::new());
+                        leader.push_body(b"[\"widget\"]");
+                        leader.finish();
+                        Bytes::from_static(b"[\"widget\"]")
+                    }
+                    Role::Follower(rx) => wait_for(rx, Duration::from_secs(1))
+                        .await
+                        .unwrap()
+                        .body
+                        .clone(),
+                }
+            }));
+        }
+
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), Bytes::from_static(b"[\"widget\"]"));
+        }
+        assert_eq!(upstream_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_followers_are_released_when_leader_gives_up() {
+        let coalescer = Arc::new(Coalescer::new());
+        let key = key(&HeaderMap::new()).unwrap();
+
+        let Some(Role::Leader(mut leader)) = coalescer.join(key.clone()) else {
+            panic!("first request should lead");
+        };
+        let Some(Role::Follower(rx)) = coalescer.join(key.clone()) else {
+            panic!("second request should follow");
+        };
+
+        // Too large to share
+        leader.push_body(&vec![0; MAX_SHARED_BODY_BYTES + 1]);
+        leader.finish();
+
+        assert!(wait_for(rx, Duration::from_secs(1)).await.is_none());
+        // The next request starts a fresh flight
+        assert!(matches!(coalescer.join(key), Some(Role::Leader(_))));
+    }
+
+    #[test]
+    fn test_request_key() {
+        let mut headers = HeaderMap::new();
+        let anonymous = key(&headers).unwrap();
+
+        headers.insert(header::AUTHORIZATION, "Bearer alice".parse().unwrap());
+        let alice = key(&headers).unwrap();
+        headers.insert(header::AUTHORIZATION, "Bearer bob".parse().unwrap());
+        let bob = key(&headers).unwrap();
+
+        // Different callers never share a response
+        assert_ne!(anonymous, alice);
+        assert_ne!(alice, bob);
+        assert!(!alice.contains("alice"));
+        // The whole header set is hashed, not a short checksum of it
+        let digest = Sha256::digest(b"authorization=Bearer alice");
+        assert!(alice.ends_with(&format!(":{:x}", digest)));
+
+        headers.insert(header::CONTENT_LENGTH, "0".parse().unwrap());
+        assert_eq!(key(&headers), Some(bob));
+
+        headers.insert(header::CONTENT_LENGTH, "12".parse().unwrap());
+        assert_eq!(key(&headers), None);
+        headers.remove(header::CONTENT_LENGTH);
+        headers.insert(header::TRANSFER_ENCODING, "chunked".parse().unwrap());
+        assert_eq!(key(&headers), None);
+
+        let post = request_key(Uuid::nil(), "POST", "/products", &HeaderMap::new(), &[]);
+        assert_eq!(post, None);
+    }
+
+    #[test]
+    fn test_api_keys_and_whitelist_headers_are_part_of_the_key() {
+        let mut alice = HeaderMap::new();
+        alice.insert("X-API-Key", "alice-key".parse().unwrap());
+        let mut bob = HeaderMap::new();
+        bob.insert("X-API-Key", "bob-key".parse().unwrap());
+        assert_ne!(key(&alice), key(&bob));
+
+        // Headers the route's whitelist rules read only count when named
+        let tenant = |id: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("X-Tenant", id.parse().unwrap());
+            headers
+        };
+        let vary = ["X-Tenant".to_string()];
+        let tenant_key = |headers: &HeaderMap, vary: &[String]| {
+            request_key(Uuid::nil(), "GET", "/products", headers, vary)
+        };
+        assert_eq!(tenant_key(&tenant("a"), &[]), tenant_key(&tenant("b"), &[]));
+        assert_ne!(
+            tenant_key(&tenant("a"), &vary),
+            tenant_key(&tenant("b"), &vary)
+        );
+    }
+
+    #[test]
+    fn test_private_responses_are_not_shared() {
+        let head = |name: header::HeaderName, value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, value.parse().unwrap());
+            headers
+        };
+
+        assert!(shareable(&HeaderMap::new()));
+        assert!(shareable(&head(
+            header::CACHE_CONTROL,
+            "public, max-age=60"
+        )));
+        assert!(!shareable(&head(
+            header::SET_COOKIE,
+            "session=abc; HttpOnly"
+        )));
+        assert!(!shareable(&head(
+            header::CACHE_CONTROL,
+            "max-age=0, Private"
+        )));
+        assert!(!shareable(&head(header::CACHE_CONTROL, "no-store")));
+        assert!(!shareable(&head(
+            header::CACHE_CONTROL,
+            "private=\"Set-Cookie\""
+        )));
+    }
+}
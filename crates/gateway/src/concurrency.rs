@@ -0,0 +1,376 @@
+use dashmap::DashMap;
+use karateway_core::models::ApiRoute;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+use uuid::Uuid;
+
+/// Semaphore for one backend together with its current cap
+struct BackendSlots {
+    limit: u32,
+    semaphore: Arc<Semaphore>,
+    /// Permits above `limit` still held by requests, forgotten as they come back
+    excess: u32,
+    /// Requests currently queued for a slot
+    waiting: Arc<AtomicU32>,
+}
+
+impl BackendSlots {
+    fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit as usize)),
+            excess: 0,
+            waiting: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Change the cap in place, so queued requests keep waiting on the same semaphore
+    ///
+    /// Lowering it only drops free permits; the rest are dropped as the
+    /// requests holding them finish.
+    fn resize(&mut self, limit: u32) {
+        if limit > self.limit {
+            let added = limit - self.limit;
+            let reclaimed = added.min(self.excess);
+            self.excess -= reclaimed;
+            self.semaphore.add_permits((added - reclaimed) as usize);
+        } else {
+            self.excess += self.limit - limit;
+        }
+        self.limit = limit;
+        self.forget_excess();
+    }
+
+    fn forget_excess(&mut self) {
+        if self.excess > 0 {
+            self.excess -= self.semaphore.forget_permits(self.excess as usize) as u32;
+        }
+    }
+}
+
+/// How long a route's requests may wait for a slot on a backend at capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuePolicy {
+    /// Max requests waiting for the backend before new ones are rejected
+    pub depth: u32,
+    pub timeout: Duration,
+}
+
+impl QueuePolicy {
+    /// The route's queue, if it sets both `queue_depth` and `queue_timeout_ms`
+    pub fn from_route(route: &ApiRoute) -> Option<Self> {
+        let depth = route.queue_depth.filter(|depth| *depth > 0)?;
+        let timeout = route.queue_timeout_ms.filter(|ms| *ms > 0)?;
+
+        Some(Self {
+            depth: depth as u32,
+            timeout: Duration::from_millis(timeout as u64),
+        })
+    }
+}
+
+/// Why a request didn't get a slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// At capacity and the route has no queue
+    AtCapacity,
+    /// At capacity with `depth` requests already waiting
+    QueueFull,
+    /// Waited the full queue timeout without a slot freeing up
+    QueueTimeout,
+}
+
+impl Rejection {
+    /// Whether the `503` tells the client to retry
+    ///
+    /// Only a route with a queue absorbs bursts, so only its rejections can
+    /// expect a slot shortly; one without a queue is rejected for as long as
+    /// the backend stays full.
+    pub fn is_retryable(self) -> bool {
+        !matches!(self, Rejection::AtCapacity)
+    }
+}
+
+/// Keeps a backend's waiting count accurate even if the request is cancelled
+struct WaitingGuard(Arc<AtomicU32>);
+
+impl Drop for WaitingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Caps concurrent upstream requests per backend service, across all routes
+///
+/// Each backend with `max_connections` set gets a semaphore keyed on its
+/// service id. A request holds a permit from `request_filter` until its
+/// context is dropped at the end of the request.
+#[derive(Default)]
+pub struct BackendConcurrency {
+    slots: DashMap<Uuid, BackendSlots>,
+}
+
+impl BackendConcurrency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a slot for `service_id`
+    ///
+    /// Returns `Ok(None)` when the backend has no cap and an error when it is
+    /// at capacity. A changed cap resizes the backend's semaphore; requests
+    /// already holding a permit finish normally.
+    pub fn try_acquire(
+        &self,
+        service_id: Uuid,
+        limit: Option<u32>,
+    ) -> Result<Option<OwnedSemaphorePermit>, TryAcquireError> {
+        match self.slots_for(service_id, limit) {
+            Some((semaphore, _)) => semaphore.try_acquire_owned().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Take a slot for `service_id`, queueing per `queue` when it is at capacity
+    ///
+    /// Without a queue this behaves like [`Self::try_acquire`]. With one, the
+    /// request waits up to `queue.timeout` for a slot, unless `queue.depth`
+    /// requests are already waiting for the backend.
+    pub async fn acquire(
+        &self,
+        service_id: Uuid,
+        limit: Option<u32>,
+        queue: Option<QueuePolicy>,
+    ) -> Result<Option<OwnedSemaphorePermit>, Rejection> {
+        let Some((semaphore, waiting)) = self.slots_for(service_id, limit) else {
+            return Ok(None);
+        };
+
+        if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+        let Some(queue) = queue else {
+            return Err(Rejection::AtCapacity);
+        };
+
+        let queued = waiting
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < queue.depth).then_some(count + 1)
+            })
+            .is_ok();
+        if !queued {
+            return Err(Rejection::QueueFull);
+        }
+        let _waiting = WaitingGuard(waiting);
+
+        match tokio::time::timeout(queue.timeout, semaphore.acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(Some(permit)),
+            // Semaphores are never closed, so only the timeout ends the wait
+            Ok(Err(_)) | Err(_) => Err(Rejection::QueueTimeout),
+        }
+    }
+
+    /// Requests currently queued for a capped backend
+    pub fn waiting(&self, service_id: &Uuid) -> u32 {
+        self.slots
+            .get(service_id)
+            .map(|slots| slots.waiting.load(Ordering::Acquire))
+            .unwrap_or(0)
+    }
+
+    /// Semaphore and waiting count for a backend, `None` when it is uncapped
+    fn slots_for(
+        &self,
+        service_id: Uuid,
+        limit: Option<u32>,
+    ) -> Option<(Arc<Semaphore>, Arc<AtomicU32>)> {
+        let Some(limit) = limit.filter(|limit| *limit > 0) else {
+            self.slots.remove(&service_id);
+            return None;
+        };
+
+        let mut slots = self
+            .slots
+            .entry(service_id)
+            .or_insert_with(|| BackendSlots::new(limit));
+        if slots.limit != limit {
+            slots.resize(limit);
+        } else {
+            slots.forget_excess();
+        }
+
+        Some((slots.semaphore.clone(), slots.waiting.clone()))
+    }
+
+    /// Current `(in_use, limit)` for a capped backend
+    pub fn usage(&self, service_id: &Uuid) -> Option<(u32, u32)> {
+        self.slots.get(service_id).map(|slots| {
+            let available = slots.semaphore.available_permits() as u32;
+            let in_use = (slots.limit + slots.excess).saturating_sub(available);
+            (in_use, slots.limit)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_is_enforced_and_released() {
+        let concurrency = BackendConcurrency::new();
+        let backend = Uuid::new_v4();
+
+        let first = concurrency.try_acquire(backend, Some(2)).unwrap();
+        let second = concurrency.try_acquire(backend, Some(2)).unwrap();
+        assert!(first.is_some() && second.is_some());
+        assert_eq!(concurrency.usage(&backend), Some((2, 2)));
+
+        // Third concurrent request is rejected
+        assert!(concurrency.try_acquire(backend, Some(2)).is_err());
+
+        // A finished request frees its slot
+        drop(first);
+        assert_eq!(concurrency.usage(&backend), Some((1, 2)));
+        assert!(concurrency.try_acquire(backend, Some(2)).is_ok());
+    }
+
+    #[test]
+    fn test_limits_are_per_backend() {
+        let concurrency = BackendConcurrency::new();
+        let small = Uuid::new_v4();
+        let other = Uuid::new_v4();
+
+        let _held = concurrency.try_acquire(small, Some(1)).unwrap();
+        assert!(concurrency.try_acquire(small, Some(1)).is_err());
+        assert!(concurrency.try_acquire(other, Some(1)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_uncapped_and_changed_limits() {
+        let concurrency = BackendConcurrency::new();
+        let backend = Uuid::new_v4();
+
+        assert!(concurrency.try_acquire(backend, None).unwrap().is_none());
+        assert_eq!(concurrency.usage(&backend), None);
+
+        let _held = concurrency.try_acquire(backend, Some(1)).unwrap();
+        assert!(concurrency.try_acquire(backend, Some(1)).is_err());
+
+        // Raising the cap takes effect immediately
+        let raised = concurrency.try_acquire(backend, Some(5)).unwrap();
+        assert!(raised.is_some());
+        assert_eq!(concurrency.usage(&backend), Some((2, 5)));
+
+        // Lowering it below what is in use lets the held requests finish
+        assert!(concurrency.try_acquire(backend, Some(1)).is_err());
+        assert_eq!(concurrency.usage(&backend), Some((2, 1)));
+        drop(raised);
+        assert!(concurrency.try_acquire(backend, Some(1)).is_err());
+        assert_eq!(concurrency.usage(&backend), Some((1, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_survives_a_raised_cap() {
+        let concurrency = Arc::new(BackendConcurrency::new());
+        let backend = Uuid::new_v4();
+        let _held = concurrency.try_acquire(backend, Some(1)).unwrap();
+
+        let queued = tokio::spawn({
+            let concurrency = concurrency.clone();
+            async move {
+                concurrency
+                    .acquire(backend, Some(1), queue(1, 5_000))
+                    .await
+                    .map(|permit| permit.is_some())
+            }
+        });
+        while concurrency.waiting(&backend) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // The reload adds a permit to the semaphore the request is waiting on
+        assert!(concurrency.try_acquire(backend, Some(3)).unwrap().is_some());
+        assert_eq!(queued.await.unwrap(), Ok(true));
+    }
+
+    fn queue(depth: u32, timeout_ms: u64) -> Option<QueuePolicy> {
+        Some(QueuePolicy {
+            depth,
+            timeout: Duration::from_millis(timeout_ms),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_free_slot_is_taken_immediately() {
+        let concurrency = BackendConcurrency::new();
+        let backend = Uuid::new_v4();
+
+        let permit = concurrency
+            .acquire(backend, Some(1), queue(1, 10))
+            .await
+            .unwrap();
+        assert!(permit.is_some());
+        assert_eq!(concurrency.waiting(&backend), 0);
+
+        // Without a queue a full backend rejects right away, and a retry can't expect a slot
+        assert_eq!(
+            concurrency.acquire(backend, Some(1), None).await.err(),
+            Some(Rejection::AtCapacity)
+        );
+        assert!(!Rejection::AtCapacity.is_retryable());
+        assert!(Rejection::QueueFull.is_retryable());
+        assert!(Rejection::QueueTimeout.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_is_served_when_slot_frees() {
+        let concurrency = Arc::new(BackendConcurrency::new());
+        let backend = Uuid::new_v4();
+        let held = concurrency.try_acquire(backend, Some(1)).unwrap();
+
+        let queued = tokio::spawn({
+            let concurrency = concurrency.clone();
+            async move {
+                concurrency
+                    .acquire(backend, Some(1), queue(1, 5_000))
+                    .await
+                    .map(|permit| permit.is_some())
+            }
+        });
+
+        while concurrency.waiting(&backend) == 0 {
+            tokio::task::yield_now().await;
+        }
+        // Queue is full, so the next request is turned away
+        assert_eq!(
+            concurrency
+                .acquire(backend, Some(1), queue(1, 5_000))
+                .await
+                .err(),
+            Some(Rejection::QueueFull)
+        );
+
+        drop(held);
+        assert_eq!(queued.await.unwrap(), Ok(true));
+        assert_eq!(concurrency.waiting(&backend), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queued_request_times_out() {
+        let concurrency = BackendConcurrency::new();
+        let backend = Uuid::new_v4();
+        let _held = concurrency.try_acquire(backend, Some(1)).unwrap();
+
+        assert_eq!(
+            concurrency
+                .acquire(backend, Some(1), queue(5, 20))
+                .await
+                .err(),
+            Some(Rejection::QueueTimeout)
+        );
+        assert_eq!(concurrency.waiting(&backend), 0);
+    }
+}
@@ -1,36 +1,327 @@
 use anyhow::Result;
 use arc_swap::ArcSwap;
-use karateway_config::repository::{
-    ApiRouteRepository, BackendServiceRepository, RateLimitRepository, WhitelistRuleRepository,
+use karateway_config::{
+    config_limits::{ConfigCounts, ConfigLimits},
+    repository::{
+        ApiRouteRepository, BackendServiceRepository, MetricTagRuleRepository, RateLimitRepository,
+        WhitelistRuleRepository,
+    },
+    AppConfig,
+};
+use karateway_core::models::{
+    effective_rate_limits, parse_custom_conditions, ApiRoute, BackendService, MetricTagRule,
+    RateLimit, RuleType, WhitelistRule,
 };
-use karateway_core::models::{ApiRoute, BackendService, RateLimit, WhitelistRule};
 use sqlx::PgPool;
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::api_version;
+use crate::content_type;
+use crate::path_case::PathCase;
+use crate::query_match;
+use crate::upstream::UpstreamTarget;
+use crate::upstream_tls::ClientCert;
+use crate::whitelist_validator::CustomRuleConditions;
+
 /// Configuration snapshot loaded from database
 #[derive(Clone, Debug)]
 pub struct GatewayConfig {
     /// All backend services indexed by ID
     pub services: HashMap<Uuid, BackendService>,
+    /// Client certificates of the services that connect with mutual TLS
+    pub client_certs: HashMap<Uuid, ClientCert>,
     /// All active API routes
     pub routes: Vec<ApiRoute>,
     /// All active rate limits indexed by route ID
     pub rate_limits: HashMap<Option<Uuid>, Vec<RateLimit>>,
     /// All active whitelist rules indexed by route ID
     pub whitelist_rules: HashMap<Option<Uuid>, Vec<WhitelistRule>>,
+    /// Header conditions of the custom whitelist rules, compiled once per load
+    pub custom_rule_conditions: Arc<CustomRuleConditions>,
+    /// Active metric tag rules, highest priority first
+    pub metric_tag_rules: Vec<MetricTagRule>,
+    /// Whether route paths match regardless of case
+    pub path_case: PathCase,
 }
 
 impl GatewayConfig {
     pub fn new() -> Self {
         Self {
             services: HashMap::new(),
+            client_certs: HashMap::new(),
             routes: Vec::new(),
             rate_limits: HashMap::new(),
             whitelist_rules: HashMap::new(),
+            custom_rule_conditions: Arc::new(HashMap::new()),
+            metric_tag_rules: Vec::new(),
+            path_case: PathCase::default(),
+        }
+    }
+
+    /// Find the highest-priority route matching a request whose backend is live
+    ///
+    /// Routes pointing at a backend that is no longer part of the active config
+    /// (disabled or removed) are skipped, so new requests fall through to the
+    /// next matching route instead of failing against a drained backend.
+    ///
+    /// Routes with `query_match` conditions only match when the query string
+    /// satisfies them, routes with `content_type_match` only when the
+    /// request's `Content-Type` does, and routes with `api_version` only when
+    /// the request's version is the same; on equal priority the route with
+    /// more query conditions wins, then the one with a content-type condition,
+    /// then the one with a version condition.
+    ///
+    /// The path is compared with each `path_pattern` per [`Self::path_case`];
+    /// the query string always compares exactly.
+    pub fn find_route(
+        &self,
+        path: &str,
+        method: &str,
+        query: Option<&str>,
+        content_type: Option<&str>,
+        version: Option<&str>,
+    ) -> Option<&ApiRoute> {
+        self.match_route(path, method, query, content_type, version, true)
+    }
+
+    fn match_route(
+        &self,
+        path: &str,
+        method: &str,
+        query: Option<&str>,
+        content_type: Option<&str>,
+        version: Option<&str>,
+        live_backend: bool,
+    ) -> Option<&ApiRoute> {
+        let params = query_match::parse_query(query);
+
+        // TODO: Implement proper pattern matching with wildcards
+        // For now, use prefix match
+        self.routes
+            .iter()
+            .filter(|route| {
+                route.method.to_string() == method.to_uppercase()
+                    && self.path_case.starts_with(path, &route.path_pattern)
+                    && query_match::matches(&route.query_match, &params)
+                    && content_type::matches(route.content_type_match.as_deref(), content_type)
+                    && api_version::matches(route.api_version.as_deref(), version)
+                    && (!live_backend || self.live_backend(route).is_some())
+            })
+            .max_by_key(|route| {
+                (
+                    route.priority,
+                    query_match::condition_count(&route.query_match),
+                    content_type::has_condition(route.content_type_match.as_deref()),
+                    api_version::has_condition(route.api_version.as_deref()),
+                )
+            })
+    }
+
+    /// The live backend of a blue/green route's inactive color, its canary
+    ///
+    /// `None` when the route has no green backend, or the canary is no longer
+    /// part of the active config.
+    pub fn canary_service(&self, route: &ApiRoute) -> Option<&BackendService> {
+        self.services
+            .get(&route.inactive_backend_service_id()?)
+            .filter(|service| service.is_active)
+    }
+
+    /// The live fallback backends of a route, in failover order
+    pub fn fallback_services(&self, route: &ApiRoute) -> Vec<&BackendService> {
+        route
+            .fallback_service_ids()
+            .iter()
+            .filter_map(|id| self.services.get(id))
+            .filter(|service| service.is_active)
+            .collect()
+    }
+
+    /// The backend a route sends its requests to
+    ///
+    /// The backend of the route's active color while it is live, otherwise
+    /// the first live fallback; `None` when the route has neither.
+    pub fn live_backend(&self, route: &ApiRoute) -> Option<&BackendService> {
+        self.services
+            .get(&route.active_backend_service_id())
+            .filter(|service| service.is_active)
+            .or_else(|| self.fallback_services(route).into_iter().next())
+    }
+
+    /// Find a route, honouring a method override where it is allowed
+    ///
+    /// The override only wins when the route it selects has
+    /// `allow_method_override` set, or when overrides are enabled for all
+    /// routes; otherwise the request is matched with its original method.
+    pub fn find_route_with_override(
+        &self,
+        request: RouteKey,
+        override_everywhere: bool,
+    ) -> Option<&ApiRoute> {
+        with_override(request, override_everywhere, |method| {
+            self.find_route(
+                request.path,
+                method,
+                request.query,
+                request.content_type,
+                request.api_version,
+            )
+        })
+    }
+
+    /// Why a request that [`Self::find_route_with_override`] found no route for is unrouted
+    ///
+    /// When a route would have matched had its backend been live, the request
+    /// is missing a backend rather than a route, and is reported against the
+    /// route it would have gone to.
+    pub fn route_miss(&self, request: RouteKey, override_everywhere: bool) -> RouteMiss {
+        with_override(request, override_everywhere, |method| {
+            self.match_route(
+                request.path,
+                method,
+                request.query,
+                request.content_type,
+                request.api_version,
+                false,
+            )
+        })
+        .map_or(RouteMiss::NoRoute, RouteMiss::no_backend)
+    }
+
+    /// Collect the rate limits that apply to a route
+    ///
+    /// See [`effective_rate_limits`] for the precedence; the admin API's
+    /// effective policy view uses the same function.
+    pub fn rate_limits_for(
+        &self,
+        route_id: &Uuid,
+        default_limit: Option<&RateLimit>,
+    ) -> Vec<RateLimit> {
+        let route_limits = self
+            .rate_limits
+            .get(&Some(*route_id))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let global_limits = self
+            .rate_limits
+            .get(&None)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        debug!(
+            "Found {} route-specific and {} global rate limits",
+            route_limits.len(),
+            global_limits.len()
+        );
+
+        effective_rate_limits(route_limits, global_limits, default_limit)
+    }
+}
+
+/// Match with the override method where the route allows it, else with the request's own
+fn with_override<'a>(
+    request: RouteKey,
+    override_everywhere: bool,
+    find: impl Fn(&str) -> Option<&'a ApiRoute>,
+) -> Option<&'a ApiRoute> {
+    request
+        .override_method
+        .and_then(&find)
+        .filter(|route| override_everywhere || route.allow_method_override)
+        .or_else(|| find(request.method))
+}
+
+/// A request as route matching sees it
+#[derive(Debug, Clone, Copy)]
+pub struct RouteKey<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub content_type: Option<&'a str>,
+    pub api_version: Option<&'a str>,
+    /// Method asked for with `X-HTTP-Method-Override`
+    pub override_method: Option<&'a str>,
+}
+
+/// Why a request couldn't be routed to a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteMiss {
+    /// No route matches the request
+    NoRoute,
+    /// A route matches, but the backend it points at and all its fallbacks are disabled or removed
+    NoBackend {
+        route_id: Uuid,
+        backend_service_id: Uuid,
+    },
+}
+
+impl RouteMiss {
+    pub fn no_backend(route: &ApiRoute) -> Self {
+        Self::NoBackend {
+            route_id: route.id,
+            backend_service_id: route.active_backend_service_id(),
+        }
+    }
+}
+
+/// Backoff for retrying a failed config reload before the next poll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReloadRetry {
+    /// Retries after the first failure, 0 to wait for the next poll instead
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each one after
+    pub base_delay: Duration,
+    /// Longest delay between two retries
+    pub max_delay: Duration,
+}
+
+impl ReloadRetry {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            max_retries: config.config_reload_max_retries,
+            base_delay: Duration::from_millis(config.config_reload_retry_base_ms),
+            max_delay: Duration::from_millis(config.config_reload_retry_max_ms),
+        }
+    }
+
+    /// Delay before retry number `retry`, counting from 0
+    fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay)
+    }
+
+    /// Run `reload`, retrying with backoff while it fails and retries are left
+    ///
+    /// Returns the last error once the retries are used up.
+    pub async fn run<F, Fut>(&self, mut reload: F) -> Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut retry = 0;
+        loop {
+            match reload().await {
+                Ok(()) => return Ok(()),
+                Err(e) if retry < self.max_retries => {
+                    let delay = self.delay(retry);
+                    warn!(
+                        "Failed to reload configuration, retrying in {}ms ({}/{}): {}",
+                        delay.as_millis(),
+                        retry + 1,
+                        self.max_retries,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    retry += 1;
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 }
@@ -39,13 +330,22 @@ impl GatewayConfig {
 pub struct ConfigLoader {
     db_pool: PgPool,
     config: Arc<ArcSwap<GatewayConfig>>,
+    /// Soft limits warned about on every load
+    limits: ConfigLimits,
+    /// How route paths are matched, carried into every snapshot
+    path_case: PathCase,
 }
 
 impl ConfigLoader {
-    pub fn new(db_pool: PgPool) -> Self {
+    pub fn new(db_pool: PgPool, limits: ConfigLimits, path_case: PathCase) -> Self {
         Self {
             db_pool,
-            config: Arc::new(ArcSwap::from_pointee(GatewayConfig::new())),
+            config: Arc::new(ArcSwap::from_pointee(GatewayConfig {
+                path_case,
+                ..GatewayConfig::new()
+            })),
+            limits,
+            path_case,
         }
     }
 
@@ -58,10 +358,37 @@ impl ConfigLoader {
         let services_result = service_repo.list(1, 1000).await?;
 
         let mut services_map = HashMap::new();
+        let mut client_certs = HashMap::new();
         for service in services_result {
-            if service.is_active {
-                services_map.insert(service.id.clone(), service);
+            if !service.is_active {
+                continue;
             }
+
+            // Keep a bad base_url out of the live config; its routes stop matching
+            if let Err(e) = UpstreamTarget::parse(&service.base_url) {
+                warn!(
+                    "Skipping backend service {} ({}): base_url {}",
+                    service.name, service.id, e
+                );
+                continue;
+            }
+
+            // Files are re-read on every load, so a rotated certificate is picked up
+            match ClientCert::for_service(&service) {
+                Some(Ok(client_cert)) => {
+                    client_certs.insert(service.id, client_cert);
+                }
+                Some(Err(e)) => {
+                    warn!(
+                        "Skipping backend service {} ({}): {}",
+                        service.name, service.id, e
+                    );
+                    continue;
+                }
+                None => {}
+            }
+
+            services_map.insert(service.id.clone(), service);
         }
 
         info!("Loaded {} active backend services", services_map.len());
@@ -107,11 +434,24 @@ impl ConfigLoader {
 
         // Group whitelist rules by route_id
         let mut whitelist_map: HashMap<Option<Uuid>, Vec<WhitelistRule>> = HashMap::new();
+        let mut custom_rule_conditions = CustomRuleConditions::new();
         for rule in whitelist_result {
             debug!(
                 "Loading whitelist rule: name={}, route_id={:?}, type={}",
                 rule.rule_name, rule.api_route_id, rule.rule_type
             );
+            if rule.rule_type == RuleType::Custom {
+                // Invalid conditions are rejected on write; a rule stored before that allows nothing
+                match parse_custom_conditions(&rule.config) {
+                    Ok(conditions) => {
+                        custom_rule_conditions.insert(rule.id, conditions);
+                    }
+                    Err(e) => warn!(
+                        "Custom whitelist rule {} has invalid conditions, it will allow nothing: {}",
+                        rule.rule_name, e
+                    ),
+                }
+            }
             whitelist_map
                 .entry(rule.api_route_id.clone())
                 .or_insert_with(Vec::new)
@@ -123,12 +463,29 @@ impl ConfigLoader {
             whitelist_map.values().map(|v| v.len()).sum::<usize>()
         );
 
+        // Load metric tag rules
+        let tag_rule_repo = MetricTagRuleRepository::new(self.db_pool.clone());
+        let metric_tag_rules = tag_rule_repo.list_active().await?;
+
+        info!("Loaded {} active metric tag rules", metric_tag_rules.len());
+
+        self.limits.warn_exceeded(ConfigCounts {
+            routes: active_routes.len() as u64,
+            services: services_map.len() as u64,
+            rules: (rate_limits_map.values().map(Vec::len).sum::<usize>()
+                + whitelist_map.values().map(Vec::len).sum::<usize>()) as u64,
+        });
+
         // Create new config snapshot
         let new_config = GatewayConfig {
             services: services_map,
+            client_certs,
             routes: active_routes,
             rate_limits: rate_limits_map,
             whitelist_rules: whitelist_map,
+            custom_rule_conditions: Arc::new(custom_rule_conditions),
+            metric_tag_rules,
+            path_case: self.path_case,
         };
 
         // Atomically swap the configuration
@@ -138,13 +495,21 @@ impl ConfigLoader {
         Ok(())
     }
 
+    /// How route paths are matched
+    pub fn path_case(&self) -> PathCase {
+        self.path_case
+    }
+
     /// Get current configuration snapshot
     pub fn get_config(&self) -> Arc<GatewayConfig> {
         self.config.load_full()
     }
 
     /// Start background task to watch for configuration changes
-    pub async fn start_reload_watcher(&self) {
+    ///
+    /// A failed reload is retried per `retry`; after that the loader waits for
+    /// the next poll, keeping the config it has.
+    pub async fn start_reload_watcher(&self, retry: ReloadRetry) {
         info!("Starting configuration reload watcher");
 
         // PostgreSQL LISTEN/NOTIFY implementation would go here
@@ -156,7 +521,7 @@ impl ConfigLoader {
 
             debug!("Checking for configuration updates");
 
-            if let Err(e) = self.load_config().await {
+            if let Err(e) = retry.run(|| self.load_config()).await {
                 error!("Failed to reload configuration: {}", e);
             } else {
                 debug!("Configuration check complete");
@@ -169,22 +534,513 @@ impl ConfigLoader {
         let config = self.get_config();
         config.services.get(service_id).cloned()
     }
+}
 
-    /// Find matching route for a request
-    pub fn find_route(&self, path: &str, method: &str) -> Option<ApiRoute> {
-        let config = self.get_config();
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use karateway_core::models::{DeploymentColor, DiscoveryType, HttpMethod};
+    use std::sync::atomic::{AtomicU32, Ordering};
 
-        // Find routes matching the method and path pattern
-        // TODO: Implement proper pattern matching with wildcards
-        // For now, use exact match
+    pub(crate) fn service(name: &str, base_url: &str) -> BackendService {
+        BackendService {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            base_url: base_url.to_string(),
+            health_check_url: None,
+            health_check_interval_seconds: None,
+            timeout_ms: None,
+            discovery_type: DiscoveryType::Static,
+            srv_name: None,
+            max_connections: None,
+            health_check_method: None,
+            health_check_headers: None,
+            health_check_body: None,
+            tls_sni: None,
+            capacity: None,
+            tls_client_cert_path: None,
+            tls_client_key_path: None,
+            liveness_ping_interval_seconds: None,
+            max_response_header_bytes: None,
+            max_response_header_count: None,
+            is_active: true,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    pub(crate) fn route(path_pattern: &str, service_id: Uuid, priority: i32) -> ApiRoute {
+        ApiRoute {
+            id: Uuid::new_v4(),
+            path_pattern: path_pattern.to_string(),
+            method: HttpMethod::GET,
+            backend_service_id: service_id,
+            green_backend_service_id: None,
+            active_color: DeploymentColor::Blue,
+            query_match: serde_json::json!({}),
+            strip_path_prefix: false,
+            preserve_host_header: false,
+            allow_method_override: false,
+            timeout_ms: None,
+            idle_timeout_ms: None,
+            fallback_backend_service_ids: serde_json::json!([]),
+            access_log_enabled: true,
+            cookie_domain: None,
+            cookie_path: None,
+            decompress_response: false,
+            api_version: None,
+            timing_headers: false,
+            debug_log_body: false,
+            content_type_match: None,
+            upstream_path_prefix: None,
+            coalesce_requests: false,
+            queue_depth: None,
+            queue_timeout_ms: None,
+            request_cost: None,
+            request_cost_header: None,
+            priority,
+            is_active: true,
+            metadata: serde_json::Value::Null,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    /// A request with no query, content type or version
+    fn request<'a>(
+        path: &'a str,
+        method: &'a str,
+        override_method: Option<&'a str>,
+    ) -> RouteKey<'a> {
+        RouteKey {
+            method,
+            path,
+            query: None,
+            content_type: None,
+            api_version: None,
+            override_method,
+        }
+    }
+
+    #[test]
+    fn test_find_route_skips_removed_backend() {
+        let primary = service("primary", "http://127.0.0.1:9001");
+        let fallback = service("fallback", "http://127.0.0.1:9002");
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![
+            route("/api", primary.id, 100),
+            route("/api", fallback.id, 10),
+        ];
+        config.services.insert(primary.id, primary.clone());
+        config.services.insert(fallback.id, fallback.clone());
+
+        let matched = config
+            .find_route("/api/users", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, primary.id);
+
+        // Disabling the primary drops it from the active config on reload
+        config.services.remove(&primary.id);
+
+        let matched = config
+            .find_route("/api/users", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, fallback.id);
+
+        config.services.remove(&fallback.id);
+        assert!(config
+            .find_route("/api/users", "GET", None, None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_removed_primary_falls_back() {
+        let primary = service("primary", "http://127.0.0.1:9001");
+        let fallback = service("fallback", "http://127.0.0.1:9002");
+        let mut orders = route("/api/orders", primary.id, 0);
+        orders.fallback_backend_service_ids = serde_json::json!([fallback.id]);
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![orders.clone()];
+        config.services.insert(fallback.id, fallback.clone());
+
+        // The primary was disabled, but the route still matches through its fallback
+        let matched = config
+            .find_route("/api/orders/1", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.id, orders.id);
+        assert_eq!(config.live_backend(matched).unwrap().id, fallback.id);
+
+        config.services.insert(primary.id, primary.clone());
+        assert_eq!(config.live_backend(&orders).unwrap().id, primary.id);
+    }
+
+    #[test]
+    fn test_route_without_backend_is_not_a_missing_route() {
+        let backend = service("orders", "http://127.0.0.1:9001");
+        let orders = route("/api/orders", backend.id, 0);
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![orders.clone()];
+        config.services.insert(backend.id, backend.clone());
+        assert_eq!(
+            config.route_miss(request("/unknown", "GET", None), false),
+            RouteMiss::NoRoute
+        );
+
+        // The backend is disabled, so the route no longer matches but is still there
+        config.services.remove(&backend.id);
+        assert!(config
+            .find_route("/api/orders/1", "GET", None, None, None)
+            .is_none());
+        assert_eq!(
+            config.route_miss(request("/api/orders/1", "GET", None), false),
+            RouteMiss::NoBackend {
+                route_id: orders.id,
+                backend_service_id: backend.id,
+            }
+        );
+        assert_eq!(
+            config.route_miss(request("/api/orders/1", "POST", None), false),
+            RouteMiss::NoRoute
+        );
+    }
+
+    #[test]
+    fn test_find_route_follows_active_color() {
+        let blue = service("blue", "http://127.0.0.1:9001");
+        let green = service("green", "http://127.0.0.1:9002");
+
+        let mut config = GatewayConfig::new();
+        let mut blue_green = route("/api", blue.id, 100);
+        blue_green.green_backend_service_id = Some(green.id);
+        config.routes = vec![blue_green];
+        config.services.insert(blue.id, blue.clone());
+        config.services.insert(green.id, green.clone());
+
+        let matched = config
+            .find_route("/api/users", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.active_backend_service_id(), blue.id);
+
+        config.routes[0].active_color = DeploymentColor::Green;
+        let matched = config
+            .find_route("/api/users", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.active_backend_service_id(), green.id);
+
+        // A switched route is only live while its green backend is
+        config.services.remove(&green.id);
+        assert!(config
+            .find_route("/api/users", "GET", None, None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_canary_is_the_inactive_color() {
+        let blue = service("blue", "http://127.0.0.1:9001");
+        let green = service("green", "http://127.0.0.1:9002");
+
+        let mut config = GatewayConfig::new();
+        let mut blue_green = route("/api", blue.id, 100);
+        blue_green.green_backend_service_id = Some(green.id);
+        config.services.insert(blue.id, blue.clone());
+        config.services.insert(green.id, green.clone());
+
+        assert_eq!(
+            config.canary_service(&blue_green).map(|s| s.id),
+            Some(green.id)
+        );
+        blue_green.active_color = DeploymentColor::Green;
+        assert_eq!(
+            config.canary_service(&blue_green).map(|s| s.id),
+            Some(blue.id)
+        );
+
+        // Nothing to force without a second backend, or once it's gone
+        assert!(config
+            .canary_service(&route("/api", blue.id, 100))
+            .is_none());
+        config.services.remove(&blue.id);
+        assert!(config.canary_service(&blue_green).is_none());
+    }
+
+    #[test]
+    fn test_find_route_with_query_conditions() {
+        let stable = service("stable", "http://127.0.0.1:9001");
+        let beta = service("beta", "http://127.0.0.1:9002");
+
+        let mut beta_route = route("/api", beta.id, 0);
+        beta_route.query_match = serde_json::json!({"version": "beta"});
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![route("/api", stable.id, 0), beta_route];
+        config.services.insert(stable.id, stable.clone());
+        config.services.insert(beta.id, beta.clone());
+
+        let matched = config
+            .find_route("/api/users", "GET", Some("version=beta"), None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, beta.id);
+
+        let matched = config
+            .find_route("/api/users", "GET", Some("version=stable"), None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, stable.id);
+
+        let matched = config
+            .find_route("/api/users", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, stable.id);
+    }
+
+    #[test]
+    fn test_find_route_with_mixed_case_paths() {
+        let stable = service("stable", "http://127.0.0.1:9001");
+        let beta = service("beta", "http://127.0.0.1:9002");
+
+        let mut beta_route = route("/api/Users", beta.id, 10);
+        beta_route.query_match = serde_json::json!({"version": "beta"});
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![route("/api/Users", stable.id, 0), beta_route];
+        config.services.insert(stable.id, stable.clone());
+        config.services.insert(beta.id, beta.clone());
+
+        // Case-sensitive by default
+        assert!(config
+            .find_route("/api/Users/1", "GET", None, None, None)
+            .is_some());
+        assert!(config
+            .find_route("/API/users/1", "GET", None, None, None)
+            .is_none());
+
+        config.path_case = PathCase::Insensitive;
+        let matched = config
+            .find_route("/API/users/1", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, stable.id);
+        let matched = config
+            .find_route("/api/USERS", "GET", Some("version=beta"), None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, beta.id);
+
+        // The query string still compares exactly
+        let matched = config
+            .find_route("/api/USERS", "GET", Some("version=BETA"), None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, stable.id);
+    }
+
+    #[test]
+    fn test_find_route_with_content_type_conditions() {
+        let api = service("api", "http://127.0.0.1:9001");
+        let uploads = service("uploads", "http://127.0.0.1:9002");
+
+        let mut upload_route = route("/files", uploads.id, 0);
+        upload_route.content_type_match = Some("multipart/form-data".to_string());
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![route("/files", api.id, 0), upload_route];
+        config.services.insert(api.id, api.clone());
+        config.services.insert(uploads.id, uploads.clone());
+
+        let matched = config
+            .find_route(
+                "/files",
+                "GET",
+                None,
+                Some("multipart/form-data; boundary=----abc"),
+                None,
+            )
+            .unwrap();
+        assert_eq!(matched.backend_service_id, uploads.id);
+
+        // Anything else falls back to the route without a condition
+        let matched = config
+            .find_route(
+                "/files",
+                "GET",
+                None,
+                Some("application/json; charset=utf-8"),
+                None,
+            )
+            .unwrap();
+        assert_eq!(matched.backend_service_id, api.id);
+        let matched = config
+            .find_route("/files", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, api.id);
+    }
+
+    #[test]
+    fn test_find_route_with_api_version() {
+        let current = service("users-v1", "http://127.0.0.1:9001");
+        let next = service("users-v2", "http://127.0.0.1:9002");
+
+        let mut v2_route = route("/api", next.id, 0);
+        v2_route.api_version = Some("v2".to_string());
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![route("/api", current.id, 0), v2_route];
+        config.services.insert(current.id, current.clone());
+        config.services.insert(next.id, next.clone());
+
+        let matched = config
+            .find_route("/api/v2/users", "GET", None, None, Some("2"))
+            .unwrap();
+        assert_eq!(matched.backend_service_id, next.id);
+
+        // Other versions and unversioned requests stay on the route without one
+        let matched = config
+            .find_route("/api/v1/users", "GET", None, None, Some("1"))
+            .unwrap();
+        assert_eq!(matched.backend_service_id, current.id);
+        let matched = config
+            .find_route("/api/users", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(matched.backend_service_id, current.id);
+    }
+
+    #[test]
+    fn test_method_override_changes_matched_route() {
+        let backend = service("orders", "http://127.0.0.1:9001");
+
+        let mut create_post = route("/orders", backend.id, 0);
+        create_post.method = HttpMethod::POST;
+        let mut delete = route("/orders", backend.id, 0);
+        delete.method = HttpMethod::DELETE;
+        delete.allow_method_override = true;
+        let mut patch = route("/orders", backend.id, 0);
+        patch.method = HttpMethod::PATCH;
+
+        let mut config = GatewayConfig::new();
+        config.routes = vec![create_post.clone(), delete.clone(), patch];
+        config.services.insert(backend.id, backend.clone());
+
+        // DELETE allows overrides, so a POST with the header lands there
+        let matched = config
+            .find_route_with_override(request("/orders/1", "POST", Some("DELETE")), false)
+            .unwrap();
+        assert_eq!(matched.id, delete.id);
+
+        // PATCH doesn't, so the request keeps its real method
+        let matched = config
+            .find_route_with_override(request("/orders/1", "POST", Some("PATCH")), false)
+            .unwrap();
+        assert_eq!(matched.id, create_post.id);
+
+        // ...unless overrides are enabled globally
+        let matched = config
+            .find_route_with_override(request("/orders/1", "POST", Some("PATCH")), true)
+            .unwrap();
+        assert_eq!(matched.method, HttpMethod::PATCH);
+
+        let matched = config
+            .find_route_with_override(request("/orders/1", "POST", None), true)
+            .unwrap();
+        assert_eq!(matched.id, create_post.id);
+    }
+
+    pub(crate) fn rate_limit(
+        name: &str,
+        api_route_id: Option<Uuid>,
+        max_requests: i32,
+    ) -> RateLimit {
+        RateLimit {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            api_route_id,
+            max_requests,
+            window_seconds: 60,
+            identifier_type: karateway_core::models::IdentifierType::Ip,
+            identifier_header: None,
+            is_active: true,
+            burst_size: None,
+            capacity_factor: None,
+            override_global: false,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_default_rate_limit_precedence() {
+        let limited = Uuid::new_v4();
+        let unlimited = Uuid::new_v4();
+        let default_limit = rate_limit("default", None, 100);
+
+        let mut config = GatewayConfig::new();
         config
-            .routes
-            .iter()
-            .filter(|route| {
-                route.method.to_string() == method.to_uppercase()
-                    && path.starts_with(&route.path_pattern)
+            .rate_limits
+            .insert(Some(limited), vec![rate_limit("orders", Some(limited), 10)]);
+
+        // Route-specific limits replace the default
+        let limits = config.rate_limits_for(&limited, Some(&default_limit));
+        assert_eq!(limits.len(), 1);
+        assert_eq!(limits[0].name, "orders");
+
+        // Routes without their own limit get the default
+        let limits = config.rate_limits_for(&unlimited, Some(&default_limit));
+        assert_eq!(limits.len(), 1);
+        assert_eq!(limits[0].name, "default");
+
+        // Global rows still stack on top of either
+        config
+            .rate_limits
+            .insert(None, vec![rate_limit("global", None, 1000)]);
+        let limits = config.rate_limits_for(&unlimited, Some(&default_limit));
+        assert_eq!(limits.len(), 2);
+        assert_eq!(config.rate_limits_for(&unlimited, None).len(), 1);
+    }
+
+    fn retry(max_retries: u32) -> ReloadRetry {
+        ReloadRetry {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_is_retried_until_it_succeeds() {
+        let attempts = AtomicU32::new(0);
+
+        // Fails once, like a brief database blip, then loads
+        let result = retry(5)
+            .run(|| async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(anyhow::anyhow!("connection refused")),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reload_retries_are_capped() {
+        let attempts = AtomicU32::new(0);
+
+        let result = retry(3)
+            .run(|| async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err(anyhow::anyhow!("connection refused"))
             })
-            .max_by_key(|route| route.priority)
-            .cloned()
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_reload_backoff_doubles_up_to_the_cap() {
+        let retry = retry(10);
+        let delays: Vec<u128> = (0..5).map(|n| retry.delay(n).as_millis()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 4, 4]);
     }
 }
@@ -0,0 +1,90 @@
+/// Base type of a `Content-Type` value: lowercased, without parameters
+///
+/// `application/json; charset=utf-8` becomes `application/json`.
+pub fn base_type(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// The base types listed in a route's `content_type_match`, empty when it has no condition
+fn conditions(condition: Option<&str>) -> Vec<String> {
+    condition
+        .unwrap_or_default()
+        .split(',')
+        .map(base_type)
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Whether a request's `Content-Type` satisfies a route's `content_type_match`
+///
+/// The condition is a comma-separated list of base types, any of which may
+/// match; `multipart/*` matches every multipart type. A route without a
+/// condition matches any request, with or without a content type, while a
+/// route with one never matches a request that has none.
+pub fn matches(condition: Option<&str>, content_type: Option<&str>) -> bool {
+    let expected = conditions(condition);
+    if expected.is_empty() {
+        return true;
+    }
+    let Some(actual) = content_type.map(base_type) else {
+        return false;
+    };
+
+    expected
+        .iter()
+        .any(|expected| match expected.strip_suffix("/*") {
+            Some(top_level) => actual
+                .split_once('/')
+                .is_some_and(|(actual_top, _)| actual_top == top_level),
+            None => *expected == actual,
+        })
+}
+
+/// Whether a route has a content-type condition, used to prefer the more specific match
+pub fn has_condition(condition: Option<&str>) -> bool {
+    !conditions(condition).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_type_drops_parameters() {
+        assert_eq!(
+            base_type("application/json; charset=utf-8"),
+            "application/json"
+        );
+        assert_eq!(
+            base_type(" Multipart/Form-Data; boundary=x"),
+            "multipart/form-data"
+        );
+        assert_eq!(base_type("text/plain"), "text/plain");
+    }
+
+    #[test]
+    fn test_matches_condition() {
+        let uploads = Some("multipart/form-data, application/octet-stream");
+        assert!(matches(uploads, Some("multipart/form-data; boundary=abc")));
+        assert!(matches(uploads, Some("application/octet-stream")));
+        assert!(!matches(uploads, Some("application/json")));
+        assert!(!matches(uploads, None));
+
+        assert!(matches(Some("multipart/*"), Some("multipart/mixed")));
+        assert!(!matches(Some("multipart/*"), Some("application/json")));
+    }
+
+    #[test]
+    fn test_no_condition_matches_anything() {
+        assert!(matches(None, Some("application/json")));
+        assert!(matches(None, None));
+        assert!(matches(Some(" , "), Some("text/plain")));
+        assert!(!has_condition(Some("")));
+        assert!(has_condition(Some("application/json")));
+    }
+}
@@ -0,0 +1,160 @@
+use http::header::SET_COOKIE;
+use http::HeaderValue;
+use karateway_core::models::ApiRoute;
+use pingora_http::ResponseHeader;
+
+/// A route's `cookie_domain` and `cookie_path`, applied to upstream `Set-Cookie` headers
+///
+/// Only attributes the upstream set are rewritten; a cookie without `Domain`
+/// is already host-only and one without `Path` already defaults to the
+/// request path the client sees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookieRewrite {
+    /// Empty removes the attribute
+    pub domain: Option<String>,
+    pub path: Option<String>,
+}
+
+impl CookieRewrite {
+    /// `None` when the route rewrites neither attribute
+    pub fn from_route(route: &ApiRoute) -> Option<Self> {
+        let trimmed = |value: &Option<String>| value.as_ref().map(|value| value.trim().to_string());
+        let rewrite = Self {
+            domain: trimmed(&route.cookie_domain),
+            path: trimmed(&route.cookie_path).filter(|path| !path.is_empty()),
+        };
+        (rewrite.domain.is_some() || rewrite.path.is_some()).then_some(rewrite)
+    }
+
+    /// Rewrite every `Set-Cookie` header of a response, keeping their order
+    pub fn apply(&self, response: &mut ResponseHeader) -> pingora_core::Result<()> {
+        let cookies: Vec<HeaderValue> = response
+            .headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .cloned()
+            .collect();
+        if cookies.is_empty() {
+            return Ok(());
+        }
+
+        response.remove_header(&SET_COOKIE);
+        for cookie in cookies {
+            // Values that aren't text are passed on as they came
+            let cookie = match cookie.to_str() {
+                Ok(text) => HeaderValue::from_str(&self.rewrite(text)).unwrap_or(cookie),
+                Err(_) => cookie,
+            };
+            response.append_header(SET_COOKIE, cookie)?;
+        }
+        Ok(())
+    }
+
+    /// One `Set-Cookie` value with its `Domain` and `Path` replaced
+    pub fn rewrite(&self, set_cookie: &str) -> String {
+        let mut parts = set_cookie.split(';');
+        let mut rewritten = vec![parts.next().unwrap_or_default().trim().to_string()];
+
+        for attribute in parts
+            .map(str::trim)
+            .filter(|attribute| !attribute.is_empty())
+        {
+            let name = attribute.split('=').next().unwrap_or_default().trim();
+            let replacement = if name.eq_ignore_ascii_case("domain") {
+                self.domain.as_ref()
+            } else if name.eq_ignore_ascii_case("path") {
+                self.path.as_ref()
+            } else {
+                None
+            };
+
+            match replacement {
+                Some(value) if value.is_empty() => {}
+                Some(value) => rewritten.push(format!("{}={}", name, value)),
+                None => rewritten.push(attribute.to_string()),
+            }
+        }
+        rewritten.join("; ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn rewriting(domain: Option<&str>, path: Option<&str>) -> CookieRewrite {
+        CookieRewrite {
+            domain: domain.map(str::to_string),
+            path: path.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_domain_and_path_are_rewritten() {
+        let rewrite = rewriting(Some("api.example.com"), Some("/shop"));
+        assert_eq!(
+            rewrite.rewrite("session=abc; Domain=backend.internal; Path=/; HttpOnly; Secure"),
+            "session=abc; Domain=api.example.com; Path=/shop; HttpOnly; Secure"
+        );
+        // Attribute names are case-insensitive and spacing varies
+        assert_eq!(
+            rewrite.rewrite("id=1;domain=.backend.internal;path=/app"),
+            "id=1; domain=api.example.com; path=/shop"
+        );
+        // Missing attributes aren't added
+        assert_eq!(rewrite.rewrite("id=1; Max-Age=60"), "id=1; Max-Age=60");
+
+        // An empty domain makes the cookie host-only
+        assert_eq!(
+            rewriting(Some(""), None).rewrite("id=1; Domain=backend.internal; Path=/"),
+            "id=1; Path=/"
+        );
+    }
+
+    #[test]
+    fn test_every_set_cookie_header_is_rewritten() {
+        let mut response = ResponseHeader::build(200, None).unwrap();
+        response
+            .append_header("Set-Cookie", "a=1; Domain=backend.internal")
+            .unwrap();
+        response
+            .append_header("Set-Cookie", "b=2; Domain=backend.internal; Path=/")
+            .unwrap();
+        response.insert_header("Content-Type", "text/html").unwrap();
+
+        rewriting(Some("api.example.com"), None)
+            .apply(&mut response)
+            .unwrap();
+
+        let cookies: Vec<&str> = response
+            .headers
+            .get_all("Set-Cookie")
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            cookies,
+            vec![
+                "a=1; Domain=api.example.com",
+                "b=2; Domain=api.example.com; Path=/"
+            ]
+        );
+        assert_eq!(response.headers.get("Content-Type").unwrap(), "text/html");
+    }
+
+    #[test]
+    fn test_routes_without_rewriting_pass_cookies_through() {
+        let mut route = crate::config_loader::tests::route("/shop", Uuid::new_v4(), 0);
+        assert_eq!(CookieRewrite::from_route(&route), None);
+
+        // A blank path is no rewrite, a blank domain removes it
+        route.cookie_path = Some("  ".to_string());
+        assert_eq!(CookieRewrite::from_route(&route), None);
+        route.cookie_domain = Some(String::new());
+        assert_eq!(
+            CookieRewrite::from_route(&route),
+            Some(rewriting(Some(""), None))
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use bytes::Bytes;
+use karateway_config::AppConfig;
+use pingora_core::upstreams::peer::{HttpPeer, Peer};
+use pingora_http::ResponseHeader;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::error_code::ErrorCode;
+use crate::timeouts::RouteTimeouts;
+
+/// Gateway-wide cap on a request's time, from `request_filter` to the last response byte
+///
+/// A safety net for the phases that have no timeout of their own, like a
+/// slow rate limiter or a long wait in a backend's queue. It only ever
+/// shortens the route's upstream timeouts, so a tighter route timeout still
+/// wins. Streaming responses stay exempt, as they are from `timeout_ms`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaxRequestDuration {
+    /// `None` when disabled
+    pub limit: Option<Duration>,
+}
+
+impl MaxRequestDuration {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            limit: (config.gateway_max_request_duration_ms > 0)
+                .then(|| Duration::from_millis(config.gateway_max_request_duration_ms)),
+        }
+    }
+
+    /// Time left for a request that started at `started_at`, `None` without a cap
+    pub fn remaining(&self, started_at: Instant, now: Instant) -> Option<Duration> {
+        self.limit
+            .map(|limit| limit.saturating_sub(now.saturating_duration_since(started_at)))
+    }
+
+    /// Whether a request that started at `started_at` is out of time
+    pub fn exceeded(&self, started_at: Instant, now: Instant) -> bool {
+        self.remaining(started_at, now) == Some(Duration::ZERO)
+    }
+
+    /// Run a phase of the request, `None` when the cap is reached first
+    pub async fn run<F: Future>(&self, started_at: Instant, phase: F) -> Option<F::Output> {
+        match self.remaining(started_at, Instant::now()) {
+            Some(remaining) => tokio::time::timeout(remaining, phase).await.ok(),
+            None => Some(phase.await),
+        }
+    }
+
+    /// Cap a route's total timeout; both are measured from the start of the request
+    pub fn limit_timeouts(&self, timeouts: &mut RouteTimeouts) {
+        if let Some(limit) = self.limit {
+            timeouts.total = Some(timeouts.total.map_or(limit, |total| total.min(limit)));
+        }
+    }
+
+    /// Keep connecting to and reading from the upstream within the time left
+    pub fn limit_peer(&self, peer: &mut HttpPeer, started_at: Instant, now: Instant) {
+        let Some(remaining) = self.remaining(started_at, now) else {
+            return;
+        };
+        let capped = |timeout: Option<Duration>| {
+            Some(timeout.map_or(remaining, |timeout| timeout.min(remaining)))
+        };
+        if let Some(options) = peer.get_mut_peer_options() {
+            options.total_connection_timeout = capped(options.total_connection_timeout);
+            options.read_timeout = capped(options.read_timeout);
+        }
+    }
+}
+
+/// Response for a request that ran out of time before reaching the upstream
+pub fn exceeded_response() -> pingora_core::Result<(ResponseHeader, Bytes)> {
+    ErrorCode::DeadlineExceeded.response("Request exceeded the maximum duration")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn max(ms: u64) -> MaxRequestDuration {
+        MaxRequestDuration {
+            limit: Some(Duration::from_millis(ms)),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_pre_proxy_phase_hits_the_deadline() {
+        let started = Instant::now();
+        let slow_rate_limiter = tokio::time::sleep(Duration::from_secs(5));
+        assert_eq!(max(50).run(started, slow_rate_limiter).await, None);
+        assert!(max(50).exceeded(started, Instant::now()));
+
+        // Phases that finish in time, or run without a cap, keep their result
+        assert_eq!(max(5_000).run(Instant::now(), async { 7 }).await, Some(7));
+        assert_eq!(
+            MaxRequestDuration::default()
+                .run(started, async { 7 })
+                .await,
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn test_shorter_route_timeouts_are_kept() {
+        let mut timeouts = RouteTimeouts {
+            total: Some(Duration::from_secs(2)),
+            idle: None,
+        };
+        max(30_000).limit_timeouts(&mut timeouts);
+        assert_eq!(timeouts.total, Some(Duration::from_secs(2)));
+
+        max(1_000).limit_timeouts(&mut timeouts);
+        assert_eq!(timeouts.total, Some(Duration::from_secs(1)));
+
+        // Routes without a total timeout get the cap
+        let mut timeouts = RouteTimeouts::default();
+        max(1_000).limit_timeouts(&mut timeouts);
+        assert_eq!(timeouts.total, Some(Duration::from_secs(1)));
+
+        MaxRequestDuration::default().limit_timeouts(&mut timeouts);
+        assert_eq!(timeouts.total, Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_upstream_reads_end_with_the_deadline() {
+        let started = Instant::now();
+        let mut peer = HttpPeer::new(("127.0.0.1", 9000), false, "orders".to_string());
+        peer.get_mut_peer_options().unwrap().read_timeout = Some(Duration::from_secs(30));
+
+        max(10_000).limit_peer(&mut peer, started, started + Duration::from_secs(4));
+        let options = peer.get_peer_options().unwrap();
+        assert_eq!(options.read_timeout, Some(Duration::from_secs(6)));
+        assert_eq!(
+            options.total_connection_timeout,
+            Some(Duration::from_secs(6))
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use bytes::Bytes;
+use flate2::write::{GzDecoder, ZlibDecoder};
+use http::header::{CONTENT_ENCODING, CONTENT_LENGTH, ETAG};
+use http::{Method, StatusCode};
+use karateway_config::AppConfig;
+use pingora_http::ResponseHeader;
+use std::io::Write;
+
+/// A content coding the gateway can undo
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gzip,
+    /// zlib-wrapped deflate, as `Content-Encoding: deflate` is specified
+    Deflate,
+}
+
+impl Encoding {
+    /// The coding of a response body, `None` when it is not compressed
+    ///
+    /// Codings other than gzip and deflate, and bodies compressed more than
+    /// once, are also `None` and forwarded untouched.
+    pub fn from_header(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            _ => None,
+        }
+    }
+}
+
+/// Global cap for routes with `decompress_response`
+#[derive(Debug, Clone, Copy)]
+pub struct Decompression {
+    /// Decompressed bodies past this are cut off, so a small compressed
+    /// response can't make the gateway produce an unbounded one
+    pub max_bytes: usize,
+}
+
+impl Decompression {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            max_bytes: config.gateway_decompress_max_bytes,
+        }
+    }
+
+    /// Start decompressing a response, dropping the headers that describe the compressed body
+    ///
+    /// `None`, leaving the response as it is, when it has no body (an answer
+    /// to `HEAD`, a 204 or a 304), isn't compressed with a supported coding,
+    /// or its compressed body alone is already over the cap. A strong `ETag`
+    /// is weakened, since it names the compressed bytes the client won't get.
+    pub fn start(&self, method: &Method, response: &mut ResponseHeader) -> Option<Decompressor> {
+        // Their headers describe a body that isn't there
+        if *method == Method::HEAD
+            || response.status == StatusCode::NO_CONTENT
+            || response.status == StatusCode::NOT_MODIFIED
+        {
+            return None;
+        }
+
+        let mut codings = response.headers.get_all(CONTENT_ENCODING).iter();
+        let encoding = match (codings.next(), codings.next()) {
+            (Some(value), None) => Encoding::from_header(value.to_str().ok()?)?,
+            _ => return None,
+        };
+
+        let compressed_length = response
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<usize>().ok());
+        if compressed_length.is_some_and(|length| length > self.max_bytes) {
+            return None;
+        }
+
+        let strong_etag = response
+            .headers
+            .get(ETAG)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| !value.starts_with("W/"))
+            .map(|value| format!("W/{}", value));
+        if let Some(weak) = strong_etag {
+            response.insert_header(ETAG, weak).ok()?;
+        }
+        response.remove_header(&CONTENT_ENCODING);
+        // The decompressed length isn't known until the body is done
+        response.remove_header(&CONTENT_LENGTH);
+
+        Some(Decompressor::new(encoding, self.max_bytes))
+    }
+}
+
+enum Decoder {
+    Gzip(GzDecoder<Vec<u8>>),
+    Deflate(ZlibDecoder<Vec<u8>>),
+}
+
+/// Streaming decoder for one response body
+pub struct Decompressor {
+    decoder: Decoder,
+    max_bytes: usize,
+    total_bytes: usize,
+    /// Whether any compressed bytes arrived, since an empty body can't be truncated
+    started: bool,
+}
+
+impl std::fmt::Debug for Decompressor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Decompressor")
+            .field("total_bytes", &self.total_bytes)
+            .finish()
+    }
+}
+
+impl Decompressor {
+    pub fn new(encoding: Encoding, max_bytes: usize) -> Self {
+        let decoder = match encoding {
+            Encoding::Gzip => Decoder::Gzip(GzDecoder::new(Vec::new())),
+            Encoding::Deflate => Decoder::Deflate(ZlibDecoder::new(Vec::new())),
+        };
+        Self {
+            decoder,
+            max_bytes,
+            total_bytes: 0,
+            started: false,
+        }
+    }
+
+    /// Decompress the next chunk of the body
+    ///
+    /// The chunk is fed in steps: each `write` inflates at most one 32KB
+    /// buffer of output, so the cap is checked long before a small chunk has
+    /// expanded into a huge one. An error when the body is corrupt or
+    /// decompresses past the cap.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Bytes, String> {
+        let mut output = Vec::new();
+        let mut input = chunk;
+        self.started |= !chunk.is_empty();
+        while !input.is_empty() {
+            let written = match &mut self.decoder {
+                Decoder::Gzip(decoder) => decoder.write(input),
+                Decoder::Deflate(decoder) => decoder.write(input),
+            }
+            .map_err(|e| format!("invalid compressed body: {}", e))?;
+            if written == 0 {
+                return Err("invalid compressed body: data after the end of the stream".to_string());
+            }
+            input = &input[written..];
+            output.extend_from_slice(&self.take_output()?);
+        }
+        Ok(Bytes::from(output))
+    }
+
+    /// Flush what is left once the upstream body has ended
+    ///
+    /// An empty body is fine: some upstreams label even those as compressed.
+    pub fn finish(&mut self) -> Result<Bytes, String> {
+        if !self.started {
+            return Ok(Bytes::new());
+        }
+        let finished = match &mut self.decoder {
+            Decoder::Gzip(decoder) => decoder.try_finish(),
+            Decoder::Deflate(decoder) => decoder.try_finish(),
+        };
+        finished.map_err(|e| format!("truncated compressed body: {}", e))?;
+        self.take_output()
+    }
+
+    fn take_output(&mut self) -> Result<Bytes, String> {
+        let output = std::mem::take(match &mut self.decoder {
+            Decoder::Gzip(decoder) => decoder.get_mut(),
+            Decoder::Deflate(decoder) => decoder.get_mut(),
+        });
+
+        self.total_bytes += output.len();
+        if self.total_bytes > self.max_bytes {
+            return Err(format!(
+                "decompressed body exceeds {} bytes",
+                self.max_bytes
+            ));
+        }
+        Ok(Bytes::from(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn gzipped_response(body: &[u8]) -> ResponseHeader {
+        let mut response = ResponseHeader::build(200, None).unwrap();
+        response.insert_header("Content-Encoding", "gzip").unwrap();
+        response
+            .insert_header("Content-Length", body.len().to_string())
+            .unwrap();
+        response
+            .insert_header("Content-Type", "application/json")
+            .unwrap();
+        response
+    }
+
+    #[test]
+    fn test_gzipped_upstream_response_is_decompressed() {
+        let body = br#"{"users": [{"id": 1, "name": "ada"}, {"id": 2, "name": "grace"}]}"#;
+        let compressed = gzip(body);
+        let mut response = gzipped_response(&compressed);
+
+        let mut decompressor = Decompression { max_bytes: 1024 }
+            .start(&Method::GET, &mut response)
+            .unwrap();
+        assert!(response.headers.get(CONTENT_ENCODING).is_none());
+        assert!(response.headers.get(CONTENT_LENGTH).is_none());
+        assert_eq!(
+            response.headers.get("Content-Type").unwrap(),
+            "application/json"
+        );
+
+        // Upstream chunks don't line up with anything in the compressed stream
+        let mut decompressed = Vec::new();
+        for chunk in compressed.chunks(7) {
+            decompressed.extend_from_slice(&decompressor.push(chunk).unwrap());
+        }
+        decompressed.extend_from_slice(&decompressor.finish().unwrap());
+        assert_eq!(decompressed, body);
+    }
+
+    #[test]
+    fn test_uncompressed_and_unsupported_responses_are_left_alone() {
+        let decompression = Decompression { max_bytes: 1024 };
+
+        let mut plain = ResponseHeader::build(200, None).unwrap();
+        plain.insert_header("Content-Length", "5").unwrap();
+        assert!(decompression.start(&Method::GET, &mut plain).is_none());
+        assert!(plain.headers.get(CONTENT_LENGTH).is_some());
+
+        let mut brotli = ResponseHeader::build(200, None).unwrap();
+        brotli.insert_header("Content-Encoding", "br").unwrap();
+        assert!(decompression.start(&Method::GET, &mut brotli).is_none());
+        assert_eq!(brotli.headers.get(CONTENT_ENCODING).unwrap(), "br");
+
+        assert_eq!(Encoding::from_header("gzip, br"), None);
+        assert_eq!(Encoding::from_header(" X-Gzip"), Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn test_size_cap() {
+        // Already too big compressed: forwarded as it is
+        let mut response = gzipped_response(&[0; 2048]);
+        assert!(Decompression { max_bytes: 1024 }
+            .start(&Method::GET, &mut response)
+            .is_none());
+        assert_eq!(response.headers.get(CONTENT_ENCODING).unwrap(), "gzip");
+
+        // Small compressed but huge decompressed: cut off
+        let compressed = gzip(&[b'a'; 64 * 1024]);
+        assert!(compressed.len() < 1024);
+        let mut decompressor = Decompressor::new(Encoding::Gzip, 1024);
+        let result = decompressor
+            .push(&compressed)
+            .and_then(|_| decompressor.finish());
+        assert!(result.unwrap_err().contains("exceeds 1024 bytes"));
+
+        // The cap stops inflating partway through a chunk
+        let bomb = gzip(&vec![0; 16 * 1024 * 1024]);
+        let mut decompressor = Decompressor::new(Encoding::Gzip, 1024);
+        assert!(decompressor.push(&bomb).is_err());
+        assert!(decompressor.total_bytes < 128 * 1024);
+    }
+
+    #[test]
+    fn test_bodiless_responses_are_left_alone() {
+        let decompression = Decompression { max_bytes: 1024 };
+        let compressed = gzip(b"{}");
+
+        // A HEAD answer keeps the headers of the GET it stands in for
+        let mut head = gzipped_response(&compressed);
+        assert!(decompression.start(&Method::HEAD, &mut head).is_none());
+        assert_eq!(head.headers.get(CONTENT_ENCODING).unwrap(), "gzip");
+        assert_eq!(
+            head.headers.get(CONTENT_LENGTH).unwrap(),
+            &compressed.len().to_string()
+        );
+
+        let mut not_modified = gzipped_response(&compressed);
+        not_modified.set_status(304).unwrap();
+        assert!(decompression
+            .start(&Method::GET, &mut not_modified)
+            .is_none());
+
+        // A labelled but empty body ends cleanly
+        let mut empty = gzipped_response(&[]);
+        let mut decompressor = decompression.start(&Method::GET, &mut empty).unwrap();
+        assert_eq!(decompressor.push(&[]).unwrap(), Bytes::new());
+        assert_eq!(decompressor.finish().unwrap(), Bytes::new());
+    }
+
+    #[test]
+    fn test_strong_etag_is_weakened() {
+        let decompression = Decompression { max_bytes: 1024 };
+        let compressed = gzip(b"{}");
+
+        let mut strong = gzipped_response(&compressed);
+        strong.insert_header("ETag", "\"v1\"").unwrap();
+        decompression.start(&Method::GET, &mut strong).unwrap();
+        assert_eq!(strong.headers.get(ETAG).unwrap(), "W/\"v1\"");
+
+        let mut weak = gzipped_response(&compressed);
+        weak.insert_header("ETag", "W/\"v1\"").unwrap();
+        decompression.start(&Method::GET, &mut weak).unwrap();
+        assert_eq!(weak.headers.get(ETAG).unwrap(), "W/\"v1\"");
+    }
+
+    #[test]
+    fn test_corrupt_body_is_an_error() {
+        let mut decompressor = Decompressor::new(Encoding::Gzip, 1024);
+        assert!(decompressor.push(b"not gzip at all").is_err());
+    }
+}
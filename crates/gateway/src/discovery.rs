@@ -0,0 +1,555 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use dashmap::DashMap;
+use hickory_resolver::system_conf::read_system_conf;
+use hickory_resolver::TokioAsyncResolver;
+use karateway_config::circuit_breaker::{self, BreakerReport, BreakerSnapshot};
+use karateway_core::models::{BackendService, DiscoveryType};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::config_loader::ConfigLoader;
+use crate::instance_health::{InstanceHealth, EJECTION};
+use crate::selection;
+
+/// Shortest time resolved instances are reused, whatever the record TTL
+const MIN_REFRESH: Duration = Duration::from_secs(5);
+/// Longest time resolved instances are reused, whatever the record TTL
+const MAX_REFRESH: Duration = Duration::from_secs(300);
+/// How long to wait for the nameserver to answer
+const DNS_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often instance breakers are reported to the admin API
+const BREAKER_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A DNS SRV record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+    pub ttl: u32,
+}
+
+/// A backend instance discovered from SRV records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instance {
+    pub host: String,
+    pub port: u16,
+    pub weight: u16,
+}
+
+/// Resolves SRV names into records
+#[async_trait]
+pub trait SrvResolver: Send + Sync {
+    async fn resolve_srv(&self, name: &str) -> Result<Vec<SrvRecord>>;
+}
+
+/// SRV resolver using the system's nameservers, from /etc/resolv.conf on Unix
+///
+/// hickory takes care of EDNS0, retrying truncated answers over TCP and
+/// random query ids.
+pub struct DnsSrvResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsSrvResolver {
+    pub fn from_system() -> Result<Self> {
+        let (config, mut opts) =
+            read_system_conf().context("Failed to read the system DNS configuration")?;
+        opts.timeout = DNS_TIMEOUT;
+
+        Ok(Self {
+            resolver: TokioAsyncResolver::tokio(config, opts),
+        })
+    }
+}
+
+#[async_trait]
+impl SrvResolver for DnsSrvResolver {
+    async fn resolve_srv(&self, name: &str) -> Result<Vec<SrvRecord>> {
+        let lookup = self.resolver.srv_lookup(name).await?;
+        let ttl = lookup
+            .as_lookup()
+            .valid_until()
+            .saturating_duration_since(Instant::now())
+            .as_secs() as u32;
+
+        Ok(lookup
+            .iter()
+            .map(|srv| SrvRecord {
+                priority: srv.priority(),
+                weight: srv.weight(),
+                port: srv.port(),
+                target: srv.target().to_utf8(),
+                ttl,
+            })
+            .collect())
+    }
+}
+
+struct Resolved {
+    instances: Vec<Instance>,
+    expires_at: Instant,
+}
+
+/// Keeps the instances of `dns_srv` backend services up to date
+pub struct ServiceDiscovery {
+    resolver: Arc<dyn SrvResolver>,
+    /// Map of service_id -> instances from the last successful resolution
+    resolved: DashMap<Uuid, Resolved>,
+    /// Passive health per (service_id, host, port), kept only for resolved instances
+    instance_health: DashMap<(Uuid, String, u16), InstanceHealth>,
+}
+
+impl ServiceDiscovery {
+    pub fn new(resolver: Arc<dyn SrvResolver>) -> Self {
+        Self {
+            resolver,
+            resolved: DashMap::new(),
+            instance_health: DashMap::new(),
+        }
+    }
+
+    /// Pick a discovered instance for a request
+    ///
+    /// Returns `None` for static services and when resolution has failed, in
+    /// which case the caller should use the service's `base_url`.
+    ///
+    /// Instances ejected for failing requests are skipped until they are let
+    /// back in. If every instance is ejected they are all used anyway, since
+    /// sending traffic to a struggling backend beats sending it nowhere.
+    pub fn pick_instance(&self, service_id: &Uuid, seed: &str) -> Option<Instance> {
+        let resolved = self.resolved.get(service_id)?;
+        let now = Instant::now();
+        let available: Vec<&Instance> = resolved
+            .instances
+            .iter()
+            .filter(|instance| self.is_available(service_id, instance, now))
+            .collect();
+        let candidates = if available.is_empty() {
+            resolved.instances.iter().collect()
+        } else {
+            available
+        };
+
+        selection::select_weighted(
+            &candidates,
+            // Weight 0 means "rarely" in SRV, not "never"
+            |instance| (instance.weight as u32).max(1),
+            seed,
+        )
+        .map(|instance| (*instance).clone())
+    }
+
+    /// Every instance discovered for a service, empty for static services
+    pub fn instances(&self, service_id: &Uuid) -> Vec<Instance> {
+        self.resolved
+            .get(service_id)
+            .map(|resolved| resolved.instances.clone())
+            .unwrap_or_default()
+    }
+
+    fn is_available(&self, service_id: &Uuid, instance: &Instance, now: Instant) -> bool {
+        self.instance_health
+            .get(&(*service_id, instance.host.clone(), instance.port))
+            .map(|health| health.is_available(now))
+            .unwrap_or(true)
+    }
+
+    /// Record whether a request to a discovered instance failed
+    pub fn record_outcome(&self, service_id: Uuid, host: &str, port: u16, failed: bool) {
+        let now = Instant::now();
+        let mut health = self
+            .instance_health
+            .entry((service_id, host.to_string(), port))
+            .or_insert_with(|| InstanceHealth::new(now));
+
+        if health.record(failed, now) {
+            warn!(
+                "Instance {}:{} of backend {} is failing, skipping it for {}s",
+                host,
+                port,
+                service_id,
+                EJECTION.as_secs()
+            );
+        }
+    }
+
+    /// The breaker of every instance that has served a request, ordered by service and address
+    pub fn breakers(&self, now: Instant) -> Vec<BreakerSnapshot> {
+        let wall_now = Utc::now();
+        let mut breakers: Vec<BreakerSnapshot> = self
+            .instance_health
+            .iter()
+            .map(|entry| {
+                let (service_id, host, port) = entry.key().clone();
+                let health = entry.value();
+                let (window_requests, window_failures) = health.window();
+                BreakerSnapshot {
+                    service_id,
+                    host,
+                    port,
+                    state: health.state(now),
+                    consecutive_failures: health.consecutive_failures(),
+                    window_requests,
+                    window_failures,
+                    retry_at: health
+                        .retry_in(now)
+                        .and_then(|retry_in| chrono::Duration::from_std(retry_in).ok())
+                        .map(|retry_in| wall_now + retry_in),
+                }
+            })
+            .collect();
+        breakers
+            .sort_by(|a, b| (a.service_id, &a.host, a.port).cmp(&(b.service_id, &b.host, b.port)));
+        breakers
+    }
+
+    /// Start reporting instance breakers to Redis, where the admin API lists them
+    ///
+    /// Reports go under a random id per gateway process, so the admin API sees
+    /// every gateway's breakers. A report expires a few intervals after the
+    /// gateway stops sending it.
+    pub fn start_breaker_reporter(self: Arc<Self>, redis_client: redis::Client) {
+        let gateway_id = Uuid::new_v4().to_string();
+        tokio::spawn(async move {
+            let mut report_interval = interval(BREAKER_REPORT_INTERVAL);
+
+            loop {
+                report_interval.tick().await;
+                let report = BreakerReport {
+                    gateway_id: gateway_id.clone(),
+                    reported_at: Utc::now(),
+                    breakers: self.breakers(Instant::now()),
+                };
+                let result = match redis_client.get_multiplexed_async_connection().await {
+                    Ok(mut conn) => {
+                        circuit_breaker::store(
+                            &mut conn,
+                            &report,
+                            BREAKER_REPORT_INTERVAL.as_secs() * 3,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+
+                if let Err(e) = result {
+                    warn!("Failed to report circuit breakers: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Start the background task re-resolving services as their records expire
+    pub fn start_background_resolver(self: Arc<Self>, config_loader: Arc<ConfigLoader>) {
+        tokio::spawn(async move {
+            info!("Starting DNS SRV discovery background task");
+            let mut refresh_interval = interval(MIN_REFRESH);
+
+            loop {
+                refresh_interval.tick().await;
+                self.refresh_due(&config_loader).await;
+            }
+        });
+    }
+
+    async fn refresh_due(&self, config_loader: &ConfigLoader) {
+        let config = config_loader.get_config();
+
+        // Forget services that were removed or switched back to static
+        self.resolved.retain(|id, _| {
+            config
+                .services
+                .get(id)
+                .is_some_and(|s| s.discovery_type == DiscoveryType::DnsSrv)
+        });
+
+        let now = Instant::now();
+        for service in config.services.values() {
+            if service.discovery_type != DiscoveryType::DnsSrv {
+                continue;
+            }
+
+            let due = self
+                .resolved
+                .get(&service.id)
+                .map(|resolved| resolved.expires_at <= now)
+                .unwrap_or(true);
+
+            if due {
+                self.refresh(service).await;
+            }
+        }
+
+        // Forget the health of instances that are no longer resolved
+        let live: HashSet<(Uuid, String, u16)> = self
+            .resolved
+            .iter()
+            .flat_map(|entry| {
+                let service_id = *entry.key();
+                entry
+                    .instances
+                    .iter()
+                    .map(move |instance| (service_id, instance.host.clone(), instance.port))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        self.instance_health.retain(|key, _| live.contains(key));
+    }
+
+    /// Resolve a service's SRV name and replace its instances
+    async fn refresh(&self, service: &BackendService) {
+        let Some(srv_name) = &service.srv_name else {
+            return;
+        };
+
+        match self.resolver.resolve_srv(srv_name).await {
+            Ok(records) => {
+                let ttl = records.iter().map(|r| r.ttl).min().unwrap_or(0);
+                let instances = instances_from_records(records);
+
+                if instances.is_empty() {
+                    warn!(
+                        "No SRV targets for {} ({}), falling back to {}",
+                        service.name, srv_name, service.base_url
+                    );
+                    self.resolved.remove(&service.id);
+                    return;
+                }
+
+                debug!(
+                    "Resolved {} instances for {} ({})",
+                    instances.len(),
+                    service.name,
+                    srv_name
+                );
+
+                let refresh_in = Duration::from_secs(ttl as u64).clamp(MIN_REFRESH, MAX_REFRESH);
+                self.resolved.insert(
+                    service.id,
+                    Resolved {
+                        instances,
+                        expires_at: Instant::now() + refresh_in,
+                    },
+                );
+            }
+            Err(e) => {
+                warn!(
+                    "SRV resolution failed for {} ({}): {}, falling back to {}",
+                    service.name, srv_name, e, service.base_url
+                );
+                self.resolved.remove(&service.id);
+            }
+        }
+    }
+}
+
+/// Turn SRV records into instances, keeping only the most preferred priority
+fn instances_from_records(records: Vec<SrvRecord>) -> Vec<Instance> {
+    // A target of "." means the service is explicitly not available
+    let records: Vec<SrvRecord> = records
+        .into_iter()
+        .filter(|r| !r.target.is_empty() && r.target != ".")
+        .collect();
+
+    let Some(best_priority) = records.iter().map(|r| r.priority).min() else {
+        return Vec::new();
+    };
+
+    records
+        .into_iter()
+        .filter(|r| r.priority == best_priority)
+        .map(|r| Instance {
+            host: r.target.trim_end_matches('.').to_string(),
+            port: r.port,
+            weight: r.weight,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::config_loader::tests::service;
+    use crate::instance_health::FAILURE_THRESHOLD;
+    use anyhow::anyhow;
+    use karateway_config::circuit_breaker::BreakerState;
+    use std::sync::Mutex;
+
+    /// Resolver returning whatever the test queued up
+    struct MockResolver {
+        response: Mutex<Result<Vec<SrvRecord>, String>>,
+    }
+
+    impl MockResolver {
+        fn set(&self, response: Result<Vec<SrvRecord>, String>) {
+            *self.response.lock().unwrap() = response;
+        }
+    }
+
+    #[async_trait]
+    impl SrvResolver for MockResolver {
+        async fn resolve_srv(&self, _name: &str) -> Result<Vec<SrvRecord>> {
+            self.response
+                .lock()
+                .unwrap()
+                .clone()
+                .map_err(|e| anyhow!(e))
+        }
+    }
+
+    fn record(priority: u16, weight: u16, port: u16, target: &str) -> SrvRecord {
+        SrvRecord {
+            priority,
+            weight,
+            port,
+            target: target.to_string(),
+            ttl: 30,
+        }
+    }
+
+    /// Discovery that has resolved `backend` to `instances`
+    pub(crate) async fn discovered(
+        backend: &BackendService,
+        instances: &[(&str, u16)],
+    ) -> ServiceDiscovery {
+        let records = instances
+            .iter()
+            .map(|(host, port)| record(10, 1, *port, host))
+            .collect();
+        let discovery = ServiceDiscovery::new(Arc::new(MockResolver {
+            response: Mutex::new(Ok(records)),
+        }));
+
+        let mut backend = backend.clone();
+        backend
+            .srv_name
+            .get_or_insert_with(|| "_http._tcp.orders.service.consul".to_string());
+        discovery.refresh(&backend).await;
+        discovery
+    }
+
+    fn srv_service() -> BackendService {
+        let mut backend = service("orders", "http://orders.fallback:8080");
+        backend.discovery_type = DiscoveryType::DnsSrv;
+        backend.srv_name = Some("_http._tcp.orders.service.consul".to_string());
+        backend
+    }
+
+    #[tokio::test]
+    async fn test_refresh_uses_resolved_instances_and_falls_back_on_failure() {
+        let resolver = Arc::new(MockResolver {
+            response: Mutex::new(Ok(vec![
+                record(10, 5, 9001, "orders-1.node.consul."),
+                record(20, 5, 9002, "orders-backup.node.consul."),
+            ])),
+        });
+        let discovery = ServiceDiscovery::new(resolver.clone());
+        let backend = srv_service();
+
+        discovery.refresh(&backend).await;
+        assert_eq!(
+            discovery.pick_instance(&backend.id, "req-1"),
+            Some(Instance {
+                host: "orders-1.node.consul".to_string(),
+                port: 9001,
+                weight: 5,
+            })
+        );
+
+        // Resolution failure drops the instances so the proxy uses base_url
+        resolver.set(Err("SERVFAIL".to_string()));
+        discovery.refresh(&backend).await;
+        assert_eq!(discovery.pick_instance(&backend.id, "req-1"), None);
+    }
+
+    #[tokio::test]
+    async fn test_failing_instance_gets_no_traffic_until_recovered() {
+        let resolver = Arc::new(MockResolver {
+            response: Mutex::new(Ok(vec![
+                record(10, 1, 9001, "orders-1.node.consul."),
+                record(10, 1, 9002, "orders-2.node.consul."),
+            ])),
+        });
+        let discovery = ServiceDiscovery::new(resolver);
+        let backend = srv_service();
+        discovery.refresh(&backend).await;
+
+        let picked_ports = |discovery: &ServiceDiscovery| -> HashSet<u16> {
+            (0..100)
+                .filter_map(|i| discovery.pick_instance(&backend.id, &format!("client-{}", i)))
+                .map(|instance| instance.port)
+                .collect()
+        };
+        assert_eq!(picked_ports(&discovery), HashSet::from([9001, 9002]));
+
+        for _ in 0..FAILURE_THRESHOLD {
+            discovery.record_outcome(backend.id, "orders-2.node.consul", 9002, true);
+        }
+        discovery.record_outcome(backend.id, "orders-1.node.consul", 9001, false);
+        assert_eq!(picked_ports(&discovery), HashSet::from([9001]));
+
+        // With every instance ejected, traffic still goes somewhere
+        for _ in 0..FAILURE_THRESHOLD {
+            discovery.record_outcome(backend.id, "orders-1.node.consul", 9001, true);
+        }
+        assert_eq!(picked_ports(&discovery), HashSet::from([9001, 9002]));
+    }
+
+    #[tokio::test]
+    async fn test_open_breaker_is_listed() {
+        let resolver = Arc::new(MockResolver {
+            response: Mutex::new(Ok(vec![
+                record(10, 1, 9001, "orders-1.node.consul."),
+                record(10, 1, 9002, "orders-2.node.consul."),
+            ])),
+        });
+        let discovery = ServiceDiscovery::new(resolver);
+        let backend = srv_service();
+        discovery.refresh(&backend).await;
+
+        discovery.record_outcome(backend.id, "orders-1.node.consul", 9001, false);
+        for _ in 0..FAILURE_THRESHOLD {
+            discovery.record_outcome(backend.id, "orders-2.node.consul", 9002, true);
+        }
+
+        let breakers = discovery.breakers(Instant::now());
+        assert_eq!(breakers.len(), 2);
+        assert_eq!(breakers[0].port, 9001);
+        assert_eq!(breakers[0].state, BreakerState::Closed);
+        assert_eq!(breakers[0].retry_at, None);
+
+        let open = &breakers[1];
+        assert_eq!(open.service_id, backend.id);
+        assert_eq!(open.host, "orders-2.node.consul");
+        assert_eq!(open.state, BreakerState::Open);
+        assert_eq!(open.consecutive_failures, FAILURE_THRESHOLD);
+        assert_eq!(open.window_failures, FAILURE_THRESHOLD);
+        assert!(open.retry_at.is_some_and(|at| at > Utc::now()));
+
+        // The ejection passing leaves it half-open until a request lets it back in
+        let breakers = discovery.breakers(Instant::now() + EJECTION);
+        assert_eq!(breakers[1].state, BreakerState::HalfOpen);
+        assert_eq!(breakers[1].retry_at, None);
+    }
+
+    #[test]
+    fn test_instances_keep_most_preferred_priority() {
+        let instances = instances_from_records(vec![
+            record(20, 1, 9003, "c."),
+            record(10, 3, 9001, "a."),
+            record(10, 1, 9002, "b."),
+            record(0, 1, 9000, "."),
+        ]);
+
+        let hosts: Vec<&str> = instances.iter().map(|i| i.host.as_str()).collect();
+        assert_eq!(hosts, vec!["a", "b"]);
+    }
+}
@@ -0,0 +1,247 @@
+use bytes::Bytes;
+use pingora_core::{Error, ErrorSource, ErrorType, Result};
+use pingora_http::ResponseHeader;
+use serde_json::{json, Value};
+
+/// Stable machine-readable code of a gateway error, sent as `error_code`
+///
+/// The `error` and `message` texts of a body may be reworded; the codes
+/// don't change, so clients and dashboards can branch on them. The same code
+/// is recorded in the `error_code` metadata of the matching audit event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Maintenance,
+    ExpectationFailed,
+    RouteNotFound,
+    NoBackend,
+    WhitelistDenied,
+    BackendUnhealthy,
+    RateLimited,
+    ClientRequestLimit,
+    RateLimiterUnavailable,
+    BackendAtCapacity,
+    BackendMisconfigured,
+    DeadlineExceeded,
+    UpstreamTimeout,
+    UpstreamError,
+    InvalidRequest,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::Maintenance => "MAINTENANCE",
+            ErrorCode::ExpectationFailed => "EXPECTATION_FAILED",
+            ErrorCode::RouteNotFound => "ROUTE_NOT_FOUND",
+            ErrorCode::NoBackend => "NO_BACKEND",
+            ErrorCode::WhitelistDenied => "WHITELIST_DENIED",
+            ErrorCode::BackendUnhealthy => "BACKEND_UNHEALTHY",
+            ErrorCode::RateLimited => "RATE_LIMITED",
+            ErrorCode::ClientRequestLimit => "CLIENT_REQUEST_LIMIT",
+            ErrorCode::RateLimiterUnavailable => "RATE_LIMITER_UNAVAILABLE",
+            ErrorCode::BackendAtCapacity => "BACKEND_AT_CAPACITY",
+            ErrorCode::BackendMisconfigured => "BACKEND_MISCONFIGURED",
+            ErrorCode::DeadlineExceeded => "DEADLINE_EXCEEDED",
+            ErrorCode::UpstreamTimeout => "UPSTREAM_TIMEOUT",
+            ErrorCode::UpstreamError => "UPSTREAM_ERROR",
+            ErrorCode::InvalidRequest => "INVALID_REQUEST",
+            ErrorCode::InternalError => "INTERNAL_ERROR",
+        }
+    }
+
+    /// Status the gateway answers with
+    pub fn status(self) -> u16 {
+        match self {
+            ErrorCode::InvalidRequest => 400,
+            ErrorCode::WhitelistDenied => 403,
+            ErrorCode::RouteNotFound => 404,
+            ErrorCode::ExpectationFailed => 417,
+            ErrorCode::RateLimited | ErrorCode::ClientRequestLimit => 429,
+            ErrorCode::InternalError => 500,
+            ErrorCode::BackendMisconfigured | ErrorCode::UpstreamError => 502,
+            ErrorCode::Maintenance
+            | ErrorCode::NoBackend
+            | ErrorCode::BackendUnhealthy
+            | ErrorCode::RateLimiterUnavailable
+            | ErrorCode::BackendAtCapacity => 503,
+            ErrorCode::DeadlineExceeded | ErrorCode::UpstreamTimeout => 504,
+        }
+    }
+
+    /// Short description of the error, the body's `error` field
+    pub fn title(self) -> &'static str {
+        match self {
+            ErrorCode::RateLimited => "Rate limit exceeded",
+            code => http::StatusCode::from_u16(code.status())
+                .ok()
+                .and_then(|status| status.canonical_reason())
+                .unwrap_or("Error"),
+        }
+    }
+
+    /// `{"error": ..., "error_code": ..., "message": ...}`
+    pub fn body(self, message: &str) -> Value {
+        json!({
+            "error": self.title(),
+            "error_code": self.as_str(),
+            "message": message,
+        })
+    }
+
+    /// Audit event metadata naming the code
+    pub fn metadata(self) -> Value {
+        json!({ "error_code": self.as_str() })
+    }
+
+    /// The `body` sent with the code's status
+    pub fn response(self, message: &str) -> Result<(ResponseHeader, Bytes)> {
+        json_response(self.status(), &self.body(message))
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A JSON error response, for bodies that carry more than `ErrorCode::body`
+pub fn json_response(status: u16, body: &Value) -> Result<(ResponseHeader, Bytes)> {
+    let body = Bytes::from(body.to_string());
+
+    let mut resp = ResponseHeader::build(status, None)?;
+    resp.insert_header("Content-Type", "application/json")?;
+    resp.insert_header("Content-Length", &body.len().to_string())?;
+    Ok((resp, body))
+}
+
+/// Status and code for a request that failed while being proxied
+///
+/// Statuses follow pingora's defaults, except that an upstream timeout is a
+/// `504` rather than a `502`. `None` when the client is gone and nothing
+/// can be sent.
+pub fn proxy_failure(e: &Error) -> Option<(u16, ErrorCode)> {
+    if let ErrorType::HTTPStatus(status) = e.etype() {
+        let code = match status {
+            504 => ErrorCode::DeadlineExceeded,
+            502 => ErrorCode::UpstreamError,
+            400..=499 => ErrorCode::InvalidRequest,
+            _ => ErrorCode::InternalError,
+        };
+        return Some((*status, code));
+    }
+
+    let code = match e.esource() {
+        ErrorSource::Upstream => match e.etype() {
+            ErrorType::ConnectTimedout
+            | ErrorType::ReadTimedout
+            | ErrorType::WriteTimedout
+            | ErrorType::TLSHandshakeTimedout => ErrorCode::UpstreamTimeout,
+            _ => ErrorCode::UpstreamError,
+        },
+        ErrorSource::Downstream => match e.etype() {
+            ErrorType::WriteError | ErrorType::ReadError | ErrorType::ConnectionClosed => {
+                return None
+            }
+            _ => ErrorCode::InvalidRequest,
+        },
+        ErrorSource::Internal | ErrorSource::Unset => ErrorCode::InternalError,
+    };
+    Some((code.status(), code))
+}
+
+/// Body of a `proxy_failure`; the error itself stays out, it can name internal hosts
+pub fn proxy_failure_body(code: ErrorCode) -> Value {
+    let message = match code {
+        ErrorCode::DeadlineExceeded => "Request exceeded the maximum duration",
+        ErrorCode::UpstreamTimeout => "Backend service did not respond in time",
+        ErrorCode::UpstreamError => "Backend service request failed",
+        ErrorCode::InvalidRequest => "The request could not be processed",
+        _ => "The gateway failed to process the request",
+    };
+    code.body(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_of(body: &[u8]) -> String {
+        let body: Value = serde_json::from_slice(body).unwrap();
+        body["error_code"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_every_error_body_carries_its_code() {
+        let (resp, body) = ErrorCode::WhitelistDenied
+            .response("Access denied by whitelist rules")
+            .unwrap();
+        assert_eq!(resp.status.as_u16(), 403);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({
+                "error": "Forbidden",
+                "error_code": "WHITELIST_DENIED",
+                "message": "Access denied by whitelist rules",
+            })
+        );
+        assert_eq!(
+            ErrorCode::RateLimited.body("")["error"],
+            "Rate limit exceeded"
+        );
+        assert_eq!(
+            ErrorCode::WhitelistDenied.metadata(),
+            json!({ "error_code": "WHITELIST_DENIED" })
+        );
+
+        // Bodies built outside this module use the same codes
+        assert_eq!(code_of(&crate::maintenance::default_body()), "MAINTENANCE");
+        assert_eq!(
+            code_of(&crate::deadline::exceeded_response().unwrap().1),
+            "DEADLINE_EXCEEDED"
+        );
+        assert_eq!(
+            code_of(
+                &crate::expect_continue::expectation_failed_response()
+                    .unwrap()
+                    .1
+            ),
+            "EXPECTATION_FAILED"
+        );
+    }
+
+    #[test]
+    fn test_proxy_failures_are_mapped_to_codes() {
+        let timeout = Error::new_up(ErrorType::ReadTimedout);
+        assert_eq!(
+            proxy_failure(&timeout),
+            Some((504, ErrorCode::UpstreamTimeout))
+        );
+        let refused = Error::new_up(ErrorType::ConnectRefused);
+        assert_eq!(
+            proxy_failure(&refused),
+            Some((502, ErrorCode::UpstreamError))
+        );
+
+        let deadline = Error::explain(ErrorType::HTTPStatus(504), "deadline");
+        assert_eq!(
+            proxy_failure(&deadline),
+            Some((504, ErrorCode::DeadlineExceeded))
+        );
+        let headers = Error::explain(ErrorType::HTTPStatus(502), "headers too large");
+        assert_eq!(
+            proxy_failure(&headers),
+            Some((502, ErrorCode::UpstreamError))
+        );
+
+        // Nothing is sent to a client that went away
+        let closed = Error::new_down(ErrorType::ConnectionClosed);
+        assert_eq!(proxy_failure(&closed), None);
+        let bad_request = Error::new_down(ErrorType::InvalidHTTPHeader);
+        assert_eq!(
+            proxy_failure(&bad_request),
+            Some((400, ErrorCode::InvalidRequest))
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use bytes::Bytes;
+use http::Version;
+use pingora_core::protocols::http::ServerSession;
+use pingora_core::Result;
+use pingora_http::{RequestHeader, ResponseHeader};
+
+use crate::error_code::ErrorCode;
+
+/// What a request's `Expect` header asks of the gateway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    None,
+    /// The client holds its body back until it is told to send it
+    Continue,
+    /// Anything other than `100-continue`, which can't be met (RFC 9110 §10.1.1)
+    Unsupported(String),
+}
+
+/// Read the expectation of a request
+///
+/// HTTP/1.0 clients can't wait for an interim response, so theirs is ignored.
+pub fn expectation(req: &RequestHeader) -> Expectation {
+    let Some(value) = req.headers.get(http::header::EXPECT) else {
+        return Expectation::None;
+    };
+    if req.version == Version::HTTP_10 {
+        return Expectation::None;
+    }
+
+    let value = String::from_utf8_lossy(value.as_bytes()).trim().to_string();
+    if value.eq_ignore_ascii_case("100-continue") {
+        Expectation::Continue
+    } else {
+        Expectation::Unsupported(value)
+    }
+}
+
+/// Tell a waiting client to send its body
+///
+/// The gateway answers the expectation itself once a request has passed its
+/// own checks, so rejected uploads are answered before any body is sent, and
+/// strips `Expect` from the upstream request.
+pub async fn send_continue(session: &mut ServerSession) -> Result<()> {
+    session
+        .write_response_header(Box::new(ResponseHeader::build(100, Some(0))?))
+        .await
+}
+
+/// `417` for an expectation the gateway can't meet
+pub fn expectation_failed_response() -> Result<(ResponseHeader, Bytes)> {
+    ErrorCode::ExpectationFailed.response("Only Expect: 100-continue is supported")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pingora_core::protocols::l4::stream::Stream;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn request(version: Version, expect: Option<&str>) -> RequestHeader {
+        let mut req = RequestHeader::build("POST", b"/upload", None).unwrap();
+        req.set_version(version);
+        if let Some(expect) = expect {
+            req.insert_header("Expect", expect).unwrap();
+        }
+        req
+    }
+
+    /// Read a response head, up to and including the blank line
+    async fn read_head(client: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(client.read_u8().await.unwrap());
+        }
+        String::from_utf8(head).unwrap()
+    }
+
+    #[test]
+    fn test_expectation() {
+        assert_eq!(
+            expectation(&request(Version::HTTP_11, None)),
+            Expectation::None
+        );
+        assert_eq!(
+            expectation(&request(Version::HTTP_11, Some("100-Continue"))),
+            Expectation::Continue
+        );
+        assert_eq!(
+            expectation(&request(Version::HTTP_11, Some("fast-please"))),
+            Expectation::Unsupported("fast-please".to_string())
+        );
+        assert_eq!(
+            expectation(&request(Version::HTTP_10, Some("100-continue"))),
+            Expectation::None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_waiting_for_continue_completes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The gateway side of the connection, as `request_filter` drives it
+        let gateway = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut session = ServerSession::new_http1(Box::new(Stream::from(socket)));
+            assert!(session.read_request().await.unwrap());
+            assert_eq!(expectation(session.req_header()), Expectation::Continue);
+
+            send_continue(&mut session).await.unwrap();
+            let mut received = 0;
+            while let Some(chunk) = session.read_request_body().await.unwrap() {
+                received += chunk.len();
+            }
+
+            let mut resp = ResponseHeader::build(201, None).unwrap();
+            resp.insert_header("Content-Length", "0").unwrap();
+            session.write_response_header(Box::new(resp)).await.unwrap();
+            session.finish_body().await.unwrap();
+            received
+        });
+
+        let body = vec![b'x'; 256 * 1024];
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let head = format!(
+            "POST /upload HTTP/1.1\r\nHost: gateway\r\nContent-Length: {}\r\nExpect: 100-continue\r\n\r\n",
+            body.len()
+        );
+        client.write_all(head.as_bytes()).await.unwrap();
+
+        // Nothing of the body is sent until the gateway says so
+        assert!(read_head(&mut client).await.starts_with("HTTP/1.1 100"));
+        client.write_all(&body).await.unwrap();
+
+        assert!(read_head(&mut client).await.starts_with("HTTP/1.1 201"));
+        assert_eq!(gateway.await.unwrap(), body.len());
+    }
+}
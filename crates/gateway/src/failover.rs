@@ -0,0 +1,84 @@
+use karateway_core::models::BackendService;
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+/// Pick the backend of a route with fallbacks, active/passive style
+///
+/// The primary gets every request while it's healthy; only when it's down
+/// does traffic move to the first healthy fallback, in the route's order.
+/// Returns the chosen backend and the healthy fallbacks after it, which are
+/// tried in turn if connecting fails. With nothing healthy the primary is
+/// kept, so the request gets the primary's 503.
+pub fn choose(
+    primary: BackendService,
+    fallbacks: Vec<BackendService>,
+    is_healthy: impl Fn(&Uuid) -> bool,
+) -> (BackendService, VecDeque<BackendService>) {
+    let mut healthy: VecDeque<BackendService> = fallbacks
+        .into_iter()
+        .filter(|fallback| fallback.id != primary.id && is_healthy(&fallback.id))
+        .collect();
+
+    if is_healthy(&primary.id) {
+        return (primary, healthy);
+    }
+    match healthy.pop_front() {
+        Some(fallback) => (fallback, healthy),
+        None => (primary, healthy),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_loader::tests::service;
+    use std::collections::HashSet;
+
+    fn backends() -> (BackendService, Vec<BackendService>) {
+        (
+            service("primary", "http://primary:8080"),
+            vec![
+                service("secondary", "http://secondary:8080"),
+                service("tertiary", "http://tertiary:8080"),
+            ],
+        )
+    }
+
+    fn names(fallbacks: &VecDeque<BackendService>) -> Vec<&str> {
+        fallbacks.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_healthy_primary_gets_the_traffic() {
+        let (primary, fallbacks) = backends();
+        let (chosen, rest) = choose(primary, fallbacks, |_| true);
+        assert_eq!(chosen.name, "primary");
+        // Still there should connecting to the primary fail
+        assert_eq!(names(&rest), vec!["secondary", "tertiary"]);
+    }
+
+    #[test]
+    fn test_down_primary_fails_over_in_order() {
+        let (primary, fallbacks) = backends();
+        let down: HashSet<Uuid> = [primary.id].into();
+        let (chosen, rest) = choose(primary, fallbacks.clone(), |id| !down.contains(id));
+        assert_eq!(chosen.name, "secondary");
+        assert_eq!(names(&rest), vec!["tertiary"]);
+
+        // Unhealthy fallbacks are skipped too
+        let (primary, _) = backends();
+        let down: HashSet<Uuid> = [primary.id, fallbacks[0].id].into();
+        let (chosen, rest) = choose(primary, fallbacks, |id| !down.contains(id));
+        assert_eq!(chosen.name, "tertiary");
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_everything_down_keeps_the_primary() {
+        let (primary, fallbacks) = backends();
+        let primary_id = primary.id;
+        let (chosen, rest) = choose(primary, fallbacks, |_| false);
+        assert_eq!(chosen.id, primary_id);
+        assert!(rest.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use http::HeaderMap;
+use karateway_core::models::BackendService;
+
+/// Bytes a header adds on top of its name and value (`: ` and CRLF)
+const HEADER_OVERHEAD: usize = 4;
+
+/// Caps on the response headers a backend may send
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderLimits {
+    /// Total size of all header lines
+    pub max_bytes: usize,
+    /// Number of header lines
+    pub max_count: usize,
+}
+
+impl Default for HeaderLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 64 * 1024,
+            max_count: 100,
+        }
+    }
+}
+
+/// Why a response head was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeaderLimitExceeded {
+    Bytes { size: usize, limit: usize },
+    Count { count: usize, limit: usize },
+}
+
+impl std::fmt::Display for HeaderLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeaderLimitExceeded::Bytes { size, limit } => {
+                write!(f, "response headers are {} bytes, limit is {}", size, limit)
+            }
+            HeaderLimitExceeded::Count { count, limit } => {
+                write!(f, "response has {} headers, limit is {}", count, limit)
+            }
+        }
+    }
+}
+
+impl HeaderLimits {
+    /// The global limits with the backend's own overrides applied
+    pub fn for_service(&self, service: &BackendService) -> Self {
+        let positive = |value: Option<i32>| value.filter(|v| *v > 0).map(|v| v as usize);
+
+        Self {
+            max_bytes: positive(service.max_response_header_bytes).unwrap_or(self.max_bytes),
+            max_count: positive(service.max_response_header_count).unwrap_or(self.max_count),
+        }
+    }
+
+    /// Check a response head against the limits
+    pub fn check(&self, headers: &HeaderMap) -> Result<(), HeaderLimitExceeded> {
+        let count = headers.len();
+        if count > self.max_count {
+            return Err(HeaderLimitExceeded::Count {
+                count,
+                limit: self.max_count,
+            });
+        }
+
+        let size: usize = headers
+            .iter()
+            .map(|(name, value)| name.as_str().len() + value.len() + HEADER_OVERHEAD)
+            .sum();
+        if size > self.max_bytes {
+            return Err(HeaderLimitExceeded::Bytes {
+                size,
+                limit: self.max_bytes,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_loader::tests::service;
+    use http::HeaderValue;
+
+    #[test]
+    fn test_oversized_upstream_headers_are_refused() {
+        let limits = HeaderLimits {
+            max_bytes: 1024,
+            max_count: 10,
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        assert_eq!(limits.check(&headers), Ok(()));
+
+        headers.insert("x-junk", HeaderValue::from_str(&"a".repeat(2048)).unwrap());
+        assert!(matches!(
+            limits.check(&headers),
+            Err(HeaderLimitExceeded::Bytes { limit: 1024, .. })
+        ));
+
+        let mut headers = HeaderMap::new();
+        for i in 0..11 {
+            headers.insert(
+                http::HeaderName::from_bytes(format!("x-h{}", i).as_bytes()).unwrap(),
+                HeaderValue::from_static("1"),
+            );
+        }
+        assert_eq!(
+            limits.check(&headers),
+            Err(HeaderLimitExceeded::Count {
+                count: 11,
+                limit: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_backend_overrides_global_limits() {
+        let global = HeaderLimits::default();
+        let mut backend = service("orders", "http://orders:9000");
+        assert_eq!(global.for_service(&backend), global);
+
+        backend.max_response_header_bytes = Some(256 * 1024);
+        backend.max_response_header_count = Some(0);
+        assert_eq!(
+            global.for_service(&backend),
+            HeaderLimits {
+                max_bytes: 256 * 1024,
+                max_count: 100,
+            }
+        );
+    }
+}
@@ -1,7 +1,16 @@
 use dashmap::DashMap;
+use futures::StreamExt;
+use karateway_config::health_cache::{
+    self, HealthCheckRecord, HealthVerdict, HEALTH_VERDICTS_CHANNEL,
+};
+use karateway_config::health_probe;
 use karateway_core::models::BackendService;
+use redis::aio::ConnectionManager;
+use redis::RedisResult;
+use std::collections::VecDeque;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
 use tokio::time::interval;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -16,6 +25,23 @@ pub enum HealthStatus {
     Unknown,
 }
 
+/// Most health check results kept per service, whatever is configured
+pub const MAX_HISTORY_SIZE: usize = 1000;
+
+/// `Retry-After` seconds for a 503 from an unhealthy service
+///
+/// Its next health check is the earliest it can come back, so clients are
+/// told to wait one check interval; `default_seconds` covers services
+/// without an interval of their own.
+pub fn retry_after_seconds(service: &BackendService, default_seconds: u64) -> u64 {
+    service
+        .health_check_interval_seconds
+        .filter(|seconds| *seconds > 0)
+        .map(|seconds| seconds as u64)
+        .unwrap_or(default_seconds)
+        .max(1)
+}
+
 /// Health checker for backend services
 pub struct HealthChecker {
     /// Map of service_id -> health status
@@ -24,20 +50,51 @@ pub struct HealthChecker {
     config_loader: Arc<ConfigLoader>,
     /// HTTP client for health checks
     client: reqwest::Client,
+    /// Redis client used to invalidate the admin API's health cache
+    redis_client: Option<redis::Client>,
+    /// Connection shared by the probes and the health cache writes, opened on first use
+    redis_conn: OnceCell<ConnectionManager>,
+    /// When each service with a health entry was first seen missing from the config
+    removed_at: DashMap<Uuid, Instant>,
+    /// How long a removed service keeps its health entry
+    removal_grace: Duration,
+    /// Recent check results per service, newest first
+    history: DashMap<Uuid, VecDeque<HealthCheckRecord>>,
+    /// Results kept per service, 0 to keep none
+    history_size: usize,
+    /// How long a probe result is shared with the other probers, zero to always probe
+    probe_coalesce_ttl: Duration,
 }
 
 impl HealthChecker {
     /// Create a new health checker
-    pub fn new(config_loader: Arc<ConfigLoader>) -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(5))
-            .build()
+    ///
+    /// Services removed from the config keep their health entry for
+    /// `removal_grace`, so one that is re-added shortly after keeps its status.
+    /// The last `history_size` results of each service are kept, up to
+    /// [`MAX_HISTORY_SIZE`]. With Redis, probes of the same service within
+    /// `probe_coalesce_ttl` are shared with the other gateways and the admin API.
+    pub fn new(
+        config_loader: Arc<ConfigLoader>,
+        redis_client: Option<redis::Client>,
+        removal_grace: Duration,
+        history_size: usize,
+        probe_coalesce_ttl: Duration,
+    ) -> Self {
+        let client = health_probe::client(health_probe::PROBE_TIMEOUT)
             .expect("Failed to create HTTP client");
 
         Self {
             service_health: Arc::new(DashMap::new()),
             config_loader,
             client,
+            redis_client,
+            redis_conn: OnceCell::new(),
+            removed_at: DashMap::new(),
+            removal_grace,
+            history: DashMap::new(),
+            history_size: history_size.min(MAX_HISTORY_SIZE),
+            probe_coalesce_ttl,
         }
     }
 
@@ -70,6 +127,72 @@ impl HealthChecker {
         });
     }
 
+    /// Start applying the probe results the admin API publishes on a forced refresh
+    ///
+    /// Without Redis the gateway only relies on its own checks. A dropped
+    /// subscription is re-established after a short pause.
+    pub fn start_verdict_listener(self: Arc<Self>) {
+        let Some(redis_client) = self.redis_client.clone() else {
+            return;
+        };
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.listen_for_verdicts(&redis_client).await {
+                    warn!("Health verdict subscription failed: {}", e);
+                }
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+
+    async fn listen_for_verdicts(&self, redis_client: &redis::Client) -> redis::RedisResult<()> {
+        let mut pubsub = redis_client.get_async_pubsub().await?;
+        pubsub.subscribe(HEALTH_VERDICTS_CHANNEL).await?;
+        info!(
+            "Subscribed to health verdicts on {}",
+            HEALTH_VERDICTS_CHANNEL
+        );
+
+        let mut messages = pubsub.on_message();
+        while let Some(message) = messages.next().await {
+            let payload: String = match message.get_payload() {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Ignoring unreadable health verdicts: {}", e);
+                    continue;
+                }
+            };
+            match health_cache::parse_verdicts(&payload) {
+                Ok(verdicts) => self.apply_verdicts(&verdicts),
+                Err(e) => warn!("Ignoring malformed health verdicts: {}", e),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record statuses probed elsewhere, as if this checker had run the probes
+    ///
+    /// The admin API caches the same results it publishes, so its cache is
+    /// left alone here.
+    fn apply_verdicts(&self, verdicts: &[HealthVerdict]) {
+        for verdict in verdicts {
+            let new_status = if verdict.is_healthy {
+                HealthStatus::Healthy
+            } else {
+                HealthStatus::Unhealthy
+            };
+
+            if let Some(old_status) = self.record_status(verdict.service_id, new_status) {
+                info!(
+                    "Service {} status changed by admin refresh: {:?} -> {:?}",
+                    verdict.service_id, old_status, new_status
+                );
+            }
+        }
+    }
+
     /// Check health for all services
     async fn check_all_services(&self) {
         let config = self.config_loader.get_config();
@@ -80,65 +203,209 @@ impl HealthChecker {
                 self.check_service(*service_id, service).await;
             }
         }
+
+        self.purge_removed(|id| config.services.contains_key(id), Instant::now());
+    }
+
+    /// Drop health entries of services that left the config more than `removal_grace` ago
+    fn purge_removed(&self, is_active: impl Fn(&Uuid) -> bool, now: Instant) {
+        // Services back in the config no longer count as removed
+        self.removed_at.retain(|id, _| !is_active(id));
+
+        for entry in self.service_health.iter() {
+            let service_id = *entry.key();
+            if !is_active(&service_id) {
+                self.removed_at.entry(service_id).or_insert(now);
+            }
+        }
+
+        let removal_grace = self.removal_grace;
+        self.removed_at.retain(|service_id, removed_at| {
+            if now.saturating_duration_since(*removed_at) < removal_grace {
+                return true;
+            }
+            debug!("Purging health status of removed service {}", service_id);
+            self.service_health.remove(service_id);
+            self.history.remove(service_id);
+            false
+        });
     }
 
     /// Check health for a single service
     async fn check_service(&self, service_id: Uuid, service: &BackendService) {
-        let health_url = match &service.health_check_url {
+        let full_url = match health_probe::health_check_url(service) {
             Some(url) => url,
             None => return, // Skip if no health check URL
         };
 
-        // Build full health check URL
-        let full_url = if health_url.starts_with("http://") || health_url.starts_with("https://") {
-            health_url.clone()
-        } else {
-            format!("{}{}", service.base_url, health_url)
-        };
-
         debug!(
             "Checking health for service {} ({}): {}",
             service.name, service_id, full_url
         );
 
-        // Perform health check
-        let is_healthy = match self.client.get(&full_url).send().await {
-            Ok(response) => {
-                let status = response.status();
-                if status.is_success() {
-                    debug!("Service {} is healthy (status: {})", service.name, status);
-                    true
-                } else {
-                    warn!(
-                        "Service {} returned non-success status: {}",
-                        service.name, status
-                    );
-                    false
-                }
-            }
-            Err(e) => {
-                error!("Health check failed for service {}: {}", service.name, e);
-                false
-            }
-        };
+        // Perform health check; a shared result carries the latency of whoever probed
+        let result = self.probe(service).await;
+        self.record_history(
+            service_id,
+            HealthCheckRecord {
+                checked_at: chrono::Utc::now(),
+                is_healthy: result.is_healthy,
+                latency_ms: result.latency_ms,
+                error: (!result.is_healthy).then(|| result.status_message.clone()),
+            },
+        );
+        self.store_history(service_id).await;
+        if result.is_healthy {
+            debug!(
+                "Service {} passed health check: {}",
+                service.name, result.status_message
+            );
+        } else {
+            error!(
+                "Health check failed for service {}: {}",
+                service.name, result.status_message
+            );
+        }
 
         // Update health status
-        let new_status = if is_healthy {
+        let new_status = if result.is_healthy {
             HealthStatus::Healthy
         } else {
             HealthStatus::Unhealthy
         };
 
-        // Log status changes
-        let old_status = self.service_health.get(&service_id).map(|s| *s);
-        if old_status != Some(new_status) {
+        if let Some(old_status) = self.update_status(service_id, new_status).await {
             info!(
                 "Service {} ({}) status changed: {:?} -> {:?}",
                 service.name, service_id, old_status, new_status
             );
         }
+    }
+
+    /// Store a service's latest status, dropping the admin API's snapshot when it changed
+    ///
+    /// Returns the previous status when it changed, like [`Self::record_status`].
+    async fn update_status(
+        &self,
+        service_id: Uuid,
+        new_status: HealthStatus,
+    ) -> Option<HealthStatus> {
+        let old_status = self.record_status(service_id, new_status)?;
+        self.invalidate_health_cache().await;
+        Some(old_status)
+    }
+
+    /// Probe a service, sharing the result through Redis when coalescing is on
+    async fn probe(&self, service: &BackendService) -> health_probe::ProbeResult {
+        let shared = match &self.redis_client {
+            Some(redis_client) if !self.probe_coalesce_ttl.is_zero() => {
+                self.redis_connection(redis_client).await.ok()
+            }
+            _ => None,
+        };
+
+        match shared {
+            Some(mut conn) => {
+                health_probe::probe_coalesced(
+                    &mut conn,
+                    &self.client,
+                    service,
+                    self.probe_coalesce_ttl,
+                )
+                .await
+            }
+            None => health_probe::probe(&self.client, service).await,
+        }
+    }
+
+    /// The Redis connection every check shares
+    ///
+    /// A connection manager multiplexes the checks over one connection and
+    /// reconnects after it drops; a failed first connect is retried on the
+    /// next call.
+    async fn redis_connection(
+        &self,
+        redis_client: &redis::Client,
+    ) -> RedisResult<ConnectionManager> {
+        self.redis_conn
+            .get_or_try_init(|| ConnectionManager::new(redis_client.clone()))
+            .await
+            .cloned()
+    }
+
+    /// Store a service's latest status
+    ///
+    /// Returns the previous status (`Unknown` if never checked) when it changed.
+    fn record_status(&self, service_id: Uuid, new_status: HealthStatus) -> Option<HealthStatus> {
+        let old_status = self
+            .service_health
+            .insert(service_id, new_status)
+            .unwrap_or(HealthStatus::Unknown);
+
+        (old_status != new_status).then_some(old_status)
+    }
+
+    /// Add a check result to a service's history, dropping the oldest beyond `history_size`
+    fn record_history(&self, service_id: Uuid, record: HealthCheckRecord) {
+        if self.history_size == 0 {
+            return;
+        }
+        let mut history = self.history.entry(service_id).or_default();
+        history.push_front(record);
+        history.truncate(self.history_size);
+    }
+
+    /// Recent check results of a service, newest first
+    pub fn history(&self, service_id: &Uuid) -> Vec<HealthCheckRecord> {
+        self.history
+            .get(service_id)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Hand a service's history to the admin API, which serves it from Redis
+    async fn store_history(&self, service_id: Uuid) {
+        let Some(redis_client) = &self.redis_client else {
+            return;
+        };
+        if self.history_size == 0 {
+            return;
+        }
+
+        let history = self.history(&service_id);
+        let result = match self.redis_connection(redis_client).await {
+            Ok(mut conn) => {
+                health_cache::store_history(
+                    &mut conn,
+                    service_id,
+                    &history,
+                    self.removal_grace.as_secs(),
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            warn!("Failed to store health history of {}: {}", service_id, e);
+        }
+    }
+
+    /// Drop the admin API's cached health snapshot so it stops reporting stale status
+    async fn invalidate_health_cache(&self) {
+        let Some(redis_client) = &self.redis_client else {
+            return;
+        };
 
-        self.service_health.insert(service_id, new_status);
+        let result = match self.redis_connection(redis_client).await {
+            Ok(mut conn) => karateway_config::health_cache::invalidate(&mut conn).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => debug!("Invalidated health cache after status change"),
+            Err(e) => warn!("Failed to invalidate health cache: {}", e),
+        }
     }
 
     /// Get all service health statuses
@@ -149,3 +416,250 @@ impl HealthChecker {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use karateway_config::config_limits::ConfigLimits;
+
+    fn health_checker() -> HealthChecker {
+        health_checker_with(None)
+    }
+
+    fn health_checker_with(redis_client: Option<redis::Client>) -> HealthChecker {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgresql://localhost/karateway")
+            .unwrap();
+        HealthChecker::new(
+            Arc::new(ConfigLoader::new(
+                pool,
+                ConfigLimits::default(),
+                Default::default(),
+            )),
+            redis_client,
+            Duration::from_secs(60),
+            10,
+            Duration::ZERO,
+        )
+    }
+
+    #[test]
+    fn test_only_status_changes_are_reported() {
+        let checker = health_checker();
+        let service_id = Uuid::new_v4();
+
+        // First observation is a change from Unknown
+        assert_eq!(
+            checker.record_status(service_id, HealthStatus::Healthy),
+            Some(HealthStatus::Unknown)
+        );
+        assert_eq!(
+            checker.record_status(service_id, HealthStatus::Healthy),
+            None
+        );
+        assert_eq!(
+            checker.record_status(service_id, HealthStatus::Unhealthy),
+            Some(HealthStatus::Healthy)
+        );
+        assert!(!checker.is_healthy(&service_id));
+    }
+
+    /// Whether the admin API's health snapshot is in Redis
+    async fn cached(conn: &mut redis::aio::MultiplexedConnection) -> bool {
+        redis::cmd("EXISTS")
+            .arg(health_cache::HEALTH_CACHE_KEY)
+            .query_async(conn)
+            .await
+            .unwrap()
+    }
+
+    async fn cache_snapshot(conn: &mut redis::aio::MultiplexedConnection) {
+        redis::cmd("SET")
+            .arg(health_cache::HEALTH_CACHE_KEY)
+            .arg("{}")
+            .query_async::<()>(conn)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Redis: set TEST_REDIS_URL and run cargo test -- --ignored"]
+    async fn test_status_change_invalidates_cache() {
+        let url = std::env::var("TEST_REDIS_URL")
+            .expect("needs Redis: set TEST_REDIS_URL and run cargo test -- --ignored");
+        let redis_client = redis::Client::open(url).unwrap();
+        let mut conn = redis_client
+            .get_multiplexed_async_connection()
+            .await
+            .unwrap();
+        let checker = health_checker_with(Some(redis_client));
+        let service_id = Uuid::new_v4();
+
+        // First observation is a change from Unknown
+        cache_snapshot(&mut conn).await;
+        assert_eq!(
+            checker
+                .update_status(service_id, HealthStatus::Healthy)
+                .await,
+            Some(HealthStatus::Unknown)
+        );
+        assert!(!cached(&mut conn).await);
+
+        // Same status again leaves the cache alone
+        cache_snapshot(&mut conn).await;
+        assert_eq!(
+            checker
+                .update_status(service_id, HealthStatus::Healthy)
+                .await,
+            None
+        );
+        assert!(cached(&mut conn).await);
+
+        // Going down is a change and must invalidate
+        assert_eq!(
+            checker
+                .update_status(service_id, HealthStatus::Unhealthy)
+                .await,
+            Some(HealthStatus::Healthy)
+        );
+        assert!(!checker.is_healthy(&service_id));
+        assert!(!cached(&mut conn).await);
+    }
+
+    #[test]
+    fn test_retry_after_follows_the_health_check_interval() {
+        let mut service = crate::config_loader::tests::service("orders", "http://orders:9000");
+        assert_eq!(retry_after_seconds(&service, 10), 10);
+
+        service.health_check_interval_seconds = Some(30);
+        assert_eq!(retry_after_seconds(&service, 10), 30);
+
+        // Never tell clients to retry immediately
+        service.health_check_interval_seconds = Some(0);
+        assert_eq!(retry_after_seconds(&service, 0), 1);
+    }
+
+    #[tokio::test]
+    async fn test_admin_refresh_verdicts_update_routing_status() {
+        let checker = health_checker();
+        let recovered = Uuid::new_v4();
+        let failed = Uuid::new_v4();
+        checker.record_status(recovered, HealthStatus::Unhealthy);
+
+        let payload = serde_json::to_string(&[
+            HealthVerdict {
+                service_id: recovered,
+                is_healthy: true,
+            },
+            HealthVerdict {
+                service_id: failed,
+                is_healthy: false,
+            },
+        ])
+        .unwrap();
+        checker.apply_verdicts(&health_cache::parse_verdicts(&payload).unwrap());
+
+        assert!(checker.is_healthy(&recovered));
+        assert!(!checker.is_healthy(&failed));
+    }
+
+    #[tokio::test]
+    async fn test_removed_service_is_purged_after_grace_period() {
+        let checker = health_checker();
+        let kept = Uuid::new_v4();
+        let removed = Uuid::new_v4();
+        checker.record_status(kept, HealthStatus::Healthy);
+        checker.record_status(removed, HealthStatus::Unhealthy);
+
+        let now = Instant::now();
+        checker.purge_removed(|id| *id == kept, now);
+
+        // Still inside the grace period
+        checker.purge_removed(|id| *id == kept, now + Duration::from_secs(30));
+        assert_eq!(checker.get_status(&removed), HealthStatus::Unhealthy);
+
+        checker.purge_removed(|id| *id == kept, now + Duration::from_secs(61));
+        assert_eq!(checker.get_status(&removed), HealthStatus::Unknown);
+        assert_eq!(checker.get_status(&kept), HealthStatus::Healthy);
+        assert!(checker.removed_at.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_readded_service_keeps_health_status() {
+        let checker = health_checker();
+        let service_id = Uuid::new_v4();
+        checker.record_status(service_id, HealthStatus::Unhealthy);
+
+        let now = Instant::now();
+        checker.purge_removed(|_| false, now);
+        // Re-added before the grace period ran out
+        checker.purge_removed(|_| true, now + Duration::from_secs(30));
+        checker.purge_removed(|_| true, now + Duration::from_secs(120));
+
+        assert_eq!(checker.get_status(&service_id), HealthStatus::Unhealthy);
+        assert!(checker.removed_at.is_empty());
+    }
+
+    /// A backend answering every request with `status`
+    async fn backend(status: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_probe_results_accumulate_in_history() {
+        let checker = health_checker();
+        let mut service = crate::config_loader::tests::service("orders", &backend("200 OK").await);
+        service.health_check_url = Some("/health".to_string());
+
+        checker.check_service(service.id, &service).await;
+        service.base_url = backend("503 Service Unavailable").await;
+        checker.check_service(service.id, &service).await;
+
+        let history = checker.history(&service.id);
+        assert_eq!(history.len(), 2);
+        // Newest first
+        assert!(!history[0].is_healthy);
+        assert!(history[0].error.as_deref().unwrap().contains("503"));
+        assert!(history[1].is_healthy);
+        assert_eq!(history[1].error, None);
+        assert!(history[0].checked_at >= history[1].checked_at);
+    }
+
+    #[tokio::test]
+    async fn test_history_is_bounded() {
+        let checker = health_checker();
+        let service_id = Uuid::new_v4();
+
+        for latency_ms in 0..25 {
+            checker.record_history(
+                service_id,
+                HealthCheckRecord {
+                    checked_at: chrono::Utc::now(),
+                    is_healthy: true,
+                    latency_ms,
+                    error: None,
+                },
+            );
+        }
+
+        let history = checker.history(&service_id);
+        assert_eq!(history.len(), 10);
+        assert_eq!(history[0].latency_ms, 24);
+        assert_eq!(history[9].latency_ms, 15);
+    }
+}
@@ -0,0 +1,138 @@
+use http::header::{self, HeaderMap, HeaderName};
+
+/// Headers that only concern one connection (RFC 7230 section 6.1)
+///
+/// `Transfer-Encoding` is handled by Pingora, which re-frames the body for
+/// each hop, so it is never removed here. `Trailer` is end-to-end: it tells
+/// the far side which trailer fields follow the body.
+pub const HOP_BY_HOP_HEADERS: [HeaderName; 6] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::UPGRADE,
+];
+
+/// Headers a `Connection` option must not remove, since the message framing depends on them
+const FRAMING_HEADERS: [HeaderName; 3] = [
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::HOST,
+];
+
+/// Whether the headers ask to switch protocols, e.g. a WebSocket handshake
+pub fn is_upgrade(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::UPGRADE) && connection_options(headers).any(|o| o == "upgrade")
+}
+
+/// Hop-by-hop headers present in `headers` that must not be forwarded
+///
+/// Includes the fixed set plus anything named in `Connection`. With
+/// `keep_upgrade`, `Connection` and `Upgrade` survive so a WebSocket
+/// handshake (or its `101` response) still reaches the other side.
+pub fn hop_by_hop_headers(headers: &HeaderMap, keep_upgrade: bool) -> Vec<HeaderName> {
+    let listed =
+        connection_options(headers).filter_map(|o| HeaderName::from_bytes(o.as_bytes()).ok());
+
+    let mut names: Vec<HeaderName> = Vec::new();
+    for name in HOP_BY_HOP_HEADERS.into_iter().chain(listed) {
+        let upgrade_header = name == header::CONNECTION || name == header::UPGRADE;
+        if (keep_upgrade && upgrade_header)
+            || FRAMING_HEADERS.contains(&name)
+            || !headers.contains_key(&name)
+            || names.contains(&name)
+        {
+            continue;
+        }
+        names.push(name);
+    }
+
+    names
+}
+
+/// Lowercased tokens of every `Connection` header
+fn connection_options(headers: &HeaderMap) -> impl Iterator<Item = String> + '_ {
+    headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|option| option.trim().to_ascii_lowercase())
+        .filter(|option| !option.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        headers
+    }
+
+    fn stripped(headers: &HeaderMap, keep_upgrade: bool) -> Vec<String> {
+        let mut names: Vec<String> = hop_by_hop_headers(headers, keep_upgrade)
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_strips_hop_by_hop_and_connection_listed_headers() {
+        let headers = headers(&[
+            ("Connection", "keep-alive, X-Debug-Token"),
+            ("Keep-Alive", "timeout=5"),
+            ("Proxy-Authorization", "Basic Zm9vOmJhcg=="),
+            ("TE", "trailers"),
+            ("Trailer", "grpc-status"),
+            ("X-Debug-Token", "abc"),
+            ("Content-Type", "application/json"),
+            ("Transfer-Encoding", "chunked"),
+        ]);
+
+        assert_eq!(
+            stripped(&headers, false),
+            [
+                "connection",
+                "keep-alive",
+                "proxy-authorization",
+                "te",
+                "x-debug-token"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_websocket_upgrade_is_preserved() {
+        let headers = headers(&[
+            ("Connection", "Upgrade"),
+            ("Upgrade", "websocket"),
+            ("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="),
+        ]);
+
+        assert!(is_upgrade(&headers));
+        assert!(stripped(&headers, true).is_empty());
+        assert_eq!(stripped(&headers, false), ["connection", "upgrade"]);
+    }
+
+    #[test]
+    fn test_connection_cannot_remove_framing_headers() {
+        let headers = headers(&[
+            ("Connection", "close, Content-Length, Host"),
+            ("Content-Length", "42"),
+            ("Host", "example.com"),
+        ]);
+
+        assert_eq!(stripped(&headers, false), ["connection"]);
+        assert!(!is_upgrade(&headers));
+    }
+}
@@ -0,0 +1,174 @@
+use karateway_config::circuit_breaker::BreakerState;
+use pingora_core::{Error, ErrorSource};
+use std::time::{Duration, Instant};
+
+/// Consecutive failed requests after which an instance is taken out of rotation
+pub const FAILURE_THRESHOLD: u32 = 3;
+
+/// Window over which the recent error rate is measured
+pub const ERROR_RATE_WINDOW: Duration = Duration::from_secs(30);
+
+/// Fewest requests in a window before its error rate counts
+pub const MIN_REQUESTS: u32 = 10;
+
+/// Error rate, in percent, at which an instance is taken out of rotation
+pub const MAX_ERROR_PERCENT: u32 = 50;
+
+/// How long an unhealthy instance is skipped before it gets traffic again
+pub const EJECTION: Duration = Duration::from_secs(30);
+
+/// Whether a finished request counts against the instance that served it
+///
+/// Upstream errors (refused connections, timeouts, resets) and 5xx answers
+/// do; client-side errors such as a dropped downstream connection don't.
+pub fn is_failure(error: Option<&Error>, status: u16) -> bool {
+    match error {
+        Some(error) => *error.esource() == ErrorSource::Upstream || status >= 500,
+        None => status >= 500,
+    }
+}
+
+/// Passive health of one discovered instance, fed by the requests it serves
+///
+/// An instance is ejected after [`FAILURE_THRESHOLD`] failures in a row, or
+/// when at least [`MAX_ERROR_PERCENT`] of the requests in the current
+/// [`ERROR_RATE_WINDOW`] failed. It's skipped for [`EJECTION`], then let back
+/// in with a clean slate; if it's still failing it's ejected again on the
+/// next few requests.
+#[derive(Debug)]
+pub struct InstanceHealth {
+    consecutive_failures: u32,
+    window_started: Instant,
+    requests: u32,
+    failures: u32,
+    ejected_until: Option<Instant>,
+}
+
+impl InstanceHealth {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            consecutive_failures: 0,
+            window_started: now,
+            requests: 0,
+            failures: 0,
+            ejected_until: None,
+        }
+    }
+
+    /// Whether the instance should receive traffic
+    pub fn is_available(&self, now: Instant) -> bool {
+        !self.ejected_until.is_some_and(|until| now < until)
+    }
+
+    /// The instance's breaker: open while ejected, half-open once the ejection
+    /// has passed but no request has let it back in yet
+    pub fn state(&self, now: Instant) -> BreakerState {
+        match self.ejected_until {
+            Some(until) if now < until => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+            None => BreakerState::Closed,
+        }
+    }
+
+    /// How long an ejected instance is still skipped
+    pub fn retry_in(&self, now: Instant) -> Option<Duration> {
+        self.ejected_until
+            .filter(|until| now < *until)
+            .map(|until| until - now)
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Requests and failures in the current error rate window
+    pub fn window(&self) -> (u32, u32) {
+        (self.requests, self.failures)
+    }
+
+    /// Record the outcome of a request, returning `true` when it got the instance ejected
+    pub fn record(&mut self, failed: bool, now: Instant) -> bool {
+        if let Some(until) = self.ejected_until {
+            if now < until {
+                // Requests picked before the ejection are still finishing
+                return false;
+            }
+            *self = Self::new(now);
+        }
+
+        if now.saturating_duration_since(self.window_started) >= ERROR_RATE_WINDOW {
+            self.window_started = now;
+            self.requests = 0;
+            self.failures = 0;
+        }
+
+        self.requests += 1;
+        if failed {
+            self.failures += 1;
+            self.consecutive_failures += 1;
+        } else {
+            self.consecutive_failures = 0;
+        }
+
+        let error_rate_exceeded = self.requests >= MIN_REQUESTS
+            && self.failures * 100 >= self.requests * MAX_ERROR_PERCENT;
+        if self.consecutive_failures >= FAILURE_THRESHOLD || error_rate_exceeded {
+            self.ejected_until = Some(now + EJECTION);
+            return true;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pingora_core::ErrorType;
+
+    #[test]
+    fn test_consecutive_failures_eject_until_recovery() {
+        let now = Instant::now();
+        let mut health = InstanceHealth::new(now);
+
+        assert!(!health.record(true, now));
+        assert!(!health.record(true, now));
+        assert!(health.record(true, now));
+        assert!(!health.is_available(now + EJECTION / 2));
+        assert_eq!(health.state(now + EJECTION / 2), BreakerState::Open);
+        assert_eq!(health.retry_in(now + EJECTION / 2), Some(EJECTION / 2));
+        assert_eq!(health.state(now + EJECTION), BreakerState::HalfOpen);
+
+        // Back in rotation once the ejection has passed, starting afresh
+        let later = now + EJECTION;
+        assert!(health.is_available(later));
+        assert!(!health.record(true, later));
+        assert!(!health.record(false, later));
+        assert!(health.is_available(later));
+    }
+
+    #[test]
+    fn test_elevated_error_rate_ejects() {
+        let now = Instant::now();
+        let mut health = InstanceHealth::new(now);
+
+        // Alternating failures never hit the consecutive threshold
+        let ejected: Vec<bool> = (0..MIN_REQUESTS)
+            .map(|i| health.record(i % 2 == 0, now))
+            .collect();
+        assert_eq!(ejected.iter().filter(|e| **e).count(), 1);
+        assert!(*ejected.last().unwrap());
+        assert!(!health.is_available(now));
+    }
+
+    #[test]
+    fn test_only_upstream_failures_count() {
+        let upstream = Error::new(ErrorType::ConnectRefused).into_up();
+        let downstream = Error::new(ErrorType::ConnectionClosed).into_down();
+
+        assert!(is_failure(Some(&*upstream), 0));
+        assert!(!is_failure(Some(&*downstream), 0));
+        assert!(is_failure(None, 503));
+        assert!(!is_failure(None, 404));
+    }
+}
@@ -0,0 +1,581 @@
+use async_trait::async_trait;
+use dashmap::DashMap;
+use karateway_config::AppConfig;
+use karateway_metrics::Connections;
+use pingora_core::apps::ServerApp;
+use pingora_core::listeners::tls::Acceptor;
+use pingora_core::protocols::{Stream, UniqueIDType, ALPN};
+use pingora_core::server::ShutdownWatch;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+use tracing::{debug, warn};
+
+use crate::client_limits::{ClientCounter, ClientLimits};
+use crate::proxy_protocol;
+use crate::tls::AlpnProtocols;
+
+/// How long a load balancer has to send the PROXY protocol header when no header timeout is set
+const PROXY_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a client has to complete the TLS handshake when no header timeout is set
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The HTTPS listener, whose connections the guard runs the TLS handshake on
+pub struct TlsListener {
+    pub port: u16,
+    /// Built from [`crate::tls::TlsPolicy::server_config`]
+    pub acceptor: Acceptor,
+}
+
+tokio::task_local! {
+    /// Signalled by the proxy once the request head of the current exchange has been read
+    static HEADER_READ: Arc<Notify>;
+
+    /// Client address from the connection's PROXY protocol header
+    static PROXIED_CLIENT: Option<SocketAddr>;
+}
+
+/// Tell the listener guard that this connection's request head arrived in time
+///
+/// Called from the proxy's first request hook. A no-op outside a guarded
+/// HTTP/1 exchange, e.g. on HTTP/2 streams, which run on their own tasks.
+pub fn header_read() {
+    let _ = HEADER_READ.try_with(|notify| notify.notify_one());
+}
+
+/// The client a load balancer reported for the current connection, if it uses the PROXY protocol
+///
+/// Like [`header_read`], only available to HTTP/1 exchanges.
+pub fn proxied_client() -> Option<SocketAddr> {
+    PROXIED_CLIENT.try_with(|client| *client).ok().flatten()
+}
+
+/// Limits applied to every downstream connection of the proxy listeners
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListenerLimits {
+    /// Most concurrent connections, `None` for no cap
+    pub max_connections: Option<usize>,
+    /// How long a client has to send a complete request head, `None` for no limit
+    pub header_read_timeout: Option<Duration>,
+}
+
+impl ListenerLimits {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            max_connections: Some(config.gateway_max_connections).filter(|max| *max > 0),
+            header_read_timeout: Some(config.gateway_header_read_timeout_ms)
+                .filter(|ms| *ms > 0)
+                .map(Duration::from_millis),
+        }
+    }
+}
+
+/// Connection counts exported on `/metrics`
+#[derive(Debug)]
+pub struct ConnectionStats {
+    slots: Option<Arc<Semaphore>>,
+    max_connections: Option<usize>,
+    active: AtomicU64,
+    rejected: AtomicU64,
+    header_timeouts: AtomicU64,
+    /// TLS connections closed for negotiating a protocol the listener doesn't serve
+    alpn_rejected: AtomicU64,
+    /// Connections per client IP, checked here
+    client_connections: Arc<ClientCounter>,
+    /// Requests in flight per client IP, checked by the proxy
+    client_requests: Arc<ClientCounter>,
+}
+
+impl ConnectionStats {
+    pub fn new(limits: ListenerLimits, clients: ClientLimits) -> Self {
+        Self {
+            slots: limits
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max))),
+            max_connections: limits.max_connections,
+            active: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            header_timeouts: AtomicU64::new(0),
+            alpn_rejected: AtomicU64::new(0),
+            client_connections: Arc::new(ClientCounter::new(
+                clients.max_connections,
+                clients.exempt.clone(),
+            )),
+            client_requests: Arc::new(ClientCounter::new(clients.max_requests, clients.exempt)),
+        }
+    }
+
+    /// The per-IP cap on requests in flight, for the proxy to enforce
+    pub fn client_requests(&self) -> Arc<ClientCounter> {
+        self.client_requests.clone()
+    }
+
+    /// Admit a connection, `None` when the cap is reached
+    fn admit(self: &Arc<Self>) -> Option<Admitted> {
+        let permit = match &self.slots {
+            Some(slots) => match slots.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    self.rejected.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+            },
+            None => None,
+        };
+
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Some(Admitted {
+            stats: self.clone(),
+            _permit: permit,
+        })
+    }
+
+    pub fn snapshot(&self) -> Connections {
+        Connections {
+            active: self.active.load(Ordering::Relaxed),
+            max_connections: self.max_connections.map(|max| max as u64),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            header_read_timeouts: self.header_timeouts.load(Ordering::Relaxed),
+            alpn_rejected: self.alpn_rejected.load(Ordering::Relaxed),
+            client_connections_rejected: self.client_connections.rejected(),
+            client_requests_rejected: self.client_requests.rejected(),
+        }
+    }
+}
+
+/// An admitted connection, counted as active until dropped
+struct Admitted {
+    stats: Arc<ConnectionStats>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for Admitted {
+    fn drop(&mut self) {
+        self.stats.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Forgets a connection's PROXY protocol client unless the connection is kept for another exchange
+///
+/// Connection ids are file descriptors, so an entry left behind would hand
+/// its client to the next connection on the same fd.
+struct ProxiedEntry<'a> {
+    proxied: &'a DashMap<UniqueIDType, Option<SocketAddr>>,
+    connection: UniqueIDType,
+    reused: bool,
+}
+
+impl Drop for ProxiedEntry<'_> {
+    fn drop(&mut self) {
+        if !self.reused {
+            self.proxied.remove(&self.connection);
+        }
+    }
+}
+
+/// Run `exchange` unless the request head takes longer than `timeout` to arrive
+///
+/// Returns `None` when the deadline passed first, dropping the exchange and
+/// with it the connection.
+async fn with_header_deadline<T>(
+    timeout: Duration,
+    exchange: impl Future<Output = Option<T>>,
+) -> Option<Option<T>> {
+    let notify = Arc::new(Notify::new());
+    let deadline = {
+        let notify = notify.clone();
+        async move {
+            if tokio::time::timeout(timeout, notify.notified())
+                .await
+                .is_ok()
+            {
+                // The head arrived in time; the rest of the exchange is unbounded here
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    HEADER_READ
+        .scope(notify, async {
+            tokio::select! {
+                result = exchange => Some(result),
+                _ = deadline => None,
+            }
+        })
+        .await
+}
+
+/// Wraps the proxy's listener app with a connection cap and a request head deadline
+///
+/// Over the cap, new connections are closed as soon as they are accepted. On
+/// HTTP/1, every exchange on a connection, including the idle wait between
+/// keep-alive requests, must deliver its request head within the timeout or
+/// the connection is closed, so slowloris clients can't hold sockets open by
+/// trickling headers. HTTP/2 connections only count against the cap.
+///
+/// Connections to the `tls` listener's port go through the TLS handshake
+/// here, with the listener's own policy. Ones that negotiated a protocol
+/// other than the configured ALPN protocols (only possible with HTTP/2 only)
+/// are closed.
+///
+/// Connections of a client IP over its own cap are closed the same way; the
+/// IP is the one from the PROXY protocol header when there is one.
+///
+/// With `proxy_protocol`, plain TCP connections must start with a PROXY
+/// protocol v1 or v2 header, read once per connection; ones that don't are
+/// closed. TLS connections are left alone.
+pub struct ListenerGuard<A> {
+    inner: Arc<A>,
+    limits: ListenerLimits,
+    stats: Arc<ConnectionStats>,
+    proxy_protocol: bool,
+    tls: Option<TlsListener>,
+    /// Protocols TLS connections may have negotiated
+    alpn: AlpnProtocols,
+    /// Clients of open PROXY protocol connections, by connection
+    proxied: DashMap<UniqueIDType, Option<SocketAddr>>,
+}
+
+impl<A> ListenerGuard<A> {
+    pub fn new(
+        inner: A,
+        limits: ListenerLimits,
+        stats: Arc<ConnectionStats>,
+        proxy_protocol: bool,
+        tls: Option<TlsListener>,
+        alpn: AlpnProtocols,
+    ) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            limits,
+            stats,
+            proxy_protocol,
+            tls,
+            alpn,
+            proxied: DashMap::new(),
+        }
+    }
+
+    /// Run the TLS handshake on a new connection to the HTTPS listener
+    ///
+    /// Returns the stream as it was for other listeners, and for connections
+    /// already past their handshake, `None` when the handshake fails.
+    async fn tls_handshake(&self, session: Stream) -> Option<Stream> {
+        let local_port = session.get_socket_digest().and_then(|digest| {
+            digest
+                .local_addr()
+                .and_then(|addr| addr.as_inet())
+                .map(|addr| addr.port())
+        });
+        let tls = match &self.tls {
+            Some(tls) if session.get_ssl_digest().is_none() && local_port == Some(tls.port) => tls,
+            _ => return Some(session),
+        };
+
+        let timeout = self.limits.header_read_timeout.unwrap_or(HANDSHAKE_TIMEOUT);
+        match tokio::time::timeout(timeout, tls.acceptor.tls_handshake(session)).await {
+            Ok(Ok(stream)) => Some(Box::new(stream)),
+            Ok(Err(e)) => {
+                debug!("TLS handshake failed, closing connection: {}", e);
+                None
+            }
+            Err(_) => {
+                debug!(
+                    "No TLS handshake within {}ms, closing connection",
+                    timeout.as_millis()
+                );
+                None
+            }
+        }
+    }
+
+    /// The client of a PROXY protocol connection, reading its header on the first exchange
+    async fn proxied_client(&self, session: &mut Stream) -> Result<Option<SocketAddr>, String> {
+        if let Some(client) = self.proxied.get(&session.id()) {
+            return Ok(*client);
+        }
+
+        let timeout = self
+            .limits
+            .header_read_timeout
+            .unwrap_or(PROXY_HEADER_TIMEOUT);
+        let client = tokio::time::timeout(timeout, proxy_protocol::read_header(session))
+            .await
+            .map_err(|_| "no PROXY protocol header in time".to_string())??;
+        self.proxied.insert(session.id(), client);
+        Ok(client)
+    }
+}
+
+#[async_trait]
+impl<A> ServerApp for ListenerGuard<A>
+where
+    A: ServerApp + Send + Sync + 'static,
+{
+    async fn process_new(
+        self: &Arc<Self>,
+        session: Stream,
+        shutdown: &ShutdownWatch,
+    ) -> Option<Stream> {
+        let mut entry = ProxiedEntry {
+            proxied: &self.proxied,
+            connection: session.id(),
+            reused: false,
+        };
+        let Some(_admitted) = self.stats.admit() else {
+            warn!(
+                "Connection limit of {} reached, closing new connection",
+                self.limits.max_connections.unwrap_or_default()
+            );
+            return None;
+        };
+
+        let mut session = self.tls_handshake(session).await?;
+        if session.get_ssl_digest().is_some()
+            && !self.alpn.accepts(session.selected_alpn_proto().as_ref())
+        {
+            warn!(
+                "Client negotiated {:?}, not one of {}, closing connection",
+                session.selected_alpn_proto(),
+                self.alpn
+            );
+            self.stats.alpn_rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        let proxy_protocol = self.proxy_protocol && session.get_ssl_digest().is_none();
+        let client = if proxy_protocol {
+            match self.proxied_client(&mut session).await {
+                Ok(client) => client,
+                Err(e) => {
+                    warn!("Closing connection: {}", e);
+                    return None;
+                }
+            }
+        } else {
+            None
+        };
+
+        let peer_ip = client.map(|client| client.ip()).or_else(|| {
+            session.get_socket_digest().and_then(|digest| {
+                digest
+                    .peer_addr()
+                    .and_then(|addr| addr.as_inet())
+                    .map(|inet| inet.ip())
+            })
+        });
+        let Some(_client_slot) = self.stats.client_connections.admit(peer_ip) else {
+            warn!(
+                "Client {:?} reached the limit of {} connections, closing new connection",
+                peer_ip,
+                self.stats.client_connections.max().unwrap_or_default()
+            );
+            return None;
+        };
+
+        let h2 = matches!(session.selected_alpn_proto(), Some(ALPN::H2));
+        let exchange = PROXIED_CLIENT.scope(client, self.inner.process_new(session, shutdown));
+        let reused = match self.limits.header_read_timeout.filter(|_| !h2) {
+            Some(timeout) => match with_header_deadline(timeout, exchange).await {
+                Some(reused) => reused,
+                None => {
+                    debug!(
+                        "Client sent no complete request head within {}ms, closing connection",
+                        timeout.as_millis()
+                    );
+                    self.stats.header_timeouts.fetch_add(1, Ordering::Relaxed);
+                    None
+                }
+            },
+            None => exchange.await,
+        };
+
+        entry.reused = reused.is_some();
+        reused
+    }
+
+    async fn cleanup(&self) {
+        self.inner.cleanup().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connections_over_the_cap_are_rejected() {
+        let stats = Arc::new(ConnectionStats::new(
+            ListenerLimits {
+                max_connections: Some(2),
+                header_read_timeout: None,
+            },
+            ClientLimits::default(),
+        ));
+
+        let first = stats.admit().unwrap();
+        let _second = stats.admit().unwrap();
+        assert!(stats.admit().is_none());
+        assert_eq!(stats.snapshot().active, 2);
+        assert_eq!(stats.snapshot().rejected, 1);
+
+        // A closed connection frees its slot
+        drop(first);
+        assert!(stats.admit().is_some());
+        assert_eq!(stats.snapshot().active, 1);
+    }
+
+    /// Hands every connection back for another exchange
+    struct KeepAlive;
+
+    #[async_trait]
+    impl ServerApp for KeepAlive {
+        async fn process_new(
+            self: &Arc<Self>,
+            session: Stream,
+            _shutdown: &ShutdownWatch,
+        ) -> Option<Stream> {
+            Some(session)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_exchange_forgets_the_proxied_client() {
+        use tokio::io::AsyncWriteExt;
+
+        let stats = Arc::new(ConnectionStats::new(
+            ListenerLimits {
+                max_connections: Some(1),
+                header_read_timeout: None,
+            },
+            ClientLimits::default(),
+        ));
+        let guard = Arc::new(ListenerGuard::new(
+            KeepAlive,
+            ListenerLimits {
+                max_connections: Some(1),
+                header_read_timeout: None,
+            },
+            stats.clone(),
+            true,
+            None,
+            AlpnProtocols::default(),
+        ));
+        let (_shutdown_tx, shutdown) = tokio::sync::watch::channel(false);
+
+        let (mut lb, server) = tokio::io::duplex(1024);
+        lb.write_all(b"PROXY TCP4 203.0.113.9 10.0.0.5 51234 8080\r\n")
+            .await
+            .unwrap();
+        let session: Stream = Box::new(server);
+        let connection = session.id();
+        let session = guard.process_new(session, &shutdown).await.unwrap();
+        assert_eq!(
+            *guard.proxied.get(&connection).unwrap(),
+            Some("203.0.113.9:51234".parse().unwrap())
+        );
+
+        // The next exchange on the connection finds the listener full
+        let _busy = stats.admit().unwrap();
+        assert!(guard.process_new(session, &shutdown).await.is_none());
+        assert!(guard.proxied.get(&connection).is_none());
+    }
+
+    /// Holds every exchange open until released, like a slow response
+    #[derive(Clone, Default)]
+    struct Parked {
+        entered: Arc<tokio::sync::Notify>,
+        release: Arc<tokio::sync::Notify>,
+    }
+
+    #[async_trait]
+    impl ServerApp for Parked {
+        async fn process_new(
+            self: &Arc<Self>,
+            session: Stream,
+            _shutdown: &ShutdownWatch,
+        ) -> Option<Stream> {
+            self.entered.notify_one();
+            self.release.notified().await;
+            Some(session)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_over_its_connection_cap_is_closed() {
+        use tokio::io::AsyncWriteExt;
+
+        let limits = ListenerLimits {
+            max_connections: None,
+            header_read_timeout: None,
+        };
+        let stats = Arc::new(ConnectionStats::new(
+            limits,
+            ClientLimits {
+                max_connections: Some(1),
+                ..Default::default()
+            },
+        ));
+        let app = Parked::default();
+        let guard = Arc::new(ListenerGuard::new(
+            app.clone(),
+            limits,
+            stats.clone(),
+            true,
+            None,
+            AlpnProtocols::default(),
+        ));
+        let (_shutdown_tx, shutdown) = tokio::sync::watch::channel(false);
+
+        // Both connections come from the same balancer, on behalf of the same client
+        let mut balancer = Vec::new();
+        let mut connect = || {
+            let (lb, server) = tokio::io::duplex(1024);
+            balancer.push(lb);
+            Box::new(server) as Stream
+        };
+        let (first, second) = (connect(), connect());
+        for (lb, port) in balancer.iter_mut().zip([51234, 51235]) {
+            lb.write_all(format!("PROXY TCP4 203.0.113.9 10.0.0.5 {port} 8080\r\n").as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let busy = tokio::spawn({
+            let (guard, shutdown) = (guard.clone(), shutdown.clone());
+            async move { guard.process_new(first, &shutdown).await.is_some() }
+        });
+        app.entered.notified().await;
+
+        assert!(guard.process_new(second, &shutdown).await.is_none());
+        assert_eq!(stats.client_connections.rejected(), 1);
+
+        // The first connection is served as usual
+        app.release.notify_one();
+        assert!(busy.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_head_is_cut_off() {
+        let timeout = Duration::from_millis(50);
+
+        // Never signals that the head was read, like a client trickling headers
+        let slow = with_header_deadline(timeout, async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Some(())
+        });
+        assert_eq!(slow.await, None);
+
+        // Once the head is in, a slow response is left alone
+        let served = with_header_deadline(timeout, async {
+            header_read();
+            tokio::time::sleep(timeout * 3).await;
+            Some("reused")
+        });
+        assert_eq!(served.await, Some(Some("reused")));
+    }
+}
@@ -0,0 +1,412 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use dashmap::DashMap;
+use futures::future::join_all;
+use karateway_config::health_probe;
+use karateway_core::models::BackendService;
+use pingora_core::connectors::http::Connector;
+use pingora_core::upstreams::peer::HttpPeer;
+use pingora_http::RequestHeader;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::{interval, timeout};
+use tracing::{debug, info, warn};
+use url::Url;
+use uuid::Uuid;
+
+use crate::config_loader::{ConfigLoader, GatewayConfig};
+use crate::discovery::ServiceDiscovery;
+use crate::upstream::UpstreamTarget;
+use crate::upstream_resolver::UpstreamResolver;
+use crate::upstream_tls::ClientCert;
+
+/// Shortest allowed ping interval, whatever the backend asks for
+pub const MIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the pinger looks for backends that are due
+const TICK: Duration = Duration::from_secs(1);
+
+/// Timeout for a single liveness ping
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where one liveness ping goes: a backend's `base_url` or one of its discovered instances
+#[derive(Debug, Clone)]
+pub struct PingTarget {
+    pub service_id: Uuid,
+    pub upstream: UpstreamTarget,
+    /// SNI for TLS backends, the host unless the service sets `tls_sni`
+    pub sni: String,
+    pub client_cert: Option<ClientCert>,
+    /// Path and query of the `HEAD` request
+    pub path: String,
+    /// Whether the target is a DNS SRV discovered instance, whose health the ping counts towards
+    pub discovered: bool,
+}
+
+/// Sends one liveness request, returning whether the backend answered
+#[async_trait]
+pub trait Pinger: Send + Sync {
+    async fn ping(&self, target: &PingTarget) -> bool;
+}
+
+/// Pings with `HEAD` over a fresh connection
+///
+/// The peer is set up like the proxy's (same TLS options and client
+/// certificate), but the connector is the pinger's own: Pingora doesn't share
+/// the proxy's pool, so each ping opens a new connection and closes it once
+/// the backend answers. That checks the backend end to end instead of a
+/// connection the pinger happened to keep.
+pub struct HttpPinger {
+    connector: Connector,
+    resolver: UpstreamResolver,
+}
+
+impl HttpPinger {
+    pub fn new(resolver: UpstreamResolver) -> Self {
+        Self {
+            connector: Connector::new(None),
+            resolver,
+        }
+    }
+
+    async fn send(&self, target: &PingTarget) -> Result<()> {
+        let upstream = &target.upstream;
+        let addr = self
+            .resolver
+            .resolve(&upstream.host, upstream.port, None)
+            .await?;
+
+        let mut peer = HttpPeer::new(addr, upstream.use_tls, target.sni.clone());
+        if upstream.use_tls {
+            if let Some(options) = peer.get_mut_peer_options() {
+                // Like the proxy's peers until certificate verification is configurable
+                options.verify_cert = false;
+                options.verify_hostname = false;
+            }
+            peer.client_cert_key = target.client_cert.as_ref().map(ClientCert::cert_key);
+        }
+
+        let (mut session, _) = self.connector.get_http_session(&peer).await?;
+        let mut request = RequestHeader::build("HEAD", target.path.as_bytes(), None)?;
+        request.insert_header(http::header::HOST, &upstream.host)?;
+        session.write_request_header(Box::new(request)).await?;
+        session.finish_request_body().await?;
+        // Any answer means the backend is alive, even a 404 or 405 for HEAD;
+        // dropping the session closes the connection
+        session.read_response_header().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Pinger for HttpPinger {
+    async fn ping(&self, target: &PingTarget) -> bool {
+        let failure = match timeout(PING_TIMEOUT, self.send(target)).await {
+            Ok(Ok(())) => return true,
+            Ok(Err(e)) => format!("{:#}", e),
+            Err(_) => format!("no answer within {:?}", PING_TIMEOUT),
+        };
+        debug!(
+            "Liveness ping to {}:{}{} failed: {}",
+            target.upstream.host, target.upstream.port, target.path, failure
+        );
+        false
+    }
+}
+
+/// Checks that idle backends still answer with periodic pings
+///
+/// Only backends with `liveness_ping_interval_seconds` are pinged, at most
+/// once per interval (never more often than [`MIN_INTERVAL`]) and only when no
+/// request has reached them within it, so busy backends get no extra traffic.
+/// A failed ping reveals a dead backend before a real request runs into it;
+/// for DNS SRV backends every discovered instance is pinged, and the outcome
+/// counts towards the instance's health like a request's. The pings don't
+/// touch the proxy's connection pool, so they don't keep its connections open.
+pub struct LivenessPings {
+    pinger: Arc<dyn Pinger>,
+    discovery: Arc<ServiceDiscovery>,
+    /// Last request or ping per backend
+    last_activity: DashMap<Uuid, Instant>,
+}
+
+impl LivenessPings {
+    pub fn new(pinger: Arc<dyn Pinger>, discovery: Arc<ServiceDiscovery>) -> Self {
+        Self {
+            pinger,
+            discovery,
+            last_activity: DashMap::new(),
+        }
+    }
+
+    /// Record that a request was sent to a backend
+    pub fn touch(&self, service_id: Uuid, now: Instant) {
+        self.last_activity.insert(service_id, now);
+    }
+
+    /// Start the background task pinging backends as they become due
+    pub fn start_background_pinger(self: Arc<Self>, config_loader: Arc<ConfigLoader>) {
+        tokio::spawn(async move {
+            info!("Starting upstream liveness ping background task");
+            let mut tick = interval(TICK);
+
+            loop {
+                tick.tick().await;
+                let config = config_loader.get_config();
+
+                // Forget backends that were removed or lost their interval
+                self.last_activity.retain(|id, _| {
+                    config
+                        .services
+                        .get(id)
+                        .is_some_and(|s| s.liveness_ping_interval_seconds.is_some())
+                });
+
+                self.ping_due(&config, Instant::now()).await;
+            }
+        });
+    }
+
+    /// Ping every backend whose ping interval has passed without activity
+    ///
+    /// All due targets are pinged at once, so one slow backend doesn't hold
+    /// up the others.
+    async fn ping_due(&self, config: &GatewayConfig, now: Instant) {
+        let mut targets = Vec::new();
+        for service in config.services.values() {
+            let Some(every) = ping_interval(service) else {
+                continue;
+            };
+
+            let due = self
+                .last_activity
+                .get(&service.id)
+                .map(|last| now.saturating_duration_since(*last) >= every)
+                .unwrap_or(true);
+            if !due {
+                continue;
+            }
+            self.touch(service.id, now);
+
+            match self.ping_targets(service, config.client_certs.get(&service.id)) {
+                Ok(service_targets) => targets.extend(service_targets),
+                Err(e) => warn!("Can't ping backend {}: {}", service.name, e),
+            }
+        }
+
+        let outcomes = join_all(targets.iter().map(|target| self.pinger.ping(target))).await;
+        for (target, answered) in targets.iter().zip(outcomes) {
+            if target.discovered {
+                self.discovery.record_outcome(
+                    target.service_id,
+                    &target.upstream.host,
+                    target.upstream.port,
+                    !answered,
+                );
+            }
+            if !answered {
+                warn!(
+                    "Liveness ping to {}:{}{} of backend {} failed",
+                    target.upstream.host, target.upstream.port, target.path, target.service_id
+                );
+            }
+        }
+    }
+
+    /// The discovered instances of a backend, or its `base_url` when it has none
+    fn ping_targets(
+        &self,
+        service: &BackendService,
+        client_cert: Option<&ClientCert>,
+    ) -> Result<Vec<PingTarget>, String> {
+        let url =
+            health_probe::health_check_url(service).unwrap_or_else(|| service.base_url.clone());
+        let upstream = UpstreamTarget::parse(&url)?;
+        let path = Url::parse(&url)
+            .map(|url| match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            })
+            .map_err(|e| format!("invalid URL: {}", e))?;
+
+        let target = |upstream: UpstreamTarget, discovered: bool| PingTarget {
+            service_id: service.id,
+            sni: service
+                .tls_sni
+                .clone()
+                .unwrap_or_else(|| upstream.host.clone()),
+            upstream,
+            client_cert: client_cert.cloned(),
+            path: path.clone(),
+            discovered,
+        };
+
+        let instances = self.discovery.instances(&service.id);
+        if instances.is_empty() {
+            return Ok(vec![target(upstream, false)]);
+        }
+        Ok(instances
+            .into_iter()
+            .map(|instance| {
+                let upstream = UpstreamTarget {
+                    host: instance.host,
+                    port: instance.port,
+                    use_tls: upstream.use_tls,
+                };
+                target(upstream, true)
+            })
+            .collect())
+    }
+}
+
+/// How often a backend should be pinged, `None` when pings are off
+fn ping_interval(service: &BackendService) -> Option<Duration> {
+    let seconds = service.liveness_ping_interval_seconds.filter(|s| *s > 0)?;
+    Some(Duration::from_secs(seconds as u64).max(MIN_INTERVAL))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_loader::tests::service;
+    use crate::discovery::tests::discovered;
+    use karateway_config::circuit_breaker::BreakerState;
+    use std::sync::Mutex;
+
+    /// Records every ping, answering unless the host is in `down`
+    #[derive(Default)]
+    struct MockPinger {
+        pings: Mutex<Vec<String>>,
+        down: Vec<String>,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl Pinger for MockPinger {
+        async fn ping(&self, target: &PingTarget) -> bool {
+            tokio::time::sleep(self.delay).await;
+            self.pings.lock().unwrap().push(format!(
+                "{}:{}{}",
+                target.upstream.host, target.upstream.port, target.path
+            ));
+            !self.down.contains(&target.upstream.host)
+        }
+    }
+
+    fn backend(liveness_ping_interval_seconds: Option<i32>) -> BackendService {
+        let mut backend = service("orders", "http://orders:9000");
+        backend.liveness_ping_interval_seconds = liveness_ping_interval_seconds;
+        backend
+    }
+
+    fn config(services: &[&BackendService]) -> GatewayConfig {
+        let mut config = GatewayConfig::new();
+        for service in services {
+            config.services.insert(service.id, (*service).clone());
+        }
+        config
+    }
+
+    async fn liveness(pinger: Arc<MockPinger>) -> LivenessPings {
+        let static_backend = backend(None);
+        LivenessPings::new(pinger, Arc::new(discovered(&static_backend, &[]).await))
+    }
+
+    #[tokio::test]
+    async fn test_pings_occur_at_the_configured_interval() {
+        let pinger = Arc::new(MockPinger::default());
+        let liveness = liveness(pinger.clone()).await;
+        let idle = backend(Some(10));
+        let disabled = backend(None);
+        let config = config(&[&idle, &disabled]);
+        let start = Instant::now();
+
+        for second in 0..=30 {
+            let now = start + Duration::from_secs(second);
+            liveness.ping_due(&config, now).await;
+        }
+
+        // At 0s, 10s, 20s and 30s; the backend without an interval is never pinged
+        let pings = pinger.pings.lock().unwrap();
+        assert_eq!(pings.len(), 4);
+        assert!(pings.iter().all(|ping| ping == "orders:9000/"));
+    }
+
+    #[tokio::test]
+    async fn test_recent_requests_and_short_intervals_limit_pings() {
+        let pinger = Arc::new(MockPinger::default());
+        let liveness = liveness(pinger.clone()).await;
+        let mut busy = backend(Some(10));
+        busy.health_check_url = Some("/health".to_string());
+        let eager = backend(Some(1));
+        let config = config(&[&busy, &eager]);
+        let start = Instant::now();
+
+        for second in 0..10 {
+            let now = start + Duration::from_secs(second);
+            // A request every second shows the busy backend is alive on its own
+            liveness.touch(busy.id, now);
+            liveness.ping_due(&config, now).await;
+        }
+
+        // Only the eager backend, capped at one ping per MIN_INTERVAL (0s and 5s)
+        assert_eq!(pinger.pings.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_backends_are_pinged_concurrently() {
+        let pinger = Arc::new(MockPinger {
+            delay: Duration::from_millis(200),
+            ..Default::default()
+        });
+        let liveness = liveness(pinger.clone()).await;
+        let backends: Vec<BackendService> = (0..5).map(|_| backend(Some(10))).collect();
+        let config = config(&backends.iter().collect::<Vec<_>>());
+
+        let started = Instant::now();
+        liveness.ping_due(&config, started).await;
+
+        assert_eq!(pinger.pings.lock().unwrap().len(), 5);
+        assert!(started.elapsed() < Duration::from_millis(600));
+    }
+
+    #[tokio::test]
+    async fn test_failed_pings_count_towards_instance_health() {
+        let pinger = Arc::new(MockPinger {
+            down: vec!["orders-2.node.consul".to_string()],
+            ..Default::default()
+        });
+        let backend = backend(Some(5));
+        let discovery = Arc::new(
+            discovered(
+                &backend,
+                &[
+                    ("orders-1.node.consul", 9001),
+                    ("orders-2.node.consul", 9002),
+                ],
+            )
+            .await,
+        );
+        let liveness = LivenessPings::new(pinger.clone(), discovery.clone());
+        let config = config(&[&backend]);
+        let start = Instant::now();
+
+        for ping in 0..3 {
+            liveness
+                .ping_due(&config, start + MIN_INTERVAL * ping)
+                .await;
+        }
+
+        // Both instances are pinged, and the one not answering is ejected
+        assert_eq!(pinger.pings.lock().unwrap().len(), 6);
+        let breakers = discovery.breakers(start + MIN_INTERVAL * 2);
+        let states: Vec<(u16, BreakerState)> = breakers
+            .iter()
+            .map(|breaker| (breaker.port, breaker.state))
+            .collect();
+        assert_eq!(
+            states,
+            [(9001, BreakerState::Closed), (9002, BreakerState::Open)]
+        );
+    }
+}
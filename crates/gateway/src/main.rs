@@ -1,79 +1,196 @@
+mod access_log;
+mod api_version;
+mod body_log;
+mod build_info;
+mod canary;
+mod client_info;
+mod client_limits;
+mod coalesce;
+mod concurrency;
 mod config_loader;
+mod content_type;
+mod cookie_rewrite;
+mod deadline;
+mod decompress;
+mod discovery;
+mod error_code;
+mod expect_continue;
+mod failover;
+mod header_limits;
 mod health_checker;
+mod hop_by_hop;
+mod instance_health;
+mod listener_guard;
+mod liveness;
+mod maintenance;
+mod method_override;
+mod metrics_server;
+mod not_found;
+mod path_case;
 mod proxy;
+mod proxy_protocol;
+mod query_match;
 mod rate_limiter;
+mod request_log;
+mod route_cache;
 mod router;
+mod selection;
+mod socket_options;
+mod tagging;
+mod timeout_override;
+mod timeouts;
+mod timing;
+mod tls;
+mod trailers;
+mod unmatched_audit;
+mod upstream;
+mod upstream_resolver;
+mod upstream_tls;
 mod whitelist_validator;
 
 use anyhow::Result;
+use karateway_metrics::GatewayMetrics;
+use pingora_core::apps::http_app::HttpServer;
 use pingora_core::server::Server;
-use pingora_proxy::http_proxy_service;
+use pingora_core::services::listening::Service;
+use pingora_proxy::http_proxy;
 use std::sync::Arc;
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use config_loader::ConfigLoader;
+use client_limits::ClientLimits;
+use concurrency::BackendConcurrency;
+use config_loader::{ConfigLoader, ReloadRetry};
+use discovery::{DnsSrvResolver, ServiceDiscovery};
 use health_checker::HealthChecker;
-use proxy::KaratewayProxy;
+use listener_guard::{ConnectionStats, ListenerGuard, ListenerLimits, TlsListener};
+use liveness::{HttpPinger, LivenessPings};
+use maintenance::Maintenance;
+use metrics_server::MetricsApp;
+use path_case::PathCase;
+use proxy::{KaratewayProxy, ProxyServices};
 use rate_limiter::RateLimiter;
+use socket_options::ListenerSocketOptions;
+use upstream_resolver::UpstreamResolver;
 
 fn main() -> Result<()> {
     // Initialize environment variables
     karateway_config::init_env();
 
-    // Initialize rustls crypto provider (required for rustls TLS)
-    let _ = rustls::crypto::ring::default_provider().install_default();
-
     // Initialize tracing
     init_tracing();
 
     info!("Starting Karateway Gateway v{}", env!("CARGO_PKG_VERSION"));
 
-    // Load application configuration synchronously using blocking runtime
-    let rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()?;
+    // Load application configuration
+    let app_config = karateway_config::AppConfig::from_env()?;
+    info!("Loaded configuration from environment");
 
-    let (config_loader, audit_logger) = rt.block_on(async {
-        // Load application configuration
-        let app_config = karateway_config::AppConfig::from_env()?;
-        info!("Loaded configuration from environment");
+    // Runtime for the background tasks; sized like the Pingora server when configured
+    let worker_threads = app_config.worker_threads();
+    let mut rt_builder = tokio::runtime::Builder::new_multi_thread();
+    if let Some(threads) = worker_threads {
+        rt_builder.worker_threads(threads);
+        info!("Using {} worker threads", threads);
+    }
+    let rt = rt_builder.enable_all().build()?;
 
+    // Initialize rustls crypto provider (required for rustls TLS). Outbound TLS
+    // uses its defaults; the HTTPS listener gets the TLS policy on its own.
+    if rustls::crypto::ring::default_provider()
+        .install_default()
+        .is_err()
+    {
+        warn!("A rustls crypto provider was already installed, keeping it for outbound TLS");
+    }
+    let tls_policy = tls::TlsPolicy::parse_or_default(
+        &app_config.gateway_tls_min_version,
+        &app_config.gateway_tls_profile,
+    );
+    info!(
+        "TLS policy: {:?} profile, minimum {}",
+        tls_policy.profile,
+        tls_policy.effective_min_version()
+    );
+
+    let (db_pool, config_loader, audit_logger, request_logger) = rt.block_on(async {
         // Initialize database connection pool
         let db_pool = sqlx::postgres::PgPoolOptions::new()
             .max_connections(10)
+            .acquire_timeout(
+                karateway_config::DatabaseConfig::new(app_config.clone()).acquire_timeout(),
+            )
             .connect(&app_config.database_url())
             .await?;
         info!("Connected to PostgreSQL database");
 
         // Initialize audit logger
-        let audit_logger = Arc::new(karateway_config::AuditLogger::new(db_pool.clone()));
+        let escalation_rules =
+            karateway_config::audit_escalation::parse_rules(&app_config.audit_escalation_rules);
+        let sampling_rules =
+            karateway_config::audit_sampling::parse_rules(&app_config.audit_sampling_rules);
+        let disabled_categories = karateway_config::audit_categories::DisabledCategories::parse(
+            &app_config.audit_disabled_categories,
+        );
+        if !disabled_categories.is_empty() {
+            info!(
+                "Audit logging disabled for categories: {}",
+                app_config.audit_disabled_categories
+            );
+        }
+        let audit_logger = Arc::new(
+            karateway_config::AuditLogger::with_webhook(
+                db_pool.clone(),
+                escalation_rules,
+                app_config.audit_webhook(),
+            )
+            .with_sampling(sampling_rules)
+            .with_disabled_categories(disabled_categories),
+        );
         info!("Audit logger initialized");
 
+        // Per-request rows in gateway_metrics
+        let request_logger = app_config.gateway_request_log.then(|| {
+            info!("Request logging to gateway_metrics enabled");
+            Arc::new(karateway_config::RequestLogger::new(db_pool.clone()))
+        });
+        if let Some(retention) =
+            karateway_config::metrics_retention::MetricsRetention::from_config(&app_config)
+        {
+            retention.start_background_cleanup(db_pool.clone());
+        }
+
         // Initialize configuration loader
-        let config_loader = Arc::new(ConfigLoader::new(db_pool.clone()));
+        let config_loader = Arc::new(ConfigLoader::new(
+            db_pool.clone(),
+            karateway_config::config_limits::ConfigLimits::from_config(&app_config),
+            PathCase::from_config(&app_config),
+        ));
 
         // Load initial configuration
         config_loader.load_config().await?;
         info!("Loaded initial configuration from database");
 
-        Ok::<_, anyhow::Error>((config_loader, audit_logger))
+        Ok::<_, anyhow::Error>((db_pool, config_loader, audit_logger, request_logger))
     })?;
 
     // Start configuration reload background task on the runtime
     let config_loader_clone = config_loader.clone();
+    let reload_retry = ReloadRetry::from_config(&app_config);
     rt.spawn(async move {
-        config_loader_clone.start_reload_watcher().await;
+        config_loader_clone.start_reload_watcher(reload_retry).await;
     });
     info!("Started configuration reload watcher");
 
     // Initialize rate limiter (optional - only if Redis is configured)
     let rate_limiter = rt.block_on(async {
-        let app_config = karateway_config::AppConfig::from_env().ok()?;
-        match RateLimiter::new(&app_config.redis_url()) {
-            Ok(limiter) => {
+        match karateway_config::RedisConfig::new(app_config.clone()).client() {
+            Ok(client) => {
+                let command_timeout = (app_config.rate_limit_redis_timeout_ms > 0)
+                    .then(|| Duration::from_millis(app_config.rate_limit_redis_timeout_ms));
                 info!("Rate limiter initialized with Redis");
-                Some(Arc::new(limiter))
+                Some(Arc::new(RateLimiter::new(client, command_timeout)))
             }
             Err(e) => {
                 info!("Rate limiter not initialized (Redis not available): {}", e);
@@ -83,38 +200,129 @@ fn main() -> Result<()> {
     });
 
     // Initialize health checker and start background task on the runtime
-    let health_checker = Arc::new(HealthChecker::new(config_loader.clone()));
+    let health_checker = Arc::new(HealthChecker::new(
+        config_loader.clone(),
+        karateway_config::RedisConfig::new(app_config.clone())
+            .client()
+            .ok(),
+        Duration::from_secs(app_config.health_removal_grace_seconds),
+        app_config.gateway_health_history_size,
+        Duration::from_secs(app_config.health_probe_coalesce_ttl_seconds),
+    ));
     let health_checker_clone = health_checker.clone();
     rt.spawn(async move {
-        health_checker_clone.start_background_checker();
+        health_checker_clone.clone().start_background_checker();
+        health_checker_clone.start_verdict_listener();
     });
     info!("Health checker started");
 
+    // Resolve DNS SRV backed services in the background
+    let discovery = Arc::new(ServiceDiscovery::new(Arc::new(
+        DnsSrvResolver::from_system()?,
+    )));
+    let discovery_clone = discovery.clone();
+    let discovery_config_loader = config_loader.clone();
+    let breaker_redis_client = karateway_config::RedisConfig::new(app_config.clone())
+        .client()
+        .ok();
+    rt.spawn(async move {
+        if let Some(redis_client) = breaker_redis_client {
+            discovery_clone.clone().start_breaker_reporter(redis_client);
+        }
+        discovery_clone.start_background_resolver(discovery_config_loader);
+    });
+    info!("Service discovery started");
+
+    // Backend hostnames are resolved per request, by the proxy and the pinger alike
+    let upstream_resolver = UpstreamResolver::from_config(&app_config)?;
+
+    // Ping idle backends that opted into liveness pings
+    let liveness = Arc::new(LivenessPings::new(
+        Arc::new(HttpPinger::new(upstream_resolver.clone())),
+        discovery.clone(),
+    ));
+    let liveness_clone = liveness.clone();
+    let liveness_config_loader = config_loader.clone();
+    rt.spawn(async move {
+        liveness_clone.start_background_pinger(liveness_config_loader);
+    });
+
+    // Serve the maintenance page instead of proxying, while maintenance mode is on
+    let maintenance = Arc::new(Maintenance::from_config(&app_config));
+    if maintenance.is_enabled() {
+        info!("Maintenance mode is on: requests get a 503 maintenance page");
+        let maintenance_clone = maintenance.clone();
+        rt.spawn(async move {
+            maintenance_clone.start_background_reloader();
+        });
+    }
+
     // Create Pingora server
     let mut server = Server::new(None)?;
+    if let Some(threads) = worker_threads {
+        if let Some(conf) = Arc::get_mut(&mut server.configuration) {
+            conf.threads = threads;
+        }
+    }
     server.bootstrap();
 
-    // Create proxy service with rate limiter, health checker, and audit logger
-    let proxy = KaratewayProxy::new(config_loader, rate_limiter, health_checker, audit_logger);
-    let mut proxy_service = http_proxy_service(&server.configuration, proxy);
+    // Request metrics and backend connection caps, shared by the proxy and the metrics endpoint
+    let metrics = Arc::new(GatewayMetrics::new());
+    let concurrency = Arc::new(BackendConcurrency::new());
+    let listener_limits = ListenerLimits::from_config(&app_config);
+    let client_limits = ClientLimits::from_config(&app_config);
+    info!(
+        "Client limits: max connections per IP {:?}, max requests per IP {:?}",
+        client_limits.max_connections, client_limits.max_requests
+    );
+    let connections = Arc::new(ConnectionStats::new(listener_limits, client_limits));
 
-    // Add TCP listener for HTTP
-    proxy_service.add_tcp("0.0.0.0:8080");
-    info!("Gateway server listening on 0.0.0.0:8080 (HTTP)");
+    // Metrics get their own listener so /metrics is never proxied to a backend
+    let mut metrics_service = Service::new(
+        "Karateway Metrics".to_string(),
+        HttpServer::new_app(MetricsApp::new(
+            metrics.clone(),
+            concurrency.clone(),
+            health_checker.clone(),
+            config_loader.clone(),
+            db_pool.clone(),
+            connections.clone(),
+        )),
+    );
+    let metrics_addr = format!(
+        "{}:{}",
+        app_config.gateway_host, app_config.gateway_metrics_port
+    );
+    metrics_service.add_tcp(&metrics_addr);
+    info!("Metrics endpoint listening on {}/metrics", metrics_addr);
 
-    // Try to add TLS listener if certificate exists
+    let alpn: tls::AlpnProtocols = app_config
+        .gateway_tls_alpn
+        .parse()
+        .map_err(|e| anyhow::anyhow!("GATEWAY_TLS_ALPN: {}", e))?;
+
+    // TLS listener if a certificate exists; the listener guard runs its handshakes
     let cert_path = "certs/cert.pem";
     let key_path = "certs/key.pem";
-
-    if std::path::Path::new(cert_path).exists() && std::path::Path::new(key_path).exists() {
-        match pingora_core::listeners::tls::TlsSettings::intermediate(cert_path, key_path) {
-            Ok(mut tls_settings) => {
-                tls_settings.enable_h2();
-                proxy_service.add_tls_with_settings("0.0.0.0:8443", None, tls_settings);
-                info!("Gateway server listening on 0.0.0.0:8443 (HTTPS)");
-            }
+    let tls_listener = if std::path::Path::new(cert_path).exists()
+        && std::path::Path::new(key_path).exists()
+    {
+        let acceptor = pingora_core::listeners::tls::TlsSettings::intermediate(cert_path, key_path)
+            .map_err(|e| e.to_string())
+            .and_then(|settings| {
+                let config = tls_policy.server_config(cert_path, key_path, alpn)?;
+                let mut acceptor = settings.build();
+                acceptor.acceptor = pingora_core::tls::TlsAcceptor::from(Arc::new(config));
+                Ok(acceptor)
+            });
+        match acceptor {
+            Ok(acceptor) => Some(TlsListener {
+                port: 8443,
+                acceptor,
+            }),
             Err(e) => {
                 info!("TLS not configured: {} (cert/key not found or invalid)", e);
+                None
             }
         }
     } else {
@@ -123,10 +331,69 @@ fn main() -> Result<()> {
             cert_path, key_path
         );
         info!("To enable HTTPS, generate certificates: openssl req -x509 -newkey rsa:4096 -keyout certs/key.pem -out certs/cert.pem -days 365 -nodes -subj \"/CN=localhost\"");
+        None
+    };
+    let tls_port = tls_listener.as_ref().map(|tls| tls.port);
+
+    // Create proxy service with rate limiter, health checker, and audit logger
+    let proxy = KaratewayProxy::new(
+        ProxyServices {
+            config_loader,
+            rate_limiter,
+            health_checker,
+            audit_logger,
+            request_logger,
+            discovery,
+            metrics,
+            concurrency,
+            liveness,
+            maintenance,
+            client_requests: connections.client_requests(),
+            upstream_resolver,
+        },
+        &app_config,
+    );
+    // Connection cap and request head deadline in front of the proxy
+    let mut proxy_service = Service::new(
+        "Pingora HTTP Proxy Service".to_string(),
+        ListenerGuard::new(
+            http_proxy(&server.configuration, proxy),
+            listener_limits,
+            connections,
+            app_config.gateway_proxy_protocol,
+            tls_listener,
+            alpn,
+        ),
+    );
+    info!(
+        "Listener limits: max connections {:?}, header read timeout {:?}",
+        listener_limits.max_connections, listener_limits.header_read_timeout
+    );
+
+    // Add TCP listener for HTTP
+    let socket_options = ListenerSocketOptions::from_config(&app_config)?;
+    if socket_options != ListenerSocketOptions::default() {
+        info!("Listener socket options: {:?}", socket_options);
+    }
+    proxy_service.add_tcp_with_settings("0.0.0.0:8080", socket_options.tcp_options());
+    info!("Gateway server listening on 0.0.0.0:8080 (HTTP)");
+    if app_config.gateway_proxy_protocol {
+        info!("PROXY protocol required on 0.0.0.0:8080");
+    }
+
+    // TLS listener; the handshake runs in the listener guard
+    if let Some(port) = tls_port {
+        let addr = format!("0.0.0.0:{}", port);
+        proxy_service.add_tcp_with_settings(&addr, socket_options.tcp_options());
+        info!(
+            "Gateway server listening on {} (HTTPS, ALPN {})",
+            addr, alpn
+        );
     }
 
-    // Add service to server
+    // Add services to server
     server.add_service(proxy_service);
+    server.add_service(metrics_service);
 
     // Keep runtime alive by moving it into a thread
     std::thread::spawn(move || {
@@ -0,0 +1,262 @@
+use arc_swap::ArcSwap;
+use bytes::Bytes;
+use karateway_config::AppConfig;
+use pingora_http::ResponseHeader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::time::interval;
+use tracing::{info, warn};
+
+use crate::error_code::ErrorCode;
+use crate::path_case::PathCase;
+
+/// Page served when no maintenance page is configured, or it can't be read yet
+pub fn default_body() -> Bytes {
+    Bytes::from(
+        ErrorCode::Maintenance
+            .body("The gateway is down for maintenance")
+            .to_string(),
+    )
+}
+
+/// How often the page file is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The page's content and the modification time it was read at
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Page {
+    content_type: &'static str,
+    body: Bytes,
+    modified: Option<SystemTime>,
+}
+
+impl Default for Page {
+    fn default() -> Self {
+        Self {
+            content_type: "application/json",
+            body: default_body(),
+            modified: None,
+        }
+    }
+}
+
+/// Gateway-wide maintenance mode: every request gets a `503` with a static page
+///
+/// Checked before routing, so routes, backends and rate limits are never
+/// touched. Paths in the bypass list (e.g. health checks) keep being
+/// proxied. The page is read from disk and re-read whenever the file changes,
+/// so it can be edited during the maintenance window.
+pub struct Maintenance {
+    enabled: bool,
+    /// File the page is read from, `None` for the default JSON error
+    page_path: Option<PathBuf>,
+    /// Path prefixes that are still proxied
+    bypass_paths: Vec<String>,
+    /// How request paths are compared with the bypass prefixes, like with routes
+    path_case: PathCase,
+    page: ArcSwap<Page>,
+}
+
+impl Maintenance {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::new(
+            config.gateway_maintenance_mode,
+            config.gateway_maintenance_page.as_deref(),
+            &config.gateway_maintenance_bypass_paths,
+            PathCase::from_config(config),
+        )
+    }
+
+    /// `bypass_paths` is a comma-separated list of path prefixes
+    pub fn new(
+        enabled: bool,
+        page_path: Option<&str>,
+        bypass_paths: &str,
+        path_case: PathCase,
+    ) -> Self {
+        let maintenance = Self {
+            enabled,
+            page_path: page_path
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            bypass_paths: bypass_paths
+                .split(',')
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(str::to_string)
+                .collect(),
+            path_case,
+            page: ArcSwap::from_pointee(Page::default()),
+        };
+        if enabled {
+            maintenance.reload();
+        }
+        maintenance
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether a request for `path` gets the maintenance page instead of being proxied
+    ///
+    /// A bypass prefix covers its own path and the paths below it, so
+    /// `/health` spares `/health/db` but not `/healthz`.
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.enabled
+            && !self.bypass_paths.iter().any(|bypass| {
+                self.path_case
+                    .strip_prefix(path, bypass)
+                    .is_some_and(|rest| {
+                        rest.is_empty() || rest.starts_with('/') || bypass.ends_with('/')
+                    })
+            })
+    }
+
+    /// The `503` with the current maintenance page
+    pub fn response(&self) -> pingora_core::Result<(ResponseHeader, Bytes)> {
+        let page = self.page.load();
+
+        let mut resp = ResponseHeader::build(503, None)?;
+        resp.insert_header("Content-Type", page.content_type)?;
+        resp.insert_header("Content-Length", &page.body.len().to_string())?;
+        resp.insert_header("Cache-Control", "no-store")?;
+        Ok((resp, page.body.clone()))
+    }
+
+    /// Re-read the page file if it changed since it was last read
+    ///
+    /// A file that can't be read is logged and the last good page is kept.
+    pub fn reload(&self) {
+        let Some(path) = &self.page_path else {
+            return;
+        };
+
+        let modified = match std::fs::metadata(path).and_then(|meta| meta.modified()) {
+            Ok(modified) => Some(modified),
+            Err(e) => {
+                warn!("Can't read maintenance page {}: {}", path.display(), e);
+                return;
+            }
+        };
+        if self.page.load().modified == modified {
+            return;
+        }
+
+        match std::fs::read(path) {
+            Ok(body) => {
+                info!(
+                    "Loaded maintenance page {} ({} bytes)",
+                    path.display(),
+                    body.len()
+                );
+                self.page.store(Arc::new(Page {
+                    content_type: content_type(path),
+                    body: Bytes::from(body),
+                    modified,
+                }));
+            }
+            Err(e) => warn!("Can't read maintenance page {}: {}", path.display(), e),
+        }
+    }
+
+    /// Watch the page file for changes while maintenance mode is on
+    pub fn start_background_reloader(self: Arc<Self>) {
+        if !self.enabled || self.page_path.is_none() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut tick = interval(RELOAD_INTERVAL);
+            loop {
+                tick.tick().await;
+                self.reload();
+            }
+        });
+    }
+}
+
+/// Content type of a page file, from its extension
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("html" | "htm") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A page file in a fresh temporary directory
+    fn page_file(name: &str, content: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("karateway-maintenance-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_maintenance_serves_the_page_from_disk() {
+        let path = page_file("maintenance.html", "<h1>Back soon</h1>");
+        let maintenance = Maintenance::new(true, path.to_str(), "", PathCase::Sensitive);
+
+        assert!(maintenance.applies_to("/api/orders"));
+        let (resp, body) = maintenance.response().unwrap();
+        assert_eq!(resp.status.as_u16(), 503);
+        assert_eq!(
+            resp.headers.get("Content-Type").unwrap(),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(body, "<h1>Back soon</h1>");
+
+        // Edits during the window are picked up
+        std::fs::write(&path, "<h1>Almost done</h1>").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        maintenance.reload();
+        assert_eq!(maintenance.response().unwrap().1, "<h1>Almost done</h1>");
+
+        // A page that disappears keeps the last one
+        std::fs::remove_file(&path).unwrap();
+        maintenance.reload();
+        assert_eq!(maintenance.response().unwrap().1, "<h1>Almost done</h1>");
+    }
+
+    #[test]
+    fn test_bypass_paths_are_still_proxied() {
+        let maintenance = Maintenance::new(true, None, "/health, /api/status", PathCase::Sensitive);
+        assert!(!maintenance.applies_to("/health"));
+        assert!(!maintenance.applies_to("/api/status/db"));
+        assert!(maintenance.applies_to("/api/orders"));
+        assert!(maintenance.applies_to("/HEALTH"));
+        // Only whole path segments are bypassed
+        assert!(maintenance.applies_to("/healthz"));
+        assert!(maintenance.applies_to("/api/statuses"));
+
+        // Folded like route paths when those are
+        let insensitive = Maintenance::new(true, None, "/health", PathCase::Insensitive);
+        assert!(!insensitive.applies_to("/HEALTH"));
+
+        // Without a page file the default JSON error is served
+        let (resp, body) = maintenance.response().unwrap();
+        assert_eq!(
+            resp.headers.get("Content-Type").unwrap(),
+            "application/json"
+        );
+        assert_eq!(body, default_body());
+
+        let off = Maintenance::new(false, None, "", PathCase::Sensitive);
+        assert!(!off.applies_to("/api/orders"));
+    }
+}
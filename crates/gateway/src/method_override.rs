@@ -0,0 +1,57 @@
+use http::HeaderMap;
+use karateway_core::models::HttpMethod;
+
+/// Header carrying the method a client actually means
+pub const METHOD_OVERRIDE_HEADER: &str = "X-HTTP-Method-Override";
+
+/// The method a request asks to be treated as via `X-HTTP-Method-Override`
+///
+/// Only POST requests may override their method: letting a GET turn into a
+/// DELETE would make state-changing calls reachable from plain links. Unknown
+/// methods and overrides to POST itself are ignored.
+pub fn requested_method(headers: &HeaderMap, method: &str) -> Option<String> {
+    if !method.eq_ignore_ascii_case("POST") {
+        return None;
+    }
+
+    let requested = headers
+        .get(METHOD_OVERRIDE_HEADER)
+        .and_then(|h| h.to_str().ok())?
+        .trim()
+        .parse::<HttpMethod>()
+        .ok()?;
+
+    match requested {
+        HttpMethod::POST => None,
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(METHOD_OVERRIDE_HEADER, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_requested_method() {
+        assert_eq!(
+            requested_method(&headers("delete"), "POST"),
+            Some("DELETE".to_string())
+        );
+        assert_eq!(
+            requested_method(&headers(" PATCH "), "POST"),
+            Some("PATCH".to_string())
+        );
+
+        // Only POST may override, and only to a known, different method
+        assert_eq!(requested_method(&headers("DELETE"), "GET"), None);
+        assert_eq!(requested_method(&headers("TRACE"), "POST"), None);
+        assert_eq!(requested_method(&headers("POST"), "POST"), None);
+        assert_eq!(requested_method(&HeaderMap::new(), "POST"), None);
+    }
+}
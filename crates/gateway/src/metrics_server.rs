@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use http::{header, Response, StatusCode};
+use karateway_config::database::PoolUsage;
+use karateway_metrics::{
+    BackendHealth, DatabasePool, ExportFormat, GatewayMetrics, MetricsSnapshot,
+};
+use pingora_core::apps::http_app::ServeHttp;
+use pingora_core::protocols::http::ServerSession;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::build_info::BuildInfo;
+use crate::concurrency::BackendConcurrency;
+use crate::config_loader::ConfigLoader;
+use crate::health_checker::{HealthChecker, HealthStatus};
+use crate::listener_guard::ConnectionStats;
+
+/// Serves `GET /metrics` in Prometheus text (default) or JSON (`?format=json`),
+/// and the gateway's build info at `GET /version`
+pub struct MetricsApp {
+    metrics: Arc<GatewayMetrics>,
+    concurrency: Arc<BackendConcurrency>,
+    health_checker: Arc<HealthChecker>,
+    config_loader: Arc<ConfigLoader>,
+    db_pool: PgPool,
+    connections: Arc<ConnectionStats>,
+}
+
+impl MetricsApp {
+    pub fn new(
+        metrics: Arc<GatewayMetrics>,
+        concurrency: Arc<BackendConcurrency>,
+        health_checker: Arc<HealthChecker>,
+        config_loader: Arc<ConfigLoader>,
+        db_pool: PgPool,
+        connections: Arc<ConnectionStats>,
+    ) -> Self {
+        Self {
+            metrics,
+            concurrency,
+            health_checker,
+            config_loader,
+            db_pool,
+            connections,
+        }
+    }
+
+    fn snapshot(&self) -> MetricsSnapshot {
+        let config = self.config_loader.get_config();
+        let backends = config
+            .services
+            .values()
+            .map(|service| {
+                let usage = self.concurrency.usage(&service.id);
+                BackendHealth {
+                    id: service.id.to_string(),
+                    name: service.name.clone(),
+                    healthy: self.health_checker.get_status(&service.id) == HealthStatus::Healthy,
+                    active_connections: usage.map(|(in_use, _)| in_use).unwrap_or(0),
+                    max_connections: usage.map(|(_, limit)| limit),
+                }
+            })
+            .collect();
+
+        let usage = PoolUsage::of(&self.db_pool);
+        let mut snapshot = self.metrics.snapshot(backends);
+        snapshot.database_pool = Some(DatabasePool {
+            in_use: usage.in_use(),
+            idle: usage.idle,
+            max_connections: usage.max,
+        });
+        snapshot.connections = Some(self.connections.snapshot());
+        snapshot
+    }
+}
+
+/// Read the requested export format from a query string
+///
+/// A missing `format` param means Prometheus text.
+pub fn requested_format(query: Option<&str>) -> Result<ExportFormat, String> {
+    let format = query.and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "format")
+            .map(|(_, value)| value.into_owned())
+    });
+
+    match format {
+        Some(format) => format.parse(),
+        None => Ok(ExportFormat::default()),
+    }
+}
+
+fn response(status: StatusCode, content_type: &str, body: String) -> Response<Vec<u8>> {
+    let body = body.into_bytes();
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .unwrap_or_default()
+}
+
+/// Version, git commit and build time of the running gateway
+fn version_response() -> Response<Vec<u8>> {
+    response(
+        StatusCode::OK,
+        "application/json",
+        BuildInfo::current().to_json().to_string(),
+    )
+}
+
+#[async_trait]
+impl ServeHttp for MetricsApp {
+    async fn response(&self, http_session: &mut ServerSession) -> Response<Vec<u8>> {
+        let uri = &http_session.req_header().uri;
+
+        match uri.path() {
+            "/metrics" => {}
+            "/version" => return version_response(),
+            _ => return response(StatusCode::NOT_FOUND, "text/plain", "Not Found".to_string()),
+        }
+
+        match requested_format(uri.query()) {
+            Ok(format) => response(
+                StatusCode::OK,
+                format.content_type(),
+                format.render(&self.snapshot()),
+            ),
+            Err(e) => response(StatusCode::BAD_REQUEST, "text/plain", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requested_format() {
+        assert_eq!(requested_format(None), Ok(ExportFormat::Prometheus));
+        assert_eq!(requested_format(Some("")), Ok(ExportFormat::Prometheus));
+        assert_eq!(
+            requested_format(Some("format=json")),
+            Ok(ExportFormat::Json)
+        );
+        assert_eq!(
+            requested_format(Some("format=prometheus")),
+            Ok(ExportFormat::Prometheus)
+        );
+        assert!(requested_format(Some("format=protobuf")).is_err());
+    }
+
+    #[test]
+    fn test_version_endpoint_returns_the_gateway_version() {
+        let response = version_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["git_commit"].is_string());
+    }
+}
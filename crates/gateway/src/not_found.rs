@@ -0,0 +1,123 @@
+use bytes::Bytes;
+use http::HeaderValue;
+use karateway_config::AppConfig;
+use pingora_core::Result;
+use tracing::warn;
+
+use crate::error_code::ErrorCode;
+
+pub const DEFAULT_CONTENT_TYPE: &str = "application/json";
+
+/// Response to requests that match no route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotFoundResponse {
+    pub status: u16,
+    pub content_type: HeaderValue,
+    pub body: Bytes,
+}
+
+impl Default for NotFoundResponse {
+    fn default() -> Self {
+        Self {
+            status: 404,
+            content_type: HeaderValue::from_static(DEFAULT_CONTENT_TYPE),
+            // In the shape of the other gateway errors
+            body: Bytes::from(
+                ErrorCode::RouteNotFound
+                    .body("No route matches the request")
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+impl NotFoundResponse {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self::new(
+            config.gateway_not_found_status,
+            &config.gateway_not_found_content_type,
+            config.gateway_not_found_body.clone(),
+        )
+    }
+
+    /// Build the response; invalid settings are logged and keep their default
+    pub fn new(status: u16, content_type: &str, body: Option<String>) -> Self {
+        let default = Self::default();
+
+        let status = match status {
+            status @ 400..=599 => status,
+            status => {
+                warn!("Invalid GATEWAY_NOT_FOUND_STATUS {}, using 404", status);
+                default.status
+            }
+        };
+        let content_type = match HeaderValue::from_str(content_type) {
+            Ok(content_type) => content_type,
+            Err(_) => {
+                warn!(
+                    "Invalid GATEWAY_NOT_FOUND_CONTENT_TYPE {:?}, using {}",
+                    content_type, DEFAULT_CONTENT_TYPE
+                );
+                default.content_type
+            }
+        };
+        let body = body.map(Bytes::from).unwrap_or(default.body);
+
+        Self {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    /// The response head and body to send
+    pub fn response(&self) -> Result<(pingora_http::ResponseHeader, Bytes)> {
+        let mut resp = pingora_http::ResponseHeader::build(self.status, None)?;
+        resp.insert_header("Content-Type", self.content_type.clone())?;
+        resp.insert_header("Content-Length", self.body.len().to_string())?;
+        Ok((resp, self.body.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmatched_routes_get_the_configured_response() {
+        let not_found = NotFoundResponse::new(
+            410,
+            "application/problem+json",
+            Some(r#"{"title":"Gone","brand":"acme"}"#.to_string()),
+        );
+
+        let (resp, body) = not_found.response().unwrap();
+        assert_eq!(resp.status.as_u16(), 410);
+        assert_eq!(
+            resp.headers.get("Content-Type").unwrap(),
+            "application/problem+json"
+        );
+        assert_eq!(body, r#"{"title":"Gone","brand":"acme"}"#);
+        assert_eq!(
+            resp.headers.get("Content-Length").unwrap(),
+            &body.len().to_string()
+        );
+    }
+
+    #[test]
+    fn test_default_is_a_json_404() {
+        let (resp, body) = NotFoundResponse::default().response().unwrap();
+        assert_eq!(resp.status.as_u16(), 404);
+        assert_eq!(
+            resp.headers.get("Content-Type").unwrap(),
+            "application/json"
+        );
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error_code"], "ROUTE_NOT_FOUND");
+
+        // Invalid settings keep their default, so unmatched requests never look successful
+        let not_found = NotFoundResponse::new(200, "text/plain\n", None);
+        assert_eq!(not_found.status, 404);
+        assert_eq!(not_found.content_type, DEFAULT_CONTENT_TYPE);
+    }
+}
@@ -0,0 +1,66 @@
+use karateway_config::AppConfig;
+
+/// How request paths are compared with route `path_pattern`s
+///
+/// Case-sensitive by default, as RFC 3986 has it. Insensitive mode only
+/// folds ASCII letters; the query string is never folded, so `query_match`
+/// conditions compare exactly in both modes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathCase {
+    #[default]
+    Sensitive,
+    Insensitive,
+}
+
+impl PathCase {
+    pub fn from_config(config: &AppConfig) -> Self {
+        if config.gateway_case_insensitive_paths {
+            PathCase::Insensitive
+        } else {
+            PathCase::Sensitive
+        }
+    }
+
+    /// The rest of `path` after `prefix`, when `path` starts with it
+    ///
+    /// The rest keeps the request's own case.
+    pub fn strip_prefix<'a>(self, path: &'a str, prefix: &str) -> Option<&'a str> {
+        match self {
+            PathCase::Sensitive => path.strip_prefix(prefix),
+            PathCase::Insensitive => {
+                let head = path.get(..prefix.len())?;
+                head.eq_ignore_ascii_case(prefix)
+                    .then(|| &path[prefix.len()..])
+            }
+        }
+    }
+
+    pub fn starts_with(self, path: &str, prefix: &str) -> bool {
+        self.strip_prefix(path, prefix).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_matching_in_both_modes() {
+        let sensitive = PathCase::Sensitive;
+        assert_eq!(
+            sensitive.strip_prefix("/api/users/1", "/api/users"),
+            Some("/1")
+        );
+        assert_eq!(sensitive.strip_prefix("/API/Users/1", "/api/users"), None);
+
+        let insensitive = PathCase::Insensitive;
+        assert_eq!(
+            insensitive.strip_prefix("/API/Users/Alice", "/api/users"),
+            Some("/Alice")
+        );
+        assert!(insensitive.starts_with("/api/users", "/API/USERS"));
+        assert!(!insensitive.starts_with("/api", "/api/users"));
+        // A prefix ending inside a multi-byte character doesn't match
+        assert!(!insensitive.starts_with("/é", "/a"));
+    }
+}
@@ -1,23 +1,65 @@
 use async_trait::async_trait;
 use bytes::Bytes;
-use karateway_config::AuditLogger;
+use karateway_config::client_ip::{self, ClientIpSource};
+use karateway_config::{AppConfig, AuditLogger, RequestLogger};
 use karateway_core::models::{
-    AuditEventCategory, AuditEventType, AuditLogBuilder, AuditSeverity, IdentifierType,
+    AuditEventCategory, AuditEventType, AuditLogBuilder, AuditSeverity, BackendService,
+    IdentifierType, RateLimit,
 };
+use karateway_metrics::GatewayMetrics;
 use pingora_core::upstreams::peer::{HttpPeer, Peer};
 use pingora_core::Result;
 use pingora_http::RequestHeader;
-use pingora_proxy::{ProxyHttp, Session};
+use pingora_proxy::{FailToProxy, ProxyHttp, Session};
+use std::collections::VecDeque;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::OwnedSemaphorePermit;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use crate::config_loader::ConfigLoader;
-use crate::health_checker::HealthChecker;
-use crate::rate_limiter::RateLimiter;
+use crate::access_log::{self, AccessLogEntry};
+use crate::api_version::{self, VersionSource};
+use crate::body_log::{BodyCapture, BodyLogging};
+use crate::canary::CanaryHeader;
+use crate::client_info::{self, ClientInfo, RequestId};
+use crate::client_limits::{ClientCounter, ClientSlot};
+use crate::coalesce::{self, Coalescer, Role, SharedResponse};
+use crate::concurrency::{BackendConcurrency, QueuePolicy};
+use crate::config_loader::{ConfigLoader, RouteMiss};
+use crate::cookie_rewrite::CookieRewrite;
+use crate::deadline::{self, MaxRequestDuration};
+use crate::decompress::{Decompression, Decompressor};
+use crate::discovery::ServiceDiscovery;
+use crate::error_code::{self, ErrorCode};
+use crate::expect_continue::{self, Expectation};
+use crate::failover;
+use crate::header_limits::HeaderLimits;
+use crate::health_checker::{self, HealthChecker};
+use crate::hop_by_hop;
+use crate::instance_health;
+use crate::listener_guard;
+use crate::liveness::LivenessPings;
+use crate::maintenance::Maintenance;
+use crate::method_override::{self, METHOD_OVERRIDE_HEADER};
+use crate::not_found::NotFoundResponse;
+use crate::rate_limiter::{self, order_tiers, FailureMode, RateLimiter, Tier, TierOutcome};
+use crate::request_log::CompletedRequest;
 use crate::router::Router;
+use crate::timeout_override::{self, TimeoutOverride};
+use crate::timeouts::{self, RouteTimeouts};
+use crate::timing;
+use crate::trailers;
+use crate::unmatched_audit::UnmatchedAudit;
+use crate::upstream::UpstreamTarget;
+use crate::upstream_resolver::UpstreamResolver;
+use crate::upstream_tls::ClientCert;
 use crate::whitelist_validator::WhitelistValidator;
 
+/// Response header naming the backend service that served a request, when enabled
+pub const BACKEND_SERVICE_HEADER: &str = "X-Backend-Service";
+
 /// Karateway proxy context for each request
 pub struct RequestContext {
     /// The upstream URL to proxy to
@@ -25,129 +67,595 @@ pub struct RequestContext {
     pub upstream_port: u16,
     pub upstream_path: String,
     pub use_tls: bool,
+    /// Presented to the backend when it requires mutual TLS
+    pub client_cert: Option<ClientCert>,
+    /// TLS SNI when it differs from `upstream_host`, e.g. connecting to a discovered IP
+    pub upstream_sni: Option<String>,
     pub preserve_host: bool,
     pub route_id: Option<Uuid>,
+    pub backend_service_id: Option<Uuid>,
+    /// Whether the upstream is a DNS SRV discovered instance, whose health the outcome feeds
+    pub discovered_instance: bool,
+    /// Metrics label of the matched route, e.g. `GET /api`
+    pub route_label: Option<String>,
+    /// Tag from the first matching metric tag rule, e.g. `admin`
+    pub metric_tag: Option<String>,
+    /// API version the request asked for, e.g. `2` for `/v2/users`
+    pub api_version: Option<String>,
+    /// Total and idle timeouts of the matched route
+    pub timeouts: RouteTimeouts,
+    /// Caps on the backend's response headers
+    pub header_limits: HeaderLimits,
+    /// When the request arrived at the gateway
+    pub started_at: Instant,
+    /// When bytes were last received from the upstream
+    pub last_read_at: Instant,
+    /// Who sent the request, resolved in `request_filter`
+    pub client: ClientInfo,
+    /// Correlation id sent upstream and echoed back, the client's or a generated one
+    pub request_id: Option<RequestId>,
+    /// When the request was last sent upstream
+    pub upstream_sent_at: Option<Instant>,
+    /// Whether the response gets `X-Upstream-Time-Ms` and `X-Gateway-Time-Ms`
+    pub timing_headers: bool,
+    /// Whether the upstream response is a long-lived stream
+    pub streaming: bool,
+    /// Method from `X-HTTP-Method-Override` that was used to match the route
+    pub method_override: Option<String>,
+    /// `(limit, remaining, reset_time)` of the most restrictive rate limit tier
+    pub rate_limit: Option<(i32, i32, u64)>,
+    /// Slot in the backend's `max_connections` cap, released when the request ends
+    pub backend_permit: Option<OwnedSemaphorePermit>,
+    /// Set when this request leads a coalesced flight and shares its response
+    pub coalesce: Option<coalesce::Leader>,
+    /// Whether the matched route has `debug_log_body`
+    pub debug_log_body: bool,
+    /// Request and response bodies collected for the debug log
+    pub request_body_log: Option<BodyCapture>,
+    pub response_body_log: Option<BodyCapture>,
+    /// Whether the request forced the canary backend of a blue/green route
+    pub canary: bool,
+    /// Whether the matched route has `decompress_response`
+    pub decompress_response: bool,
+    /// Decodes a compressed upstream body before it is forwarded
+    pub decompressor: Option<Decompressor>,
+    /// `Set-Cookie` rewriting of the matched route
+    pub cookie_rewrite: Option<CookieRewrite>,
+    /// Whether the request gets an access log line, off when the matched route silences it
+    pub access_log_enabled: bool,
+    /// Healthy fallback backends of the route still to try if connecting fails, in order
+    pub fallbacks: VecDeque<BackendService>,
+    /// Name of the backend service the request was routed to
+    pub backend_service_name: Option<String>,
+    /// Whether the response names that backend in `X-Backend-Service`
+    pub backend_service_header: bool,
+    /// Counts the request against its client IP's in-flight cap until it ends
+    pub client_slot: Option<ClientSlot>,
+}
+
+impl RequestContext {
+    /// Seed for weighted choices, the request id
+    ///
+    /// The id is set once when the request comes in, so retries and failover
+    /// of one request pick the same discovered instance.
+    fn selection_seed(&self) -> &str {
+        self.request_id.as_ref().map_or("", |id| id.value.as_str())
+    }
+
+    /// Name the backend that served the request, when the header is enabled
+    pub fn insert_backend_service_header(&self, resp: &mut pingora_http::ResponseHeader) {
+        if !self.backend_service_header {
+            return;
+        }
+        if let Some(name) = &self.backend_service_name {
+            resp.insert_header(BACKEND_SERVICE_HEADER, name).ok();
+        }
+    }
+
+    /// Build the upstream peer from the details captured in `request_filter`
+    ///
+    /// This deliberately reads only from the context, never from the live
+    /// config, so a reload that disables or removes the backend while the
+    /// request is in flight doesn't affect where it is sent. `addr` is
+    /// `upstream_host` resolved.
+    pub fn upstream_peer(&self, addr: SocketAddr) -> HttpPeer {
+        let sni = self
+            .upstream_sni
+            .clone()
+            .unwrap_or_else(|| self.upstream_host.clone());
+        let mut peer = HttpPeer::new(addr, self.use_tls, sni);
+
+        // Bound each upstream read; the total budget is enforced per body chunk
+        if let Some(options) = peer.get_mut_peer_options() {
+            options.read_timeout = self.timeouts.read_timeout();
+        }
+
+        // Configure TLS options for HTTPS backends
+        if self.use_tls {
+            if let Some(options) = peer.get_mut_peer_options() {
+                // Temporarily disable cert verification to test connection
+                // TODO: Re-enable with proper certificate configuration
+                options.verify_cert = false;
+                options.verify_hostname = false;
+            }
+            peer.client_cert_key = self.client_cert.as_ref().map(ClientCert::cert_key);
+        }
+
+        peer
+    }
 }
 
 /// Karateway proxy service
 pub struct KaratewayProxy {
     router: Router,
     rate_limiter: Option<Arc<RateLimiter>>,
+    /// Whether requests are let through when the rate limiter errors
+    rate_limit_failure_mode: FailureMode,
     health_checker: Arc<HealthChecker>,
     audit_logger: Arc<AuditLogger>,
+    /// Writes `gateway_metrics` rows, unless disabled
+    request_logger: Option<Arc<RequestLogger>>,
+    discovery: Arc<ServiceDiscovery>,
+    metrics: Arc<GatewayMetrics>,
+    concurrency: Arc<BackendConcurrency>,
+    /// Skips liveness pings to backends that just served a request
+    liveness: Arc<LivenessPings>,
+    /// Answers every request with a maintenance page, when enabled
+    maintenance: Arc<Maintenance>,
+    /// Requests in flight per client IP
+    client_requests: Arc<ClientCounter>,
+    /// Single-flight table for routes with `coalesce_requests`
+    coalescer: Arc<Coalescer>,
+    /// Ordered sources the client IP is resolved from
+    client_ip_sources: Vec<ClientIpSource>,
+    /// Headers a client's correlation id is read from, in order
+    request_id_headers: Vec<String>,
+    /// Ordered sources the API version is read from, empty when disabled
+    api_version_sources: Vec<VersionSource>,
+    /// Audits requests that match no route, when enabled
+    unmatched_audit: UnmatchedAudit,
+    /// Sent for requests that match no route
+    not_found: NotFoundResponse,
+    /// Global caps on upstream response headers, overridable per backend
+    header_limits: HeaderLimits,
+    /// Settings for routes with `debug_log_body`
+    body_logging: BodyLogging,
+    /// Size cap for routes with `decompress_response`
+    decompression: Decompression,
+    /// Add timing headers to every response, not just on routes with `timing_headers`
+    timing_headers: bool,
+    /// Name the routed backend in `X-Backend-Service` on every response
+    backend_service_header: bool,
+    /// Upstream request header carrying the matched route's id, `None` when off
+    route_id_header: Option<http::HeaderName>,
+    /// Who may override a route's total timeout per request
+    timeout_override: TimeoutOverride,
+    /// Who may force a blue/green route's canary per request
+    canary_header: CanaryHeader,
+    /// `Retry-After` for unhealthy services without a health check interval
+    unhealthy_retry_after_seconds: u64,
+    /// Hard cap on a request's whole time in the gateway
+    max_request_duration: MaxRequestDuration,
+    /// Looks up backend hostnames, retrying brief DNS failures
+    upstream_resolver: UpstreamResolver,
+}
+
+/// The parts of the gateway the proxy shares with its other services
+pub struct ProxyServices {
+    pub config_loader: Arc<ConfigLoader>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    pub health_checker: Arc<HealthChecker>,
+    pub audit_logger: Arc<AuditLogger>,
+    pub request_logger: Option<Arc<RequestLogger>>,
+    pub discovery: Arc<ServiceDiscovery>,
+    pub metrics: Arc<GatewayMetrics>,
+    pub concurrency: Arc<BackendConcurrency>,
+    pub liveness: Arc<LivenessPings>,
+    pub maintenance: Arc<Maintenance>,
+    pub client_requests: Arc<ClientCounter>,
+    pub upstream_resolver: UpstreamResolver,
 }
 
 impl KaratewayProxy {
-    pub fn new(
-        config_loader: Arc<ConfigLoader>,
-        rate_limiter: Option<Arc<RateLimiter>>,
-        health_checker: Arc<HealthChecker>,
-        audit_logger: Arc<AuditLogger>,
-    ) -> Self {
+    pub fn new(services: ProxyServices, config: &AppConfig) -> Self {
+        let ProxyServices {
+            config_loader,
+            rate_limiter,
+            health_checker,
+            audit_logger,
+            request_logger,
+            discovery,
+            metrics,
+            concurrency,
+            liveness,
+            maintenance,
+            client_requests,
+            upstream_resolver,
+        } = services;
+
+        let default_rate_limit = config.default_rate_limit();
+        if let Some(limit) = &default_rate_limit {
+            info!(
+                "Default rate limit enabled: {} requests per {}s by {}",
+                limit.max_requests, limit.window_seconds, limit.identifier_type
+            );
+        }
+
         Self {
-            router: Router::new(config_loader),
+            router: Router::new(
+                config_loader,
+                default_rate_limit,
+                config.gateway_method_override,
+                config.gateway_route_cache_size,
+            ),
             rate_limiter,
+            rate_limit_failure_mode: FailureMode::parse_or_default(&config.rate_limit_failure_mode),
             health_checker,
             audit_logger,
+            request_logger,
+            discovery,
+            metrics,
+            concurrency,
+            liveness,
+            maintenance,
+            client_requests,
+            coalescer: Arc::new(Coalescer::new()),
+            client_ip_sources: client_ip::parse_sources(&config.gateway_client_ip_sources),
+            request_id_headers: client_info::parse_request_id_headers(
+                &config.gateway_request_id_headers,
+            ),
+            api_version_sources: api_version::parse_sources(&config.gateway_api_version_sources),
+            not_found: NotFoundResponse::from_config(config),
+            unmatched_audit: UnmatchedAudit::new(
+                config.gateway_audit_unmatched_routes,
+                config.gateway_audit_unmatched_max_per_minute,
+            ),
+            header_limits: HeaderLimits {
+                max_bytes: config.gateway_max_response_header_bytes,
+                max_count: config.gateway_max_response_header_count,
+            },
+            body_logging: BodyLogging::from_config(config),
+            decompression: Decompression::from_config(config),
+            timing_headers: config.gateway_timing_headers,
+            backend_service_header: config.gateway_backend_service_header,
+            route_id_header: route_id_header(
+                config.gateway_route_id_header_enabled,
+                &config.gateway_route_id_header,
+            ),
+            timeout_override: TimeoutOverride::from_config(config),
+            canary_header: CanaryHeader::from_config(config),
+            unhealthy_retry_after_seconds: config.gateway_unhealthy_retry_after_seconds,
+            max_request_duration: MaxRequestDuration::from_config(config),
+            upstream_resolver,
         }
     }
 
-    /// Helper to extract client IP from session
-    fn get_client_ip(session: &Session) -> Option<String> {
-        session
-            .req_header()
-            .headers
-            .get("X-Forwarded-For")
-            .and_then(|h| h.to_str().ok())
-            .map(|s| s.split(',').next().unwrap_or("").trim().to_string())
-            .or_else(|| {
-                session.client_addr().map(|addr| {
-                    addr.as_inet()
-                        .map(|inet| inet.ip().to_string())
-                        .unwrap_or_else(|| addr.to_string())
-                })
-            })
-    }
+    /// Resolve who sent the request, using the configured client IP source precedence
+    fn client_info(&self, session: &Session) -> ClientInfo {
+        // Behind a PROXY protocol balancer, the peer is the client it reported
+        let peer_ip = match listener_guard::proxied_client() {
+            Some(client) => Some(client.ip().to_string()),
+            None => session.client_addr().map(|addr| {
+                // Extract just the IP address, not the port
+                addr.as_inet()
+                    .map(|inet| inet.ip().to_string())
+                    .unwrap_or_else(|| addr.to_string())
+            }),
+        };
 
-    /// Helper to extract user agent from session
-    fn get_user_agent(session: &Session) -> Option<String> {
-        session
-            .req_header()
-            .headers
-            .get("User-Agent")
-            .and_then(|h| h.to_str().ok())
-            .map(|s| s.to_string())
+        ClientInfo::from_request(
+            &session.req_header().headers,
+            peer_ip,
+            &self.client_ip_sources,
+            &self.request_id_headers,
+        )
     }
-}
 
-#[async_trait]
-impl ProxyHttp for KaratewayProxy {
-    type CTX = RequestContext;
+    /// Value a rate limit counts requests by
+    fn rate_limit_identifier(
+        headers: &http::HeaderMap,
+        client: &ClientInfo,
+        limit: &RateLimit,
+    ) -> String {
+        let header = |name: &str, missing: String| {
+            headers
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_string)
+                .unwrap_or(missing)
+        };
 
-    fn new_ctx(&self) -> Self::CTX {
-        RequestContext {
-            upstream_host: String::new(),
-            upstream_port: 80,
-            upstream_path: String::new(),
-            use_tls: false,
-            preserve_host: false,
-            route_id: None,
+        match limit.identifier_type {
+            IdentifierType::Ip => client.ip_or_unknown(),
+            // Get API key from header
+            IdentifierType::ApiKey => header("X-API-Key", "no-api-key".to_string()),
+            // Get user ID from header (JWT, session, etc.)
+            IdentifierType::UserId => header("X-User-ID", "no-user-id".to_string()),
+            IdentifierType::Global => {
+                // Global rate limit for all requests
+                "global".to_string()
+            }
+            // Requests without the header share one bucket, like those without an API key
+            IdentifierType::Header => {
+                let name = limit.identifier_header.as_deref().unwrap_or_default();
+                header(name, format!("no-{}", name.to_lowercase()))
+            }
         }
     }
 
-    async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool> {
+    /// Answer a request whose wait before the upstream ran past the maximum duration
+    async fn reject_over_deadline(&self, session: &mut Session) -> Result<bool> {
+        warn!(
+            "Request exceeded the maximum duration of {}ms before reaching the upstream: {} {}",
+            self.max_request_duration
+                .limit
+                .unwrap_or_default()
+                .as_millis(),
+            session.req_header().method,
+            session.req_header().uri.path()
+        );
+        let (resp, body_bytes) = deadline::exceeded_response()?;
+        session.write_response_header(Box::new(resp), false).await?;
+        session.write_response_body(Some(body_bytes), true).await?;
+
+        Ok(true) // Request handled
+    }
+
+    /// Match the request and run every check before it goes upstream
+    ///
+    /// Returns `true` when a response was already written. Only the waits
+    /// (rate limiter, coalesced leader, backend queue) run under the maximum
+    /// request duration, so the deadline never cuts off a response being written.
+    async fn filter_request(
+        &self,
+        session: &mut Session,
+        ctx: &mut RequestContext,
+    ) -> Result<bool> {
+        ctx.client = self.client_info(session);
+        // Reuse the client's id under its own header name, or start one under the first configured
+        ctx.request_id = Some(
+            ctx.client
+                .request_id
+                .clone()
+                .unwrap_or_else(|| RequestId::generate(&self.request_id_headers[0])),
+        );
+
         let req_header = session.req_header();
         let path = req_header.uri.path();
+        let query = req_header.uri.query();
         let method = req_header.method.as_str();
 
-        debug!("Incoming request: {} {}", method, path);
+        // Maintenance answers before anything else looks at the request
+        if self.maintenance.applies_to(path) {
+            debug!("Maintenance mode: answering {} {}", method, path);
+            let (resp, body_bytes) = self.maintenance.response()?;
+            session.write_response_header(Box::new(resp), false).await?;
+            session.write_response_body(Some(body_bytes), true).await?;
+            return Ok(true); // Request handled
+        }
 
-        // Find matching route and backend service
-        let (route, service) = match self.router.route_request(path, method) {
-            Some(result) => result,
+        // Keyed on the peer like the connection cap, headers can't spread a client over many IPs.
+        // Behind a load balancer that is the balancer unless it speaks the PROXY protocol
+        let peer_ip = ctx
+            .client
+            .peer_ip
+            .as_deref()
+            .and_then(|ip| ip.parse::<std::net::IpAddr>().ok());
+        match self.client_requests.admit(peer_ip) {
+            Some(slot) => ctx.client_slot = Some(slot),
             None => {
+                warn!(
+                    "Client {} has {} requests in flight, rejecting {} {}",
+                    ctx.client.peer_ip.as_deref().unwrap_or("unknown"),
+                    self.client_requests.max().unwrap_or_default(),
+                    method,
+                    path
+                );
+                let (resp, body_bytes) = ErrorCode::ClientRequestLimit
+                    .response("Too many concurrent requests from this client")?;
+                session.write_response_header(Box::new(resp), false).await?;
+                session.write_response_body(Some(body_bytes), true).await?;
+                return Ok(true); // Request handled
+            }
+        }
+
+        let expect_continue = match expect_continue::expectation(req_header) {
+            Expectation::None => false,
+            Expectation::Continue => true,
+            Expectation::Unsupported(expectation) => {
+                debug!(
+                    "Unsupported expectation {:?} for {} {}",
+                    expectation, method, path
+                );
+                let (resp, body_bytes) = expect_continue::expectation_failed_response()?;
+                session.write_response_header(Box::new(resp), false).await?;
+                session.write_response_body(Some(body_bytes), true).await?;
+                return Ok(true); // Request handled
+            }
+        };
+
+        debug!(
+            "Incoming request: {} {} (client_ip={:?}, request_id={:?}, forwarded_for={:?})",
+            method, path, ctx.client.ip, ctx.request_id, ctx.client.forwarded_for
+        );
+
+        // Tag before matching so unmatched requests are broken down too
+        ctx.metric_tag = self.router.metric_tag(path, &req_header.headers);
+
+        let override_method = method_override::requested_method(&req_header.headers, method);
+        let content_type = req_header
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        ctx.api_version =
+            api_version::extract(&self.api_version_sources, path, &req_header.headers);
+
+        // Find matching route and backend service
+        let (route, service) = match self.router.route_request(
+            path,
+            method,
+            query,
+            content_type,
+            ctx.api_version.as_deref(),
+            override_method.as_deref(),
+        ) {
+            Ok(result) => result,
+            Err(RouteMiss::NoBackend {
+                route_id,
+                backend_service_id,
+            }) => {
+                // The route exists, its backend doesn't; a 404 would hide that
+                warn!(
+                    "Route {} matched {} {}, but its backend service {} is unavailable",
+                    route_id, method, path, backend_service_id
+                );
+
+                if self.audit_logger.is_enabled(&AuditEventCategory::Admin) {
+                    let audit_log = AuditLogBuilder::new(
+                        AuditEventType::BackendError,
+                        AuditEventCategory::Admin,
+                        AuditSeverity::Warning,
+                        format!(
+                            "No active backend service for {} {}: backend service {} is disabled or removed",
+                            method, path, backend_service_id
+                        ),
+                    )
+                    .request_method(method)
+                    .request_path(path)
+                    .client_ip(ctx.client.ip_or_unknown())
+                    .user_agent(ctx.client.user_agent.clone().unwrap_or_default())
+                    .api_route_id(route_id)
+                    .backend_service_id(backend_service_id)
+                    .metadata(ErrorCode::NoBackend.metadata())
+                    .status_code(503)
+                    .build();
+
+                    self.audit_logger.log(audit_log);
+                }
+
+                let (resp, body_bytes) = no_backend_response(self.unhealthy_retry_after_seconds)?;
+                session.write_response_header(Box::new(resp), false).await?;
+                session.write_response_body(Some(body_bytes), true).await?;
+
+                return Ok(true); // Request handled
+            }
+            Err(RouteMiss::NoRoute) => {
                 warn!("No route found for {} {}", method, path);
 
-                // Send 404 response
-                let mut resp = pingora_http::ResponseHeader::build(404, None)?;
-                resp.insert_header("Content-Length", "9")?;
+                let client_ip = ctx.client.ip_or_unknown();
+                // Checked first, so a disabled category doesn't use up the budget
+                if self.audit_logger.is_enabled(&AuditEventCategory::Admin)
+                    && self.unmatched_audit.should_log(&client_ip, Instant::now())
+                {
+                    let audit_log = AuditLogBuilder::new(
+                        AuditEventType::InvalidRequest,
+                        AuditEventCategory::Admin,
+                        AuditSeverity::Info,
+                        format!("No route found for {} {}", method, path),
+                    )
+                    .request_method(method)
+                    .request_path(path)
+                    .client_ip(client_ip)
+                    .user_agent(ctx.client.user_agent.clone().unwrap_or_default())
+                    .metadata(ErrorCode::RouteNotFound.metadata())
+                    .status_code(self.not_found.status)
+                    .build();
+
+                    self.audit_logger.log(audit_log);
+                }
+
+                let (resp, body_bytes) = self.not_found.response()?;
                 session.write_response_header(Box::new(resp), false).await?;
-                session
-                    .write_response_body(Some(b"Not Found".as_ref().into()), true)
-                    .await?;
+                session.write_response_body(Some(body_bytes), true).await?;
 
                 return Ok(true); // Request handled
             }
         };
 
+        // Trusted testers can opt into the canary regardless of the active color
+        let canary = self
+            .canary_header
+            .forces_canary(&session.req_header().headers, ctx.client.peer_ip.as_deref())
+            .then(|| self.router.canary_service(&route))
+            .flatten();
+        let service = match canary {
+            Some(canary) => {
+                debug!(
+                    "Canary requested for route {}: {} instead of {}",
+                    route.id, canary.name, service.name
+                );
+                ctx.canary = true;
+                canary
+            }
+            None => service,
+        };
+
+        // Canary requests stay on the canary; the rest leave a primary that is down
+        let (service, fallbacks) = if ctx.canary {
+            (service, VecDeque::new())
+        } else {
+            let primary_id = service.id;
+            let (chosen, fallbacks) =
+                failover::choose(service, self.router.fallback_services(&route), |id| {
+                    self.health_checker.is_healthy(id)
+                });
+            if chosen.id != primary_id {
+                warn!(
+                    "Backend service {} of route {} is unhealthy, failing over to {}",
+                    primary_id, route.id, chosen.name
+                );
+            }
+            (chosen, fallbacks)
+        };
+        ctx.fallbacks = fallbacks;
+
+        // A route with a different method was only matched through the override
+        if route.method.to_string() != method.to_uppercase() {
+            debug!("Method overridden: {} -> {}", method, route.method);
+            ctx.method_override = Some(route.method.to_string());
+        }
+
         // Store route ID in context
         ctx.route_id = Some(route.id);
+        ctx.backend_service_id = Some(service.id);
+        ctx.route_label = Some(format!("{} {}", route.method, route.path_pattern));
+        ctx.timeouts = RouteTimeouts::from_route(&route);
+        if let Some(total) = self
+            .timeout_override
+            .requested(&session.req_header().headers, ctx.client.peer_ip.as_deref())
+        {
+            debug!(
+                "Overriding total timeout of route {} with {}ms",
+                route.id,
+                total.as_millis()
+            );
+            ctx.timeouts.total = Some(total);
+        }
+        self.max_request_duration.limit_timeouts(&mut ctx.timeouts);
+        ctx.debug_log_body = route.debug_log_body;
+        ctx.decompress_response = route.decompress_response;
+        ctx.cookie_rewrite = CookieRewrite::from_route(&route);
+        ctx.access_log_enabled = route.access_log_enabled;
+        ctx.timing_headers = self.timing_headers || route.timing_headers;
+        ctx.backend_service_header = self.backend_service_header;
+        ctx.request_body_log = self
+            .body_logging
+            .capture(route.debug_log_body, content_type);
 
         // Check whitelist rules
-        if let Some(whitelist_rules) = self.router.get_whitelist_rules(&route.id) {
+        let whitelist_rules = self.router.get_whitelist_rules(&route.id);
+        if let Some(whitelist_rules) = &whitelist_rules {
             debug!(
                 "Whitelist rules are configured, checking {} rules for route {}",
                 whitelist_rules.len(),
                 route.id
             );
 
-            // Get client IP for validation
-            let client_ip = session
-                .req_header()
-                .headers
-                .get("X-Forwarded-For")
-                .and_then(|h| h.to_str().ok())
-                .map(|s| s.split(',').next().unwrap_or("").trim().to_string())
-                .or_else(|| {
-                    session.client_addr().map(|addr| {
-                        addr.as_inet()
-                            .map(|inet| inet.ip().to_string())
-                            .unwrap_or_else(|| addr.to_string())
-                    })
-                });
+            let client_ip = ctx.client.ip.clone();
 
             let (allowed, matching_rule) = WhitelistValidator::validate_request(
-                &whitelist_rules,
+                whitelist_rules,
+                &self.router.get_custom_rule_conditions(),
                 session.req_header(),
                 client_ip.as_deref(),
             );
@@ -158,31 +666,29 @@ impl ProxyHttp for KaratewayProxy {
                     route.path_pattern, path, method, client_ip
                 );
 
-                // Log audit event for whitelist denial
-                let audit_log = AuditLogBuilder::new(
-                    AuditEventType::WhitelistDenied,
-                    AuditEventCategory::Whitelist,
-                    AuditSeverity::Warning,
-                    format!("Access denied by whitelist rules for {} {}", method, path),
-                )
-                .request_method(method)
-                .request_path(path)
-                .client_ip(client_ip.as_deref().unwrap_or("unknown"))
-                .user_agent(Self::get_user_agent(session).unwrap_or_default())
-                .api_route_id(route.id)
-                .status_code(403)
-                .build();
+                if self.audit_logger.is_enabled(&AuditEventCategory::Whitelist) {
+                    // Log audit event for whitelist denial
+                    let audit_log = AuditLogBuilder::new(
+                        AuditEventType::WhitelistDenied,
+                        AuditEventCategory::Whitelist,
+                        AuditSeverity::Warning,
+                        format!("Access denied by whitelist rules for {} {}", method, path),
+                    )
+                    .request_method(method)
+                    .request_path(path)
+                    .client_ip(ctx.client.ip_or_unknown())
+                    .user_agent(ctx.client.user_agent.clone().unwrap_or_default())
+                    .api_route_id(route.id)
+                    .metadata(ErrorCode::WhitelistDenied.metadata())
+                    .status_code(403)
+                    .build();
 
-                self.audit_logger.log(audit_log);
+                    self.audit_logger.log(audit_log);
+                }
 
                 // Send 403 Forbidden response
-                let mut resp = pingora_http::ResponseHeader::build(403, None)?;
-                resp.insert_header("Content-Type", "application/json")?;
-
-                let body = r#"{"error":"Forbidden","message":"Access denied by whitelist rules"}"#;
-                let body_bytes = Bytes::from(body);
-
-                resp.insert_header("Content-Length", &body_bytes.len().to_string())?;
+                let (resp, body_bytes) =
+                    ErrorCode::WhitelistDenied.response("Access denied by whitelist rules")?;
                 session.write_response_header(Box::new(resp), false).await?;
                 session.write_response_body(Some(body_bytes), true).await?;
 
@@ -207,15 +713,8 @@ impl ProxyHttp for KaratewayProxy {
             );
 
             // Send 503 Service Unavailable response
-            let mut resp = pingora_http::ResponseHeader::build(503, None)?;
-            resp.insert_header("Content-Type", "application/json")?;
-            let body = format!(
-                r#"{{"error":"Service Unavailable","message":"Backend service {} is currently unhealthy"}}"#,
-                service.name
-            );
-            let body_bytes = Bytes::from(body);
-
-            resp.insert_header("Content-Length", &body_bytes.len().to_string())?;
+            let (resp, body_bytes) =
+                unhealthy_response(&service, self.unhealthy_retry_after_seconds)?;
             session.write_response_header(Box::new(resp), false).await?;
             session.write_response_body(Some(body_bytes), true).await?;
 
@@ -228,183 +727,294 @@ impl ProxyHttp for KaratewayProxy {
                 "Rate limiter is configured, checking rate limits for route {}",
                 route.id
             );
-            if let Some(rate_limits) = self.router.get_rate_limits(&route.id) {
+            if let Some(mut rate_limits) = self.router.get_rate_limits(&route.id) {
                 debug!("Found {} rate limits to check", rate_limits.len());
-                for limit in rate_limits {
-                    debug!("Checking rate limit: {}", limit.name);
-                    // Get identifier for rate limiting
-                    let identifier = match limit.identifier_type {
-                        IdentifierType::Ip => {
-                            // Get client IP from headers or peer address
-                            session
-                                .req_header()
-                                .headers
-                                .get("X-Forwarded-For")
-                                .and_then(|h| h.to_str().ok())
-                                .map(|s| s.split(',').next().unwrap_or("").trim().to_string())
-                                .or_else(|| {
-                                    // Extract just the IP address, not the port
-                                    session.client_addr().map(|addr| {
-                                        addr.as_inet()
-                                            .map(|inet| inet.ip().to_string())
-                                            .unwrap_or_else(|| addr.to_string())
-                                    })
-                                })
-                                .unwrap_or_else(|| "unknown".to_string())
-                        }
-                        IdentifierType::ApiKey => {
-                            // Get API key from header
-                            session
-                                .req_header()
-                                .headers
-                                .get("X-API-Key")
-                                .and_then(|h| h.to_str().ok())
-                                .unwrap_or("no-api-key")
-                                .to_string()
-                        }
-                        IdentifierType::UserId => {
-                            // Get user ID from header (JWT, session, etc.)
-                            session
-                                .req_header()
-                                .headers
-                                .get("X-User-ID")
-                                .and_then(|h| h.to_str().ok())
-                                .unwrap_or("no-user-id")
-                                .to_string()
+                // Capacity-scaled limits follow the backend's current capacity
+                for limit in &mut rate_limits {
+                    limit.max_requests = limit.effective_max_requests(service.capacity);
+                }
+                // Layered limits (e.g. 1000/hour AND 50/minute): all must pass
+                order_tiers(&mut rate_limits);
+                let identifiers: Vec<String> = rate_limits
+                    .iter()
+                    .map(|limit| {
+                        Self::rate_limit_identifier(
+                            &session.req_header().headers,
+                            &ctx.client,
+                            limit,
+                        )
+                    })
+                    .collect();
+                let tiers: Vec<Tier> = rate_limits
+                    .iter()
+                    .zip(&identifiers)
+                    .map(|(limit, identifier)| Tier::new(route.id, limit, identifier))
+                    .collect();
+
+                let cost = rate_limiter::request_cost(&route, &session.req_header().headers);
+
+                let checked = self
+                    .max_request_duration
+                    .run(ctx.started_at, rate_limiter.check_tiers(&tiers, cost))
+                    .await;
+                let Some(checked) = checked else {
+                    return self.reject_over_deadline(session).await;
+                };
+                let outcome = match checked {
+                    Ok(outcome) => Some(outcome),
+                    Err(e) => match self.rate_limit_failure_mode.error_response() {
+                        None => {
+                            warn!("Rate limiter error, allowing request (fail-open): {}", e);
+                            None
                         }
-                        IdentifierType::Global => {
-                            // Global rate limit for all requests
-                            "global".to_string()
+                        Some((status, body)) => {
+                            warn!("Rate limiter error, rejecting request (fail-closed): {}", e);
+
+                            let mut resp = pingora_http::ResponseHeader::build(status, None)?;
+                            resp.insert_header("Content-Type", "application/json")?;
+                            resp.insert_header("Retry-After", "1")?;
+                            let body_bytes = Bytes::from(body);
+
+                            resp.insert_header("Content-Length", &body_bytes.len().to_string())?;
+                            session.write_response_header(Box::new(resp), false).await?;
+                            session.write_response_body(Some(body_bytes), true).await?;
+
+                            return Ok(true); // Request handled
                         }
-                    };
+                    },
+                };
 
-                    let rate_limit_key =
-                        format!("{}:{}:{}", route.id, limit.identifier_type, identifier);
-
-                    // Check rate limit
-                    let (allowed, remaining, reset_time) = if let Some(burst) = limit.burst_size {
-                        rate_limiter
-                            .check_rate_limit_with_burst(
-                                &rate_limit_key,
-                                limit.max_requests,
-                                limit.window_seconds,
-                                burst,
-                            )
-                            .await
-                    } else {
-                        rate_limiter
-                            .check_rate_limit(
-                                &rate_limit_key,
-                                limit.max_requests,
-                                limit.window_seconds,
-                            )
-                            .await
-                    }
-                    .map_err(|e| {
-                        warn!("Rate limiter error: {}", e);
-                        pingora_core::Error::because(
-                            pingora_core::ErrorType::InternalError,
-                            "Rate limiter error",
-                            e,
-                        )
-                    })?;
+                match outcome {
+                    Some(TierOutcome::Rejected(status)) => {
+                        let limit = &rate_limits[status.index];
+                        let identifier = &identifiers[status.index];
+                        let reset_time = status.reset_time;
 
-                    if !allowed {
                         info!(
                             "Rate limit exceeded: route={}, identifier_type={}, identifier={}, limit={}",
                             route.path_pattern, limit.identifier_type, identifier, limit.name
                         );
 
-                        // Log audit event for rate limit exceeded
-                        let mut metadata = serde_json::Map::new();
-                        metadata.insert(
-                            "limit_name".to_string(),
-                            serde_json::Value::String(limit.name.clone()),
-                        );
-                        metadata.insert(
-                            "identifier_type".to_string(),
-                            serde_json::Value::String(limit.identifier_type.to_string()),
-                        );
-                        metadata.insert(
-                            "identifier".to_string(),
-                            serde_json::Value::String(identifier.clone()),
-                        );
-                        metadata.insert(
-                            "max_requests".to_string(),
-                            serde_json::Value::Number(limit.max_requests.into()),
-                        );
-                        metadata.insert(
-                            "window_seconds".to_string(),
-                            serde_json::Value::Number(limit.window_seconds.into()),
-                        );
+                        if self.audit_logger.is_enabled(&AuditEventCategory::RateLimit) {
+                            // Log audit event for rate limit exceeded
+                            let mut metadata = serde_json::Map::new();
+                            metadata.insert(
+                                "limit_name".to_string(),
+                                serde_json::Value::String(limit.name.clone()),
+                            );
+                            metadata.insert(
+                                "identifier_type".to_string(),
+                                serde_json::Value::String(limit.identifier_type.to_string()),
+                            );
+                            metadata.insert(
+                                "identifier".to_string(),
+                                serde_json::Value::String(identifier.clone()),
+                            );
+                            metadata.insert(
+                                "max_requests".to_string(),
+                                serde_json::Value::Number(limit.max_requests.into()),
+                            );
+                            metadata.insert(
+                                "window_seconds".to_string(),
+                                serde_json::Value::Number(limit.window_seconds.into()),
+                            );
+                            metadata.insert(
+                                "request_cost".to_string(),
+                                serde_json::Value::Number(cost.into()),
+                            );
+                            metadata.insert(
+                                "error_code".to_string(),
+                                serde_json::Value::String(ErrorCode::RateLimited.to_string()),
+                            );
 
-                        let audit_log = AuditLogBuilder::new(
-                            AuditEventType::RateLimitExceeded,
-                            AuditEventCategory::RateLimit,
-                            AuditSeverity::Warning,
-                            format!(
-                                "Rate limit '{}' exceeded for {} {} (identifier: {})",
-                                limit.name, method, path, identifier
-                            ),
-                        )
-                        .request_method(method)
-                        .request_path(path)
-                        .client_ip(Self::get_client_ip(session).unwrap_or_default())
-                        .user_agent(Self::get_user_agent(session).unwrap_or_default())
-                        .api_route_id(route.id)
-                        .metadata(serde_json::Value::Object(metadata))
-                        .status_code(429)
-                        .build();
+                            let audit_log = AuditLogBuilder::new(
+                                AuditEventType::RateLimitExceeded,
+                                AuditEventCategory::RateLimit,
+                                AuditSeverity::Warning,
+                                format!(
+                                    "Rate limit '{}' exceeded for {} {} (identifier: {})",
+                                    limit.name, method, path, identifier
+                                ),
+                            )
+                            .request_method(method)
+                            .request_path(path)
+                            .client_ip(ctx.client.ip.clone().unwrap_or_default())
+                            .user_agent(ctx.client.user_agent.clone().unwrap_or_default())
+                            .api_route_id(route.id)
+                            .metadata(serde_json::Value::Object(metadata))
+                            .status_code(429)
+                            .build();
 
-                        self.audit_logger.log(audit_log);
+                            self.audit_logger.log(audit_log);
+                        }
 
                         // Rate limit exceeded - return 429
-                        let mut resp = pingora_http::ResponseHeader::build(429, None)?;
-                        resp.insert_header("Content-Type", "application/json")?;
+                        let code = ErrorCode::RateLimited;
+                        let body = serde_json::json!({
+                            "error": code.title(),
+                            "error_code": code.as_str(),
+                            "retry_after": limit.window_seconds,
+                            "limit": limit.name,
+                        });
+                        let (mut resp, body_bytes) =
+                            error_code::json_response(code.status(), &body)?;
                         resp.insert_header("X-RateLimit-Limit", &limit.max_requests.to_string())?;
                         resp.insert_header("X-RateLimit-Remaining", "0")?;
                         resp.insert_header("X-RateLimit-Reset", &reset_time.to_string())?;
                         resp.insert_header("Retry-After", &limit.window_seconds.to_string())?;
 
-                        let body = format!(
-                            r#"{{"error":"Rate limit exceeded","retry_after":{},"limit":"{}"}}"#,
-                            limit.window_seconds, limit.name
-                        );
-                        let body_bytes = Bytes::from(body);
-
-                        resp.insert_header("Content-Length", &body_bytes.len().to_string())?;
                         session.write_response_header(Box::new(resp), false).await?;
                         session.write_response_body(Some(body_bytes), true).await?;
 
                         return Ok(true); // Request handled
-                    } else {
-                        // Add rate limit headers to response (will be added in response_filter)
-                        debug!(
-                            "Rate limit check passed: remaining={}, reset_in={}s",
-                            remaining,
-                            reset_time.saturating_sub(
-                                std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_secs()
-                            )
-                        );
                     }
+                    Some(TierOutcome::Allowed(status)) => {
+                        if let Some(status) = status {
+                            debug!(
+                                "Rate limit check passed: remaining={}, reset_at={}",
+                                status.remaining, status.reset_time
+                            );
+                            ctx.rate_limit = Some((
+                                rate_limits[status.index].max_requests,
+                                status.remaining,
+                                status.reset_time,
+                            ));
+                        }
+                    }
+                    None => {}
                 }
             }
         }
 
-        // Parse backend URL
-        let backend_url = url::Url::parse(&service.base_url).map_err(|e| {
-            pingora_core::Error::because(
-                pingora_core::ErrorType::InternalError,
-                format!("Invalid backend URL: {}", e),
-                e,
+        // Identical in-flight requests wait for the first one's response
+        // Canary requests go to another backend, so they never share a response
+        if route.coalesce_requests && !ctx.canary {
+            let path_and_query = req_header
+                .uri
+                .path_and_query()
+                .map(|pq| pq.as_str())
+                .unwrap_or(path);
+            // Whatever the whitelist decided on may also change the response
+            let vary_headers = whitelist_rules
+                .as_deref()
+                .map(|rules| {
+                    WhitelistValidator::header_names(
+                        rules,
+                        &self.router.get_custom_rule_conditions(),
+                    )
+                })
+                .unwrap_or_default();
+            let key = coalesce::request_key(
+                route.id,
+                method,
+                path_and_query,
+                &req_header.headers,
+                &vary_headers,
+            );
+
+            match key.and_then(|key| self.coalescer.join(key)) {
+                Some(Role::Leader(leader)) => ctx.coalesce = Some(leader),
+                Some(Role::Follower(rx)) => {
+                    let wait = ctx.timeouts.total.unwrap_or(coalesce::DEFAULT_WAIT);
+                    let shared = self
+                        .max_request_duration
+                        .run(ctx.started_at, coalesce::wait_for(rx, wait))
+                        .await;
+                    match shared {
+                        None => return self.reject_over_deadline(session).await,
+                        Some(Some(shared)) => {
+                            debug!("Coalesced {} {} into an in-flight request", method, path);
+                            Self::write_shared_response(session, ctx, &shared).await?;
+                            return Ok(true); // Request handled
+                        }
+                        // The leader gave up; go upstream on our own
+                        Some(None) => {}
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // Enforce the backend's concurrency cap across all routes, queueing
+        // briefly if the route allows it
+        let max_connections = service.max_connections.map(|max| max.max(0) as u32);
+        let queue = QueuePolicy::from_route(&route);
+        let acquired = self
+            .max_request_duration
+            .run(
+                ctx.started_at,
+                self.concurrency.acquire(service.id, max_connections, queue),
             )
-        })?;
+            .await;
+        let Some(acquired) = acquired else {
+            return self.reject_over_deadline(session).await;
+        };
+        match acquired {
+            Ok(permit) => ctx.backend_permit = permit,
+            Err(rejection) => {
+                warn!(
+                    "Backend {} is at its limit of {} concurrent connections ({:?})",
+                    service.name,
+                    max_connections.unwrap_or_default(),
+                    rejection
+                );
+
+                let (mut resp, body_bytes) =
+                    ErrorCode::BackendAtCapacity.response("Backend is at capacity")?;
+                if rejection.is_retryable() {
+                    resp.insert_header("Retry-After", "1")?;
+                }
+
+                session.write_response_header(Box::new(resp), false).await?;
+                session.write_response_body(Some(body_bytes), true).await?;
+
+                return Ok(true); // Request handled
+            }
+        }
+
+        // Parse backend URL; bad ones are filtered at load time, this is a safety net
+        let target = match UpstreamTarget::parse(&service.base_url) {
+            Ok(target) => target,
+            Err(e) => {
+                warn!(
+                    "Backend service {} ({}) has an unusable base_url ({}), returning 502",
+                    service.name, service.id, e
+                );
+
+                if self.audit_logger.is_enabled(&AuditEventCategory::Admin) {
+                    let audit_log = AuditLogBuilder::new(
+                        AuditEventType::BackendError,
+                        AuditEventCategory::Admin,
+                        AuditSeverity::Warning,
+                        format!(
+                            "Invalid base_url for backend service {}: {}",
+                            service.name, e
+                        ),
+                    )
+                    .request_method(method)
+                    .request_path(path)
+                    .client_ip(ctx.client.ip_or_unknown())
+                    .api_route_id(route.id)
+                    .backend_service_id(service.id)
+                    .metadata(ErrorCode::BackendMisconfigured.metadata())
+                    .status_code(502)
+                    .build();
+
+                    self.audit_logger.log(audit_log);
+                }
+
+                // The URL itself stays out of the response
+                let (resp, body) =
+                    ErrorCode::BackendMisconfigured.response("Backend service is misconfigured")?;
+                session.write_response_header(Box::new(resp), false).await?;
+                session.write_response_body(Some(body), true).await?;
+
+                return Ok(true); // Request handled
+            }
+        };
 
         // Transform path if needed
-        let transformed_path = self.router.transform_path(&route, path);
+        let transformed_path = Router::transform_path(&route, path, self.router.path_case());
 
         // Build query string
         let query = req_header
@@ -415,18 +1025,14 @@ impl ProxyHttp for KaratewayProxy {
         let full_path = format!("{}{}", transformed_path, query);
 
         // Store upstream information in context
-        ctx.upstream_host = backend_url.host_str().unwrap_or("localhost").to_string();
-        ctx.upstream_port = backend_url
-            .port()
-            .unwrap_or(if backend_url.scheme() == "https" {
-                443
-            } else {
-                80
-            });
         ctx.upstream_path = full_path;
-        ctx.use_tls = backend_url.scheme() == "https";
+        self.use_backend(ctx, &service, target);
         ctx.preserve_host = route.preserve_host_header;
 
+        if service.liveness_ping_interval_seconds.is_some() {
+            self.liveness.touch(service.id, Instant::now());
+        }
+
         debug!(
             "Route config: preserve_host_header={}, route_id={}",
             route.preserve_host_header, route.id
@@ -437,30 +1043,206 @@ impl ProxyHttp for KaratewayProxy {
             ctx.upstream_host, ctx.upstream_port, ctx.upstream_path, ctx.use_tls
         );
 
+        // Every check passed, so a client holding its body back can send it now
+        if expect_continue {
+            expect_continue::send_continue(session).await?;
+        }
+
         Ok(false) // Continue to upstream
     }
 
+    /// Send the request to `service` at `target`, or to one of its discovered instances
+    fn use_backend(
+        &self,
+        ctx: &mut RequestContext,
+        service: &BackendService,
+        target: UpstreamTarget,
+    ) {
+        ctx.backend_service_id = Some(service.id);
+        ctx.backend_service_name = Some(service.name.clone());
+        ctx.client_cert = self.router.get_client_cert(&service.id);
+        ctx.upstream_sni = service.tls_sni.clone();
+        ctx.header_limits = self.header_limits.for_service(service);
+        ctx.upstream_host = target.host;
+        ctx.upstream_port = target.port;
+        ctx.use_tls = target.use_tls;
+        ctx.discovered_instance = false;
+
+        // Prefer a DNS SRV discovered instance, keeping base_url as the fallback
+        if let Some(instance) = self
+            .discovery
+            .pick_instance(&service.id, ctx.selection_seed())
+        {
+            ctx.upstream_host = instance.host;
+            ctx.upstream_port = instance.port;
+            ctx.discovered_instance = true;
+        }
+    }
+
+    /// Move a request whose backend couldn't be reached to the next fallback that has room
+    ///
+    /// Returns the fallback it now goes to, `None` when there is none left.
+    fn fail_over(&self, ctx: &mut RequestContext) -> Option<BackendService> {
+        while let Some(fallback) = ctx.fallbacks.pop_front() {
+            let Ok(target) = UpstreamTarget::parse(&fallback.base_url) else {
+                continue;
+            };
+            let max_connections = fallback.max_connections.map(|max| max.max(0) as u32);
+            let Ok(permit) = self.concurrency.try_acquire(fallback.id, max_connections) else {
+                continue;
+            };
+
+            ctx.backend_permit = permit;
+            self.use_backend(ctx, &fallback, target);
+            return Some(fallback);
+        }
+        None
+    }
+
+    /// Replay a coalesced response to a follower
+    async fn write_shared_response(
+        session: &mut Session,
+        ctx: &RequestContext,
+        shared: &SharedResponse,
+    ) -> Result<()> {
+        // The body is sent in one piece, so it is re-framed; HEAD keeps the upstream's length
+        let head = session.req_header().method == http::Method::HEAD;
+
+        let mut resp = pingora_http::ResponseHeader::build(shared.status, None)?;
+        for (name, value) in &shared.headers {
+            let framing =
+                name == http::header::CONTENT_LENGTH || name == http::header::TRANSFER_ENCODING;
+            if head || !framing {
+                resp.append_header(name.clone(), value.clone())?;
+            }
+        }
+        if !head {
+            resp.insert_header("Content-Length", shared.body.len().to_string())?;
+        }
+        resp.insert_header("X-Powered-By", "Karateway")?;
+        if let Some(request_id) = &ctx.request_id {
+            resp.insert_header(request_id.header.clone(), &request_id.value)?;
+        }
+        ctx.insert_backend_service_header(&mut resp);
+
+        if let Some((limit, remaining, reset_time)) = ctx.rate_limit {
+            resp.insert_header("X-RateLimit-Limit", limit.to_string())?;
+            resp.insert_header("X-RateLimit-Remaining", remaining.to_string())?;
+            resp.insert_header("X-RateLimit-Reset", reset_time.to_string())?;
+        }
+
+        session.write_response_header(Box::new(resp), false).await?;
+        session
+            .write_response_body(Some(shared.body.clone()), true)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProxyHttp for KaratewayProxy {
+    type CTX = RequestContext;
+
+    fn new_ctx(&self) -> Self::CTX {
+        RequestContext {
+            upstream_host: String::new(),
+            upstream_port: 80,
+            upstream_path: String::new(),
+            use_tls: false,
+            client_cert: None,
+            upstream_sni: None,
+            preserve_host: false,
+            route_id: None,
+            backend_service_id: None,
+            discovered_instance: false,
+            route_label: None,
+            metric_tag: None,
+            api_version: None,
+            timeouts: RouteTimeouts::default(),
+            header_limits: self.header_limits,
+            started_at: Instant::now(),
+            last_read_at: Instant::now(),
+            client: ClientInfo::default(),
+            request_id: None,
+            upstream_sent_at: None,
+            timing_headers: false,
+            streaming: false,
+            method_override: None,
+            rate_limit: None,
+            backend_permit: None,
+            coalesce: None,
+            debug_log_body: false,
+            request_body_log: None,
+            response_body_log: None,
+            canary: false,
+            decompress_response: false,
+            decompressor: None,
+            cookie_rewrite: None,
+            access_log_enabled: true,
+            fallbacks: VecDeque::new(),
+            backend_service_name: None,
+            backend_service_header: false,
+            client_slot: None,
+        }
+    }
+
+    async fn early_request_filter(
+        &self,
+        _session: &mut Session,
+        _ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        // The request head is in, so the listener's header read deadline no longer applies
+        listener_guard::header_read();
+
+        Ok(())
+    }
+
+    async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool> {
+        self.filter_request(session, ctx).await
+    }
+
     async fn upstream_peer(
         &self,
         _session: &mut Session,
         ctx: &mut Self::CTX,
     ) -> Result<Box<HttpPeer>> {
-        let mut peer = HttpPeer::new(
-            (&ctx.upstream_host as &str, ctx.upstream_port),
-            ctx.use_tls,
-            ctx.upstream_host.clone(),
-        );
-
-        // Configure TLS options for HTTPS backends
-        if ctx.use_tls {
-            if let Some(options) = peer.get_mut_peer_options() {
-                // Temporarily disable cert verification to test connection
-                // TODO: Re-enable with proper certificate configuration
-                options.verify_cert = false;
-                options.verify_hostname = false;
-            }
+        let now = Instant::now();
+        // Also reached on retries, which may start after the deadline
+        if self.max_request_duration.exceeded(ctx.started_at, now) {
+            warn!(
+                "Request exceeded the maximum duration before connecting to {}:{}",
+                ctx.upstream_host, ctx.upstream_port
+            );
+            return Err(pingora_core::Error::explain(
+                pingora_core::ErrorType::HTTPStatus(504),
+                "Maximum request duration exceeded",
+            ));
         }
 
+        // Resolving counts against the route's total timeout like everything else
+        let time_left = ctx
+            .timeouts
+            .total
+            .map(|total| total.saturating_sub(now.saturating_duration_since(ctx.started_at)));
+        let addr = self
+            .upstream_resolver
+            .resolve(&ctx.upstream_host, ctx.upstream_port, time_left)
+            .await
+            .map_err(|e| {
+                warn!("{:#}", e);
+                // An upstream failure, reported like a refused connection
+                pingora_core::Error::explain(
+                    pingora_core::ErrorType::ConnectError,
+                    format!("{:#}", e),
+                )
+                .into_up()
+            })?;
+        let mut peer = ctx.upstream_peer(addr);
+        // The lookup may have taken part of what is left
+        self.max_request_duration
+            .limit_peer(&mut peer, ctx.started_at, Instant::now());
+
         debug!(
             "Created upstream peer: {}:{} (TLS: {})",
             ctx.upstream_host, ctx.upstream_port, ctx.use_tls
@@ -469,12 +1251,39 @@ impl ProxyHttp for KaratewayProxy {
         Ok(Box::new(peer))
     }
 
-    async fn upstream_request_filter(
+    fn fail_to_connect(
         &self,
         _session: &mut Session,
+        peer: &HttpPeer,
+        ctx: &mut Self::CTX,
+        mut e: Box<pingora_core::Error>,
+    ) -> Box<pingora_core::Error> {
+        // Nothing reached the backend yet, so the next fallback can take the request
+        if let Some(fallback) = self.fail_over(ctx) {
+            warn!(
+                "Failed to connect to {}, failing over to {}: {}",
+                peer.address(),
+                fallback.name,
+                e
+            );
+            e.set_retry(true);
+        }
+        e
+    }
+
+    async fn upstream_request_filter(
+        &self,
+        session: &mut Session,
         upstream_request: &mut RequestHeader,
         ctx: &mut Self::CTX,
     ) -> Result<()> {
+        // Capture the client-facing Host before it may be rewritten below
+        let original_host = upstream_request
+            .headers
+            .get("host")
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.to_string());
+
         // Update the request URI with the transformed path
         upstream_request.set_uri(ctx.upstream_path.parse().map_err(|e| {
             pingora_core::Error::because(
@@ -484,6 +1293,26 @@ impl ProxyHttp for KaratewayProxy {
             )
         })?);
 
+        // Send the overridden method upstream
+        if let Some(method) = &ctx.method_override {
+            let method = http::Method::from_bytes(method.as_bytes()).map_err(|e| {
+                pingora_core::Error::because(
+                    pingora_core::ErrorType::InternalError,
+                    format!("Invalid override method: {}", method),
+                    e,
+                )
+            })?;
+            upstream_request.set_method(method);
+        }
+        // Also when it wasn't honoured, or the backend could apply it past the route's methods
+        upstream_request.remove_header(METHOD_OVERRIDE_HEADER);
+
+        // Debug controls are for the gateway only; the token must not reach the backend
+        upstream_request.remove_header(timeout_override::TIMEOUT_OVERRIDE_HEADER);
+        upstream_request.remove_header(timeout_override::DEBUG_TOKEN_HEADER);
+        // The gateway already answered any 100-continue itself
+        upstream_request.remove_header("Expect");
+
         // Update Host header if not preserving original
         if !ctx.preserve_host {
             debug!(
@@ -507,6 +1336,32 @@ impl ProxyHttp for KaratewayProxy {
             );
         }
 
+        // Drop headers meant for the client connection; WebSocket handshakes keep Upgrade
+        let upgrade = hop_by_hop::is_upgrade(&upstream_request.headers);
+        for name in hop_by_hop::hop_by_hop_headers(&upstream_request.headers, upgrade) {
+            upstream_request.remove_header(&name);
+        }
+
+        // TE is hop-by-hop; keep only the trailers token so gRPC keeps working
+        if trailers::accepts_trailers(&session.req_header().headers) {
+            upstream_request
+                .insert_header("TE", trailers::TE_TRAILERS)
+                .ok();
+        }
+
+        // After the strip, so a client naming them in `Connection` can't drop them
+        if let Some(request_id) = &ctx.request_id {
+            upstream_request
+                .insert_header(request_id.header.clone(), &request_id.value)
+                .ok();
+        }
+
+        insert_route_id_header(
+            upstream_request,
+            self.route_id_header.as_ref(),
+            ctx.route_id,
+        );
+
         // Add X-Forwarded headers
         upstream_request
             .insert_header(
@@ -515,6 +1370,25 @@ impl ProxyHttp for KaratewayProxy {
             )
             .ok();
 
+        // Append this hop to the RFC 7239 Forwarded header
+        let downstream_proto = if session
+            .digest()
+            .and_then(|d| d.ssl_digest.as_ref())
+            .is_some()
+        {
+            "https"
+        } else {
+            "http"
+        };
+        // This hop received the request from the peer, whoever the client claims to be
+        let element = client_ip::forwarded_element(
+            ctx.client.peer_ip.as_deref(),
+            downstream_proto,
+            original_host.as_deref(),
+        );
+        let forwarded = client_ip::append_forwarded(&upstream_request.headers, &element);
+        upstream_request.insert_header("Forwarded", forwarded).ok();
+
         debug!(
             "Upstream request: {} {} with Host: {:?}",
             upstream_request.method,
@@ -522,27 +1396,291 @@ impl ProxyHttp for KaratewayProxy {
             upstream_request.headers.get("host")
         );
 
+        // Retries run this again, so the upstream time covers the last attempt
+        ctx.upstream_sent_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    async fn request_body_filter(
+        &self,
+        session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<()> {
+        if let (Some(capture), Some(chunk)) = (ctx.request_body_log.as_mut(), body.as_ref()) {
+            capture.push(chunk);
+        }
+        if end_of_stream {
+            if let Some(capture) = ctx.request_body_log.take() {
+                let req_header = session.req_header();
+                debug!(
+                    "Request body for {} {}: {}",
+                    req_header.method,
+                    req_header.uri.path(),
+                    capture.render(&self.body_logging.redact_fields)
+                );
+            }
+        }
+
         Ok(())
     }
 
     async fn response_filter(
         &self,
-        _session: &mut Session,
+        session: &mut Session,
         upstream_response: &mut pingora_http::ResponseHeader,
-        _ctx: &mut Self::CTX,
+        ctx: &mut Self::CTX,
     ) -> Result<()> {
+        // Refuse oversized heads before anything is sent to the client
+        if let Err(exceeded) = ctx.header_limits.check(&upstream_response.headers) {
+            warn!(
+                "Upstream {}:{}{} exceeded header limits: {}",
+                ctx.upstream_host, ctx.upstream_port, ctx.upstream_path, exceeded
+            );
+
+            if self.audit_logger.is_enabled(&AuditEventCategory::Admin) {
+                let req_header = session.req_header();
+                let mut builder = AuditLogBuilder::new(
+                    AuditEventType::BackendError,
+                    AuditEventCategory::Admin,
+                    AuditSeverity::Warning,
+                    format!("Backend response refused: {}", exceeded),
+                )
+                .request_method(req_header.method.as_str())
+                .request_path(req_header.uri.path())
+                .client_ip(ctx.client.ip_or_unknown())
+                .metadata(ErrorCode::UpstreamError.metadata())
+                .status_code(502);
+                if let Some(route_id) = ctx.route_id {
+                    builder = builder.api_route_id(route_id);
+                }
+                if let Some(service_id) = ctx.backend_service_id {
+                    builder = builder.backend_service_id(service_id);
+                }
+                self.audit_logger.log(builder.build());
+            }
+
+            return Err(pingora_core::Error::explain(
+                pingora_core::ErrorType::HTTPStatus(502),
+                format!("Upstream response headers too large: {}", exceeded),
+            ));
+        }
+
+        // Drop headers meant for the upstream connection, except on a 101 Switching Protocols
+        let upgrade = upstream_response.status == http::StatusCode::SWITCHING_PROTOCOLS;
+        for name in hop_by_hop::hop_by_hop_headers(&upstream_response.headers, upgrade) {
+            upstream_response.remove_header(&name);
+        }
+
+        // Before the head is shared, so coalesced followers get the decompressed body too
+        if ctx.decompress_response {
+            ctx.decompressor = self
+                .decompression
+                .start(&session.req_header().method, upstream_response);
+        }
+
+        if let Some(cookie_rewrite) = &ctx.cookie_rewrite {
+            cookie_rewrite.apply(upstream_response)?;
+        }
+
+        // Share the head as the upstream sent it; followers add their own headers
+        ctx.streaming = timeouts::is_streaming_response(&upstream_response.headers);
+        if ctx.streaming {
+            // A stream never finishes, so followers shouldn't wait on it
+            ctx.coalesce = None;
+        } else if !coalesce::shareable(&upstream_response.headers) {
+            // Meant for this client only; followers go upstream themselves
+            ctx.coalesce = None;
+        } else if let Some(leader) = ctx.coalesce.as_mut() {
+            leader.set_head(
+                upstream_response.status.as_u16(),
+                &upstream_response.headers,
+            );
+        }
+
         // Add custom response headers
         upstream_response
             .insert_header("X-Powered-By", "Karateway")
             .ok();
+        if let Some(request_id) = &ctx.request_id {
+            upstream_response
+                .insert_header(request_id.header.clone(), &request_id.value)
+                .ok();
+        }
+        ctx.insert_backend_service_header(upstream_response);
+
+        if ctx.timing_headers {
+            for (name, value) in
+                timing::timing_headers(ctx.started_at, ctx.upstream_sent_at, Instant::now())
+            {
+                upstream_response.insert_header(name, value).ok();
+            }
+        }
+
+        if let Some((limit, remaining, reset_time)) = ctx.rate_limit {
+            upstream_response
+                .insert_header("X-RateLimit-Limit", limit.to_string())
+                .ok();
+            upstream_response
+                .insert_header("X-RateLimit-Remaining", remaining.to_string())
+                .ok();
+            upstream_response
+                .insert_header("X-RateLimit-Reset", reset_time.to_string())
+                .ok();
+        }
+
+        let content_type = upstream_response
+            .headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        // A stream never ends, so there would be nothing to log
+        ctx.response_body_log = self
+            .body_logging
+            .capture(ctx.debug_log_body && !ctx.streaming, content_type);
+
+        ctx.last_read_at = Instant::now();
 
         Ok(())
     }
 
+    fn response_body_filter(
+        &self,
+        session: &mut Session,
+        body: &mut Option<Bytes>,
+        end_of_stream: bool,
+        ctx: &mut Self::CTX,
+    ) -> Result<Option<std::time::Duration>> {
+        let now = Instant::now();
+
+        if let Some(kind) = ctx
+            .timeouts
+            .check(ctx.started_at, ctx.last_read_at, now, ctx.streaming)
+        {
+            warn!(
+                "Upstream {:?} timeout: {}:{}{} (streaming: {})",
+                kind, ctx.upstream_host, ctx.upstream_port, ctx.upstream_path, ctx.streaming
+            );
+            return Err(pingora_core::Error::explain(
+                pingora_core::ErrorType::ReadTimedout,
+                format!("Upstream {:?} timeout exceeded", kind),
+            ));
+        }
+
+        ctx.last_read_at = now;
+
+        if let Some(decompressor) = ctx.decompressor.as_mut() {
+            let mut decompressed = match body.take() {
+                Some(chunk) => decompressor.push(&chunk),
+                None => Ok(Bytes::new()),
+            };
+            if end_of_stream {
+                decompressed = decompressed.and_then(|head| {
+                    let rest = decompressor.finish()?;
+                    Ok(if rest.is_empty() {
+                        head
+                    } else {
+                        [head, rest].concat().into()
+                    })
+                });
+            }
+            match decompressed {
+                Ok(chunk) => *body = (!chunk.is_empty()).then_some(chunk),
+                Err(e) => {
+                    // The head has gone out already, so all that's left is to cut the body off
+                    warn!(
+                        "Can't decompress response from {}:{}{}: {}",
+                        ctx.upstream_host, ctx.upstream_port, ctx.upstream_path, e
+                    );
+                    return Err(pingora_core::Error::explain(
+                        pingora_core::ErrorType::HTTPStatus(502),
+                        format!("Upstream response decompression failed: {}", e),
+                    ));
+                }
+            }
+        }
+
+        if let (Some(leader), Some(chunk)) = (ctx.coalesce.as_mut(), body.as_ref()) {
+            leader.push_body(chunk);
+        }
+        if let (Some(capture), Some(chunk)) = (ctx.response_body_log.as_mut(), body.as_ref()) {
+            capture.push(chunk);
+        }
+        if end_of_stream {
+            if let Some(leader) = ctx.coalesce.take() {
+                leader.finish();
+            }
+            if let Some(capture) = ctx.response_body_log.take() {
+                let req_header = session.req_header();
+                debug!(
+                    "Response body for {} {}: {}",
+                    req_header.method,
+                    req_header.uri.path(),
+                    capture.render(&self.body_logging.redact_fields)
+                );
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn response_trailer_filter(
+        &self,
+        _session: &mut Session,
+        upstream_trailers: &mut http::HeaderMap,
+        ctx: &mut Self::CTX,
+    ) -> Result<Option<Bytes>> {
+        forward_response_trailers(upstream_trailers, ctx);
+
+        // None lets Pingora pass the trailers on as a trailer frame
+        Ok(None)
+    }
+
+    async fn fail_to_proxy(
+        &self,
+        session: &mut Session,
+        e: &pingora_core::Error,
+        _ctx: &mut Self::CTX,
+    ) -> FailToProxy
+    where
+        Self::CTX: Send + Sync,
+    {
+        let Some((status, code)) = error_code::proxy_failure(e) else {
+            // The client is gone
+            return FailToProxy {
+                error_code: 0,
+                can_reuse_downstream: false,
+            };
+        };
+
+        // A failure mid-response can only cut the body off
+        if session.response_written().is_none() {
+            let body = error_code::proxy_failure_body(code);
+            match error_code::json_response(status, &body) {
+                Ok((resp, body_bytes)) => {
+                    let sent = async {
+                        session.write_response_header(Box::new(resp), false).await?;
+                        session.write_response_body(Some(body_bytes), true).await
+                    };
+                    if let Err(e) = sent.await {
+                        warn!("Failed to send {} error response: {}", code, e);
+                    }
+                }
+                Err(e) => warn!("Failed to build {} error response: {}", code, e),
+            }
+        }
+
+        FailToProxy {
+            error_code: status,
+            can_reuse_downstream: false,
+        }
+    }
+
     async fn logging(
         &self,
         session: &mut Session,
-        _error: Option<&pingora_core::Error>,
+        error: Option<&pingora_core::Error>,
         ctx: &mut Self::CTX,
     ) {
         let req_header = session.req_header();
@@ -551,12 +1689,382 @@ impl ProxyHttp for KaratewayProxy {
             .map(|r| r.status.as_u16())
             .unwrap_or(0);
 
-        info!(
-            method = %req_header.method,
-            path = %req_header.uri.path(),
-            status = status,
-            upstream = format!("{}:{}{}", ctx.upstream_host, ctx.upstream_port, ctx.upstream_path),
-            "Request completed"
+        access_log::record(
+            ctx.access_log_enabled,
+            AccessLogEntry {
+                method: req_header.method.as_str(),
+                path: req_header.uri.path(),
+                status,
+                upstream: &format!(
+                    "{}:{}{}",
+                    ctx.upstream_host, ctx.upstream_port, ctx.upstream_path
+                ),
+            },
+        );
+
+        // Unmatched requests share one label so 404 scans can't grow the series
+        // count; for the same reason only versions some route asks for are labelled
+        self.metrics.record_request(
+            ctx.route_label.as_deref().unwrap_or("unmatched"),
+            ctx.metric_tag.as_deref(),
+            ctx.api_version
+                .as_deref()
+                .filter(|version| self.router.is_routed_api_version(version)),
+            status,
+            ctx.started_at.elapsed(),
+        );
+
+        if let Some(service_id) = ctx.backend_service_id.filter(|_| ctx.discovered_instance) {
+            self.discovery.record_outcome(
+                service_id,
+                &ctx.upstream_host,
+                ctx.upstream_port,
+                instance_health::is_failure(error, status),
+            );
+        }
+
+        if let Some(request_logger) = &self.request_logger {
+            let request = CompletedRequest {
+                method: req_header.method.as_str(),
+                path: req_header.uri.path(),
+                status,
+                elapsed: ctx.started_at.elapsed(),
+                route_id: ctx.route_id,
+                backend_service_id: ctx.backend_service_id,
+                response_bytes: session.body_bytes_sent(),
+                tag: ctx.metric_tag.as_deref(),
+            };
+            request_logger.log(request.to_metric(error));
+        }
+    }
+}
+
+/// The configured route id header, `None` when disabled or not a valid header name
+fn route_id_header(enabled: bool, name: &str) -> Option<http::HeaderName> {
+    if !enabled {
+        return None;
+    }
+    match http::HeaderName::from_bytes(name.trim().as_bytes()) {
+        Ok(header) => Some(header),
+        Err(_) => {
+            warn!(
+                "Invalid GATEWAY_ROUTE_ID_HEADER {:?}, not sending route ids upstream",
+                name
+            );
+            None
+        }
+    }
+}
+
+/// Tell the backend which route matched, replacing any value the client sent
+fn insert_route_id_header(
+    upstream_request: &mut RequestHeader,
+    header: Option<&http::HeaderName>,
+    route_id: Option<Uuid>,
+) {
+    if let (Some(header), Some(route_id)) = (header, route_id) {
+        upstream_request
+            .insert_header(header.clone(), route_id.to_string())
+            .ok();
+    }
+}
+
+/// Prepare upstream response trailers for the client
+///
+/// Only unforwardable fields are dropped. Response headers such as
+/// X-Powered-By go on the header block, never on trailers.
+fn forward_response_trailers(trailers: &mut http::HeaderMap, ctx: &mut RequestContext) {
+    trailers::strip_unforwardable(trailers);
+    if let Some(status) = trailers::grpc_status(trailers) {
+        debug!(
+            "Upstream trailers for {}{}: grpc-status {}",
+            ctx.upstream_host, ctx.upstream_path, status
+        );
+    }
+    ctx.last_read_at = Instant::now();
+}
+
+/// 503 for a service that failed its health check, asking clients to wait for the next check
+fn unhealthy_response(
+    service: &BackendService,
+    default_retry_after_seconds: u64,
+) -> Result<(pingora_http::ResponseHeader, Bytes)> {
+    let retry_after = health_checker::retry_after_seconds(service, default_retry_after_seconds);
+    let (mut resp, body) = ErrorCode::BackendUnhealthy.response(&format!(
+        "Backend service {} is currently unhealthy",
+        service.name
+    ))?;
+    resp.insert_header("Retry-After", &retry_after.to_string())?;
+    Ok((resp, body))
+}
+
+/// 503 response for a route whose backend is disabled or removed
+fn no_backend_response(retry_after_seconds: u64) -> Result<(pingora_http::ResponseHeader, Bytes)> {
+    let (mut resp, body) =
+        ErrorCode::NoBackend.response("No active backend service is available for this route")?;
+    resp.insert_header("Retry-After", &retry_after_seconds.to_string())?;
+    Ok((resp, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_loader::tests::{route, service};
+    use crate::config_loader::GatewayConfig;
+    use envconfig::Envconfig;
+
+    /// Context of a request that was matched to `host:port`
+    fn request_ctx(upstream_host: &str, upstream_port: u16) -> RequestContext {
+        RequestContext {
+            upstream_host: upstream_host.to_string(),
+            upstream_port,
+            upstream_path: "/".to_string(),
+            use_tls: false,
+            client_cert: None,
+            upstream_sni: None,
+            preserve_host: false,
+            route_id: None,
+            backend_service_id: None,
+            discovered_instance: false,
+            route_label: None,
+            metric_tag: None,
+            api_version: None,
+            timeouts: RouteTimeouts::default(),
+            header_limits: HeaderLimits::default(),
+            started_at: Instant::now(),
+            last_read_at: Instant::now(),
+            client: ClientInfo::default(),
+            request_id: None,
+            upstream_sent_at: None,
+            timing_headers: false,
+            streaming: false,
+            method_override: None,
+            rate_limit: None,
+            backend_permit: None,
+            coalesce: None,
+            debug_log_body: false,
+            request_body_log: None,
+            response_body_log: None,
+            canary: false,
+            decompress_response: false,
+            decompressor: None,
+            cookie_rewrite: None,
+            access_log_enabled: true,
+            fallbacks: VecDeque::new(),
+            backend_service_name: None,
+            backend_service_header: false,
+            client_slot: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_a_request_keeps_its_discovered_instance() {
+        let backend = service("orders", "http://orders.fallback:8080");
+        let instances = [("10.0.0.1", 80), ("10.0.0.2", 80), ("10.0.0.3", 80)];
+        let discovery = crate::discovery::tests::discovered(&backend, &instances).await;
+
+        for n in 0..20 {
+            let ctx = RequestContext {
+                request_id: Some(RequestId {
+                    header: "x-request-id".to_string(),
+                    value: format!("req-{}", n),
+                }),
+                ..request_ctx("orders.fallback", 8080)
+            };
+            let first = discovery.pick_instance(&backend.id, ctx.selection_seed());
+            assert!(first.is_some());
+            assert_eq!(
+                discovery.pick_instance(&backend.id, ctx.selection_seed()),
+                first
+            );
+        }
+    }
+
+    #[test]
+    fn test_in_flight_request_survives_backend_disable() {
+        let backend = service("orders", "http://127.0.0.1:9001");
+        let mut config = GatewayConfig::new();
+        config.routes = vec![route("/orders", backend.id, 0)];
+        config.services.insert(backend.id, backend.clone());
+
+        // Request matched while the backend was still active
+        assert!(config
+            .find_route("/orders/1", "GET", None, None, None)
+            .is_some());
+        let ctx = RequestContext {
+            upstream_path: "/orders/1".to_string(),
+            route_id: Some(config.routes[0].id),
+            backend_service_id: Some(backend.id),
+            ..request_ctx("127.0.0.1", 9001)
+        };
+
+        // Backend is disabled and dropped by the next reload
+        config.services.remove(&backend.id);
+
+        // New requests no longer match it...
+        assert!(config
+            .find_route("/orders/1", "GET", None, None, None)
+            .is_none());
+
+        // ...but the in-flight one still resolves its captured upstream
+        let peer = ctx.upstream_peer("127.0.0.1:9001".parse().unwrap());
+        assert_eq!(peer.sni(), "127.0.0.1");
+        assert!(!peer.tls());
+        assert_eq!(peer.address().as_inet().map(|a| a.port()), Some(9001));
+    }
+
+    #[test]
+    fn test_sni_is_independent_of_the_connect_address() {
+        let ctx = RequestContext {
+            use_tls: true,
+            upstream_sni: Some("orders.internal".to_string()),
+            ..request_ctx("10.0.0.7", 8443)
+        };
+
+        let addr = "10.0.0.7:8443".parse().unwrap();
+        let peer = ctx.upstream_peer(addr);
+        assert!(peer.tls());
+        assert_eq!(peer.sni(), "orders.internal");
+        assert_eq!(
+            peer.address().as_inet().map(|a| a.to_string()),
+            Some("10.0.0.7:8443".to_string())
         );
+
+        // Without one the SNI is the host connected to
+        let peer = RequestContext {
+            upstream_sni: None,
+            ..ctx
+        }
+        .upstream_peer(addr);
+        assert_eq!(peer.sni(), "10.0.0.7");
+    }
+
+    #[test]
+    fn test_unhealthy_503_tells_clients_when_to_retry() {
+        let mut backend = service("orders", "http://127.0.0.1:9001");
+        backend.health_check_interval_seconds = Some(30);
+
+        let (resp, body) = unhealthy_response(&backend, 10).unwrap();
+        assert_eq!(resp.status.as_u16(), 503);
+        assert_eq!(resp.headers.get("Retry-After").unwrap(), "30");
+        assert_eq!(
+            resp.headers.get("Content-Length").unwrap(),
+            &body.len().to_string()
+        );
+        assert!(String::from_utf8_lossy(&body).contains(r#""error_code":"BACKEND_UNHEALTHY""#));
+    }
+
+    #[test]
+    fn test_rate_limit_keyed_on_a_custom_header() {
+        let limit = RateLimit {
+            identifier_type: IdentifierType::Header,
+            identifier_header: Some("X-Tenant-ID".to_string()),
+            ..crate::config_loader::tests::rate_limit("per-tenant", None, 100)
+        };
+        let client = ClientInfo::default();
+        let tenant = |id: &str| {
+            let mut headers = http::HeaderMap::new();
+            headers.insert("x-tenant-id", id.parse().unwrap());
+            KaratewayProxy::rate_limit_identifier(&headers, &client, &limit)
+        };
+
+        assert_eq!(tenant("acme"), "acme");
+        assert_ne!(tenant("acme"), tenant("globex"));
+        // Requests without a tenant are counted together
+        assert_eq!(
+            KaratewayProxy::rate_limit_identifier(&http::HeaderMap::new(), &client, &limit),
+            "no-x-tenant-id"
+        );
+    }
+
+    #[test]
+    fn test_backend_service_header_names_the_routed_backend() {
+        let mut ctx = request_ctx("10.0.0.7", 9000);
+        ctx.backend_service_name = Some("orders-secondary".to_string());
+
+        let mut resp = pingora_http::ResponseHeader::build(200, None).unwrap();
+        ctx.insert_backend_service_header(&mut resp);
+        // Off by default, it would leak the internal topology
+        assert!(resp.headers.get(BACKEND_SERVICE_HEADER).is_none());
+
+        ctx.backend_service_header = true;
+        ctx.insert_backend_service_header(&mut resp);
+        assert_eq!(
+            resp.headers.get(BACKEND_SERVICE_HEADER).unwrap(),
+            "orders-secondary"
+        );
+    }
+
+    #[test]
+    fn test_route_id_header_carries_the_matched_route() {
+        let route_id = Uuid::new_v4();
+        let mut upstream_request = RequestHeader::build("GET", b"/orders", None).unwrap();
+        // A client can't pass off its own value
+        upstream_request
+            .insert_header("X-Route-ID", "spoofed")
+            .unwrap();
+
+        // Off by default
+        let mut env = std::collections::HashMap::new();
+        env.insert("DB_PASSWORD".to_string(), "secret".to_string());
+        env.insert("JWT_SECRET".to_string(), "secret".to_string());
+        let defaults = AppConfig::init_from_hashmap(&env).unwrap();
+        insert_route_id_header(
+            &mut upstream_request,
+            route_id_header(
+                defaults.gateway_route_id_header_enabled,
+                &defaults.gateway_route_id_header,
+            )
+            .as_ref(),
+            Some(route_id),
+        );
+        assert_eq!(
+            upstream_request.headers.get("X-Route-ID").unwrap(),
+            "spoofed"
+        );
+
+        let header = route_id_header(true, "X-Route-ID");
+        insert_route_id_header(&mut upstream_request, header.as_ref(), Some(route_id));
+        assert_eq!(
+            upstream_request.headers.get("x-route-id").unwrap(),
+            &route_id.to_string()
+        );
+
+        // The name is configurable; invalid names turn the header off
+        let custom = route_id_header(true, "X-Gateway-Route");
+        insert_route_id_header(&mut upstream_request, custom.as_ref(), Some(route_id));
+        assert_eq!(
+            upstream_request.headers.get("X-Gateway-Route").unwrap(),
+            &route_id.to_string()
+        );
+        assert_eq!(route_id_header(true, "Bad Header"), None);
+    }
+
+    #[test]
+    fn test_missing_backend_is_a_503_not_a_404() {
+        let (resp, body) = no_backend_response(10).unwrap();
+        assert_eq!(resp.status.as_u16(), 503);
+        assert_eq!(resp.headers.get("Retry-After").unwrap(), "10");
+        assert!(String::from_utf8_lossy(&body).contains("No active backend service"));
+        assert!(String::from_utf8_lossy(&body).contains(r#""error_code":"NO_BACKEND""#));
+    }
+
+    #[test]
+    fn test_grpc_trailers_reach_the_client() {
+        let mut ctx = request_ctx("orders.internal", 50051);
+        let before = ctx.last_read_at;
+
+        let mut trailers = http::HeaderMap::new();
+        trailers.insert("grpc-status", "5".parse().unwrap());
+        trailers.insert("grpc-message", "order not found".parse().unwrap());
+        trailers.insert("content-length", "0".parse().unwrap());
+
+        forward_response_trailers(&mut trailers, &mut ctx);
+
+        assert_eq!(trailers.get("grpc-status").unwrap(), "5");
+        assert_eq!(trailers.get("grpc-message").unwrap(), "order not found");
+        assert!(trailers.get("content-length").is_none());
+        assert!(trailers.get("X-Powered-By").is_none());
+        assert!(ctx.last_read_at >= before);
     }
 }
@@ -0,0 +1,197 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Longest v1 header, including its CRLF
+const V1_MAX_LENGTH: usize = 107;
+
+/// First 12 bytes of every v2 header
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Read the PROXY protocol header a load balancer puts in front of a connection
+///
+/// Returns the client address it conveys, or `None` for a health check or
+/// other connection the balancer made itself (`UNKNOWN` in v1, `LOCAL` in
+/// v2). Only the header is consumed, so the HTTP request after it is left
+/// for the proxy. A connection that doesn't start with a valid v1 or v2
+/// header is an error.
+pub async fn read_header<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<SocketAddr>, String> {
+    let mut start = [0u8; 5];
+    read(reader, &mut start).await?;
+
+    if start == *b"PROXY" {
+        let mut line = start.to_vec();
+        while !line.ends_with(b"\r\n") {
+            if line.len() >= V1_MAX_LENGTH {
+                return Err("PROXY v1 header is too long".to_string());
+            }
+            let mut byte = [0u8; 1];
+            read(reader, &mut byte).await?;
+            line.push(byte[0]);
+        }
+        let line = std::str::from_utf8(&line[..line.len() - 2])
+            .map_err(|_| "PROXY v1 header is not ASCII".to_string())?;
+        return parse_v1(line);
+    }
+
+    if start == V2_SIGNATURE[..5] {
+        let mut header = [0u8; 16];
+        header[..5].copy_from_slice(&start);
+        read(reader, &mut header[5..]).await?;
+        if header[..12] != V2_SIGNATURE {
+            return Err("invalid PROXY v2 signature".to_string());
+        }
+
+        let length = u16::from_be_bytes([header[14], header[15]]) as usize;
+        let mut addresses = vec![0u8; length];
+        read(reader, &mut addresses).await?;
+        return parse_v2(header[12], header[13], &addresses);
+    }
+
+    Err("connection did not start with a PROXY protocol header".to_string())
+}
+
+async fn read<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> Result<(), String> {
+    reader
+        .read_exact(buf)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("incomplete PROXY protocol header: {}", e))
+}
+
+/// Parse a v1 header line without its CRLF, e.g. `PROXY TCP4 203.0.113.9 10.0.0.5 51234 8080`
+pub fn parse_v1(line: &str) -> Result<Option<SocketAddr>, String> {
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", protocol @ ("TCP4" | "TCP6"), source, destination, source_port, destination_port] =>
+        {
+            let address = |value: &str| -> Result<IpAddr, String> {
+                let ip: IpAddr = value
+                    .parse()
+                    .map_err(|_| format!("invalid address {} in PROXY v1 header", value))?;
+                match (*protocol, ip) {
+                    ("TCP4", IpAddr::V4(_)) | ("TCP6", IpAddr::V6(_)) => Ok(ip),
+                    _ => Err(format!("{} is not a {} address", value, protocol)),
+                }
+            };
+            let port = |value: &str| -> Result<u16, String> {
+                value
+                    .parse()
+                    .map_err(|_| format!("invalid port {} in PROXY v1 header", value))
+            };
+
+            address(destination)?;
+            port(destination_port)?;
+            Ok(Some(SocketAddr::new(address(source)?, port(source_port)?)))
+        }
+        _ => Err(format!("malformed PROXY v1 header: {}", line)),
+    }
+}
+
+/// Parse the rest of a v2 header: its version/command and family bytes, and the address block
+///
+/// TLVs after the addresses are ignored.
+pub fn parse_v2(
+    version_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> Result<Option<SocketAddr>, String> {
+    if version_command >> 4 != 2 {
+        return Err(format!(
+            "unsupported PROXY protocol version {}",
+            version_command >> 4
+        ));
+    }
+    match version_command & 0x0f {
+        // LOCAL: the balancer's own connection, e.g. a health check
+        0x0 => return Ok(None),
+        0x1 => {}
+        command => return Err(format!("unknown PROXY v2 command {}", command)),
+    }
+
+    let port = |offset: usize| u16::from_be_bytes([addresses[offset], addresses[offset + 1]]);
+    match family >> 4 {
+        // AF_INET: source and destination addresses, then their ports
+        0x1 if addresses.len() >= 12 => {
+            let ip = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port(8))))
+        }
+        0x2 if addresses.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&addresses[..16]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(octets)),
+                port(32),
+            )))
+        }
+        0x1 | 0x2 => Err("PROXY v2 address block is too short".to_string()),
+        // AF_UNSPEC and AF_UNIX carry no client IP
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_v1_header() {
+        let mut stream: &[u8] =
+            b"PROXY TCP4 203.0.113.9 10.0.0.5 51234 8080\r\nGET / HTTP/1.1\r\n\r\n";
+        let client = read_header(&mut stream).await.unwrap();
+        assert_eq!(client, Some("203.0.113.9:51234".parse().unwrap()));
+        // The request itself is left for the proxy
+        assert_eq!(stream, b"GET / HTTP/1.1\r\n\r\n");
+
+        assert_eq!(
+            parse_v1("PROXY TCP6 2001:db8::1 2001:db8::2 443 8080").unwrap(),
+            Some("[2001:db8::1]:443".parse().unwrap())
+        );
+        assert_eq!(parse_v1("PROXY UNKNOWN").unwrap(), None);
+        assert!(parse_v1("PROXY TCP4 2001:db8::1 10.0.0.5 1 2").is_err());
+        assert!(parse_v1("PROXY TCP4 203.0.113.9 10.0.0.5 51234").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_v2_header() {
+        let mut header = V2_SIGNATURE.to_vec();
+        // Version 2, PROXY command, TCP over IPv4, 12 address bytes
+        header.extend_from_slice(&[0x21, 0x11, 0x00, 0x0c]);
+        header.extend_from_slice(&[203, 0, 113, 9, 10, 0, 0, 5]);
+        header.extend_from_slice(&51234u16.to_be_bytes());
+        header.extend_from_slice(&8080u16.to_be_bytes());
+        header.extend_from_slice(b"GET / HTTP/1.1\r\n\r\n");
+
+        let mut stream = header.as_slice();
+        let client = read_header(&mut stream).await.unwrap();
+        assert_eq!(client, Some("203.0.113.9:51234".parse().unwrap()));
+        assert_eq!(stream, b"GET / HTTP/1.1\r\n\r\n");
+
+        // IPv6 addresses, followed by a TLV that is skipped
+        let mut addresses = "2001:db8::1".parse::<Ipv6Addr>().unwrap().octets().to_vec();
+        addresses.extend_from_slice(&"2001:db8::2".parse::<Ipv6Addr>().unwrap().octets());
+        addresses.extend_from_slice(&443u16.to_be_bytes());
+        addresses.extend_from_slice(&8080u16.to_be_bytes());
+        addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]);
+        assert_eq!(
+            parse_v2(0x21, 0x21, &addresses).unwrap(),
+            Some("[2001:db8::1]:443".parse().unwrap())
+        );
+
+        // LOCAL connections from the balancer carry no client
+        assert_eq!(parse_v2(0x20, 0x00, &[]).unwrap(), None);
+        assert!(parse_v2(0x11, 0x11, &addresses).is_err());
+        assert!(parse_v2(0x21, 0x11, &[203, 0, 113]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connection_without_header_is_rejected() {
+        let mut stream: &[u8] = b"GET / HTTP/1.1\r\n\r\n";
+        assert!(read_header(&mut stream).await.is_err());
+
+        let mut truncated: &[u8] = b"PROXY TCP4 203.0.113.9";
+        assert!(read_header(&mut truncated).await.is_err());
+    }
+}
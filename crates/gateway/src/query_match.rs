@@ -0,0 +1,83 @@
+use serde_json::Value;
+
+/// Decode a raw query string into `(name, value)` pairs
+///
+/// Repeated params keep every occurrence in order, and a bare `?flag` yields
+/// an empty value.
+pub fn parse_query(query: Option<&str>) -> Vec<(String, String)> {
+    query
+        .map(|query| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .into_owned()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether a request's query params satisfy a route's `query_match` conditions
+///
+/// Every condition must hold. A condition is met when any occurrence of the
+/// param has the expected value, so `?tag=a&tag=b` satisfies both `a` and `b`.
+/// An empty expected value only requires the param to be present.
+pub fn matches(conditions: &Value, params: &[(String, String)]) -> bool {
+    let Some(conditions) = conditions.as_object() else {
+        return true;
+    };
+
+    conditions.iter().all(|(name, expected)| {
+        let expected = expected.as_str().unwrap_or_default();
+        params
+            .iter()
+            .any(|(key, value)| key == name && (expected.is_empty() || value == expected))
+    })
+}
+
+/// Number of query conditions on a route, used to prefer the more specific match
+pub fn condition_count(conditions: &Value) -> usize {
+    conditions.as_object().map(|c| c.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_matches_conditions() {
+        let conditions = json!({"version": "beta"});
+
+        assert!(matches(&conditions, &parse_query(Some("version=beta"))));
+        assert!(matches(
+            &conditions,
+            &parse_query(Some("page=2&version=beta"))
+        ));
+        assert!(!matches(&conditions, &parse_query(Some("version=stable"))));
+        assert!(!matches(&conditions, &parse_query(Some("page=2"))));
+        assert!(!matches(&conditions, &parse_query(None)));
+    }
+
+    #[test]
+    fn test_repeated_and_empty_params() {
+        let params = parse_query(Some("tag=a&tag=b&debug&empty=&&"));
+        assert_eq!(
+            params,
+            vec![
+                ("tag".to_string(), "a".to_string()),
+                ("tag".to_string(), "b".to_string()),
+                ("debug".to_string(), String::new()),
+                ("empty".to_string(), String::new()),
+            ]
+        );
+
+        assert!(matches(&json!({"tag": "b"}), &params));
+        assert!(matches(&json!({"tag": "a", "debug": ""}), &params));
+        assert!(!matches(&json!({"missing": ""}), &params));
+    }
+
+    #[test]
+    fn test_no_conditions_ignore_query() {
+        assert!(matches(&json!({}), &parse_query(Some("version=beta"))));
+        assert!(matches(&json!({}), &parse_query(None)));
+        assert_eq!(condition_count(&json!({})), 0);
+    }
+}
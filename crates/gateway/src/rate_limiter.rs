@@ -1,106 +1,482 @@
 use anyhow::Result;
-use redis::AsyncCommands;
-use std::time::{SystemTime, UNIX_EPOCH};
+use http::HeaderMap;
+use karateway_core::models::{ApiRoute, RateLimit};
+use once_cell::sync::Lazy;
+use redis::aio::MultiplexedConnection;
+use redis::{RedisResult, Script};
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::error_code::ErrorCode;
+
+/// What happens to a request when the rate limiter itself fails, e.g. Redis is unreachable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailureMode {
+    /// Let the request through unlimited and log a warning (favours availability)
+    Open,
+    /// Reject the request with `503` (favours protecting the backends)
+    #[default]
+    Closed,
+}
+
+impl std::str::FromStr for FailureMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "open" => Ok(FailureMode::Open),
+            "closed" => Ok(FailureMode::Closed),
+            other => Err(format!("Invalid rate limit failure mode: {}", other)),
+        }
+    }
+}
+
+impl FailureMode {
+    /// Parse the configured mode, falling back to `Closed` on invalid values
+    pub fn parse_or_default(value: &str) -> Self {
+        value.parse().unwrap_or_else(|e| {
+            warn!("{}, failing closed", e);
+            FailureMode::default()
+        })
+    }
+
+    /// Response to send when the limiter fails, `None` to let the request through
+    pub fn error_response(self) -> Option<(u16, String)> {
+        match self {
+            FailureMode::Open => None,
+            FailureMode::Closed => {
+                let code = ErrorCode::RateLimiterUnavailable;
+                Some((
+                    code.status(),
+                    code.body("Rate limiter unavailable").to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// One layer of a multi-tier limit, e.g. the `50/minute` in `1000/hour AND 50/minute`
+pub struct Tier<'a> {
+    pub limit: &'a RateLimit,
+    /// Redis key of this tier's counter for the current client
+    pub key: String,
+}
+
+impl<'a> Tier<'a> {
+    /// Each limit counts under its own key, so tiers sharing an identifier type don't mix budgets
+    pub fn new(route_id: Uuid, limit: &'a RateLimit, identifier: &str) -> Self {
+        Self {
+            limit,
+            key: format!(
+                "{}:{}:{}:{}",
+                route_id, limit.id, limit.identifier_type, identifier
+            ),
+        }
+    }
+}
+
+/// Budget left on one tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierStatus {
+    /// Position of the tier in the slice passed to `check_tiers`
+    pub index: usize,
+    pub remaining: i32,
+    pub reset_time: u64,
+}
+
+/// Result of checking every tier of a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TierOutcome {
+    /// All tiers passed; carries the most restrictive one (if any) for the response headers
+    Allowed(Option<TierStatus>),
+    /// The first tier, in evaluation order, that had no budget left
+    Rejected(TierStatus),
+}
+
+/// Most slots one request can take, the same bound the admin API puts on `request_cost`
+pub const MAX_REQUEST_COST: i32 = 1000;
+
+/// Slots a request takes in each tier of its route's limits
+///
+/// The route's `request_cost` (1 when unset), raised to the number in its
+/// `request_cost_header` if the request carries one. Header values that
+/// aren't a number from 1 to [`MAX_REQUEST_COST`] are ignored, and a header
+/// can't make a request cheaper than the route's cost.
+pub fn request_cost(route: &ApiRoute, headers: &HeaderMap) -> i32 {
+    let fixed = route.request_cost.unwrap_or(1).clamp(1, MAX_REQUEST_COST);
+
+    let requested = route
+        .request_cost_header
+        .as_deref()
+        .and_then(|header| headers.get(header))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<i32>().ok())
+        .filter(|cost| (1..=MAX_REQUEST_COST).contains(cost));
+
+    requested.map_or(fixed, |requested| requested.max(fixed))
+}
+
+/// Budget a sliding window with `count` requests has left after taking `cost` more
+///
+/// `None` when the request doesn't fit.
+fn window_remaining(count: i32, max_requests: i32, cost: i32) -> Option<i32> {
+    let remaining = max_requests - count - cost;
+    (remaining >= 0).then_some(remaining)
+}
+
+/// Sliding window check in one round trip, atomic against concurrent requests
+///
+/// `KEYS[1]` is the window's sorted set, which holds one member per counted
+/// request, scored by its timestamp and ending in `:<cost>` (members without
+/// one count as 1). ARGV: now, window seconds, max requests, cost, and the
+/// member to add, empty to only look. Returns the cost already in the window,
+/// the oldest member's timestamp (-1 when empty) and whether the member was
+/// added, which happens only when the request fits.
+static SLIDING_WINDOW: Lazy<Script> = Lazy::new(|| {
+    Script::new(
+        r"
+local key = KEYS[1]
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+redis.call('ZREMRANGEBYSCORE', key, 0, now - window)
+
+local used = 0
+for _, member in ipairs(redis.call('ZRANGE', key, 0, -1)) do
+    used = used + (tonumber(string.match(member, ':(%d+)$')) or 1)
+end
+local oldest = redis.call('ZRANGE', key, 0, 0, 'WITHSCORES')[2]
+oldest = oldest and tonumber(oldest) or -1
+
+if ARGV[5] == '' or used + tonumber(ARGV[4]) > tonumber(ARGV[3]) then
+    return {used, oldest, 0}
+end
+redis.call('ZADD', key, now, ARGV[5])
+redis.call('EXPIRE', key, window + 60)
+return {used, oldest, 1}
+",
+    )
+});
+
+/// Tokens a bucket holding `tokens` has left after paying `cost`, `None` when it can't
+fn bucket_remaining(tokens: i32, cost: i32) -> Option<i32> {
+    (tokens >= cost).then(|| tokens - cost)
+}
+
+/// Sort limits into evaluation order: shortest window first, then smallest budget
+///
+/// Short windows are the ones a burst trips, so checking them first rejects
+/// without touching the long-window counters at all.
+pub fn order_tiers(limits: &mut [RateLimit]) {
+    limits.sort_by_key(|limit| (limit.window_seconds, limit.max_requests, limit.id));
+}
+
+/// The tier with the least budget left, earliest reset breaking ties
+pub fn most_restrictive(statuses: &[TierStatus]) -> Option<TierStatus> {
+    statuses
+        .iter()
+        .copied()
+        .min_by_key(|status| (status.remaining, status.reset_time))
+}
+
+/// Run a Redis command, giving up once it takes longer than `timeout`
+///
+/// A slow Redis then fails the check like an unreachable one, so the
+/// failure mode decides the request instead of it waiting on Redis.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    command: impl Future<Output = RedisResult<T>>,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return Ok(command.await?);
+    };
+    match tokio::time::timeout(timeout, command).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(anyhow::anyhow!(
+            "Redis command timed out after {}ms",
+            timeout.as_millis()
+        )),
+    }
+}
 
 /// Rate limiter using Redis with sliding window algorithm
 pub struct RateLimiter {
     redis_client: redis::Client,
+    /// Longest a single Redis command may take, `None` to wait indefinitely
+    command_timeout: Option<Duration>,
 }
 
 impl RateLimiter {
-    /// Create a new rate limiter
-    pub fn new(redis_url: &str) -> Result<Self> {
-        let redis_client = redis::Client::open(redis_url)?;
-        Ok(Self { redis_client })
+    /// Create a new rate limiter on top of a configured Redis client
+    pub fn new(redis_client: redis::Client, command_timeout: Option<Duration>) -> Self {
+        Self {
+            redis_client,
+            command_timeout,
+        }
+    }
+
+    /// Run a Redis command within the command timeout
+    async fn run<T>(&self, command: impl Future<Output = RedisResult<T>>) -> Result<T> {
+        with_timeout(self.command_timeout, command).await
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection> {
+        self.run(self.redis_client.get_multiplexed_async_connection())
+            .await
+    }
+
+    /// Run the sliding window script on a window key
+    ///
+    /// Returns the cost already counted in the window, the oldest counted
+    /// request's timestamp, and whether `member` was added.
+    async fn sliding_window(
+        &self,
+        conn: &mut MultiplexedConnection,
+        redis_key: &str,
+        now: u64,
+        (max_requests, window_seconds): (i32, i32),
+        cost: i32,
+        member: Option<&str>,
+    ) -> Result<(i32, Option<u64>, bool)> {
+        let (used, oldest, added): (i32, i64, i32) = self
+            .run(
+                SLIDING_WINDOW
+                    .key(redis_key)
+                    .arg(now)
+                    .arg(window_seconds)
+                    .arg(max_requests)
+                    .arg(cost)
+                    .arg(member.unwrap_or_default())
+                    .invoke_async(conn),
+            )
+            .await?;
+        Ok((used, u64::try_from(oldest).ok(), added == 1))
     }
 
-    /// Check if a request is allowed under rate limiting
+    /// Check if a request taking `cost` slots is allowed under rate limiting
     /// Returns (allowed, remaining, reset_time)
+    ///
+    /// The request is counted as one entry carrying its cost, so a costly
+    /// request doesn't grow the window's sorted set by its cost.
     pub async fn check_rate_limit(
         &self,
         key: &str,
         max_requests: i32,
         window_seconds: i32,
+        cost: i32,
     ) -> Result<(bool, i32, u64)> {
-        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let mut conn = self.connection().await?;
+        self.count_in_window(&mut conn, key, (max_requests, window_seconds), cost)
+            .await
+    }
 
+    /// [`Self::check_rate_limit`] on an open connection
+    async fn count_in_window(
+        &self,
+        conn: &mut MultiplexedConnection,
+        key: &str,
+        (max_requests, window_seconds): (i32, i32),
+        cost: i32,
+    ) -> Result<(bool, i32, u64)> {
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-
-        let window_start = now - window_seconds as u64;
         let redis_key = format!("ratelimit:{}", key);
+        let member = format!("{}:{}:{}", now, uuid::Uuid::new_v4(), cost);
 
-        // Use Redis sorted set with timestamps as scores
-        // Remove old entries outside the window
-        let _: () = conn.zrembyscore(&redis_key, 0, window_start as f64).await?;
-
-        // Count current requests in window
-        let count: i32 = conn.zcard(&redis_key).await?;
+        let (count, oldest, added) = self
+            .sliding_window(
+                conn,
+                &redis_key,
+                now,
+                (max_requests, window_seconds),
+                cost,
+                Some(&member),
+            )
+            .await?;
 
         debug!(
-            "Rate limit check: key={}, count={}/{}, window={}s",
-            key, count, max_requests, window_seconds
+            "Rate limit check: key={}, count={}/{}, cost={}, window={}s",
+            key, count, max_requests, cost, window_seconds
         );
 
-        if count >= max_requests {
+        let remaining = window_remaining(count, max_requests, cost).filter(|_| added);
+        let Some(remaining) = remaining else {
             // Rate limit exceeded
-            let oldest: Option<(String, f64)> = conn.zrange_withscores(&redis_key, 0, 0).await?;
-            let reset_time = if let Some((_, score)) = oldest {
-                (score as u64) + window_seconds as u64
-            } else {
-                now + window_seconds as u64
-            };
+            let reset_time = oldest.unwrap_or(now) + window_seconds as u64;
 
             warn!(
-                "Rate limit exceeded: key={}, count={}/{}, reset_in={}s",
+                "Rate limit exceeded: key={}, count={}/{}, cost={}, reset_in={}s",
                 key,
                 count,
                 max_requests,
+                cost,
                 reset_time.saturating_sub(now)
             );
 
-            Ok((false, 0, reset_time))
-        } else {
-            // Allow request and add to sorted set
-            let request_id = format!("{}:{}", now, uuid::Uuid::new_v4());
-            let _: () = conn.zadd(&redis_key, request_id, now as f64).await?;
+            return Ok((false, 0, reset_time));
+        };
 
-            // Set expiry to a window and some buffer
-            let _: () = conn
-                .expire(&redis_key, (window_seconds + 60) as i64)
-                .await?;
+        let reset_time = now + window_seconds as u64;
+
+        debug!(
+            "Rate limit allowed: key={}, remaining={}, reset_in={}s",
+            key, remaining, window_seconds
+        );
 
-            let remaining = max_requests - count - 1;
-            let reset_time = now + window_seconds as u64;
+        Ok((true, remaining, reset_time))
+    }
 
-            debug!(
-                "Rate limit allowed: key={}, remaining={}, reset_in={}s",
-                key, remaining, window_seconds
-            );
+    /// Check every tier of a layered limit; all must pass
+    ///
+    /// Tiers are first checked without consuming budget, in order, stopping
+    /// at the first one without room for `cost` slots. Only when all of them
+    /// have room is the request counted against each, so a request rejected
+    /// by one tier never uses up budget on the others. A tier that runs out
+    /// between the two passes (a concurrent request took the last slots)
+    /// still rejects, after the tiers before it were counted. Both passes
+    /// share one connection.
+    pub async fn check_tiers(&self, tiers: &[Tier<'_>], cost: i32) -> Result<TierOutcome> {
+        let mut conn = self.connection().await?;
+        for (index, tier) in tiers.iter().enumerate() {
+            if let Some(reset_time) = self.peek(&mut conn, tier, cost).await? {
+                return Ok(TierOutcome::Rejected(TierStatus {
+                    index,
+                    remaining: 0,
+                    reset_time,
+                }));
+            }
+        }
+
+        let mut statuses = Vec::with_capacity(tiers.len());
+        for (index, tier) in tiers.iter().enumerate() {
+            let (allowed, remaining, reset_time) = self.consume(&mut conn, tier, cost).await?;
+            let status = TierStatus {
+                index,
+                remaining,
+                reset_time,
+            };
+            if !allowed {
+                return Ok(TierOutcome::Rejected(status));
+            }
+            statuses.push(status);
+        }
+
+        Ok(TierOutcome::Allowed(most_restrictive(&statuses)))
+    }
+
+    /// Count a request taking `cost` slots against a tier
+    async fn consume(
+        &self,
+        conn: &mut MultiplexedConnection,
+        tier: &Tier<'_>,
+        cost: i32,
+    ) -> Result<(bool, i32, u64)> {
+        let limit = tier.limit;
+        let window = (limit.max_requests, limit.window_seconds);
+        match limit.burst_size {
+            Some(burst) => self.take_tokens(conn, &tier.key, window, burst, cost).await,
+            None => self.count_in_window(conn, &tier.key, window, cost).await,
+        }
+    }
+
+    /// Check a tier without counting the request
+    ///
+    /// Returns the reset time when the tier has no room for `cost` slots, `None` when it has.
+    async fn peek(
+        &self,
+        conn: &mut MultiplexedConnection,
+        tier: &Tier<'_>,
+        cost: i32,
+    ) -> Result<Option<u64>> {
+        let limit = tier.limit;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let window_seconds = limit.window_seconds as u64;
 
-            Ok((true, remaining, reset_time))
+        match limit.burst_size {
+            Some(burst) => {
+                let redis_key = format!("ratelimit:bucket:{}", tier.key);
+                let (tokens, last_refill): (Option<i32>, Option<u64>) = self
+                    .run(
+                        redis::pipe()
+                            .hget(&redis_key, "tokens")
+                            .hget(&redis_key, "last_refill")
+                            .query_async(conn),
+                    )
+                    .await?;
+
+                let (Some(tokens), Some(last_refill)) = (tokens, last_refill) else {
+                    return Ok(None);
+                };
+                let refill_rate = limit.max_requests as f64 / limit.window_seconds as f64;
+                let elapsed = now.saturating_sub(last_refill);
+                let tokens = (tokens + (elapsed as f64 * refill_rate) as i32)
+                    .min(limit.max_requests + burst);
+
+                Ok(bucket_remaining(tokens, cost)
+                    .is_none()
+                    .then(|| now + ((cost - tokens).max(1) as f64 / refill_rate) as u64))
+            }
+            None => {
+                let redis_key = format!("ratelimit:{}", tier.key);
+                let (count, oldest, _) = self
+                    .sliding_window(
+                        conn,
+                        &redis_key,
+                        now,
+                        (limit.max_requests, limit.window_seconds),
+                        cost,
+                        None,
+                    )
+                    .await?;
+                if window_remaining(count, limit.max_requests, cost).is_some() {
+                    return Ok(None);
+                }
+                Ok(Some(oldest.unwrap_or(now) + window_seconds))
+            }
         }
     }
 
-    /// Check rate limit with token bucket algorithm (supports burst)
+    /// Check rate limit with token bucket algorithm (supports burst), taking `cost` tokens
     pub async fn check_rate_limit_with_burst(
         &self,
         key: &str,
         max_requests: i32,
         window_seconds: i32,
         burst_size: i32,
+        cost: i32,
     ) -> Result<(bool, i32, u64)> {
-        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let mut conn = self.connection().await?;
+        self.take_tokens(
+            &mut conn,
+            key,
+            (max_requests, window_seconds),
+            burst_size,
+            cost,
+        )
+        .await
+    }
 
+    /// [`Self::check_rate_limit_with_burst`] on an open connection
+    async fn take_tokens(
+        &self,
+        conn: &mut MultiplexedConnection,
+        key: &str,
+        (max_requests, window_seconds): (i32, i32),
+        burst_size: i32,
+        cost: i32,
+    ) -> Result<(bool, i32, u64)> {
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
         let redis_key = format!("ratelimit:bucket:{}", key);
 
         // Get current token count and last refill time
-        let (tokens, last_refill): (Option<i32>, Option<u64>) = redis::pipe()
-            .hget(&redis_key, "tokens")
-            .hget(&redis_key, "last_refill")
-            .query_async(&mut conn)
+        let (tokens, last_refill): (Option<i32>, Option<u64>) = self
+            .run(
+                redis::pipe()
+                    .hget(&redis_key, "tokens")
+                    .hget(&redis_key, "last_refill")
+                    .query_async(conn),
+            )
             .await?;
 
         let refill_rate = max_requests as f64 / window_seconds as f64;
@@ -116,16 +492,18 @@ impl RateLimiter {
         let tokens_to_add = (elapsed as f64 * refill_rate) as i32;
         current_tokens = (current_tokens + tokens_to_add).min(max_tokens);
 
-        if current_tokens > 0 {
-            // Allow request and consume one token
-            current_tokens -= 1;
+        if let Some(remaining) = bucket_remaining(current_tokens, cost) {
+            // Allow request and consume its tokens
+            current_tokens = remaining;
 
-            redis::pipe()
-                .hset(&redis_key, "tokens", current_tokens)
-                .hset(&redis_key, "last_refill", now)
-                .expire(&redis_key, (window_seconds * 2) as i64)
-                .query_async::<()>(&mut conn)
-                .await?;
+            self.run(
+                redis::pipe()
+                    .hset(&redis_key, "tokens", current_tokens)
+                    .hset(&redis_key, "last_refill", now)
+                    .expire(&redis_key, (window_seconds * 2) as i64)
+                    .query_async::<()>(conn),
+            )
+            .await?;
 
             let reset_time = now + ((max_tokens - current_tokens) as f64 / refill_rate) as u64;
 
@@ -139,14 +517,235 @@ impl RateLimiter {
             Ok((true, current_tokens, reset_time))
         } else {
             // Rate limit exceeded
-            let reset_time = now + (1.0 / refill_rate) as u64;
+            let reset_time = now + ((cost - current_tokens).max(1) as f64 / refill_rate) as u64;
 
             warn!(
-                "Rate limit exceeded (token bucket): key={}, no tokens available",
-                key
+                "Rate limit exceeded (token bucket): key={}, {} tokens available, {} needed",
+                key, current_tokens, cost
             );
 
             Ok((false, 0, reset_time))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_loader::tests::rate_limit;
+
+    fn tier(name: &str, max_requests: i32, window_seconds: i32) -> RateLimit {
+        RateLimit {
+            window_seconds,
+            ..rate_limit(name, None, max_requests)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_redis_command_times_out() {
+        let slow_command = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            RedisResult::Ok(1)
+        };
+        let err = with_timeout(Some(Duration::from_millis(20)), slow_command)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out after 20ms"));
+
+        // Commands that answer in time, or run without a timeout, keep their result
+        let fast_command = async { RedisResult::Ok(1) };
+        assert_eq!(
+            with_timeout(Some(Duration::from_millis(20)), fast_command)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            with_timeout(None, async { RedisResult::Ok(1) })
+                .await
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_failure_modes() {
+        assert_eq!(FailureMode::parse_or_default("open"), FailureMode::Open);
+        assert_eq!(
+            FailureMode::parse_or_default(" Closed "),
+            FailureMode::Closed
+        );
+        assert_eq!(FailureMode::parse_or_default("bogus"), FailureMode::Closed);
+
+        // Fail-open lets the request through, fail-closed answers 503
+        assert_eq!(FailureMode::Open.error_response(), None);
+        let (status, body) = FailureMode::Closed.error_response().unwrap();
+        assert_eq!(status, 503);
+        assert!(body.contains("Rate limiter unavailable"));
+        assert!(body.contains(r#""error_code":"RATE_LIMITER_UNAVAILABLE""#));
+    }
+
+    #[test]
+    fn test_layered_tiers_are_ordered_and_keyed_separately() {
+        // 1000/hour AND 50/minute AND 10/second
+        let mut limits = vec![
+            tier("hourly", 1000, 3600),
+            tier("per-minute", 50, 60),
+            tier("per-second", 10, 1),
+        ];
+        order_tiers(&mut limits);
+        let names: Vec<_> = limits.iter().map(|limit| limit.name.as_str()).collect();
+        assert_eq!(names, ["per-second", "per-minute", "hourly"]);
+
+        // Same route, client and identifier type, but independent counters
+        let route_id = Uuid::new_v4();
+        let keys: std::collections::HashSet<_> = limits
+            .iter()
+            .map(|limit| Tier::new(route_id, limit, "192.0.2.1").key)
+            .collect();
+        assert_eq!(keys.len(), 3);
+    }
+
+    #[test]
+    fn test_weighted_requests_take_their_cost_from_the_budget() {
+        // 10 requests per window: a cost-4 request leaves 6, two more leave nothing for a third
+        assert_eq!(window_remaining(0, 10, 4), Some(6));
+        assert_eq!(window_remaining(4, 10, 4), Some(2));
+        assert_eq!(window_remaining(8, 10, 4), None);
+        // A cheap request still fits in what's left
+        assert_eq!(window_remaining(8, 10, 1), Some(1));
+        assert_eq!(window_remaining(9, 10, 1), Some(0));
+        assert_eq!(window_remaining(10, 10, 1), None);
+
+        assert_eq!(bucket_remaining(5, 3), Some(2));
+        assert_eq!(bucket_remaining(3, 3), Some(0));
+        assert_eq!(bucket_remaining(2, 3), None);
+    }
+
+    /// Limiter on `TEST_REDIS_URL`, for tests against a real Redis
+    pub(crate) fn test_limiter() -> RateLimiter {
+        let url = std::env::var("TEST_REDIS_URL")
+            .expect("needs Redis: set TEST_REDIS_URL and run cargo test -- --ignored");
+        RateLimiter::new(redis::Client::open(url).unwrap(), None)
+    }
+
+    /// Entries in the sliding window of `tier`, removing the window afterwards
+    async fn take_window(limiter: &RateLimiter, tier: &Tier<'_>) -> i32 {
+        let key = format!("ratelimit:{}", tier.key);
+        let mut conn = limiter.connection().await.unwrap();
+        let entries = redis::cmd("ZCARD")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        let _: () = redis::cmd("DEL")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        entries
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Redis: set TEST_REDIS_URL and run cargo test -- --ignored"]
+    async fn test_weighted_requests_are_one_entry_each() {
+        let limiter = test_limiter();
+        let limit = tier("per-minute", 10, 60);
+        let tiers = [Tier::new(Uuid::new_v4(), &limit, "192.0.2.1")];
+
+        for remaining in [6, 2] {
+            match limiter.check_tiers(&tiers, 4).await.unwrap() {
+                TierOutcome::Allowed(Some(status)) => assert_eq!(status.remaining, remaining),
+                outcome => panic!("a cost-4 request should fit, got {:?}", outcome),
+            }
+        }
+        assert!(matches!(
+            limiter.check_tiers(&tiers, 4).await.unwrap(),
+            TierOutcome::Rejected(_)
+        ));
+        // A cheap request still fits in what's left
+        assert!(matches!(
+            limiter.check_tiers(&tiers, 2).await.unwrap(),
+            TierOutcome::Allowed(_)
+        ));
+
+        // Three requests counted, three entries, whatever they cost
+        assert_eq!(take_window(&limiter, &tiers[0]).await, 3);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Redis: set TEST_REDIS_URL and run cargo test -- --ignored"]
+    async fn test_rejected_request_consumes_no_other_tier() {
+        let limiter = test_limiter();
+        let roomy = tier("per-minute", 10, 60);
+        let tight = tier("hourly", 2, 3600);
+        let route_id = Uuid::new_v4();
+        let tiers = [
+            Tier::new(route_id, &roomy, "192.0.2.1"),
+            Tier::new(route_id, &tight, "192.0.2.1"),
+        ];
+
+        for _ in 0..2 {
+            assert!(matches!(
+                limiter.check_tiers(&tiers, 1).await.unwrap(),
+                TierOutcome::Allowed(_)
+            ));
+        }
+        match limiter.check_tiers(&tiers, 1).await.unwrap() {
+            TierOutcome::Rejected(status) => assert_eq!(status.index, 1),
+            outcome => panic!("the hourly tier should reject, got {:?}", outcome),
+        }
+
+        // The tier checked first only counts the two requests that went through
+        assert_eq!(take_window(&limiter, &tiers[0]).await, 2);
+        assert_eq!(take_window(&limiter, &tiers[1]).await, 2);
+    }
+
+    #[test]
+    fn test_request_cost_comes_from_the_route_and_its_header() {
+        let mut route = crate::config_loader::tests::route("/search", Uuid::new_v4(), 0);
+        let headers = |cost: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-request-cost", cost.parse().unwrap());
+            headers
+        };
+        assert_eq!(request_cost(&route, &HeaderMap::new()), 1);
+
+        route.request_cost = Some(5);
+        assert_eq!(request_cost(&route, &headers("20")), 5);
+
+        route.request_cost_header = Some("X-Request-Cost".to_string());
+        assert_eq!(request_cost(&route, &headers("20")), 20);
+        assert_eq!(request_cost(&route, &HeaderMap::new()), 5);
+        // A header can't make a request cheaper, and out-of-bounds values are ignored
+        assert_eq!(request_cost(&route, &headers("2")), 5);
+        assert_eq!(request_cost(&route, &headers("0")), 5);
+        assert_eq!(request_cost(&route, &headers("-3")), 5);
+        assert_eq!(request_cost(&route, &headers("5000")), 5);
+        assert_eq!(request_cost(&route, &headers("lots")), 5);
+    }
+
+    #[test]
+    fn test_most_restrictive_tier_is_reported() {
+        let statuses = [
+            TierStatus {
+                index: 0,
+                remaining: 9,
+                reset_time: 101,
+            },
+            TierStatus {
+                index: 1,
+                remaining: 3,
+                reset_time: 160,
+            },
+            TierStatus {
+                index: 2,
+                remaining: 3,
+                reset_time: 3700,
+            },
+        ];
+
+        assert_eq!(most_restrictive(&statuses).map(|s| s.index), Some(1));
+        assert_eq!(most_restrictive(&[]), None);
+    }
+}
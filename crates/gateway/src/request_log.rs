@@ -0,0 +1,149 @@
+use chrono::Utc;
+use karateway_core::models::GatewayMetric;
+use pingora_core::{Error, ErrorSource};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Longest path stored, the length of the `gateway_metrics.path` column
+pub const MAX_PATH_LEN: usize = 500;
+
+/// Concise reason a request failed, or `None` when it succeeded
+///
+/// Only the error type and where it came from are kept. The error context
+/// and cause are left out on purpose: they can contain upstream addresses,
+/// query strings or header values, which don't belong in a table anyone
+/// with dashboard access can read.
+pub fn error_message(error: Option<&Error>, status: u16) -> Option<String> {
+    match error {
+        Some(error) => {
+            let etype = error.etype().as_str();
+            Some(match error.esource() {
+                ErrorSource::Unset => etype.to_string(),
+                source => format!("{} ({})", etype, source.as_str().to_lowercase()),
+            })
+        }
+        None if status >= 500 => Some(format!("Responded with status {}", status)),
+        None => None,
+    }
+}
+
+/// What is known about a request once it has finished
+pub struct CompletedRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    /// 0 when no response was written
+    pub status: u16,
+    pub elapsed: Duration,
+    pub route_id: Option<Uuid>,
+    pub backend_service_id: Option<Uuid>,
+    pub response_bytes: usize,
+    pub tag: Option<&'a str>,
+}
+
+impl CompletedRequest<'_> {
+    /// The `gateway_metrics` row for this request
+    pub fn to_metric(&self, error: Option<&Error>) -> GatewayMetric {
+        let mut metadata = serde_json::json!({ "response_bytes": self.response_bytes });
+        if let Some(tag) = self.tag {
+            metadata["tag"] = tag.into();
+        }
+
+        GatewayMetric {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            route_id: self.route_id,
+            method: Some(self.method.to_string()),
+            path: Some(truncate(self.path, MAX_PATH_LEN).to_string()),
+            status_code: (self.status > 0).then_some(self.status as i32),
+            response_time_ms: Some(self.elapsed.as_secs_f32() * 1000.0),
+            backend_service_id: self.backend_service_id,
+            error_message: error_message(error, self.status),
+            metadata,
+        }
+    }
+}
+
+/// Cut `value` to at most `max` bytes without splitting a character
+fn truncate(value: &str, max: usize) -> &str {
+    if value.len() <= max {
+        return value;
+    }
+
+    let mut end = max;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pingora_core::ErrorType;
+
+    fn request(status: u16) -> CompletedRequest<'static> {
+        CompletedRequest {
+            method: "GET",
+            path: "/orders/1",
+            status,
+            elapsed: Duration::from_millis(1500),
+            route_id: Some(Uuid::new_v4()),
+            backend_service_id: Some(Uuid::new_v4()),
+            response_bytes: 128,
+            tag: Some("admin"),
+        }
+    }
+
+    #[test]
+    fn test_failed_request_records_error_message() {
+        let mut error = Error::new_up(ErrorType::ConnectTimedout);
+        error.context = Some("connecting to 10.0.0.5:9001/internal?token=secret".into());
+
+        let metric = request(502).to_metric(Some(&*error));
+        assert_eq!(
+            metric.error_message.as_deref(),
+            Some("ConnectTimedout (upstream)")
+        );
+        assert_eq!(metric.status_code, Some(502));
+        assert_eq!(metric.metadata["response_bytes"], 128);
+        assert_eq!(metric.metadata["tag"], "admin");
+        assert!((metric.response_time_ms.unwrap() - 1500.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_error_message_without_pingora_error() {
+        assert_eq!(
+            request(503).to_metric(None).error_message.as_deref(),
+            Some("Responded with status 503")
+        );
+        assert_eq!(request(200).to_metric(None).error_message, None);
+        assert_eq!(request(404).to_metric(None).error_message, None);
+    }
+
+    #[test]
+    fn test_tag_is_only_stored_when_the_request_has_one() {
+        let tagged = request(200).to_metric(None);
+        assert_eq!(
+            tagged.metadata,
+            serde_json::json!({ "response_bytes": 128, "tag": "admin" })
+        );
+
+        let mut untagged = request(200);
+        untagged.tag = None;
+        assert_eq!(
+            untagged.to_metric(None).metadata,
+            serde_json::json!({ "response_bytes": 128 })
+        );
+    }
+
+    #[test]
+    fn test_long_paths_are_truncated() {
+        let path = format!("/{}", "é".repeat(MAX_PATH_LEN));
+        let mut request = request(200);
+        request.path = &path;
+
+        let stored = request.to_metric(None).path.unwrap();
+        assert!(stored.len() <= MAX_PATH_LEN);
+        assert!(path.starts_with(&stored));
+    }
+}
@@ -0,0 +1,397 @@
+use arc_swap::ArcSwapOption;
+use karateway_core::models::ApiRoute;
+use lru::LruCache;
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::BuildHasher;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+use crate::config_loader::{GatewayConfig, RouteKey};
+use crate::content_type;
+use crate::path_case::PathCase;
+use crate::query_match;
+
+/// Most shards a cache is split into; each has a lock of its own
+const MAX_SHARDS: usize = 16;
+
+/// The parts of a request that decide its match
+///
+/// Requests that only differ elsewhere share an entry: `/orders/1` and
+/// `/orders/2` both become the `/orders` route pattern they start with,
+/// query params no route looks at are dropped, and so are content-type
+/// parameters like a multipart boundary.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    method: String,
+    /// Longest route pattern the path starts with
+    prefix: String,
+    /// The params some route's `query_match` names, sorted
+    query: Vec<(String, String)>,
+    content_type: Option<String>,
+    api_version: Option<String>,
+    override_method: Option<String>,
+}
+
+/// The routes of one config snapshot, as far as building cache keys goes
+struct RouteIndex {
+    /// Snapshot the index was built from; the weak reference keeps its
+    /// address from being reused by a later snapshot
+    config: Weak<GatewayConfig>,
+    path_case: PathCase,
+    /// Route patterns, ASCII-lowercased when paths match regardless of case
+    patterns: HashSet<String>,
+    /// Distinct pattern lengths, longest first
+    pattern_lengths: Vec<usize>,
+    /// Query params named by some route's `query_match`
+    query_names: HashSet<String>,
+}
+
+impl RouteIndex {
+    fn new(config: &Arc<GatewayConfig>) -> Self {
+        let path_case = config.path_case;
+        let patterns: HashSet<String> = config
+            .routes
+            .iter()
+            .map(|route| match path_case {
+                PathCase::Sensitive => route.path_pattern.clone(),
+                PathCase::Insensitive => route.path_pattern.to_ascii_lowercase(),
+            })
+            .collect();
+        let mut pattern_lengths: Vec<usize> = patterns.iter().map(String::len).collect();
+        pattern_lengths.sort_unstable_by(|a, b| b.cmp(a));
+        pattern_lengths.dedup();
+        let query_names = config
+            .routes
+            .iter()
+            .filter_map(|route| route.query_match.as_object())
+            .flat_map(|conditions| conditions.keys().cloned())
+            .collect();
+
+        Self {
+            config: Arc::downgrade(config),
+            path_case,
+            patterns,
+            pattern_lengths,
+            query_names,
+        }
+    }
+
+    fn is_for(&self, config: &Arc<GatewayConfig>) -> bool {
+        std::ptr::eq(self.config.as_ptr(), Arc::as_ptr(config))
+    }
+
+    /// The entry `request` belongs to, `None` when no route pattern prefixes its path
+    ///
+    /// Every pattern a path starts with is a prefix of the longest one, so
+    /// paths sharing that longest pattern are matched by the same routes.
+    fn key(&self, request: &RouteKey) -> Option<CacheKey> {
+        let path = match self.path_case {
+            PathCase::Sensitive => std::borrow::Cow::Borrowed(request.path),
+            PathCase::Insensitive => std::borrow::Cow::Owned(request.path.to_ascii_lowercase()),
+        };
+        let prefix = self
+            .pattern_lengths
+            .iter()
+            .filter_map(|&len| path.get(..len))
+            .find(|head| self.patterns.contains(*head))?;
+
+        let mut query: Vec<(String, String)> = query_match::parse_query(request.query)
+            .into_iter()
+            .filter(|(name, _)| self.query_names.contains(name))
+            .collect();
+        query.sort();
+        query.dedup();
+
+        Some(CacheKey {
+            method: request.method.to_uppercase(),
+            prefix: prefix.to_string(),
+            query,
+            content_type: request.content_type.map(content_type::base_type),
+            api_version: request.api_version.map(str::to_string),
+            override_method: request.override_method.map(str::to_uppercase),
+        })
+    }
+}
+
+struct Shard {
+    /// Snapshot the entries were matched against
+    config: Weak<GatewayConfig>,
+    /// Index of the matched route in the snapshot's `routes`
+    entries: LruCache<CacheKey, usize>,
+}
+
+/// Remembers which route recent requests matched, so hot routes skip the scan
+///
+/// Entries are per route pattern rather than per URL, so paths with ids in
+/// them don't each take one. Only matches are cached, so requests for
+/// unknown paths can't push hot entries out. The entries belong to one
+/// config snapshot and are dropped as soon as a reload swaps it. The cache
+/// is split into shards by key, so concurrent requests rarely wait on the
+/// same lock.
+pub struct RouteCache {
+    /// Empty when disabled
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    /// Built once per snapshot, on its first request
+    index: ArcSwapOption<RouteIndex>,
+}
+
+impl RouteCache {
+    /// A cache holding about `capacity` matches, disabled with 0
+    ///
+    /// The capacity is spread over the shards, rounded up.
+    pub fn new(capacity: usize) -> Self {
+        let shards = capacity.min(MAX_SHARDS);
+        let per_shard = NonZeroUsize::new(capacity.div_ceil(shards.max(1)));
+        Self {
+            shards: per_shard
+                .map(|per_shard| {
+                    (0..shards)
+                        .map(|_| {
+                            Mutex::new(Shard {
+                                config: Weak::new(),
+                                entries: LruCache::new(per_shard),
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            hasher: RandomState::new(),
+            index: ArcSwapOption::empty(),
+        }
+    }
+
+    /// The index of `config`, building it when the snapshot is new
+    fn index(&self, config: &Arc<GatewayConfig>) -> Arc<RouteIndex> {
+        if let Some(index) = self.index.load_full().filter(|index| index.is_for(config)) {
+            return index;
+        }
+        let index = Arc::new(RouteIndex::new(config));
+        self.index.store(Some(index.clone()));
+        index
+    }
+
+    /// The route `request` matched in `config`, running `find` on a miss
+    pub fn find_route<'a>(
+        &self,
+        config: &'a Arc<GatewayConfig>,
+        request: RouteKey,
+        find: impl FnOnce(&'a GatewayConfig) -> Option<&'a ApiRoute>,
+    ) -> Option<&'a ApiRoute> {
+        if self.shards.is_empty() {
+            return find(config);
+        }
+        let Some(key) = self.index(config).key(&request) else {
+            return find(config);
+        };
+
+        let shard = &self.shards[self.hasher.hash_one(&key) as usize % self.shards.len()];
+        {
+            let mut shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+            if !std::ptr::eq(shard.config.as_ptr(), Arc::as_ptr(config)) {
+                shard.entries.clear();
+                shard.config = Arc::downgrade(config);
+            }
+            if let Some(&index) = shard.entries.get(&key) {
+                return config.routes.get(index);
+            }
+        }
+
+        // Matched without holding the lock; other requests keep hitting the cache meanwhile
+        let route = find(config)?;
+        let index = config
+            .routes
+            .iter()
+            .position(|candidate| std::ptr::eq(candidate, route))?;
+
+        let mut shard = shard.lock().unwrap_or_else(PoisonError::into_inner);
+        // A reload may have replaced the snapshot while matching
+        if std::ptr::eq(shard.config.as_ptr(), Arc::as_ptr(config)) {
+            shard.entries.put(key, index);
+        }
+        Some(route)
+    }
+
+    /// Number of cached matches
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| {
+                shard
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .entries
+                    .len()
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_loader::tests::{route, service};
+    use std::time::Instant;
+
+    fn config(paths: &[&str]) -> Arc<GatewayConfig> {
+        let backend = service("orders", "http://orders:9000");
+        let mut config = GatewayConfig::new();
+        config.routes = paths
+            .iter()
+            .map(|path| route(path, backend.id, 0))
+            .collect();
+        config.services.insert(backend.id, backend);
+        Arc::new(config)
+    }
+
+    fn key(path: &str) -> RouteKey<'_> {
+        RouteKey {
+            method: "GET",
+            path,
+            query: None,
+            content_type: None,
+            api_version: None,
+            override_method: None,
+        }
+    }
+
+    fn lookup<'a>(
+        cache: &RouteCache,
+        config: &'a Arc<GatewayConfig>,
+        path: &str,
+    ) -> Option<&'a ApiRoute> {
+        cache.find_route(config, key(path), |config| {
+            config.find_route(path, "GET", None, None, None)
+        })
+    }
+
+    #[test]
+    fn test_repeated_requests_hit_the_cache() {
+        let cache = RouteCache::new(16);
+        let config = config(&["/api/orders", "/api/users"]);
+
+        let matched = lookup(&cache, &config, "/api/orders/1").unwrap();
+        assert_eq!(matched.path_pattern, "/api/orders");
+
+        // A hit never runs the scan
+        let cached = cache.find_route(&config, key("/api/orders/1"), |_| unreachable!());
+        assert_eq!(cached.unwrap().id, matched.id);
+
+        // Misses aren't cached
+        assert!(lookup(&cache, &config, "/unknown").is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_requests_for_the_same_route_share_an_entry() {
+        let cache = RouteCache::new(16);
+        let mut config = config(&["/api/orders"]);
+        let mut beta = config.routes[0].clone();
+        beta.id = uuid::Uuid::new_v4();
+        beta.query_match = serde_json::json!({"version": "beta"});
+        Arc::make_mut(&mut config).routes.push(beta);
+
+        let matched = lookup(&cache, &config, "/api/orders/1").unwrap();
+        for path in ["/api/orders/2", "/api/orders/1/items"] {
+            let cached = cache.find_route(&config, key(path), |_| unreachable!());
+            assert_eq!(cached.unwrap().id, matched.id);
+        }
+        // Params no route looks at and content-type parameters don't make a new entry
+        let request = RouteKey {
+            query: Some("page=2"),
+            content_type: Some("multipart/form-data; boundary=abc"),
+            ..key("/api/orders/3")
+        };
+        assert!(cache
+            .find_route(&config, request, |_| unreachable!())
+            .is_some());
+        assert_eq!(cache.len(), 1);
+
+        // A param a route does look at does
+        let request = RouteKey {
+            query: Some("version=beta&page=2"),
+            ..key("/api/orders/3")
+        };
+        let beta = cache.find_route(&config, request, |config| {
+            config.find_route("/api/orders/3", "GET", request.query, None, None)
+        });
+        assert_eq!(beta.unwrap().query_match["version"], "beta");
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_cache_is_invalidated_when_routes_change() {
+        let cache = RouteCache::new(16);
+        let before = config(&["/api"]);
+        assert_eq!(
+            lookup(&cache, &before, "/api/orders").unwrap().path_pattern,
+            "/api"
+        );
+
+        // A reload adds a more specific route with a higher priority
+        let mut reloaded = (*before).clone();
+        let mut orders = reloaded.routes[0].clone();
+        orders.id = uuid::Uuid::new_v4();
+        orders.path_pattern = "/api/orders".to_string();
+        orders.priority = 10;
+        reloaded.routes.push(orders);
+        let reloaded = Arc::new(reloaded);
+
+        assert_eq!(
+            lookup(&cache, &reloaded, "/api/orders")
+                .unwrap()
+                .path_pattern,
+            "/api/orders"
+        );
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_is_bounded() {
+        let cache = RouteCache::new(1);
+        let config = config(&["/a", "/b", "/c"]);
+        for path in ["/a", "/b", "/c"] {
+            lookup(&cache, &config, path);
+        }
+        assert_eq!(cache.len(), 1);
+
+        let disabled = RouteCache::new(0);
+        assert!(lookup(&disabled, &config, "/a").is_some());
+        assert_eq!(disabled.len(), 0);
+    }
+
+    /// `cargo test -p karateway-gateway bench_route_cache -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_route_cache() {
+        let paths: Vec<String> = (0..500).map(|i| format!("/api/service-{}", i)).collect();
+        let config = config(&paths.iter().map(String::as_str).collect::<Vec<_>>());
+        let hot = [
+            "/api/service-499/items",
+            "/api/service-250/items",
+            "/api/service-7",
+        ];
+        let requests = 100_000;
+
+        for (name, cache) in [
+            ("scan", RouteCache::new(0)),
+            ("cached", RouteCache::new(1024)),
+        ] {
+            let started = Instant::now();
+            for i in 0..requests {
+                assert!(lookup(&cache, &config, hot[i % hot.len()]).is_some());
+            }
+            let elapsed = started.elapsed();
+            println!(
+                "{:>6}: {:?} for {} lookups over {} routes ({:?} each)",
+                name,
+                elapsed,
+                requests,
+                config.routes.len(),
+                elapsed / requests as u32
+            );
+        }
+    }
+}
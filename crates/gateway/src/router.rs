@@ -1,38 +1,104 @@
-use karateway_core::models::{ApiRoute, BackendService, RateLimit, WhitelistRule};
+use http::HeaderMap;
+use karateway_core::models::{
+    effective_whitelist_rules, ApiRoute, BackendService, RateLimit, WhitelistRule,
+};
 use std::sync::Arc;
 use tracing::{debug, warn};
 use uuid::Uuid;
 
-use crate::config_loader::ConfigLoader;
+use crate::api_version;
+use crate::config_loader::{ConfigLoader, RouteKey, RouteMiss};
+use crate::path_case::PathCase;
+use crate::route_cache::RouteCache;
+use crate::tagging;
+use crate::upstream_tls::ClientCert;
+use crate::whitelist_validator::CustomRuleConditions;
 
 /// Router handles matching incoming requests to configured routes
 pub struct Router {
     config_loader: Arc<ConfigLoader>,
+    /// Catch-all rate limit for routes without a route-specific limit
+    default_rate_limit: Option<RateLimit>,
+    /// Honour `X-HTTP-Method-Override` on every route, not just opted-in ones
+    method_override_everywhere: bool,
+    /// Recent matches of the current config snapshot
+    route_cache: RouteCache,
 }
 
 impl Router {
-    pub fn new(config_loader: Arc<ConfigLoader>) -> Self {
-        Self { config_loader }
+    pub fn new(
+        config_loader: Arc<ConfigLoader>,
+        default_rate_limit: Option<RateLimit>,
+        method_override_everywhere: bool,
+        route_cache_size: usize,
+    ) -> Self {
+        Self {
+            config_loader,
+            default_rate_limit,
+            method_override_everywhere,
+            route_cache: RouteCache::new(route_cache_size),
+        }
     }
 
     /// Find the matching route and backend service for a request
-    pub fn route_request(&self, path: &str, method: &str) -> Option<(ApiRoute, BackendService)> {
+    ///
+    /// `override_method` is the method requested via `X-HTTP-Method-Override`;
+    /// the matched route's method tells whether it was applied. `api_version`
+    /// is the version extracted from the request, if any. A route whose
+    /// backend is disabled or removed goes to its first live fallback, and is
+    /// reported as [`RouteMiss::NoBackend`] when it has none.
+    pub fn route_request(
+        &self,
+        path: &str,
+        method: &str,
+        query: Option<&str>,
+        content_type: Option<&str>,
+        api_version: Option<&str>,
+        override_method: Option<&str>,
+    ) -> Result<(ApiRoute, BackendService), RouteMiss> {
         debug!("Routing request: {} {}", method, path);
 
+        // Resolve route and service from the same snapshot so a concurrent
+        // reload can't hand us a route whose backend has just been removed
+        let config = self.config_loader.get_config();
+
         // Find matching route
-        let route = self.config_loader.find_route(path, method)?;
+        let key = RouteKey {
+            method,
+            path,
+            query,
+            content_type,
+            api_version,
+            override_method,
+        };
+        let matched = self
+            .route_cache
+            .find_route(&config, key, |config| {
+                config.find_route_with_override(key, self.method_override_everywhere)
+            })
+            .cloned();
+        let Some(route) = matched else {
+            return Err(config.route_miss(key, self.method_override_everywhere));
+        };
 
         debug!(
             "Matched route: {} {} -> service {}",
-            route.method, route.path_pattern, route.backend_service_id
+            route.method,
+            route.path_pattern,
+            route.active_backend_service_id()
         );
 
-        // Get the backend service
-        let service = self.config_loader.get_service(&route.backend_service_id)?;
-
-        if !service.is_active {
-            warn!("Backend service {} is not active", service.id);
-            return None;
+        // The backend the route's active color points at, or a fallback while it's disabled
+        let Some(service) = config.live_backend(&route).cloned() else {
+            return Err(RouteMiss::no_backend(&route));
+        };
+        if service.id != route.active_backend_service_id() {
+            warn!(
+                "Backend service {} of route {} is not active, falling back to {}",
+                route.active_backend_service_id(),
+                route.id,
+                service.name
+            );
         }
 
         debug!(
@@ -40,15 +106,23 @@ impl Router {
             service.name, service.base_url
         );
 
-        Some((route, service))
+        Ok((route, service))
     }
 
     /// Transform the request path according to route configuration
-    pub fn transform_path(&self, route: &ApiRoute, original_path: &str) -> String {
-        if route.strip_path_prefix {
+    ///
+    /// Transforms apply in order: `strip_path_prefix` removes the matched
+    /// `path_pattern`, then `upstream_path_prefix` is prepended. A `/public`
+    /// route with prefix `/internal` thus sends `/public/foo` to
+    /// `/internal/public/foo`, or to `/internal/foo` when it also strips.
+    ///
+    /// The prefix is stripped per `path_case`, the policy the route was
+    /// matched with; the rest of the path keeps the request's case.
+    pub fn transform_path(route: &ApiRoute, original_path: &str, path_case: PathCase) -> String {
+        let path = if route.strip_path_prefix {
             // Remove the matched prefix
             let prefix = &route.path_pattern;
-            if let Some(stripped) = original_path.strip_prefix(prefix) {
+            if let Some(stripped) = path_case.strip_prefix(original_path, prefix) {
                 // Ensure the path starts with /
                 if stripped.is_empty() || !stripped.starts_with('/') {
                     format!("/{}", stripped)
@@ -60,6 +134,23 @@ impl Router {
             }
         } else {
             original_path.to_string()
+        };
+
+        let prefix = route
+            .upstream_path_prefix
+            .as_deref()
+            .unwrap_or_default()
+            .trim()
+            .trim_matches('/');
+        if prefix.is_empty() {
+            return path;
+        }
+
+        // The root of the request maps to the prefix itself
+        if path == "/" {
+            format!("/{}", prefix)
+        } else {
+            format!("/{}{}", prefix, path)
         }
     }
 
@@ -83,6 +174,57 @@ impl Router {
         }
     }
 
+    /// The live backend of the route's inactive color, for requests forcing the canary
+    pub fn canary_service(&self, route: &ApiRoute) -> Option<BackendService> {
+        self.config_loader
+            .get_config()
+            .canary_service(route)
+            .cloned()
+    }
+
+    /// The live fallback backends of a route, in failover order
+    pub fn fallback_services(&self, route: &ApiRoute) -> Vec<BackendService> {
+        self.config_loader
+            .get_config()
+            .fallback_services(route)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Client certificate to connect to a backend with, if it requires mutual TLS
+    pub fn get_client_cert(&self, service_id: &Uuid) -> Option<ClientCert> {
+        self.config_loader
+            .get_config()
+            .client_certs
+            .get(service_id)
+            .cloned()
+    }
+
+    /// How route paths are matched
+    pub fn path_case(&self) -> PathCase {
+        self.config_loader.path_case()
+    }
+
+    /// Metrics tag for a request, from the first matching tag rule
+    pub fn metric_tag(&self, path: &str, headers: &HeaderMap) -> Option<String> {
+        let config = self.config_loader.get_config();
+        tagging::request_tag(&config.metric_tag_rules, path, headers, self.path_case())
+            .map(str::to_string)
+    }
+
+    /// Whether any active route routes on `version`, which bounds the metrics labels
+    pub fn is_routed_api_version(&self, version: &str) -> bool {
+        self.config_loader.get_config().routes.iter().any(|route| {
+            route
+                .api_version
+                .as_deref()
+                .and_then(api_version::normalize)
+                .as_deref()
+                == Some(version)
+        })
+    }
+
     /// Get rate limits for a route
     pub fn get_rate_limits(&self, route_id: &Uuid) -> Option<Vec<RateLimit>> {
         let config = self.config_loader.get_config();
@@ -93,20 +235,7 @@ impl Router {
             config.rate_limits.keys().collect::<Vec<_>>()
         );
 
-        // Get route-specific rate limits
-        let mut limits = config
-            .rate_limits
-            .get(&Some(*route_id))
-            .cloned()
-            .unwrap_or_default();
-
-        debug!("Found {} route-specific rate limits", limits.len());
-
-        // Also get global rate limits (where api_route_id is None)
-        if let Some(global_limits) = config.rate_limits.get(&None) {
-            debug!("Found {} global rate limits", global_limits.len());
-            limits.extend(global_limits.clone());
-        }
+        let limits = config.rate_limits_for(route_id, self.default_rate_limit.as_ref());
 
         if limits.is_empty() {
             debug!("No rate limits found for route {}", route_id);
@@ -121,33 +250,44 @@ impl Router {
         }
     }
 
+    /// Compiled conditions of the custom whitelist rules, by rule ID
+    pub fn get_custom_rule_conditions(&self) -> Arc<CustomRuleConditions> {
+        self.config_loader
+            .get_config()
+            .custom_rule_conditions
+            .clone()
+    }
+
     /// Get whitelist rules for a route
     pub fn get_whitelist_rules(&self, route_id: &Uuid) -> Option<Vec<WhitelistRule>> {
         let config = self.config_loader.get_config();
 
         debug!("Looking for whitelist rules for route_id: {}", route_id);
 
-        // Get route-specific whitelist rules
-        let mut rules = config
+        let route_rules = config
             .whitelist_rules
             .get(&Some(*route_id))
-            .cloned()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let global_rules = config
+            .whitelist_rules
+            .get(&None)
+            .map(Vec::as_slice)
             .unwrap_or_default();
 
-        debug!("Found {} route-specific whitelist rules", rules.len());
+        debug!(
+            "Found {} route-specific and {} global whitelist rules",
+            route_rules.len(),
+            global_rules.len()
+        );
 
-        // Also get global whitelist rules (where api_route_id is None)
-        if let Some(global_rules) = config.whitelist_rules.get(&None) {
-            debug!("Found {} global whitelist rules", global_rules.len());
-            rules.extend(global_rules.clone());
-        }
+        // Sorted by priority (highest first)
+        let rules = effective_whitelist_rules(route_rules, global_rules);
 
         if rules.is_empty() {
             debug!("No whitelist rules found for route {}", route_id);
             None
         } else {
-            // Sort by priority (highest first)
-            rules.sort_by(|a, b| b.priority.cmp(&a.priority));
             debug!(
                 "Returning {} total whitelist rules for route {}",
                 rules.len(),
@@ -161,7 +301,7 @@ impl Router {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use karateway_core::models::HttpMethod;
+    use karateway_core::models::{DeploymentColor, HttpMethod};
     use uuid::Uuid;
 
     #[test]
@@ -171,9 +311,29 @@ mod tests {
             path_pattern: "/api/v1".to_string(),
             method: HttpMethod::GET,
             backend_service_id: Uuid::new_v4(),
+            green_backend_service_id: None,
+            active_color: DeploymentColor::Blue,
+            query_match: serde_json::json!({}),
             strip_path_prefix: true,
             preserve_host_header: true,
+            allow_method_override: false,
             timeout_ms: Some(5000),
+            idle_timeout_ms: None,
+            fallback_backend_service_ids: serde_json::json!([]),
+            access_log_enabled: true,
+            cookie_domain: None,
+            cookie_path: None,
+            decompress_response: false,
+            api_version: None,
+            timing_headers: false,
+            debug_log_body: false,
+            content_type_match: None,
+            upstream_path_prefix: None,
+            coalesce_requests: false,
+            queue_depth: None,
+            queue_timeout_ms: None,
+            request_cost: None,
+            request_cost_header: None,
             priority: 100,
             is_active: true,
             metadata: serde_json::Value::Null,
@@ -203,4 +363,66 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    fn test_route(path_pattern: &str, strip: bool, upstream_path_prefix: Option<&str>) -> ApiRoute {
+        let mut route = crate::config_loader::tests::route(path_pattern, Uuid::new_v4(), 100);
+        route.strip_path_prefix = strip;
+        route.upstream_path_prefix = upstream_path_prefix.map(str::to_string);
+        route
+    }
+
+    #[test]
+    fn test_transform_path_with_prefix() {
+        let route = test_route("/public", false, Some("/internal"));
+        assert_eq!(
+            Router::transform_path(&route, "/public/foo", PathCase::Sensitive),
+            "/internal/public/foo"
+        );
+
+        // Slashes around the prefix don't matter
+        let route = test_route("/public", false, Some("internal/"));
+        assert_eq!(
+            Router::transform_path(&route, "/public/foo", PathCase::Sensitive),
+            "/internal/public/foo"
+        );
+
+        let route = test_route("/public", false, Some(""));
+        assert_eq!(
+            Router::transform_path(&route, "/public/foo", PathCase::Sensitive),
+            "/public/foo"
+        );
+    }
+
+    #[test]
+    fn test_transform_path_strips_before_prepending() {
+        let route = test_route("/api/v1", true, Some("/internal/v2"));
+        assert_eq!(
+            Router::transform_path(&route, "/api/v1/users", PathCase::Sensitive),
+            "/internal/v2/users"
+        );
+        assert_eq!(
+            Router::transform_path(&route, "/api/v1", PathCase::Sensitive),
+            "/internal/v2"
+        );
+
+        let route = test_route("/api/v1", true, None);
+        assert_eq!(
+            Router::transform_path(&route, "/api/v1/users", PathCase::Sensitive),
+            "/users"
+        );
+    }
+
+    #[test]
+    fn test_transform_path_strips_mixed_case_prefix_per_policy() {
+        let route = test_route("/api/v1", true, Some("/internal"));
+        assert_eq!(
+            Router::transform_path(&route, "/API/V1/Users", PathCase::Insensitive),
+            "/internal/Users"
+        );
+        // A case-sensitive route never matched this path, so nothing is stripped
+        assert_eq!(
+            Router::transform_path(&route, "/API/V1/Users", PathCase::Sensitive),
+            "/internal/API/V1/Users"
+        );
+    }
 }
@@ -0,0 +1,97 @@
+/// FNV-1a offset basis (64-bit)
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+/// FNV-1a prime (64-bit)
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Stable 64-bit hash of a seed
+///
+/// `std`'s `DefaultHasher` is not guaranteed to be stable between Rust
+/// releases, so FNV-1a is used to keep draws reproducible across builds.
+pub fn seed_hash(seed: &str) -> u64 {
+    seed.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Pick an item with probability proportional to its weight
+///
+/// Shared by canary and weighted routing. Seeding with the request id instead
+/// of a fresh RNG means a retried request lands on the same variant as the
+/// original attempt, on any gateway instance. The same `seed` always yields
+/// the same item for the same set of weights.
+///
+/// Items with a weight of 0 are never selected; returns `None` when no item
+/// has a positive weight.
+pub fn select_weighted<'a, T>(
+    items: &'a [T],
+    weight: impl Fn(&T) -> u32,
+    seed: &str,
+) -> Option<&'a T> {
+    let total: u64 = items.iter().map(|item| weight(item) as u64).sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut point = seed_hash(seed) % total;
+    for item in items {
+        let weight = weight(item) as u64;
+        if point < weight {
+            return Some(item);
+        }
+        point -= weight;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARIANTS: [(&str, u32); 2] = [("stable", 90), ("canary", 10)];
+
+    fn pick(seed: &str) -> &'static str {
+        select_weighted(&VARIANTS, |(_, weight)| *weight, seed)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_same_request_id_yields_same_variant() {
+        for i in 0..100 {
+            let request_id = format!("req-{}", i);
+            let first = pick(&request_id);
+            for _ in 0..10 {
+                assert_eq!(pick(&request_id), first);
+            }
+        }
+    }
+
+    #[test]
+    fn test_selection_follows_weights() {
+        let canary = (0..10_000)
+            .filter(|i| pick(&format!("req-{}", i)) == "canary")
+            .count();
+
+        // 10% canary, with some slack for the hash distribution
+        assert!(
+            (700..1300).contains(&canary),
+            "canary picked {} times",
+            canary
+        );
+    }
+
+    #[test]
+    fn test_zero_weights_are_never_selected() {
+        let items = [("off", 0), ("on", 1)];
+        for i in 0..100 {
+            let seed = format!("req-{}", i);
+            let picked = select_weighted(&items, |(_, weight)| *weight, &seed);
+            assert_eq!(picked.map(|(name, _)| *name), Some("on"));
+        }
+
+        let none: [(&str, u32); 1] = [("off", 0)];
+        assert!(select_weighted(&none, |(_, weight)| *weight, "req").is_none());
+        assert!(select_weighted(&[] as &[(&str, u32)], |(_, weight)| *weight, "req").is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::{bail, Result};
+use karateway_config::AppConfig;
+use pingora_core::listeners::TcpSocketOptions;
+use pingora_core::protocols::l4::ext::TcpKeepalive;
+use std::time::Duration;
+
+/// Largest idle time and probe interval Linux accepts for TCP keepalive, in seconds
+const MAX_KEEPALIVE_SECONDS: u64 = 32_767;
+
+/// Largest number of unanswered keepalive probes Linux accepts
+const MAX_KEEPALIVE_PROBES: usize = 127;
+
+/// Largest TCP Fast Open queue, the same as the accept backlog pingora requests
+const MAX_FASTOPEN_QUEUE: usize = 65_535;
+
+/// TCP options of the proxy listeners
+///
+/// The accept backlog (65535, capped by `net.core.somaxconn`), `SO_REUSEADDR`
+/// and `TCP_NODELAY` on accepted connections are set by pingora and not
+/// configurable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListenerSocketOptions {
+    /// Idle time, probe interval and probe count, `None` to leave keepalive off
+    pub keepalive: Option<(Duration, Duration, usize)>,
+    /// Bind with `SO_REUSEPORT`, so a new gateway can start alongside the old one
+    pub reuse_port: bool,
+    /// TCP Fast Open queue length, `None` to leave it off
+    pub fastopen_queue: Option<usize>,
+}
+
+impl ListenerSocketOptions {
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        Self::new(
+            config.gateway_tcp_keepalive_seconds,
+            config.gateway_tcp_keepalive_interval_seconds,
+            config.gateway_tcp_keepalive_probes,
+            config.gateway_so_reuseport,
+            config.gateway_tcp_fastopen_queue,
+        )
+    }
+
+    /// Check the options against what the kernel accepts; zero turns keepalive or Fast Open off
+    pub fn new(
+        keepalive_seconds: u64,
+        interval: u64,
+        probes: usize,
+        reuse_port: bool,
+        fastopen_queue: usize,
+    ) -> Result<Self> {
+        let keepalive = match keepalive_seconds {
+            0 => None,
+            idle => {
+                if idle > MAX_KEEPALIVE_SECONDS {
+                    bail!(
+                        "GATEWAY_TCP_KEEPALIVE_SECONDS must be at most {}, got {}",
+                        MAX_KEEPALIVE_SECONDS,
+                        idle
+                    );
+                }
+                if !(1..=MAX_KEEPALIVE_SECONDS).contains(&interval) {
+                    bail!(
+                        "GATEWAY_TCP_KEEPALIVE_INTERVAL_SECONDS must be between 1 and {}, got {}",
+                        MAX_KEEPALIVE_SECONDS,
+                        interval
+                    );
+                }
+                if !(1..=MAX_KEEPALIVE_PROBES).contains(&probes) {
+                    bail!(
+                        "GATEWAY_TCP_KEEPALIVE_PROBES must be between 1 and {}, got {}",
+                        MAX_KEEPALIVE_PROBES,
+                        probes
+                    );
+                }
+                Some((
+                    Duration::from_secs(idle),
+                    Duration::from_secs(interval),
+                    probes,
+                ))
+            }
+        };
+
+        let fastopen_queue = match fastopen_queue {
+            0 => None,
+            queue if queue > MAX_FASTOPEN_QUEUE => bail!(
+                "GATEWAY_TCP_FASTOPEN_QUEUE must be at most {}, got {}",
+                MAX_FASTOPEN_QUEUE,
+                queue
+            ),
+            queue => Some(queue),
+        };
+
+        Ok(Self {
+            keepalive,
+            reuse_port,
+            fastopen_queue,
+        })
+    }
+
+    /// The options to bind a listener with
+    pub fn tcp_options(&self) -> TcpSocketOptions {
+        TcpSocketOptions {
+            tcp_keepalive: self.keepalive.map(|(idle, interval, count)| TcpKeepalive {
+                idle,
+                interval,
+                count,
+                // Zero keeps the kernel default
+                #[cfg(target_os = "linux")]
+                user_timeout: Duration::ZERO,
+            }),
+            tcp_fastopen: self.fastopen_queue,
+            so_reuseport: self.reuse_port.then_some(true),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_socket_options_are_range_checked() {
+        let options = ListenerSocketOptions::new(60, 10, 6, true, 1024).unwrap();
+        assert_eq!(
+            options.keepalive,
+            Some((Duration::from_secs(60), Duration::from_secs(10), 6))
+        );
+        assert_eq!(options.fastopen_queue, Some(1024));
+        assert!(options.reuse_port);
+
+        // The defaults leave everything as pingora sets it
+        assert_eq!(
+            ListenerSocketOptions::new(0, 10, 6, false, 0).unwrap(),
+            ListenerSocketOptions::default()
+        );
+
+        assert!(ListenerSocketOptions::new(40_000, 10, 6, false, 0).is_err());
+        assert!(ListenerSocketOptions::new(60, 0, 6, false, 0).is_err());
+        assert!(ListenerSocketOptions::new(60, 10, 0, false, 0).is_err());
+        assert!(ListenerSocketOptions::new(0, 10, 6, false, 100_000).is_err());
+        // Keepalive settings only matter when it's on
+        assert!(ListenerSocketOptions::new(0, 0, 0, false, 0).is_ok());
+    }
+}
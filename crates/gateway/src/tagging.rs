@@ -0,0 +1,116 @@
+use http::HeaderMap;
+use karateway_core::models::{MetricTagRule, TagMatchType};
+
+use crate::path_case::PathCase;
+
+/// Tag of the first rule (in priority order) that matches a request
+///
+/// A request carries at most one tag, which keeps the number of metric
+/// series bounded by the number of rules rather than by traffic. Path
+/// prefixes are compared the way route paths are.
+pub fn request_tag<'a>(
+    rules: &'a [MetricTagRule],
+    path: &str,
+    headers: &HeaderMap,
+    path_case: PathCase,
+) -> Option<&'a str> {
+    rules
+        .iter()
+        .find(|rule| matches(rule, path, headers, path_case))
+        .map(|rule| rule.tag.as_str())
+}
+
+fn matches(rule: &MetricTagRule, path: &str, headers: &HeaderMap, path_case: PathCase) -> bool {
+    match rule.match_type {
+        TagMatchType::PathPrefix => path_case.starts_with(path, &rule.pattern),
+        TagMatchType::Header => {
+            let Some(value) = rule
+                .header_name
+                .as_deref()
+                .and_then(|name| headers.get(name))
+            else {
+                return false;
+            };
+            // An empty pattern only requires the header to be present
+            rule.pattern.is_empty() || value.to_str().is_ok_and(|value| value == rule.pattern)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn rule(
+        tag: &str,
+        match_type: TagMatchType,
+        header: Option<&str>,
+        pattern: &str,
+    ) -> MetricTagRule {
+        MetricTagRule {
+            id: Uuid::new_v4(),
+            tag: tag.to_string(),
+            match_type,
+            header_name: header.map(str::to_string),
+            pattern: pattern.to_string(),
+            is_active: true,
+            priority: 0,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_requests_are_tagged_by_path_and_header() {
+        let rules = vec![
+            rule(
+                "team-payments",
+                TagMatchType::Header,
+                Some("X-Team"),
+                "payments",
+            ),
+            rule("admin", TagMatchType::PathPrefix, None, "/admin"),
+            rule("mobile", TagMatchType::Header, Some("X-App-Version"), ""),
+        ];
+        let mut headers = HeaderMap::new();
+
+        assert_eq!(
+            request_tag(&rules, "/admin/users", &headers, PathCase::Sensitive),
+            Some("admin")
+        );
+        assert_eq!(
+            request_tag(&rules, "/api/orders", &headers, PathCase::Sensitive),
+            None
+        );
+
+        // Path rules fold case when route paths do
+        assert_eq!(
+            request_tag(&rules, "/ADMIN/users", &headers, PathCase::Sensitive),
+            None
+        );
+        assert_eq!(
+            request_tag(&rules, "/ADMIN/users", &headers, PathCase::Insensitive),
+            Some("admin")
+        );
+
+        headers.insert("X-App-Version", "4.2".parse().unwrap());
+        assert_eq!(
+            request_tag(&rules, "/api/orders", &headers, PathCase::Sensitive),
+            Some("mobile")
+        );
+
+        // Earlier rules win
+        headers.insert("X-Team", "payments".parse().unwrap());
+        assert_eq!(
+            request_tag(&rules, "/admin/users", &headers, PathCase::Sensitive),
+            Some("team-payments")
+        );
+
+        headers.insert("X-Team", "search".parse().unwrap());
+        assert_eq!(
+            request_tag(&rules, "/admin/users", &headers, PathCase::Sensitive),
+            Some("admin")
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use http::HeaderMap;
+use karateway_config::ip_allowlist::IpAllowlist;
+use karateway_config::AppConfig;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Request header asking for a different total timeout, in milliseconds
+pub const TIMEOUT_OVERRIDE_HEADER: &str = "X-Gateway-Timeout-Ms";
+
+/// Request header carrying the debug token that makes a client trusted
+pub const DEBUG_TOKEN_HEADER: &str = "X-Gateway-Debug-Token";
+
+/// Who may override a route's total timeout with [`TIMEOUT_OVERRIDE_HEADER`]
+///
+/// A client is trusted when its peer IP is in `trusted_networks` or it sends
+/// the configured token. With neither configured nobody is, even when enabled.
+/// The peer IP is the connection's (or the PROXY protocol client), never one
+/// from `X-Forwarded-For`, which any client can set.
+#[derive(Debug, Clone)]
+pub struct TimeoutOverride {
+    pub enabled: bool,
+    /// `None` when no trusted IPs are configured
+    pub trusted_networks: Option<IpAllowlist>,
+    pub token: Option<String>,
+    /// Requested timeouts above this are clamped to it
+    pub max: Duration,
+}
+
+impl TimeoutOverride {
+    pub fn from_config(config: &AppConfig) -> Self {
+        let trusted_ips = config.gateway_timeout_override_trusted_ips.trim();
+        Self {
+            enabled: config.gateway_timeout_override_enabled,
+            trusted_networks: (!trusted_ips.is_empty()).then(|| IpAllowlist::parse(trusted_ips)),
+            token: config
+                .gateway_timeout_override_token
+                .clone()
+                .filter(|token| !token.is_empty()),
+            max: Duration::from_millis(config.gateway_timeout_override_max_ms),
+        }
+    }
+
+    /// The total timeout a request asks for, if it may have one
+    ///
+    /// Untrusted clients and invalid values keep the route's own timeout.
+    pub fn requested(&self, headers: &HeaderMap, peer_ip: Option<&str>) -> Option<Duration> {
+        if !self.enabled {
+            return None;
+        }
+        let value = headers.get(TIMEOUT_OVERRIDE_HEADER)?;
+
+        if !self.is_trusted(headers, peer_ip) {
+            warn!(
+                "Ignoring {} from untrusted client {:?}",
+                TIMEOUT_OVERRIDE_HEADER, peer_ip
+            );
+            return None;
+        }
+
+        let millis = match value
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+        {
+            Some(millis) if millis > 0 => millis,
+            _ => {
+                debug!(
+                    "Ignoring invalid {} value {:?}",
+                    TIMEOUT_OVERRIDE_HEADER, value
+                );
+                return None;
+            }
+        };
+
+        Some(Duration::from_millis(millis).min(self.max))
+    }
+
+    fn is_trusted(&self, headers: &HeaderMap, peer_ip: Option<&str>) -> bool {
+        let trusted_ip = self
+            .trusted_networks
+            .as_ref()
+            .is_some_and(|networks| networks.allows(peer_ip));
+        let trusted_token = self.token.as_deref().is_some_and(|token| {
+            headers
+                .get(DEBUG_TOKEN_HEADER)
+                .is_some_and(|sent| constant_time_eq(sent.as_bytes(), token.as_bytes()))
+        });
+
+        trusted_ip || trusted_token
+    }
+}
+
+/// Compare two secrets without returning early on the first differing byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_info::ClientInfo;
+    use karateway_config::client_ip;
+
+    fn timeout_override() -> TimeoutOverride {
+        TimeoutOverride {
+            enabled: true,
+            trusted_networks: Some(IpAllowlist::parse("10.0.0.0/8")),
+            token: Some("s3cret".to_string()),
+            max: Duration::from_secs(60),
+        }
+    }
+
+    fn headers(timeout: &str, token: Option<&str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TIMEOUT_OVERRIDE_HEADER, timeout.parse().unwrap());
+        if let Some(token) = token {
+            headers.insert(DEBUG_TOKEN_HEADER, token.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_trusted_clients_can_override_up_to_the_max() {
+        let timeout_override = timeout_override();
+
+        assert_eq!(
+            timeout_override.requested(&headers("2500", None), Some("10.1.2.3")),
+            Some(Duration::from_millis(2500))
+        );
+        // Trusted by token from anywhere
+        assert_eq!(
+            timeout_override.requested(&headers("2500", Some("s3cret")), Some("203.0.113.9")),
+            Some(Duration::from_millis(2500))
+        );
+        // A huge timeout can't hold the connection open longer than the max
+        assert_eq!(
+            timeout_override.requested(&headers("86400000", None), Some("10.1.2.3")),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            timeout_override.requested(&headers("soon", None), Some("10.1.2.3")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_untrusted_clients_keep_the_route_timeout() {
+        let timeout_override = timeout_override();
+
+        assert_eq!(
+            timeout_override.requested(&headers("2500", None), Some("203.0.113.9")),
+            None
+        );
+        assert_eq!(
+            timeout_override.requested(&headers("2500", Some("guess")), Some("203.0.113.9")),
+            None
+        );
+        assert_eq!(
+            timeout_override.requested(&headers("2500", None), None),
+            None
+        );
+
+        // Claiming a trusted IP in X-Forwarded-For doesn't make a client trusted
+        let mut spoofed = headers("2500", None);
+        spoofed.insert("X-Forwarded-For", "10.1.2.3".parse().unwrap());
+        let client = ClientInfo::from_request(
+            &spoofed,
+            Some("203.0.113.9".to_string()),
+            &client_ip::DEFAULT_SOURCES,
+            &[],
+        );
+        assert_eq!(client.ip.as_deref(), Some("10.1.2.3"));
+        assert_eq!(
+            timeout_override.requested(&spoofed, client.peer_ip.as_deref()),
+            None
+        );
+
+        // Nothing configured to trust means nobody is trusted
+        let nobody = TimeoutOverride {
+            trusted_networks: None,
+            token: None,
+            ..timeout_override.clone()
+        };
+        assert_eq!(
+            nobody.requested(&headers("2500", None), Some("10.1.2.3")),
+            None
+        );
+
+        let disabled = TimeoutOverride {
+            enabled: false,
+            ..timeout_override
+        };
+        assert_eq!(
+            disabled.requested(&headers("2500", Some("s3cret")), Some("10.1.2.3")),
+            None
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use http::HeaderMap;
+use karateway_core::models::ApiRoute;
+use std::time::{Duration, Instant};
+
+/// Content types treated as long-lived streams
+const STREAMING_CONTENT_TYPES: [&str; 3] = [
+    "text/event-stream",
+    "application/grpc",
+    "application/x-ndjson",
+];
+
+/// Which timeout a request ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// No bytes from the upstream for longer than the idle timeout
+    Idle,
+    /// The whole response took longer than the total timeout
+    Total,
+}
+
+/// Timeouts applied while proxying a request to its upstream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteTimeouts {
+    /// Budget for the whole response (`timeout_ms`)
+    pub total: Option<Duration>,
+    /// Max gap between bytes read from the upstream (`idle_timeout_ms`)
+    pub idle: Option<Duration>,
+}
+
+impl RouteTimeouts {
+    pub fn from_route(route: &ApiRoute) -> Self {
+        let millis = |value: Option<i32>| {
+            value
+                .filter(|ms| *ms > 0)
+                .map(|ms| Duration::from_millis(ms as u64))
+        };
+
+        Self {
+            total: millis(route.timeout_ms),
+            idle: millis(route.idle_timeout_ms),
+        }
+    }
+
+    /// Per-read timeout for the upstream connection
+    ///
+    /// Uses the idle timeout when set, otherwise the total budget so a single
+    /// read can never outlast the whole request.
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.idle.or(self.total)
+    }
+
+    /// Check a response that is still being received
+    ///
+    /// Streaming responses are only subject to the idle timeout, so a healthy
+    /// stream can run for as long as the upstream keeps sending data.
+    pub fn check(
+        &self,
+        started_at: Instant,
+        last_read_at: Instant,
+        now: Instant,
+        streaming: bool,
+    ) -> Option<TimeoutKind> {
+        if let Some(idle) = self.idle {
+            if now.saturating_duration_since(last_read_at) > idle {
+                return Some(TimeoutKind::Idle);
+            }
+        }
+
+        if !streaming {
+            if let Some(total) = self.total {
+                if now.saturating_duration_since(started_at) > total {
+                    return Some(TimeoutKind::Total);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Whether an upstream response is a long-lived stream (SSE, gRPC, NDJSON)
+pub fn is_streaming_response(headers: &HeaderMap) -> bool {
+    headers
+        .get("Content-Type")
+        .and_then(|h| h.to_str().ok())
+        .map(|content_type| {
+            let content_type = content_type.trim().to_lowercase();
+            STREAMING_CONTENT_TYPES
+                .iter()
+                .any(|prefix| content_type.starts_with(prefix))
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeouts(total_ms: u64, idle_ms: u64) -> RouteTimeouts {
+        RouteTimeouts {
+            total: Some(Duration::from_millis(total_ms)),
+            idle: Some(Duration::from_millis(idle_ms)),
+        }
+    }
+
+    #[test]
+    fn test_healthy_long_stream_is_not_cut_off() {
+        let timeouts = timeouts(1_000, 500);
+        let started = Instant::now();
+        let last_read = started + Duration::from_secs(60);
+        let now = last_read + Duration::from_millis(100);
+
+        // A minute in, well past the total budget, but still receiving data
+        assert_eq!(timeouts.check(started, last_read, now, true), None);
+    }
+
+    #[test]
+    fn test_stalled_stream_hits_idle_timeout() {
+        let timeouts = timeouts(1_000, 500);
+        let started = Instant::now();
+        let last_read = started + Duration::from_millis(100);
+        let now = last_read + Duration::from_millis(600);
+
+        assert_eq!(
+            timeouts.check(started, last_read, now, true),
+            Some(TimeoutKind::Idle)
+        );
+    }
+
+    #[test]
+    fn test_regular_response_keeps_total_timeout() {
+        let timeouts = timeouts(1_000, 500);
+        let started = Instant::now();
+        let now = started + Duration::from_millis(1_200);
+        let last_read = now - Duration::from_millis(100);
+
+        assert_eq!(
+            timeouts.check(started, last_read, now, false),
+            Some(TimeoutKind::Total)
+        );
+        assert_eq!(timeouts.read_timeout(), Some(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_is_streaming_response() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_streaming_response(&headers));
+
+        headers.insert("Content-Type", "application/json".parse().unwrap());
+        assert!(!is_streaming_response(&headers));
+
+        headers.insert(
+            "Content-Type",
+            "text/event-stream; charset=utf-8".parse().unwrap(),
+        );
+        assert!(is_streaming_response(&headers));
+
+        headers.insert("Content-Type", "application/grpc+proto".parse().unwrap());
+        assert!(is_streaming_response(&headers));
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Instant;
+
+/// Time from sending the request upstream to receiving its response head
+pub const UPSTREAM_TIME_HEADER: &str = "X-Upstream-Time-Ms";
+
+/// Time from the request arriving at the gateway to its response head being ready
+pub const GATEWAY_TIME_HEADER: &str = "X-Gateway-Time-Ms";
+
+/// Values of the timing headers for a response head received at `now`
+///
+/// `started_at` is the request's arrival, the same instant the latency
+/// metrics are measured from; `upstream_sent_at` is when the last attempt
+/// was sent upstream, `None` if it never was. The difference between the two
+/// values is the time spent in the gateway itself.
+pub fn timing_headers(
+    started_at: Instant,
+    upstream_sent_at: Option<Instant>,
+    now: Instant,
+) -> Vec<(&'static str, String)> {
+    let millis = |since: Instant| now.saturating_duration_since(since).as_millis().to_string();
+
+    let mut headers = Vec::with_capacity(2);
+    if let Some(sent_at) = upstream_sent_at {
+        headers.push((UPSTREAM_TIME_HEADER, millis(sent_at)));
+    }
+    headers.push((GATEWAY_TIME_HEADER, millis(started_at)));
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_headers_reflect_a_slow_upstream() {
+        let started_at = Instant::now();
+        // 5ms of gateway work before the request goes out, then a 750ms upstream
+        let sent_at = started_at + Duration::from_millis(5);
+        let now = sent_at + Duration::from_millis(750);
+
+        assert_eq!(
+            timing_headers(started_at, Some(sent_at), now),
+            vec![
+                (UPSTREAM_TIME_HEADER, "750".to_string()),
+                (GATEWAY_TIME_HEADER, "755".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_upstream_time_without_an_upstream_request() {
+        let started_at = Instant::now();
+        let now = started_at + Duration::from_millis(2);
+
+        assert_eq!(
+            timing_headers(started_at, None, now),
+            vec![(GATEWAY_TIME_HEADER, "2".to_string())]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use pingora_core::protocols::ALPN;
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{version, ServerConfig, SupportedCipherSuite, SupportedProtocolVersion};
+use std::sync::Arc;
+use tracing::warn;
+
+static TLS12_AND_13: &[&SupportedProtocolVersion] = &[&version::TLS12, &version::TLS13];
+static TLS13_ONLY: &[&SupportedProtocolVersion] = &[&version::TLS13];
+
+/// Lowest TLS version a client may negotiate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    #[default]
+    V1_2,
+    V1_3,
+}
+
+impl std::str::FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().trim_start_matches("tls").trim() {
+            "1.2" => Ok(TlsVersion::V1_2),
+            "1.3" => Ok(TlsVersion::V1_3),
+            other => Err(format!("Invalid TLS minimum version: {}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for TlsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsVersion::V1_2 => write!(f, "TLS 1.2"),
+            TlsVersion::V1_3 => write!(f, "TLS 1.3"),
+        }
+    }
+}
+
+/// Cipher suite profile, after Mozilla's server side TLS guidelines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TlsProfile {
+    /// TLS 1.2 ECDHE AEAD suites plus TLS 1.3, for general-purpose servers
+    #[default]
+    Intermediate,
+    /// TLS 1.3 only, for clients known to be recent
+    Modern,
+}
+
+impl std::str::FromStr for TlsProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "intermediate" => Ok(TlsProfile::Intermediate),
+            "modern" => Ok(TlsProfile::Modern),
+            other => Err(format!("Invalid TLS profile: {}", other)),
+        }
+    }
+}
+
+/// TLS version floor and cipher suites offered by the gateway
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TlsPolicy {
+    pub min_version: TlsVersion,
+    pub profile: TlsProfile,
+}
+
+impl TlsPolicy {
+    /// Parse the configured values, falling back to the intermediate profile on invalid ones
+    pub fn parse_or_default(min_version: &str, profile: &str) -> Self {
+        Self {
+            min_version: min_version.parse().unwrap_or_else(|e| {
+                warn!("{}, using TLS 1.2", e);
+                TlsVersion::default()
+            }),
+            profile: profile.parse().unwrap_or_else(|e| {
+                warn!("{}, using intermediate", e);
+                TlsProfile::default()
+            }),
+        }
+    }
+
+    /// The version actually enforced; the modern profile implies TLS 1.3
+    pub fn effective_min_version(&self) -> TlsVersion {
+        match self.profile {
+            TlsProfile::Modern => TlsVersion::V1_3,
+            TlsProfile::Intermediate => self.min_version,
+        }
+    }
+
+    /// Crypto provider of the HTTPS listener, without the TLS 1.2 suites when 1.3 is the minimum
+    pub fn crypto_provider(&self) -> CryptoProvider {
+        let mut provider = ring::default_provider();
+
+        if self.effective_min_version() == TlsVersion::V1_3 {
+            provider
+                .cipher_suites
+                .retain(|suite| matches!(suite, SupportedCipherSuite::Tls13(_)));
+        }
+
+        provider
+    }
+
+    pub fn protocol_versions(&self) -> &'static [&'static SupportedProtocolVersion] {
+        match self.effective_min_version() {
+            TlsVersion::V1_2 => TLS12_AND_13,
+            TlsVersion::V1_3 => TLS13_ONLY,
+        }
+    }
+
+    /// rustls config of the HTTPS listener
+    ///
+    /// Built on a provider of its own rather than the process default, so TLS
+    /// to backends, Redis and the database isn't held to the listener's policy.
+    pub fn server_config(
+        &self,
+        cert_path: &str,
+        key_path: &str,
+        alpn: AlpnProtocols,
+    ) -> Result<ServerConfig, String> {
+        let certs = CertificateDer::pem_file_iter(cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("can't read certificate {}: {}", cert_path, e))?;
+        let key = PrivateKeyDer::from_pem_file(key_path)
+            .map_err(|e| format!("can't read key {}: {}", key_path, e))?;
+
+        let mut config = ServerConfig::builder_with_provider(Arc::new(self.crypto_provider()))
+            .with_protocol_versions(self.protocol_versions())
+            .map_err(|e| format!("unusable TLS policy: {}", e))?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("invalid certificate or key: {}", e))?;
+        config.alpn_protocols = alpn.advertised();
+        Ok(config)
+    }
+}
+
+/// Application protocols the HTTPS listener negotiates over ALPN
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AlpnProtocols {
+    /// HTTP/2, falling back to HTTP/1.1 for clients without it
+    #[default]
+    H2Http11,
+    /// HTTP/1.1 only, for clients or middleboxes that mishandle HTTP/2
+    Http11,
+    /// HTTP/2 only
+    H2,
+}
+
+impl std::str::FromStr for AlpnProtocols {
+    type Err = String;
+
+    /// A comma-separated set of `h2` and `http/1.1`; the order doesn't matter
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mut h2, mut http11) = (false, false);
+        for protocol in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match protocol.to_lowercase().as_str() {
+                "h2" => h2 = true,
+                "http/1.1" => http11 = true,
+                other => return Err(format!("Invalid ALPN protocol: {}", other)),
+            }
+        }
+
+        match (h2, http11) {
+            (true, true) => Ok(AlpnProtocols::H2Http11),
+            (false, true) => Ok(AlpnProtocols::Http11),
+            (true, false) => Ok(AlpnProtocols::H2),
+            (false, false) => Err("No ALPN protocols configured".to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for AlpnProtocols {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AlpnProtocols::H2Http11 => write!(f, "h2, http/1.1"),
+            AlpnProtocols::Http11 => write!(f, "http/1.1"),
+            AlpnProtocols::H2 => write!(f, "h2"),
+        }
+    }
+}
+
+impl AlpnProtocols {
+    /// Protocols the listener advertises, most preferred first
+    ///
+    /// Nothing for HTTP/1.1 only: a client that negotiates no protocol
+    /// speaks HTTP/1.1.
+    pub fn advertised(&self) -> Vec<Vec<u8>> {
+        match self {
+            AlpnProtocols::Http11 => Vec::new(),
+            AlpnProtocols::H2Http11 => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            AlpnProtocols::H2 => vec![b"h2".to_vec()],
+        }
+    }
+
+    /// Whether a TLS connection that negotiated `selected` may be served
+    ///
+    /// A client offering only other protocols fails the handshake, but one
+    /// offering none gets through it, so HTTP/2 only closes connections that
+    /// didn't settle on `h2`.
+    pub fn accepts(&self, selected: Option<&ALPN>) -> bool {
+        match self {
+            AlpnProtocols::H2 => matches!(selected, Some(ALPN::H2)),
+            AlpnProtocols::H2Http11 | AlpnProtocols::Http11 => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_tls12_suites(provider: &CryptoProvider) -> bool {
+        provider
+            .cipher_suites
+            .iter()
+            .any(|suite| matches!(suite, SupportedCipherSuite::Tls12(_)))
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(
+            TlsPolicy::parse_or_default("1.2", "intermediate"),
+            TlsPolicy::default()
+        );
+        assert_eq!(
+            TlsPolicy::parse_or_default("TLS1.3", "Modern"),
+            TlsPolicy {
+                min_version: TlsVersion::V1_3,
+                profile: TlsProfile::Modern,
+            }
+        );
+        assert_eq!(
+            TlsPolicy::parse_or_default("1.0", "legacy"),
+            TlsPolicy::default()
+        );
+    }
+
+    #[test]
+    fn test_negotiated_protocol_follows_alpn_config() {
+        let both: AlpnProtocols = "h2,http/1.1".parse().unwrap();
+        assert_eq!(both, AlpnProtocols::default());
+        assert_eq!(" HTTP/1.1 , h2 ".parse(), Ok(both));
+        assert_eq!(both.advertised(), [b"h2".to_vec(), b"http/1.1".to_vec()]);
+        assert!(both.accepts(Some(&ALPN::H2)));
+        assert!(both.accepts(Some(&ALPN::H1)));
+        assert!(both.accepts(None));
+
+        let http11: AlpnProtocols = "http/1.1".parse().unwrap();
+        assert_eq!(http11, AlpnProtocols::Http11);
+        assert!(http11.advertised().is_empty());
+        assert!(http11.accepts(None));
+
+        let h2: AlpnProtocols = "h2".parse().unwrap();
+        assert_eq!(h2, AlpnProtocols::H2);
+        assert_eq!(h2.advertised(), [b"h2".to_vec()]);
+        assert!(h2.accepts(Some(&ALPN::H2)));
+        assert!(!h2.accepts(Some(&ALPN::H1)));
+        assert!(!h2.accepts(None));
+
+        assert!("h3".parse::<AlpnProtocols>().is_err());
+        assert!("".parse::<AlpnProtocols>().is_err());
+    }
+
+    #[test]
+    fn test_default_policy_keeps_tls12() {
+        let provider = TlsPolicy::default().crypto_provider();
+        assert!(has_tls12_suites(&provider));
+        assert_eq!(
+            provider.cipher_suites.len(),
+            ring::default_provider().cipher_suites.len()
+        );
+    }
+
+    fn fixture(name: &str) -> String {
+        format!("{}/testdata/mtls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn test_server_config_follows_the_policy() {
+        let modern = TlsPolicy::parse_or_default("1.2", "modern");
+        let config = modern
+            .server_config(
+                &fixture("server.pem"),
+                &fixture("server-key.pem"),
+                AlpnProtocols::default(),
+            )
+            .unwrap();
+        assert!(!has_tls12_suites(config.crypto_provider()));
+        assert_eq!(config.alpn_protocols[0], b"h2");
+
+        // The process-wide default is left alone
+        if let Some(default) = CryptoProvider::get_default() {
+            assert!(has_tls12_suites(default));
+        }
+
+        assert!(modern
+            .server_config("missing.pem", "missing-key.pem", AlpnProtocols::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_tls13_only_drops_tls12_suites() {
+        for policy in [
+            TlsPolicy::parse_or_default("1.3", "intermediate"),
+            TlsPolicy::parse_or_default("1.2", "modern"),
+        ] {
+            assert_eq!(policy.effective_min_version(), TlsVersion::V1_3);
+            let provider = policy.crypto_provider();
+            assert!(!has_tls12_suites(&provider));
+            assert!(!provider.cipher_suites.is_empty());
+        }
+    }
+}
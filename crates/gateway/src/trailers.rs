@@ -0,0 +1,91 @@
+use http::header::{self, HeaderMap, HeaderName};
+
+use crate::hop_by_hop;
+
+/// `TE` value announcing that the client accepts trailers
+pub const TE_TRAILERS: &str = "trailers";
+
+/// Whether the client's `TE` header lists `trailers`
+///
+/// `TE` is hop-by-hop, so it is re-sent upstream only in its one form HTTP/2
+/// allows (`TE: trailers`). gRPC servers reject requests without it.
+pub fn accepts_trailers(headers: &HeaderMap) -> bool {
+    headers.get_all(http::header::TE).iter().any(|value| {
+        value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case(TE_TRAILERS))
+        })
+    })
+}
+
+/// Fields a trailer section must not carry (RFC 9110 section 6.5.1)
+///
+/// Framing, routing, authentication and content metadata only mean something
+/// in the header block, so a peer must never be handed them after the body.
+const PROHIBITED_IN_TRAILERS: [HeaderName; 9] = [
+    header::CONTENT_LENGTH,
+    header::TRANSFER_ENCODING,
+    header::CONTENT_ENCODING,
+    header::CONTENT_TYPE,
+    header::CONTENT_RANGE,
+    header::HOST,
+    header::AUTHORIZATION,
+    header::SET_COOKIE,
+    header::TRAILER,
+];
+
+/// Drop the fields of a trailer section that must not be passed on
+///
+/// Hop-by-hop fields go like they do from the header block, along with the
+/// fields only allowed there; everything else, such as `grpc-status`, is kept.
+pub fn strip_unforwardable(trailers: &mut HeaderMap) {
+    for name in hop_by_hop::hop_by_hop_headers(trailers, false) {
+        trailers.remove(&name);
+    }
+    for name in &PROHIBITED_IN_TRAILERS {
+        trailers.remove(name);
+    }
+}
+
+/// The `grpc-status` code carried by a set of trailers, if any
+pub fn grpc_status(trailers: &HeaderMap) -> Option<&str> {
+    trailers
+        .get("grpc-status")
+        .and_then(|status| status.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_trailers() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_trailers(&headers));
+
+        headers.insert(http::header::TE, "gzip".parse().unwrap());
+        assert!(!accepts_trailers(&headers));
+
+        headers.insert(http::header::TE, "gzip, Trailers".parse().unwrap());
+        assert!(accepts_trailers(&headers));
+    }
+
+    #[test]
+    fn test_unforwardable_trailer_fields_are_dropped() {
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "5".parse().unwrap());
+        trailers.insert("grpc-message", "not found".parse().unwrap());
+        trailers.insert(header::CONTENT_LENGTH, "0".parse().unwrap());
+        trailers.insert(header::CONNECTION, "x-debug".parse().unwrap());
+        trailers.insert("x-debug", "1".parse().unwrap());
+        trailers.insert(header::SET_COOKIE, "session=1".parse().unwrap());
+
+        strip_unforwardable(&mut trailers);
+
+        let mut names: Vec<&str> = trailers.keys().map(|name| name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["grpc-message", "grpc-status"]);
+        assert_eq!(grpc_status(&trailers), Some("5"));
+    }
+}
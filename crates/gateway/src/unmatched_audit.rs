@@ -0,0 +1,98 @@
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// Upper bound on client IPs tracked at once
+///
+/// Once full, IPs whose window has passed are dropped first; if that frees
+/// nothing, events from new IPs are suppressed until older windows expire.
+pub const MAX_TRACKED_IPS: usize = 10_000;
+
+/// Length of the per-IP counting window
+pub const WINDOW: Duration = Duration::from_secs(60);
+
+/// Decides which unmatched-route requests get an `InvalidRequest` audit event
+///
+/// Off by default. When on, each client IP gets at most `max_per_window`
+/// events per minute, so a scan hitting thousands of paths doesn't flood the
+/// audit log.
+pub struct UnmatchedAudit {
+    enabled: bool,
+    max_per_window: u32,
+    /// Window start and events logged in it, per client IP
+    windows: DashMap<String, (Instant, u32)>,
+}
+
+impl UnmatchedAudit {
+    pub fn new(enabled: bool, max_per_window: u32) -> Self {
+        Self {
+            enabled,
+            max_per_window,
+            windows: DashMap::new(),
+        }
+    }
+
+    /// Whether an unmatched request from `client_ip` should be audited
+    pub fn should_log(&self, client_ip: &str, now: Instant) -> bool {
+        if !self.enabled || self.max_per_window == 0 {
+            return false;
+        }
+
+        if !self.windows.contains_key(client_ip) && self.windows.len() >= MAX_TRACKED_IPS {
+            self.windows
+                .retain(|_, (start, _)| now.saturating_duration_since(*start) < WINDOW);
+            if self.windows.len() >= MAX_TRACKED_IPS {
+                return false;
+            }
+        }
+
+        let mut window = self
+            .windows
+            .entry(client_ip.to_string())
+            .or_insert((now, 0));
+        let (start, count) = window.value_mut();
+        if now.saturating_duration_since(*start) >= WINDOW {
+            *start = now;
+            *count = 0;
+        }
+
+        if *count < self.max_per_window {
+            *count += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_controls_whether_events_are_emitted() {
+        let now = Instant::now();
+
+        let disabled = UnmatchedAudit::new(false, 10);
+        assert!(!disabled.should_log("192.0.2.1", now));
+
+        let enabled = UnmatchedAudit::new(true, 10);
+        assert!(enabled.should_log("192.0.2.1", now));
+    }
+
+    #[test]
+    fn test_events_are_limited_per_client_ip() {
+        let audit = UnmatchedAudit::new(true, 3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(audit.should_log("192.0.2.1", now));
+        }
+        assert!(!audit.should_log("192.0.2.1", now + Duration::from_secs(1)));
+
+        // Other clients have their own budget
+        assert!(audit.should_log("198.51.100.7", now));
+
+        // The budget is back once the window has passed
+        assert!(audit.should_log("192.0.2.1", now + WINDOW));
+    }
+}
@@ -0,0 +1,65 @@
+use url::Url;
+
+/// Host, port and scheme a backend's `base_url` points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamTarget {
+    pub host: String,
+    pub port: u16,
+    pub use_tls: bool,
+}
+
+impl UpstreamTarget {
+    /// Parse a backend `base_url`, rejecting anything that can't be proxied to
+    ///
+    /// Checked when the config is loaded so a bad URL never goes live, and
+    /// again per request in case one slips through.
+    pub fn parse(base_url: &str) -> Result<Self, String> {
+        let url = Url::parse(base_url).map_err(|e| format!("invalid URL: {}", e))?;
+
+        let use_tls = match url.scheme() {
+            "http" => false,
+            "https" => true,
+            scheme => return Err(format!("unsupported scheme: {}", scheme)),
+        };
+
+        let host = url
+            .host_str()
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| "missing host".to_string())?;
+
+        Ok(Self {
+            host: host.to_string(),
+            port: url.port().unwrap_or(if use_tls { 443 } else { 80 }),
+            use_tls,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_valid_urls() {
+        assert_eq!(
+            UpstreamTarget::parse("http://orders.internal:9001/api"),
+            Ok(UpstreamTarget {
+                host: "orders.internal".to_string(),
+                port: 9001,
+                use_tls: false,
+            })
+        );
+        assert_eq!(
+            UpstreamTarget::parse("https://example.com").map(|t| (t.port, t.use_tls)),
+            Ok((443, true))
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unusable_urls() {
+        assert!(UpstreamTarget::parse("not a url").is_err());
+        assert!(UpstreamTarget::parse("/relative/path").is_err());
+        assert!(UpstreamTarget::parse("ftp://files.example.com").is_err());
+        assert!(UpstreamTarget::parse("unix:/var/run/app.sock").is_err());
+    }
+}
@@ -0,0 +1,340 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::system_conf::read_system_conf;
+use hickory_resolver::TokioAsyncResolver;
+use karateway_config::AppConfig;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Most resolution retries allowed, whatever is configured, to keep the added latency small
+const MAX_RETRIES: u32 = 5;
+/// How long a single lookup may take before it counts as a transient failure
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(1);
+/// Most time spent resolving one host, lookups and backoff included
+const MAX_RESOLVE_TIME: Duration = Duration::from_secs(2);
+
+/// A failed hostname lookup
+#[derive(Debug)]
+pub struct LookupError {
+    pub message: String,
+    /// The resolver couldn't answer this time (SERVFAIL, a timeout), so asking
+    /// again may work. A name that doesn't exist is never transient.
+    pub transient: bool,
+}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+/// Resolves backend hostnames into addresses
+#[async_trait]
+pub trait HostResolver: Send + Sync {
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, LookupError>;
+}
+
+/// Resolver using the system's nameservers and hosts file, through hickory
+///
+/// Unlike `getaddrinfo`, whose errors only carry a message, hickory tells a
+/// name that doesn't exist apart from a nameserver that didn't answer.
+pub struct DnsHostResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsHostResolver {
+    pub fn from_system() -> Result<Self> {
+        let (config, mut opts) =
+            read_system_conf().context("Failed to read the system DNS configuration")?;
+        // Retries and their timeouts are the upstream resolver's
+        opts.timeout = LOOKUP_TIMEOUT;
+        opts.attempts = 1;
+
+        Ok(Self {
+            resolver: TokioAsyncResolver::tokio(config, opts),
+        })
+    }
+}
+
+#[async_trait]
+impl HostResolver for DnsHostResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, LookupError> {
+        match self.resolver.lookup_ip(host).await {
+            Ok(lookup) => Ok(lookup.iter().map(|ip| SocketAddr::new(ip, port)).collect()),
+            Err(e) => Err(lookup_error(&e)),
+        }
+    }
+}
+
+/// Classify a hickory lookup failure
+///
+/// NXDOMAIN and a name without addresses are permanent. SERVFAIL, timeouts
+/// and connection errors mean the nameserver couldn't answer this time.
+fn lookup_error(error: &ResolveError) -> LookupError {
+    let transient = match error.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+            *response_code == ResponseCode::ServFail
+        }
+        _ => true,
+    };
+    LookupError {
+        message: error.to_string(),
+        transient,
+    }
+}
+
+/// How often a failed backend hostname lookup is tried again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DnsRetry {
+    /// Lookups after the first one, at most `MAX_RETRIES`
+    pub retries: u32,
+    /// Wait before the first retry, doubled for each one after it
+    pub backoff: Duration,
+    /// Time allowed for each lookup
+    pub lookup_timeout: Duration,
+    /// Time allowed for all lookups and waits together
+    pub max_time: Duration,
+}
+
+impl DnsRetry {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            retries: config.gateway_dns_retries.min(MAX_RETRIES),
+            backoff: Duration::from_millis(config.gateway_dns_retry_backoff_ms),
+            lookup_timeout: LOOKUP_TIMEOUT,
+            max_time: MAX_RESOLVE_TIME,
+        }
+    }
+}
+
+/// Resolves the host a request is sent to, retrying transient lookup failures
+///
+/// Only resolution is retried here; a resolved address that refuses the
+/// connection fails over to the route's fallbacks as before.
+#[derive(Clone)]
+pub struct UpstreamResolver {
+    resolver: Arc<dyn HostResolver>,
+    retry: DnsRetry,
+}
+
+impl UpstreamResolver {
+    pub fn new(resolver: Arc<dyn HostResolver>, retry: DnsRetry) -> Self {
+        Self { resolver, retry }
+    }
+
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        Ok(Self::new(
+            Arc::new(DnsHostResolver::from_system()?),
+            DnsRetry::from_config(config),
+        ))
+    }
+
+    /// Address to connect to for `host:port`
+    ///
+    /// IP literals are used as they are. For hostnames, the first address
+    /// returned is used; a transient lookup failure is tried again up to
+    /// `retries` times before giving up. Names that don't exist or have no
+    /// addresses fail at once. Lookups and waits stop after `max_time`, or
+    /// after `time_left` when the request has less left than that.
+    pub async fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+        time_left: Option<Duration>,
+    ) -> Result<SocketAddr> {
+        // IPv6 hosts of a URL come with brackets
+        if let Ok(ip) = host.trim_matches(['[', ']']).parse::<IpAddr>() {
+            return Ok(SocketAddr::new(ip, port));
+        }
+
+        let budget = time_left.map_or(self.retry.max_time, |left| left.min(self.retry.max_time));
+        let deadline = Instant::now() + budget;
+        let mut backoff = self.retry.backoff;
+        let mut attempt = 0;
+        loop {
+            let timeout = deadline
+                .saturating_duration_since(Instant::now())
+                .min(self.retry.lookup_timeout);
+            let error = match tokio::time::timeout(timeout, self.resolver.resolve(host, port)).await
+            {
+                Ok(Ok(addrs)) => match addrs.into_iter().next() {
+                    Some(addr) => {
+                        if attempt > 0 {
+                            debug!("Resolved {} after {} retries", host, attempt);
+                        }
+                        return Ok(addr);
+                    }
+                    None => LookupError {
+                        message: "no addresses found".to_string(),
+                        transient: false,
+                    },
+                },
+                Ok(Err(e)) => e,
+                Err(_) => LookupError {
+                    message: format!("lookup timed out after {:?}", timeout),
+                    transient: true,
+                },
+            };
+
+            // Give up rather than wait past what is left
+            let out_of_time = Instant::now() + backoff >= deadline;
+            if !error.transient || attempt >= self.retry.retries || out_of_time {
+                return Err(error)
+                    .with_context(|| format!("Failed to resolve backend host {}", host));
+            }
+            attempt += 1;
+            warn!(
+                "Failed to resolve backend host {}, retrying in {:?} ({}/{}): {}",
+                host, backoff, attempt, self.retry.retries, error
+            );
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::op::Query;
+    use hickory_resolver::proto::rr::{Name, RecordType};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails the first `failures` lookups, then resolves to 10.0.0.1
+    struct FlakyResolver {
+        failures: u32,
+        /// Whether the failures are transient or an NXDOMAIN
+        transient: bool,
+        /// How long each lookup takes
+        delay: Duration,
+        calls: AtomicU32,
+    }
+
+    #[async_trait]
+    impl HostResolver for FlakyResolver {
+        async fn resolve(&self, _host: &str, port: u16) -> Result<Vec<SocketAddr>, LookupError> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(self.delay).await;
+            if call < self.failures {
+                return Err(LookupError {
+                    message: "lookup failed".to_string(),
+                    transient: self.transient,
+                });
+            }
+            Ok(vec![SocketAddr::from(([10, 0, 0, 1], port))])
+        }
+    }
+
+    fn resolver(failures: u32) -> (Arc<FlakyResolver>, UpstreamResolver) {
+        flaky_resolver(failures, true, Duration::ZERO)
+    }
+
+    fn flaky_resolver(
+        failures: u32,
+        transient: bool,
+        delay: Duration,
+    ) -> (Arc<FlakyResolver>, UpstreamResolver) {
+        let flaky = Arc::new(FlakyResolver {
+            failures,
+            transient,
+            delay,
+            calls: AtomicU32::new(0),
+        });
+        let retry = DnsRetry {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+            lookup_timeout: Duration::from_millis(50),
+            max_time: Duration::from_millis(200),
+        };
+        (flaky.clone(), UpstreamResolver::new(flaky, retry))
+    }
+
+    /// Answer of a nameserver that had nothing for `typo.internal`
+    fn no_records(response_code: ResponseCode) -> ResolveError {
+        ResolveErrorKind::NoRecordsFound {
+            query: Box::new(Query::query(
+                Name::from_ascii("typo.internal.").unwrap(),
+                RecordType::A,
+            )),
+            soa: None,
+            negative_ttl: None,
+            response_code,
+            trusted: true,
+        }
+        .into()
+    }
+
+    #[tokio::test]
+    async fn test_transient_resolution_failure_is_retried() {
+        let (flaky, upstream) = resolver(2);
+        let addr = upstream
+            .resolve("orders.internal", 8080, None)
+            .await
+            .unwrap();
+        assert_eq!(addr, SocketAddr::from(([10, 0, 0, 1], 8080)));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+
+        // Retries are capped, after which the request fails
+        let (flaky, upstream) = resolver(5);
+        assert!(upstream
+            .resolve("orders.internal", 8080, None)
+            .await
+            .is_err());
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+
+        // IP literals are never looked up
+        let (flaky, upstream) = resolver(5);
+        let addr = upstream.resolve("[::1]", 8443, None).await.unwrap();
+        assert_eq!(addr, "[::1]:8443".parse().unwrap());
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_host_is_not_retried() {
+        let (flaky, upstream) = flaky_resolver(1, false, Duration::ZERO);
+        let error = upstream
+            .resolve("typo.internal", 8080, None)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("lookup failed"));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_nxdomain_is_permanent_and_servfail_transient() {
+        assert!(!lookup_error(&no_records(ResponseCode::NXDomain)).transient);
+        assert!(!lookup_error(&no_records(ResponseCode::NoError)).transient);
+        assert!(lookup_error(&no_records(ResponseCode::ServFail)).transient);
+        assert!(lookup_error(&ResolveErrorKind::Timeout.into()).transient);
+    }
+
+    #[tokio::test]
+    async fn test_slow_lookups_stop_at_the_time_left() {
+        // Every lookup hangs past its own timeout, so each one counts as transient
+        let (flaky, upstream) = flaky_resolver(0, true, Duration::from_secs(10));
+        let started = Instant::now();
+        let error = upstream
+            .resolve("orders.internal", 8080, None)
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("timed out"));
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 3);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // A request with less time left than a lookup gets a single, shorter try
+        let (flaky, upstream) = flaky_resolver(0, true, Duration::from_secs(10));
+        let started = Instant::now();
+        assert!(upstream
+            .resolve("orders.internal", 8080, Some(Duration::from_millis(10)))
+            .await
+            .is_err());
+        assert_eq!(flaky.calls.load(Ordering::SeqCst), 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
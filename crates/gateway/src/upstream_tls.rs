@@ -0,0 +1,133 @@
+use karateway_core::models::BackendService;
+use pingora_core::utils::tls::CertKey;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::sync::Arc;
+
+/// Client certificate a backend requiring mutual TLS is connected with
+#[derive(Clone)]
+pub struct ClientCert(Arc<CertKey>);
+
+impl std::fmt::Debug for ClientCert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the key material out of logs
+        f.write_str("ClientCert")
+    }
+}
+
+impl ClientCert {
+    /// Load a PEM certificate chain and its PEM private key
+    pub fn load(cert_path: &str, key_path: &str) -> Result<Self, String> {
+        let certs = CertificateDer::pem_file_iter(cert_path)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("can't read client certificate {}: {}", cert_path, e))?;
+        if certs.is_empty() {
+            return Err(format!("no certificate in {}", cert_path));
+        }
+        let key = PrivateKeyDer::from_pem_file(key_path)
+            .map_err(|e| format!("can't read client key {}: {}", key_path, e))?;
+
+        Ok(Self(Arc::new(CertKey::new(
+            certs.into_iter().map(|cert| cert.to_vec()).collect(),
+            key.secret_der().to_vec(),
+        ))))
+    }
+
+    /// Load a backend's client certificate, `None` when it has none configured
+    pub fn for_service(service: &BackendService) -> Option<Result<Self, String>> {
+        match (&service.tls_client_cert_path, &service.tls_client_key_path) {
+            (Some(cert_path), Some(key_path)) => Some(Self::load(cert_path, key_path)),
+            _ => None,
+        }
+    }
+
+    /// The certificate and key in the form `HttpPeer::client_cert_key` takes
+    pub fn cert_key(&self) -> Arc<CertKey> {
+        self.0.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pingora_core::connectors::TransportConnector;
+    use pingora_core::upstreams::peer::{HttpPeer, Peer};
+    use rustls::server::WebPkiClientVerifier;
+    use rustls::{RootCertStore, ServerConfig, ServerConnection};
+
+    fn fixture(name: &str) -> String {
+        format!("{}/testdata/mtls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    /// A backend that only completes the handshake with clients holding a cert from the test CA
+    fn mtls_backend() -> (u16, std::thread::JoinHandle<Option<Vec<u8>>>) {
+        let mut roots = RootCertStore::empty();
+        for ca in CertificateDer::pem_file_iter(fixture("ca.pem")).unwrap() {
+            roots.add(ca.unwrap()).unwrap();
+        }
+        let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+            .build()
+            .unwrap();
+        let server_certs = CertificateDer::pem_file_iter(fixture("server.pem"))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let server_key = PrivateKeyDer::from_pem_file(fixture("server-key.pem")).unwrap();
+        let config = Arc::new(
+            ServerConfig::builder()
+                .with_client_cert_verifier(verifier)
+                .with_single_cert(server_certs, server_key)
+                .unwrap(),
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let backend = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().ok()?;
+            let mut conn = ServerConnection::new(config).ok()?;
+            while conn.is_handshaking() {
+                conn.complete_io(&mut socket).ok()?;
+            }
+            // The client certificate the backend saw, if any
+            conn.peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| cert.to_vec())
+        });
+
+        (port, backend)
+    }
+
+    #[tokio::test]
+    async fn test_client_cert_is_presented_to_mtls_backend() {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let (port, backend) = mtls_backend();
+
+        let client_cert =
+            ClientCert::load(&fixture("client.pem"), &fixture("client-key.pem")).unwrap();
+        let mut peer = HttpPeer::new(("127.0.0.1", port), true, "localhost".to_string());
+        if let Some(options) = peer.get_mut_peer_options() {
+            options.verify_cert = false;
+            options.verify_hostname = false;
+        }
+        peer.client_cert_key = Some(client_cert.cert_key());
+
+        let stream = TransportConnector::new(None)
+            .new_stream(&peer)
+            .await
+            .unwrap();
+        let presented = tokio::task::spawn_blocking(move || backend.join().unwrap())
+            .await
+            .unwrap();
+        drop(stream);
+
+        let expected = CertificateDer::from_pem_file(fixture("client.pem")).unwrap();
+        assert_eq!(presented, Some(expected.to_vec()));
+    }
+
+    #[test]
+    fn test_unreadable_client_cert_is_an_error() {
+        assert!(ClientCert::load(&fixture("missing.pem"), &fixture("client-key.pem")).is_err());
+        // A key is not a certificate
+        assert!(ClientCert::load(&fixture("client-key.pem"), &fixture("client-key.pem")).is_err());
+    }
+}
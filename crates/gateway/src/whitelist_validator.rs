@@ -1,6 +1,15 @@
-use karateway_core::models::{RuleType, WhitelistRule};
+use karateway_config::jwt_rule::JwtRule;
+use karateway_core::models::{HeaderCondition, RuleType, WhitelistRule};
 use pingora_http::RequestHeader;
+use std::collections::HashMap;
 use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Compiled header conditions of custom rules, by rule ID
+pub type CustomRuleConditions = HashMap<Uuid, Vec<HeaderCondition>>;
+
+/// Header API key rules read the key from
+pub const API_KEY_HEADER: &str = "X-API-Key";
 
 /// Validates a request against whitelist rules
 pub struct WhitelistValidator;
@@ -10,6 +19,7 @@ impl WhitelistValidator {
     /// Returns (allowed, rule_name) - if allowed is false, rule_name contains the blocking rule name
     pub fn validate_request(
         rules: &[WhitelistRule],
+        custom_conditions: &CustomRuleConditions,
         req_header: &RequestHeader,
         client_ip: Option<&str>,
     ) -> (bool, Option<String>) {
@@ -29,10 +39,7 @@ impl WhitelistValidator {
                 RuleType::Ip => Self::validate_ip_rule(rule, client_ip),
                 RuleType::ApiKey => Self::validate_api_key_rule(rule, req_header),
                 RuleType::Jwt => Self::validate_jwt_rule(rule, req_header),
-                RuleType::Custom => {
-                    warn!("Custom whitelist rules not yet implemented");
-                    false
-                }
+                RuleType::Custom => Self::validate_custom_rule(rule, custom_conditions, req_header),
             };
 
             if allowed {
@@ -101,10 +108,37 @@ impl WhitelistValidator {
         }
     }
 
+    /// Headers `rules` decide on, so a response they let through may differ by them
+    pub fn header_names(
+        rules: &[WhitelistRule],
+        custom_conditions: &CustomRuleConditions,
+    ) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for rule in rules {
+            let rule_names = match rule.rule_type {
+                RuleType::Ip => Vec::new(),
+                RuleType::ApiKey => vec![API_KEY_HEADER.to_string()],
+                RuleType::Jwt => vec!["Authorization".to_string()],
+                RuleType::Custom => custom_conditions
+                    .get(&rule.id)
+                    .into_iter()
+                    .flatten()
+                    .map(|condition| condition.header.clone())
+                    .collect(),
+            };
+            for name in rule_names {
+                if !names.iter().any(|seen| seen.eq_ignore_ascii_case(&name)) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
     /// Validate API key-based whitelist rule
     fn validate_api_key_rule(rule: &WhitelistRule, req_header: &RequestHeader) -> bool {
         // Get API key from header
-        let api_key = match req_header.headers.get("X-API-Key") {
+        let api_key = match req_header.headers.get(API_KEY_HEADER) {
             Some(header_value) => match header_value.to_str() {
                 Ok(key) => key,
                 Err(_) => {
@@ -149,6 +183,42 @@ impl WhitelistValidator {
         matches
     }
 
+    /// Validate a custom rule: every header condition must hold
+    ///
+    /// A rule without conditions, or whose conditions failed to compile at
+    /// load, allows nothing.
+    fn validate_custom_rule(
+        rule: &WhitelistRule,
+        custom_conditions: &CustomRuleConditions,
+        req_header: &RequestHeader,
+    ) -> bool {
+        let conditions = match custom_conditions.get(&rule.id) {
+            Some(conditions) if !conditions.is_empty() => conditions,
+            _ => {
+                warn!(
+                    "No usable conditions configured in custom rule {}",
+                    rule.rule_name
+                );
+                return false;
+            }
+        };
+
+        conditions.iter().all(|condition| {
+            let value = req_header
+                .headers
+                .get(condition.header.as_str())
+                .and_then(|value| value.to_str().ok());
+            let matched = condition.matches(value);
+            if !matched {
+                debug!(
+                    "Header {} did not satisfy custom rule {}",
+                    condition.header, rule.rule_name
+                );
+            }
+            matched
+        })
+    }
+
     /// Validate JWT-based whitelist rule
     fn validate_jwt_rule(rule: &WhitelistRule, req_header: &RequestHeader) -> bool {
         // Get JWT from Authorization header
@@ -174,23 +244,17 @@ impl WhitelistValidator {
             return false;
         };
 
-        // TODO: Implement JWT validation
-        // For now, just check if token is present and matches expected patterns
-        let _jwt_secret = rule.config.get("jwt_secret").and_then(|v| v.as_str());
-        let _allowed_issuers = rule.config.get("allowed_issuers");
-        let _allowed_audiences = rule.config.get("allowed_audiences");
-
-        warn!("JWT validation not fully implemented yet");
+        let Some(jwt_rule) = JwtRule::from_config(&rule.config) else {
+            warn!("JWT whitelist rule {} has no jwt_secret", rule.rule_name);
+            return false;
+        };
 
-        // Basic check: token should have 3 parts separated by dots
-        let parts: Vec<&str> = token.split('.').collect();
-        if parts.len() == 3 {
-            debug!("JWT token has valid format (3 parts)");
-            // TODO: Actually validate the JWT signature and claims
-            true
-        } else {
-            debug!("JWT token has invalid format");
-            false
+        match jwt_rule.verify(token) {
+            Ok(_) => true,
+            Err(rejection) => {
+                debug!("JWT rejected by rule {}: {}", rule.rule_name, rejection);
+                false
+            }
         }
     }
 }
@@ -200,6 +264,58 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    fn custom_rule(config: serde_json::Value) -> (WhitelistRule, CustomRuleConditions) {
+        let rule = WhitelistRule {
+            id: Uuid::new_v4(),
+            rule_name: "browsers".to_string(),
+            rule_type: RuleType::Custom,
+            api_route_id: None,
+            config: config.clone(),
+            is_active: true,
+            priority: 0,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        };
+        let conditions = karateway_core::models::parse_custom_conditions(&config).unwrap();
+        let custom = HashMap::from([(rule.id, conditions)]);
+        (rule, custom)
+    }
+
+    fn request(user_agent: Option<&str>) -> RequestHeader {
+        let mut req = RequestHeader::build("GET", b"/api", None).unwrap();
+        if let Some(user_agent) = user_agent {
+            req.insert_header("User-Agent", user_agent).unwrap();
+        }
+        req
+    }
+
+    #[test]
+    fn test_custom_rule_header_regex() {
+        let (rule, custom) = custom_rule(json!({
+            "conditions": [{"header": "User-Agent", "matches": "^Mozilla"}]
+        }));
+        let rules = [rule];
+
+        let allowed = |req: &RequestHeader| {
+            WhitelistValidator::validate_request(&rules, &custom, req, None).0
+        };
+        assert!(allowed(&request(Some("Mozilla/5.0 (Macintosh)"))));
+        assert!(!allowed(&request(Some("curl/8.5.0"))));
+        assert!(!allowed(&request(Some("Bot Mozilla"))));
+        assert!(!allowed(&request(None)));
+
+        // Conditions that didn't compile at load allow nothing
+        assert!(
+            !WhitelistValidator::validate_request(
+                &rules,
+                &CustomRuleConditions::new(),
+                &request(Some("Mozilla/5.0")),
+                None
+            )
+            .0
+        );
+    }
+
     #[test]
     fn test_ip_matches_exact() {
         assert!(WhitelistValidator::ip_matches("192.168.1.1", "192.168.1.1"));
@@ -28,6 +28,9 @@ pub enum KaratewayError {
 
     #[error("Forbidden: {0}")]
     Forbidden(String),
+
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
 }
 
 pub type Result<T> = std::result::Result<T, KaratewayError>;
@@ -42,6 +45,8 @@ impl KaratewayError {
     /// Get HTTP status code for this error
     pub fn status_code(&self) -> u16 {
         match self {
+            // Every pooled connection stayed busy for the whole acquire timeout
+            KaratewayError::Database(sqlx::Error::PoolTimedOut) => 503,
             KaratewayError::Database(_) => 500,
             KaratewayError::Redis(_) => 500,
             KaratewayError::Validation(_) => 400,
@@ -51,12 +56,14 @@ impl KaratewayError {
             KaratewayError::Configuration(_) => 500,
             KaratewayError::Unauthorized(_) => 401,
             KaratewayError::Forbidden(_) => 403,
+            KaratewayError::ServiceUnavailable(_) => 503,
         }
     }
 
     /// Get error code string
     pub fn error_code(&self) -> String {
         match self {
+            KaratewayError::Database(sqlx::Error::PoolTimedOut) => "DATABASE_POOL_EXHAUSTED",
             KaratewayError::Database(_) => "DATABASE_ERROR",
             KaratewayError::Redis(_) => "REDIS_ERROR",
             KaratewayError::Validation(_) => "VALIDATION_ERROR",
@@ -66,6 +73,7 @@ impl KaratewayError {
             KaratewayError::Configuration(_) => "CONFIGURATION_ERROR",
             KaratewayError::Unauthorized(_) => "UNAUTHORIZED",
             KaratewayError::Forbidden(_) => "FORBIDDEN",
+            KaratewayError::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
         }
         .to_string()
     }
@@ -5,6 +5,8 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::KaratewayError;
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, ToSchema)]
 #[sqlx(type_name = "varchar")]
 pub enum HttpMethod {
@@ -55,15 +57,85 @@ impl std::str::FromStr for HttpMethod {
     }
 }
 
+/// Which of a route's two backends receives traffic in a blue/green setup
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, ToSchema,
+)]
+#[sqlx(type_name = "varchar")]
+pub enum DeploymentColor {
+    /// `backend_service_id`
+    #[default]
+    #[sqlx(rename = "blue")]
+    Blue,
+    /// `green_backend_service_id`
+    #[sqlx(rename = "green")]
+    Green,
+}
+
+impl std::fmt::Display for DeploymentColor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeploymentColor::Blue => write!(f, "blue"),
+            DeploymentColor::Green => write!(f, "green"),
+        }
+    }
+}
+
+impl DeploymentColor {
+    pub fn flipped(self) -> Self {
+        match self {
+            DeploymentColor::Blue => DeploymentColor::Green,
+            DeploymentColor::Green => DeploymentColor::Blue,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct ApiRoute {
     pub id: Uuid,
     pub path_pattern: String,
     pub method: HttpMethod,
     pub backend_service_id: Uuid,
+    pub green_backend_service_id: Option<Uuid>,
+    pub active_color: DeploymentColor,
+    /// Query params a request must carry to match, e.g. `{"version": "beta"}`
+    pub query_match: serde_json::Value,
     pub strip_path_prefix: bool,
     pub preserve_host_header: bool,
+    /// Honour `X-HTTP-Method-Override` on POST requests for this route
+    pub allow_method_override: bool,
     pub timeout_ms: Option<i32>,
+    pub idle_timeout_ms: Option<i32>,
+    /// Backend service ids to fail over to, in order, when the active backend is unhealthy or can't be reached
+    pub fallback_backend_service_ids: serde_json::Value,
+    /// Whether completed requests get a `Request completed` access log line; off for chatty routes like health checks
+    pub access_log_enabled: bool,
+    /// Replaces the `Domain` of upstream `Set-Cookie` headers; empty removes it, leaving host-only cookies
+    pub cookie_domain: Option<String>,
+    /// Replaces the `Path` of upstream `Set-Cookie` headers, e.g. the route's public prefix
+    pub cookie_path: Option<String>,
+    /// Decompress gzip and deflate upstream responses before forwarding them, up to `GATEWAY_DECOMPRESS_MAX_BYTES`
+    pub decompress_response: bool,
+    /// API version the request must carry, e.g. `2` for `/v2/` paths, see `GATEWAY_API_VERSION_SOURCES`
+    pub api_version: Option<String>,
+    /// Add `X-Upstream-Time-Ms` and `X-Gateway-Time-Ms` to responses, even when off globally
+    pub timing_headers: bool,
+    /// Log request and response bodies at debug level, when body logging is enabled globally
+    pub debug_log_body: bool,
+    /// Base content types the request must have, comma-separated, e.g. `multipart/form-data`
+    pub content_type_match: Option<String>,
+    /// Prepended to the upstream path after `strip_path_prefix`, e.g. `/internal`
+    pub upstream_path_prefix: Option<String>,
+    /// Collapse identical concurrent bodiless requests into one upstream call
+    pub coalesce_requests: bool,
+    /// Max requests waiting for a slot when the backend is at `max_connections`
+    pub queue_depth: Option<i32>,
+    /// How long a queued request waits for a slot before getting a 503
+    pub queue_timeout_ms: Option<i32>,
+    /// Slots a request takes in each of the route's rate limits, 1 when unset
+    pub request_cost: Option<i32>,
+    /// Header whose number raises a request's cost above `request_cost`, e.g. `X-Request-Cost`
+    pub request_cost_header: Option<String>,
     pub is_active: bool,
     pub priority: i32,
     pub metadata: serde_json::Value,
@@ -80,13 +152,67 @@ pub struct CreateApiRouteRequest {
 
     pub backend_service_id: Uuid,
 
+    pub green_backend_service_id: Option<Uuid>,
+
+    /// Color that starts out live, blue unless set; green needs `green_backend_service_id`
+    pub active_color: Option<DeploymentColor>,
+
+    pub query_match: Option<serde_json::Value>,
+
+    /// Defaults to `ROUTE_DEFAULT_STRIP_PATH_PREFIX` (false unless configured)
     pub strip_path_prefix: Option<bool>,
 
+    /// Defaults to `ROUTE_DEFAULT_PRESERVE_HOST_HEADER` (false unless configured)
     pub preserve_host_header: Option<bool>,
 
+    pub allow_method_override: Option<bool>,
+
     #[validate(range(min = 100, max = 120000))]
     pub timeout_ms: Option<i32>,
 
+    #[validate(range(min = 100, max = 3600000))]
+    pub idle_timeout_ms: Option<i32>,
+
+    #[validate(length(max = 10))]
+    pub fallback_backend_service_ids: Option<Vec<Uuid>>,
+
+    pub access_log_enabled: Option<bool>,
+
+    #[validate(length(max = 253))]
+    pub cookie_domain: Option<String>,
+
+    #[validate(length(max = 500))]
+    pub cookie_path: Option<String>,
+
+    pub decompress_response: Option<bool>,
+
+    #[validate(length(min = 1, max = 20))]
+    pub api_version: Option<String>,
+
+    pub timing_headers: Option<bool>,
+
+    pub debug_log_body: Option<bool>,
+
+    #[validate(length(max = 500))]
+    pub content_type_match: Option<String>,
+
+    #[validate(length(max = 500))]
+    pub upstream_path_prefix: Option<String>,
+
+    pub coalesce_requests: Option<bool>,
+
+    #[validate(range(min = 1, max = 10000))]
+    pub queue_depth: Option<i32>,
+
+    #[validate(range(min = 1, max = 60000))]
+    pub queue_timeout_ms: Option<i32>,
+
+    #[validate(range(min = 1, max = 1000))]
+    pub request_cost: Option<i32>,
+
+    #[validate(length(min = 1, max = 100))]
+    pub request_cost_header: Option<String>,
+
     pub priority: Option<i32>,
 
     pub metadata: Option<serde_json::Value>,
@@ -101,13 +227,69 @@ pub struct UpdateApiRouteRequest {
 
     pub backend_service_id: Option<Uuid>,
 
+    /// `null` removes the green backend, leaving it out keeps the current one
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schema(value_type = Option<Uuid>, nullable)]
+    pub green_backend_service_id: Option<Option<Uuid>>,
+
+    pub query_match: Option<serde_json::Value>,
+
     pub strip_path_prefix: Option<bool>,
 
     pub preserve_host_header: Option<bool>,
 
+    pub allow_method_override: Option<bool>,
+
     #[validate(range(min = 100, max = 120000))]
     pub timeout_ms: Option<i32>,
 
+    #[validate(range(min = 100, max = 3600000))]
+    pub idle_timeout_ms: Option<i32>,
+
+    #[validate(length(max = 10))]
+    pub fallback_backend_service_ids: Option<Vec<Uuid>>,
+
+    pub access_log_enabled: Option<bool>,
+
+    #[validate(length(max = 253))]
+    pub cookie_domain: Option<String>,
+
+    #[validate(length(max = 500))]
+    pub cookie_path: Option<String>,
+
+    pub decompress_response: Option<bool>,
+
+    #[validate(length(min = 1, max = 20))]
+    pub api_version: Option<String>,
+
+    pub timing_headers: Option<bool>,
+
+    pub debug_log_body: Option<bool>,
+
+    #[validate(length(max = 500))]
+    pub content_type_match: Option<String>,
+
+    #[validate(length(max = 500))]
+    pub upstream_path_prefix: Option<String>,
+
+    pub coalesce_requests: Option<bool>,
+
+    #[validate(range(min = 1, max = 10000))]
+    pub queue_depth: Option<i32>,
+
+    #[validate(range(min = 1, max = 60000))]
+    pub queue_timeout_ms: Option<i32>,
+
+    #[validate(range(min = 1, max = 1000))]
+    pub request_cost: Option<i32>,
+
+    #[validate(length(min = 1, max = 100))]
+    pub request_cost_header: Option<String>,
+
     pub is_active: Option<bool>,
 
     pub priority: Option<i32>,
@@ -115,6 +297,375 @@ pub struct UpdateApiRouteRequest {
     pub metadata: Option<serde_json::Value>,
 }
 
+/// Values for the create request flags that are left out, set by the operator
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RouteDefaults {
+    pub strip_path_prefix: bool,
+    pub preserve_host_header: bool,
+}
+
+impl CreateApiRouteRequest {
+    /// Fill in omitted `strip_path_prefix` and `preserve_host_header`; explicit values are kept
+    /// Check that a green `active_color` has a green backend to send traffic to
+    pub fn validate_active_color(&self) -> crate::Result<()> {
+        if self.active_color == Some(DeploymentColor::Green)
+            && self.green_backend_service_id.is_none()
+        {
+            return Err(KaratewayError::Validation(
+                "active_color green needs a green_backend_service_id".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn with_defaults(mut self, defaults: RouteDefaults) -> Self {
+        self.strip_path_prefix
+            .get_or_insert(defaults.strip_path_prefix);
+        self.preserve_host_header
+            .get_or_insert(defaults.preserve_host_header);
+        self
+    }
+}
+
+/// Longest rate limit and whitelist rule name the schema allows
+const POLICY_NAME_MAX_LENGTH: usize = 100;
+
+/// Copy an existing route under a new path, see `POST /api/routes/{id}/clone`
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct CloneApiRouteRequest {
+    #[validate(length(min = 1, max = 500))]
+    pub path_pattern: String,
+
+    /// Defaults to the original route's method
+    pub method: Option<HttpMethod>,
+
+    /// Also copy the route's active rate limits and whitelist rules (default true)
+    pub include_policies: Option<bool>,
+}
+
+impl CloneApiRouteRequest {
+    pub fn include_policies(&self) -> bool {
+        self.include_policies.unwrap_or(true)
+    }
+}
+
+impl ApiRoute {
+    /// A create request for a copy of this route at the path and method of `clone`
+    ///
+    /// Everything else is copied as is, including the live color, so the copy
+    /// behaves like the original until either is edited. The copy starts active.
+    pub fn clone_request(&self, clone: &CloneApiRouteRequest) -> CreateApiRouteRequest {
+        CreateApiRouteRequest {
+            path_pattern: clone.path_pattern.clone(),
+            method: clone.method.clone().unwrap_or_else(|| self.method.clone()),
+            backend_service_id: self.backend_service_id,
+            green_backend_service_id: self.green_backend_service_id,
+            active_color: Some(self.active_color),
+            query_match: Some(self.query_match.clone()),
+            strip_path_prefix: Some(self.strip_path_prefix),
+            preserve_host_header: Some(self.preserve_host_header),
+            allow_method_override: Some(self.allow_method_override),
+            timeout_ms: self.timeout_ms,
+            idle_timeout_ms: self.idle_timeout_ms,
+            decompress_response: Some(self.decompress_response),
+            api_version: self.api_version.clone(),
+            timing_headers: Some(self.timing_headers),
+            debug_log_body: Some(self.debug_log_body),
+            content_type_match: self.content_type_match.clone(),
+            upstream_path_prefix: self.upstream_path_prefix.clone(),
+            cookie_domain: self.cookie_domain.clone(),
+            cookie_path: self.cookie_path.clone(),
+            fallback_backend_service_ids: Some(self.fallback_service_ids()),
+            access_log_enabled: Some(self.access_log_enabled),
+            coalesce_requests: Some(self.coalesce_requests),
+            queue_depth: self.queue_depth,
+            queue_timeout_ms: self.queue_timeout_ms,
+            request_cost: self.request_cost,
+            request_cost_header: self.request_cost_header.clone(),
+            priority: Some(self.priority),
+            metadata: Some(self.metadata.clone()),
+        }
+    }
+}
+
+/// Name for the copy of a rate limit or whitelist rule made for route `clone_id`
+///
+/// Policy names are unique, so the copy gets a short suffix from the new
+/// route's id, trimming the original name to keep within the column length.
+pub fn cloned_policy_name(name: &str, clone_id: Uuid) -> String {
+    let suffix = format!(" (clone {})", &clone_id.simple().to_string()[..8]);
+    let keep = POLICY_NAME_MAX_LENGTH.saturating_sub(suffix.chars().count());
+    let mut cloned: String = name.chars().take(keep).collect();
+    cloned.push_str(&suffix);
+    cloned
+}
+
+/// Tell a field set to `null` (`Some(None)`) apart from one left out (`None`)
+fn double_option<'de, D, T>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl ApiRoute {
+    /// Backend that currently receives this route's traffic
+    ///
+    /// `backend_service_id` is the blue backend. A route switched to green
+    /// without a green backend (e.g. after it was deleted) stays on blue.
+    pub fn active_backend_service_id(&self) -> Uuid {
+        match (self.active_color, self.green_backend_service_id) {
+            (DeploymentColor::Green, Some(green)) => green,
+            _ => self.backend_service_id,
+        }
+    }
+
+    /// The backend of the color not receiving traffic, the canary of a blue/green route
+    ///
+    /// `None` for routes without a green backend.
+    pub fn inactive_backend_service_id(&self) -> Option<Uuid> {
+        let green = self.green_backend_service_id?;
+        Some(match self.active_color {
+            DeploymentColor::Blue => green,
+            DeploymentColor::Green => self.backend_service_id,
+        })
+    }
+
+    /// `fallback_backend_service_ids` in failover order, skipping anything that isn't an id
+    pub fn fallback_service_ids(&self) -> Vec<Uuid> {
+        self.fallback_backend_service_ids
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| id.as_str()?.parse().ok())
+            .collect()
+    }
+
+    /// Check that `query_match` maps param names to the string values they must have
+    ///
+    /// An empty string only requires the param to be present.
+    pub fn validate_query_match(query_match: &serde_json::Value) -> crate::Result<()> {
+        let conditions = query_match.as_object().ok_or_else(|| {
+            KaratewayError::Validation("query_match must be a JSON object".to_string())
+        })?;
+
+        for (name, value) in conditions {
+            if name.is_empty() {
+                return Err(KaratewayError::Validation(
+                    "query_match param names must not be empty".to_string(),
+                ));
+            }
+            if !value.is_string() {
+                return Err(KaratewayError::Validation(format!(
+                    "query_match.{} must be a string",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that `request_cost_header` can be a header name
+    pub fn validate_request_cost_header(header: &str) -> crate::Result<()> {
+        if header
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        {
+            Ok(())
+        } else {
+            Err(KaratewayError::Validation(format!(
+                "request_cost_header {:?} is not a valid header name",
+                header
+            )))
+        }
+    }
+
+    /// Check that `upstream_path_prefix` is a path, or empty to send the path unchanged
+    pub fn validate_upstream_path_prefix(prefix: &str) -> crate::Result<()> {
+        if prefix.is_empty() || prefix.starts_with('/') {
+            Ok(())
+        } else {
+            Err(KaratewayError::Validation(format!(
+                "upstream_path_prefix {:?} must start with /",
+                prefix
+            )))
+        }
+    }
+
+    /// Apply the fields set in `req`, leaving the rest as they are
+    ///
+    /// The repository saves exactly this, so a preview built with it shows
+    /// what the update will do.
+    pub fn apply_update(&mut self, req: UpdateApiRouteRequest) {
+        if let Some(path_pattern) = req.path_pattern {
+            self.path_pattern = path_pattern;
+        }
+        if let Some(method) = req.method {
+            self.method = method;
+        }
+        if let Some(backend_service_id) = req.backend_service_id {
+            self.backend_service_id = backend_service_id;
+        }
+        if let Some(green_backend_service_id) = req.green_backend_service_id {
+            self.green_backend_service_id = green_backend_service_id;
+            // Without a green backend only blue is left to be live
+            if green_backend_service_id.is_none() {
+                self.active_color = DeploymentColor::Blue;
+            }
+        }
+        if let Some(query_match) = req.query_match {
+            self.query_match = query_match;
+        }
+        if let Some(strip_path_prefix) = req.strip_path_prefix {
+            self.strip_path_prefix = strip_path_prefix;
+        }
+        if let Some(preserve_host_header) = req.preserve_host_header {
+            self.preserve_host_header = preserve_host_header;
+        }
+        if let Some(allow_method_override) = req.allow_method_override {
+            self.allow_method_override = allow_method_override;
+        }
+        if let Some(timeout_ms) = req.timeout_ms {
+            self.timeout_ms = Some(timeout_ms);
+        }
+        if let Some(idle_timeout_ms) = req.idle_timeout_ms {
+            self.idle_timeout_ms = Some(idle_timeout_ms);
+        }
+        if let Some(fallback_backend_service_ids) = req.fallback_backend_service_ids {
+            self.fallback_backend_service_ids = serde_json::json!(fallback_backend_service_ids);
+        }
+        if let Some(access_log_enabled) = req.access_log_enabled {
+            self.access_log_enabled = access_log_enabled;
+        }
+        if let Some(cookie_domain) = req.cookie_domain {
+            self.cookie_domain = Some(cookie_domain);
+        }
+        if let Some(cookie_path) = req.cookie_path {
+            self.cookie_path = Some(cookie_path);
+        }
+        if let Some(decompress_response) = req.decompress_response {
+            self.decompress_response = decompress_response;
+        }
+        if let Some(api_version) = req.api_version {
+            self.api_version = Some(api_version);
+        }
+        if let Some(timing_headers) = req.timing_headers {
+            self.timing_headers = timing_headers;
+        }
+        if let Some(debug_log_body) = req.debug_log_body {
+            self.debug_log_body = debug_log_body;
+        }
+        if let Some(content_type_match) = req.content_type_match {
+            self.content_type_match = Some(content_type_match);
+        }
+        if let Some(upstream_path_prefix) = req.upstream_path_prefix {
+            self.upstream_path_prefix = Some(upstream_path_prefix);
+        }
+        if let Some(coalesce_requests) = req.coalesce_requests {
+            self.coalesce_requests = coalesce_requests;
+        }
+        if let Some(queue_depth) = req.queue_depth {
+            self.queue_depth = Some(queue_depth);
+        }
+        if let Some(queue_timeout_ms) = req.queue_timeout_ms {
+            self.queue_timeout_ms = Some(queue_timeout_ms);
+        }
+        if let Some(request_cost) = req.request_cost {
+            self.request_cost = Some(request_cost);
+        }
+        if let Some(request_cost_header) = req.request_cost_header {
+            self.request_cost_header = Some(request_cost_header);
+        }
+        if let Some(is_active) = req.is_active {
+            self.is_active = is_active;
+        }
+        if let Some(priority) = req.priority {
+            self.priority = priority;
+        }
+        if let Some(metadata) = req.metadata {
+            self.metadata = metadata;
+        }
+    }
+
+    /// Fields that differ in `updated`, sorted by name
+    ///
+    /// `id` and the timestamps are left out.
+    pub fn diff(&self, updated: &ApiRoute) -> Vec<RouteFieldChange> {
+        let fields = |route: &ApiRoute| match serde_json::to_value(route) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => serde_json::Map::new(),
+        };
+        let before = fields(self);
+        let mut after = fields(updated);
+
+        let mut changes: Vec<RouteFieldChange> = before
+            .into_iter()
+            .filter(|(field, _)| !matches!(field.as_str(), "id" | "created_at" | "updated_at"))
+            .filter_map(|(field, from)| {
+                let to = after.remove(&field).unwrap_or_default();
+                (from != to).then_some(RouteFieldChange { field, from, to })
+            })
+            .collect();
+        changes.sort_by(|a, b| a.field.cmp(&b.field));
+        changes
+    }
+
+    /// Whether `other` already has the path, method and query match, which are unique across routes
+    ///
+    /// With `ignore_path_case` paths differing only in ASCII case are the same
+    /// path, like the gateway matches them with `GATEWAY_CASE_INSENSITIVE_PATHS`.
+    pub fn conflicts_with(&self, other: &ApiRoute, ignore_path_case: bool) -> bool {
+        let same_path = if ignore_path_case {
+            self.path_pattern.eq_ignore_ascii_case(&other.path_pattern)
+        } else {
+            self.path_pattern == other.path_pattern
+        };
+        self.id != other.id
+            && self.method == other.method
+            && same_path
+            && self.query_match == other.query_match
+    }
+}
+
+/// One field a route update changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RouteFieldChange {
+    pub field: String,
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+}
+
+/// What is wrong with the route an update would produce
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RoutePreviewIssueKind {
+    /// The request itself fails validation
+    Invalid,
+    /// A backend service the route would point at doesn't exist
+    DanglingReference,
+    /// Another route already has the same path, method and query match
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoutePreviewIssue {
+    pub kind: RoutePreviewIssueKind,
+    pub message: String,
+}
+
+/// Result of `POST /api/routes/{id}/preview`; nothing is saved
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoutePreview {
+    /// The route as it would be after the update
+    pub route: ApiRoute,
+    pub changes: Vec<RouteFieldChange>,
+    /// Whether the update would be accepted and leaves no conflicts behind
+    pub valid: bool,
+    pub issues: Vec<RoutePreviewIssue>,
+}
+
 /// Table identifier for api_routes table
 #[derive(sea_query::Iden)]
 pub enum ApiRoutes {
@@ -123,12 +674,298 @@ pub enum ApiRoutes {
     PathPattern,
     Method,
     BackendServiceId,
+    GreenBackendServiceId,
+    ActiveColor,
+    QueryMatch,
     StripPathPrefix,
     PreserveHostHeader,
+    AllowMethodOverride,
     TimeoutMs,
+    IdleTimeoutMs,
+    FallbackBackendServiceIds,
+    AccessLogEnabled,
+    CookieDomain,
+    CookiePath,
+    DecompressResponse,
+    ApiVersion,
+    TimingHeaders,
+    DebugLogBody,
+    ContentTypeMatch,
+    UpstreamPathPrefix,
+    CoalesceRequests,
+    QueueDepth,
+    QueueTimeoutMs,
+    RequestCost,
+    RequestCostHeader,
     IsActive,
     Priority,
     Metadata,
     CreatedAt,
     UpdatedAt,
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn route(green_backend_service_id: Option<Uuid>) -> ApiRoute {
+        ApiRoute {
+            id: Uuid::new_v4(),
+            path_pattern: "/api".to_string(),
+            method: HttpMethod::GET,
+            backend_service_id: Uuid::new_v4(),
+            green_backend_service_id,
+            active_color: DeploymentColor::Blue,
+            query_match: serde_json::json!({}),
+            strip_path_prefix: false,
+            preserve_host_header: false,
+            allow_method_override: false,
+            timeout_ms: None,
+            idle_timeout_ms: None,
+            fallback_backend_service_ids: serde_json::json!([]),
+            access_log_enabled: true,
+            cookie_domain: None,
+            cookie_path: None,
+            decompress_response: false,
+            api_version: None,
+            timing_headers: false,
+            debug_log_body: false,
+            content_type_match: None,
+            upstream_path_prefix: None,
+            coalesce_requests: false,
+            queue_depth: None,
+            queue_timeout_ms: None,
+            request_cost: None,
+            request_cost_header: None,
+            is_active: true,
+            priority: 0,
+            metadata: serde_json::json!({}),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn create_request(
+        strip_path_prefix: Option<bool>,
+        preserve_host_header: Option<bool>,
+    ) -> CreateApiRouteRequest {
+        serde_json::from_value(serde_json::json!({
+            "path_pattern": "/api",
+            "method": "GET",
+            "backend_service_id": Uuid::new_v4(),
+            "strip_path_prefix": strip_path_prefix,
+            "preserve_host_header": preserve_host_header,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_omitted_flags_use_configured_defaults() {
+        let defaults = RouteDefaults {
+            strip_path_prefix: true,
+            preserve_host_header: true,
+        };
+
+        let req = create_request(None, None).with_defaults(defaults);
+        assert_eq!(req.strip_path_prefix, Some(true));
+        assert_eq!(req.preserve_host_header, Some(true));
+
+        // Explicit values win over the defaults
+        let req = create_request(Some(false), None).with_defaults(defaults);
+        assert_eq!(req.strip_path_prefix, Some(false));
+        assert_eq!(req.preserve_host_header, Some(true));
+
+        let req = create_request(None, Some(true)).with_defaults(RouteDefaults::default());
+        assert_eq!(req.strip_path_prefix, Some(false));
+        assert_eq!(req.preserve_host_header, Some(true));
+    }
+
+    #[test]
+    fn test_switch_and_rollback() {
+        let green = Uuid::new_v4();
+        let mut route = route(Some(green));
+        let blue = route.backend_service_id;
+        assert_eq!(route.active_backend_service_id(), blue);
+
+        route.active_color = route.active_color.flipped();
+        assert_eq!(route.active_color, DeploymentColor::Green);
+        assert_eq!(route.active_backend_service_id(), green);
+
+        assert_eq!(route.inactive_backend_service_id(), Some(blue));
+
+        // Rolling back is just another flip
+        route.active_color = route.active_color.flipped();
+        assert_eq!(route.active_color, DeploymentColor::Blue);
+        assert_eq!(route.active_backend_service_id(), blue);
+        assert_eq!(route.inactive_backend_service_id(), Some(green));
+    }
+
+    #[test]
+    fn test_validate_query_match() {
+        assert!(ApiRoute::validate_query_match(&serde_json::json!({})).is_ok());
+        assert!(ApiRoute::validate_query_match(
+            &serde_json::json!({"version": "beta", "debug": ""})
+        )
+        .is_ok());
+        assert!(ApiRoute::validate_query_match(&serde_json::json!(["version"])).is_err());
+        assert!(ApiRoute::validate_query_match(&serde_json::json!({"version": 2})).is_err());
+        assert!(ApiRoute::validate_query_match(&serde_json::json!({"": "beta"})).is_err());
+    }
+
+    #[test]
+    fn test_validate_upstream_path_prefix() {
+        assert!(ApiRoute::validate_upstream_path_prefix("").is_ok());
+        assert!(ApiRoute::validate_upstream_path_prefix("/internal").is_ok());
+        assert!(ApiRoute::validate_upstream_path_prefix("/internal/").is_ok());
+        assert!(ApiRoute::validate_upstream_path_prefix("internal").is_err());
+        assert!(ApiRoute::validate_upstream_path_prefix(" /internal").is_err());
+    }
+
+    #[test]
+    fn test_clone_copies_the_route_as_it_is_live() {
+        let mut original = route(Some(Uuid::new_v4()));
+        original.active_color = DeploymentColor::Green;
+        original.timeout_ms = Some(5000);
+        original.query_match = serde_json::json!({"version": "beta"});
+        let clone = CloneApiRouteRequest {
+            path_pattern: "/v2/api".to_string(),
+            method: None,
+            include_policies: None,
+        };
+
+        let req = original.clone_request(&clone);
+        assert_eq!(req.path_pattern, "/v2/api");
+        assert_eq!(req.method, HttpMethod::GET);
+        assert_eq!(req.backend_service_id, original.backend_service_id);
+        assert_eq!(
+            req.green_backend_service_id,
+            original.green_backend_service_id
+        );
+        // The copy serves from the same color as the original
+        assert_eq!(req.active_color, Some(DeploymentColor::Green));
+        assert!(req.validate_active_color().is_ok());
+        assert_eq!(req.timeout_ms, Some(5000));
+        assert_eq!(
+            req.query_match,
+            Some(serde_json::json!({"version": "beta"}))
+        );
+        assert!(clone.include_policies());
+    }
+
+    #[test]
+    fn test_green_needs_a_green_backend() {
+        let mut req = create_request(None, None);
+        req.active_color = Some(DeploymentColor::Green);
+        assert!(req.validate_active_color().is_err());
+
+        req.green_backend_service_id = Some(Uuid::new_v4());
+        assert!(req.validate_active_color().is_ok());
+        assert!(create_request(None, None).validate_active_color().is_ok());
+    }
+
+    #[test]
+    fn test_cloned_policy_names_fit_the_column() {
+        let clone_id = Uuid::new_v4();
+        let name = cloned_policy_name("per-ip", clone_id);
+        assert!(name.starts_with("per-ip (clone "));
+        assert_ne!(name, cloned_policy_name("per-ip", Uuid::new_v4()));
+
+        let long = cloned_policy_name(&"x".repeat(100), clone_id);
+        assert_eq!(long.chars().count(), POLICY_NAME_MAX_LENGTH);
+        assert!(long.ends_with(')'));
+    }
+
+    #[test]
+    fn test_update_can_clear_the_green_backend() {
+        let green = Uuid::new_v4();
+        let mut original = route(Some(green));
+        original.active_color = DeploymentColor::Green;
+
+        // Leaving the field out keeps the green backend
+        let req: UpdateApiRouteRequest =
+            serde_json::from_value(serde_json::json!({"timeout_ms": 5000})).unwrap();
+        let mut kept = original.clone();
+        kept.apply_update(req);
+        assert_eq!(kept.green_backend_service_id, Some(green));
+        assert_eq!(kept.active_color, DeploymentColor::Green);
+
+        // null clears it, and traffic goes back to blue
+        let req: UpdateApiRouteRequest =
+            serde_json::from_value(serde_json::json!({"green_backend_service_id": null})).unwrap();
+        let mut cleared = original.clone();
+        cleared.apply_update(req);
+        assert_eq!(cleared.green_backend_service_id, None);
+        assert_eq!(cleared.active_color, DeploymentColor::Blue);
+        assert_eq!(
+            cleared.active_backend_service_id(),
+            original.backend_service_id
+        );
+    }
+
+    #[test]
+    fn test_preview_diff_matches_the_update() {
+        let original = route(None);
+        let req: UpdateApiRouteRequest = serde_json::from_value(serde_json::json!({
+            "path_pattern": "/api/v2",
+            "timeout_ms": 5000,
+            "is_active": true,
+            "query_match": {"version": "beta"}
+        }))
+        .unwrap();
+
+        let mut updated = original.clone();
+        updated.apply_update(req);
+        let changes = original.diff(&updated);
+
+        // Unchanged values, like is_active, aren't listed
+        let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["path_pattern", "query_match", "timeout_ms"]);
+
+        // Each change ends up on the updated route, and the rest is untouched
+        let after = serde_json::to_value(&updated).unwrap();
+        let before = serde_json::to_value(&original).unwrap();
+        for change in &changes {
+            assert_eq!(change.from, before[&change.field]);
+            assert_eq!(change.to, after[&change.field]);
+        }
+        for (field, value) in before.as_object().unwrap() {
+            if !fields.contains(&field.as_str()) {
+                assert_eq!(&after[field], value, "{} changed", field);
+            }
+        }
+        assert!(original.diff(&original).is_empty());
+    }
+
+    #[test]
+    fn test_routes_with_the_same_match_conflict() {
+        let existing = route(None);
+        assert!(!existing.conflicts_with(&existing, false));
+
+        let mut other = route(None);
+        assert!(other.conflicts_with(&existing, false));
+
+        // Inactive routes still take the unique path, method and query match
+        other.is_active = false;
+        assert!(other.conflicts_with(&existing, false));
+
+        // Paths differing in case only conflict when the gateway folds them
+        other.path_pattern = existing.path_pattern.to_uppercase();
+        assert!(!other.conflicts_with(&existing, false));
+        assert!(other.conflicts_with(&existing, true));
+        other.path_pattern = existing.path_pattern.clone();
+
+        other.query_match = serde_json::json!({"version": "beta"});
+        assert!(!other.conflicts_with(&existing, false));
+        other.query_match = existing.query_match.clone();
+        other.method = HttpMethod::POST;
+        assert!(!other.conflicts_with(&existing, false));
+    }
+
+    #[test]
+    fn test_green_without_backend_stays_on_blue() {
+        let mut route = route(None);
+        route.active_color = DeploymentColor::Green;
+        assert_eq!(route.active_backend_service_id(), route.backend_service_id);
+        assert_eq!(route.inactive_backend_service_id(), None);
+    }
+}
@@ -36,6 +36,27 @@ pub struct AuditLog {
     pub created_at: DateTime<Utc>,
 }
 
+/// Number of audit events sharing one value, e.g. one event type or client IP
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct AuditLogCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// Audit log rollups over `[from, to)`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditLogStats {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub total: i64,
+    /// Most frequent first
+    pub by_event_type: Vec<AuditLogCount>,
+    /// Most frequent first
+    pub by_severity: Vec<AuditLogCount>,
+    /// Noisiest client IPs first; events without a client IP are left out
+    pub top_client_ips: Vec<AuditLogCount>,
+}
+
 /// Event types for audit logging
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -47,6 +68,8 @@ pub enum AuditEventType {
     InvalidRequest,
     BackendError,
     ConfigurationChanged,
+    /// Synthetic event raised when an escalation rule's threshold is crossed
+    SecurityAlert,
 }
 
 impl ToString for AuditEventType {
@@ -59,6 +82,7 @@ impl ToString for AuditEventType {
             AuditEventType::InvalidRequest => "invalid_request".to_string(),
             AuditEventType::BackendError => "backend_error".to_string(),
             AuditEventType::ConfigurationChanged => "configuration_changed".to_string(),
+            AuditEventType::SecurityAlert => "security_alert".to_string(),
         }
     }
 }
@@ -204,7 +228,7 @@ impl AuditLogBuilder {
 }
 
 /// Table identifier for audit_logs table
-#[derive(Iden)]
+#[derive(Iden, Clone, Copy)]
 pub enum AuditLogs {
     Table,
     Id,
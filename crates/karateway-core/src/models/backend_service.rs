@@ -6,6 +6,42 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::{models::HttpMethod, KaratewayError, Result};
+
+/// How the gateway finds the instances of a backend service
+#[derive(Debug, Clone, Default, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, ToSchema)]
+#[sqlx(type_name = "varchar")]
+pub enum DiscoveryType {
+    /// Always proxy to `base_url`
+    #[default]
+    #[sqlx(rename = "static")]
+    Static,
+    /// Resolve `srv_name` periodically and balance across its targets
+    #[sqlx(rename = "dns_srv")]
+    DnsSrv,
+}
+
+impl std::fmt::Display for DiscoveryType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiscoveryType::Static => write!(f, "static"),
+            DiscoveryType::DnsSrv => write!(f, "dns_srv"),
+        }
+    }
+}
+
+impl DiscoveryType {
+    /// Check that the settings this discovery type relies on are present
+    pub fn validate_srv_name(&self, srv_name: Option<&str>) -> Result<()> {
+        match (self, srv_name) {
+            (DiscoveryType::DnsSrv, None) => Err(KaratewayError::Validation(
+                "srv_name is required when discovery_type is dns_srv".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct BackendService {
     pub id: Uuid,
@@ -14,7 +50,31 @@ pub struct BackendService {
     pub base_url: String,
     pub health_check_url: Option<String>,
     pub health_check_interval_seconds: Option<i32>,
+    /// Method of health probes, `GET` when `None`
+    pub health_check_method: Option<HttpMethod>,
+    /// Headers sent with health probes, e.g. `{"Authorization": "Bearer ..."}`
+    pub health_check_headers: Option<serde_json::Value>,
+    /// Body sent with health probes
+    pub health_check_body: Option<String>,
     pub timeout_ms: Option<i32>,
+    pub discovery_type: DiscoveryType,
+    pub srv_name: Option<String>,
+    /// Max concurrent upstream requests across all routes (unlimited when `None`)
+    pub max_connections: Option<i32>,
+    /// Capacity units (e.g. instances) that capacity-scaled rate limits multiply
+    pub capacity: Option<i32>,
+    /// PEM client certificate chain presented to HTTPS backends that require mTLS
+    pub tls_client_cert_path: Option<String>,
+    /// PEM private key of `tls_client_cert_path`
+    pub tls_client_key_path: Option<String>,
+    /// Hostname sent as TLS SNI to HTTPS backends, instead of the address connected to
+    pub tls_sni: Option<String>,
+    /// Send a liveness `HEAD` this often while the backend is idle (off when `None`)
+    pub liveness_ping_interval_seconds: Option<i32>,
+    /// Overrides `GATEWAY_MAX_RESPONSE_HEADER_BYTES` for this backend
+    pub max_response_header_bytes: Option<i32>,
+    /// Overrides `GATEWAY_MAX_RESPONSE_HEADER_COUNT` for this backend
+    pub max_response_header_count: Option<i32>,
     pub is_active: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -36,8 +96,44 @@ pub struct CreateBackendServiceRequest {
     #[validate(range(min = 10, max = 3600))]
     pub health_check_interval_seconds: Option<i32>,
 
+    pub health_check_method: Option<HttpMethod>,
+
+    pub health_check_headers: Option<serde_json::Value>,
+
+    #[validate(length(max = 10000))]
+    pub health_check_body: Option<String>,
+
     #[validate(range(min = 100, max = 60000))]
     pub timeout_ms: Option<i32>,
+
+    pub discovery_type: Option<DiscoveryType>,
+
+    #[validate(length(min = 1, max = 255))]
+    pub srv_name: Option<String>,
+
+    #[validate(range(min = 1, max = 100000))]
+    pub max_connections: Option<i32>,
+
+    #[validate(range(min = 1, max = 1000000))]
+    pub capacity: Option<i32>,
+
+    #[validate(length(min = 1, max = 500))]
+    pub tls_client_cert_path: Option<String>,
+
+    #[validate(length(min = 1, max = 500))]
+    pub tls_client_key_path: Option<String>,
+
+    #[validate(length(min = 1, max = 253))]
+    pub tls_sni: Option<String>,
+
+    #[validate(range(min = 5, max = 3600))]
+    pub liveness_ping_interval_seconds: Option<i32>,
+
+    #[validate(range(min = 1024, max = 1048576))]
+    pub max_response_header_bytes: Option<i32>,
+
+    #[validate(range(min = 1, max = 1000))]
+    pub max_response_header_count: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
@@ -56,16 +152,129 @@ pub struct UpdateBackendServiceRequest {
     #[validate(range(min = 10, max = 3600))]
     pub health_check_interval_seconds: Option<i32>,
 
+    pub health_check_method: Option<HttpMethod>,
+
+    pub health_check_headers: Option<serde_json::Value>,
+
+    #[validate(length(max = 10000))]
+    pub health_check_body: Option<String>,
+
     #[validate(range(min = 100, max = 60000))]
     pub timeout_ms: Option<i32>,
 
+    pub discovery_type: Option<DiscoveryType>,
+
+    #[validate(length(min = 1, max = 255))]
+    pub srv_name: Option<String>,
+
+    #[validate(range(min = 1, max = 100000))]
+    pub max_connections: Option<i32>,
+
+    #[validate(range(min = 1, max = 1000000))]
+    pub capacity: Option<i32>,
+
+    #[validate(length(min = 1, max = 500))]
+    pub tls_client_cert_path: Option<String>,
+
+    #[validate(length(min = 1, max = 500))]
+    pub tls_client_key_path: Option<String>,
+
+    #[validate(length(min = 1, max = 253))]
+    pub tls_sni: Option<String>,
+
+    #[validate(range(min = 5, max = 3600))]
+    pub liveness_ping_interval_seconds: Option<i32>,
+
+    #[validate(range(min = 1024, max = 1048576))]
+    pub max_response_header_bytes: Option<i32>,
+
+    #[validate(range(min = 1, max = 1000))]
+    pub max_response_header_count: Option<i32>,
+
     pub is_active: Option<bool>,
 }
 
+/// Copy an existing service under a new name, see `POST /api/services/{id}/clone`
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct CloneBackendServiceRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+
+    /// Defaults to the original service's base URL
+    #[validate(url)]
+    pub base_url: Option<String>,
+}
+
 impl BackendService {
+    /// A create request for a copy of this service named as `clone` says
+    ///
+    /// Routes stay on the original; the copy starts active with no routes.
+    pub fn clone_request(&self, clone: &CloneBackendServiceRequest) -> CreateBackendServiceRequest {
+        CreateBackendServiceRequest {
+            name: clone.name.clone(),
+            description: self.description.clone(),
+            base_url: clone
+                .base_url
+                .clone()
+                .unwrap_or_else(|| self.base_url.clone()),
+            health_check_url: self.health_check_url.clone(),
+            health_check_interval_seconds: self.health_check_interval_seconds,
+            health_check_method: self.health_check_method.clone(),
+            health_check_headers: self.health_check_headers.clone(),
+            health_check_body: self.health_check_body.clone(),
+            timeout_ms: self.timeout_ms,
+            discovery_type: Some(self.discovery_type.clone()),
+            srv_name: self.srv_name.clone(),
+            max_connections: self.max_connections,
+            capacity: self.capacity,
+            tls_client_cert_path: self.tls_client_cert_path.clone(),
+            tls_client_key_path: self.tls_client_key_path.clone(),
+            tls_sni: self.tls_sni.clone(),
+            liveness_ping_interval_seconds: self.liveness_ping_interval_seconds,
+            max_response_header_bytes: self.max_response_header_bytes,
+            max_response_header_count: self.max_response_header_count,
+        }
+    }
+
     pub fn is_healthy(&self) -> bool {
         self.is_active
     }
+
+    /// Check that `health_check_headers` maps header names to string values
+    pub fn validate_health_check_headers(headers: Option<&serde_json::Value>) -> Result<()> {
+        let Some(headers) = headers else {
+            return Ok(());
+        };
+        let headers = headers.as_object().ok_or_else(|| {
+            KaratewayError::Validation("health_check_headers must be a JSON object".to_string())
+        })?;
+
+        for (name, value) in headers {
+            if name.is_empty() {
+                return Err(KaratewayError::Validation(
+                    "health_check_headers names must not be empty".to_string(),
+                ));
+            }
+            if !value.is_string() {
+                return Err(KaratewayError::Validation(format!(
+                    "health_check_headers.{} must be a string",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that a client certificate comes with its key, and the other way round
+    pub fn validate_client_cert(cert_path: Option<&str>, key_path: Option<&str>) -> Result<()> {
+        match (cert_path, key_path) {
+            (Some(_), None) | (None, Some(_)) => Err(KaratewayError::Validation(
+                "tls_client_cert_path and tls_client_key_path must be set together".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
 }
 
 /// Table identifier for backend_services table
@@ -78,7 +287,20 @@ pub enum BackendServices {
     BaseUrl,
     HealthCheckUrl,
     HealthCheckIntervalSeconds,
+    HealthCheckMethod,
+    HealthCheckHeaders,
+    HealthCheckBody,
     TimeoutMs,
+    DiscoveryType,
+    SrvName,
+    MaxConnections,
+    Capacity,
+    TlsClientCertPath,
+    TlsClientKeyPath,
+    TlsSni,
+    LivenessPingIntervalSeconds,
+    MaxResponseHeaderBytes,
+    MaxResponseHeaderCount,
     IsActive,
     CreatedAt,
     UpdatedAt,
@@ -0,0 +1,38 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+/// One proxied request, as stored in `gateway_metrics`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct GatewayMetric {
+    pub id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    pub route_id: Option<Uuid>,
+    pub method: Option<String>,
+    /// Request path without the query string
+    pub path: Option<String>,
+    pub status_code: Option<i32>,
+    pub response_time_ms: Option<f32>,
+    pub backend_service_id: Option<Uuid>,
+    /// Why the request failed, e.g. `ConnectTimedout (upstream)`; never contains request data
+    pub error_message: Option<String>,
+    /// Response size and metric tag
+    pub metadata: serde_json::Value,
+}
+
+/// Table identifier for gateway_metrics table
+#[derive(sea_query::Iden)]
+pub enum GatewayMetrics {
+    Table,
+    Id,
+    Timestamp,
+    RouteId,
+    Method,
+    Path,
+    StatusCode,
+    ResponseTimeMs,
+    BackendServiceId,
+    ErrorMessage,
+    Metadata,
+}
@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::{KaratewayError, Result};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, ToSchema)]
+#[sqlx(type_name = "varchar")]
+pub enum TagMatchType {
+    /// `pattern` is a path prefix, e.g. `/admin`
+    #[sqlx(rename = "path_prefix")]
+    PathPrefix,
+    /// `header_name` must carry the value `pattern` (any value when `pattern` is empty)
+    #[sqlx(rename = "header")]
+    Header,
+}
+
+impl std::fmt::Display for TagMatchType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagMatchType::PathPrefix => write!(f, "path_prefix"),
+            TagMatchType::Header => write!(f, "header"),
+        }
+    }
+}
+
+/// Attaches a metrics tag to requests by path prefix or header value
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct MetricTagRule {
+    pub id: Uuid,
+    /// Label value written to metrics, e.g. `admin` or `team-payments`
+    pub tag: String,
+    pub match_type: TagMatchType,
+    pub header_name: Option<String>,
+    pub pattern: String,
+    pub is_active: bool,
+    pub priority: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct CreateMetricTagRuleRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub tag: String,
+
+    pub match_type: TagMatchType,
+
+    #[validate(length(min = 1, max = 100))]
+    pub header_name: Option<String>,
+
+    #[validate(length(max = 500))]
+    pub pattern: String,
+
+    pub priority: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct UpdateMetricTagRuleRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub tag: Option<String>,
+
+    pub match_type: Option<TagMatchType>,
+
+    #[validate(length(min = 1, max = 100))]
+    pub header_name: Option<String>,
+
+    #[validate(length(max = 500))]
+    pub pattern: Option<String>,
+
+    pub is_active: Option<bool>,
+
+    pub priority: Option<i32>,
+}
+
+impl TagMatchType {
+    /// Check that a rule of this type has what it needs to match
+    pub fn validate_rule(&self, header_name: Option<&str>, pattern: &str) -> Result<()> {
+        match self {
+            TagMatchType::PathPrefix if !pattern.starts_with('/') => Err(
+                KaratewayError::Validation("pattern must be a path starting with '/'".to_string()),
+            ),
+            TagMatchType::Header if header_name.unwrap_or_default().is_empty() => Err(
+                KaratewayError::Validation("header_name is required for header rules".to_string()),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Table identifier for metric_tag_rules table
+#[derive(sea_query::Iden)]
+pub enum MetricTagRules {
+    Table,
+    Id,
+    Tag,
+    MatchType,
+    HeaderName,
+    Pattern,
+    IsActive,
+    Priority,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_rule() {
+        assert!(TagMatchType::PathPrefix
+            .validate_rule(None, "/admin")
+            .is_ok());
+        assert!(TagMatchType::PathPrefix
+            .validate_rule(None, "admin")
+            .is_err());
+
+        assert!(TagMatchType::Header
+            .validate_rule(Some("X-Team"), "payments")
+            .is_ok());
+        assert!(TagMatchType::Header
+            .validate_rule(Some("X-Team"), "")
+            .is_ok());
+        let err = TagMatchType::Header
+            .validate_rule(None, "payments")
+            .unwrap_err();
+        assert!(err.to_string().contains("header_name"));
+    }
+}
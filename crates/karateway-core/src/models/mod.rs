@@ -2,14 +2,20 @@ pub mod api_route;
 pub mod audit_log;
 pub mod backend_service;
 pub mod config_version;
+pub mod gateway_metric;
 pub mod load_balancer;
+pub mod metric_tag_rule;
 pub mod rate_limit;
+pub mod routes_openapi;
 pub mod whitelist_rule;
 
 pub use api_route::*;
 pub use audit_log::*;
 pub use backend_service::*;
 pub use config_version::*;
+pub use gateway_metric::*;
 pub use load_balancer::*;
+pub use metric_tag_rule::*;
 pub use rate_limit::*;
+pub use routes_openapi::*;
 pub use whitelist_rule::*;
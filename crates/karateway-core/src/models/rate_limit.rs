@@ -5,6 +5,8 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::KaratewayError;
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, ToSchema)]
 #[sqlx(type_name = "varchar")]
 pub enum IdentifierType {
@@ -16,6 +18,9 @@ pub enum IdentifierType {
     UserId,
     #[sqlx(rename = "global")]
     Global,
+    /// A header named by the limit's `identifier_header`, e.g. `X-Tenant-ID`
+    #[sqlx(rename = "header")]
+    Header,
 }
 
 impl std::fmt::Display for IdentifierType {
@@ -25,6 +30,44 @@ impl std::fmt::Display for IdentifierType {
             IdentifierType::ApiKey => write!(f, "api_key"),
             IdentifierType::UserId => write!(f, "user_id"),
             IdentifierType::Global => write!(f, "global"),
+            IdentifierType::Header => write!(f, "header"),
+        }
+    }
+}
+
+impl IdentifierType {
+    /// Check that a `header` limit names a valid header to count requests by
+    pub fn validate_header(&self, identifier_header: Option<&str>) -> crate::Result<()> {
+        match (self, identifier_header) {
+            (IdentifierType::Header, None) => Err(KaratewayError::Validation(
+                "identifier_header is required when identifier_type is header".to_string(),
+            )),
+            (IdentifierType::Header, Some(header))
+                if !header
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_') =>
+            {
+                Err(KaratewayError::Validation(format!(
+                    "identifier_header {:?} is not a valid header name",
+                    header
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::str::FromStr for IdentifierType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ip" => Ok(IdentifierType::Ip),
+            "api_key" => Ok(IdentifierType::ApiKey),
+            "user_id" => Ok(IdentifierType::UserId),
+            "global" => Ok(IdentifierType::Global),
+            "header" => Ok(IdentifierType::Header),
+            _ => Err(format!("Invalid identifier type: {}", s)),
         }
     }
 }
@@ -37,8 +80,15 @@ pub struct RateLimit {
     pub max_requests: i32,
     pub window_seconds: i32,
     pub identifier_type: IdentifierType,
+    /// Header requests are counted by when `identifier_type` is `header`
+    pub identifier_header: Option<String>,
     pub is_active: bool,
     pub burst_size: Option<i32>,
+    /// Requests per window for each unit of the backend's `capacity`, overriding
+    /// `max_requests` when the backend has one
+    pub capacity_factor: Option<f64>,
+    /// Replace the global limits on the limit's route instead of stacking with them
+    pub override_global: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -58,8 +108,17 @@ pub struct CreateRateLimitRequest {
 
     pub identifier_type: IdentifierType,
 
+    #[validate(length(min = 1, max = 100))]
+    pub identifier_header: Option<String>,
+
     #[validate(range(min = 1, max = 1000000))]
     pub burst_size: Option<i32>,
+
+    #[validate(range(min = 0.001, max = 1000000.0))]
+    pub capacity_factor: Option<f64>,
+
+    #[serde(default)]
+    pub override_global: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
@@ -77,10 +136,114 @@ pub struct UpdateRateLimitRequest {
 
     pub identifier_type: Option<IdentifierType>,
 
+    #[validate(length(min = 1, max = 100))]
+    pub identifier_header: Option<String>,
+
     pub is_active: Option<bool>,
 
     #[validate(range(min = 1, max = 1000000))]
     pub burst_size: Option<i32>,
+
+    #[validate(range(min = 0.001, max = 1000000.0))]
+    pub capacity_factor: Option<f64>,
+
+    pub override_global: Option<bool>,
+}
+
+impl RateLimit {
+    /// Requests allowed per window on a route whose backend has `capacity`
+    ///
+    /// A limit with a `capacity_factor` allows `capacity_factor * capacity`
+    /// requests, rounded to the nearest whole request and never below one, so
+    /// it follows the backend as it is scaled. Limits without a factor, and
+    /// backends without a capacity, keep the absolute `max_requests`.
+    pub fn effective_max_requests(&self, capacity: Option<i32>) -> i32 {
+        match (self.capacity_factor, capacity) {
+            (Some(factor), Some(capacity)) => (factor * capacity as f64)
+                .round()
+                .clamp(1.0, i32::MAX as f64) as i32,
+            _ => self.max_requests,
+        }
+    }
+
+    /// A create request for a copy of this limit on route `api_route_id`
+    pub fn clone_request(&self, api_route_id: Uuid) -> CreateRateLimitRequest {
+        CreateRateLimitRequest {
+            name: super::cloned_policy_name(&self.name, api_route_id),
+            api_route_id: Some(api_route_id),
+            max_requests: self.max_requests,
+            window_seconds: self.window_seconds,
+            identifier_type: self.identifier_type.clone(),
+            identifier_header: self.identifier_header.clone(),
+            burst_size: self.burst_size,
+            capacity_factor: self.capacity_factor,
+            override_global: self.override_global,
+        }
+    }
+
+    /// Check that a request costing `request_cost` can ever fit this limit
+    ///
+    /// A window admits at most `max_requests` slots and a bucket holds at
+    /// most `max_requests + burst_size` tokens, so a costlier request would be
+    /// rejected every time. Capacity-scaled limits are checked against their
+    /// absolute `max_requests`.
+    pub fn validate_request_cost(
+        max_requests: i32,
+        burst_size: Option<i32>,
+        request_cost: i32,
+    ) -> crate::Result<()> {
+        let budget = max_requests.saturating_add(burst_size.unwrap_or(0));
+        if request_cost > budget {
+            return Err(KaratewayError::Validation(format!(
+                "request_cost {} is more than the {} a rate limit of {} requests{} ever allows",
+                request_cost,
+                budget,
+                max_requests,
+                burst_size.map_or(String::new(), |burst| format!(" with a burst of {}", burst))
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check that `override_global` is only set on a route-specific limit
+    pub fn validate_override(
+        api_route_id: Option<Uuid>,
+        override_global: bool,
+    ) -> crate::Result<()> {
+        if override_global && api_route_id.is_none() {
+            return Err(KaratewayError::Validation(
+                "override_global only applies to route-specific limits".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Rate limits the gateway applies to a route
+///
+/// Precedence:
+/// 1. Route-specific limits (`api_route_id` = route)
+/// 2. The configured default limit, only when the route has no
+///    route-specific limits of its own
+///
+/// Global limits (`api_route_id` = NULL) are stacked on top, unless one of
+/// the route-specific limits has `override_global`, in which case the route
+/// only gets its own limits.
+pub fn effective_rate_limits(
+    route_limits: &[RateLimit],
+    global_limits: &[RateLimit],
+    default_limit: Option<&RateLimit>,
+) -> Vec<RateLimit> {
+    let mut limits = route_limits.to_vec();
+
+    if limits.is_empty() {
+        limits.extend(default_limit.cloned());
+    }
+
+    if !route_limits.iter().any(|limit| limit.override_global) {
+        limits.extend_from_slice(global_limits);
+    }
+    limits
 }
 
 /// Table identifier for rate_limits table
@@ -93,8 +256,112 @@ pub enum RateLimits {
     MaxRequests,
     WindowSeconds,
     IdentifierType,
+    IdentifierHeader,
     IsActive,
     BurstSize,
+    CapacityFactor,
+    OverrideGlobal,
     CreatedAt,
     UpdatedAt,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(max_requests: i32, capacity_factor: Option<f64>) -> RateLimit {
+        RateLimit {
+            id: Uuid::new_v4(),
+            name: "per-instance".to_string(),
+            api_route_id: None,
+            max_requests,
+            window_seconds: 60,
+            identifier_type: IdentifierType::Global,
+            identifier_header: None,
+            is_active: true,
+            burst_size: None,
+            capacity_factor,
+            override_global: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_clone_moves_the_limit_to_the_new_route() {
+        let original = limit(100, Some(2.5));
+        let clone_route = Uuid::new_v4();
+
+        let req = original.clone_request(clone_route);
+        assert_eq!(req.api_route_id, Some(clone_route));
+        assert_ne!(req.name, original.name);
+        assert_eq!(req.max_requests, 100);
+        assert_eq!(req.capacity_factor, Some(2.5));
+        assert_eq!(original.api_route_id, None);
+    }
+
+    #[test]
+    fn test_request_cost_must_fit_the_budget() {
+        assert!(RateLimit::validate_request_cost(10, None, 10).is_ok());
+        assert!(RateLimit::validate_request_cost(10, None, 11).is_err());
+        // A bucket's burst raises what one request may take
+        assert!(RateLimit::validate_request_cost(10, Some(5), 15).is_ok());
+        assert!(RateLimit::validate_request_cost(10, Some(5), 16).is_err());
+    }
+
+    #[test]
+    fn test_route_limits_stack_with_global_limits() {
+        let route_id = Uuid::new_v4();
+        let route_limit = RateLimit {
+            name: "orders".to_string(),
+            api_route_id: Some(route_id),
+            ..limit(5000, None)
+        };
+        let global_limit = limit(100, None);
+
+        let limits = effective_rate_limits(&[route_limit], &[global_limit], None);
+        let names: Vec<&str> = limits.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["orders", "per-instance"]);
+    }
+
+    #[test]
+    fn test_override_global_replaces_global_limits() {
+        let route_id = Uuid::new_v4();
+        let route_limit = RateLimit {
+            name: "orders".to_string(),
+            api_route_id: Some(route_id),
+            override_global: true,
+            ..limit(5000, None)
+        };
+        let default_limit = limit(10, None);
+
+        let limits =
+            effective_rate_limits(&[route_limit], &[limit(100, None)], Some(&default_limit));
+        assert_eq!(limits.len(), 1);
+        assert_eq!(limits[0].name, "orders");
+
+        // Only route-specific limits can override
+        assert!(RateLimit::validate_override(None, true).is_err());
+        assert!(RateLimit::validate_override(Some(route_id), true).is_ok());
+        assert!(RateLimit::validate_override(None, false).is_ok());
+    }
+
+    #[test]
+    fn test_capacity_factor_scales_max_requests() {
+        let scaled = limit(100, Some(250.0));
+
+        assert_eq!(scaled.effective_max_requests(Some(4)), 1000);
+        // Scaling the backend out raises the limit with it
+        assert_eq!(scaled.effective_max_requests(Some(6)), 1500);
+        // Rounded, and never below a single request
+        assert_eq!(limit(100, Some(2.5)).effective_max_requests(Some(3)), 8);
+        assert_eq!(limit(100, Some(0.001)).effective_max_requests(Some(1)), 1);
+    }
+
+    #[test]
+    fn test_absolute_limits_are_the_default() {
+        assert_eq!(limit(100, None).effective_max_requests(Some(4)), 100);
+        // A backend without a capacity falls back to the absolute limit
+        assert_eq!(limit(100, Some(250.0)).effective_max_requests(None), 100);
+    }
+}
@@ -0,0 +1,157 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::ApiRoute;
+
+/// Minimal OpenAPI 3.0 document of the routes the gateway proxies
+///
+/// One operation per route, under its `path_pattern` and method, tagged with
+/// the name of the backend service its active color points at. There are no
+/// request or response schemas, the gateway doesn't know them; what the
+/// gateway itself matches on (query match, API version, content type) and
+/// which backends serve the route go in the `x-karateway-route` extension.
+///
+/// Routes sharing a path and method that differ only in what they match on
+/// can't be told apart in OpenAPI; the one the gateway tries first (highest
+/// priority) is the operation and the others are listed in its
+/// `x-karateway-alternatives`.
+///
+/// `service_names` maps backend service ids to their names; a backend
+/// missing from it is left unnamed.
+pub fn routes_openapi(routes: &[ApiRoute], service_names: &HashMap<Uuid, String>) -> Value {
+    let service_name = |id: Uuid| service_names.get(&id).cloned();
+
+    let mut routes: Vec<&ApiRoute> = routes.iter().collect();
+    routes.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.path_pattern.cmp(&b.path_pattern))
+    });
+
+    let mut paths = Map::new();
+    for route in routes {
+        let backend_id = route.active_backend_service_id();
+        let backend = service_name(backend_id);
+        let extension = json!({
+            "route_id": route.id,
+            "backend_service_id": backend_id,
+            "backend_service": backend,
+            "fallback_backend_service_ids": route.fallback_service_ids(),
+            "query_match": route.query_match,
+            "api_version": route.api_version,
+            "content_type_match": route.content_type_match,
+            "priority": route.priority,
+        });
+
+        let item = paths
+            .entry(route.path_pattern.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        let method = route.method.to_string().to_lowercase();
+        if let Some(operation) = item.get_mut(&method) {
+            operation["x-karateway-alternatives"]
+                .as_array_mut()
+                .expect("operations are created with an alternatives list")
+                .push(extension);
+            continue;
+        }
+
+        item[method] = json!({
+            "operationId": route.id,
+            "summary": format!(
+                "{} {} -> {}",
+                route.method,
+                route.path_pattern,
+                backend.as_deref().unwrap_or("unknown backend")
+            ),
+            "description": format!(
+                "Matches every path starting with {}",
+                route.path_pattern
+            ),
+            "tags": backend.into_iter().collect::<Vec<_>>(),
+            "responses": {
+                "default": { "description": "Response of the backend service" }
+            },
+            "x-karateway-route": extension,
+            "x-karateway-alternatives": [],
+        });
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Karateway gateway routes",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::HttpMethod;
+
+    fn route(
+        path_pattern: &str,
+        method: HttpMethod,
+        backend_service_id: Uuid,
+        priority: i32,
+    ) -> ApiRoute {
+        ApiRoute {
+            path_pattern: path_pattern.to_string(),
+            method,
+            backend_service_id,
+            priority,
+            ..crate::models::api_route::tests::route(None)
+        }
+    }
+
+    #[test]
+    fn test_spec_lists_the_configured_routes() {
+        let users = Uuid::new_v4();
+        let orders = Uuid::new_v4();
+        let services =
+            HashMap::from([(users, "users".to_string()), (orders, "orders".to_string())]);
+
+        let list = route("/api/users", HttpMethod::GET, users, 0);
+        let list_v2 = route("/api/users", HttpMethod::GET, users, 10);
+        let create = route("/api/users", HttpMethod::POST, users, 0);
+        let orders_route = route("/api/orders", HttpMethod::DELETE, orders, 0);
+        let spec = routes_openapi(
+            &[
+                list.clone(),
+                list_v2.clone(),
+                create.clone(),
+                orders_route.clone(),
+            ],
+            &services,
+        );
+
+        assert_eq!(spec["openapi"], "3.0.3");
+        let paths = spec["paths"].as_object().unwrap();
+        assert_eq!(
+            paths.keys().collect::<Vec<_>>(),
+            vec!["/api/orders", "/api/users"]
+        );
+
+        let get = &spec["paths"]["/api/users"]["get"];
+        // The route tried first is the operation
+        assert_eq!(get["operationId"], json!(list_v2.id));
+        assert_eq!(get["tags"], json!(["users"]));
+        assert_eq!(get["x-karateway-route"]["backend_service_id"], json!(users));
+        assert_eq!(
+            get["x-karateway-alternatives"][0]["route_id"],
+            json!(list.id)
+        );
+
+        assert_eq!(
+            spec["paths"]["/api/users"]["post"]["operationId"],
+            json!(create.id)
+        );
+        let delete = &spec["paths"]["/api/orders"]["delete"];
+        assert_eq!(delete["operationId"], json!(orders_route.id));
+        assert_eq!(delete["summary"], "DELETE /api/orders -> orders");
+        assert_eq!(delete["x-karateway-route"]["backend_service"], "orders");
+    }
+}
@@ -1,10 +1,13 @@
 use chrono::{DateTime, Utc};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use utoipa::ToSchema;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::{KaratewayError, Result};
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type, PartialEq, Eq, ToSchema)]
 #[sqlx(type_name = "varchar")]
 pub enum RuleType {
@@ -29,6 +32,181 @@ impl std::fmt::Display for RuleType {
     }
 }
 
+impl RuleType {
+    /// Check that `config` has the shape the gateway expects for this rule type
+    ///
+    /// Returns a validation error naming the missing or mistyped field.
+    pub fn validate_config(&self, config: &serde_json::Value) -> Result<()> {
+        if !config.is_object() {
+            return Err(KaratewayError::Validation(format!(
+                "config for {} rule must be a JSON object",
+                self
+            )));
+        }
+
+        match self {
+            RuleType::Ip => require_string_array(config, "allowed_ips", true),
+            RuleType::ApiKey => require_string_array(config, "allowed_keys", true),
+            RuleType::Jwt => {
+                match config.get("jwt_secret") {
+                    Some(serde_json::Value::String(secret)) if !secret.is_empty() => {}
+                    Some(_) => {
+                        return Err(KaratewayError::Validation(
+                            "config.jwt_secret must be a non-empty string".to_string(),
+                        ))
+                    }
+                    None => {
+                        return Err(KaratewayError::Validation(
+                            "config.jwt_secret is required for jwt rules".to_string(),
+                        ))
+                    }
+                }
+                require_string_array(config, "allowed_issuers", false)?;
+                require_string_array(config, "allowed_audiences", false)
+            }
+            RuleType::Custom => parse_custom_conditions(config).map(|_| ()),
+        }
+    }
+}
+
+/// Longest regex accepted in a custom rule's `matches` condition
+pub const MAX_HEADER_PATTERN_LENGTH: usize = 256;
+
+/// How a custom rule tests a header value
+#[derive(Debug, Clone)]
+pub enum HeaderMatcher {
+    /// The header only has to be present
+    Present,
+    Equals(String),
+    Matches(Regex),
+}
+
+/// One header condition of a custom whitelist rule
+#[derive(Debug, Clone)]
+pub struct HeaderCondition {
+    pub header: String,
+    pub matcher: HeaderMatcher,
+}
+
+impl HeaderCondition {
+    /// Whether the request's value for `header` (`None` when absent) satisfies the condition
+    pub fn matches(&self, value: Option<&str>) -> bool {
+        match (&self.matcher, value) {
+            (_, None) => false,
+            (HeaderMatcher::Present, Some(_)) => true,
+            (HeaderMatcher::Equals(expected), Some(value)) => value == expected,
+            (HeaderMatcher::Matches(regex), Some(value)) => regex.is_match(value),
+        }
+    }
+}
+
+/// Parse a custom rule's `conditions`, compiling `matches` patterns
+///
+/// Each condition names a `header` and at most one of `equals` or `matches`;
+/// with neither, the header only has to be present. Patterns use the `regex`
+/// crate, which matches in linear time, and are capped at
+/// [`MAX_HEADER_PATTERN_LENGTH`] characters.
+///
+/// A single condition may also be given at the top level, without the
+/// `conditions` array. A config with no condition at all is rejected, since
+/// the rule would allow nothing.
+pub fn parse_custom_conditions(config: &serde_json::Value) -> Result<Vec<HeaderCondition>> {
+    match config.get("conditions") {
+        Some(serde_json::Value::Array(conditions)) if conditions.is_empty() => Err(
+            KaratewayError::Validation("config.conditions must not be empty".to_string()),
+        ),
+        Some(serde_json::Value::Array(conditions)) => conditions
+            .iter()
+            .enumerate()
+            .map(|(index, condition)| {
+                parse_header_condition(&format!("config.conditions[{}]", index), condition)
+            })
+            .collect(),
+        Some(_) => Err(KaratewayError::Validation(
+            "config.conditions must be an array".to_string(),
+        )),
+        None if config.get("header").is_some() => {
+            Ok(vec![parse_header_condition("config", config)?])
+        }
+        None => Err(KaratewayError::Validation(
+            "config.conditions is required".to_string(),
+        )),
+    }
+}
+
+/// Parse one condition, `path` naming it in errors (`config.conditions[0]`)
+fn parse_header_condition(path: &str, condition: &serde_json::Value) -> Result<HeaderCondition> {
+    let field = |name: &str| -> Result<Option<&str>> {
+        match condition.get(name) {
+            Some(serde_json::Value::String(value)) => Ok(Some(value)),
+            Some(_) => Err(KaratewayError::Validation(format!(
+                "{}.{} must be a string",
+                path, name
+            ))),
+            None => Ok(None),
+        }
+    };
+
+    let header = match field("header")? {
+        Some(header) if !header.is_empty() => header.to_string(),
+        _ => {
+            return Err(KaratewayError::Validation(format!(
+                "{}.header is required",
+                path
+            )))
+        }
+    };
+
+    let matcher = match (field("equals")?, field("matches")?) {
+        (Some(_), Some(_)) => {
+            return Err(KaratewayError::Validation(format!(
+                "{} can't have both equals and matches",
+                path
+            )))
+        }
+        (Some(expected), None) => HeaderMatcher::Equals(expected.to_string()),
+        (None, Some(pattern)) => {
+            if pattern.chars().count() > MAX_HEADER_PATTERN_LENGTH {
+                return Err(KaratewayError::Validation(format!(
+                    "{}.matches is longer than {} characters",
+                    path, MAX_HEADER_PATTERN_LENGTH
+                )));
+            }
+            let regex = Regex::new(pattern).map_err(|e| {
+                KaratewayError::Validation(format!("{}.matches is not a valid regex: {}", path, e))
+            })?;
+            HeaderMatcher::Matches(regex)
+        }
+        (None, None) => HeaderMatcher::Present,
+    };
+
+    Ok(HeaderCondition { header, matcher })
+}
+
+/// Check that `config[field]` is an array of strings
+fn require_string_array(config: &serde_json::Value, field: &str, required: bool) -> Result<()> {
+    match config.get(field) {
+        Some(serde_json::Value::Array(items)) => {
+            if let Some(index) = items.iter().position(|item| !item.is_string()) {
+                return Err(KaratewayError::Validation(format!(
+                    "config.{}[{}] must be a string",
+                    field, index
+                )));
+            }
+            Ok(())
+        }
+        Some(_) => Err(KaratewayError::Validation(format!(
+            "config.{} must be an array of strings",
+            field
+        ))),
+        None if required => Err(KaratewayError::Validation(format!(
+            "config.{} is required",
+            field
+        ))),
+        None => Ok(()),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct WhitelistRule {
     pub id: Uuid,
@@ -72,6 +250,51 @@ pub struct UpdateWhitelistRuleRequest {
     pub priority: Option<i32>,
 }
 
+/// A token to check against a `jwt` whitelist rule
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+pub struct ValidateTokenRequest {
+    #[validate(length(min = 1, max = 8192))]
+    pub token: String,
+}
+
+/// Whether a token passes a `jwt` whitelist rule, and why not
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TokenValidationResult {
+    pub valid: bool,
+    /// The first check the token failed, `None` when valid
+    pub reason: Option<String>,
+    /// The token as sent, with its signature redacted
+    pub token: String,
+    /// The decoded JOSE header, `None` when the token couldn't be decoded
+    pub header: Option<serde_json::Value>,
+    /// The decoded claims, shown even when the token fails
+    pub claims: Option<serde_json::Value>,
+}
+
+impl WhitelistRule {
+    /// A create request for a copy of this rule on route `api_route_id`
+    pub fn clone_request(&self, api_route_id: Uuid) -> CreateWhitelistRuleRequest {
+        CreateWhitelistRuleRequest {
+            rule_name: super::cloned_policy_name(&self.rule_name, api_route_id),
+            rule_type: self.rule_type.clone(),
+            api_route_id: Some(api_route_id),
+            config: self.config.clone(),
+            priority: Some(self.priority),
+        }
+    }
+}
+
+/// Whitelist rules the gateway applies to a route: its own plus the global ones, highest priority first
+pub fn effective_whitelist_rules(
+    route_rules: &[WhitelistRule],
+    global_rules: &[WhitelistRule],
+) -> Vec<WhitelistRule> {
+    let mut rules = route_rules.to_vec();
+    rules.extend_from_slice(global_rules);
+    rules.sort_by(|a, b| b.priority.cmp(&a.priority));
+    rules
+}
+
 /// Table identifier for whitelist_rules table
 #[derive(sea_query::Iden)]
 pub enum WhitelistRules {
@@ -86,3 +309,132 @@ pub enum WhitelistRules {
     CreatedAt,
     UpdatedAt,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_ip_config() {
+        assert!(RuleType::Ip
+            .validate_config(&json!({"allowed_ips": ["10.0.0.0/8", "127.0.0.1"]}))
+            .is_ok());
+
+        let err = RuleType::Ip
+            .validate_config(&json!({"allowed_ip": ["127.0.0.1"]}))
+            .unwrap_err();
+        assert!(err.to_string().contains("allowed_ips"));
+
+        let err = RuleType::Ip
+            .validate_config(&json!({"allowed_ips": "127.0.0.1"}))
+            .unwrap_err();
+        assert!(err.to_string().contains("allowed_ips"));
+
+        let err = RuleType::Ip
+            .validate_config(&json!({"allowed_ips": ["127.0.0.1", 42]}))
+            .unwrap_err();
+        assert!(err.to_string().contains("allowed_ips[1]"));
+    }
+
+    #[test]
+    fn test_validate_api_key_config() {
+        assert!(RuleType::ApiKey
+            .validate_config(&json!({"allowed_keys": ["key-1"]}))
+            .is_ok());
+
+        let err = RuleType::ApiKey.validate_config(&json!({})).unwrap_err();
+        assert!(err.to_string().contains("allowed_keys"));
+    }
+
+    #[test]
+    fn test_validate_jwt_config() {
+        assert!(RuleType::Jwt
+            .validate_config(&json!({"jwt_secret": "s3cret", "allowed_issuers": ["auth"]}))
+            .is_ok());
+
+        let err = RuleType::Jwt.validate_config(&json!({})).unwrap_err();
+        assert!(err.to_string().contains("jwt_secret"));
+
+        let err = RuleType::Jwt
+            .validate_config(&json!({"jwt_secret": "s3cret", "allowed_audiences": "api"}))
+            .unwrap_err();
+        assert!(err.to_string().contains("allowed_audiences"));
+    }
+
+    #[test]
+    fn test_validate_custom_config() {
+        // A rule that could never allow anything is refused up front
+        assert!(RuleType::Custom.validate_config(&json!({})).is_err());
+        assert!(RuleType::Custom
+            .validate_config(&json!({"conditions": []}))
+            .is_err());
+        assert!(RuleType::Custom
+            .validate_config(&json!("anything"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_custom_conditions() {
+        assert!(RuleType::Custom
+            .validate_config(&json!({"conditions": [
+                {"header": "User-Agent", "matches": "^Mozilla"},
+                {"header": "X-Env", "equals": "prod"},
+                {"header": "X-Request-ID"},
+            ]}))
+            .is_ok());
+
+        let err = RuleType::Custom
+            .validate_config(
+                &json!({"conditions": [{"header": "User-Agent", "matches": "(unclosed"}]}),
+            )
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("conditions[0].matches is not a valid regex"));
+
+        let long = "a".repeat(MAX_HEADER_PATTERN_LENGTH + 1);
+        let err = RuleType::Custom
+            .validate_config(&json!({"conditions": [{"header": "X", "matches": long}]}))
+            .unwrap_err();
+        assert!(err.to_string().contains("longer than"));
+
+        let err = RuleType::Custom
+            .validate_config(&json!({"conditions": [{"matches": "^a"}]}))
+            .unwrap_err();
+        assert!(err.to_string().contains("conditions[0].header"));
+    }
+
+    #[test]
+    fn test_header_condition_matches() {
+        let conditions = parse_custom_conditions(&json!({"conditions": [
+            {"header": "User-Agent", "matches": "^Mozilla"},
+            {"header": "X-Env", "equals": "prod"},
+        ]}))
+        .unwrap();
+
+        assert!(conditions[0].matches(Some("Mozilla/5.0 (X11; Linux x86_64)")));
+        assert!(!conditions[0].matches(Some("curl/8.5.0")));
+        assert!(!conditions[0].matches(None));
+        assert!(conditions[1].matches(Some("prod")));
+        assert!(!conditions[1].matches(Some("production")));
+    }
+
+    #[test]
+    fn test_single_top_level_condition() {
+        let config = json!({"header": "User-Agent", "matches": "^Mozilla"});
+        assert!(RuleType::Custom.validate_config(&config).is_ok());
+
+        let conditions = parse_custom_conditions(&config).unwrap();
+        assert_eq!(conditions.len(), 1);
+        assert!(conditions[0].matches(Some("Mozilla/5.0 (X11; Linux x86_64)")));
+        assert!(!conditions[0].matches(Some("curl/8.5.0")));
+
+        let err = RuleType::Custom
+            .validate_config(&json!({"header": "User-Agent", "matches": "("}))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("config.matches is not a valid regex"));
+    }
+}
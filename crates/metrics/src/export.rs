@@ -0,0 +1,345 @@
+use std::fmt::Write;
+
+use crate::registry::{MetricsSnapshot, RouteMetrics, LATENCY_BUCKETS_MS};
+
+/// Output format of the metrics endpoint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Prometheus text exposition format
+    #[default]
+    Prometheus,
+    /// The same snapshot as structured JSON
+    Json,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "prometheus" | "text" => Ok(ExportFormat::Prometheus),
+            "json" => Ok(ExportFormat::Json),
+            other => Err(format!(
+                "Invalid metrics format: {} (expected prometheus or json)",
+                other
+            )),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+            ExportFormat::Json => "application/json",
+        }
+    }
+
+    pub fn render(&self, snapshot: &MetricsSnapshot) -> String {
+        match self {
+            ExportFormat::Prometheus => render_prometheus(snapshot),
+            ExportFormat::Json => serde_json::to_string(snapshot).unwrap_or_default(),
+        }
+    }
+}
+
+fn render_prometheus(snapshot: &MetricsSnapshot) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP karateway_requests_total Requests handled per route\n");
+    out.push_str("# TYPE karateway_requests_total counter\n");
+    for route in &snapshot.routes {
+        let _ = writeln!(
+            out,
+            "karateway_requests_total{{{}}} {}",
+            route_labels(route),
+            route.requests
+        );
+    }
+
+    out.push_str(
+        "# HELP karateway_request_errors_total Requests per route that failed or returned 5xx\n",
+    );
+    out.push_str("# TYPE karateway_request_errors_total counter\n");
+    for route in &snapshot.routes {
+        let _ = writeln!(
+            out,
+            "karateway_request_errors_total{{{}}} {}",
+            route_labels(route),
+            route.errors
+        );
+    }
+
+    out.push_str("# HELP karateway_request_duration_ms Request latency per route\n");
+    out.push_str("# TYPE karateway_request_duration_ms histogram\n");
+    for route in &snapshot.routes {
+        let labels = route_labels(route);
+        for (bound, count) in LATENCY_BUCKETS_MS.iter().zip(&route.latency_buckets) {
+            let _ = writeln!(
+                out,
+                "karateway_request_duration_ms_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "karateway_request_duration_ms_bucket{{{},le=\"+Inf\"}} {}",
+            labels, route.requests
+        );
+        let _ = writeln!(
+            out,
+            "karateway_request_duration_ms_sum{{{}}} {}",
+            labels, route.latency_sum_ms
+        );
+        let _ = writeln!(
+            out,
+            "karateway_request_duration_ms_count{{{}}} {}",
+            labels, route.requests
+        );
+    }
+
+    out.push_str(
+        "# HELP karateway_backend_healthy Whether a backend service passes its health check\n",
+    );
+    out.push_str("# TYPE karateway_backend_healthy gauge\n");
+    for backend in &snapshot.backends {
+        let _ = writeln!(
+            out,
+            "karateway_backend_healthy{{backend=\"{}\",id=\"{}\"}} {}",
+            escape_label(&backend.name),
+            backend.id,
+            backend.healthy as u8
+        );
+    }
+
+    // Connection gauges are only exported for backends with a cap
+    let capped: Vec<_> = snapshot
+        .backends
+        .iter()
+        .filter_map(|backend| Some((backend, backend.max_connections?)))
+        .collect();
+
+    out.push_str(
+        "# HELP karateway_backend_active_connections Requests in flight to a capped backend\n",
+    );
+    out.push_str("# TYPE karateway_backend_active_connections gauge\n");
+    for (backend, _) in &capped {
+        let _ = writeln!(
+            out,
+            "karateway_backend_active_connections{{backend=\"{}\",id=\"{}\"}} {}",
+            escape_label(&backend.name),
+            backend.id,
+            backend.active_connections
+        );
+    }
+
+    out.push_str("# HELP karateway_backend_max_connections Concurrency cap of a backend\n");
+    out.push_str("# TYPE karateway_backend_max_connections gauge\n");
+    for (backend, max_connections) in &capped {
+        let _ = writeln!(
+            out,
+            "karateway_backend_max_connections{{backend=\"{}\",id=\"{}\"}} {}",
+            escape_label(&backend.name),
+            backend.id,
+            max_connections
+        );
+    }
+
+    if let Some(pool) = &snapshot.database_pool {
+        out.push_str("# HELP karateway_db_pool_connections Database pool connections by state\n");
+        out.push_str("# TYPE karateway_db_pool_connections gauge\n");
+        let _ = writeln!(
+            out,
+            "karateway_db_pool_connections{{state=\"in_use\"}} {}",
+            pool.in_use
+        );
+        let _ = writeln!(
+            out,
+            "karateway_db_pool_connections{{state=\"idle\"}} {}",
+            pool.idle
+        );
+
+        out.push_str("# HELP karateway_db_pool_max_connections Size cap of the database pool\n");
+        out.push_str("# TYPE karateway_db_pool_max_connections gauge\n");
+        let _ = writeln!(
+            out,
+            "karateway_db_pool_max_connections {}",
+            pool.max_connections
+        );
+    }
+
+    if let Some(connections) = &snapshot.connections {
+        out.push_str("# HELP karateway_connections Open client connections\n");
+        out.push_str("# TYPE karateway_connections gauge\n");
+        let _ = writeln!(out, "karateway_connections {}", connections.active);
+
+        if let Some(max_connections) = connections.max_connections {
+            out.push_str("# HELP karateway_max_connections Cap on open client connections\n");
+            out.push_str("# TYPE karateway_max_connections gauge\n");
+            let _ = writeln!(out, "karateway_max_connections {}", max_connections);
+        }
+
+        out.push_str(
+            "# HELP karateway_connections_rejected_total Connections closed because the cap was reached\n",
+        );
+        out.push_str("# TYPE karateway_connections_rejected_total counter\n");
+        let _ = writeln!(
+            out,
+            "karateway_connections_rejected_total {}",
+            connections.rejected
+        );
+
+        out.push_str(
+            "# HELP karateway_header_read_timeouts_total Connections closed for a slow request head\n",
+        );
+        out.push_str("# TYPE karateway_header_read_timeouts_total counter\n");
+        let _ = writeln!(
+            out,
+            "karateway_header_read_timeouts_total {}",
+            connections.header_read_timeouts
+        );
+
+        out.push_str(
+            "# HELP karateway_alpn_rejected_total TLS connections closed for negotiating a protocol the listener doesn't serve\n",
+        );
+        out.push_str("# TYPE karateway_alpn_rejected_total counter\n");
+        let _ = writeln!(
+            out,
+            "karateway_alpn_rejected_total {}",
+            connections.alpn_rejected
+        );
+
+        out.push_str(
+            "# HELP karateway_client_connections_rejected_total Connections closed because their client IP reached its cap\n",
+        );
+        out.push_str("# TYPE karateway_client_connections_rejected_total counter\n");
+        let _ = writeln!(
+            out,
+            "karateway_client_connections_rejected_total {}",
+            connections.client_connections_rejected
+        );
+
+        out.push_str(
+            "# HELP karateway_client_requests_rejected_total Requests rejected because their client IP reached its cap\n",
+        );
+        out.push_str("# TYPE karateway_client_requests_rejected_total counter\n");
+        let _ = writeln!(
+            out,
+            "karateway_client_requests_rejected_total {}",
+            connections.client_requests_rejected
+        );
+    }
+
+    out
+}
+
+/// `route="..."`, plus `tag="..."` and `api_version="..."` when the requests had them
+fn route_labels(route: &RouteMetrics) -> String {
+    let mut labels = format!("route=\"{}\"", escape_label(&route.route));
+    if let Some(tag) = &route.tag {
+        labels.push_str(&format!(",tag=\"{}\"", escape_label(tag)));
+    }
+    if let Some(api_version) = &route.api_version {
+        labels.push_str(&format!(",api_version=\"{}\"", escape_label(api_version)));
+    }
+    labels
+}
+
+/// Escape a Prometheus label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::{BackendHealth, Connections, DatabasePool, GatewayMetrics};
+    use std::time::Duration;
+
+    fn snapshot() -> MetricsSnapshot {
+        let metrics = GatewayMetrics::new();
+        metrics.record_request("GET /api", None, None, 200, Duration::from_millis(20));
+        metrics.record_request("GET /api", None, None, 503, Duration::from_millis(40));
+        metrics.record_request(
+            "GET /api",
+            Some("admin"),
+            None,
+            200,
+            Duration::from_millis(5),
+        );
+        metrics.record_request("GET /api", None, Some("2"), 200, Duration::from_millis(8));
+        let mut snapshot = metrics.snapshot(vec![BackendHealth {
+            id: "b1".to_string(),
+            name: "orders".to_string(),
+            healthy: true,
+            active_connections: 3,
+            max_connections: Some(10),
+        }]);
+        snapshot.database_pool = Some(DatabasePool {
+            in_use: 4,
+            idle: 2,
+            max_connections: 10,
+        });
+        snapshot.connections = Some(Connections {
+            active: 12,
+            max_connections: Some(10_000),
+            rejected: 0,
+            header_read_timeouts: 3,
+            alpn_rejected: 2,
+            client_connections_rejected: 4,
+            client_requests_rejected: 0,
+        });
+        snapshot
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("json".parse(), Ok(ExportFormat::Json));
+        assert_eq!("Prometheus".parse(), Ok(ExportFormat::Prometheus));
+        assert!("protobuf".parse::<ExportFormat>().is_err());
+        assert_eq!(ExportFormat::default(), ExportFormat::Prometheus);
+    }
+
+    #[test]
+    fn test_formats_render_the_same_snapshot() {
+        let snapshot = snapshot();
+
+        let text = ExportFormat::Prometheus.render(&snapshot);
+        assert!(text.contains("karateway_requests_total{route=\"GET /api\"} 2"));
+        assert!(text.contains("karateway_requests_total{route=\"GET /api\",tag=\"admin\"} 1"));
+        assert!(text.contains("karateway_requests_total{route=\"GET /api\",api_version=\"2\"} 1"));
+        assert!(text.contains("karateway_request_errors_total{route=\"GET /api\"} 1"));
+        assert!(
+            text.contains("karateway_request_duration_ms_bucket{route=\"GET /api\",le=\"+Inf\"} 2")
+        );
+        assert!(text.contains("karateway_backend_healthy{backend=\"orders\",id=\"b1\"} 1"));
+        assert!(
+            text.contains("karateway_backend_active_connections{backend=\"orders\",id=\"b1\"} 3")
+        );
+        assert!(text.contains("karateway_db_pool_connections{state=\"in_use\"} 4"));
+        assert!(text.contains("karateway_db_pool_max_connections 10"));
+        assert!(text.contains("karateway_connections 12"));
+        assert!(text.contains("karateway_header_read_timeouts_total 3"));
+        assert!(text.contains("karateway_alpn_rejected_total 2"));
+        assert!(text.contains("karateway_client_connections_rejected_total 4"));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&ExportFormat::Json.render(&snapshot)).unwrap();
+        assert_eq!(json["routes"][0]["route"], "GET /api");
+        assert_eq!(json["routes"][0]["requests"], 2);
+        assert_eq!(json["routes"][0]["errors"], 1);
+        assert_eq!(json["routes"][0]["error_rate"], 0.5);
+        assert!(json["routes"][0].get("tag").is_none());
+        assert_eq!(json["routes"][1]["api_version"], "2");
+        assert_eq!(json["routes"][2]["tag"], "admin");
+        assert!(json["routes"][0]["latency_ms"]["p50"].is_number());
+        assert_eq!(json["backends"][0]["healthy"], true);
+        assert_eq!(json["backends"][0]["active_connections"], 3);
+        assert_eq!(json["backends"][0]["max_connections"], 10);
+        assert_eq!(json["database_pool"]["in_use"], 4);
+        assert_eq!(json["connections"]["active"], 12);
+    }
+}
@@ -1,14 +1,8 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
-}
+pub mod export;
+pub mod registry;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
-    }
-}
+pub use export::ExportFormat;
+pub use registry::{
+    BackendHealth, Connections, DatabasePool, GatewayMetrics, LatencyPercentiles, MetricsSnapshot,
+    RouteMetrics,
+};
@@ -0,0 +1,291 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Upper bounds (inclusive) of the latency histogram buckets, in milliseconds
+pub const LATENCY_BUCKETS_MS: [f64; 12] = [
+    5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1_000.0, 2_500.0, 5_000.0, 10_000.0, 30_000.0,
+];
+
+/// Running totals for one route
+#[derive(Debug, Clone, Default)]
+struct RouteCounters {
+    requests: u64,
+    errors: u64,
+    latency_sum_ms: f64,
+    /// Per-bucket (non-cumulative) counts; the last slot is `+Inf`
+    buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+/// In-process request metrics aggregated per route, metric tag and API version
+///
+/// Both export formats render from the same [`MetricsSnapshot`], so the
+/// Prometheus and JSON views can never disagree.
+#[derive(Debug, Default)]
+pub struct GatewayMetrics {
+    routes: DashMap<(String, Option<String>, Option<String>), RouteCounters>,
+}
+
+impl GatewayMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a finished request
+    ///
+    /// `tag` comes from the request's metric tag rule, if any, and
+    /// `api_version` from the request itself. A status of 0 (no response
+    /// written) or any 5xx counts as an error.
+    pub fn record_request(
+        &self,
+        route: &str,
+        tag: Option<&str>,
+        api_version: Option<&str>,
+        status: u16,
+        latency: Duration,
+    ) {
+        let latency_ms = latency.as_secs_f64() * 1_000.0;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+
+        let mut counters = self
+            .routes
+            .entry((
+                route.to_string(),
+                tag.map(str::to_string),
+                api_version.map(str::to_string),
+            ))
+            .or_default();
+        counters.requests += 1;
+        if status == 0 || status >= 500 {
+            counters.errors += 1;
+        }
+        counters.latency_sum_ms += latency_ms;
+        counters.buckets[bucket] += 1;
+    }
+
+    /// Aggregate the current counters, together with the backend health known to the caller
+    pub fn snapshot(&self, backends: Vec<BackendHealth>) -> MetricsSnapshot {
+        let mut routes: Vec<RouteMetrics> = self
+            .routes
+            .iter()
+            .map(|entry| {
+                let (route, tag, api_version) = entry.key();
+                RouteMetrics::from_counters(
+                    route,
+                    tag.as_deref(),
+                    api_version.as_deref(),
+                    entry.value(),
+                )
+            })
+            .collect();
+        routes.sort_by(|a, b| {
+            (&a.route, &a.tag, &a.api_version).cmp(&(&b.route, &b.tag, &b.api_version))
+        });
+
+        let mut backends = backends;
+        backends.sort_by(|a, b| a.name.cmp(&b.name));
+
+        MetricsSnapshot {
+            routes,
+            backends,
+            database_pool: None,
+            connections: None,
+        }
+    }
+}
+
+/// Point-in-time view of all gateway metrics
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSnapshot {
+    pub routes: Vec<RouteMetrics>,
+    pub backends: Vec<BackendHealth>,
+    /// Database connection pool utilization, when the caller knows it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub database_pool: Option<DatabasePool>,
+    /// Downstream connections of the proxy listeners, when the caller knows them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connections: Option<Connections>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteMetrics {
+    /// Route label, e.g. `GET /api/orders`
+    pub route: String,
+    /// Metric tag the requests were counted under, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// API version the requests asked for, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    pub requests: u64,
+    pub errors: u64,
+    /// `errors / requests`, 0 when there were no requests
+    pub error_rate: f64,
+    pub latency_ms: LatencyPercentiles,
+    #[serde(skip)]
+    pub latency_sum_ms: f64,
+    /// Cumulative counts per bucket of [`LATENCY_BUCKETS_MS`], then `+Inf`
+    #[serde(skip)]
+    pub latency_buckets: Vec<u64>,
+}
+
+/// Latency percentiles estimated from the histogram buckets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LatencyPercentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendHealth {
+    pub id: String,
+    pub name: String,
+    pub healthy: bool,
+    /// Requests currently holding one of the backend's connection slots
+    pub active_connections: u32,
+    /// Configured concurrency cap, `None` when unlimited
+    pub max_connections: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DatabasePool {
+    /// Connections checked out by a query
+    pub in_use: u32,
+    pub idle: u32,
+    pub max_connections: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Connections {
+    /// Open client connections
+    pub active: u64,
+    /// Configured cap, `None` when unlimited
+    pub max_connections: Option<u64>,
+    /// Connections closed on arrival because the cap was reached
+    pub rejected: u64,
+    /// Connections closed for not sending a request head in time
+    pub header_read_timeouts: u64,
+    /// TLS connections closed for negotiating a protocol outside `GATEWAY_TLS_ALPN`
+    pub alpn_rejected: u64,
+    /// Connections closed because their client IP had too many open
+    pub client_connections_rejected: u64,
+    /// Requests answered with a `429` because their client IP had too many in flight
+    pub client_requests_rejected: u64,
+}
+
+impl RouteMetrics {
+    fn from_counters(
+        route: &str,
+        tag: Option<&str>,
+        api_version: Option<&str>,
+        counters: &RouteCounters,
+    ) -> Self {
+        let latency_buckets: Vec<u64> = counters
+            .buckets
+            .iter()
+            .scan(0, |total, count| {
+                *total += count;
+                Some(*total)
+            })
+            .collect();
+
+        let error_rate = if counters.requests == 0 {
+            0.0
+        } else {
+            counters.errors as f64 / counters.requests as f64
+        };
+
+        Self {
+            route: route.to_string(),
+            tag: tag.map(str::to_string),
+            api_version: api_version.map(str::to_string),
+            requests: counters.requests,
+            errors: counters.errors,
+            error_rate,
+            latency_ms: LatencyPercentiles {
+                p50: percentile(&latency_buckets, 0.50),
+                p90: percentile(&latency_buckets, 0.90),
+                p99: percentile(&latency_buckets, 0.99),
+            },
+            latency_sum_ms: counters.latency_sum_ms,
+            latency_buckets,
+        }
+    }
+}
+
+/// Estimate a percentile by linear interpolation inside the bucket it falls in
+///
+/// Works like Prometheus' `histogram_quantile`; values in the `+Inf` bucket
+/// are reported as the largest finite bound.
+fn percentile(cumulative: &[u64], quantile: f64) -> f64 {
+    let total = cumulative.last().copied().unwrap_or(0);
+    if total == 0 {
+        return 0.0;
+    }
+
+    let rank = quantile * total as f64;
+    let index = cumulative
+        .iter()
+        .position(|count| *count as f64 >= rank)
+        .unwrap_or(cumulative.len() - 1);
+
+    let Some(upper) = LATENCY_BUCKETS_MS.get(index) else {
+        return LATENCY_BUCKETS_MS[LATENCY_BUCKETS_MS.len() - 1];
+    };
+    let lower = if index == 0 {
+        0.0
+    } else {
+        LATENCY_BUCKETS_MS[index - 1]
+    };
+    let below = if index == 0 { 0 } else { cumulative[index - 1] };
+    let in_bucket = cumulative[index] - below;
+    if in_bucket == 0 {
+        return *upper;
+    }
+
+    lower + (upper - lower) * (rank - below as f64) / in_bucket as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_aggregates_per_route() {
+        let metrics = GatewayMetrics::new();
+        for _ in 0..9 {
+            metrics.record_request("GET /api", None, None, 200, Duration::from_millis(20));
+        }
+        metrics.record_request("GET /api", None, None, 502, Duration::from_millis(800));
+        metrics.record_request("POST /orders", None, None, 0, Duration::from_millis(3));
+
+        let snapshot = metrics.snapshot(Vec::new());
+        assert_eq!(snapshot.routes.len(), 2);
+
+        let api = &snapshot.routes[0];
+        assert_eq!(api.route, "GET /api");
+        assert_eq!(api.requests, 10);
+        assert_eq!(api.errors, 1);
+        assert!((api.error_rate - 0.1).abs() < f64::EPSILON);
+        assert_eq!(api.latency_buckets.last(), Some(&10));
+        // 9 of 10 requests fall in the 10-25ms bucket
+        assert!(api.latency_ms.p50 > 10.0 && api.latency_ms.p50 <= 25.0);
+        assert!(api.latency_ms.p99 > 500.0 && api.latency_ms.p99 <= 1_000.0);
+
+        assert_eq!(snapshot.routes[1].errors, 1);
+    }
+
+    #[test]
+    fn test_percentile_of_empty_and_overflowing_histograms() {
+        assert_eq!(percentile(&[0; LATENCY_BUCKETS_MS.len() + 1], 0.5), 0.0);
+
+        let metrics = GatewayMetrics::new();
+        metrics.record_request("GET /slow", None, None, 200, Duration::from_secs(60));
+        let snapshot = metrics.snapshot(Vec::new());
+        assert_eq!(snapshot.routes[0].latency_ms.p99, 30_000.0);
+    }
+}
@@ -10,6 +10,7 @@ import type {
     JsonResponse,
     RateLimit,
     ServicesHealthResponse,
+    StatsResponse,
     UpdateApiRouteRequest,
     UpdateBackendServiceRequest,
     UpdateRateLimitRequest,
@@ -92,6 +93,11 @@ class ApiClient {
         return this.request(url)
     }
 
+    // Stats
+    async getStats(): Promise<JsonResponse<StatsResponse>> {
+        return this.request('/api/stats')
+    }
+
     // API Routes
     async getRoutes(page = 1, limit = 10, search?: string): Promise<JsonResponse<ApiRoute[]>> {
         const params = new URLSearchParams({
@@ -17,6 +17,8 @@ export interface MetaResponse {
 }
 
 // Backend Service
+export type DiscoveryType = 'Static' | 'DnsSrv'
+
 export interface BackendService {
   id: string
   name: string
@@ -25,6 +27,19 @@ export interface BackendService {
   health_check_url?: string
   health_check_interval_seconds?: number
   timeout_ms?: number
+  discovery_type: DiscoveryType
+  srv_name?: string
+  max_connections?: number
+  health_check_method?: HttpMethod
+  health_check_headers?: Record<string, string>
+  health_check_body?: string
+  tls_sni?: string
+  capacity?: number
+  tls_client_cert_path?: string
+  tls_client_key_path?: string
+  liveness_ping_interval_seconds?: number
+  max_response_header_bytes?: number
+  max_response_header_count?: number
   is_active: boolean
   created_at: string
   updated_at: string
@@ -37,6 +52,19 @@ export interface CreateBackendServiceRequest {
   health_check_url?: string
   health_check_interval_seconds?: number
   timeout_ms?: number
+  discovery_type?: DiscoveryType
+  srv_name?: string
+  max_connections?: number
+  health_check_method?: HttpMethod
+  health_check_headers?: Record<string, string>
+  health_check_body?: string
+  tls_sni?: string
+  capacity?: number
+  tls_client_cert_path?: string
+  tls_client_key_path?: string
+  liveness_ping_interval_seconds?: number
+  max_response_header_bytes?: number
+  max_response_header_count?: number
 }
 
 export interface UpdateBackendServiceRequest {
@@ -46,20 +74,55 @@ export interface UpdateBackendServiceRequest {
   health_check_url?: string
   health_check_interval_seconds?: number
   timeout_ms?: number
+  discovery_type?: DiscoveryType
+  srv_name?: string
+  max_connections?: number
+  health_check_method?: HttpMethod
+  health_check_headers?: Record<string, string>
+  health_check_body?: string
+  tls_sni?: string
+  capacity?: number
+  tls_client_cert_path?: string
+  tls_client_key_path?: string
+  liveness_ping_interval_seconds?: number
+  max_response_header_bytes?: number
+  max_response_header_count?: number
   is_active?: boolean
 }
 
 // API Route
 export type HttpMethod = 'GET' | 'POST' | 'PUT' | 'DELETE' | 'PATCH' | 'HEAD' | 'OPTIONS'
 
+export type DeploymentColor = 'Blue' | 'Green'
+
 export interface ApiRoute {
   id: string
   path_pattern: string
   method: HttpMethod
   backend_service_id: string
+  green_backend_service_id?: string
+  active_color: DeploymentColor
+  query_match: Record<string, string>
+  allow_method_override: boolean
   strip_path_prefix: boolean
   preserve_host_header: boolean
   timeout_ms?: number
+  idle_timeout_ms?: number
+  fallback_backend_service_ids: string[]
+  access_log_enabled: boolean
+  cookie_domain?: string
+  cookie_path?: string
+  decompress_response: boolean
+  api_version?: string
+  timing_headers: boolean
+  debug_log_body: boolean
+  content_type_match?: string
+  upstream_path_prefix?: string
+  coalesce_requests: boolean
+  queue_depth?: number
+  queue_timeout_ms?: number
+  request_cost?: number
+  request_cost_header?: string
   is_active: boolean
   priority: number
   metadata: Record<string, any>
@@ -71,9 +134,29 @@ export interface CreateApiRouteRequest {
   path_pattern: string
   method: HttpMethod
   backend_service_id: string
+  green_backend_service_id?: string
+  active_color?: DeploymentColor
+  query_match?: Record<string, string>
+  allow_method_override?: boolean
   strip_path_prefix?: boolean
   preserve_host_header?: boolean
   timeout_ms?: number
+  idle_timeout_ms?: number
+  fallback_backend_service_ids?: string[]
+  access_log_enabled?: boolean
+  cookie_domain?: string
+  cookie_path?: string
+  decompress_response?: boolean
+  api_version?: string
+  timing_headers?: boolean
+  debug_log_body?: boolean
+  content_type_match?: string
+  upstream_path_prefix?: string
+  coalesce_requests?: boolean
+  queue_depth?: number
+  queue_timeout_ms?: number
+  request_cost?: number
+  request_cost_header?: string
   priority?: number
   metadata?: Record<string, any>
 }
@@ -82,26 +165,49 @@ export interface UpdateApiRouteRequest {
   path_pattern?: string
   method?: HttpMethod
   backend_service_id?: string
+  green_backend_service_id?: string
+  query_match?: Record<string, string>
+  allow_method_override?: boolean
   strip_path_prefix?: boolean
   preserve_host_header?: boolean
   timeout_ms?: number
+  idle_timeout_ms?: number
+  fallback_backend_service_ids?: string[]
+  access_log_enabled?: boolean
+  cookie_domain?: string
+  cookie_path?: string
+  decompress_response?: boolean
+  api_version?: string
+  timing_headers?: boolean
+  debug_log_body?: boolean
+  content_type_match?: string
+  upstream_path_prefix?: string
+  coalesce_requests?: boolean
+  queue_depth?: number
+  queue_timeout_ms?: number
+  request_cost?: number
+  request_cost_header?: string
   is_active?: boolean
   priority?: number
   metadata?: Record<string, any>
 }
 
 // Rate Limit
-export type IdentifierType = 'Ip' | 'ApiKey' | 'UserId' | 'Global'
+export type IdentifierType = 'Ip' | 'ApiKey' | 'UserId' | 'Global' | 'Header'
 
 export interface RateLimit {
   id: string
   name: string
   api_route_id?: string
   identifier_type: IdentifierType
+  identifier_header?: string
   max_requests: number
   window_seconds: number
   burst_size?: number
+  capacity_factor?: number
+  override_global: boolean
   is_active: boolean
+  enforced: boolean
   created_at: string
   updated_at: string
 }
@@ -110,18 +216,24 @@ export interface CreateRateLimitRequest {
   name: string
   api_route_id?: string
   identifier_type: IdentifierType
+  identifier_header?: string
   max_requests: number
   window_seconds: number
   burst_size?: number
+  capacity_factor?: number
+  override_global?: boolean
 }
 
 export interface UpdateRateLimitRequest {
   name?: string
   api_route_id?: string
   identifier_type?: IdentifierType
+  identifier_header?: string
   max_requests?: number
   window_seconds?: number
   burst_size?: number
+  capacity_factor?: number
+  override_global?: boolean
   is_active?: boolean
 }
 
@@ -157,9 +269,48 @@ export interface UpdateWhitelistRuleRequest {
   priority?: number
 }
 
+// Metric Tag Rule
+export type TagMatchType = 'PathPrefix' | 'Header'
+
+export interface MetricTagRule {
+  id: string
+  tag: string
+  match_type: TagMatchType
+  header_name?: string
+  pattern: string
+  is_active: boolean
+  priority: number
+  created_at: string
+  updated_at: string
+}
+
+export interface CreateMetricTagRuleRequest {
+  tag: string
+  match_type: TagMatchType
+  header_name?: string
+  pattern: string
+  priority?: number
+}
+
+export interface UpdateMetricTagRuleRequest {
+  tag?: string
+  match_type?: TagMatchType
+  header_name?: string
+  pattern?: string
+  is_active?: boolean
+  priority?: number
+}
+
 // Backend Service with Routes
 export interface BackendServiceWithRoutes extends BackendService {
   routes: ApiRoute[]
+  effective_policies?: EffectivePolicies[]
+}
+
+export interface EffectivePolicies {
+  api_route_id: string
+  rate_limits: RateLimit[]
+  whitelist_rules: WhitelistRule[]
 }
 
 // Service Health
@@ -177,6 +328,20 @@ export interface ServicesHealthResponse {
   last_checked: string
 }
 
+// Stats
+export interface StateCounts {
+  total: number
+  active: number
+  inactive: number
+}
+
+export interface StatsResponse {
+  services: StateCounts
+  routes: StateCounts
+  rate_limits: StateCounts
+  whitelist_rules: StateCounts
+}
+
 // Audit Logs
 export type AuditSeverity = 'info' | 'warning' | 'critical'
 export type AuditEventCategory = 'authentication' | 'rate_limit' | 'whitelist' | 'admin'
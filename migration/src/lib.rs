@@ -13,6 +13,35 @@ mod m20251116_075509_update_timestamp_triggers;
 mod m20251116_075511_audit_triggers;
 mod m20251116_075513_config_snapshot_functions;
 mod m20251116_075515_audit_logs;
+mod m20261014_000001_route_idle_timeout;
+mod m20261014_000002_backend_discovery;
+mod m20261014_000003_route_blue_green;
+mod m20261014_000004_route_query_match;
+mod m20261014_000005_backend_max_connections;
+mod m20261014_000006_route_method_override;
+mod m20261014_000007_route_request_queue;
+mod m20261014_000008_metric_tag_rules;
+mod m20261014_000009_route_coalesce_requests;
+mod m20261014_000010_route_upstream_path_prefix;
+mod m20261014_000011_backend_response_header_limits;
+mod m20261014_000012_backend_liveness_ping;
+mod m20261014_000013_route_content_type_match;
+mod m20261014_000014_route_debug_log_body;
+mod m20261014_000015_route_timing_headers;
+mod m20261014_000016_route_api_version;
+mod m20261014_000017_backend_client_cert;
+mod m20261014_000018_capacity_scaled_rate_limits;
+mod m20261014_000019_backend_tls_sni;
+mod m20261014_000020_backend_health_check_request;
+mod m20261014_000021_route_decompress_response;
+mod m20261014_000022_route_cookie_rewrite;
+mod m20261014_000023_route_access_log_enabled;
+mod m20261014_000024_route_fallback_backends;
+mod m20261014_000025_header_rate_limit_identifier;
+mod m20261014_000026_gateway_metrics_retention;
+mod m20261014_000027_rate_limit_override_global;
+mod m20261014_000028_route_request_cost;
+mod m20261014_000029_config_snapshot_all_rows;
 
 pub struct Migrator;
 
@@ -33,6 +62,35 @@ impl MigratorTrait for Migrator {
             Box::new(m20251116_075511_audit_triggers::Migration),
             Box::new(m20251116_075513_config_snapshot_functions::Migration),
             Box::new(m20251116_075515_audit_logs::Migration),
+            Box::new(m20261014_000001_route_idle_timeout::Migration),
+            Box::new(m20261014_000002_backend_discovery::Migration),
+            Box::new(m20261014_000003_route_blue_green::Migration),
+            Box::new(m20261014_000004_route_query_match::Migration),
+            Box::new(m20261014_000005_backend_max_connections::Migration),
+            Box::new(m20261014_000006_route_method_override::Migration),
+            Box::new(m20261014_000007_route_request_queue::Migration),
+            Box::new(m20261014_000008_metric_tag_rules::Migration),
+            Box::new(m20261014_000009_route_coalesce_requests::Migration),
+            Box::new(m20261014_000010_route_upstream_path_prefix::Migration),
+            Box::new(m20261014_000011_backend_response_header_limits::Migration),
+            Box::new(m20261014_000012_backend_liveness_ping::Migration),
+            Box::new(m20261014_000013_route_content_type_match::Migration),
+            Box::new(m20261014_000014_route_debug_log_body::Migration),
+            Box::new(m20261014_000015_route_timing_headers::Migration),
+            Box::new(m20261014_000016_route_api_version::Migration),
+            Box::new(m20261014_000017_backend_client_cert::Migration),
+            Box::new(m20261014_000018_capacity_scaled_rate_limits::Migration),
+            Box::new(m20261014_000019_backend_tls_sni::Migration),
+            Box::new(m20261014_000020_backend_health_check_request::Migration),
+            Box::new(m20261014_000021_route_decompress_response::Migration),
+            Box::new(m20261014_000022_route_cookie_rewrite::Migration),
+            Box::new(m20261014_000023_route_access_log_enabled::Migration),
+            Box::new(m20261014_000024_route_fallback_backends::Migration),
+            Box::new(m20261014_000025_header_rate_limit_identifier::Migration),
+            Box::new(m20261014_000026_gateway_metrics_retention::Migration),
+            Box::new(m20261014_000027_rate_limit_override_global::Migration),
+            Box::new(m20261014_000028_route_request_cost::Migration),
+            Box::new(m20261014_000029_config_snapshot_all_rows::Migration),
         ]
     }
 }
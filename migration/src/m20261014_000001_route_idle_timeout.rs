@@ -0,0 +1,36 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Max gap between upstream reads; NULL falls back to timeout_ms
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .add_column_if_not_exists(integer_null(ApiRoutes::IdleTimeoutMs))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .drop_column(ApiRoutes::IdleTimeoutMs)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiRoutes {
+    Table,
+    IdleTimeoutMs,
+}
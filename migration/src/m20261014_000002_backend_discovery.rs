@@ -0,0 +1,46 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .add_column_if_not_exists(
+                        string_len(BackendServices::DiscoveryType, 20)
+                            .not_null()
+                            .default("static")
+                            .check(
+                                Expr::col(BackendServices::DiscoveryType)
+                                    .is_in(["static", "dns_srv"]),
+                            ),
+                    )
+                    .add_column_if_not_exists(string_len_null(BackendServices::SrvName, 255))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .drop_column(BackendServices::DiscoveryType)
+                    .drop_column(BackendServices::SrvName)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BackendServices {
+    Table,
+    DiscoveryType,
+    SrvName,
+}
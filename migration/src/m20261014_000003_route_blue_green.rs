@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .add_column_if_not_exists(uuid_null(ApiRoutes::GreenBackendServiceId))
+                    .add_column_if_not_exists(
+                        string_len(ApiRoutes::ActiveColor, 10)
+                            .not_null()
+                            .default("blue")
+                            .check(Expr::col(ApiRoutes::ActiveColor).is_in(["blue", "green"])),
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_api_routes_green_backend_service")
+                            .from_tbl(ApiRoutes::Table)
+                            .from_col(ApiRoutes::GreenBackendServiceId)
+                            .to_tbl(BackendServices::Table)
+                            .to_col(BackendServices::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .drop_foreign_key(Alias::new("fk_api_routes_green_backend_service"))
+                    .drop_column(ApiRoutes::GreenBackendServiceId)
+                    .drop_column(ApiRoutes::ActiveColor)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiRoutes {
+    Table,
+    GreenBackendServiceId,
+    ActiveColor,
+}
+
+#[derive(DeriveIden)]
+enum BackendServices {
+    Table,
+    Id,
+}
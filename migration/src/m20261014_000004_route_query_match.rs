@@ -0,0 +1,81 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .add_column_if_not_exists(json_binary(ApiRoutes::QueryMatch).default("{}"))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Routes may now share a path and method as long as their query conditions differ
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_api_routes_path_method")
+                    .table(ApiRoutes::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .unique()
+                    .name("idx_api_routes_path_method_query")
+                    .table(ApiRoutes::Table)
+                    .col(ApiRoutes::PathPattern)
+                    .col(ApiRoutes::Method)
+                    .col(ApiRoutes::QueryMatch)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_api_routes_path_method_query")
+                    .table(ApiRoutes::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .unique()
+                    .name("idx_api_routes_path_method")
+                    .table(ApiRoutes::Table)
+                    .col(ApiRoutes::PathPattern)
+                    .col(ApiRoutes::Method)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .drop_column(ApiRoutes::QueryMatch)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiRoutes {
+    Table,
+    PathPattern,
+    Method,
+    QueryMatch,
+}
@@ -0,0 +1,38 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .add_column_if_not_exists(integer_null(ApiRoutes::QueueDepth))
+                    .add_column_if_not_exists(integer_null(ApiRoutes::QueueTimeoutMs))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ApiRoutes::Table)
+                    .drop_column(ApiRoutes::QueueDepth)
+                    .drop_column(ApiRoutes::QueueTimeoutMs)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiRoutes {
+    Table,
+    QueueDepth,
+    QueueTimeoutMs,
+}
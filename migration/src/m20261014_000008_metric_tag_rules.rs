@@ -0,0 +1,73 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MetricTagRules::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(MetricTagRules::Id)
+                            .primary_key()
+                            .extra("DEFAULT gen_random_uuid()"),
+                    )
+                    .col(string_len(MetricTagRules::Tag, 100).not_null())
+                    .col(string_len(MetricTagRules::MatchType, 20).not_null().check(
+                        Expr::col(MetricTagRules::MatchType).is_in(["path_prefix", "header"]),
+                    ))
+                    .col(string_len_null(MetricTagRules::HeaderName, 100))
+                    .col(string_len(MetricTagRules::Pattern, 500).not_null())
+                    .col(boolean(MetricTagRules::IsActive).default(true))
+                    .col(integer(MetricTagRules::Priority).default(0))
+                    .col(
+                        timestamp_with_time_zone(MetricTagRules::CreatedAt)
+                            .extra("DEFAULT CURRENT_TIMESTAMP"),
+                    )
+                    .col(
+                        timestamp_with_time_zone(MetricTagRules::UpdatedAt)
+                            .extra("DEFAULT CURRENT_TIMESTAMP"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE TRIGGER update_metric_tag_rules_updated_at BEFORE UPDATE ON metric_tag_rules FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(MetricTagRules::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum MetricTagRules {
+    Table,
+    Id,
+    Tag,
+    MatchType,
+    HeaderName,
+    Pattern,
+    IsActive,
+    Priority,
+    CreatedAt,
+    UpdatedAt,
+}
@@ -0,0 +1,38 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .add_column_if_not_exists(integer_null(BackendServices::MaxResponseHeaderBytes))
+                    .add_column_if_not_exists(integer_null(BackendServices::MaxResponseHeaderCount))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .drop_column(BackendServices::MaxResponseHeaderBytes)
+                    .drop_column(BackendServices::MaxResponseHeaderCount)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BackendServices {
+    Table,
+    MaxResponseHeaderBytes,
+    MaxResponseHeaderCount,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .add_column_if_not_exists(string_len_null(
+                        BackendServices::TlsClientCertPath,
+                        500,
+                    ))
+                    .add_column_if_not_exists(string_len_null(
+                        BackendServices::TlsClientKeyPath,
+                        500,
+                    ))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .drop_column(BackendServices::TlsClientCertPath)
+                    .drop_column(BackendServices::TlsClientKeyPath)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BackendServices {
+    Table,
+    TlsClientCertPath,
+    TlsClientKeyPath,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .add_column_if_not_exists(string_len_null(
+                        BackendServices::HealthCheckMethod,
+                        10,
+                    ))
+                    .add_column_if_not_exists(json_binary_null(BackendServices::HealthCheckHeaders))
+                    .add_column_if_not_exists(text_null(BackendServices::HealthCheckBody))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(BackendServices::Table)
+                    .drop_column(BackendServices::HealthCheckMethod)
+                    .drop_column(BackendServices::HealthCheckHeaders)
+                    .drop_column(BackendServices::HealthCheckBody)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum BackendServices {
+    Table,
+    HealthCheckMethod,
+    HealthCheckHeaders,
+    HealthCheckBody,
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Header a limit with the `header` identifier type counts requests by
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RateLimits::Table)
+                    .add_column_if_not_exists(string_len_null(RateLimits::IdentifierHeader, 100))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE rate_limits DROP CONSTRAINT IF EXISTS rate_limits_identifier_type_check;
+                 ALTER TABLE rate_limits ADD CONSTRAINT rate_limits_identifier_type_check
+                     CHECK (identifier_type IN ('ip', 'api_key', 'user_id', 'global', 'header'));",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "DELETE FROM rate_limits WHERE identifier_type = 'header';
+                 ALTER TABLE rate_limits DROP CONSTRAINT IF EXISTS rate_limits_identifier_type_check;
+                 ALTER TABLE rate_limits ADD CONSTRAINT rate_limits_identifier_type_check
+                     CHECK (identifier_type IN ('ip', 'api_key', 'user_id', 'global'));",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RateLimits::Table)
+                    .drop_column(RateLimits::IdentifierHeader)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RateLimits {
+    Table,
+    IdentifierHeader,
+}
@@ -0,0 +1,122 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Hourly aggregates of purged gateway_metrics rows. No foreign keys, so
+        // the history of deleted routes and backends is kept.
+        manager
+            .create_table(
+                Table::create()
+                    .table(GatewayMetricsHourly::Table)
+                    .if_not_exists()
+                    .col(
+                        uuid(GatewayMetricsHourly::Id)
+                            .primary_key()
+                            .extra("DEFAULT gen_random_uuid()"),
+                    )
+                    .col(timestamp_with_time_zone(GatewayMetricsHourly::Hour))
+                    .col(uuid_null(GatewayMetricsHourly::RouteId))
+                    .col(uuid_null(GatewayMetricsHourly::BackendServiceId))
+                    .col(integer_null(GatewayMetricsHourly::StatusCode))
+                    .col(big_integer(GatewayMetricsHourly::RequestCount))
+                    .col(big_integer(GatewayMetricsHourly::ErrorCount))
+                    .col(double(GatewayMetricsHourly::TotalResponseTimeMs))
+                    .col(double_null(GatewayMetricsHourly::MaxResponseTimeMs))
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+
+        // Rolling up the same hour twice merges into one row
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_gateway_metrics_hourly_bucket
+                ON gateway_metrics_hourly(hour, route_id, backend_service_id, status_code)
+                NULLS NOT DISTINCT;",
+        )
+        .await?;
+
+        db.execute_unprepared(
+            r#"
+            CREATE OR REPLACE FUNCTION cleanup_old_gateway_metrics(
+                cutoff TIMESTAMPTZ DEFAULT NOW() - INTERVAL '30 days',
+                keep_hourly BOOLEAN DEFAULT TRUE
+            ) RETURNS BIGINT AS $$
+            DECLARE
+                deleted BIGINT;
+            BEGIN
+                -- Several gateways run this; one at a time so no row is rolled up twice
+                PERFORM pg_advisory_xact_lock(hashtext('cleanup_old_gateway_metrics'));
+
+                IF keep_hourly THEN
+                    INSERT INTO gateway_metrics_hourly AS h (
+                        hour, route_id, backend_service_id, status_code,
+                        request_count, error_count, total_response_time_ms, max_response_time_ms
+                    )
+                    SELECT date_trunc('hour', timestamp), route_id, backend_service_id, status_code,
+                           count(*), count(error_message),
+                           coalesce(sum(response_time_ms), 0), max(response_time_ms)
+                    FROM gateway_metrics
+                    WHERE timestamp < cutoff
+                    GROUP BY 1, 2, 3, 4
+                    ON CONFLICT (hour, route_id, backend_service_id, status_code) DO UPDATE SET
+                        request_count = h.request_count + EXCLUDED.request_count,
+                        error_count = h.error_count + EXCLUDED.error_count,
+                        total_response_time_ms =
+                            h.total_response_time_ms + EXCLUDED.total_response_time_ms,
+                        max_response_time_ms =
+                            GREATEST(h.max_response_time_ms, EXCLUDED.max_response_time_ms);
+                END IF;
+
+                DELETE FROM gateway_metrics WHERE timestamp < cutoff;
+                GET DIAGNOSTICS deleted = ROW_COUNT;
+                RETURN deleted;
+            END;
+            $$ LANGUAGE plpgsql;
+            "#,
+        )
+        .await?;
+
+        db.execute_unprepared(
+            "COMMENT ON FUNCTION cleanup_old_gateway_metrics(TIMESTAMPTZ, BOOLEAN) IS 'Deletes gateway metrics older than cutoff (default 30 days), first rolling them up into gateway_metrics_hourly unless keep_hourly is false. Returns the rows deleted. Run manually: SELECT cleanup_old_gateway_metrics();';"
+        ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "DROP FUNCTION IF EXISTS cleanup_old_gateway_metrics(TIMESTAMPTZ, BOOLEAN);",
+            )
+            .await?;
+
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(GatewayMetricsHourly::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum GatewayMetricsHourly {
+    Table,
+    Id,
+    Hour,
+    RouteId,
+    BackendServiceId,
+    StatusCode,
+    RequestCount,
+    ErrorCount,
+    TotalResponseTimeMs,
+    MaxResponseTimeMs,
+}
@@ -0,0 +1,36 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Route limits that replace the global limits instead of stacking with them
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RateLimits::Table)
+                    .add_column_if_not_exists(boolean(RateLimits::OverrideGlobal).default(false))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(RateLimits::Table)
+                    .drop_column(RateLimits::OverrideGlobal)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum RateLimits {
+    Table,
+    OverrideGlobal,
+}
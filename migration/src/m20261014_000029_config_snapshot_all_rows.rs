@@ -0,0 +1,62 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// `create_config_snapshot`, keeping only the rows matching `filter` of the toggleable tables
+fn snapshot_function(filter: &str) -> String {
+    format!(
+        r#"
+        CREATE OR REPLACE FUNCTION create_config_snapshot(
+            p_version_name VARCHAR(100),
+            p_description TEXT DEFAULT NULL,
+            p_created_by VARCHAR(100) DEFAULT NULL
+        )
+        RETURNS UUID AS $$
+        DECLARE
+            v_snapshot_id UUID;
+            v_snapshot JSONB;
+        BEGIN
+            -- Build complete config snapshot
+            SELECT jsonb_build_object(
+                'backend_services', (SELECT jsonb_agg(row_to_json(t.*)) FROM backend_services t {filter}),
+                'api_routes', (SELECT jsonb_agg(row_to_json(t.*)) FROM api_routes t {filter}),
+                'whitelist_rules', (SELECT jsonb_agg(row_to_json(t.*)) FROM whitelist_rules t {filter}),
+                'rate_limits', (SELECT jsonb_agg(row_to_json(t.*)) FROM rate_limits t {filter}),
+                'load_balancer_config', (SELECT jsonb_agg(row_to_json(t.*)) FROM load_balancer_config t)
+            ) INTO v_snapshot;
+
+            -- Insert snapshot
+            INSERT INTO config_versions (version_name, description, config_snapshot, created_by)
+            VALUES (p_version_name, p_description, v_snapshot, p_created_by)
+            RETURNING id INTO v_snapshot_id;
+
+            RETURN v_snapshot_id;
+        END;
+        $$ LANGUAGE plpgsql;
+        "#
+    )
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Snapshots are restore points, so disabled rows belong in them too;
+        // deleting a route also deletes its disabled rules
+        manager
+            .get_connection()
+            .execute_unprepared(&snapshot_function(""))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(&snapshot_function("WHERE is_active = true"))
+            .await?;
+
+        Ok(())
+    }
+}